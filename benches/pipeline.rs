@@ -0,0 +1,102 @@
+//! Throughput and latency of the forecast pipeline stages, on synthetic sessions.
+//!
+//! Run with `cargo bench`; compare against a saved baseline with
+//! `cargo bench -- --save-baseline main` and `--baseline main`.
+
+use std::hint::black_box;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use cpo_charging_forecast::config::{ModelConfig, SynthConfig};
+use cpo_charging_forecast::data::{load_sessions, read_sessions};
+use cpo_charging_forecast::forecast::{self, future_timestamps};
+use cpo_charging_forecast::{preprocess, synth};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+
+fn synth_config(days: u32) -> SynthConfig {
+    SynthConfig {
+        days,
+        ..SynthConfig::default()
+    }
+}
+
+/// A site export of `days` synthetic days, written to a temporary file.
+fn export_file(days: u32) -> PathBuf {
+    let config = synth_config(days);
+    let path = std::env::temp_dir().join(format!("cpo-bench-{}-{}.csv", std::process::id(), days));
+    let text_path = path.to_str().expect("temp path is UTF-8");
+    synth::write_sessions_csv(&synth::sessions(&config).unwrap(), &config, text_path).unwrap();
+    path
+}
+
+fn ingest(c: &mut Criterion) {
+    let path = export_file(365);
+    let csv = std::fs::read(&path).unwrap();
+    let mut group = c.benchmark_group("ingest");
+    group.throughput(Throughput::Bytes(csv.len() as u64));
+    group.bench_function("read_sessions", |b| {
+        b.iter(|| read_sessions(black_box(&csv[..])).unwrap())
+    });
+    group.bench_function("load_sessions_mmap", |b| {
+        b.iter(|| load_sessions(black_box(&path)).unwrap())
+    });
+    group.finish();
+    std::fs::remove_file(&path).ok();
+
+    let sessions = read_sessions(&csv[..]).unwrap();
+    let mut group = c.benchmark_group("resample");
+    group.throughput(Throughput::Elements(sessions.len() as u64));
+    group.bench_function("hourly_load", |b| {
+        b.iter(|| preprocess::hourly_load(black_box(&sessions)))
+    });
+    group.bench_function("hourly_occupancy", |b| {
+        b.iter(|| preprocess::hourly_occupancy(black_box(&sessions)))
+    });
+    group.finish();
+}
+
+fn fit(c: &mut Criterion) {
+    let model = ModelConfig::default();
+    let mut group = c.benchmark_group("fit");
+    group
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(30));
+    for days in [28, 91, 182, 365] {
+        let history = synth::demand(&synth_config(days)).unwrap();
+        group.throughput(Throughput::Elements(history.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(days), &history, |b, history| {
+            b.iter(|| forecast::fit(black_box(history), &model).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn predict(c: &mut Criterion) {
+    let history = synth::demand(&synth_config(182)).unwrap();
+    let last = *history.timestamps.last().unwrap();
+    let mut group = c.benchmark_group("predict");
+    for (name, model) in [
+        ("prophet", ModelConfig::default()),
+        (
+            "prophet_lags",
+            ModelConfig {
+                lags: vec![24, 168],
+                ..ModelConfig::default()
+            },
+        ),
+    ] {
+        let fitted = forecast::fit(&history, &model).unwrap();
+        for horizon in [24, 168] {
+            let timestamps = future_timestamps(last, horizon, None);
+            group.bench_with_input(
+                BenchmarkId::new(name, horizon),
+                &timestamps,
+                |b, timestamps| b.iter(|| fitted.predict(black_box(timestamps.clone())).unwrap()),
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, ingest, fit, predict);
+criterion_main!(benches);
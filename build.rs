@@ -0,0 +1,25 @@
+//! Build metadata: the git commit the crate is built from, for run manifests
+//! (see `manifest::GIT_COMMIT`). Builds outside a checkout go without.
+
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    // Rebuilt when the checked-out commit changes, not on every file change
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    if let Ok(head) = std::fs::read_to_string(".git/HEAD")
+        && let Some(reference) = head.strip_prefix("ref: ")
+        && Path::new(".git").join(reference.trim()).exists()
+    {
+        println!("cargo:rerun-if-changed=.git/{}", reference.trim());
+    }
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    if let Some(commit) = commit {
+        println!("cargo:rustc-env=GIT_COMMIT={}", commit.trim());
+    }
+}
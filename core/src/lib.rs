@@ -0,0 +1,174 @@
+//! Prediction from a published model, for charge-point controllers and other
+//! gateways that can't carry the fitting stack.
+//!
+//! A [`PublishedModel`] is what predicting needs of a fitted model: its
+//! piecewise-linear trend, its Fourier seasonalities, and how wide the
+//! intervals it issued were at each lead. `cpo-charging-forecast` exports one
+//! from a Prophet or `fourier` fit (see its `artifact` module); evaluating it
+//! here is arithmetic only, so the crate is `no_std` and needs just `alloc`.
+//!
+//! Signed models come with a detached Ed25519 signature of the JSON's exact
+//! bytes; check it with [`verify`] before parsing.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+/// A fitted model, reduced to what predicting needs.
+///
+/// Timestamps are UNIX seconds on the same wall clock as the history it was
+/// fitted on.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PublishedModel {
+    pub site: String,
+    /// The forecaster it was exported from, e.g. `prophet`.
+    pub method: String,
+    pub model_version: String,
+    /// The last fitted hour; leads are counted from it.
+    pub fitted_to: i64,
+    /// Seconds between points.
+    pub step: i64,
+    /// The trend's knots `(timestamp, value)`, oldest first: linear between
+    /// them, and on the slope of the first or last segment beyond them.
+    pub trend: Vec<(i64, f64)>,
+    pub seasonalities: Vec<PublishedSeasonality>,
+    /// How far below and above the point the interval reaches at each lead,
+    /// the first one step after `fitted_to`; the last holds for longer leads.
+    pub intervals: Vec<(f64, f64)>,
+}
+
+/// A seasonality as a Fourier series in UNIX seconds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PublishedSeasonality {
+    /// E.g. `daily`.
+    pub name: String,
+    /// Seconds.
+    pub period: f64,
+    /// Whether it scales the trend (`trend * (1 + value)`) rather than adding to it.
+    pub multiplicative: bool,
+    /// The `sin` and `cos` coefficients of each harmonic in turn, the fundamental first.
+    pub coefficients: Vec<f64>,
+}
+
+/// A prediction for one timestamp, in kW.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Point {
+    pub point: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl PublishedSeasonality {
+    pub fn value(&self, ts: i64) -> f64 {
+        let phase = 2.0 * core::f64::consts::PI * ts as f64 / self.period;
+        self.coefficients
+            .chunks(2)
+            .enumerate()
+            .map(|(i, c)| {
+                let angle = (i + 1) as f64 * phase;
+                c[0] * libm::sin(angle) + c.get(1).copied().unwrap_or(0.0) * libm::cos(angle)
+            })
+            .sum()
+    }
+}
+
+impl PublishedModel {
+    /// The trend at `ts`.
+    pub fn trend_at(&self, ts: i64) -> f64 {
+        match self.trend.as_slice() {
+            [] => 0.0,
+            [(_, value)] => *value,
+            knots => {
+                // The segment holding `ts`, or the first or last one when it's beyond them
+                let i = knots
+                    .partition_point(|(t, _)| *t <= ts)
+                    .clamp(1, knots.len() - 1);
+                let ((t0, v0), (t1, v1)) = (knots[i - 1], knots[i]);
+                v0 + (v1 - v0) * (ts - t0) as f64 / (t1 - t0) as f64
+            }
+        }
+    }
+
+    /// The prediction at `ts`, clamped at zero as the fitted model's is.
+    pub fn predict(&self, ts: i64) -> Point {
+        let (mut additive, mut multiplicative) = (0.0, 0.0);
+        for seasonality in &self.seasonalities {
+            match seasonality.multiplicative {
+                true => multiplicative += seasonality.value(ts),
+                false => additive += seasonality.value(ts),
+            }
+        }
+        let point = self.trend_at(ts) * (1.0 + multiplicative) + additive;
+        let lead = ((ts - self.fitted_to) / self.step.max(1) - 1).max(0) as usize;
+        let (below, above) = self
+            .intervals
+            .get(lead)
+            .or(self.intervals.last())
+            .copied()
+            .unwrap_or_default();
+        Point {
+            point: point.max(0.0),
+            lower: (point - below).max(0.0),
+            upper: (point + above).max(0.0),
+        }
+    }
+
+    /// [`PublishedModel::predict`] at each of `timestamps`.
+    pub fn predict_all(&self, timestamps: &[i64]) -> Vec<Point> {
+        timestamps.iter().map(|ts| self.predict(*ts)).collect()
+    }
+}
+
+/// Why a published model wasn't trusted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The public key isn't 64 hex digits of an Ed25519 key.
+    Key,
+    /// The signature isn't 128 hex digits.
+    Signature,
+    /// The signature isn't the key's over these bytes: the model was altered,
+    /// corrupted, or signed by someone else.
+    Mismatch,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            VerifyError::Key => "the public key isn't a hex Ed25519 key",
+            VerifyError::Signature => "the signature isn't a hex Ed25519 signature",
+            VerifyError::Mismatch => "the signature doesn't match the model",
+        })
+    }
+}
+
+/// Check that `signature` (hex, as in the model's `.sig` file) is `public_key`'s
+/// (hex) over `bytes`, the published JSON as received.
+pub fn verify(bytes: &[u8], signature: &str, public_key: &str) -> Result<(), VerifyError> {
+    let key = decode_hex::<32>(public_key).ok_or(VerifyError::Key)?;
+    let key = VerifyingKey::from_bytes(&key).map_err(|_| VerifyError::Key)?;
+    let signature =
+        Signature::from_bytes(&decode_hex::<64>(signature).ok_or(VerifyError::Signature)?);
+    key.verify_strict(bytes, &signature)
+        .map_err(|_| VerifyError::Mismatch)
+}
+
+/// `N` bytes from their hex digits, ignoring surrounding whitespace.
+fn decode_hex<const N: usize>(text: &str) -> Option<[u8; N]> {
+    let text = text.trim().as_bytes();
+    if text.len() != 2 * N {
+        return None;
+    }
+    let mut bytes = [0; N];
+    for (byte, digits) in bytes.iter_mut().zip(text.chunks(2)) {
+        let digit = |d: u8| (d as char).to_digit(16);
+        *byte = (digit(digits[0])? * 16 + digit(digits[1])?) as u8;
+    }
+    Some(bytes)
+}
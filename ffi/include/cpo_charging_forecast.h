@@ -0,0 +1,49 @@
+/*
+ * C API of the CPO charging-demand forecaster.
+ *
+ * Timestamps are UNIX seconds of local wall-clock time, one per hour; values
+ * are kW. Link against libcpo_charging_forecast_ffi (cdylib or staticlib).
+ *
+ * Functions that fail return NULL or -1; cf_last_error() then describes why.
+ * Models are not thread-safe: use each handle from one thread at a time.
+ */
+#ifndef CPO_CHARGING_FORECAST_H
+#define CPO_CHARGING_FORECAST_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Opaque fitted model. */
+typedef struct CfModel CfModel;
+
+/* Message of the last failed call on this thread; valid until the next failure. */
+const char *cf_last_error(void);
+
+/* Fit on len hourly points. model_toml is the [model] config section as TOML
+ * (e.g. "transform = \"log1p\"\nlags = [24, 168]"), or NULL for defaults.
+ * Returns NULL on failure; free the model with cf_free. */
+CfModel *cf_fit(const int64_t *timestamps, const double *values, size_t len, const char *model_toml);
+
+/* Predict at len timestamps into point, lower and upper (len elements each).
+ * Returns 0 on success, -1 on failure. */
+int32_t cf_predict(const CfModel *model, const int64_t *timestamps, size_t len,
+                   double *point, double *lower, double *upper);
+
+/* Write up to capacity hourly timestamps after last_timestamp into out.
+ * With an IANA timezone (or NULL) the grid follows DST changes, so fewer
+ * than horizon_hours may be written. Returns the count, or -1 on failure. */
+int64_t cf_future_timestamps(int64_t last_timestamp, uint32_t horizon_hours, const char *timezone,
+                             int64_t *out, size_t capacity);
+
+/* Free a model from cf_fit; NULL is ignored. */
+void cf_free(CfModel *model);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CPO_CHARGING_FORECAST_H */
@@ -0,0 +1,185 @@
+//! C API for embedding the forecaster, e.g. in the site EMS controller.
+//!
+//! See `include/cpo_charging_forecast.h`. Models are opaque handles from
+//! [`cf_fit`], freed with [`cf_free`]. Functions report failure through their
+//! return value and leave a message for [`cf_last_error`]; panics are caught
+//! and reported the same way rather than unwinding into C.
+
+use std::cell::RefCell;
+use std::error::Error;
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::ptr;
+use std::str::FromStr;
+
+use charging::config::ModelConfig;
+use charging::data::Series;
+use charging::forecast::{self, Forecaster};
+use chrono_tz::Tz;
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with_borrow_mut(|e| *e = message);
+}
+
+/// Run `f`, recording any error or panic for `cf_last_error`.
+fn guard<T>(f: impl FnOnce() -> Result<T, Box<dyn Error>>) -> Option<T> {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => Some(value),
+        Ok(Err(e)) => {
+            set_last_error(e.to_string());
+            None
+        }
+        Err(_) => {
+            set_last_error("panic inside the forecaster".to_string());
+            None
+        }
+    }
+}
+
+/// A borrowed C array as a slice; null is only allowed when `len` is zero.
+unsafe fn slice<'a, T>(data: *const T, len: usize) -> Result<&'a [T], Box<dyn Error>> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    if data.is_null() {
+        return Err("null array".into());
+    }
+    Ok(unsafe { std::slice::from_raw_parts(data, len) })
+}
+
+unsafe fn optional_str<'a>(text: *const c_char) -> Result<Option<&'a str>, Box<dyn Error>> {
+    if text.is_null() {
+        return Ok(None);
+    }
+    Ok(Some(unsafe { CStr::from_ptr(text) }.to_str()?))
+}
+
+/// Opaque fitted model.
+pub struct CfModel {
+    inner: Box<dyn Forecaster>,
+}
+
+/// Message of the last failed call on this thread; empty if none.
+///
+/// The pointer stays valid until the next failing call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn cf_last_error() -> *const c_char {
+    LAST_ERROR.with_borrow(|e| e.as_ptr())
+}
+
+/// Fit a model on `len` hourly points; `model_toml` is the `[model]` section as TOML, or null.
+///
+/// Returns null on failure.
+///
+/// # Safety
+///
+/// `timestamps` and `values` must point to `len` readable elements, and
+/// `model_toml` must be null or a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cf_fit(
+    timestamps: *const i64,
+    values: *const f64,
+    len: usize,
+    model_toml: *const c_char,
+) -> *mut CfModel {
+    guard(|| {
+        let history = Series::new(
+            unsafe { slice(timestamps, len) }?.to_vec(),
+            unsafe { slice(values, len) }?.to_vec(),
+        );
+        let model: ModelConfig = match unsafe { optional_str(model_toml) }? {
+            Some(text) => toml::from_str(text)?,
+            None => ModelConfig::default(),
+        };
+        let inner = forecast::fit(&history, &model)?;
+        Ok(Box::into_raw(Box::new(CfModel { inner })))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Predict at `len` timestamps into `point`, `lower` and `upper` (each `len` long).
+///
+/// Returns 0 on success, -1 on failure.
+///
+/// # Safety
+///
+/// `model` must come from `cf_fit` and not be freed; `timestamps` must point
+/// to `len` readable and the outputs to `len` writable elements.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cf_predict(
+    model: *const CfModel,
+    timestamps: *const i64,
+    len: usize,
+    point: *mut f64,
+    lower: *mut f64,
+    upper: *mut f64,
+) -> i32 {
+    guard(|| {
+        let model = unsafe { model.as_ref() }.ok_or("null model")?;
+        if len > 0 && (point.is_null() || lower.is_null() || upper.is_null()) {
+            return Err("null output array".into());
+        }
+        let forecast = model
+            .inner
+            .predict(unsafe { slice(timestamps, len) }?.to_vec())?;
+        for (out, values) in [
+            (point, &forecast.point),
+            (lower, &forecast.lower),
+            (upper, &forecast.upper),
+        ] {
+            unsafe { ptr::copy_nonoverlapping(values.as_ptr(), out, len.min(values.len())) };
+        }
+        Ok(0)
+    })
+    .unwrap_or(-1)
+}
+
+/// Write up to `capacity` hourly timestamps after `last_timestamp` into `out`.
+///
+/// `timezone` (IANA name, or null) makes the grid DST-aware, in which case
+/// fewer than `horizon_hours` may be written. Returns the number written, or
+/// -1 on failure.
+///
+/// # Safety
+///
+/// `out` must point to `capacity` writable elements and `timezone` must be
+/// null or a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cf_future_timestamps(
+    last_timestamp: i64,
+    horizon_hours: u32,
+    timezone: *const c_char,
+    out: *mut i64,
+    capacity: usize,
+) -> i64 {
+    guard(|| {
+        let timezone = unsafe { optional_str(timezone) }?
+            .map(Tz::from_str)
+            .transpose()?;
+        let timestamps = forecast::future_timestamps(last_timestamp, horizon_hours, timezone);
+        let n = timestamps.len().min(capacity);
+        if n > 0 && out.is_null() {
+            return Err("null output array".into());
+        }
+        unsafe { ptr::copy_nonoverlapping(timestamps.as_ptr(), out, n) };
+        Ok(n as i64)
+    })
+    .unwrap_or(-1)
+}
+
+/// Free a model from `cf_fit`; null is ignored.
+///
+/// # Safety
+///
+/// `model` must come from `cf_fit` and not have been freed already.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cf_free(model: *mut CfModel) {
+    if !model.is_null() {
+        drop(unsafe { Box::from_raw(model) });
+    }
+}
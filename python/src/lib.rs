@@ -0,0 +1,160 @@
+//! Python bindings for the production forecaster.
+//!
+//! Series cross the boundary as plain lists of timestamps (UNIX seconds of
+//! local time) and values; configs and results as dicts with the same keys as
+//! the TOML config and JSON outputs.
+
+use std::error::Error;
+use std::str::FromStr;
+
+use charging::config::ModelConfig;
+use charging::data::{Series, load_sessions};
+use charging::forecast::{self, Forecast};
+use charging::preprocess::{self, Pipeline};
+use charging::{backtest, metrics};
+use chrono_tz::Tz;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pythonize::{depythonize, pythonize};
+use serde::de::DeserializeOwned;
+
+fn py_err(e: Box<dyn Error>) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+/// Deserialize a config dict or list, with serde's message on unknown keys or bad values.
+fn from_py<T: DeserializeOwned>(obj: &Bound<'_, PyAny>) -> PyResult<T> {
+    depythonize(obj).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn model_config(model: Option<&Bound<'_, PyAny>>) -> PyResult<ModelConfig> {
+    model
+        .map(from_py)
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
+/// A fitted model, as chosen by the `method`, `transform` and `fallbacks` settings.
+#[pyclass(unsendable)]
+struct Forecaster {
+    inner: Box<dyn forecast::Forecaster>,
+}
+
+#[pymethods]
+impl Forecaster {
+    /// Short method name, e.g. `"prophet"`.
+    #[getter]
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    /// Forecast dict (`timestamps`, `point`, `lower`, `upper`) at the given timestamps.
+    fn predict<'py>(&self, py: Python<'py>, timestamps: Vec<i64>) -> PyResult<Bound<'py, PyAny>> {
+        let forecast = self.inner.predict(timestamps).map_err(py_err)?;
+        Ok(pythonize(py, &forecast)?)
+    }
+}
+
+/// Fit the forecaster on an hourly series, with an optional `[model]` config dict.
+#[pyfunction]
+#[pyo3(signature = (timestamps, values, model=None))]
+fn fit(
+    timestamps: Vec<i64>,
+    values: Vec<f64>,
+    model: Option<&Bound<'_, PyAny>>,
+) -> PyResult<Forecaster> {
+    let inner =
+        forecast::fit(&Series::new(timestamps, values), &model_config(model)?).map_err(py_err)?;
+    Ok(Forecaster { inner })
+}
+
+/// The next `horizon_hours` hourly timestamps, DST-aware if an IANA `timezone` is given.
+#[pyfunction]
+#[pyo3(signature = (last_timestamp, horizon_hours, timezone=None))]
+fn future_timestamps(
+    last_timestamp: i64,
+    horizon_hours: u32,
+    timezone: Option<&str>,
+) -> PyResult<Vec<i64>> {
+    let timezone = timezone
+        .map(Tz::from_str)
+        .transpose()
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(forecast::future_timestamps(
+        last_timestamp,
+        horizon_hours,
+        timezone,
+    ))
+}
+
+/// Hourly load (kW) of a session export CSV, as `(timestamps, values)`.
+#[pyfunction]
+fn hourly_load(path: &str) -> PyResult<(Vec<i64>, Vec<f64>)> {
+    let series = preprocess::hourly_load(&load_sessions(path).map_err(py_err)?);
+    Ok((series.timestamps.into(), series.values.into()))
+}
+
+/// Apply preprocessing steps (the `data.preprocess` list) to a series.
+#[pyfunction]
+#[pyo3(name = "preprocess")]
+fn apply_preprocess(
+    timestamps: Vec<i64>,
+    values: Vec<f64>,
+    steps: &Bound<'_, PyAny>,
+) -> PyResult<(Vec<i64>, Vec<f64>)> {
+    let pipeline: Pipeline = from_py(steps)?;
+    let series = pipeline
+        .apply(Series::new(timestamps, values))
+        .map_err(py_err)?;
+    Ok((series.timestamps.into(), series.values.into()))
+}
+
+/// Accuracy metrics of a forecast dict against the actual values.
+#[pyfunction]
+#[pyo3(signature = (actual, forecast, interval_width=0.8))]
+fn evaluate<'py>(
+    py: Python<'py>,
+    actual: Vec<f64>,
+    forecast: &Bound<'py, PyAny>,
+    interval_width: f64,
+) -> PyResult<Bound<'py, PyAny>> {
+    let forecast: Forecast = from_py(forecast)?;
+    Ok(pythonize(
+        py,
+        &metrics::evaluate(&actual, &forecast, interval_width),
+    )?)
+}
+
+/// Rolling-origin backtest; returns the same document as `backtest.json`.
+#[pyfunction]
+#[pyo3(name = "backtest", signature = (timestamps, values, model=None, holdout_hours=168, folds=3, step_hours=168))]
+fn run_backtest<'py>(
+    py: Python<'py>,
+    timestamps: Vec<i64>,
+    values: Vec<f64>,
+    model: Option<&Bound<'py, PyAny>>,
+    holdout_hours: u32,
+    folds: usize,
+    step_hours: usize,
+) -> PyResult<Bound<'py, PyAny>> {
+    let model = model_config(model)?;
+    let history = Series::new(timestamps, values);
+    let folds =
+        backtest::run(&history, &model, holdout_hours, folds, step_hours).map_err(py_err)?;
+    Ok(pythonize(
+        py,
+        &backtest::summarize(&folds, &model, holdout_hours),
+    )?)
+}
+
+#[pymodule]
+fn cpo_charging_forecast(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Forecaster>()?;
+    m.add_function(wrap_pyfunction!(fit, m)?)?;
+    m.add_function(wrap_pyfunction!(future_timestamps, m)?)?;
+    m.add_function(wrap_pyfunction!(hourly_load, m)?)?;
+    m.add_function(wrap_pyfunction!(apply_preprocess, m)?)?;
+    m.add_function(wrap_pyfunction!(evaluate, m)?)?;
+    m.add_function(wrap_pyfunction!(run_backtest, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,129 @@
+//! Grid-constraint checks: flag forecast hours that may exceed the site limit.
+
+use chrono::DateTime;
+use serde_json::json;
+
+use crate::data::format_timestamp;
+use crate::forecast::Forecast;
+use crate::preprocess::HOUR;
+
+/// A run of consecutive forecast hours whose upper bound exceeds the limit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// First violating hour.
+    pub start: i64,
+    /// End of the last violating hour (exclusive).
+    pub end: i64,
+    /// `end - start` in hours; a fraction on a sub-hourly grid.
+    pub hours: f64,
+    pub peak_upper_kw: f64,
+    pub peak_upper_at: i64,
+    /// Whether the point forecast itself exceeds the limit somewhere in the run.
+    pub likely: bool,
+}
+
+/// Name of the upper bound's quantile, e.g. "P90" for an 80% interval.
+pub fn upper_quantile_label(interval_width: f64) -> String {
+    format!("P{}", ((1.0 + interval_width) / 2.0 * 100.0).round())
+}
+
+/// Find runs of hours (or forecast steps) whose upper bound is above `limit_kw`.
+pub fn check(forecast: &Forecast, limit_kw: f64) -> Vec<Violation> {
+    let mut violations: Vec<Violation> = Vec::new();
+    let step = forecast.step();
+
+    for i in 0..forecast.len() {
+        let (ts, upper) = (forecast.timestamps[i], forecast.upper[i]);
+        if upper <= limit_kw {
+            continue;
+        }
+        let likely = forecast.point[i] > limit_kw;
+        match violations.last_mut() {
+            Some(v) if v.end == ts => {
+                v.end = ts + step;
+                v.hours += forecast.step_hours();
+                v.likely |= likely;
+                if upper > v.peak_upper_kw {
+                    v.peak_upper_kw = upper;
+                    v.peak_upper_at = ts;
+                }
+            }
+            _ => violations.push(Violation {
+                start: ts,
+                end: ts + step,
+                hours: forecast.step_hours(),
+                peak_upper_kw: upper,
+                peak_upper_at: ts,
+                likely,
+            }),
+        }
+    }
+
+    violations
+}
+
+/// One human-readable line per affected day, e.g.
+/// "P95 exceeds 250 kW limit for 6 hours on Tue 2024-10-08 (peak 312.4 kW at 18:00)".
+pub fn summarize(violations: &[Violation], limit_kw: f64, interval_width: f64) -> Vec<String> {
+    let label = upper_quantile_label(interval_width);
+    let day_of = |ts: i64| ts.div_euclid(24 * HOUR);
+
+    let mut lines = Vec::new();
+    let mut i = 0;
+    while i < violations.len() {
+        let day = day_of(violations[i].start);
+        let same_day: Vec<&Violation> = violations[i..]
+            .iter()
+            .take_while(|v| day_of(v.start) == day)
+            .collect();
+        i += same_day.len();
+
+        let hours: f64 = same_day.iter().map(|v| v.hours).sum();
+        let peak = same_day
+            .iter()
+            .max_by(|a, b| a.peak_upper_kw.total_cmp(&b.peak_upper_kw))
+            .expect("at least one violation per day");
+        let likely = if same_day.iter().any(|v| v.likely) {
+            " — point forecast exceeds it too"
+        } else {
+            ""
+        };
+        let date = DateTime::from_timestamp(peak.peak_upper_at, 0)
+            .map(|dt| dt.format("%a %Y-%m-%d").to_string())
+            .unwrap_or_default();
+        let time = DateTime::from_timestamp(peak.peak_upper_at, 0)
+            .map(|dt| dt.format("%H:%M").to_string())
+            .unwrap_or_default();
+
+        lines.push(format!(
+            "{} exceeds {} kW limit for {} hour{} on {} (peak {:.1} kW at {}){}",
+            label,
+            limit_kw,
+            hours,
+            if hours == 1.0 { "" } else { "s" },
+            date,
+            peak.peak_upper_kw,
+            time,
+            likely,
+        ));
+    }
+
+    lines
+}
+
+/// Violations and summary as a JSON document.
+pub fn to_json(violations: &[Violation], limit_kw: f64, interval_width: f64) -> serde_json::Value {
+    json!({
+        "site_limit_kw": limit_kw,
+        "bound": upper_quantile_label(interval_width),
+        "summary": summarize(violations, limit_kw, interval_width),
+        "violations": violations.iter().map(|v| json!({
+            "start": format_timestamp(v.start),
+            "end": format_timestamp(v.end),
+            "hours": v.hours,
+            "peak_upper_kw": v.peak_upper_kw,
+            "peak_upper_at": format_timestamp(v.peak_upper_at),
+            "likely": v.likely,
+        })).collect::<Vec<_>>(),
+    })
+}
@@ -0,0 +1,72 @@
+//! Flag recent hours where actual demand strayed far from the model's fit.
+
+use crate::data::Series;
+use crate::forecast::Forecast;
+
+/// Robust z-score above which an hour counts as anomalous.
+pub const DEFAULT_THRESHOLD: f64 = 3.5;
+
+/// An hour whose actual demand doesn't fit the model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anomaly {
+    pub timestamp: i64,
+    pub actual_kw: f64,
+    pub expected_kw: f64,
+    /// Robust z-score of the residual.
+    pub score: f64,
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+/// Median and median absolute deviation of `residuals`, unless they have no spread.
+pub(crate) fn spread(residuals: &[f64]) -> Option<(f64, f64)> {
+    if residuals.is_empty() {
+        return None;
+    }
+    let center = median(&mut residuals.to_vec());
+    let mad = median(
+        &mut residuals
+            .iter()
+            .map(|r| (r - center).abs())
+            .collect::<Vec<_>>(),
+    );
+    (mad > f64::EPSILON).then_some((center, mad))
+}
+
+/// Compare `actual` against in-sample `fitted` values at the same timestamps.
+///
+/// Residuals are scored with the median absolute deviation, so a handful of
+/// outliers can't inflate the spread and hide themselves.
+pub fn detect(actual: &Series, fitted: &Forecast, threshold: f64) -> Vec<Anomaly> {
+    let residuals: Vec<f64> = actual
+        .values
+        .iter()
+        .zip(&fitted.point)
+        .map(|(a, f)| a - f)
+        .collect();
+    let Some((center, mad)) = spread(&residuals) else {
+        return Vec::new();
+    };
+
+    residuals
+        .iter()
+        .enumerate()
+        .filter_map(|(i, r)| {
+            let score = 0.6745 * (r - center) / mad;
+            (score.abs() > threshold).then(|| Anomaly {
+                timestamp: actual.timestamps[i],
+                actual_kw: actual.values[i],
+                expected_kw: fitted.point[i],
+                score,
+            })
+        })
+        .collect()
+}
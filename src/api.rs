@@ -0,0 +1,209 @@
+//! Request and response shapes of the HTTP server, as published in its
+//! OpenAPI document (`GET /openapi.json`) and used by [`crate::client`].
+
+use serde::{Deserialize, Serialize};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{IntoParams, Modify, OpenApi, ToSchema};
+
+use crate::jobs::FitJob;
+use crate::server;
+
+/// `GET /` answer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Status {
+    /// Always `ok`.
+    pub status: String,
+}
+
+/// Body of every 4xx answer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ApiError {
+    pub error: String,
+}
+
+/// Query parameters of `GET /forecast`; see [`server::ForecastQuery`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ForecastParams {
+    /// Site name; may be left out when the caller sees only one site.
+    pub site: Option<String>,
+    /// First hour included: a date, a local `YYYY-MM-DD HH:MM` time, or an
+    /// instant (Unix seconds or RFC 3339).
+    pub from: Option<String>,
+    /// First hour no longer included, like `from`.
+    pub to: Option<String>,
+    /// `hour` (default), `day` or `week`.
+    pub resolution: Option<String>,
+    /// Points skipped, after aggregation.
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+/// One `GET /forecast` point, in kW.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ForecastPoint {
+    /// Start of the hour, day or week (`YYYY-MM-DD HH:MM`, local time).
+    pub timestamp: String,
+    pub yhat: f64,
+    pub yhat_lower: f64,
+    pub yhat_upper: f64,
+    /// Hours covered; days and weeks only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hours: Option<usize>,
+    /// Energy over the bucket; days and weeks only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub energy_kwh: Option<f64>,
+    /// Highest hourly `yhat`; days and weeks only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peak_kw: Option<f64>,
+}
+
+/// One page of `GET /forecast` points.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ForecastPage {
+    pub resolution: String,
+    /// Points in the whole slice.
+    pub total: usize,
+    pub offset: usize,
+    /// Offset of the next page, while points remain.
+    pub next_offset: Option<usize>,
+    pub points: Vec<ForecastPoint>,
+}
+
+/// A `/metrics` option of the Grafana datasource.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct MetricOption {
+    pub label: String,
+    pub value: String,
+}
+
+/// Dashboard time range, in RFC 3339.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Range {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Target {
+    /// A metric from `/search`.
+    pub target: String,
+}
+
+/// `POST /query` body.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct QueryRequest {
+    pub range: Range,
+    pub targets: Vec<Target>,
+}
+
+/// One metric of a `POST /query` answer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TimeSeries {
+    pub target: String,
+    /// `[value, epoch milliseconds]` pairs.
+    #[schema(value_type = Vec<Vec<f64>>)]
+    pub datapoints: Vec<(f64, i64)>,
+}
+
+/// `POST /openadr` answer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct OpenAdrReceipt {
+    /// Ids of the events kept.
+    pub stored: Vec<String>,
+    /// Ids of the deleted events forgotten.
+    pub removed: Vec<String>,
+    /// The refit that applies them.
+    pub fit: FitJob,
+}
+
+/// One telemetry reading for `POST /nowcast`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Reading {
+    /// A local `YYYY-MM-DD HH:MM` time, or an instant (Unix seconds or RFC 3339).
+    pub timestamp: String,
+    pub power_kw: f64,
+}
+
+/// `POST /nowcast` body.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct NowcastRequest {
+    /// The site's readings since the forecast was issued; earlier ones are ignored.
+    pub readings: Vec<Reading>,
+}
+
+/// One hour of a `POST /nowcast` answer, in kW.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct NowcastHour {
+    /// Start of the hour (`YYYY-MM-DD HH:MM`, local time).
+    pub timestamp: String,
+    pub yhat: f64,
+    pub yhat_lower: f64,
+    pub yhat_upper: f64,
+    /// The stored forecast's `yhat`.
+    pub forecast: f64,
+}
+
+/// `POST /nowcast` answer; see [`crate::nowcast::live`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Nowcast {
+    /// First hour of the stored forecast.
+    pub issued: String,
+    /// Hours since the issue with readings.
+    pub hours_observed: usize,
+    /// How far the readings ran above the forecast, the latest hours weighing most.
+    pub adjustment_kw: f64,
+    /// The hour of the latest reading.
+    pub current: NowcastHour,
+    /// The hour after; absent at the end of the forecast.
+    pub next: Option<NowcastHour>,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "cpo-charging-forecast", description = "Charging demand forecasts per site."),
+    paths(
+        server::health,
+        server::openapi_json,
+        server::list_sites,
+        server::forecast,
+        server::search,
+        server::metrics,
+        server::query_route,
+        server::annotations,
+        server::submit_fit,
+        server::fit_status,
+        server::openadr_events,
+        server::nowcast,
+    ),
+    modifiers(&Security),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+struct ApiDoc;
+
+/// The `api_key` and `bearer` schemes of `[server.tenants]`.
+struct Security;
+
+impl Modify for Security {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
+        );
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("An API key or an HS256 JWT"))
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// The server's OpenAPI document.
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    ApiDoc::openapi()
+}
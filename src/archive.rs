@@ -0,0 +1,261 @@
+//! Forecast archive: every issued forecast, with the model that produced it.
+//!
+//! One JSON file per run under `<dir>/<site>/`, named by run id, so the
+//! archive can be browsed, synced and diffed with ordinary tools as well as
+//! through the `archive` command.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::PathBuf;
+
+use crate::config::{Config, ModelConfig};
+use crate::data::{Series, Session, format_timestamp};
+use crate::forecast::{Forecast, fit, forecast_series, model_version};
+use crate::manifest::Manifest;
+use crate::preprocess::{self, HOUR};
+
+/// Run ids are the issue time in this format, so they sort chronologically.
+pub const RUN_ID_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Run manifests are stored as `<run id>.manifest.json`.
+const MANIFEST_SUFFIX: &str = ".manifest.json";
+
+/// The start of the last structural break alerted is kept in this file.
+const ALERTED_BREAK_FILE: &str = "alerted_break";
+
+/// One archived forecast run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedForecast {
+    pub run_id: String,
+    pub site: String,
+    /// When the forecast was issued (UNIX seconds).
+    pub issued_at: i64,
+    /// Last actual the model was fitted on.
+    pub origin: i64,
+    pub model_version: String,
+    pub model: ModelConfig,
+    pub forecast: Forecast,
+}
+
+impl ArchivedForecast {
+    pub fn new(
+        site: &str,
+        issued_at: DateTime<Utc>,
+        origin: i64,
+        model: &ModelConfig,
+        forecast: Forecast,
+    ) -> Self {
+        Self {
+            run_id: issued_at.format(RUN_ID_FORMAT).to_string(),
+            site: site.to_string(),
+            issued_at: issued_at.timestamp(),
+            origin,
+            model_version: model_version(model),
+            model: model.clone(),
+            forecast,
+        }
+    }
+}
+
+/// The archive directory of one site.
+#[derive(Debug, Clone)]
+pub struct Archive {
+    dir: PathBuf,
+}
+
+impl Archive {
+    pub fn open(root: &str, site: &str) -> Self {
+        Self {
+            dir: PathBuf::from(root).join(site),
+        }
+    }
+
+    fn path(&self, run_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", run_id))
+    }
+
+    /// Store a run, replacing any earlier run with the same id.
+    pub fn save(&self, run: &ArchivedForecast) -> Result<PathBuf, Box<dyn Error>> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.path(&run.run_id);
+        std::fs::write(&path, serde_json::to_string_pretty(run)?)?;
+        Ok(path)
+    }
+
+    /// Store the manifest of a run, next to it.
+    pub fn save_manifest(&self, manifest: &Manifest) -> Result<PathBuf, Box<dyn Error>> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self
+            .dir
+            .join(format!("{}{}", manifest.run_id, MANIFEST_SUFFIX));
+        std::fs::write(&path, serde_json::to_string_pretty(manifest)? + "\n")?;
+        Ok(path)
+    }
+
+    /// The manifest of a run, for runs archived with one.
+    pub fn manifest(&self, run_id: &str) -> Result<Manifest, Box<dyn Error>> {
+        let path = self.dir.join(format!("{}{}", run_id, MANIFEST_SUFFIX));
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("No manifest of run {} ({}): {}", run_id, path.display(), e))?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Record that the structural break starting at `start` was alerted.
+    pub fn save_alerted_break(&self, start: i64) -> Result<(), Box<dyn Error>> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.dir.join(ALERTED_BREAK_FILE), format!("{}\n", start))?;
+        Ok(())
+    }
+
+    /// Start of the last structural break alerted, if any was.
+    pub fn alerted_break(&self) -> Result<Option<i64>, Box<dyn Error>> {
+        let path = self.dir.join(ALERTED_BREAK_FILE);
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                Ok(Some(text.trim().parse().map_err(|e| {
+                    format!("Could not read {}: {}", path.display(), e)
+                })?))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Could not read {}: {}", path.display(), e).into()),
+        }
+    }
+
+    pub fn get(&self, run_id: &str) -> Result<ArchivedForecast, Box<dyn Error>> {
+        let path = self.path(run_id);
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("No archived run {} ({}): {}", run_id, path.display(), e))?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// All runs, oldest first.
+    pub fn list(&self) -> Result<Vec<ArchivedForecast>, Box<dyn Error>> {
+        let mut runs = Vec::new();
+        if !self.dir.exists() {
+            return Ok(runs);
+        }
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let manifest = path.to_string_lossy().ends_with(MANIFEST_SUFFIX);
+            if path.extension().is_some_and(|ext| ext == "json") && !manifest {
+                runs.push(serde_json::from_str::<ArchivedForecast>(
+                    &std::fs::read_to_string(&path)?,
+                )?);
+            }
+        }
+        runs.sort_by(|a, b| a.run_id.cmp(&b.run_id));
+        Ok(runs)
+    }
+}
+
+/// Hour-by-hour difference between two forecasts on their common timestamps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HourDiff {
+    pub timestamp: i64,
+    pub a: f64,
+    pub b: f64,
+}
+
+impl HourDiff {
+    pub fn delta(&self) -> f64 {
+        self.b - self.a
+    }
+}
+
+pub fn diff(a: &Forecast, b: &Forecast) -> Vec<HourDiff> {
+    a.timestamps
+        .iter()
+        .zip(&a.point)
+        .filter_map(|(ts, va)| {
+            let j = b.timestamps.binary_search(ts).ok()?;
+            Some(HourDiff {
+                timestamp: *ts,
+                a: *va,
+                b: b.point[j],
+            })
+        })
+        .collect()
+}
+
+/// Re-run an archived forecast with its own model settings on the history up to its origin.
+///
+/// Returns the regenerated forecast; diffing it against the archived one
+/// shows whether the run is reproducible from the data on hand.
+pub fn replay(run: &ArchivedForecast, history: &Series) -> Result<Forecast, Box<dyn Error>> {
+    let end = history.timestamps.partition_point(|ts| *ts <= run.origin);
+    if end == 0 {
+        return Err(format!("No history at or before the origin of run {}", run.run_id).into());
+    }
+    let train = history.slice(0..end);
+    fit(&train, &run.model)?.predict(run.forecast.timestamps.clone())
+}
+
+/// Regenerate the daily forecasts that would have been issued at `hour`:00 on each day in
+/// `[from, to]` (UNIX seconds of midnight).
+///
+/// Each run only sees sessions that had ended by its issue time, so charging
+/// still in progress then doesn't leak into the training data. Days without
+/// enough history to fit are skipped. Returns the number of runs stored.
+pub fn backfill(
+    archive: &Archive,
+    config: &Config,
+    sessions: &[Session],
+    (from, to): (i64, i64),
+    hour: u32,
+) -> Result<usize, Box<dyn Error>> {
+    let step = preprocess::step_seconds(config.data.step_minutes)?;
+    let mut stored = 0;
+    let mut day = from;
+    while day <= to {
+        let issued_at = day + hour as i64 * HOUR;
+        day += 24 * HOUR;
+
+        let known: Vec<Session> = sessions
+            .iter()
+            .filter(|s| s.stop <= issued_at)
+            .cloned()
+            .collect();
+        let history = config
+            .data
+            .preprocess
+            .apply_at(preprocess::load(&known, step), step)?;
+        // Only steps that had fully elapsed at issue time
+        let end = history
+            .timestamps
+            .partition_point(|ts| *ts + step <= issued_at);
+        if end == 0 {
+            continue;
+        }
+        let train = history.slice(0..end);
+        let forecast = match forecast_series(
+            &train,
+            &config.model,
+            config.forecast.horizon_hours,
+            config.timezone,
+        ) {
+            Ok(forecast) => forecast,
+            Err(e) => {
+                eprintln!("Skipping {}: {}", format_timestamp(issued_at), e);
+                continue;
+            }
+        };
+
+        let issued = DateTime::from_timestamp(issued_at, 0).ok_or("Issue time out of range")?;
+        let run = ArchivedForecast::new(
+            &config.site,
+            issued,
+            train.timestamps[end - 1],
+            &config.model,
+            forecast,
+        );
+        archive.save(&run)?;
+        eprintln!(
+            "Backfilled run {} (origin {})",
+            run.run_id,
+            format_timestamp(run.origin)
+        );
+        stored += 1;
+    }
+    Ok(stored)
+}
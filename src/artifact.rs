@@ -0,0 +1,231 @@
+//! Published models: a fitted model exported for `cpo-charging-forecast-core`,
+//! which predicts from it on gateways without the fitting stack.
+//!
+//! Only the trend and the seasonalities travel, read off the model's own
+//! decomposition, with the width of the intervals it issued at each lead.
+//! That covers Prophet and the `fourier` method as long as every component is
+//! a seasonality: calendar, lag and price regressors need data a gateway
+//! doesn't have, and the fallbacks have no components. The export is checked
+//! against the model's forecast before it's handed out.
+//!
+//! With `output.model_key` the JSON is signed with Ed25519, the hex signature
+//! going next to it in `<model>.sig`, so a gateway holding the public key can
+//! tell a spoofed or corrupted model from the published one
+//! (`cpo_charging_forecast_core::verify`). Keys are hex files made by
+//! [`generate_key`].
+
+use std::error::Error;
+use std::io::Write;
+
+use ed25519_dalek::{Signer, SigningKey};
+
+pub use cpo_charging_forecast_core::{
+    Point, PublishedModel, PublishedSeasonality, VerifyError, verify,
+};
+
+use crate::config::{ModelConfig, Transform};
+use crate::export;
+use crate::forecast::{Forecaster, model_version};
+use crate::fourier;
+use crate::metrics::least_squares;
+use crate::remote;
+
+/// Slope changes of the trend smaller than this, over the whole history and
+/// relative to the trend's largest value, aren't kept as knots.
+const KINK: f64 = 1e-9;
+
+/// Largest difference between the published and the model's own forecast,
+/// relative to the forecast's largest value.
+const TOLERANCE: f64 = 1e-6;
+
+/// Export `model`, fitted with `config` for `site`, checked on and with the
+/// intervals it issues at `horizon`.
+pub fn export(
+    model: &dyn Forecaster,
+    config: &ModelConfig,
+    site: &str,
+    horizon: Vec<i64>,
+) -> Result<PublishedModel, Box<dyn Error>> {
+    if config.transform != Transform::None {
+        return Err("models fitted with model.transform can't be published".into());
+    }
+    let fitted = model.decompose()?;
+    let timestamps = &fitted.fitted.timestamps;
+    let (Some(&fitted_to), Some(&first_step)) = (timestamps.last(), horizon.first()) else {
+        return Err("nothing fitted or forecast to publish".into());
+    };
+
+    let known = fourier::seasonalities(config);
+    let mut seasonalities = Vec::new();
+    for (name, values) in &fitted.components {
+        if name == "additive_terms" || name == "multiplicative_terms" {
+            continue;
+        }
+        let Some(&(_, period, order)) = known.iter().find(|s| s.0 == name) else {
+            return Err(format!(
+                "{} can't be published: gateways evaluate only the trend and the seasonalities",
+                name
+            )
+            .into());
+        };
+        let rows: Vec<Vec<f64>> = timestamps
+            .iter()
+            .map(|ts| harmonics(*ts, period, order))
+            .collect();
+        let coefficients = least_squares(&rows, values)
+            .ok_or_else(|| format!("the {} seasonality can't be solved for", name))?;
+        seasonalities.push(PublishedSeasonality {
+            name: name.clone(),
+            period,
+            multiplicative: fitted.multiplicative.contains(name),
+            coefficients,
+        });
+    }
+
+    let issued = model.attribute(horizon)?.fitted;
+    let published = PublishedModel {
+        site: site.to_string(),
+        method: model.name().to_string(),
+        model_version: model_version(config),
+        fitted_to,
+        step: first_step - fitted_to,
+        trend: knots(timestamps, &fitted.trend),
+        seasonalities,
+        intervals: (0..issued.len())
+            .map(|i| {
+                (
+                    issued.point[i] - issued.lower[i],
+                    issued.upper[i] - issued.point[i],
+                )
+            })
+            .collect(),
+    };
+
+    let largest = issued
+        .point
+        .iter()
+        .fold(1.0, |max: f64, p| max.max(p.abs()));
+    for (ts, point) in issued.timestamps.iter().zip(&issued.point) {
+        let gap = (published.predict(*ts).point - point.max(0.0)).abs();
+        if gap > TOLERANCE * largest {
+            return Err(format!(
+                "the published model strays {:.6} kW from the fitted one at {}",
+                gap,
+                crate::data::format_timestamp(*ts)
+            )
+            .into());
+        }
+    }
+    Ok(published)
+}
+
+/// `sin` and `cos` of each harmonic of `period` seconds at `ts`, as the core evaluates them.
+fn harmonics(ts: i64, period: f64, order: u32) -> Vec<f64> {
+    let phase = 2.0 * std::f64::consts::PI * ts as f64 / period;
+    (1..=order)
+        .flat_map(|n| [(n as f64 * phase).sin(), (n as f64 * phase).cos()])
+        .collect()
+}
+
+/// The points of a piecewise-linear `trend` where its slope changes, and its ends.
+fn knots(timestamps: &[i64], trend: &[f64]) -> Vec<(i64, f64)> {
+    let n = timestamps.len().min(trend.len());
+    if n < 3 {
+        return timestamps
+            .iter()
+            .copied()
+            .zip(trend.iter().copied())
+            .collect();
+    }
+    let span = (timestamps[n - 1] - timestamps[0]) as f64;
+    let tolerance = KINK * trend.iter().fold(1.0, |max: f64, v| max.max(v.abs()));
+    let slope = |i: usize| (trend[i + 1] - trend[i]) / (timestamps[i + 1] - timestamps[i]) as f64;
+    let mut knots = vec![(timestamps[0], trend[0])];
+    knots.extend(
+        (1..n - 1)
+            .filter(|i| ((slope(*i) - slope(i - 1)) * span).abs() > tolerance)
+            .map(|i| (timestamps[i], trend[i])),
+    );
+    knots.push((timestamps[n - 1], trend[n - 1]));
+    knots
+}
+
+/// `model` as the JSON that's published and signed.
+pub fn to_json(model: &PublishedModel) -> Result<String, Box<dyn Error>> {
+    Ok(serde_json::to_string_pretty(model)?)
+}
+
+/// Where the signature of the model at `path` goes.
+pub fn signature_path(path: &str) -> String {
+    format!("{}.sig", path)
+}
+
+/// The hex signature of `bytes` by `key`.
+pub fn sign(bytes: &[u8], key: &SigningKey) -> String {
+    to_hex(&key.sign(bytes).to_bytes())
+}
+
+/// Write `model` as JSON, signed by `key` when given, to a file or an
+/// object-store URL (see [`crate::remote`]).
+pub fn save(
+    model: &PublishedModel,
+    path: &str,
+    key: Option<&SigningKey>,
+) -> Result<(), Box<dyn Error>> {
+    let json = to_json(model)?;
+    remote::with_output(path, |path| export::write_text(path, &json))?;
+    match key {
+        Some(key) => remote::with_output(&signature_path(path), |path| {
+            export::write_text(path, &sign(json.as_bytes(), key))
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Read a model [`save`] wrote, first checking its signature against the hex
+/// `public_key` when given.
+pub fn load(path: &str, public_key: Option<&str>) -> Result<PublishedModel, Box<dyn Error>> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    if let Some(public_key) = public_key {
+        let signature_path = signature_path(path);
+        let signature = std::fs::read_to_string(&signature_path)
+            .map_err(|e| format!("{}: {}", signature_path, e))?;
+        verify(&bytes, &signature, public_key).map_err(|e| format!("{}: {}", path, e))?;
+    }
+    serde_json::from_slice(&bytes).map_err(|e| format!("{}: {}", path, e).into())
+}
+
+/// Make a signing key, saved in hex to `path` (readable by its owner only) and
+/// its public key to `<path>.pub`, and return the public key.
+pub fn generate_key(path: &str) -> Result<String, Box<dyn Error>> {
+    let key = SigningKey::generate(&mut rand::rngs::OsRng);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path).map_err(|e| format!("{}: {}", path, e))?;
+    writeln!(file, "{}", to_hex(&key.to_bytes()))?;
+    let public_key = to_hex(key.verifying_key().as_bytes());
+    crate::export::write_text(format!("{}.pub", path), &format!("{}\n", public_key))?;
+    Ok(public_key)
+}
+
+/// The signing key [`generate_key`] saved at `path`.
+pub fn signing_key(path: &str) -> Result<SigningKey, Box<dyn Error>> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let text = text.trim();
+    let bytes = (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            text.get(i..i + 2)
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+        })
+        .collect::<Option<Vec<u8>>>()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .ok_or_else(|| format!("{}: not a hex Ed25519 signing key", path))?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,121 @@
+//! Revisions of already ingested history between runs.
+//!
+//! Meter corrections are often backfilled into past hours. Each run keeps
+//! the hourly history it was fitted on (in the `[store]`); the next run
+//! compares its history with it and reports the hours whose load changed,
+//! which otherwise shift accuracy figures without anyone noticing.
+
+use serde::Serialize;
+use std::error::Error;
+
+use crate::data::{Series, format_timestamp};
+use crate::preprocess::HOUR;
+
+/// Revised hours listed in [`RevisionReport::summary`].
+const LISTED: usize = 5;
+
+/// One hour whose load changed since the previous run.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Revision {
+    /// Local wall-clock seconds.
+    pub timestamp: i64,
+    /// kW as previously ingested.
+    pub before: f64,
+    /// kW now, or `None` when the hour is gone from the history.
+    pub after: Option<f64>,
+}
+
+impl Revision {
+    /// Change in kW; a dropped hour counts as its whole load.
+    pub fn change(&self) -> f64 {
+        self.after.unwrap_or(0.0) - self.before
+    }
+}
+
+/// What changed in the history up to the previous run's last hour.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RevisionReport {
+    /// Points of the previous history compared: hours, or steps of `step`.
+    pub checked_hours: usize,
+    /// Seconds each compared point lasts: an hour, or the `data.step_minutes` grid.
+    pub step: i64,
+    /// Oldest first.
+    pub revisions: Vec<Revision>,
+}
+
+impl RevisionReport {
+    /// Compare `current` with the `previous` history hour by hour; changes
+    /// up to `tolerance_kw` are rounding, not revisions.
+    pub fn new(previous: &Series, current: &Series, tolerance_kw: f64) -> Self {
+        let revisions = previous
+            .timestamps
+            .iter()
+            .zip(&previous.values)
+            .filter_map(|(&timestamp, &before)| {
+                let after = current
+                    .timestamps
+                    .binary_search(&timestamp)
+                    .ok()
+                    .map(|i| current.values[i]);
+                let revision = Revision {
+                    timestamp,
+                    before,
+                    after,
+                };
+                (after.is_none() || revision.change().abs() > tolerance_kw).then_some(revision)
+            })
+            .collect();
+        Self {
+            checked_hours: previous.len(),
+            step: previous.step(),
+            revisions,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.revisions.is_empty()
+    }
+
+    /// Net change in energy over the revised hours.
+    pub fn net_change_kwh(&self) -> f64 {
+        self.revisions.iter().map(Revision::change).sum::<f64>() * self.step as f64 / HOUR as f64
+    }
+
+    /// A headline and the largest revisions, for stderr.
+    pub fn summary(&self) -> Vec<String> {
+        let (Some(first), Some(last)) = (self.revisions.first(), self.revisions.last()) else {
+            return Vec::new();
+        };
+        let points = match self.step {
+            0 | HOUR => "hours".to_string(),
+            step => format!("{}-minute steps", step / 60),
+        };
+        let mut lines = vec![format!(
+            "{} of {} past {} revised since the last run, {} to {}, net {:+.1} kWh",
+            self.revisions.len(),
+            self.checked_hours,
+            points,
+            format_timestamp(first.timestamp),
+            format_timestamp(last.timestamp),
+            self.net_change_kwh()
+        )];
+        let mut largest: Vec<&Revision> = self.revisions.iter().collect();
+        largest.sort_by(|a, b| b.change().abs().total_cmp(&a.change().abs()));
+        for revision in largest.into_iter().take(LISTED) {
+            let after = revision
+                .after
+                .map_or("gone".to_string(), |kw| format!("{:.1} kW", kw));
+            lines.push(format!(
+                "  {}: {:.1} kW → {}",
+                format_timestamp(revision.timestamp),
+                revision.before,
+                after
+            ));
+        }
+        lines
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        crate::export::write_text(path, &serde_json::to_string_pretty(self)?)
+    }
+}
@@ -0,0 +1,247 @@
+//! Rolling-origin backtests: refit on truncated history and score the holdout.
+
+use chrono::Utc;
+use csv::Writer;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::Path;
+
+use crate::config::ModelConfig;
+use crate::data::{Series, format_timestamp};
+use crate::export;
+use crate::forecast::{Forecast, Regressors, fit_with, model_version};
+use crate::metrics::{Metrics, evaluate};
+use crate::preprocess::HOUR;
+
+/// One backtest fold: the forecast issued at `origin` and what actually happened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fold {
+    /// Last timestamp the model was allowed to see.
+    pub origin: i64,
+    pub actual: Vec<f64>,
+    pub forecast: Forecast,
+}
+
+/// Metrics of one fold, as stored in the backtest file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FoldSummary {
+    pub origin: String,
+    pub metrics: Metrics,
+}
+
+/// The persisted result of a backtest run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BacktestReport {
+    pub run_at: String,
+    pub model_version: String,
+    pub holdout_hours: u32,
+    pub folds: Vec<FoldSummary>,
+    pub overall: Metrics,
+}
+
+impl BacktestReport {
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        export::write_text(path, &serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+/// Fit and score `folds` forecasts of `holdout_hours` each.
+///
+/// The most recent fold holds out the last `holdout_hours` of history; each
+/// earlier fold moves the origin back by `step_hours`.
+pub fn run(
+    history: &Series,
+    model: &ModelConfig,
+    holdout_hours: u32,
+    folds: usize,
+    step_hours: usize,
+) -> Result<Vec<Fold>, Box<dyn Error>> {
+    let holdout = holdout_hours as usize;
+    let mut results = Vec::new();
+    // Each fold's fit only sees the prices published by its origin
+    let regressors = Regressors::load(history, model)?;
+
+    for k in (0..folds).rev() {
+        let Some(end) = history.len().checked_sub(holdout + k * step_hours) else {
+            continue;
+        };
+        if end == 0 {
+            continue;
+        }
+        let train = history.slice(0..end);
+        // The actual holdout hours, so folds line up with the history across DST changes
+        let forecast = fit_with(&train, model, &regressors)?
+            .predict(history.timestamps[end..end + holdout].to_vec())?;
+        let actual = history.values[end..end + holdout].to_vec();
+        eprintln!(
+            "Backtest fold from {}: {}",
+            format_timestamp(train.timestamps[end - 1]),
+            summary_line(&evaluate(&actual, &forecast, model.interval_width))
+        );
+        results.push(Fold {
+            origin: train.timestamps[end - 1],
+            actual,
+            forecast,
+        });
+    }
+
+    if results.is_empty() {
+        return Err("Not enough history for a single backtest fold".into());
+    }
+    Ok(results)
+}
+
+/// Score every fold and the folds pooled together.
+pub fn summarize(folds: &[Fold], model: &ModelConfig, holdout_hours: u32) -> BacktestReport {
+    let mut pooled = Forecast::default();
+    let mut actual = Vec::new();
+    for fold in folds {
+        pooled.timestamps.extend(&fold.forecast.timestamps);
+        pooled.point.extend(&fold.forecast.point);
+        pooled.lower.extend(&fold.forecast.lower);
+        pooled.upper.extend(&fold.forecast.upper);
+        actual.extend(&fold.actual);
+    }
+
+    BacktestReport {
+        run_at: Utc::now().format("%Y-%m-%d %H:%M UTC").to_string(),
+        model_version: model_version(model),
+        holdout_hours,
+        folds: folds
+            .iter()
+            .map(|f| FoldSummary {
+                origin: format_timestamp(f.origin),
+                metrics: evaluate(&f.actual, &f.forecast, model.interval_width),
+            })
+            .collect(),
+        overall: evaluate(&actual, &pooled, model.interval_width),
+    }
+}
+
+/// Score the folds grouped by `key(lead, timestamp)`, where `lead` is 1 for the first forecast hour.
+fn grouped(
+    folds: &[Fold],
+    width: f64,
+    groups: usize,
+    key: impl Fn(usize, i64) -> usize,
+) -> Vec<Metrics> {
+    let mut actual = vec![Vec::new(); groups];
+    let mut forecast = vec![Forecast::default(); groups];
+    for fold in folds {
+        let f = &fold.forecast;
+        for i in 0..f.len().min(fold.actual.len()) {
+            let g = key(i + 1, f.timestamps[i]);
+            if g >= groups {
+                continue;
+            }
+            actual[g].push(fold.actual[i]);
+            forecast[g].timestamps.push(f.timestamps[i]);
+            forecast[g].point.push(f.point[i]);
+            forecast[g].lower.push(f.lower[i]);
+            forecast[g].upper.push(f.upper[i]);
+        }
+    }
+    actual
+        .iter()
+        .zip(&forecast)
+        .map(|(a, f)| evaluate(a, f, width))
+        .collect()
+}
+
+/// Metrics per lead time, index 0 = 1 h ahead.
+pub fn by_lead(folds: &[Fold], model: &ModelConfig) -> Vec<Metrics> {
+    let longest = folds.iter().map(|f| f.forecast.len()).max().unwrap_or(0);
+    grouped(folds, model.interval_width, longest, |lead, _| lead - 1)
+}
+
+/// Metrics per hour of day, index 0 = 00:00.
+pub fn by_hour_of_day(folds: &[Fold], model: &ModelConfig) -> Vec<Metrics> {
+    grouped(folds, model.interval_width, 24, |_, ts| {
+        (ts.rem_euclid(24 * HOUR) / HOUR) as usize
+    })
+}
+
+/// Metrics for lead-time bands ending at each of `checkpoints` (hours), e.g. `[1, 6, 24]`
+/// gives 1 h, 2–6 h and 7–24 h.
+pub fn by_lead_band(
+    folds: &[Fold],
+    model: &ModelConfig,
+    checkpoints: &[usize],
+) -> Vec<(String, Metrics)> {
+    let band = |lead: usize| {
+        checkpoints
+            .iter()
+            .position(|&c| lead <= c)
+            .unwrap_or(checkpoints.len())
+    };
+    let metrics = grouped(folds, model.interval_width, checkpoints.len(), |lead, _| {
+        band(lead)
+    });
+    checkpoints
+        .iter()
+        .enumerate()
+        .zip(metrics)
+        .filter(|(_, m)| m.n > 0)
+        .map(|((i, &end), m)| {
+            let start = if i == 0 { 1 } else { checkpoints[i - 1] + 1 };
+            let label = if start == end {
+                format!("{} h", end)
+            } else {
+                format!("{}–{} h", start, end)
+            };
+            (label, m)
+        })
+        .collect()
+}
+
+/// Write grouped metrics as CSV with `key` as the first column.
+pub fn write_breakdown_csv(
+    key: &str,
+    rows: &[(String, Metrics)],
+    path: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_writer(export::create(path)?);
+    wtr.write_record([
+        key, "n", "mae", "rmse", "smape", "bias", "coverage", "winkler",
+    ])?;
+    for (k, m) in rows {
+        wtr.write_record([
+            k.clone(),
+            m.n.to_string(),
+            format!("{:.3}", m.mae),
+            format!("{:.3}", m.rmse),
+            format!("{:.3}", m.smape),
+            format!("{:.3}", m.bias),
+            format!("{:.3}", m.coverage),
+            format!("{:.3}", m.winkler),
+        ])?;
+    }
+    wtr.flush()?;
+    eprintln!("Breakdown written to {}", path);
+    Ok(())
+}
+
+/// Pinball losses of the lower bound, median and upper bound.
+pub fn pinball_line(m: &Metrics) -> String {
+    format!(
+        "pinball loss lower {:.1} kW, median {:.1} kW, upper {:.1} kW",
+        m.pinball_lower, m.pinball_median, m.pinball_upper
+    )
+}
+
+/// One-line rendering of a metrics set.
+pub fn summary_line(m: &Metrics) -> String {
+    format!(
+        "MAE {:.1} kW, RMSE {:.1} kW, sMAPE {:.1}%, bias {:+.1} kW, coverage {:.0}%, Winkler {:.0} kW",
+        m.mae,
+        m.rmse,
+        m.smape,
+        m.bias,
+        m.coverage * 100.0,
+        m.winkler
+    )
+}
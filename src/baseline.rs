@@ -0,0 +1,162 @@
+//! Baseline forecasters that work on very little history.
+//!
+//! These are the fallbacks for sites Prophet can't be fitted on yet, so a
+//! freshly commissioned site still gets a usable (if rough) forecast.
+
+use chrono::{DateTime, Datelike, Timelike};
+use std::error::Error;
+
+use crate::data::Series;
+use crate::forecast::{Forecast, Forecaster};
+use crate::metrics::quantile;
+use crate::preprocess::HOUR;
+
+const DAY: i64 = 24 * HOUR;
+const WEEK: i64 = 7 * DAY;
+
+/// Interval offsets from the quantiles of a set of residuals.
+pub(crate) fn residual_offsets(mut residuals: Vec<f64>, interval_width: f64) -> (f64, f64) {
+    residuals.sort_by(f64::total_cmp);
+    (
+        quantile(&residuals, (1.0 - interval_width) / 2.0),
+        quantile(&residuals, (1.0 + interval_width) / 2.0),
+    )
+}
+
+fn with_interval(timestamps: Vec<i64>, point: Vec<f64>, (lower, upper): (f64, f64)) -> Forecast {
+    Forecast {
+        lower: point.iter().map(|p| (p + lower).max(0.0)).collect(),
+        upper: point.iter().map(|p| (p + upper).max(0.0)).collect(),
+        point,
+        timestamps,
+    }
+}
+
+/// Repeat the most recent full season: a week if there is one, else a day.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeasonalNaive {
+    history: Series,
+    season: i64,
+    offsets: (f64, f64),
+}
+
+impl SeasonalNaive {
+    pub fn fit(history: &Series, interval_width: f64) -> Result<Self, Box<dyn Error>> {
+        let first = *history.timestamps.first().ok_or("Empty history")?;
+        let last = *history.timestamps.last().ok_or("Empty history")?;
+        let step = history.step().clamp(1, HOUR);
+        let span = last - first + step;
+        let season = if span >= WEEK {
+            WEEK
+        } else if span >= DAY {
+            DAY
+        } else {
+            return Err("Seasonal naive needs at least a day of history".into());
+        };
+
+        let lag = (season / step) as usize;
+        let residuals = history
+            .values
+            .windows(lag + 1)
+            .map(|w| w[lag] - w[0])
+            .collect();
+        Ok(Self {
+            history: history.clone(),
+            season,
+            offsets: residual_offsets(residuals, interval_width),
+        })
+    }
+
+    fn value_at(&self, ts: i64) -> f64 {
+        let last = *self.history.timestamps.last().unwrap_or(&ts);
+        // Whole seasons back, landing on or before the last observation
+        let seasons = ((ts - last) as f64 / self.season as f64).ceil().max(1.0) as i64;
+        let source = ts - seasons * self.season;
+        match self.history.timestamps.binary_search(&source) {
+            Ok(i) => self.history.values[i],
+            Err(_) => 0.0,
+        }
+    }
+}
+
+impl Forecaster for SeasonalNaive {
+    fn name(&self) -> &'static str {
+        "seasonal naive"
+    }
+
+    fn predict(&self, timestamps: Vec<i64>) -> Result<Forecast, Box<dyn Error>> {
+        let point = timestamps.iter().map(|ts| self.value_at(*ts)).collect();
+        Ok(with_interval(timestamps, point, self.offsets))
+    }
+}
+
+/// Index of the `step`-second slot of the week of a timestamp, 0 = Monday 00:00.
+fn week_slot(ts: i64, step: i64) -> usize {
+    let dt = DateTime::from_timestamp(ts, 0).unwrap_or_default();
+    let second =
+        dt.weekday().num_days_from_monday() as i64 * DAY + dt.num_seconds_from_midnight() as i64;
+    (second / step) as usize
+}
+
+/// Mean demand of each step of the week, hours on an hourly history; slots
+/// never seen use the time-of-day mean.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeeklyAverage {
+    profile: Vec<f64>,
+    step: i64,
+    offsets: (f64, f64),
+}
+
+impl WeeklyAverage {
+    pub fn fit(history: &Series, interval_width: f64) -> Result<Self, Box<dyn Error>> {
+        if history.is_empty() {
+            return Err("Empty history".into());
+        }
+        let step = history.step().clamp(1, HOUR);
+        let per_day = (DAY / step) as usize;
+        let mut week = vec![(0.0, 0usize); 7 * per_day];
+        let mut day = vec![(0.0, 0usize); per_day];
+        for (ts, v) in history.timestamps.iter().zip(&history.values) {
+            let slot = week_slot(*ts, step);
+            week[slot].0 += v;
+            week[slot].1 += 1;
+            day[slot % per_day].0 += v;
+            day[slot % per_day].1 += 1;
+        }
+        let overall = history.values.iter().sum::<f64>() / history.len() as f64;
+        let mean = |(sum, n): (f64, usize)| (n > 0).then(|| sum / n as f64);
+        let profile: Vec<f64> = (0..7 * per_day)
+            .map(|slot| {
+                mean(week[slot])
+                    .or(mean(day[slot % per_day]))
+                    .unwrap_or(overall)
+            })
+            .collect();
+
+        let residuals = history
+            .timestamps
+            .iter()
+            .zip(&history.values)
+            .map(|(ts, v)| v - profile[week_slot(*ts, step)])
+            .collect();
+        Ok(Self {
+            offsets: residual_offsets(residuals, interval_width),
+            profile,
+            step,
+        })
+    }
+}
+
+impl Forecaster for WeeklyAverage {
+    fn name(&self) -> &'static str {
+        "weekly average"
+    }
+
+    fn predict(&self, timestamps: Vec<i64>) -> Result<Forecast, Box<dyn Error>> {
+        let point = timestamps
+            .iter()
+            .map(|ts| self.profile[week_slot(*ts, self.step)])
+            .collect();
+        Ok(with_interval(timestamps, point, self.offsets))
+    }
+}
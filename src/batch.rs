@@ -0,0 +1,126 @@
+//! Batch runs over many sites: retries and the succeeded/failed report.
+//!
+//! A site that fails for a transient reason (an API timeout, an optimizer
+//! that diverged) is retried after a jittered, doubling delay; a site that
+//! still fails is reported instead of stopping the batch.
+
+use chrono::Utc;
+use rand::Rng;
+use serde::Serialize;
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use crate::config::BatchConfig;
+use crate::outcome::{Status, status_of};
+
+/// How one site of a batch went.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SiteOutcome {
+    pub site: String,
+    pub status: Status,
+    pub attempts: u32,
+    /// The last attempt's error.
+    pub error: Option<String>,
+    pub seconds: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BatchReport {
+    /// UNIX seconds.
+    pub started_at: i64,
+    pub succeeded: Vec<String>,
+    pub failed: Vec<String>,
+    pub sites: Vec<SiteOutcome>,
+}
+
+impl BatchReport {
+    pub fn new() -> Self {
+        Self {
+            started_at: Utc::now().timestamp(),
+            ..Self::default()
+        }
+    }
+
+    pub fn push(&mut self, outcome: SiteOutcome) {
+        match outcome.error {
+            None => self.succeeded.push(outcome.site.clone()),
+            Some(_) => self.failed.push(outcome.site.clone()),
+        }
+        self.sites.push(outcome);
+    }
+
+    /// One line per site, for stderr.
+    pub fn summary(&self) -> String {
+        let mut text = format!(
+            "{} of {} sites succeeded\n",
+            self.succeeded.len(),
+            self.sites.len()
+        );
+        for site in &self.sites {
+            let tries = if site.attempts == 1 {
+                "1 attempt".to_string()
+            } else {
+                format!("{} attempts", site.attempts)
+            };
+            match &site.error {
+                None => text.push_str(&format!("✅ {} ({})\n", site.site, tries)),
+                Some(e) => text.push_str(&format!("⚠️  {} failed ({}): {}\n", site.site, tries, e)),
+            }
+        }
+        text
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .map_err(|e| format!("Could not write batch report {}: {}", path, e).into())
+    }
+}
+
+/// Whether another attempt might succeed: anything but bad data, which won't
+/// change by retrying.
+pub fn is_transient(e: &(dyn Error + 'static)) -> bool {
+    status_of(e) != Status::DataError
+}
+
+/// Wait before retry `retry` (1 for the first): doubling from
+/// `retry_delay_seconds`, jittered by ±50% so sites don't retry in lockstep.
+pub fn backoff(config: &BatchConfig, retry: u32, rng: &mut impl Rng) -> Duration {
+    let base =
+        config.retry_delay_seconds.max(0.0) * 2f64.powi(retry.saturating_sub(1).min(16) as i32);
+    Duration::from_secs_f64(base * rng.gen_range(0.5..1.5))
+}
+
+/// Run `attempt` for `site` until it succeeds, fails for good or runs out of
+/// retries, sleeping with `sleep` in between.
+///
+/// `attempt` gets the attempt number, from 1.
+pub fn run_site(
+    site: &str,
+    config: &BatchConfig,
+    mut sleep: impl FnMut(Duration),
+    mut attempt: impl FnMut(u32) -> Result<(), Box<dyn Error>>,
+) -> SiteOutcome {
+    let started = Instant::now();
+    let mut rng = rand::thread_rng();
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let error = match attempt(attempts) {
+            Ok(()) => None,
+            Err(e) if attempts <= config.retries && is_transient(e.as_ref()) => {
+                let delay = backoff(config, attempts, &mut rng);
+                eprintln!("⚠️  {} failed ({}), retrying in {:.0?}", site, e, delay);
+                sleep(delay);
+                continue;
+            }
+            Err(e) => Some(e),
+        };
+        return SiteOutcome {
+            site: site.to_string(),
+            status: error.as_deref().map_or(Status::Success, status_of),
+            attempts,
+            error: error.map(|e| e.to_string()),
+            seconds: started.elapsed().as_secs_f64(),
+        };
+    }
+}
@@ -0,0 +1,115 @@
+//! Bias correction: learn how far off the model systematically is in each
+//! weekday/hour slot and adjust new forecasts by the same amount.
+
+use chrono::{DateTime, Datelike, Timelike};
+
+use crate::config::{BiasCorrectionConfig, SeasonalityMode};
+use crate::data::Series;
+use crate::forecast::Forecast;
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Index of the weekday/hour slot of a timestamp, 0 = Monday 00:00.
+fn slot(ts: i64) -> usize {
+    let dt = DateTime::from_timestamp(ts, 0).unwrap_or_default();
+    dt.weekday().num_days_from_monday() as usize * 24 + dt.hour() as usize
+}
+
+/// Learned adjustment per weekday/hour slot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BiasCorrection {
+    pub mode: SeasonalityMode,
+    /// Factors (multiplicative) or kW offsets (additive), indexed by slot.
+    pub adjustments: Vec<f64>,
+}
+
+impl BiasCorrection {
+    /// Learn from actuals and in-sample fitted values at the same timestamps.
+    ///
+    /// Slots that never had demand are left unadjusted.
+    pub fn learn(actual: &Series, fitted: &Forecast, config: &BiasCorrectionConfig) -> Self {
+        let mut actual_sum = vec![0.0; 7 * 24];
+        let mut fitted_sum = vec![0.0; 7 * 24];
+        let mut count = vec![0usize; 7 * 24];
+        for ((ts, a), f) in actual
+            .timestamps
+            .iter()
+            .zip(&actual.values)
+            .zip(&fitted.point)
+        {
+            let s = slot(*ts);
+            actual_sum[s] += a;
+            fitted_sum[s] += f;
+            count[s] += 1;
+        }
+
+        let cap = config.max_adjustment.max(0.0);
+        let adjustments = (0..7 * 24)
+            .map(|s| match config.mode {
+                SeasonalityMode::Multiplicative if fitted_sum[s] > 0.0 && actual_sum[s] > 0.0 => {
+                    (actual_sum[s] / fitted_sum[s]).clamp(1.0 - cap, 1.0 + cap)
+                }
+                SeasonalityMode::Multiplicative => 1.0,
+                SeasonalityMode::Additive if count[s] > 0 => {
+                    let offset = (actual_sum[s] - fitted_sum[s]) / count[s] as f64;
+                    let mean_fitted = fitted_sum[s] / count[s] as f64;
+                    offset.clamp(-cap * mean_fitted, cap * mean_fitted)
+                }
+                SeasonalityMode::Additive => 0.0,
+            })
+            .collect();
+        Self {
+            mode: config.mode,
+            adjustments,
+        }
+    }
+
+    /// Apply to one value at `ts`, keeping it non-negative.
+    fn adjust(&self, ts: i64, value: f64) -> f64 {
+        let a = self.adjustments[slot(ts)];
+        let adjusted = match self.mode {
+            SeasonalityMode::Multiplicative => value * a,
+            SeasonalityMode::Additive => value + a,
+        };
+        adjusted.max(0.0)
+    }
+
+    /// Correct the point forecast and shift its interval along with it.
+    pub fn apply(&self, forecast: &Forecast) -> Forecast {
+        let map = |values: &[f64]| {
+            forecast
+                .timestamps
+                .iter()
+                .zip(values)
+                .map(|(ts, v)| self.adjust(*ts, *v))
+                .collect::<Vec<_>>()
+        };
+        Forecast {
+            timestamps: forecast.timestamps.clone(),
+            point: map(&forecast.point),
+            lower: map(&forecast.lower),
+            upper: map(&forecast.upper),
+        }
+    }
+
+    /// The slot with the largest adjustment, e.g. `"+8.2% on Fri 18:00"`.
+    pub fn largest(&self) -> String {
+        let neutral = match self.mode {
+            SeasonalityMode::Multiplicative => 1.0,
+            SeasonalityMode::Additive => 0.0,
+        };
+        let Some((s, a)) = self
+            .adjustments
+            .iter()
+            .enumerate()
+            .max_by(|a, b| (a.1 - neutral).abs().total_cmp(&(b.1 - neutral).abs()))
+        else {
+            return "none".to_string();
+        };
+        let amount = match self.mode {
+            SeasonalityMode::Multiplicative => format!("{:+.1}%", (a - 1.0) * 100.0),
+            SeasonalityMode::Additive => format!("{:+.1} kW", a),
+        };
+        format!("{} on {} {:02}:00", amount, WEEKDAYS[s / 24], s % 24)
+    }
+}
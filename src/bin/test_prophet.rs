@@ -1,21 +1,23 @@
 use augurs::prophet::{
-    Prophet, TrainingData, ProphetOptions, FeatureMode, 
-    GrowthType, SeasonalityOption, wasmstan::WasmstanOptimizer, PredictionData
+    FeatureMode, GrowthType, PredictionData, Prophet, ProphetOptions, SeasonalityOption,
+    TrainingData, wasmstan::WasmstanOptimizer,
 };
-use csv::ReaderBuilder;
 use chrono::NaiveDateTime;
-use std::error::Error;
+use csv::ReaderBuilder;
 use plotters::prelude::*;
+use std::error::Error;
 
 fn parse_datetime_to_timestamp(datetime_str: &str) -> Result<i64, Box<dyn Error>> {
     // Parse "2024-01-01 13:14" -> NaiveDateTime
     let naive_dt = NaiveDateTime::parse_from_str(datetime_str, "%Y-%m-%d %H:%M")?;
     // Convert to UNIX timestamp
-    Ok(naive_dt.timestamp())
+    Ok(naive_dt.and_utc().timestamp())
 }
 
 fn load_data_from_csv(file_path: &str) -> Result<(Vec<i64>, Vec<f64>), Box<dyn Error>> {
-    let mut rdr = ReaderBuilder::new().has_headers(false).from_path(file_path)?;
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(false)
+        .from_path(file_path)?;
     let mut timestamps = Vec::new();
     let mut values = Vec::new();
 
@@ -35,7 +37,10 @@ fn load_data_from_csv(file_path: &str) -> Result<(Vec<i64>, Vec<f64>), Box<dyn E
                     values.push(energy);
                 }
             } else {
-                println!("Skipping invalid row: {:?} -> {:?} | {:?}", ts_str, energy_str, record);
+                println!(
+                    "Skipping invalid row: {:?} -> {:?} | {:?}",
+                    ts_str, energy_str, record
+                );
             }
         }
     }
@@ -47,16 +52,25 @@ fn load_data_from_csv(file_path: &str) -> Result<(Vec<i64>, Vec<f64>), Box<dyn E
     Ok((timestamps, values))
 }
 
-fn plot_forecast(timestamps: &Vec<i64>, future_timestamps: &Vec<i64>, actual_values: &Vec<f64>, predicted_values: &Vec<f64>) -> Result<(), Box<dyn Error>> {
-
+fn plot_forecast(
+    timestamps: &[i64],
+    future_timestamps: &[i64],
+    actual_values: &[f64],
+    predicted_values: &[f64],
+) -> Result<(), Box<dyn Error>> {
     let output_file = "forecast.png";
     let root = BitMapBackend::new(output_file, (900, 600)).into_drawing_area();
     root.fill(&WHITE)?;
 
     let min_x = *timestamps.first().unwrap();
-    let max_x = future_timestamps.last().unwrap_or_else(|| timestamps.last().unwrap());
+    let max_x = future_timestamps
+        .last()
+        .unwrap_or_else(|| timestamps.last().unwrap());
     let min_y = actual_values.iter().cloned().fold(f64::INFINITY, f64::min);
-    let max_y = actual_values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let max_y = actual_values
+        .iter()
+        .cloned()
+        .fold(f64::NEG_INFINITY, f64::max);
 
     let mut chart = ChartBuilder::on(&root)
         .caption("EV Charging Demand Forecast", ("Arial", 20))
@@ -68,20 +82,28 @@ fn plot_forecast(timestamps: &Vec<i64>, future_timestamps: &Vec<i64>, actual_val
     chart.configure_mesh().draw()?;
 
     // Plot actual values (BLUE)
-    chart.draw_series(LineSeries::new(
-        timestamps.iter().zip(actual_values.iter()).map(|(x, y)| (*x, *y)),
-        &BLUE,
-    ))?
-    .label("Actual Demand")
-    .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 10, y)], &BLUE));
+    chart
+        .draw_series(LineSeries::new(
+            timestamps
+                .iter()
+                .zip(actual_values.iter())
+                .map(|(x, y)| (*x, *y)),
+            &BLUE,
+        ))?
+        .label("Actual Demand")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 10, y)], BLUE));
 
     // Plot predicted values (RED)
-    chart.draw_series(LineSeries::new(
-        timestamps.iter().zip(predicted_values.iter()).map(|(x, y)| (*x, *y)),
-        &RED,
-    ))?
-    .label("Predicted Demand")
-    .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 10, y)], &RED));
+    chart
+        .draw_series(LineSeries::new(
+            timestamps
+                .iter()
+                .zip(predicted_values.iter())
+                .map(|(x, y)| (*x, *y)),
+            &RED,
+        ))?
+        .label("Predicted Demand")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 10, y)], RED));
 
     chart.configure_series_labels().draw()?;
 
@@ -89,7 +111,6 @@ fn plot_forecast(timestamps: &Vec<i64>, future_timestamps: &Vec<i64>, actual_val
     Ok(())
 }
 
-
 fn main() -> Result<(), Box<dyn Error>> {
     // Load real data from CSV
     let (timestamps, values) = load_data_from_csv("data/site_data.csv")?;
@@ -99,12 +120,8 @@ fn main() -> Result<(), Box<dyn Error>> {
         return Err("Not enough data points for forecasting. Try using more data.".into());
     }
 
-    // Clone timestamps & values before passing them to TrainingData to avoid ownership issues
-    let timestamps_clone = timestamps.clone();
-    let values_clone = values.clone();
-
-    // Create training data
-    let data = TrainingData::new(timestamps_clone, values_clone)?;
+    // Create training data; Prophet takes ownership, and the originals are still needed for the plot
+    let data = TrainingData::new(timestamps.clone(), values.clone())?;
 
     // Use WasmstanOptimizer
     let optimizer = WasmstanOptimizer::new();
@@ -112,7 +129,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let options = ProphetOptions {
         // Linear growth model (captures increasing or decreasing trends)
         growth: GrowthType::Linear,
-        
+
         // Multiplicative seasonality (captures large fluctuations in demand)
         seasonality_mode: FeatureMode::Multiplicative,
 
@@ -159,14 +176,12 @@ fn main() -> Result<(), Box<dyn Error>> {
     // println!("Predictions: {:?}", predictions.yhat.point);
     // println!("Lower bounds: {:?}", predictions.yhat.lower.unwrap());
     // println!("Upper bounds: {:?}", predictions.yhat.upper.unwrap());
-    
+
     // Extract predicted values
     let predicted_values = predictions.yhat.point.clone();
 
     // Call the function to generate the plot
     plot_forecast(&timestamps, &future_timestamps, &values, &predicted_values)?;
 
-
     Ok(())
 }
-
@@ -0,0 +1,188 @@
+//! Structural breaks: a site's demand settling at a new level, e.g. when a
+//! competitor's hub opens nearby and volume halves.
+//!
+//! Each complete day of the watched window is compared with what the baseline
+//! weeks before it had on the same weekdays and hours. A two-sided CUSUM on
+//! the standardized daily residuals adds up deviations that persist and
+//! forgets single odd days: each day counts for at most half the threshold,
+//! so one outage or event day doesn't alert but a lasting shift does within a
+//! few days.
+
+use chrono::{DateTime, Datelike, Timelike};
+use serde_json::{Value, json};
+
+use crate::anomaly;
+use crate::config::BreakConfig;
+use crate::data::{Series, format_timestamp};
+use crate::preprocess::HOUR;
+
+const DAY: i64 = 24 * HOUR;
+
+/// Fewest complete baseline days a break is looked for against.
+const MIN_BASELINE_DAYS: usize = 7;
+
+/// A lasting level shift in the watched window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Break {
+    /// First day at the new level.
+    pub start: i64,
+    /// End of the day the CUSUM crossed the threshold.
+    pub detected_at: i64,
+    /// Mean load since `start`.
+    pub actual_kw: f64,
+    /// What the baseline had for the same hours.
+    pub expected_kw: f64,
+}
+
+impl Break {
+    /// Relative change of the level, e.g. `-0.5` for halved demand.
+    pub fn change(&self) -> f64 {
+        self.actual_kw / self.expected_kw - 1.0
+    }
+
+    /// E.g. "Demand fell 48% from Mon 2024-09-16 (12.1 kW against 23.4 kW before), detected 2024-09-18".
+    pub fn summary(&self) -> String {
+        let day = |ts: i64| {
+            DateTime::from_timestamp(ts, 0)
+                .map(|dt| dt.format("%a %Y-%m-%d").to_string())
+                .unwrap_or_default()
+        };
+        format!(
+            "Demand {} {:.0}% from {} ({:.1} kW against {:.1} kW before), detected {}",
+            if self.change() < 0.0 { "fell" } else { "rose" },
+            self.change().abs() * 100.0,
+            day(self.start),
+            self.actual_kw,
+            self.expected_kw,
+            day(self.detected_at - DAY)
+        )
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "summary": self.summary(),
+            "start": format_timestamp(self.start),
+            "detected_at": format_timestamp(self.detected_at),
+            "actual_kw": self.actual_kw,
+            "expected_kw": self.expected_kw,
+            "change": self.change(),
+        })
+    }
+}
+
+/// A site's break check as a JSON document; `break` is null when there was none.
+pub fn report_json(site: &str, found: Option<&Break>) -> Value {
+    json!({ "site": site, "break": found.map(Break::to_json) })
+}
+
+/// Index of the weekday/hour slot of a timestamp, 0 = Monday 00:00.
+fn slot(ts: i64) -> usize {
+    let dt = DateTime::from_timestamp(ts, 0).unwrap_or_default();
+    dt.weekday().num_days_from_monday() as usize * 24 + dt.hour() as usize
+}
+
+/// Complete days of `series` in `[from, to)`: each day's start, actual and
+/// expected load summed over its `per_day` points.
+fn days(
+    series: &Series,
+    (from, to): (i64, i64),
+    per_day: usize,
+    expected: &[f64],
+) -> Vec<(i64, f64, f64)> {
+    let mut days: Vec<(i64, f64, f64, usize)> = Vec::new();
+    for (ts, value) in series
+        .timestamps
+        .iter()
+        .zip(&series.values)
+        .filter(|(ts, _)| (from..to).contains(*ts))
+    {
+        let day = ts.div_euclid(DAY) * DAY;
+        match days.last_mut() {
+            Some(last) if last.0 == day => {
+                last.1 += value;
+                last.2 += expected[slot(*ts)];
+                last.3 += 1;
+            }
+            _ => days.push((day, *value, expected[slot(*ts)], 1)),
+        }
+    }
+    days.into_iter()
+        .filter(|d| d.3 == per_day)
+        .map(|(day, actual, expected, _)| (day, actual, expected))
+        .collect()
+}
+
+/// The first lasting shift in the last `config.window_days` of `history`, if any.
+///
+/// `None` too when there isn't a week of baseline before the window, or it
+/// has no day-to-day spread to measure a shift against.
+pub fn detect(history: &Series, config: &BreakConfig) -> Option<Break> {
+    let step = history.step().max(1);
+    let per_day = (DAY / step) as usize;
+    let end = history.timestamps.last()? + step;
+    let window_start = (end - config.window_days as i64 * DAY).div_euclid(DAY) * DAY;
+    let baseline_start = window_start - config.baseline_days as i64 * DAY;
+
+    // The baseline's mean load per weekday/hour slot
+    let mut sum = vec![0.0; 7 * 24];
+    let mut count = vec![0usize; 7 * 24];
+    for (ts, value) in history
+        .timestamps
+        .iter()
+        .zip(&history.values)
+        .filter(|(ts, _)| (baseline_start..window_start).contains(*ts))
+    {
+        sum[slot(*ts)] += value;
+        count[slot(*ts)] += 1;
+    }
+    let overall = sum.iter().sum::<f64>() / count.iter().sum::<usize>().max(1) as f64;
+    let expected: Vec<f64> = sum
+        .iter()
+        .zip(&count)
+        .map(|(s, n)| if *n > 0 { s / *n as f64 } else { overall })
+        .collect();
+
+    let baseline = days(history, (baseline_start, window_start), per_day, &expected);
+    if baseline.len() < MIN_BASELINE_DAYS {
+        return None;
+    }
+    let residuals: Vec<f64> = baseline
+        .iter()
+        .map(|(_, actual, expected)| actual - expected)
+        .collect();
+    let (_, mad) = anomaly::spread(&residuals)?;
+    let sigma = 1.4826 * mad;
+
+    let watched = days(history, (window_start, end), per_day, &expected);
+    let (mut high, mut low) = (0.0, 0.0);
+    let (mut high_start, mut low_start) = (0, 0);
+    for (i, (day, actual, expected)) in watched.iter().enumerate() {
+        let limit = config.threshold / 2.0;
+        let z = ((actual - expected) / sigma).clamp(-limit, limit);
+        high = f64::max(0.0, high + z - config.drift);
+        low = f64::max(0.0, low - z - config.drift);
+        if high == 0.0 {
+            high_start = i + 1;
+        }
+        if low == 0.0 {
+            low_start = i + 1;
+        }
+        if high > config.threshold || low > config.threshold {
+            let from = if high > config.threshold {
+                high_start
+            } else {
+                low_start
+            };
+            let shifted = &watched[from..=i];
+            return Some(Break {
+                start: watched[from].0,
+                detected_at: day + DAY,
+                actual_kw: shifted.iter().map(|d| d.1).sum::<f64>()
+                    / (per_day * shifted.len()) as f64,
+                expected_kw: shifted.iter().map(|d| d.2).sum::<f64>()
+                    / (per_day * shifted.len()) as f64,
+            });
+        }
+    }
+    None
+}
@@ -0,0 +1,262 @@
+//! Calendar features for use as Prophet regressors.
+
+use chrono::{DateTime, Datelike, NaiveDate, TimeDelta, Weekday};
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use crate::config::{CalendarConfig, DateRange, RegionConfig};
+
+fn parse_date(text: &str) -> Result<NaiveDate, Box<dyn Error>> {
+    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid calendar date {:?}: {}", text, e).into())
+}
+
+/// One holiday as returned by the OpenHolidays API.
+#[cfg(feature = "http")]
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiHoliday {
+    start_date: String,
+    end_date: String,
+}
+
+/// Holidays of one kind (`"PublicHolidays"` or `"SchoolHolidays"`) in a region and year.
+fn regional_holidays(
+    region: &RegionConfig,
+    kind: &str,
+    year: i32,
+) -> Result<Vec<DateRange>, Box<dyn Error>> {
+    let code = region.subdivision.as_deref().unwrap_or(&region.country);
+    let cache = Path::new(&region.cache_dir).join(format!("{}-{}-{}.json", code, kind, year));
+    if let Ok(text) = std::fs::read_to_string(&cache) {
+        return Ok(serde_json::from_str(&text)?);
+    }
+
+    fetch_holidays(region, kind, year, &cache)
+}
+
+/// Fetch holidays from the API and cache them.
+#[cfg(feature = "http")]
+fn fetch_holidays(
+    region: &RegionConfig,
+    kind: &str,
+    year: i32,
+    cache: &Path,
+) -> Result<Vec<DateRange>, Box<dyn Error>> {
+    let code = region.subdivision.as_deref().unwrap_or(&region.country);
+    let url = format!("{}/{}", region.api_url.trim_end_matches('/'), kind);
+    let mut request = ureq::get(&url)
+        .query("countryIsoCode", &region.country)
+        .query("validFrom", &format!("{}-01-01", year))
+        .query("validTo", &format!("{}-12-31", year));
+    if let Some(subdivision) = &region.subdivision {
+        request = request.query("subdivisionCode", subdivision);
+    }
+    let holidays: Vec<ApiHoliday> = request
+        .call()
+        .map_err(|e| format!("Fetching {} for {} {} failed: {}", kind, code, year, e))?
+        .into_json()?;
+    let ranges: Vec<DateRange> = holidays
+        .into_iter()
+        .map(|h| DateRange {
+            from: h.start_date,
+            to: h.end_date,
+        })
+        .collect();
+
+    std::fs::create_dir_all(&region.cache_dir)?;
+    std::fs::write(cache, serde_json::to_string_pretty(&ranges)?)?;
+    eprintln!(
+        "Fetched {} {} for {} {} into {}",
+        ranges.len(),
+        kind,
+        code,
+        year,
+        cache.display()
+    );
+    Ok(ranges)
+}
+
+#[cfg(not(feature = "http"))]
+fn fetch_holidays(
+    _: &RegionConfig,
+    kind: &str,
+    year: i32,
+    cache: &Path,
+) -> Result<Vec<DateRange>, Box<dyn Error>> {
+    Err(format!(
+        "No cached {} for {} at {}, and fetching needs the `http` feature",
+        kind,
+        year,
+        cache.display()
+    )
+    .into())
+}
+
+/// Every date of an inclusive range.
+fn days(from: NaiveDate, to: NaiveDate) -> impl Iterator<Item = NaiveDate> {
+    from.iter_days().take_while(move |d| *d <= to)
+}
+
+/// A parsed [`CalendarConfig`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Calendar {
+    weekend: bool,
+    month: bool,
+    public_holidays: Option<HashSet<NaiveDate>>,
+    /// Day offsets around the public holidays with features of their own, e.g. `[-1, 1]`.
+    holiday_offsets: Vec<i32>,
+    bridge_days: bool,
+    school_holidays: Option<Vec<(NaiveDate, NaiveDate)>>,
+}
+
+impl Calendar {
+    /// Parse the configured dates, adding regional holidays for the given `years`.
+    ///
+    /// A region that can't be fetched (e.g. offline without a cache) is
+    /// skipped with a warning rather than failing the forecast.
+    pub fn new(
+        config: &CalendarConfig,
+        years: RangeInclusive<i32>,
+    ) -> Result<Self, Box<dyn Error>> {
+        if config.holiday_lower_window > 0 || config.holiday_upper_window < 0 {
+            return Err("calendar.holiday_lower_window must be 0 or less, and holiday_upper_window 0 or more".into());
+        }
+        let mut public_dates: Vec<DateRange> = config
+            .public_holidays
+            .iter()
+            .map(|d| DateRange {
+                from: d.clone(),
+                to: d.clone(),
+            })
+            .collect();
+        let mut school_dates = config.school_holidays.clone();
+        if let Some(region) = &config.region {
+            for year in years {
+                let kinds = [
+                    (region.public_holidays, "PublicHolidays", &mut public_dates),
+                    (region.school_holidays, "SchoolHolidays", &mut school_dates),
+                ];
+                for (enabled, kind, dates) in kinds {
+                    if !enabled {
+                        continue;
+                    }
+                    match regional_holidays(region, kind, year) {
+                        Ok(ranges) => dates.extend(ranges),
+                        Err(e) => eprintln!("⚠️  {}; leaving them out", e),
+                    }
+                }
+            }
+        }
+
+        let public_holidays = if public_dates.is_empty() {
+            None
+        } else {
+            let mut set = HashSet::new();
+            for r in &public_dates {
+                set.extend(days(parse_date(&r.from)?, parse_date(&r.to)?));
+            }
+            Some(set)
+        };
+        let school_holidays = if school_dates.is_empty() {
+            None
+        } else {
+            Some(
+                school_dates
+                    .iter()
+                    .map(|r| Ok((parse_date(&r.from)?, parse_date(&r.to)?)))
+                    .collect::<Result<_, Box<dyn Error>>>()?,
+            )
+        };
+        Ok(Self {
+            weekend: config.weekend,
+            month: config.month,
+            public_holidays,
+            holiday_offsets: (config.holiday_lower_window..=config.holiday_upper_window)
+                .filter(|k| *k != 0)
+                .collect(),
+            bridge_days: config.bridge_days,
+            school_holidays,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.weekend
+            && !self.month
+            && self.public_holidays.is_none()
+            && self.school_holidays.is_none()
+    }
+
+    /// Every enabled feature at the given timestamps, by regressor name.
+    pub fn features(&self, timestamps: &[i64]) -> BTreeMap<String, Vec<f64>> {
+        let dates: Vec<NaiveDate> = timestamps
+            .iter()
+            .map(|ts| {
+                DateTime::from_timestamp(*ts, 0)
+                    .unwrap_or_default()
+                    .date_naive()
+            })
+            .collect();
+        let flag = |f: &dyn Fn(&NaiveDate) -> bool| {
+            dates.iter().map(|d| if f(d) { 1.0 } else { 0.0 }).collect()
+        };
+
+        let mut features = BTreeMap::new();
+        if self.weekend {
+            features.insert(
+                "is_weekend".to_string(),
+                flag(&|d| matches!(d.weekday(), Weekday::Sat | Weekday::Sun)),
+            );
+        }
+        if let Some(holidays) = &self.public_holidays {
+            features.insert(
+                "is_public_holiday".to_string(),
+                flag(&|d| holidays.contains(d)),
+            );
+            // A day near a holiday that is a holiday itself keeps the holiday's effect
+            let shifted =
+                |d: &NaiveDate, days: i32| d.checked_sub_signed(TimeDelta::days(days as i64));
+            for &k in &self.holiday_offsets {
+                let name = match k < 0 {
+                    true => format!("public_holiday_minus_{}", -k),
+                    false => format!("public_holiday_plus_{}", k),
+                };
+                let near = |d: &NaiveDate| {
+                    !holidays.contains(d) && shifted(d, k).is_some_and(|h| holidays.contains(&h))
+                };
+                features.insert(name, flag(&near));
+            }
+            if self.bridge_days {
+                let off = |d: Option<NaiveDate>| {
+                    d.is_some_and(|d| {
+                        holidays.contains(&d) || matches!(d.weekday(), Weekday::Sat | Weekday::Sun)
+                    })
+                };
+                let bridge = |d: &NaiveDate| {
+                    let (before, after) = (d.pred_opt(), d.succ_opt());
+                    !off(Some(*d))
+                        && off(before)
+                        && off(after)
+                        && [before, after]
+                            .iter()
+                            .any(|n| n.is_some_and(|n| holidays.contains(&n)))
+                };
+                features.insert("is_bridge_day".to_string(), flag(&bridge));
+            }
+        }
+        if let Some(ranges) = &self.school_holidays {
+            features.insert(
+                "is_school_holiday".to_string(),
+                flag(&|d| ranges.iter().any(|(from, to)| (from..=to).contains(&d))),
+            );
+        }
+        if self.month {
+            for month in 2..=12 {
+                features.insert(format!("month_{}", month), flag(&|d| d.month() == month));
+            }
+        }
+        features
+    }
+}
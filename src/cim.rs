@@ -0,0 +1,207 @@
+//! Forecasts as CIM schedule documents, the XML profile DSOs take load
+//! forecasts in (IEC 61968-9 / IEC 62325-451-6, `GL_MarketDocument`).
+//!
+//! A document carries one time series per curve: the point forecast, and with
+//! `bounds` its lower and upper interval bound as series 2 and 3. Times are
+//! UTC; a forecast hour that doesn't exist locally (clocks going forward) is
+//! left out of its period.
+
+use chrono::{DateTime, TimeZone};
+use chrono_tz::Tz;
+use quick_xml::escape::escape;
+use std::error::Error;
+use std::io::Write;
+
+use crate::config::CimConfig;
+use crate::export::{ExportContext, Exporter};
+use crate::forecast::Forecast;
+use crate::preprocess::HOUR;
+
+const NAMESPACE: &str = "urn:iec62325.351:tc57wg16:451-6:generationloaddocument:3:0";
+
+pub struct CimExporter {
+    config: CimConfig,
+}
+
+impl CimExporter {
+    pub fn new(config: CimConfig) -> Self {
+        Self { config }
+    }
+
+    /// Quantity per kW of the configured unit.
+    fn scale(&self) -> Result<f64, Box<dyn Error>> {
+        match self.config.unit.as_str() {
+            "KWT" => Ok(1.0),
+            "MAW" => Ok(0.001),
+            unit => Err(format!("Unsupported CIM unit {:?} (expected KWT or MAW)", unit).into()),
+        }
+    }
+}
+
+impl Exporter for CimExporter {
+    fn name(&self) -> &'static str {
+        "CIM XML"
+    }
+
+    fn write(
+        &self,
+        forecast: &Forecast,
+        context: &ExportContext,
+        out: &mut dyn Write,
+    ) -> Result<(), Box<dyn Error>> {
+        let config = &self.config;
+        for (name, value) in [
+            ("sender_mrid", &config.sender_mrid),
+            ("receiver_mrid", &config.receiver_mrid),
+        ] {
+            if value.is_empty() {
+                return Err(format!("CIM export needs output.cim.{}", name).into());
+            }
+        }
+        let scale = self.scale()?;
+        let step = forecast
+            .timestamps
+            .windows(2)
+            .map(|w| w[1] - w[0])
+            .filter(|d| *d > 0)
+            .min()
+            .unwrap_or(HOUR);
+        let points: Vec<(i64, usize)> = (0..forecast.len())
+            .filter_map(|i| to_utc(forecast.timestamps[i], context.timezone).map(|ts| (ts, i)))
+            .collect();
+        let (Some(&(start, _)), Some(&(last, _))) = (points.first(), points.last()) else {
+            return Err("CIM export of an empty forecast".into());
+        };
+        let end = last + step;
+        let point = config.measurement_point.as_deref().unwrap_or(&context.site);
+
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(out, r#"<GL_MarketDocument xmlns="{}">"#, NAMESPACE)?;
+        writeln!(
+            out,
+            "  <mRID>{}</mRID>",
+            escape(format!(
+                "{}-{}",
+                point,
+                DateTime::from_timestamp(context.issued_at, 0)
+                    .unwrap_or_default()
+                    .format("%Y%m%d%H%M")
+            ))
+        )?;
+        writeln!(out, "  <revisionNumber>1</revisionNumber>")?;
+        writeln!(out, "  <type>{}</type>", escape(&config.document_type))?;
+        writeln!(
+            out,
+            "  <process.processType>{}</process.processType>",
+            escape(&config.process_type)
+        )?;
+        for (party, mrid, role) in [
+            ("sender", &config.sender_mrid, &config.sender_role),
+            ("receiver", &config.receiver_mrid, &config.receiver_role),
+        ] {
+            writeln!(
+                out,
+                r#"  <{0}_MarketParticipant.mRID codingScheme="{1}">{2}</{0}_MarketParticipant.mRID>"#,
+                party,
+                escape(&config.coding_scheme),
+                escape(mrid)
+            )?;
+            writeln!(
+                out,
+                "  <{0}_MarketParticipant.marketRole.type>{1}</{0}_MarketParticipant.marketRole.type>",
+                party,
+                escape(role)
+            )?;
+        }
+        writeln!(
+            out,
+            "  <createdDateTime>{}</createdDateTime>",
+            DateTime::from_timestamp(context.issued_at, 0)
+                .unwrap_or_default()
+                .format("%Y-%m-%dT%H:%M:%SZ")
+        )?;
+        writeln!(out, "  <time_Period.timeInterval>")?;
+        writeln!(
+            out,
+            "    <start>{}</start>\n    <end>{}</end>",
+            format_utc(start),
+            format_utc(end)
+        )?;
+        writeln!(out, "  </time_Period.timeInterval>")?;
+
+        let mut curves = vec![&forecast.point];
+        if config.bounds {
+            curves.extend([&forecast.lower, &forecast.upper]);
+        }
+        for (series, values) in curves.into_iter().enumerate() {
+            writeln!(out, "  <TimeSeries>")?;
+            writeln!(out, "    <mRID>{}</mRID>", series + 1)?;
+            writeln!(
+                out,
+                "    <businessType>{}</businessType>",
+                escape(&config.business_type)
+            )?;
+            writeln!(out, "    <objectAggregation>A04</objectAggregation>")?;
+            writeln!(
+                out,
+                r#"    <MarketEvaluationPoint.mRID codingScheme="{}">{}</MarketEvaluationPoint.mRID>"#,
+                escape(&config.coding_scheme),
+                escape(point)
+            )?;
+            writeln!(
+                out,
+                "    <quantity_Measure_Unit.name>{}</quantity_Measure_Unit.name>",
+                escape(&config.unit)
+            )?;
+            writeln!(out, "    <curveType>A01</curveType>")?;
+            writeln!(out, "    <Period>")?;
+            writeln!(
+                out,
+                "      <timeInterval>\n        <start>{}</start>\n        <end>{}</end>\n      </timeInterval>",
+                format_utc(start),
+                format_utc(end)
+            )?;
+            writeln!(out, "      <resolution>PT{}M</resolution>", step / 60)?;
+            for &(ts, i) in &points {
+                let quantity = values[i] * scale;
+                writeln!(
+                    out,
+                    "      <Point>\n        <position>{}</position>\n        <quantity>{}</quantity>\n      </Point>",
+                    (ts - start) / step + 1,
+                    format_quantity(quantity, scale)
+                )?;
+            }
+            writeln!(out, "    </Period>")?;
+            writeln!(out, "  </TimeSeries>")?;
+        }
+        writeln!(out, "</GL_MarketDocument>")?;
+        Ok(())
+    }
+}
+
+/// A local wall-clock timestamp in UTC, or `None` for one skipped by the clocks.
+fn to_utc(ts: i64, timezone: Option<Tz>) -> Option<i64> {
+    let Some(tz) = timezone else {
+        return Some(ts);
+    };
+    let local = DateTime::from_timestamp(ts, 0)?.naive_utc();
+    tz.from_local_datetime(&local)
+        .earliest()
+        .map(|time| time.timestamp())
+}
+
+/// `2024-01-01T23:00Z`, as CIM documents write their intervals.
+fn format_utc(ts: i64) -> String {
+    DateTime::from_timestamp(ts, 0)
+        .unwrap_or_default()
+        .format("%Y-%m-%dT%H:%MZ")
+        .to_string()
+}
+
+/// Watt precision in either unit.
+fn format_quantity(quantity: f64, scale: f64) -> String {
+    match scale < 1.0 {
+        true => format!("{:.6}", quantity),
+        false => format!("{:.3}", quantity),
+    }
+}
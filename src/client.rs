@@ -0,0 +1,166 @@
+//! Typed client of the HTTP server, over the shapes in [`crate::api`].
+//!
+//! ```no_run
+//! use cpo_charging_forecast::api::ForecastParams;
+//! use cpo_charging_forecast::client::Client;
+//!
+//! let client = Client::new("http://127.0.0.1:8080").with_token("…");
+//! let params = ForecastParams {
+//!     site: Some("north".to_string()),
+//!     resolution: Some("day".to_string()),
+//!     ..ForecastParams::default()
+//! };
+//! for point in client.forecast(&params)?.points {
+//!     println!("{} {:.0} kWh", point.timestamp, point.energy_kwh.unwrap_or_default());
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::error::Error;
+
+use crate::api::{
+    ApiError, ForecastPage, ForecastParams, ForecastPoint, Nowcast, NowcastRequest, QueryRequest,
+    TimeSeries,
+};
+use crate::jobs::FitJob;
+
+pub struct Client {
+    base_url: String,
+    token: Option<String>,
+    agent: ureq::Agent,
+}
+
+impl Client {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: None,
+            agent: ureq::Agent::new(),
+        }
+    }
+
+    /// Authenticate as a tenant, with one of its API keys or a JWT.
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    /// The sites this client may see.
+    pub fn sites(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        self.send(self.request("GET", "/sites"), None)
+    }
+
+    /// One page of a site's forecast.
+    pub fn forecast(&self, params: &ForecastParams) -> Result<ForecastPage, Box<dyn Error>> {
+        let mut request = self.request("GET", "/forecast");
+        let text = [
+            ("site", params.site.clone()),
+            ("from", params.from.clone()),
+            ("to", params.to.clone()),
+            ("resolution", params.resolution.clone()),
+        ];
+        let counts = [("offset", params.offset), ("limit", params.limit)];
+        for (key, value) in text
+            .into_iter()
+            .chain(counts.map(|(key, value)| (key, value.map(|v| v.to_string()))))
+        {
+            if let Some(value) = value {
+                request = request.query(key, &value);
+            }
+        }
+        self.send(request, None)
+    }
+
+    /// Every point of a forecast slice, following `next_offset` through the pages.
+    pub fn forecast_points(
+        &self,
+        params: &ForecastParams,
+    ) -> Result<Vec<ForecastPoint>, Box<dyn Error>> {
+        let mut params = params.clone();
+        let mut points = Vec::new();
+        loop {
+            let page = self.forecast(&params)?;
+            points.extend(page.points);
+            match page.next_offset {
+                Some(offset) => params.offset = Some(offset),
+                None => return Ok(points),
+            }
+        }
+    }
+
+    /// Queue a refit of a site; poll [`Client::fit_job`] for how it went.
+    pub fn submit_fit(&self, site: Option<&str>) -> Result<FitJob, Box<dyn Error>> {
+        let request = self.request("POST", "/fits");
+        let request = match site {
+            Some(site) => request.query("site", site),
+            None => request,
+        };
+        self.send(request, None)
+    }
+
+    pub fn fit_job(&self, id: u64) -> Result<FitJob, Box<dyn Error>> {
+        self.send(self.request("GET", &format!("/fits/{}", id)), None)
+    }
+
+    /// Grafana series, as `POST /query` answers them.
+    pub fn query(&self, query: &QueryRequest) -> Result<Vec<TimeSeries>, Box<dyn Error>> {
+        self.send(
+            self.request("POST", "/query"),
+            Some(serde_json::to_value(query)?),
+        )
+    }
+
+    /// The current and next hour of a site's forecast, corrected by the readings since it was issued.
+    pub fn nowcast(
+        &self,
+        site: Option<&str>,
+        request: &NowcastRequest,
+    ) -> Result<Nowcast, Box<dyn Error>> {
+        let http = self.request("POST", "/nowcast");
+        let http = match site {
+            Some(site) => http.query("site", site),
+            None => http,
+        };
+        self.send(http, Some(serde_json::to_value(request)?))
+    }
+
+    /// The server's OpenAPI document.
+    pub fn openapi(&self) -> Result<Value, Box<dyn Error>> {
+        self.send(self.request("GET", "/openapi.json"), None)
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let request = self
+            .agent
+            .request(method, &format!("{}{}", self.base_url, path));
+        match &self.token {
+            Some(token) => request.set("Authorization", &format!("Bearer {}", token)),
+            None => request,
+        }
+    }
+
+    fn send<T: DeserializeOwned>(
+        &self,
+        request: ureq::Request,
+        body: Option<Value>,
+    ) -> Result<T, Box<dyn Error>> {
+        let url = request.url().to_string();
+        let response = match body {
+            Some(body) => request.send_json(body),
+            None => request.call(),
+        };
+        match response {
+            Ok(response) => Ok(response.into_json()?),
+            // The server explains its 4xx answers
+            Err(ureq::Error::Status(code, response)) => {
+                let message = response
+                    .into_json::<ApiError>()
+                    .map_or_else(|_| "no details".to_string(), |e| e.error);
+                Err(format!("{} answered {}: {}", url, code, message).into())
+            }
+            Err(e) => Err(format!("Could not reach {}: {}", url, e).into()),
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! Clustering sites by demand shape.
+//!
+//! Sites are grouped on their normalized daily profiles (see
+//! [`crate::profile`]), so a small and a large site with the same evening peak
+//! end up together: k-means for the Euclidean distance, k-medoids for DTW,
+//! whose curves can't be averaged. Starting centres are picked farthest-first
+//! from the most typical site, so the same sites always give the same clusters.
+
+use csv::Writer;
+use std::error::Error;
+
+use crate::config::{ClusterConfig, ProfileDistance};
+use crate::export;
+use crate::profile::Profiles;
+
+/// Assignment rounds before giving up on convergence.
+const MAX_ITERATIONS: usize = 100;
+
+/// Peak over mean below which a profile counts as flat.
+const FLAT_PEAK: f64 = 1.25;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cluster {
+    /// 1-based, in order of the centre's peak hour.
+    pub id: usize,
+    /// The shape of the centre, e.g. `evening peak (18:00)`.
+    pub label: String,
+    /// The centre's daily profile.
+    pub centre: Vec<f64>,
+    pub sites: Vec<String>,
+}
+
+/// Which cluster a site is in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Membership {
+    pub site: String,
+    pub cluster: usize,
+    pub label: String,
+    /// From the site's profile to its cluster's centre, for spotting outliers.
+    pub distance: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clustering {
+    pub clusters: Vec<Cluster>,
+    /// One per site, in the order the profiles were given.
+    pub members: Vec<Membership>,
+}
+
+impl Clustering {
+    /// Where `site` went.
+    pub fn member(&self, site: &str) -> Option<&Membership> {
+        self.members.iter().find(|m| m.site == site)
+    }
+}
+
+/// Distance between two daily profiles.
+pub fn distance(a: &[f64], b: &[f64], config: &ClusterConfig) -> f64 {
+    match config.distance {
+        ProfileDistance::Euclidean => a
+            .iter()
+            .zip(b)
+            .map(|(x, y)| (x - y).powi(2))
+            .sum::<f64>()
+            .sqrt(),
+        ProfileDistance::Dtw => dtw(a, b, config.window_hours),
+    }
+}
+
+/// Dynamic time warping distance, matching hours at most `window` apart.
+pub fn dtw(a: &[f64], b: &[f64], window: usize) -> f64 {
+    let (n, m) = (a.len(), b.len());
+    let window = window.max(n.abs_diff(m));
+    let mut cost = vec![vec![f64::INFINITY; m + 1]; n + 1];
+    cost[0][0] = 0.0;
+    for i in 1..=n {
+        for j in i.saturating_sub(window).max(1)..=(i + window).min(m) {
+            let step = cost[i - 1][j].min(cost[i][j - 1]).min(cost[i - 1][j - 1]);
+            cost[i][j] = (a[i - 1] - b[j - 1]).powi(2) + step;
+        }
+    }
+    cost[n][m].sqrt()
+}
+
+fn mean_curve(curves: &[&[f64]]) -> Vec<f64> {
+    let len = curves.first().map_or(0, |c| c.len());
+    (0..len)
+        .map(|h| curves.iter().map(|c| c[h]).sum::<f64>() / curves.len() as f64)
+        .collect()
+}
+
+fn nearest(curve: &[f64], centres: &[Vec<f64>], config: &ClusterConfig) -> (usize, f64) {
+    centres
+        .iter()
+        .map(|centre| distance(curve, centre, config))
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap_or((0, 0.0))
+}
+
+/// Describe a daily profile by when it peaks.
+pub fn label(profile: &[f64]) -> String {
+    let mean = profile.iter().sum::<f64>() / profile.len().max(1) as f64;
+    let (hour, peak) = profile
+        .iter()
+        .cloned()
+        .enumerate()
+        .min_by(|a, b| b.1.total_cmp(&a.1))
+        .unwrap_or((0, 0.0));
+    if peak < FLAT_PEAK * mean {
+        return "flat".to_string();
+    }
+    let part = match hour {
+        5..=9 => "morning",
+        10..=14 => "midday",
+        15..=19 => "evening",
+        _ => "night",
+    };
+    format!("{} peak ({:02}:00)", part, hour)
+}
+
+/// Group sites into at most `config.k` clusters of similar daily profile.
+pub fn cluster(
+    profiles: &[Profiles],
+    config: &ClusterConfig,
+) -> Result<Clustering, Box<dyn Error>> {
+    if profiles.is_empty() {
+        return Err("No site profiles to cluster".into());
+    }
+    if config.k == 0 {
+        return Err("cluster.k must be at least 1".into());
+    }
+    let curves: Vec<&[f64]> = profiles.iter().map(|p| p.daily.as_slice()).collect();
+
+    // Farthest-first: the most typical site, then whichever is farthest from every centre so far
+    let typical = mean_curve(&curves);
+    let (first, _) = nearest(
+        &typical,
+        &curves.iter().map(|c| c.to_vec()).collect::<Vec<_>>(),
+        config,
+    );
+    let mut centres = vec![curves[first].to_vec()];
+    while centres.len() < config.k.min(curves.len()) {
+        let (farthest, _) = curves
+            .iter()
+            .map(|curve| nearest(curve, &centres, config).1)
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap_or((0, 0.0));
+        centres.push(curves[farthest].to_vec());
+    }
+
+    let mut assignment: Vec<usize> = Vec::new();
+    for _ in 0..MAX_ITERATIONS {
+        let next: Vec<usize> = curves
+            .iter()
+            .map(|curve| nearest(curve, &centres, config).0)
+            .collect();
+        if next == assignment {
+            break;
+        }
+        assignment = next;
+        for (c, centre) in centres.iter_mut().enumerate() {
+            let members: Vec<&[f64]> = curves
+                .iter()
+                .zip(&assignment)
+                .filter(|(_, a)| **a == c)
+                .map(|(curve, _)| *curve)
+                .collect();
+            if members.is_empty() {
+                continue;
+            }
+            *centre = match config.distance {
+                ProfileDistance::Euclidean => mean_curve(&members),
+                // The member closest to all the others
+                ProfileDistance::Dtw => members
+                    .iter()
+                    .min_by(|a, b| {
+                        let total = |m: &[f64]| {
+                            members
+                                .iter()
+                                .map(|other| distance(m, other, config))
+                                .sum::<f64>()
+                        };
+                        total(a).total_cmp(&total(b))
+                    })
+                    .map(|m| m.to_vec())
+                    .unwrap_or_default(),
+            };
+        }
+    }
+
+    // Number the non-empty clusters by when their centres peak
+    let peak_hour = |c: &Vec<f64>| {
+        c.iter()
+            .enumerate()
+            .min_by(|a, b| b.1.total_cmp(a.1))
+            .map_or(0, |(h, _)| h)
+    };
+    let mut used: Vec<usize> = (0..centres.len())
+        .filter(|c| assignment.contains(c))
+        .collect();
+    used.sort_by_key(|c| peak_hour(&centres[*c]));
+    let clusters: Vec<Cluster> = used
+        .iter()
+        .enumerate()
+        .map(|(i, c)| Cluster {
+            id: i + 1,
+            label: label(&centres[*c]),
+            centre: centres[*c].clone(),
+            sites: profiles
+                .iter()
+                .zip(&assignment)
+                .filter(|(_, a)| **a == *c)
+                .map(|(p, _)| p.site.clone())
+                .collect(),
+        })
+        .collect();
+    let members = profiles
+        .iter()
+        .zip(&assignment)
+        .map(|(profile, c)| {
+            let i = used.iter().position(|u| u == c).unwrap_or_default();
+            Membership {
+                site: profile.site.clone(),
+                cluster: clusters[i].id,
+                label: clusters[i].label.clone(),
+                distance: distance(&profile.daily, &clusters[i].centre, config),
+            }
+        })
+        .collect();
+    Ok(Clustering { clusters, members })
+}
+
+/// Write cluster membership as CSV: `site,cluster,label,distance`.
+pub fn write_csv(clustering: &Clustering, path: &str) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_writer(export::create(path)?);
+    wtr.write_record(["site", "cluster", "label", "distance"])?;
+    for member in &clustering.members {
+        wtr.write_record([
+            member.site.clone(),
+            member.cluster.to_string(),
+            member.label.clone(),
+            format!("{:.4}", member.distance),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
@@ -0,0 +1,272 @@
+//! Hourly series and forecasts as Arrow record batches, read from and written
+//! to Parquet (`.parquet`) or Arrow IPC files (any other extension), and
+//! chargers' energy registers read from them.
+//!
+//! A [`Series`] holds its columns in Arrow buffers, so a file's `Int64` or
+//! second-resolution timestamp column and `Float64` value column become the
+//! series' own without a copy, and a series becomes a batch sharing them.
+//! Other types are cast, zoned timestamps converted to local time, and the
+//! batches of a multi-batch file concatenated; those copy.
+
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, AsArray, Float64Array, RecordBatch, TimestampSecondArray};
+use arrow::buffer::ScalarBuffer;
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Float64Type, Int64Type, Schema, TimeUnit};
+use arrow::ipc::reader::FileReader;
+use arrow::ipc::writer::FileWriter;
+use chrono::DateTime;
+use chrono_tz::Tz;
+use parquet::arrow::ArrowWriter;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+use crate::data::Series;
+use crate::forecast::Forecast;
+use crate::register::Reading;
+use crate::source::Readings;
+
+/// Column names used when writing, and looked for first when reading.
+pub const TIMESTAMP_COLUMN: &str = "timestamp";
+pub const VALUE_COLUMN: &str = "value";
+/// The column of charger ids that makes a file hold energy registers.
+pub const CHARGER_COLUMN: &str = "charger";
+
+fn is_parquet(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("parquet"))
+}
+
+fn no_nulls(array: &dyn Array, column: &str) -> Result<(), Box<dyn Error>> {
+    if array.null_count() > 0 {
+        return Err(format!("Column {} has {} null values", column, array.null_count()).into());
+    }
+    Ok(())
+}
+
+/// Local wall-clock seconds, the crate's timestamp convention.
+///
+/// Integer columns and timestamps without a timezone are taken as local
+/// already; timestamps with one are converted to that zone's wall clock.
+fn timestamp_seconds(array: ArrayRef, column: &str) -> Result<ScalarBuffer<i64>, Box<dyn Error>> {
+    no_nulls(array.as_ref(), column)?;
+    let (array, timezone) = match array.data_type().clone() {
+        DataType::Int64 => (array, None),
+        DataType::Timestamp(TimeUnit::Second, timezone) => (array, timezone),
+        DataType::Timestamp(_, timezone) => {
+            let seconds = DataType::Timestamp(TimeUnit::Second, timezone.clone());
+            (cast(&array, &seconds)?, timezone)
+        }
+        other => return Err(format!("Column {} is {}, not a timestamp", column, other).into()),
+    };
+    // Reinterpreting the seconds as plain integers shares the buffer
+    let seconds = cast(&array, &DataType::Int64)?;
+    let seconds = seconds.as_primitive::<Int64Type>().values().clone();
+
+    let Some(timezone) = timezone else {
+        return Ok(seconds);
+    };
+    let timezone: Tz = timezone
+        .parse()
+        .map_err(|e| format!("Column {}: {}", column, e))?;
+    Ok(seconds
+        .iter()
+        .map(|&ts| {
+            let utc = DateTime::from_timestamp(ts, 0).unwrap_or_default();
+            utc.with_timezone(&timezone)
+                .naive_local()
+                .and_utc()
+                .timestamp()
+        })
+        .collect())
+}
+
+fn float_values(array: ArrayRef, column: &str) -> Result<ScalarBuffer<f64>, Box<dyn Error>> {
+    no_nulls(array.as_ref(), column)?;
+    let array = match array.data_type() {
+        DataType::Float64 => array,
+        other if other.is_numeric() => cast(&array, &DataType::Float64)?,
+        other => return Err(format!("Column {} is {}, not numeric", column, other).into()),
+    };
+    Ok(array.as_primitive::<Float64Type>().values().clone())
+}
+
+/// A series from a batch with a timestamp and a numeric value column.
+///
+/// The columns are found by name (`timestamp`, `value`), or else taken to
+/// be the first two.
+pub fn series_from_batch(batch: RecordBatch) -> Result<Series, Box<dyn Error>> {
+    let schema = batch.schema();
+    let index = |name: &str, fallback: usize| schema.index_of(name).unwrap_or(fallback);
+    let (ts_index, value_index) = (index(TIMESTAMP_COLUMN, 0), index(VALUE_COLUMN, 1));
+    if batch.num_columns() < 2 || ts_index == value_index {
+        return Err("Expected a timestamp and a value column".into());
+    }
+    let ts_name = schema.field(ts_index).name().clone();
+    let value_name = schema.field(value_index).name().clone();
+    let (_, mut columns, _) = batch.into_parts();
+    let values = columns.swap_remove(value_index.max(ts_index));
+    let timestamps = columns.swap_remove(value_index.min(ts_index));
+    let (timestamps, values) = if ts_index < value_index {
+        (timestamps, values)
+    } else {
+        (values, timestamps)
+    };
+    drop(columns);
+    Ok(Series::from_buffers(
+        timestamp_seconds(timestamps, &ts_name)?,
+        float_values(values, &value_name)?,
+    ))
+}
+
+/// A `timestamp`, `value` batch sharing the series' buffers.
+pub fn series_to_batch(series: Series) -> Result<RecordBatch, Box<dyn Error>> {
+    let schema = Schema::new(vec![
+        Field::new(
+            TIMESTAMP_COLUMN,
+            DataType::Timestamp(TimeUnit::Second, None),
+            false,
+        ),
+        Field::new(VALUE_COLUMN, DataType::Float64, false),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(TimestampSecondArray::new(series.timestamps, None)),
+        Arc::new(Float64Array::new(series.values, None)),
+    ];
+    Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+}
+
+/// The forecast CSV's columns (`timestamp`, `yhat`, `yhat_lower`, `yhat_upper`) as a batch.
+pub fn forecast_to_batch(forecast: Forecast) -> Result<RecordBatch, Box<dyn Error>> {
+    let value = |name: &str| Field::new(name, DataType::Float64, false);
+    let schema = Schema::new(vec![
+        Field::new(
+            TIMESTAMP_COLUMN,
+            DataType::Timestamp(TimeUnit::Second, None),
+            false,
+        ),
+        value("yhat"),
+        value("yhat_lower"),
+        value("yhat_upper"),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(TimestampSecondArray::new(forecast.timestamps.into(), None)),
+        Arc::new(Float64Array::new(forecast.point.into(), None)),
+        Arc::new(Float64Array::new(forecast.lower.into(), None)),
+        Arc::new(Float64Array::new(forecast.upper.into(), None)),
+    ];
+    Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+}
+
+/// All record batches of a Parquet or Arrow IPC file.
+pub fn read_batches(path: impl AsRef<Path>) -> Result<Vec<RecordBatch>, Box<dyn Error>> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| format!("Could not open {}: {}", path.display(), e))?;
+    let batches = if is_parquet(path) {
+        ParquetRecordBatchReaderBuilder::try_new(file)?
+            .build()?
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        FileReader::try_new(file, None)?.collect::<Result<Vec<_>, _>>()?
+    };
+    Ok(batches)
+}
+
+/// Write one batch as a Parquet or Arrow IPC file.
+pub fn write_batch(path: impl AsRef<Path>, batch: &RecordBatch) -> Result<(), Box<dyn Error>> {
+    let path = path.as_ref();
+    let file =
+        File::create(path).map_err(|e| format!("Could not create {}: {}", path.display(), e))?;
+    if is_parquet(path) {
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), None)?;
+        writer.write(batch)?;
+        writer.close()?;
+    } else {
+        let mut writer = FileWriter::try_new(file, &batch.schema())?;
+        writer.write(batch)?;
+        writer.finish()?;
+    }
+    Ok(())
+}
+
+/// The readings of a Parquet or Arrow IPC file: with a `charger` column,
+/// chargers' energy registers (see [`registers_from_batch`]), or else an
+/// hourly series (see [`series_from_batch`]).
+pub fn read_readings(path: impl AsRef<Path>) -> Result<Readings, Box<dyn Error>> {
+    let batches = read_batches(path)?;
+    let registers = batches
+        .first()
+        .is_some_and(|batch| batch.schema().index_of(CHARGER_COLUMN).is_ok());
+    if !registers {
+        return Ok(Readings::Hourly(series_from_batches(batches)?));
+    }
+    let mut readings = Vec::new();
+    for batch in batches {
+        readings.extend(registers_from_batch(batch)?);
+    }
+    Ok(Readings::Registers(readings))
+}
+
+/// Register readings from a batch with `charger`, `timestamp` and `value` columns.
+pub fn registers_from_batch(batch: RecordBatch) -> Result<Vec<Reading>, Box<dyn Error>> {
+    let schema = batch.schema();
+    let column = |name: &str| -> Result<ArrayRef, Box<dyn Error>> {
+        let index = schema
+            .index_of(name)
+            .map_err(|_| format!("Expected a {} column next to {}", name, CHARGER_COLUMN))?;
+        Ok(Arc::clone(batch.column(index)))
+    };
+    let chargers = cast(&column(CHARGER_COLUMN)?, &DataType::Utf8)?;
+    no_nulls(chargers.as_ref(), CHARGER_COLUMN)?;
+    let timestamps = timestamp_seconds(column(TIMESTAMP_COLUMN)?, TIMESTAMP_COLUMN)?;
+    let values = float_values(column(VALUE_COLUMN)?, VALUE_COLUMN)?;
+    Ok(chargers
+        .as_string::<i32>()
+        .iter()
+        .zip(timestamps.iter().copied())
+        .zip(values.iter().copied())
+        .map(|((charger, timestamp), value)| Reading {
+            charger: charger.unwrap_or_default().to_string(),
+            timestamp,
+            value,
+        })
+        .collect())
+}
+
+/// An hourly series from a Parquet or Arrow IPC file (see [`series_from_batch`]).
+pub fn read_series(path: impl AsRef<Path>) -> Result<Series, Box<dyn Error>> {
+    series_from_batches(read_batches(path)?)
+}
+
+fn series_from_batches(batches: Vec<RecordBatch>) -> Result<Series, Box<dyn Error>> {
+    let mut batches = batches.into_iter();
+    let mut series = match batches.next() {
+        Some(batch) => series_from_batch(batch)?,
+        None => Series::default(),
+    };
+    // A single batch keeps its buffers; more are concatenated
+    let mut rest = batches.peekable();
+    if rest.peek().is_none() {
+        return Ok(series);
+    }
+    let mut timestamps = series.timestamps.to_vec();
+    let mut values = series.values.to_vec();
+    for batch in rest {
+        let more = series_from_batch(batch)?;
+        timestamps.extend_from_slice(&more.timestamps);
+        values.extend_from_slice(&more.values);
+    }
+    series.timestamps = timestamps.into();
+    series.values = values.into();
+    Ok(series)
+}
+
+/// Write a forecast as a Parquet or Arrow IPC file.
+pub fn write_forecast(forecast: &Forecast, path: &str) -> Result<(), Box<dyn Error>> {
+    write_batch(path, &forecast_to_batch(forecast.clone())?)?;
+    eprintln!("Forecast written to {}", path);
+    Ok(())
+}
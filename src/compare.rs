@@ -0,0 +1,253 @@
+//! Multi-site comparison page: one row and one small chart per site.
+
+use std::error::Error;
+use std::fmt::Write;
+use std::path::Path;
+
+use chrono::Utc;
+
+use crate::backtest::BacktestReport;
+use crate::config::Config;
+use crate::data::Series;
+use crate::export;
+use crate::forecast::Forecast;
+use crate::metrics::Metrics;
+use crate::pipeline::{fit_and_forecast, load_sessions, tail};
+use crate::plot;
+use crate::preprocess;
+use crate::report::escape;
+
+/// Headline numbers of one site.
+#[derive(Debug, Clone)]
+pub struct SiteSummary {
+    pub site: String,
+    /// Recent history shown in the site's chart.
+    pub history: Series,
+    pub forecast: Forecast,
+    /// Forecast mean load against the mean of the same number of past hours, in percent.
+    pub growth_percent: f64,
+    /// Share of charger-hours occupied over the recent history (0–1).
+    pub utilization: f64,
+    /// Overall accuracy from the site's last saved backtest.
+    pub error: Option<Metrics>,
+    /// Most points per chart line (see `output.plot_max_points`).
+    pub plot_max_points: usize,
+    /// Label of the site's demand-shape cluster, with `[cluster]` set.
+    pub cluster: Option<String>,
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len().max(1) as f64
+}
+
+/// A site's hourly load and occupancy, loaded but not yet fitted.
+#[derive(Debug, Clone)]
+pub struct SiteHistory {
+    pub history: Series,
+    pub occupancy: Series,
+}
+
+/// Load and resample a site's export.
+pub fn load_site(config: &Config) -> Result<SiteHistory, Box<dyn Error>> {
+    let (sessions, _) = load_sessions(config)?;
+    Ok(SiteHistory {
+        history: config
+            .data
+            .preprocess
+            .apply(preprocess::hourly_load(&sessions))?,
+        occupancy: preprocess::hourly_occupancy(&sessions),
+    })
+}
+
+/// [`load_site`] for every site, in parallel across cores with the `parallel` feature.
+pub fn load_sites(configs: &[Config]) -> Vec<Result<SiteHistory, Box<dyn Error>>> {
+    // Errors cross threads as text
+    let load = |config: &Config| load_site(config).map_err(|e| format!("{}: {}", config.site, e));
+    #[cfg(feature = "parallel")]
+    let loaded: Vec<_> = {
+        use rayon::prelude::*;
+        configs.par_iter().map(load).collect()
+    };
+    #[cfg(not(feature = "parallel"))]
+    let loaded: Vec<_> = configs.iter().map(load).collect();
+    loaded
+        .into_iter()
+        .map(|site| site.map_err(Into::into))
+        .collect()
+}
+
+/// Load, fit and forecast one site, looking back `recent_hours` for history-based figures.
+pub fn summarize_site(config: &Config, recent_hours: usize) -> Result<SiteSummary, Box<dyn Error>> {
+    summarize_history(config, load_site(config)?, recent_hours)
+}
+
+/// [`summarize_site`] of an already loaded site.
+pub fn summarize_history(
+    config: &Config,
+    site: SiteHistory,
+    recent_hours: usize,
+) -> Result<SiteSummary, Box<dyn Error>> {
+    let SiteHistory { history, occupancy } = site;
+    let (_, forecast) = fit_and_forecast(config, &history)?;
+
+    let past = tail(&history, forecast.len());
+    let past_mean = mean(&past.values);
+    let growth_percent = if past_mean > 0.0 {
+        100.0 * (mean(&forecast.point) / past_mean - 1.0)
+    } else {
+        0.0
+    };
+
+    // Without a configured charger count, the busiest hour on record stands in for capacity
+    let recent_occupancy = tail(&occupancy, recent_hours);
+    let chargers = match config.chargers {
+        Some(chargers) => chargers as f64,
+        None => occupancy.values.iter().cloned().fold(0.0, f64::max),
+    };
+    let utilization = if chargers > 0.0 {
+        mean(&recent_occupancy.values) / chargers
+    } else {
+        0.0
+    };
+
+    let error = BacktestReport::load(&config.backtest.output)
+        .ok()
+        .map(|b| b.overall);
+    if error.is_none() {
+        eprintln!(
+            "⚠️  No backtest at {} for {}; error left blank",
+            config.backtest.output, config.site
+        );
+    }
+
+    Ok(SiteSummary {
+        site: config.site.clone(),
+        history: tail(&history, recent_hours),
+        forecast,
+        growth_percent,
+        utilization,
+        error,
+        plot_max_points: config.output.plot_max_points,
+        cluster: None,
+    })
+}
+
+/// 1-based rank of each value, highest first; missing values rank last.
+fn ranks(values: &[Option<f64>]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| {
+        let key = |i: usize| values[i].unwrap_or(f64::NEG_INFINITY);
+        key(b).total_cmp(&key(a))
+    });
+    let mut ranks = vec![0; values.len()];
+    for (rank, i) in order.into_iter().enumerate() {
+        ranks[i] = rank + 1;
+    }
+    ranks
+}
+
+/// Render the comparison page.
+///
+/// Sites are ranked on growth, utilization and sMAPE (higher = more attention
+/// needed) and listed by their average rank, worst first.
+pub fn render_page(sites: &[SiteSummary]) -> Result<String, Box<dyn Error>> {
+    let growth = ranks(
+        &sites
+            .iter()
+            .map(|s| Some(s.growth_percent))
+            .collect::<Vec<_>>(),
+    );
+    let utilization = ranks(
+        &sites
+            .iter()
+            .map(|s| Some(s.utilization))
+            .collect::<Vec<_>>(),
+    );
+    let error = ranks(
+        &sites
+            .iter()
+            .map(|s| s.error.map(|m| m.smape))
+            .collect::<Vec<_>>(),
+    );
+
+    let mut order: Vec<usize> = (0..sites.len()).collect();
+    order.sort_by_key(|&i| growth[i] + utilization[i] + error[i]);
+    let clustered = sites.iter().any(|s| s.cluster.is_some());
+
+    let mut html = String::new();
+    writeln!(
+        html,
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">"
+    )?;
+    writeln!(html, "<title>Site comparison</title>")?;
+    writeln!(
+        html,
+        "<style>body{{font-family:sans-serif;margin:16px}}\
+         table{{border-collapse:collapse}}td,th{{border:1px solid #ccc;padding:4px 8px;text-align:right}}\
+         .grid{{display:flex;flex-wrap:wrap;gap:8px}}</style></head><body>"
+    )?;
+    writeln!(html, "<h1>Site comparison</h1>")?;
+    writeln!(
+        html,
+        "<p>Generated {} — {} sites</p>",
+        Utc::now().format("%Y-%m-%d %H:%M UTC"),
+        sites.len()
+    )?;
+
+    writeln!(html, "<table>")?;
+    writeln!(
+        html,
+        "<tr><th>Site</th><th>Growth</th><th>#</th><th>Utilization</th><th>#</th>\
+         <th>sMAPE</th><th>MAE kW</th><th>#</th><th>Forecast peak kW</th>{}</tr>",
+        if clustered { "<th>Cluster</th>" } else { "" }
+    )?;
+    for &i in &order {
+        let s = &sites[i];
+        let peak = s.forecast.point.iter().cloned().fold(0.0, f64::max);
+        let (smape, mae) = match s.error {
+            Some(m) => (format!("{:.1}%", m.smape), format!("{:.1}", m.mae)),
+            None => ("—".to_string(), "—".to_string()),
+        };
+        writeln!(
+            html,
+            "<tr><td>{}</td><td>{:+.1}%</td><td>{}</td><td>{:.0}%</td><td>{}</td>\
+             <td>{}</td><td>{}</td><td>{}</td><td>{:.1}</td>{}</tr>",
+            escape(&s.site),
+            s.growth_percent,
+            growth[i],
+            s.utilization * 100.0,
+            utilization[i],
+            smape,
+            mae,
+            error[i],
+            peak,
+            match clustered {
+                true => format!("<td>{}</td>", escape(s.cluster.as_deref().unwrap_or("—"))),
+                false => String::new(),
+            }
+        )?;
+    }
+    writeln!(html, "</table>")?;
+
+    writeln!(html, "<h2>Forecasts</h2>\n<div class=\"grid\">")?;
+    for &i in &order {
+        let s = &sites[i];
+        let svg = plot::forecast_svg(
+            &s.history,
+            &s.forecast,
+            &s.site,
+            (420, 240),
+            s.plot_max_points,
+        )?;
+        writeln!(html, "<div>{}</div>", svg)?;
+    }
+    writeln!(html, "</div>\n</body></html>")?;
+    Ok(html)
+}
+
+/// Render and write the comparison page.
+pub fn write_page(sites: &[SiteSummary], path: &Path) -> Result<(), Box<dyn Error>> {
+    export::write_text(path, &render_page(sites)?)?;
+    eprintln!("Site comparison written to {}", path.display());
+    Ok(())
+}
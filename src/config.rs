@@ -0,0 +1,2206 @@
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+
+use crate::migrate::{self, CONFIG_VERSION, MIGRATIONS};
+use crate::preprocess::Pipeline;
+
+/// Top-level configuration, normally loaded from a TOML file.
+///
+/// Every section has defaults matching the original hard-coded setup, so an
+/// empty file (or no file at all) reproduces the classic site forecast.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Schema version the file was written for (see [`crate::migrate`]).
+    pub version: u32,
+    /// Site identifier used in published outputs and messages.
+    pub site: String,
+    /// Number of charge points, for utilization; the busiest observed hour is used when unset.
+    pub chargers: Option<u32>,
+    /// IANA timezone of the site, e.g. `"Europe/Berlin"`, for a DST-aware forecast horizon.
+    pub timezone: Option<Tz>,
+    /// YAML site registry to take the site's timezone, chargers and grid limit from (see `site`).
+    pub registry: Option<String>,
+    pub data: DataConfig,
+    pub model: ModelConfig,
+    pub forecast: ForecastConfig,
+    pub output: OutputConfig,
+    pub depot: Option<DepotConfig>,
+    pub v2g: Option<V2gConfig>,
+    pub alerts: Option<AlertConfig>,
+    pub breaks: Option<BreakConfig>,
+    pub guards: Option<GuardConfig>,
+    pub utilization: Option<UtilizationConfig>,
+    pub ramp: Option<RampConfig>,
+    pub peaks: Option<PeaksConfig>,
+    pub exceedance: Option<ExceedanceConfig>,
+    pub curtailment: Option<CurtailmentConfig>,
+    pub pricing: Option<PricingConfig>,
+    pub weather: Option<WeatherConfig>,
+    pub market: MarketConfig,
+    pub notify: Option<NotifyConfig>,
+    pub mqtt: Option<MqttConfig>,
+    pub postgres: Option<PostgresConfig>,
+    pub influx: Option<InfluxConfig>,
+    /// Outputs every issued forecast fans out to, besides the sections above.
+    pub sinks: Vec<SinkConfig>,
+    pub kafka: Option<KafkaConfig>,
+    pub server: ServerConfig,
+    pub backtest: BacktestConfig,
+    pub batch: BatchConfig,
+    pub diff: DiffConfig,
+    pub report: ReportConfig,
+    /// Grouping sites by demand shape; `compare` adds each site's cluster when set.
+    pub cluster: Option<ClusterConfig>,
+    pub challenger: Option<ChallengerConfig>,
+    pub archive: Option<ArchiveConfig>,
+    /// Append-only JSON Lines log of every issued forecast point.
+    pub forecast_log: Option<ForecastLogConfig>,
+    pub store: Option<StoreConfig>,
+    pub bias_correction: Option<BiasCorrectionConfig>,
+    pub near_term: Option<NearTermConfig>,
+    pub partial_refit: Option<PartialRefitConfig>,
+    pub openadr: Option<OpenAdrConfig>,
+    pub simulate: SimulateConfig,
+    pub synth: SynthConfig,
+    pub seasonality: SeasonalityConfig,
+    /// Per-site overrides: `[sites.<id>]` tables of any of the keys above,
+    /// merged over the rest of the config for that site (see [`Config::for_site`]).
+    pub sites: BTreeMap<String, toml::Table>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            site: "site".to_string(),
+            chargers: None,
+            timezone: None,
+            registry: None,
+            data: DataConfig::default(),
+            model: ModelConfig::default(),
+            forecast: ForecastConfig::default(),
+            output: OutputConfig::default(),
+            depot: None,
+            v2g: None,
+            alerts: None,
+            breaks: None,
+            guards: None,
+            utilization: None,
+            ramp: None,
+            peaks: None,
+            exceedance: None,
+            curtailment: None,
+            pricing: None,
+            weather: None,
+            market: MarketConfig::default(),
+            notify: None,
+            mqtt: None,
+            postgres: None,
+            influx: None,
+            sinks: Vec::new(),
+            kafka: None,
+            server: ServerConfig::default(),
+            backtest: BacktestConfig::default(),
+            batch: BatchConfig::default(),
+            diff: DiffConfig::default(),
+            report: ReportConfig::default(),
+            cluster: None,
+            challenger: None,
+            archive: None,
+            forecast_log: None,
+            store: None,
+            bias_correction: None,
+            near_term: None,
+            partial_refit: None,
+            openadr: None,
+            simulate: SimulateConfig::default(),
+            synth: SynthConfig::default(),
+            seasonality: SeasonalityConfig::default(),
+            sites: BTreeMap::new(),
+        }
+    }
+}
+
+/// Prefix of environment variables that override config values.
+pub const ENV_PREFIX: &str = "EVFORECAST_";
+
+/// Top-level keys of the runtime config: where results go and the services a
+/// run talks to. Everything else is site/model config: what is forecast and how.
+pub const RUNTIME_KEYS: &[&str] = &[
+    "output",
+    "notify",
+    "mqtt",
+    "postgres",
+    "influx",
+    "sinks",
+    "kafka",
+    "server",
+    "store",
+    "archive",
+    "forecast_log",
+    "batch",
+];
+
+/// Which of the two config files a key belongs in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigKind {
+    Site,
+    Runtime,
+}
+
+impl ConfigKind {
+    /// The kind of a top-level key.
+    pub fn of(key: &str) -> Self {
+        match RUNTIME_KEYS.contains(&key) {
+            true => Self::Runtime,
+            false => Self::Site,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Site => "site/model",
+            Self::Runtime => "runtime",
+        }
+    }
+}
+
+impl Config {
+    /// Load a config from a TOML file, then apply `EVFORECAST_*` overrides.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        Self::from_toml(&read_config(path)?, &path.display().to_string())
+    }
+
+    /// Load the site/model config (the defaults when `None`) and the runtime
+    /// config from their own files, then apply `EVFORECAST_*` overrides.
+    ///
+    /// Each file may only hold its own kind of keys (see [`RUNTIME_KEYS`]);
+    /// `[sites.<id>]` overrides may override either.
+    pub fn from_files(site: Option<&Path>, runtime: &Path) -> Result<Self, Box<dyn Error>> {
+        let site = match site {
+            Some(path) => (path.display().to_string(), read_config(path)?),
+            None => ("defaults".to_string(), String::new()),
+        };
+        let runtime = (runtime.display().to_string(), read_config(runtime)?);
+        let (mut table, site_text) = parse_table(&site.1, &site.0)?;
+        check_kind(&table, ConfigKind::Site, &site.0)?;
+        let (runtime_table, runtime_text) = parse_table(&runtime.1, &runtime.0)?;
+        check_kind(&runtime_table, ConfigKind::Runtime, &runtime.0)?;
+        merge(&mut table, runtime_table);
+        apply_env_overrides(&mut table, std::env::vars())?;
+        from_table(table, &[(site.0, site_text), (runtime.0, runtime_text)])
+    }
+
+    /// The defaults with `EVFORECAST_*` overrides applied, for runs without a file.
+    pub fn from_env() -> Result<Self, Box<dyn Error>> {
+        Self::from_toml("", "from environment")
+    }
+
+    /// Parse a config from TOML text, then apply `EVFORECAST_*` overrides.
+    ///
+    /// `source` names the text in error messages.
+    pub fn from_toml(text: &str, source: &str) -> Result<Self, Box<dyn Error>> {
+        let (mut table, text) = parse_table(text, source)?;
+        apply_env_overrides(&mut table, std::env::vars())?;
+        from_table(table, &[(source.to_string(), text)])
+    }
+
+    /// This config for `site`: its `[sites.<site>]` overrides merged over
+    /// the shared keys, with `EVFORECAST_*` overrides applied on top.
+    ///
+    /// Sections merge key by key; any other value, arrays included, is
+    /// replaced. A site without a section gets the shared config.
+    pub fn for_site(&self, site: &str) -> Result<Self, Box<dyn Error>> {
+        let mut table = toml::Table::try_from(self)?;
+        if let Some(overrides) = self.sites.get(site) {
+            merge(&mut table, overrides.clone());
+        }
+        apply_env_overrides(&mut table, std::env::vars())?;
+        table.insert("site".to_string(), toml::Value::String(site.to_string()));
+        let config = table.try_into().map_err(|e: toml::de::Error| {
+            format!(
+                "Invalid config for site {}: {}{}",
+                site,
+                e.message(),
+                suggestion(e.message())
+            )
+        })?;
+        Ok(config)
+    }
+}
+
+fn read_config(path: &Path) -> Result<String, Box<dyn Error>> {
+    Ok(std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read config {}: {}", path.display(), e))?)
+}
+
+/// The table of a config text, migrated to [`CONFIG_VERSION`], and its migrated text.
+fn parse_table(text: &str, source: &str) -> Result<(toml::Table, String), Box<dyn Error>> {
+    let mut doc: toml_edit::DocumentMut = text
+        .parse()
+        .map_err(|e| format!("Invalid config {}: {}", source, e))?;
+    let from = migrate::version(&doc).map_err(|e| format!("Invalid config {}: {}", source, e))?;
+    let changes = migrate::migrate(&mut doc, MIGRATIONS)
+        .map_err(|e| format!("Invalid config {}: {}", source, e))?;
+    if !changes.is_empty() {
+        eprintln!(
+            "⚠️  Config {} is version {}, migrated ({}); `config migrate` updates the file",
+            source,
+            from,
+            changes.join(", ")
+        );
+    }
+    // Unchanged texts are parsed as written, so errors point at the file's own lines
+    let text = match migrate::version(&doc)? == from {
+        true => text.to_string(),
+        false => doc.to_string(),
+    };
+    let table = toml::from_str(&text).map_err(|e| format!("Invalid config {}: {}", source, e))?;
+    Ok((table, text))
+}
+
+/// Keys of `table` that belong in the other kind of config file.
+fn check_kind(table: &toml::Table, kind: ConfigKind, source: &str) -> Result<(), Box<dyn Error>> {
+    let misplaced: Vec<&str> = table
+        .keys()
+        .map(String::as_str)
+        .filter(|key| !["sites", "version"].contains(key) && ConfigKind::of(key) != kind)
+        .collect();
+    if misplaced.is_empty() {
+        return Ok(());
+    }
+    let other = match kind {
+        ConfigKind::Site => ConfigKind::Runtime,
+        ConfigKind::Runtime => ConfigKind::Site,
+    };
+    Err(format!(
+        "Invalid config {}: `{}` {} in the {} config, not the {} one",
+        source,
+        misplaced.join("`, `"),
+        if misplaced.len() == 1 {
+            "belongs"
+        } else {
+            "belong"
+        },
+        other.name(),
+        kind.name()
+    )
+    .into())
+}
+
+/// `table` as a config; errors point at their line in whichever of `sources`
+/// (name, text) has it.
+fn from_table(table: toml::Table, sources: &[(String, String)]) -> Result<Config, Box<dyn Error>> {
+    table.try_into().map_err(|e: toml::de::Error| {
+        // The error has no line once the texts are merged; each text alone has
+        // it, unless an override made the error
+        let located = sources.iter().find_map(|(source, text)| {
+            toml::from_str::<Config>(text)
+                .err()
+                .map(|e| (source.as_str(), e))
+        });
+        let (source, e) = match located {
+            Some((source, e)) => (source.to_string(), e),
+            None => (
+                sources
+                    .iter()
+                    .map(|s| s.0.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" + "),
+                e,
+            ),
+        };
+        format!(
+            "Invalid config {}: {}{}",
+            source,
+            e.to_string().trim_end(),
+            suggestion(e.message())
+        )
+        .into()
+    })
+}
+
+/// A "did you mean `x`?" line for an unknown key or value close to an expected one.
+///
+/// serde names them like "unknown field `horizon`, expected one of `horizon_hours`, ...".
+pub fn suggestion(message: &str) -> String {
+    let quoted: Vec<&str> = message.split('`').skip(1).step_by(2).collect();
+    let Some((given, expected)) = quoted
+        .split_first()
+        .filter(|_| message.starts_with("unknown "))
+    else {
+        return String::new();
+    };
+    let best = expected
+        .iter()
+        .map(|candidate| (strsim::jaro_winkler(given, candidate), candidate))
+        .max_by(|a, b| a.0.total_cmp(&b.0));
+    match best {
+        Some((score, candidate)) if score >= 0.8 => format!("\ndid you mean `{}`?", candidate),
+        _ => String::new(),
+    }
+}
+
+/// Merge `overrides` into `table`, recursing into sections both have.
+fn merge(table: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (table.get_mut(&key), value) {
+            (Some(toml::Value::Table(section)), toml::Value::Table(value)) => merge(section, value),
+            (_, value) => {
+                table.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Set `EVFORECAST_SECTION__KEY=value` variables into the TOML table.
+///
+/// `__` separates nesting levels and names are lowercased, so
+/// `EVFORECAST_FORECAST__HORIZON_HOURS=48` sets `forecast.horizon_hours`.
+/// Values are parsed as TOML (numbers, booleans, arrays, quoted strings) and
+/// taken as plain strings otherwise, or when the key holds a string already
+/// or by default: `EVFORECAST_SITE=042` is the site "042", not 42.
+fn apply_env_overrides(
+    table: &mut toml::Table,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<(), Box<dyn Error>> {
+    let defaults = toml::Table::try_from(Config::default())?;
+    for (name, raw) in vars {
+        let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let path: Vec<String> = key.split("__").map(str::to_lowercase).collect();
+        if path.iter().any(String::is_empty) {
+            return Err(format!("Invalid config override {}: empty key", name).into());
+        }
+        let text = is_string_at(table, &path) || is_string_at(&defaults, &path);
+        let value = match toml::from_str::<toml::Table>(&format!("value = {}", raw))
+            .ok()
+            .and_then(|mut t| t.remove("value"))
+        {
+            Some(value) if value.is_str() || !text => value,
+            _ => toml::Value::String(raw),
+        };
+
+        let (last, parents) = path.split_last().expect("split yields at least one key");
+        let mut target = &mut *table;
+        for part in parents {
+            let entry = target
+                .entry(part.clone())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            target = entry.as_table_mut().ok_or_else(|| {
+                format!(
+                    "Invalid config override {}: {} is not a section",
+                    name, part
+                )
+            })?;
+        }
+        target.insert(last.clone(), value);
+    }
+    Ok(())
+}
+
+/// Whether the value at the key `path` of `table` is a string.
+fn is_string_at(table: &toml::Table, path: &[String]) -> bool {
+    let Some((last, parents)) = path.split_last() else {
+        return false;
+    };
+    parents
+        .iter()
+        .try_fold(table, |table, part| table.get(part)?.as_table())
+        .and_then(|table| table.get(last))
+        .is_some_and(toml::Value::is_str)
+}
+
+/// Where the training data comes from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DataConfig {
+    /// Session export CSV (start time in column 1, energy in Wh in column 7).
+    pub path: String,
+    /// Pull the sessions from a REST endpoint instead of `path`.
+    pub http: Option<HttpSourceConfig>,
+    /// Query sessions or an hourly load from Postgres/Timescale instead of `path`.
+    pub sql: Option<SqlSourceConfig>,
+    /// Minutes between the points of the load series: 60 for hourly, or
+    /// e.g. 5 to forecast the peaks of fast-charging hubs. Has to divide an hour.
+    pub step_minutes: u32,
+    /// What to do with energy flowing back to the grid (vehicle-to-grid export).
+    pub export: Export,
+    /// Fail the run when more than `max_skip_rate` of the rows can't be parsed
+    /// (`--strict`), rather than only warning.
+    pub strict: bool,
+    /// Share of unparseable rows (0..1) a strict run tolerates.
+    pub max_skip_rate: f64,
+    /// Separators and date formats of a session export CSV written in another locale.
+    pub locale: LocaleConfig,
+    /// Which columns of a session export CSV hold what, for exports laid out differently.
+    pub columns: ColumnConfig,
+    /// Columns of a session export CSV holding personal data, e.g. user ids or RFID tags (see [`crate::privacy`]).
+    pub personal: Option<PersonalDataConfig>,
+    /// Read a load series as instantaneous power samples, integrated to interval energy.
+    pub power: Option<PowerConfig>,
+    /// Read a load series as cumulative energy register readings, differenced to interval energy.
+    pub register: Option<RegisterConfig>,
+    /// Steps applied to the hourly load series before fitting.
+    pub preprocess: Pipeline,
+}
+
+impl Default for DataConfig {
+    fn default() -> Self {
+        Self {
+            path: "data/site_data.csv".to_string(),
+            http: None,
+            sql: None,
+            step_minutes: 60,
+            export: Export::Drop,
+            strict: false,
+            max_skip_rate: 0.01,
+            locale: LocaleConfig::default(),
+            columns: ColumnConfig::default(),
+            personal: None,
+            power: None,
+            register: None,
+            preprocess: Pipeline::default(),
+        }
+    }
+}
+
+/// How a session export writes its fields, e.g. `;`-separated with decimal
+/// commas and `31.12.2024 18:30` dates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocaleConfig {
+    pub delimiter: char,
+    pub decimal: char,
+    /// Digit group separator, e.g. `.` in `1.234,56`; none by default.
+    pub thousands: Option<char>,
+    /// chrono formats (e.g. `%d.%m.%Y %H:%M`) tried in order after `YYYY-MM-DD HH:MM`.
+    pub date_formats: Vec<String>,
+}
+
+impl Default for LocaleConfig {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            decimal: '.',
+            thousands: None,
+            date_formats: Vec::new(),
+        }
+    }
+}
+
+/// Columns of a session export, counted from 0; `cpo-charging-forecast
+/// inspect` proposes them for a new export.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColumnConfig {
+    pub start: usize,
+    /// The start column again for an export without stop times.
+    pub stop: usize,
+    pub energy: usize,
+    pub energy_unit: EnergyUnit,
+    /// Site ids of a multi-site export: only the rows of `site` are read.
+    pub site: Option<usize>,
+}
+
+impl Default for ColumnConfig {
+    fn default() -> Self {
+        Self {
+            start: 1,
+            stop: 2,
+            energy: 7,
+            energy_unit: EnergyUnit::Wh,
+            site: None,
+        }
+    }
+}
+
+/// Personal data in a session export, kept out of everything made from it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PersonalDataConfig {
+    /// Columns (from 0) of user ids, RFID tags, card numbers and the like.
+    pub columns: Vec<usize>,
+    /// What `ingest` leaves of them.
+    pub action: PersonalAction,
+    /// Key of the `hash` action, best a secret reference such as `${env:ID_HASH_KEY}`.
+    pub key: Option<String>,
+}
+
+/// What becomes of a personal field in an ingested export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PersonalAction {
+    /// Emptied, keeping the column so `[data.columns]` still reads the copy.
+    #[default]
+    Drop,
+    /// Replaced by a keyed hash: the same id gets the same pseudonym in every export.
+    Hash,
+}
+
+/// Power samples of a load series (see [`crate::power`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerConfig {
+    /// Longest gap between two samples integrated across; longer ones count as unmetered.
+    pub max_gap_minutes: u32,
+    /// CSV of cumulative `timestamp,energy_kwh` register readings to check the integrated energy against.
+    pub register: Option<String>,
+    /// Relative difference from the register's energy (of at least 1 kWh) before a warning.
+    pub tolerance: f64,
+}
+
+/// Handling of negative energy: sessions and loads exporting to the grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Export {
+    /// Sessions with negative energy are dropped and forecasts kept at or above zero.
+    #[default]
+    Drop,
+    /// Import and export are forecast as separate channels, the forecast being their difference.
+    Split,
+    /// The signed net load is forecast as it is, and may go below zero.
+    Net,
+}
+
+/// Unit of energy register readings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnergyUnit {
+    #[default]
+    Wh,
+    Kwh,
+}
+
+/// Cumulative energy registers of a load series (see [`crate::register`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegisterConfig {
+    pub unit: EnergyUnit,
+    /// Reading the counter wraps around to zero at, e.g. `4294967296` for a
+    /// 32-bit counter; unset if it never does.
+    pub rollover: Option<f64>,
+    /// Highest plausible power in kW: a change faster than this is a meter replacement.
+    pub max_kw: f64,
+    /// Longest gap between two readings spread over; longer ones count as unmetered.
+    pub max_gap_minutes: u32,
+}
+
+impl Default for RegisterConfig {
+    fn default() -> Self {
+        Self {
+            unit: EnergyUnit::Wh,
+            rollover: None,
+            max_kw: 1000.0,
+            max_gap_minutes: 180,
+        }
+    }
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            max_gap_minutes: 15,
+            register: None,
+            tolerance: 0.05,
+        }
+    }
+}
+
+/// How requests to the session API authenticate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HttpAuth {
+    #[default]
+    None,
+    /// `Authorization: Bearer <token>`.
+    Bearer,
+    /// The token in the `api_key_header` header.
+    ApiKey,
+}
+
+/// How the session API splits its results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pagination {
+    /// Everything in one response.
+    #[default]
+    None,
+    /// Count up `page_param` from `first_page` until a short or empty page.
+    Page,
+    /// Follow `next_field`: a URL, or a cursor sent back as `cursor_param`.
+    Next,
+}
+
+/// A Postgres query for the site's readings.
+///
+/// Rows of two columns are an hourly load, `(timestamp, kW)`; rows of three
+/// are sessions, `(start, stop, Wh)`. Timestamps may be `TIMESTAMP` (local
+/// wall-clock time) or `TIMESTAMPTZ`; `$1` in the query is the site.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SqlSourceConfig {
+    /// libpq-style connection string, e.g. `host=localhost user=forecast dbname=ev`.
+    pub url: String,
+    pub query: String,
+}
+
+impl Default for SqlSourceConfig {
+    fn default() -> Self {
+        Self {
+            url: "host=localhost user=postgres".to_string(),
+            query: "SELECT ts, kw FROM meter_hourly WHERE site = $1 ORDER BY ts".to_string(),
+        }
+    }
+}
+
+/// A REST endpoint returning sessions as JSON records.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpSourceConfig {
+    pub url: String,
+    /// Query parameters sent with every request, e.g. a site ID or date range.
+    pub query: BTreeMap<String, String>,
+    pub auth: HttpAuth,
+    /// Bearer token or API key; best set as `EVFORECAST_DATA__HTTP__TOKEN`.
+    pub token: Option<String>,
+    pub api_key_header: String,
+    pub pagination: Pagination,
+    pub page_param: String,
+    pub first_page: u64,
+    /// Page size parameter and value, when the API takes one.
+    pub page_size_param: Option<String>,
+    pub page_size: usize,
+    pub next_field: String,
+    pub cursor_param: String,
+    /// Stop after this many requests.
+    pub max_pages: usize,
+    /// Dotted path to the array of records; empty when the response is the array.
+    pub records_field: String,
+    /// Record field paths; Unix seconds, RFC 3339 or local `YYYY-MM-DD HH:MM[:SS]`.
+    pub start_field: String,
+    pub stop_field: Option<String>,
+    pub energy_field: String,
+    /// Factor from the API's energy unit to Wh (1000 for kWh).
+    pub energy_scale: f64,
+}
+
+impl Default for HttpSourceConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            query: BTreeMap::new(),
+            auth: HttpAuth::None,
+            token: None,
+            api_key_header: "X-API-Key".to_string(),
+            pagination: Pagination::None,
+            page_param: "page".to_string(),
+            first_page: 1,
+            page_size_param: None,
+            page_size: 500,
+            next_field: "next".to_string(),
+            cursor_param: "cursor".to_string(),
+            max_pages: 10_000,
+            records_field: "data".to_string(),
+            start_field: "start".to_string(),
+            stop_field: Some("stop".to_string()),
+            energy_field: "energy_wh".to_string(),
+            energy_scale: 1.0,
+        }
+    }
+}
+
+/// Trend shape passed through to Prophet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Growth {
+    Linear,
+    Flat,
+}
+
+/// How seasonal terms combine with the trend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeasonalityMode {
+    Additive,
+    Multiplicative,
+}
+
+/// Forecasting method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Method {
+    Prophet,
+    /// Teunter–Syntetos–Babai intermittent-demand smoothing, for mostly-idle chargers.
+    Tsb,
+    /// Prophet's trend and additive seasonalities by least squares, without Stan; see [`crate::fourier`].
+    Fourier,
+    /// TSB when the share of zero hours exceeds `zero_share_threshold`, Prophet otherwise.
+    Auto,
+}
+
+/// Simpler models tried, in order, when Prophet can't be fitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fallback {
+    /// Repeat the last week (or the last day, with under a week of history).
+    SeasonalNaive,
+    /// Average of each hour of the week over the whole history.
+    WeeklyAverage,
+    /// A [`crate::fourier`] regression, where Stan isn't available or too slow.
+    Fourier,
+}
+
+/// Variance-stabilizing transform of the target before fitting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    None,
+    /// `ln(1 + y)`.
+    Log1p,
+    /// Box-Cox of `1 + y`, with `box_cox_lambda` or an estimated lambda.
+    BoxCox,
+}
+
+/// Model settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelConfig {
+    pub method: Method,
+    /// Fewest hourly points Prophet is fitted on; shorter histories go to `fallbacks`.
+    pub min_history_hours: usize,
+    /// Tried in order when Prophet fails or the history is too short; empty means fail.
+    pub fallbacks: Vec<Fallback>,
+    /// Share of zero-demand hours above which `auto` picks TSB.
+    pub zero_share_threshold: f64,
+    /// TSB smoothing of the demand size when demand occurs.
+    pub tsb_alpha: f64,
+    /// TSB smoothing of the probability that an hour has demand.
+    pub tsb_beta: f64,
+    pub transform: Transform,
+    /// Fixed Box-Cox lambda; estimated by maximum likelihood when unset.
+    pub box_cox_lambda: Option<f64>,
+    pub growth: Growth,
+    pub seasonality_mode: SeasonalityMode,
+    pub daily_seasonality: bool,
+    pub weekly_seasonality: bool,
+    pub yearly_seasonality: bool,
+    /// Fourier terms of the daily seasonality; Prophet's 4 when unset. See `seasonality`.
+    pub daily_fourier_order: Option<u32>,
+    /// Fourier terms of the weekly seasonality; Prophet's 3 when unset.
+    pub weekly_fourier_order: Option<u32>,
+    /// Fourier terms of the yearly seasonality; Prophet's 10 when unset.
+    pub yearly_fourier_order: Option<u32>,
+    /// Width of the uncertainty interval, e.g. 0.8 for P10–P90.
+    pub interval_width: f64,
+    /// Seed of the Stan optimizer; `batch` draws a fresh one to restart a failed fit.
+    pub optimizer_seed: Option<u32>,
+    /// The optimizer Prophet is fitted with; wasmstan when unset.
+    pub optimizer: Option<OptimizerConfig>,
+    /// Lagged demand regressors, in hours (e.g. `[24, 168]`).
+    pub lags: Vec<u32>,
+    /// Rolling-mean regressors: window lengths in hours, each ending at the shortest lag.
+    pub rolling_means: Vec<u32>,
+    /// Calendar regressors for Prophet.
+    pub calendar: CalendarConfig,
+    /// Refit Prophet with extreme hours pulled in, so they can't drag the trend.
+    pub robust: Option<RobustConfig>,
+    /// Fit Prophet again when its optimizer fails or doesn't converge, before the `fallbacks`.
+    pub retry: Option<RetryConfig>,
+    /// Day-ahead prices of the site's bidding zone as the `day_ahead_price` regressor.
+    pub prices: Option<PricesConfig>,
+}
+
+/// Robust Prophet fit, approximating a Huber loss.
+///
+/// Before fitting, hours whose departure from the median of the same hour of
+/// the week (within `window_weeks`) is more than `threshold` robust z-scores,
+/// scaled by the median absolute deviation at that hour of the day, are
+/// pulled in to that bound.
+/// Only the copy Prophet is fitted on changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RobustConfig {
+    pub threshold: f64,
+    /// Weeks before and after an hour that its baseline is taken from.
+    pub window_weeks: usize,
+}
+
+impl Default for RobustConfig {
+    fn default() -> Self {
+        Self {
+            threshold: crate::anomaly::DEFAULT_THRESHOLD,
+            window_weeks: 3,
+        }
+    }
+}
+
+/// The Stan optimizer behind Prophet fits, named by its `type`.
+///
+/// [`crate::forecast::set_optimizer`] overrides it, e.g. with a mock in tests.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum OptimizerConfig {
+    /// Stan compiled to WebAssembly, run in-process; needs the `wasmstan` feature.
+    Wasmstan,
+    /// A Prophet model compiled with cmdstan, run as a child process; needs the `cmdstan` feature.
+    Cmdstan(CmdstanConfig),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CmdstanConfig {
+    /// The compiled `prophet` binary, with the `libtbb` it links in `lib/` next to it.
+    pub prophet_path: String,
+}
+
+/// Prophet fits tried again after the optimizer failed or didn't converge.
+///
+/// First from starting values with Gaussian noise of `jitter` added, on
+/// Prophet's scaled parameters, then, with `simplify`, once more with only
+/// additive daily and weekly seasonality and no regressors.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    pub attempts: u32,
+    pub jitter: f64,
+    pub simplify: bool,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: 2,
+            jitter: 0.1,
+            simplify: true,
+        }
+    }
+}
+
+/// An inclusive range of dates (`YYYY-MM-DD`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DateRange {
+    pub from: String,
+    pub to: String,
+}
+
+/// Calendar dummies passed to Prophet as extra regressors.
+///
+/// Their future values are known, so they are generated for the forecast
+/// horizon automatically. A lighter alternative to full seasonality terms.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CalendarConfig {
+    /// `is_weekend`: Saturday and Sunday.
+    pub weekend: bool,
+    /// `month_2` … `month_12`: one dummy per month, January as the baseline.
+    pub month: bool,
+    /// `is_public_holiday`, for these dates.
+    pub public_holidays: Vec<String>,
+    /// Days before each public holiday with an effect of their own, as
+    /// Prophet's `lower_window`: -1 adds `public_holiday_minus_1` for the day before.
+    pub holiday_lower_window: i32,
+    /// Days after each public holiday, as Prophet's `upper_window`: 1 adds
+    /// `public_holiday_plus_1` for the day after.
+    pub holiday_upper_window: i32,
+    /// `is_bridge_day`: a single working day between a public holiday and a
+    /// weekend or another holiday, e.g. the Friday after a Thursday holiday.
+    pub bridge_days: bool,
+    /// `is_school_holiday`, for these date ranges.
+    pub school_holidays: Vec<DateRange>,
+    /// Public and school holidays of a country or state, added to the dates above.
+    pub region: Option<RegionConfig>,
+}
+
+/// Regional holiday calendars, fetched from an OpenHolidays-compatible API.
+///
+/// Each year is fetched once and cached as JSON under `cache_dir`; delete a
+/// file there to refetch it, or drop in your own for offline sites.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegionConfig {
+    /// ISO 3166-1 country code, e.g. `"DE"`.
+    pub country: String,
+    /// ISO 3166-2 subdivision code, e.g. `"DE-BE"`; nationwide holidays only if unset.
+    pub subdivision: Option<String>,
+    pub public_holidays: bool,
+    pub school_holidays: bool,
+    pub cache_dir: String,
+    pub api_url: String,
+}
+
+impl Default for RegionConfig {
+    fn default() -> Self {
+        Self {
+            country: String::new(),
+            subdivision: None,
+            public_holidays: true,
+            school_holidays: true,
+            cache_dir: "holidays".to_string(),
+            api_url: "https://openholidaysapi.org".to_string(),
+        }
+    }
+}
+
+impl Default for ModelConfig {
+    fn default() -> Self {
+        Self {
+            method: Method::Prophet,
+            min_history_hours: 30,
+            fallbacks: vec![Fallback::SeasonalNaive, Fallback::WeeklyAverage],
+            zero_share_threshold: 0.7,
+            tsb_alpha: 0.1,
+            tsb_beta: 0.05,
+            transform: Transform::None,
+            box_cox_lambda: None,
+            // Linear growth model (captures increasing or decreasing trends)
+            growth: Growth::Linear,
+            // Multiplicative seasonality (captures large fluctuations in demand)
+            seasonality_mode: SeasonalityMode::Multiplicative,
+            // Hourly data: strong daily patterns, weekdays vs. weekends
+            daily_seasonality: true,
+            weekly_seasonality: true,
+            // EV charging demand doesn't follow strict yearly cycles
+            yearly_seasonality: false,
+            daily_fourier_order: None,
+            weekly_fourier_order: None,
+            yearly_fourier_order: None,
+            interval_width: 0.8,
+            optimizer_seed: None,
+            optimizer: None,
+            lags: Vec::new(),
+            rolling_means: Vec::new(),
+            calendar: CalendarConfig::default(),
+            robust: None,
+            retry: None,
+            prices: None,
+        }
+    }
+}
+
+/// Day-ahead market prices, fetched from the ENTSO-E transparency platform.
+///
+/// Each day is fetched once and cached as JSON under `cache_dir`; delete a
+/// file there to refetch it, or drop in your own (e.g. Nord Pool prices) for
+/// zones or sites the API doesn't serve.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PricesConfig {
+    /// EIC code of the bidding zone, e.g. `"10Y1001A1001A82H"` for DE-LU.
+    pub bidding_zone: String,
+    /// Security token of the API; best set as `EVFORECAST_MODEL__PRICES__TOKEN`.
+    /// Never written out with the config, so archived runs replay from the cache.
+    #[serde(skip_serializing)]
+    pub token: Option<String>,
+    /// Local time of the prices; the site's `timezone` when unset, else UTC.
+    pub timezone: Option<Tz>,
+    pub cache_dir: String,
+    pub api_url: String,
+}
+
+impl Default for PricesConfig {
+    fn default() -> Self {
+        Self {
+            bidding_zone: String::new(),
+            token: None,
+            timezone: None,
+            cache_dir: "prices".to_string(),
+            api_url: "https://web-api.tp.entsoe.eu/api".to_string(),
+        }
+    }
+}
+
+/// Forecast horizon settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ForecastConfig {
+    /// Number of hourly steps to forecast.
+    pub horizon_hours: u32,
+}
+
+impl Default for ForecastConfig {
+    fn default() -> Self {
+        // Next 7 days
+        Self { horizon_hours: 168 }
+    }
+}
+
+/// Where results are written.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    pub plot: Option<String>,
+    /// Most points drawn per line in every chart; longer lines are downsampled (0 draws all).
+    pub plot_max_points: usize,
+    pub csv: Option<String>,
+    /// JSON run summary, written at the end of every CLI run (see [`crate::outcome::RunRecord`]).
+    pub summary: Option<String>,
+    /// Forecast as Parquet (`.parquet`) or Arrow IPC; needs the `arrow` feature.
+    pub arrow: Option<String>,
+    /// In-sample fitted values and Prophet's trend and seasonal components per history hour, as CSV.
+    pub decomposition: Option<String>,
+    /// Prophet's trend changepoints with their change in growth rate, as CSV.
+    pub changepoints: Option<String>,
+    /// Chart of the whole history with its significant changepoints.
+    pub changepoint_plot: Option<String>,
+    /// Each forecast hour's trend and what every seasonality and regressor adds to it, as JSON.
+    pub attribution: Option<String>,
+    /// The run's manifest: data, options, model and build it came from, as JSON (see [`crate::manifest`]).
+    pub manifest: Option<String>,
+    /// The fitted model for gateways to predict from, as JSON (see [`crate::artifact`]).
+    pub model: Option<String>,
+    /// Ed25519 key file, made by `model keygen`, that signs `model` into `<model>.sig`.
+    pub model_key: Option<String>,
+    /// Further files the forecast is written to, each in its own format.
+    pub exports: Vec<ExportConfig>,
+    /// Identifiers of the CIM schedule documents (`format = "cim"`).
+    pub cim: CimConfig,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            plot: Some("forecast.png".to_string()),
+            plot_max_points: crate::plot::MAX_POINTS,
+            csv: None,
+            summary: None,
+            arrow: None,
+            decomposition: None,
+            changepoints: None,
+            changepoint_plot: None,
+            attribution: None,
+            manifest: None,
+            model: None,
+            model_key: None,
+            exports: Vec::new(),
+            cim: CimConfig::default(),
+        }
+    }
+}
+
+/// A file format the forecast can be exported in; see [`crate::export::Exporter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+    /// IEC 61968/62325 CIM-style schedule XML.
+    Cim,
+}
+
+/// One `[[output.exports]]` entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportConfig {
+    pub format: ExportFormat,
+    pub path: String,
+}
+
+/// Header fields of CIM schedule documents, as agreed with the receiving DSO.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CimConfig {
+    /// Our party's mRID (e.g. an EIC code).
+    pub sender_mrid: String,
+    /// The DSO's mRID.
+    pub receiver_mrid: String,
+    /// Coding scheme of both party mRIDs; `A01` is EIC.
+    pub coding_scheme: String,
+    pub sender_role: String,
+    pub receiver_role: String,
+    /// Document type; `A65` is a system total load forecast.
+    pub document_type: String,
+    /// Process type; `A01` is day ahead.
+    pub process_type: String,
+    /// Business type of the time series; `A04` is consumption.
+    pub business_type: String,
+    /// Metering point the schedule is for; the site name when unset.
+    pub measurement_point: Option<String>,
+    /// Quantity unit: `KWT` (kW) or `MAW` (MW).
+    pub unit: String,
+    /// Also write the interval bounds, as two more time series.
+    pub bounds: bool,
+}
+
+impl Default for CimConfig {
+    fn default() -> Self {
+        Self {
+            sender_mrid: String::new(),
+            receiver_mrid: String::new(),
+            coding_scheme: "A01".to_string(),
+            sender_role: "A08".to_string(),
+            receiver_role: "A18".to_string(),
+            document_type: "A65".to_string(),
+            process_type: "A01".to_string(),
+            business_type: "A04".to_string(),
+            measurement_point: None,
+            unit: "KWT".to_string(),
+            bounds: false,
+        }
+    }
+}
+
+/// How fleet vehicles draw power while plugged in at the depot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChargingStrategy {
+    /// Full charger power from arrival until the required energy is delivered.
+    #[default]
+    Immediate,
+    /// Constant power spread evenly over the whole dwell time.
+    Spread,
+    /// Full charger power in the cheapest hours of the dwell time, by `depot.prices`.
+    Cheapest,
+}
+
+/// Fleet depot mode settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DepotConfig {
+    /// CSV with one row per scheduled vehicle visit.
+    pub schedule: String,
+    /// Power of each depot charger in kW.
+    pub charger_power_kw: f64,
+    /// SOC to charge to when a row has no explicit required energy.
+    pub target_soc_percent: f64,
+    pub strategy: ChargingStrategy,
+    /// Day-ahead prices the `cheapest` strategy charges by.
+    pub prices: Option<PricesConfig>,
+    /// Output CSV for the combined depot projection.
+    pub output: Option<String>,
+}
+
+impl Default for DepotConfig {
+    fn default() -> Self {
+        Self {
+            schedule: "data/fleet_schedule.csv".to_string(),
+            charger_power_kw: 22.0,
+            target_soc_percent: 100.0,
+            strategy: ChargingStrategy::Immediate,
+            prices: None,
+            output: Some("depot_forecast.csv".to_string()),
+        }
+    }
+}
+
+/// Vehicle-to-grid potential settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct V2gConfig {
+    /// Maximum discharge power per plugged-in vehicle, in kW.
+    pub discharge_limit_kw: f64,
+    /// Share of plugged-in vehicles that are V2G-capable and opted in (0–1).
+    pub participation: f64,
+    /// Number of bidirectional chargers, capping how many vehicles can discharge at once.
+    pub chargers: Option<u32>,
+    /// Output CSV for the V2G capacity series.
+    pub output: Option<String>,
+}
+
+impl Default for V2gConfig {
+    fn default() -> Self {
+        Self {
+            discharge_limit_kw: 11.0,
+            participation: 1.0,
+            chargers: None,
+            output: Some("v2g_capacity.csv".to_string()),
+        }
+    }
+}
+
+/// Capacity utilization KPIs of the forecast (see [`crate::utilization`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UtilizationConfig {
+    /// Installed charging power in kW; the sum of the site's chargers in the registry when unset.
+    pub installed_kw: Option<f64>,
+    /// Share of the installed power counted as running close to full.
+    pub threshold: f64,
+    /// Days of history whose daily peaks the growth trend is fitted on.
+    pub trend_days: usize,
+    /// Hourly utilization of the forecast, as CSV.
+    pub csv: Option<String>,
+    /// The KPIs, as JSON.
+    pub json: Option<String>,
+}
+
+impl Default for UtilizationConfig {
+    fn default() -> Self {
+        Self {
+            installed_kw: None,
+            threshold: 0.8,
+            trend_days: 90,
+            csv: None,
+            json: None,
+        }
+    }
+}
+
+/// A forecast of the step-to-step ramp next to the level (see [`crate::ramp`]).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RampConfig {
+    /// Ramp limit of the grid connection, in kW per step: steps whose ramp
+    /// interval reaches beyond it are a validation warning.
+    pub max_ramp_kw: Option<f64>,
+    /// The ramp forecast, as CSV.
+    pub csv: Option<String>,
+}
+
+/// The forecast's highest hours, for staff and battery controllers (see [`crate::peaks`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PeaksConfig {
+    /// Peak hours listed.
+    pub top: usize,
+    /// Only the first hours of the horizon, e.g. 168 for the coming week; all when unset.
+    pub within_hours: Option<usize>,
+    /// The peaks, as a JSON list.
+    pub json: Option<String>,
+    /// The peaks, as an iCalendar file.
+    pub ics: Option<String>,
+}
+
+impl Default for PeaksConfig {
+    fn default() -> Self {
+        Self {
+            top: 10,
+            within_hours: None,
+            json: None,
+            ics: None,
+        }
+    }
+}
+
+/// Probability-of-exceedance curves of the forecast (see [`crate::exceedance`]).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExceedanceConfig {
+    /// Demand levels in kW, e.g. the connection limit; each gets a curve.
+    pub thresholds_kw: Vec<f64>,
+    /// The probabilities of each step, as CSV.
+    pub csv: Option<String>,
+    /// The curves, as a PNG chart.
+    pub plot: Option<String>,
+}
+
+/// Pre-emptive curtailment sized from the forecast distribution (see [`crate::exceedance`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CurtailmentConfig {
+    /// Demand not to exceed, in kW; `alerts.site_limit_kw` when unset.
+    pub limit_kw: Option<f64>,
+    /// Chance of still going over the limit accepted each hour, e.g. 0.05.
+    pub risk_tolerance: f64,
+    /// The recommendation of each hour, as CSV.
+    pub csv: Option<String>,
+}
+
+impl Default for CurtailmentConfig {
+    fn default() -> Self {
+        Self {
+            limit_kw: None,
+            risk_tolerance: 0.05,
+            csv: None,
+        }
+    }
+}
+
+/// Days of the week a time-of-use rate applies on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Days {
+    #[default]
+    All,
+    /// Monday to Friday.
+    Weekdays,
+    Weekends,
+}
+
+/// An energy price for some hours of the day, e.g. the evening peak.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TouRate {
+    /// First hour of the day the rate applies to (0–23).
+    pub from_hour: u32,
+    /// First hour it no longer does; below `from_hour` wraps past midnight.
+    pub to_hour: u32,
+    /// Per kWh.
+    pub price: f64,
+    #[serde(default)]
+    pub days: Days,
+}
+
+/// Site temperatures, for weather-normalized actuals (see [`crate::weather`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WeatherConfig {
+    /// CSV of `timestamp,temperature` readings in °C, at any resolution; remote paths are downloaded.
+    pub path: String,
+    /// Daily mean temperature below which heating degree days count.
+    pub heating_base_c: f64,
+    /// And above which cooling degree days do.
+    pub cooling_base_c: f64,
+    /// Hourly actual and normalized load, as CSV.
+    pub output: String,
+    /// Monthly energy, actual and normalized, as CSV.
+    pub monthly: Option<String>,
+}
+
+impl Default for WeatherConfig {
+    fn default() -> Self {
+        Self {
+            path: "weather.csv".to_string(),
+            heating_base_c: 15.0,
+            cooling_base_c: 22.0,
+            output: "normalized.csv".to_string(),
+            monthly: None,
+        }
+    }
+}
+
+/// What a site bills, for the `revenue` forecast (see [`crate::revenue`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PricingConfig {
+    /// Only printed and written along with the amounts.
+    pub currency: String,
+    /// Per kWh, in the hours no `tou` rate covers.
+    pub energy_price: f64,
+    /// Time-of-use rates; the first one covering an hour applies.
+    pub tou: Vec<TouRate>,
+    /// Per hour a vehicle stays plugged in after it could have finished charging.
+    pub idle_fee_per_hour: f64,
+    /// Overstay that is free of charge.
+    pub idle_grace_minutes: f64,
+    /// Power sessions are assumed to charge at, to tell when they could have finished.
+    pub charging_power_kw: f64,
+    /// The site's hourly revenue forecast, as CSV.
+    pub output: Option<String>,
+    /// Revenue per site and month with the total over all sites, as CSV; taken
+    /// from the config `revenue` runs with, not from the sites' own.
+    pub monthly: Option<String>,
+}
+
+impl Default for PricingConfig {
+    fn default() -> Self {
+        Self {
+            currency: "EUR".to_string(),
+            energy_price: 0.0,
+            tou: Vec::new(),
+            idle_fee_per_hour: 0.0,
+            idle_grace_minutes: 0.0,
+            charging_power_kw: 11.0,
+            output: None,
+            monthly: Some("revenue_monthly.csv".to_string()),
+        }
+    }
+}
+
+/// Which electricity-market gate the `market` command forecasts for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    /// The whole next delivery day, before the gate on the day before.
+    #[default]
+    DayAhead,
+    /// The next few hours past the lead time, reissued every `intraday_hours`.
+    Intraday,
+}
+
+/// Forecasts aligned to market gates, for imbalance and trading (see [`crate::market`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MarketConfig {
+    pub preset: Preset,
+    /// Length of a market time unit; divides the hour.
+    pub step_minutes: u32,
+    /// Local hour on the day before delivery the day-ahead gate closes.
+    pub gate_hour: u32,
+    /// Hours each intraday issue covers.
+    pub intraday_hours: u32,
+    /// Intraday gate closure before delivery.
+    pub lead_minutes: u32,
+    /// The issue's schedule, as CSV; compared with on the next issue.
+    pub output: String,
+    /// Only the time units that are new or changed since the previous issue, as CSV.
+    pub delta: Option<String>,
+    /// Changes up to this are left out of `delta`.
+    pub delta_tolerance_kw: f64,
+}
+
+impl Default for MarketConfig {
+    fn default() -> Self {
+        Self {
+            preset: Preset::DayAhead,
+            step_minutes: 15,
+            gate_hour: 12,
+            intraday_hours: 4,
+            lead_minutes: 60,
+            output: "market.csv".to_string(),
+            delta: None,
+            delta_tolerance_kw: 0.1,
+        }
+    }
+}
+
+/// OpenADR 3 demand-response events applied to the forecast (see [`crate::openadr`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OpenAdrConfig {
+    /// Event JSON files, one per event id, in a directory per site.
+    pub events_dir: String,
+    /// Share of the load shed at each `SIMPLE` level, from level 0.
+    pub shed_per_level: Vec<f64>,
+    /// Price `PRICE` signals are compared with; they're ignored when unset.
+    pub reference_price: Option<f64>,
+    /// Relative change in demand per relative change in price.
+    pub price_elasticity: f64,
+}
+
+impl Default for OpenAdrConfig {
+    fn default() -> Self {
+        Self {
+            events_dir: "openadr".to_string(),
+            shed_per_level: vec![0.0, 0.1, 0.25, 0.5],
+            reference_price: None,
+            price_elasticity: -0.3,
+        }
+    }
+}
+
+/// Grid-constraint alerting.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertConfig {
+    /// Site connection limit in kW. Hours whose upper bound exceeds it are flagged.
+    pub site_limit_kw: Option<f64>,
+    /// Write the violations as JSON to this path.
+    pub json: Option<String>,
+    /// POST the violations as JSON to this URL.
+    pub webhook: Option<String>,
+}
+
+/// Structural break alerts: watch the recent hours for a lasting level shift
+/// against the weeks before (see [`crate::breaks`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BreakConfig {
+    /// Days of recent history watched for a break.
+    pub window_days: u32,
+    /// Days before them the usual weekly pattern is taken from.
+    pub baseline_days: u32,
+    /// CUSUM slack, in standard deviations of the baseline's daily residuals:
+    /// days off by less never add up to an alert.
+    pub drift: f64,
+    /// CUSUM alarm level, in the same units; higher means fewer, later alerts.
+    pub threshold: f64,
+    /// Write the break, if any, as JSON to this path.
+    pub json: Option<String>,
+    /// POST each new break as JSON to this URL.
+    pub webhook: Option<String>,
+}
+
+impl Default for BreakConfig {
+    fn default() -> Self {
+        Self {
+            window_days: 14,
+            baseline_days: 28,
+            drift: 0.5,
+            threshold: 5.0,
+            json: None,
+            webhook: None,
+        }
+    }
+}
+
+/// Data guards checked before forecasting: stale or patchy data fails the
+/// run rather than giving a forecast nobody should trade on (see [`crate::guards`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GuardConfig {
+    /// Hours the last observation may be behind the current time.
+    pub max_age_hours: u32,
+    /// Share (0..1) of the recent window that may be missing.
+    pub max_missing_share: f64,
+    /// Hours before the current time the missing share is measured over.
+    pub window_hours: u32,
+}
+
+impl Default for GuardConfig {
+    fn default() -> Self {
+        Self {
+            max_age_hours: 48,
+            max_missing_share: 0.2,
+            window_hours: 30 * 24,
+        }
+    }
+}
+
+/// Payload flavour for the run notification webhook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    Slack,
+    Teams,
+    /// Plain JSON with every summary field.
+    #[default]
+    Generic,
+}
+
+/// Post a forecast summary after each run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    pub webhook: String,
+    pub format: WebhookFormat,
+    /// Name shown in the message title, defaulting to the site id.
+    pub site_name: Option<String>,
+    /// How many hours of recent history to scan for anomalies.
+    pub anomaly_lookback_hours: usize,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            webhook: String::new(),
+            format: WebhookFormat::Generic,
+            site_name: None,
+            anomaly_lookback_hours: 24 * 7,
+        }
+    }
+}
+
+/// Publish forecasts to an MQTT broker.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Topic for the full forecast series (JSON).
+    pub topic: String,
+    /// Optional topic for just the next hour's value, for controllers that
+    /// only care about the immediate setpoint.
+    pub next_hour_topic: Option<String>,
+    /// 0 (at most once) or 1 (at least once).
+    pub qos: u8,
+    /// Keep the last forecast on the broker for late subscribers.
+    pub retain: bool,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "cpo-charging-forecast".to_string(),
+            username: None,
+            password: None,
+            topic: "ev-charging/forecast".to_string(),
+            next_hour_topic: None,
+            qos: 1,
+            retain: true,
+        }
+    }
+}
+
+/// Upsert forecast rows into Postgres (or TimescaleDB).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PostgresConfig {
+    /// libpq-style connection string, e.g. `host=localhost user=forecast dbname=ev`.
+    pub url: String,
+    pub table: String,
+    /// Create the table (and hypertable, with `timescale`) if it doesn't exist.
+    pub create_table: bool,
+    pub timescale: bool,
+}
+
+impl Default for PostgresConfig {
+    fn default() -> Self {
+        Self {
+            url: "host=localhost user=postgres".to_string(),
+            table: "forecasts".to_string(),
+            create_table: true,
+            timescale: false,
+        }
+    }
+}
+
+/// Write forecast points to InfluxDB (v2 HTTP API).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InfluxConfig {
+    pub url: String,
+    pub org: String,
+    pub bucket: String,
+    pub token: Option<String>,
+    pub measurement: String,
+}
+
+impl Default for InfluxConfig {
+    fn default() -> Self {
+        Self {
+            url: "http://localhost:8086".to_string(),
+            org: String::new(),
+            bucket: "ev-charging".to_string(),
+            token: None,
+            measurement: "forecast".to_string(),
+        }
+    }
+}
+
+/// One `[[sinks]]` output, named by its `type`; see [`crate::sink`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SinkConfig {
+    File(ExportConfig),
+    Stdout(StdoutSinkConfig),
+    Postgres(PostgresConfig),
+    Influx(InfluxConfig),
+    Mqtt(MqttConfig),
+    Webhook(WebhookSinkConfig),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StdoutSinkConfig {
+    pub format: ExportFormat,
+}
+
+/// POST each forecast as JSON (the `json` export format).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookSinkConfig {
+    pub url: String,
+}
+
+/// How Kafka message payloads are decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageFormat {
+    /// One JSON object per message.
+    #[default]
+    Json,
+    /// One CSV line per message, with the columns named in `csv_columns`.
+    Csv,
+}
+
+/// Live meter values from a Kafka topic (`cpo-charging-forecast stream`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KafkaConfig {
+    pub brokers: Vec<String>,
+    pub topic: String,
+    /// Consumer group; its committed offsets survive restarts.
+    pub group: String,
+    pub format: MessageFormat,
+    /// Column names of CSV messages, in order.
+    pub csv_columns: Vec<String>,
+    pub site_field: String,
+    /// Unix seconds, `YYYY-MM-DD HH:MM[:SS]` local time, or RFC 3339.
+    pub timestamp_field: String,
+    /// Power reading in kW.
+    pub power_field: String,
+    /// With per-connector readings, the site's load is the sum of the connectors' hourly means.
+    pub connector_field: Option<String>,
+    /// Re-forecast a site once this many new hours are complete.
+    pub refit_after_hours: usize,
+    /// Hours of history kept per site.
+    pub window_hours: usize,
+    /// Directory for one forecast CSV per site, `<site>.csv`. The file
+    /// outputs of `[[output.exports]]` and `[[sinks]]`, which every site
+    /// would write over, are refused.
+    pub output_dir: Option<String>,
+}
+
+impl Default for KafkaConfig {
+    fn default() -> Self {
+        Self {
+            brokers: vec!["localhost:9092".to_string()],
+            topic: "meter-values".to_string(),
+            group: "cpo-charging-forecast".to_string(),
+            format: MessageFormat::Json,
+            csv_columns: vec![
+                "site".to_string(),
+                "timestamp".to_string(),
+                "power_kw".to_string(),
+            ],
+            site_field: "site".to_string(),
+            timestamp_field: "timestamp".to_string(),
+            power_field: "power_kw".to_string(),
+            connector_field: None,
+            refit_after_hours: 6,
+            window_hours: 24 * 365,
+            output_dir: None,
+        }
+    }
+}
+
+/// HTTP server mode settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: String,
+    /// Reload the data and refit this often; 0 keeps the startup forecast.
+    pub refresh_minutes: u64,
+    /// Customers served from this deployment, by tenant id. When set, every
+    /// request needs one of a tenant's API keys or a JWT naming the tenant,
+    /// and sees only the `[sites."<tenant>/<site>"]` sections of that tenant.
+    pub tenants: BTreeMap<String, TenantConfig>,
+    /// HMAC secret of HS256 JWTs whose `tenant` claim names the tenant.
+    pub jwt_secret: Option<String>,
+    /// Fits run at once, for refreshes and `POST /fits`.
+    pub fit_workers: usize,
+    /// Fits waiting beyond the running ones; more get HTTP 429.
+    pub max_queued_fits: usize,
+}
+
+impl ServerConfig {
+    /// Fail on tenant ids that can't prefix a `<tenant>/<site>` id: empty, or
+    /// holding a `/` that would make such ids ambiguous.
+    pub fn check_tenants(&self) -> Result<(), Box<dyn Error>> {
+        match self
+            .tenants
+            .keys()
+            .find(|id| id.is_empty() || id.contains('/'))
+        {
+            Some(id) => Err(format!(
+                "Invalid tenant id {:?} in [server.tenants]: tenant ids prefix `<tenant>/<site>` and can't be empty or hold a `/`",
+                id
+            )
+            .into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: "127.0.0.1:8080".to_string(),
+            refresh_minutes: 60,
+            tenants: BTreeMap::new(),
+            jwt_secret: None,
+            fit_workers: 2,
+            max_queued_fits: 20,
+        }
+    }
+}
+
+/// One customer of a multi-tenant server.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TenantConfig {
+    /// Sent as `Authorization: Bearer <key>` or `X-API-Key: <key>`.
+    pub api_keys: Vec<String>,
+}
+
+/// Rolling-origin backtest settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BacktestConfig {
+    /// Hours forecast per fold; defaults to the forecast horizon.
+    pub holdout_hours: Option<u32>,
+    pub folds: usize,
+    /// How far apart consecutive fold origins are.
+    pub step_hours: usize,
+    /// Where the result is saved (and where `report` looks for it).
+    pub output: String,
+    /// Error by lead time (1 h ahead … holdout) as CSV.
+    pub lead_csv: Option<String>,
+    /// Error by hour of day as CSV.
+    pub hour_csv: Option<String>,
+    /// Chart of error against lead time.
+    pub lead_plot: Option<String>,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        Self {
+            holdout_hours: None,
+            folds: 3,
+            step_hours: 168,
+            output: "backtest.json".to_string(),
+            lead_csv: Some("backtest_by_lead.csv".to_string()),
+            hour_csv: Some("backtest_by_hour.csv".to_string()),
+            lead_plot: Some("backtest_by_lead.png".to_string()),
+        }
+    }
+}
+
+/// Settings of `batch`, which forecasts every site in turn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BatchConfig {
+    /// Extra attempts at a site after a transient failure.
+    pub retries: u32,
+    /// Wait before the first retry; it doubles on each further one, ±50% jitter.
+    pub retry_delay_seconds: f64,
+    /// Succeeded and failed sites, as JSON.
+    pub report: Option<String>,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            retries: 2,
+            retry_delay_seconds: 10.0,
+            report: Some("batch_report.json".to_string()),
+        }
+    }
+}
+
+/// Settings of `diff`, which compares two forecasts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiffConfig {
+    /// Largest hourly changes listed.
+    pub top: usize,
+    /// Any hour changing by more than this many kW is a validation warning.
+    pub max_change_kw: Option<f64>,
+    /// So is a relative change in energy over the common hours above this, e.g. `0.2`.
+    pub max_energy_change: Option<f64>,
+    /// The report as JSON.
+    pub report: Option<String>,
+}
+
+impl Default for DiffConfig {
+    fn default() -> Self {
+        Self {
+            top: 10,
+            max_change_kw: None,
+            max_energy_change: None,
+            report: None,
+        }
+    }
+}
+
+/// How profiles are compared when clustering sites (see [`crate::cluster`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfileDistance {
+    /// Hour by hour: k-means on the curves.
+    #[default]
+    Euclidean,
+    /// Dynamic time warping, so a peak an hour or two later still matches: k-medoids.
+    Dtw,
+}
+
+/// Settings of `cluster`, which groups sites by their daily demand profile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClusterConfig {
+    /// Clusters, at most one per site.
+    pub k: usize,
+    pub distance: ProfileDistance,
+    /// Hours a DTW match may shift by.
+    pub window_hours: usize,
+    /// Membership CSV.
+    pub output: String,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            k: 3,
+            distance: ProfileDistance::Euclidean,
+            window_hours: 2,
+            output: "clusters.csv".to_string(),
+        }
+    }
+}
+
+/// Site report settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReportConfig {
+    /// Output file; `.md` gives Markdown, anything else HTML.
+    pub path: String,
+    /// How many hours of recent actuals to scan for anomalies.
+    pub anomaly_lookback_hours: usize,
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            path: "report.html".to_string(),
+            anomaly_lookback_hours: 24 * 7,
+        }
+    }
+}
+
+/// A challenger model run in shadow mode next to the production (`[model]`) one.
+///
+/// Both forecasts are logged on every `forecast` run; `shadow` scores them
+/// against the actuals that have come in since.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChallengerConfig {
+    pub model: ModelConfig,
+    /// JSON-lines log of paired champion/challenger forecasts.
+    pub log: String,
+    /// Only forecast hours within this many days of the latest actual count.
+    pub window_days: u32,
+}
+
+impl Default for ChallengerConfig {
+    fn default() -> Self {
+        Self {
+            model: ModelConfig::default(),
+            log: "shadow.jsonl".to_string(),
+            window_days: 14,
+        }
+    }
+}
+
+/// Forecast archive, an audit trail of every issued forecast.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArchiveConfig {
+    /// Root directory; forecasts are stored as `<dir>/<site>/<run id>.json`.
+    pub dir: String,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            dir: "archive".to_string(),
+        }
+    }
+}
+
+/// Forecast log (see [`crate::forecast_log`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ForecastLogConfig {
+    /// Local JSON Lines file, appended to.
+    pub path: String,
+    /// Size the log is rotated at, in bytes.
+    pub max_bytes: u64,
+    /// Rotated files kept, `<path>.1` the newest.
+    pub keep: usize,
+}
+
+impl Default for ForecastLogConfig {
+    fn default() -> Self {
+        Self {
+            path: "forecasts.jsonl".to_string(),
+            max_bytes: 64 << 20,
+            keep: 10,
+        }
+    }
+}
+
+/// SQLite project store: registered sites and their configs, ingestion
+/// watermarks, fitted models and recent forecasts (see `store`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StoreConfig {
+    /// Database file, created on first use.
+    pub path: String,
+    /// Forecasts kept per site; older ones are deleted as new ones come in.
+    pub keep_forecasts: usize,
+    /// Past hours changing by more than this since the previous run count as revised.
+    pub revision_tolerance_kw: f64,
+    /// Revised hours found by each run, as JSON (see [`crate::audit::RevisionReport`]).
+    pub revisions: Option<String>,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        Self {
+            path: "project.db".to_string(),
+            keep_forecasts: 30,
+            revision_tolerance_kw: 0.01,
+            revisions: None,
+        }
+    }
+}
+
+/// Per weekday/hour bias correction learned from recent residuals.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BiasCorrectionConfig {
+    /// Scale forecasts by actual/fitted (multiplicative) or shift them by the mean residual (additive).
+    pub mode: SeasonalityMode,
+    /// Hours of recent history the residuals are taken from.
+    pub lookback_hours: usize,
+    /// Largest relative change applied to any hour (0.3 = ±30%).
+    pub max_adjustment: f64,
+}
+
+impl Default for BiasCorrectionConfig {
+    fn default() -> Self {
+        Self {
+            mode: SeasonalityMode::Multiplicative,
+            lookback_hours: 24 * 28,
+            max_adjustment: 0.3,
+        }
+    }
+}
+
+/// Blending the first forecast hours with a short-term autoregressive model
+/// of the latest readings (see [`crate::nowcast`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NearTermConfig {
+    /// Hours of lead time blended, at any step; the short-term model's weight falls linearly to 0 after them.
+    pub hours: usize,
+    /// Past hours each short-term prediction is made from.
+    pub lags: usize,
+    /// Hours of recent history the short-term model is fitted on.
+    pub lookback_hours: usize,
+}
+
+impl Default for NearTermConfig {
+    fn default() -> Self {
+        Self {
+            hours: 6,
+            lags: 3,
+            lookback_hours: 24 * 14,
+        }
+    }
+}
+
+/// What a run fits when `[partial_refit]` is configured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RefitScope {
+    /// Everything, keeping what partial refits start from.
+    Full,
+    /// The seasonal and regressor coefficients, on the last full fit's trend.
+    Seasonality,
+    /// The trend, under the last full fit's seasonal and regressor terms.
+    Trend,
+}
+
+/// Cheap updates between full refits, on recent data (see [`crate::partial`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PartialRefitConfig {
+    pub refit: RefitScope,
+    /// Where full fits keep their trend and terms for the partial refits after them.
+    pub state: String,
+    /// Hours of recent history a partial refit is fitted on.
+    pub lookback_hours: usize,
+    /// Hours after a full fit that partial refits can still start from.
+    pub max_age_hours: usize,
+}
+
+impl Default for PartialRefitConfig {
+    fn default() -> Self {
+        Self {
+            refit: RefitScope::Full,
+            state: "partial_refit.csv".to_string(),
+            lookback_hours: 24 * 14,
+            max_age_hours: 24 * 7,
+        }
+    }
+}
+
+/// Scenario sampling (`simulate`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimulateConfig {
+    /// Number of sample paths.
+    pub samples: usize,
+    /// Fixed RNG seed for reproducible scenarios; random when unset.
+    pub seed: Option<u64>,
+    /// Hours of recent residuals used to estimate their hour-to-hour correlation.
+    pub residual_hours: usize,
+    /// Wide CSV: one row per hour, one column per path.
+    pub output: String,
+}
+
+impl Default for SimulateConfig {
+    fn default() -> Self {
+        Self {
+            samples: 100,
+            seed: None,
+            residual_hours: 24 * 28,
+            output: "scenarios.csv".to_string(),
+        }
+    }
+}
+
+/// Synthetic session generation (`synth`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SynthConfig {
+    /// First day (`YYYY-MM-DD`).
+    pub start: String,
+    pub days: u32,
+    pub seed: u64,
+    /// Mean sessions on a weekday at the start, before noise.
+    pub sessions_per_day: f64,
+    /// Relative growth of the session rate per year, e.g. 0.3 for +30 %.
+    pub growth_per_year: f64,
+    /// Weekend session rate relative to weekdays.
+    pub weekend_factor: f64,
+    pub morning_peak_hour: u32,
+    pub evening_peak_hour: u32,
+    /// Coefficient of variation of the day-to-day rate; 0 for none.
+    pub noise: f64,
+    /// Chance per day that an outage starts.
+    pub outage_probability: f64,
+    /// Longest outage, in hours; lengths are uniform up to this.
+    pub max_outage_hours: u32,
+    pub chargers: u32,
+    pub charger_kw: f64,
+    pub mean_duration_minutes: f64,
+    /// Session export CSV to write.
+    pub output: String,
+}
+
+impl Default for SynthConfig {
+    fn default() -> Self {
+        Self {
+            start: "2024-01-01".to_string(),
+            days: 182,
+            seed: 42,
+            sessions_per_day: 30.0,
+            growth_per_year: 0.3,
+            weekend_factor: 0.6,
+            morning_peak_hour: 8,
+            evening_peak_hour: 18,
+            noise: 0.15,
+            outage_probability: 0.02,
+            max_outage_hours: 24,
+            chargers: 8,
+            charger_kw: 150.0,
+            mean_duration_minutes: 35.0,
+            output: "synthetic.csv".to_string(),
+        }
+    }
+}
+
+/// Seasonality detection (`seasonality`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SeasonalityConfig {
+    /// Autocorrelation one period apart from which a seasonality is recommended.
+    pub min_autocorrelation: f64,
+    /// Share of the seasonal profile's variance its Fourier terms have to carry.
+    pub variance_share: f64,
+}
+
+impl Default for SeasonalityConfig {
+    fn default() -> Self {
+        Self {
+            min_autocorrelation: 0.3,
+            variance_share: 0.95,
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Convergence checks on Prophet's optimizer.
+//!
+//! Stan's L-BFGS runs inside the wasmstan runtime, which keeps its iteration
+//! log to itself, so what a fit reports is worked out from where it ended up:
+//! the log posterior Stan maximized and how well the fit follows the history.
+//! A fit that clearly didn't converge (parameters that aren't finite, that
+//! never left their initial values, or that fit the history worse than a flat
+//! line at its mean) fails instead of forecasting, so that the model's
+//! `fallbacks`, or `batch` restarting from a fresh seed, take over.
+
+use augurs::prophet::optimizer::{Data, InitialParams, OptimizedParams};
+use serde::{Deserialize, Serialize};
+
+/// How Prophet's optimizer ended, on Prophet's scaled values.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OptimizerDiagnostics {
+    /// Wall-clock seconds in the optimizer.
+    pub seconds: f64,
+    /// Log posterior at the optimum, without Stan's constant terms.
+    pub log_prob: f64,
+    /// Root mean square of the in-sample residuals.
+    pub residual_rms: f64,
+    /// Standard deviation of the history: the residuals of a flat line at its mean.
+    pub spread: f64,
+    pub sigma_obs: f64,
+    /// Whether the optimizer moved off its initial values.
+    pub moved: bool,
+    /// Why the fit didn't converge; empty when it did.
+    pub problems: Vec<String>,
+}
+
+impl OptimizerDiagnostics {
+    pub fn converged(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// One line for logs.
+    pub fn summary(&self) -> String {
+        let mut line = format!(
+            "Optimizer: log-prob {:.1}, residual RMS {:.3} against a spread of {:.3}, {:.2}s",
+            self.log_prob, self.residual_rms, self.spread, self.seconds
+        );
+        if !self.converged() {
+            line.push_str(&format!(", not converged: {}", self.problems.join("; ")));
+        }
+        line
+    }
+}
+
+/// Diagnose the optimum `params` that `seconds` of optimizing found from `init` on `data`.
+pub fn diagnose(
+    init: &InitialParams,
+    data: &Data,
+    params: &OptimizedParams,
+    seconds: f64,
+) -> OptimizerDiagnostics {
+    let (n, k) = (data.y.len(), data.K.max(0) as usize);
+    let sigma = *params.sigma_obs;
+    // y ~ normal(trend · (1 + X·(s_m ∘ β)) + X·(s_a ∘ β), sigma_obs)
+    let residuals: Vec<f64> = (0..n)
+        .map(|i| {
+            let row = &data.X[i * k..(i + 1) * k];
+            let term = |indicator: &[i32]| -> f64 {
+                (0..k)
+                    .map(|j| row[j] * indicator[j] as f64 * params.beta[j])
+                    .sum()
+            };
+            let trend = params.trend.get(i).copied().unwrap_or(f64::NAN);
+            data.y[i] - (trend * (1.0 + term(&data.s_m)) + term(&data.s_a))
+        })
+        .collect();
+    let squares: f64 = residuals.iter().map(|r| r * r).sum();
+    let mean = data.y.iter().sum::<f64>() / n.max(1) as f64;
+    let spread = (data.y.iter().map(|y| (y - mean).powi(2)).sum::<f64>() / n.max(1) as f64).sqrt();
+    let residual_rms = (squares / n.max(1) as f64).sqrt();
+
+    // Prophet's Stan model: k, m ~ N(0, 5), delta ~ Laplace(0, tau), sigma_obs ~ N(0, 0.5), beta ~ N(0, sigmas)
+    let normal = |x: f64, scale: f64| -0.5 * (x / scale).powi(2);
+    let log_prob = normal(params.k, 5.0) + normal(params.m, 5.0) + normal(sigma, 0.5)
+        - params.delta.iter().map(|d| d.abs()).sum::<f64>() / *data.tau
+        + params
+            .beta
+            .iter()
+            .zip(&data.sigmas)
+            .map(|(b, s)| normal(*b, **s))
+            .sum::<f64>()
+        - n as f64 * sigma.ln()
+        - squares / (2.0 * sigma * sigma);
+
+    let moved = params.k != init.k
+        || params.m != init.m
+        || sigma != *init.sigma_obs
+        || params.delta != init.delta
+        || params.beta != init.beta;
+    let finite = [params.k, params.m, sigma, log_prob]
+        .iter()
+        .chain(&params.delta)
+        .chain(&params.beta)
+        .all(|v| v.is_finite());
+    let mut problems = Vec::new();
+    if !finite {
+        problems.push("the optimizer ended on parameters that aren't finite".to_string());
+    } else if !moved {
+        problems.push("the optimizer never moved off its initial values".to_string());
+    } else if residual_rms > spread + 1e-6 {
+        // The tolerance is for a history that hardly varies, scaled to at most 1
+        problems.push(format!(
+            "it fits the history worse than a flat line at its mean (residual RMS {:.3} against {:.3})",
+            residual_rms, spread
+        ));
+    }
+    OptimizerDiagnostics {
+        seconds,
+        log_prob,
+        residual_rms,
+        spread,
+        sigma_obs: sigma,
+        moved,
+        problems,
+    }
+}
@@ -0,0 +1,653 @@
+use arrow_buffer::ScalarBuffer;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use chrono_tz::Tz;
+use csv::{ByteRecord, ReaderBuilder};
+use serde::Serialize;
+use std::error::Error;
+use std::io::Read;
+use std::ops::Range;
+use std::path::Path;
+
+/// Path standing for stdin when reading and stdout when writing.
+pub const STDIO: &str = "-";
+
+/// Timestamp format used by the site exports and our own CSV outputs.
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// One charging session from the site export.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Session {
+    /// Plug-in time (UNIX seconds).
+    pub start: i64,
+    /// Plug-out time (UNIX seconds). Equal to `start` if the export had none.
+    pub stop: i64,
+    /// Delivered energy in Wh.
+    pub energy_wh: f64,
+}
+
+/// A regularly spaced time series (UNIX seconds, one value per step).
+///
+/// The columns are Arrow buffers: they read as slices, clone by reference
+/// count, and move into and out of Arrow arrays without a copy (see
+/// [`crate::columnar`]). Collect or `.into()` a `Vec` to build one, and
+/// `.to_vec()` to edit one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    pub timestamps: ScalarBuffer<i64>,
+    pub values: ScalarBuffer<f64>,
+    /// With `data.export = "split"` and sessions, the export netted into
+    /// each value (as a positive load), so that import and export sharing a
+    /// step are forecast apart (see [`crate::pipeline::split_channels`]).
+    /// Series built from this one leave it out.
+    pub export: Option<Vec<f64>>,
+}
+
+impl Default for Series {
+    fn default() -> Self {
+        Self::new(Vec::new(), Vec::new())
+    }
+}
+
+impl Series {
+    pub fn new(timestamps: Vec<i64>, values: Vec<f64>) -> Self {
+        Self::from_buffers(timestamps.into(), values.into())
+    }
+
+    /// A series on existing buffers, e.g. another series' timestamps.
+    pub fn from_buffers(timestamps: ScalarBuffer<i64>, values: ScalarBuffer<f64>) -> Self {
+        Self {
+            timestamps,
+            values,
+            export: None,
+        }
+    }
+
+    /// The points in `range`, sharing this series' buffers.
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        Self::from_buffers(
+            self.timestamps.slice(range.start, range.len()),
+            self.values.slice(range.start, range.len()),
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    /// Seconds between the first two points; an hour for shorter series.
+    pub fn step(&self) -> i64 {
+        match self.timestamps.get(..2) {
+            Some([first, second]) => second - first,
+            _ => crate::preprocess::HOUR,
+        }
+    }
+}
+
+/// A [`Series`] stored at a quarter of its size, for holding many series at
+/// once, like the server does every site's history.
+///
+/// The timestamps shrink to a start and a step, and the values to `f32`
+/// (about seven significant digits). Widen back with [`CompactSeries::to_series`]
+/// to fit or forecast, which stay in `f64`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompactSeries {
+    pub start: i64,
+    /// Seconds between points.
+    pub step: i64,
+    pub values: Vec<f32>,
+}
+
+impl CompactSeries {
+    /// Compact a series; fails if its timestamps are not evenly spaced.
+    pub fn from_series(series: &Series) -> Result<Self, Box<dyn Error>> {
+        let start = series.timestamps.first().copied().unwrap_or_default();
+        let step = series.step();
+        if let Some(i) =
+            (0..series.len()).find(|&i| series.timestamps[i] != start + i as i64 * step)
+        {
+            return Err(format!(
+                "Cannot compact a series with uneven steps (at {})",
+                format_timestamp(series.timestamps[i])
+            )
+            .into());
+        }
+        Ok(Self {
+            start,
+            step,
+            values: series.values.iter().map(|v| *v as f32).collect(),
+        })
+    }
+
+    pub fn to_series(&self) -> Series {
+        Series::new(
+            (0..self.len() as i64)
+                .map(|i| self.start + i * self.step)
+                .collect(),
+            self.values.iter().map(|v| *v as f64).collect(),
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+pub fn parse_datetime_to_timestamp(datetime_str: &str) -> Result<i64, Box<dyn Error>> {
+    // Parse "2024-01-01 13:14" -> NaiveDateTime
+    let naive_dt = NaiveDateTime::parse_from_str(datetime_str, TIMESTAMP_FORMAT)?;
+    // Convert to UNIX timestamp
+    Ok(naive_dt.and_utc().timestamp())
+}
+
+/// A timestamp from an API or message: Unix seconds and RFC 3339 are instants,
+/// shown in `timezone` when set; `YYYY-MM-DD HH:MM[:SS]` is local time already.
+pub fn parse_timestamp_text(text: &str, timezone: Option<Tz>) -> Option<i64> {
+    let text = text.trim();
+    let instant = match text.parse::<f64>() {
+        Ok(seconds) => DateTime::from_timestamp(seconds as i64, 0)?.fixed_offset(),
+        Err(_) => match DateTime::parse_from_rfc3339(text) {
+            Ok(instant) => instant,
+            Err(_) => {
+                return [
+                    "%Y-%m-%d %H:%M:%S",
+                    "%Y-%m-%dT%H:%M:%S",
+                    "%Y-%m-%d %H:%M",
+                    "%Y-%m-%dT%H:%M",
+                ]
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+                .map(|local| local.and_utc().timestamp());
+            }
+        },
+    };
+    let local = match timezone {
+        Some(timezone) => instant.with_timezone(&timezone).naive_local(),
+        None => instant.naive_local(),
+    };
+    Some(local.and_utc().timestamp())
+}
+
+/// [`parse_datetime_to_timestamp`] of a raw, untrimmed CSV field.
+///
+/// The zero-padded `YYYY-MM-DD HH:MM` of the exports is parsed by hand,
+/// without allocating; anything else goes through chrono.
+fn parse_timestamp_field(field: &[u8]) -> Option<i64> {
+    let field = field.trim_ascii();
+    let &[
+        y0,
+        y1,
+        y2,
+        y3,
+        b'-',
+        m0,
+        m1,
+        b'-',
+        d0,
+        d1,
+        b' ',
+        h0,
+        h1,
+        b':',
+        n0,
+        n1,
+    ] = field
+    else {
+        return parse_datetime_to_timestamp(std::str::from_utf8(field).ok()?).ok();
+    };
+    let number = |digits: &[u8]| {
+        digits.iter().try_fold(0u32, |n, d| {
+            d.is_ascii_digit().then(|| n * 10 + (d - b'0') as u32)
+        })
+    };
+    let date = NaiveDate::from_ymd_opt(
+        number(&[y0, y1, y2, y3])? as i32,
+        number(&[m0, m1])?,
+        number(&[d0, d1])?,
+    )?;
+    let datetime = date.and_hms_opt(number(&[h0, h1])?, number(&[n0, n1])?, 0)?;
+    Some(datetime.and_utc().timestamp())
+}
+
+fn parse_number_field(field: &[u8]) -> Option<f64> {
+    std::str::from_utf8(field.trim_ascii()).ok()?.parse().ok()
+}
+
+/// How a session export writes its fields, numbers and dates, and which rows it keeps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvFormat {
+    /// Keep sessions with negative energy (see `data.export`).
+    pub signed: bool,
+    pub delimiter: u8,
+    pub decimal: u8,
+    /// Digit group separator, e.g. `.` in `1.234,56`.
+    pub thousands: Option<u8>,
+    /// chrono formats of local date strings, tried after `YYYY-MM-DD HH:MM`.
+    pub date_formats: Vec<String>,
+    /// Columns (from 0) of the start time, the stop time and the energy.
+    pub start: usize,
+    pub stop: usize,
+    pub energy: usize,
+    /// Wh per unit of the energy column.
+    pub energy_scale: f64,
+    /// A column of site ids, and the site whose rows are kept; other rows are left out uncounted.
+    pub site: Option<(usize, String)>,
+}
+
+impl Default for CsvFormat {
+    fn default() -> Self {
+        Self {
+            signed: false,
+            delimiter: b',',
+            decimal: b'.',
+            thousands: None,
+            date_formats: Vec::new(),
+            start: 1,
+            stop: 2,
+            energy: 7,
+            energy_scale: 1.0,
+            site: None,
+        }
+    }
+}
+
+impl CsvFormat {
+    /// Fewest fields of a row with a session.
+    fn columns(&self) -> usize {
+        self.start
+            .max(self.energy)
+            .max(self.site.as_ref().map_or(0, |(column, _)| *column))
+            + 1
+    }
+
+    /// The name of column `column` in skipped rows: the export's own for the usual layout.
+    fn column_name(&self, column: usize, usual: &str) -> String {
+        match (self.start, self.energy) {
+            (1, 7) => usual.to_string(),
+            _ => format!("column {}", column),
+        }
+    }
+
+    /// A start or stop time field, as a local timestamp.
+    pub fn timestamp(&self, field: &[u8]) -> Option<i64> {
+        parse_timestamp_field(field).or_else(|| {
+            let text = std::str::from_utf8(field.trim_ascii()).ok()?;
+            self.date_formats
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+                .map(|local| local.and_utc().timestamp())
+        })
+    }
+
+    /// An energy field, in the column's own unit.
+    pub fn number(&self, field: &[u8]) -> Option<f64> {
+        if self.decimal == b'.' && self.thousands.is_none() {
+            return parse_number_field(field);
+        }
+        let number: Vec<u8> = field
+            .trim_ascii()
+            .iter()
+            .filter(|b| Some(**b) != self.thousands)
+            .map(|b| if *b == self.decimal { b'.' } else { *b })
+            .collect();
+        parse_number_field(&number)
+    }
+}
+
+pub fn format_timestamp(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|dt| dt.naive_utc().format(TIMESTAMP_FORMAT).to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+/// Skipped rows kept per read; the rest are only counted.
+pub const MAX_SKIPPED_ROWS: usize = 100;
+
+/// A row of a session export that couldn't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedRow {
+    /// Record number in the file, the header being row 1.
+    pub row: usize,
+    /// The column that failed, if it was one.
+    pub column: Option<String>,
+    pub reason: String,
+}
+
+/// Row counts of one session export read.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RowCounts {
+    /// Data rows, not counting a header.
+    pub read: usize,
+    /// Rows that failed to parse.
+    pub invalid: usize,
+    /// Rows with zero energy, or negative energy unless it's kept as export.
+    pub non_positive: usize,
+    /// The first [`MAX_SKIPPED_ROWS`] invalid rows, and why.
+    pub skipped: Vec<SkippedRow>,
+    /// Spans `[from, to)` of a load series' grid without a reading, filled by resampling.
+    pub gaps: Vec<(i64, i64)>,
+}
+
+impl RowCounts {
+    pub fn dropped(&self) -> usize {
+        self.invalid + self.non_positive
+    }
+
+    /// Share of the rows read that failed to parse.
+    pub fn skip_rate(&self) -> f64 {
+        match self.read {
+            0 => 0.0,
+            read => self.invalid as f64 / read as f64,
+        }
+    }
+
+    /// Count an invalid row, keeping why while there's room.
+    pub fn skip(&mut self, row: usize, column: Option<&str>, reason: String) {
+        self.invalid += 1;
+        if self.skipped.len() < MAX_SKIPPED_ROWS {
+            self.skipped.push(SkippedRow {
+                row,
+                column: column.map(str::to_string),
+                reason,
+            });
+        }
+    }
+}
+
+/// Load charging sessions from a site export CSV.
+///
+/// Rows that fail to parse are skipped and recorded in the row counts (see
+/// [`load_sessions_counted`]), as are sessions with zero or negative energy.
+pub fn load_sessions(file_path: impl AsRef<Path>) -> Result<Vec<Session>, Box<dyn Error>> {
+    Ok(load_sessions_counted(file_path)?.0)
+}
+
+/// [`load_sessions`], also returning how many rows were read and dropped.
+///
+/// Regular files are memory-mapped rather than read through a buffer.
+pub fn load_sessions_counted(
+    file_path: impl AsRef<Path>,
+) -> Result<(Vec<Session>, RowCounts), Box<dyn Error>> {
+    load_sessions_formatted(file_path, &CsvFormat::default())
+}
+
+/// [`load_sessions_counted`], keeping the sessions with negative energy:
+/// vehicle-to-grid export (see `data.export`).
+pub fn load_sessions_signed(
+    file_path: impl AsRef<Path>,
+) -> Result<(Vec<Session>, RowCounts), Box<dyn Error>> {
+    let format = CsvFormat {
+        signed: true,
+        ..CsvFormat::default()
+    };
+    load_sessions_formatted(file_path, &format)
+}
+
+/// [`load_sessions_counted`] of an export in another `format`, e.g. with
+/// decimal commas (see `[data.locale]`).
+pub fn load_sessions_formatted(
+    file_path: impl AsRef<Path>,
+    format: &CsvFormat,
+) -> Result<(Vec<Session>, RowCounts), Box<dyn Error>> {
+    #[cfg(feature = "parallel")]
+    if let Some(map) = map_file(file_path.as_ref())? {
+        return read_bytes_with(&map[..], format);
+    }
+    merge_parsed(vec![parse_sessions(open(file_path)?, true, format)?])
+}
+
+/// A read-only map of `path`, or `None` for stdin, pipes and other non-files.
+#[cfg(feature = "parallel")]
+fn map_file(path: &Path) -> Result<Option<memmap2::Mmap>, Box<dyn Error>> {
+    if path == Path::new(STDIO) {
+        return Ok(None);
+    }
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Could not open {}: {}", path.display(), e))?;
+    if !file.metadata()?.is_file() {
+        return Ok(None);
+    }
+    // SAFETY: the map is only read while the export is parsed. Like any
+    // mmap, it faults if another process truncates the file meanwhile.
+    let map = unsafe { memmap2::Mmap::map(&file) }
+        .map_err(|e| format!("Could not map {}: {}", path.display(), e))?;
+    Ok(Some(map))
+}
+
+/// Whether `path` names a Parquet or Arrow IPC file of an hourly series,
+/// rather than a session export (see [`crate::pipeline::load_history`]).
+pub fn is_columnar(path: impl AsRef<Path>) -> bool {
+    let extension = path
+        .as_ref()
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("");
+    ["parquet", "arrow", "ipc", "feather"]
+        .iter()
+        .any(|columnar| extension.eq_ignore_ascii_case(columnar))
+}
+
+/// Open an input file, or stdin for [`STDIO`].
+pub fn open(path: impl AsRef<Path>) -> Result<Box<dyn Read>, Box<dyn Error>> {
+    let path = path.as_ref();
+    if path == Path::new(STDIO) {
+        return Ok(Box::new(std::io::stdin().lock()));
+    }
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Could not open {}: {}", path.display(), e))?;
+    Ok(Box::new(std::io::BufReader::new(file)))
+}
+
+/// [`load_sessions`] from any reader, e.g. an uploaded file's contents.
+pub fn read_sessions(reader: impl Read) -> Result<Vec<Session>, Box<dyn Error>> {
+    Ok(read_sessions_counted(reader)?.0)
+}
+
+/// [`read_sessions`], also returning how many rows were read and dropped.
+///
+/// An unparseable first row is taken to be the header and not counted.
+pub fn read_sessions_counted(
+    reader: impl Read,
+) -> Result<(Vec<Session>, RowCounts), Box<dyn Error>> {
+    read_sessions_formatted(reader, &CsvFormat::default())
+}
+
+/// [`read_sessions_counted`] of an export in another `format`.
+pub fn read_sessions_formatted(
+    reader: impl Read,
+    format: &CsvFormat,
+) -> Result<(Vec<Session>, RowCounts), Box<dyn Error>> {
+    merge_parsed(vec![parse_sessions(reader, true, format)?])
+}
+
+/// Exports at least this big are parsed in chunks across cores.
+#[cfg(feature = "parallel")]
+const PARALLEL_BYTES: usize = 1 << 20;
+
+/// [`read_sessions_counted`] of an export in memory, e.g. a mapped file.
+///
+/// Large exports are split at line ends and the pieces parsed in parallel.
+#[cfg(feature = "parallel")]
+pub fn read_sessions_bytes(bytes: &[u8]) -> Result<(Vec<Session>, RowCounts), Box<dyn Error>> {
+    read_bytes_with(bytes, &CsvFormat::default())
+}
+
+#[cfg(feature = "parallel")]
+fn read_bytes_with(
+    bytes: &[u8],
+    format: &CsvFormat,
+) -> Result<(Vec<Session>, RowCounts), Box<dyn Error>> {
+    use rayon::prelude::*;
+
+    // Quoted fields may hold line breaks, so those exports are read in one go
+    let threads = rayon::current_num_threads();
+    if threads < 2 || bytes.len() < PARALLEL_BYTES || bytes.contains(&b'"') {
+        return merge_parsed(vec![parse_sessions(bytes, true, format)?]);
+    }
+    let parts = line_chunks(bytes, threads * 4)
+        .into_par_iter()
+        .enumerate()
+        .map(|(i, chunk)| parse_sessions(chunk, i == 0, format))
+        .collect::<Result<Vec<_>, _>>();
+    match parts {
+        Ok(parts) => merge_parsed(parts),
+        // Re-read in one go, for an error with the export's own line numbers
+        Err(_) => merge_parsed(vec![parse_sessions(bytes, true, format)?]),
+    }
+}
+
+/// `bytes` in about `n` pieces, each ending at a line end.
+#[cfg(feature = "parallel")]
+fn line_chunks(bytes: &[u8], n: usize) -> Vec<&[u8]> {
+    let mut chunks = Vec::with_capacity(n);
+    let mut start = 0;
+    for k in 1..n {
+        let target = (bytes.len() * k / n).max(start);
+        let Some(newline) = bytes[target..].iter().position(|b| *b == b'\n') else {
+            break;
+        };
+        chunks.push(&bytes[start..=target + newline]);
+        start = target + newline + 1;
+    }
+    chunks.push(&bytes[start..]);
+    chunks
+}
+
+/// Sessions and row counts of one stretch of an export.
+struct Parsed {
+    sessions: Vec<Session>,
+    counts: RowCounts,
+    /// Records in the stretch, counting a header.
+    rows: usize,
+}
+
+/// Parse export rows in `format`; `header` says the first one may be the header.
+fn parse_sessions(
+    reader: impl Read,
+    header: bool,
+    format: &CsvFormat,
+) -> Result<Parsed, csv::Error> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(format.delimiter)
+        // Short rows are skipped and recorded rather than failing the read
+        .flexible(true)
+        .buffer_capacity(1 << 16)
+        .from_reader(reader);
+    let mut sessions = Vec::new();
+    let mut counts = RowCounts::default();
+
+    // One record, reused for every row: parsing allocates nothing per row
+    let mut record = ByteRecord::new();
+    let mut row = 0;
+    while rdr.read_byte_record(&mut record)? {
+        row += 1;
+        let counted = row > 1 || !header;
+
+        // Rows of other sites in a multi-site export aren't this site's to count
+        if let Some((column, site)) = &format.site
+            && record
+                .get(*column)
+                .is_some_and(|field| field.trim_ascii() != site.as_bytes())
+        {
+            continue;
+        }
+
+        // Get `Start time` (column 1), `Count.Stop time` (column 2) and
+        // `Modified Count.Energy (Wh)` (column 7), or the configured columns
+        if let (Some(ts_field), Some(energy_field)) =
+            (record.get(format.start), record.get(format.energy))
+        {
+            // Convert timestamp to UNIX format
+            if let (Some(start), Some(energy)) = (
+                format.timestamp(ts_field),
+                format.number(energy_field).map(|e| e * format.energy_scale),
+            ) {
+                counts.read += 1;
+                // A missing or inconsistent stop time collapses the session to its start
+                let stop = record
+                    .get(format.stop)
+                    .and_then(|field| format.timestamp(field))
+                    .filter(|stop| *stop >= start)
+                    .unwrap_or(start);
+
+                // Skip zero or negative energy values
+                if energy > 0.0 || (format.signed && energy < 0.0) {
+                    sessions.push(Session {
+                        start,
+                        stop,
+                        energy_wh: energy,
+                    });
+                } else {
+                    counts.non_positive += 1;
+                }
+            } else if counted {
+                counts.read += 1;
+                let (column, reason) = match format.timestamp(ts_field) {
+                    None => (
+                        format.column_name(format.start, "Start time"),
+                        format!("bad timestamp {:?}", String::from_utf8_lossy(ts_field)),
+                    ),
+                    Some(_) => (
+                        format.column_name(format.energy, "Modified Count.Energy (Wh)"),
+                        format!("bad energy {:?}", String::from_utf8_lossy(energy_field)),
+                    ),
+                };
+                counts.skip(row, Some(&column), reason);
+            }
+        } else if counted {
+            counts.read += 1;
+            counts.skip(
+                row,
+                None,
+                format!("{} columns, {} expected", record.len(), format.columns()),
+            );
+        }
+    }
+
+    Ok(Parsed {
+        sessions,
+        counts,
+        rows: row,
+    })
+}
+
+/// Join parsed stretches in order, numbering their skipped rows from the file's start.
+fn merge_parsed(parts: Vec<Parsed>) -> Result<(Vec<Session>, RowCounts), Box<dyn Error>> {
+    let mut sessions = Vec::with_capacity(parts.iter().map(|p| p.sessions.len()).sum());
+    let mut counts = RowCounts::default();
+    let mut rows = 0;
+    for part in parts {
+        sessions.extend(part.sessions);
+        counts.read += part.counts.read;
+        counts.invalid += part.counts.invalid;
+        counts.non_positive += part.counts.non_positive;
+        let room = MAX_SKIPPED_ROWS - counts.skipped.len();
+        counts
+            .skipped
+            .extend(
+                part.counts
+                    .skipped
+                    .into_iter()
+                    .take(room)
+                    .map(|skipped| SkippedRow {
+                        row: rows + skipped.row,
+                        ..skipped
+                    }),
+            );
+        rows += part.rows;
+    }
+
+    if sessions.is_empty() {
+        return Err("No valid data found in CSV. Please check file format.".into());
+    }
+
+    Ok((sessions, counts))
+}
@@ -0,0 +1,300 @@
+//! Database outputs: upsert forecast rows into Postgres/Timescale or InfluxDB.
+//! Postgres can also be the input, see [`read_postgres`].
+//!
+//! Rows are keyed by `(site, ds, horizon)`, where `horizon` is the lead time in
+//! forecast steps: hours, or `data.step_minutes` on a finer grid. Re-running
+//! the same issue overwrites its rows; each new issue adds a fresh set of
+//! leads, so dashboards can compare what was forecast when.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use postgres::{Client, NoTls, Row};
+use std::error::Error;
+
+use crate::config::{InfluxConfig, PostgresConfig, SqlSourceConfig};
+use crate::data::{RowCounts, Series, Session};
+use crate::forecast::Forecast;
+use crate::register::Reading;
+use crate::secret;
+use crate::source::Readings;
+
+/// Lead time in steps of each forecast point, counted from the step before the first one.
+fn horizons(forecast: &Forecast) -> Vec<i64> {
+    let step = forecast.step().max(1);
+    let origin = forecast
+        .timestamps
+        .first()
+        .map(|t| t - step)
+        .unwrap_or_default();
+    forecast
+        .timestamps
+        .iter()
+        .map(|t| (t - origin) / step)
+        .collect()
+}
+
+fn naive(timestamp: i64) -> Result<NaiveDateTime, Box<dyn Error>> {
+    Ok(DateTime::from_timestamp(timestamp, 0)
+        .ok_or_else(|| format!("Timestamp {} out of range", timestamp))?
+        .naive_utc())
+}
+
+/// Table names are spliced into SQL, so only allow plain (optionally schema-qualified) identifiers.
+fn check_identifier(name: &str) -> Result<(), Box<dyn Error>> {
+    let valid = !name.is_empty()
+        && name.split('.').all(|part| {
+            !part.is_empty()
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                && !part.starts_with(|c: char| c.is_ascii_digit())
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid table name {:?}", name).into())
+    }
+}
+
+/// Upsert a forecast into Postgres, creating the table first if configured.
+pub fn write_postgres(
+    config: &PostgresConfig,
+    site: &str,
+    model_version: &str,
+    forecast: &Forecast,
+) -> Result<(), Box<dyn Error>> {
+    check_identifier(&config.table)?;
+    let table = &config.table;
+    let mut client = Client::connect(&secret::resolve(&config.url)?, NoTls)?;
+
+    if config.create_table {
+        client.batch_execute(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                site TEXT NOT NULL,
+                ds TIMESTAMP NOT NULL,
+                horizon INTEGER NOT NULL,
+                yhat DOUBLE PRECISION NOT NULL,
+                yhat_lower DOUBLE PRECISION,
+                yhat_upper DOUBLE PRECISION,
+                model_version TEXT NOT NULL,
+                issued_at TIMESTAMPTZ NOT NULL,
+                PRIMARY KEY (site, ds, horizon)
+            )"
+        ))?;
+        if config.timescale {
+            client.batch_execute(&format!(
+                "SELECT create_hypertable('{table}', 'ds', if_not_exists => TRUE)"
+            ))?;
+        }
+    }
+
+    let issued_at = Utc::now();
+    let mut tx = client.transaction()?;
+    let upsert = tx.prepare(&format!(
+        "INSERT INTO {table} (site, ds, horizon, yhat, yhat_lower, yhat_upper, model_version, issued_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+         ON CONFLICT (site, ds, horizon) DO UPDATE SET
+            yhat = EXCLUDED.yhat,
+            yhat_lower = EXCLUDED.yhat_lower,
+            yhat_upper = EXCLUDED.yhat_upper,
+            model_version = EXCLUDED.model_version,
+            issued_at = EXCLUDED.issued_at"
+    ))?;
+    for (i, horizon) in horizons(forecast).into_iter().enumerate() {
+        tx.execute(
+            &upsert,
+            &[
+                &site,
+                &naive(forecast.timestamps[i])?,
+                &(horizon as i32),
+                &forecast.point[i],
+                &forecast.lower[i],
+                &forecast.upper[i],
+                &model_version,
+                &issued_at,
+            ],
+        )?;
+    }
+    tx.commit()?;
+
+    eprintln!(
+        "Upserted {} rows into Postgres table {}",
+        forecast.len(),
+        table
+    );
+    Ok(())
+}
+
+/// Run the `[data.sql]` query for `site`: an hourly load, sessions, or chargers'
+/// energy registers (`timestamp, charger, reading`, with a text charger id),
+/// by its columns.
+///
+/// Sessions with negative energy are kept when `signed` (see `data.export`).
+pub fn read_postgres(
+    config: &SqlSourceConfig,
+    site: &str,
+    timezone: Option<Tz>,
+    signed: bool,
+) -> Result<(Readings, RowCounts), Box<dyn Error>> {
+    let mut client = Client::connect(&secret::resolve(&config.url)?, NoTls)?;
+    let rows = match config.query.contains("$1") {
+        true => client.query(&config.query, &[&site])?,
+        false => client.query(&config.query, &[])?,
+    };
+    let mut counts = RowCounts {
+        read: rows.len(),
+        ..RowCounts::default()
+    };
+    match rows.first().map(|row| row.len()) {
+        None | Some(2) => {
+            let (mut timestamps, mut values) = (Vec::new(), Vec::new());
+            for row in &rows {
+                match (timestamp(row, 0, timezone), row.try_get::<_, f64>(1)) {
+                    (Ok(ts), Ok(kw)) => {
+                        timestamps.push(ts);
+                        values.push(kw);
+                    }
+                    _ => counts.invalid += 1,
+                }
+            }
+            Ok((Readings::Hourly(Series::new(timestamps, values)), counts))
+        }
+        Some(3) if rows[0].try_get::<_, String>(1).is_ok() => {
+            let mut readings = Vec::new();
+            for row in &rows {
+                match (
+                    timestamp(row, 0, timezone),
+                    row.try_get::<_, String>(1),
+                    row.try_get::<_, f64>(2),
+                ) {
+                    (Ok(timestamp), Ok(charger), Ok(value)) => readings.push(Reading {
+                        charger,
+                        timestamp,
+                        value,
+                    }),
+                    _ => counts.invalid += 1,
+                }
+            }
+            Ok((Readings::Registers(readings), counts))
+        }
+        Some(3) => {
+            let mut sessions = Vec::new();
+            for row in &rows {
+                match (
+                    timestamp(row, 0, timezone),
+                    timestamp(row, 1, timezone),
+                    row.try_get::<_, f64>(2),
+                ) {
+                    (Ok(start), stop, Ok(energy_wh))
+                        if energy_wh > 0.0 || (signed && energy_wh < 0.0) =>
+                    {
+                        sessions.push(Session {
+                            start,
+                            stop: stop.unwrap_or(start),
+                            energy_wh,
+                        })
+                    }
+                    (Ok(_), _, Ok(_)) => counts.non_positive += 1,
+                    _ => counts.invalid += 1,
+                }
+            }
+            Ok((Readings::Sessions(sessions), counts))
+        }
+        Some(n) => Err(format!(
+            "The data.sql query returns {} columns; expected (timestamp, kW), (start, stop, Wh) or (timestamp, charger, register)",
+            n
+        )
+        .into()),
+    }
+}
+
+/// Column `i` of `row` in local wall-clock seconds, from a `TIMESTAMP` as is
+/// or a `TIMESTAMPTZ` in `timezone`.
+fn timestamp(row: &Row, i: usize, timezone: Option<Tz>) -> Result<i64, Box<dyn Error>> {
+    if let Ok(local) = row.try_get::<_, NaiveDateTime>(i) {
+        return Ok(local.and_utc().timestamp());
+    }
+    let time: DateTime<Utc> = row.try_get(i)?;
+    Ok(match timezone {
+        Some(tz) => time.with_timezone(&tz).naive_local().and_utc().timestamp(),
+        None => time.timestamp(),
+    })
+}
+
+/// Escape a tag value for InfluxDB line protocol.
+fn escape_tag(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+/// Forecast points as InfluxDB line protocol with second precision.
+///
+/// `site` and `horizon` are tags, so a point written again for the same hour
+/// and lead replaces the earlier one.
+pub fn influx_lines(
+    measurement: &str,
+    site: &str,
+    model_version: &str,
+    forecast: &Forecast,
+) -> String {
+    let measurement = escape_tag(measurement);
+    let site = escape_tag(site);
+    let version = model_version.replace('\\', "\\\\").replace('"', "\\\"");
+    horizons(forecast)
+        .into_iter()
+        .enumerate()
+        .map(|(i, horizon)| {
+            format!(
+                "{},site={},horizon={} yhat={},yhat_lower={},yhat_upper={},model_version=\"{}\" {}\n",
+                measurement,
+                site,
+                horizon,
+                forecast.point[i],
+                forecast.lower[i],
+                forecast.upper[i],
+                version,
+                forecast.timestamps[i],
+            )
+        })
+        .collect()
+}
+
+/// Write a forecast to InfluxDB via the v2 `/api/v2/write` endpoint.
+pub fn write_influx(
+    config: &InfluxConfig,
+    site: &str,
+    model_version: &str,
+    forecast: &Forecast,
+) -> Result<(), Box<dyn Error>> {
+    let url = format!("{}/api/v2/write", config.url.trim_end_matches('/'));
+    let mut request = ureq::post(&url)
+        .query("org", &config.org)
+        .query("bucket", &config.bucket)
+        .query("precision", "s")
+        .set("Content-Type", "text/plain; charset=utf-8");
+    if let Some(token) = secret::resolve_option(config.token.as_deref())? {
+        request = request.set("Authorization", &format!("Token {}", token));
+    }
+    request
+        .send_string(&influx_lines(
+            &config.measurement,
+            site,
+            model_version,
+            forecast,
+        ))
+        .map_err(|e| {
+            format!(
+                "InfluxDB write to {} failed: {}",
+                secret::url_host(&config.url),
+                secret::http_error(&e)
+            )
+        })?;
+
+    eprintln!(
+        "Wrote {} points to InfluxDB bucket {}",
+        forecast.len(),
+        config.bucket
+    );
+    Ok(())
+}
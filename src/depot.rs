@@ -0,0 +1,246 @@
+//! Fleet depot mode: scheduled vehicle charging on top of the background
+//! (walk-in) demand forecast.
+
+use csv::{ReaderBuilder, Writer};
+use serde::Deserialize;
+use std::error::Error;
+use std::path::Path;
+
+use crate::config::{ChargingStrategy, DepotConfig};
+use crate::data::{self, format_timestamp, parse_datetime_to_timestamp};
+use crate::export;
+use crate::forecast::Forecast;
+use crate::preprocess::{HOUR, floor_hour, overlap_seconds};
+use crate::prices::Prices;
+
+/// One scheduled depot visit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VehicleVisit {
+    pub vehicle_id: String,
+    pub arrival: i64,
+    pub departure: i64,
+    /// Energy that must be delivered before departure, in kWh.
+    pub required_kwh: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScheduleRow {
+    vehicle_id: String,
+    arrival: String,
+    departure: String,
+    arrival_soc: Option<f64>,
+    battery_kwh: Option<f64>,
+    required_kwh: Option<f64>,
+}
+
+/// Load a fleet schedule CSV.
+///
+/// Columns: `vehicle_id,arrival,departure,arrival_soc,battery_kwh,required_kwh`.
+/// When `required_kwh` is empty it is derived from the arrival SOC (percent),
+/// the battery size and the configured target SOC.
+pub fn load_schedule(
+    path: impl AsRef<Path>,
+    target_soc_percent: f64,
+) -> Result<Vec<VehicleVisit>, Box<dyn Error>> {
+    let mut rdr = ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data::open(path)?);
+    let mut visits = Vec::new();
+
+    for (i, row) in rdr.deserialize::<ScheduleRow>().enumerate() {
+        let row = row?;
+        let line = i + 2;
+        let arrival = parse_datetime_to_timestamp(&row.arrival).map_err(|e| {
+            format!(
+                "Schedule line {}: bad arrival {:?}: {}",
+                line, row.arrival, e
+            )
+        })?;
+        let departure = parse_datetime_to_timestamp(&row.departure).map_err(|e| {
+            format!(
+                "Schedule line {}: bad departure {:?}: {}",
+                line, row.departure, e
+            )
+        })?;
+        if departure <= arrival {
+            return Err(format!(
+                "Schedule line {}: vehicle {} departs before it arrives",
+                line, row.vehicle_id
+            )
+            .into());
+        }
+
+        let required_kwh = match (row.required_kwh, row.arrival_soc, row.battery_kwh) {
+            (Some(kwh), _, _) => kwh,
+            (None, Some(soc), Some(battery)) => {
+                battery * (target_soc_percent - soc).max(0.0) / 100.0
+            }
+            _ => {
+                return Err(format!(
+                    "Schedule line {}: need either required_kwh or arrival_soc + battery_kwh",
+                    line
+                )
+                .into());
+            }
+        };
+
+        visits.push(VehicleVisit {
+            vehicle_id: row.vehicle_id,
+            arrival,
+            departure,
+            required_kwh,
+        });
+    }
+
+    Ok(visits)
+}
+
+/// Combined depot load on the forecast grid, in kW.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepotProjection {
+    pub background: Forecast,
+    pub fleet: Vec<f64>,
+    pub total: Forecast,
+    /// Vehicles that can't get their required energy before departure,
+    /// with the unmet energy in kWh.
+    pub shortfalls: Vec<(String, f64)>,
+}
+
+/// Hourly fleet load for the given visits on an hourly grid starting at each timestamp.
+///
+/// Returns the load per hour and the list of vehicles whose dwell time is too
+/// short for their required energy at the configured charger power. The
+/// `cheapest` strategy charges by `prices`; without them it charges like `immediate`.
+pub fn fleet_load(
+    visits: &[VehicleVisit],
+    timestamps: &[i64],
+    config: &DepotConfig,
+    prices: Option<&Prices>,
+) -> (Vec<f64>, Vec<(String, f64)>) {
+    let mut load = vec![0.0; timestamps.len()];
+    let mut shortfalls = Vec::new();
+
+    for visit in visits {
+        let dwell_hours = (visit.departure - visit.arrival) as f64 / HOUR as f64;
+        let deliverable = config.charger_power_kw * dwell_hours;
+        if visit.required_kwh > deliverable {
+            shortfalls.push((visit.vehicle_id.clone(), visit.required_kwh - deliverable));
+        }
+        let energy = visit.required_kwh.min(deliverable);
+        if energy <= 0.0 {
+            continue;
+        }
+
+        // Charging window and constant power within it
+        let (end, power_kw) = match config.strategy {
+            ChargingStrategy::Immediate => {
+                let secs = (energy / config.charger_power_kw * HOUR as f64).ceil() as i64;
+                (visit.arrival + secs, config.charger_power_kw)
+            }
+            ChargingStrategy::Spread => (visit.departure, energy / dwell_hours),
+            ChargingStrategy::Cheapest => {
+                for (hour, kw) in cheapest_hours(visit, energy, config.charger_power_kw, prices) {
+                    if let Ok(i) = timestamps.binary_search(&hour) {
+                        load[i] += kw;
+                    }
+                }
+                continue;
+            }
+        };
+
+        for (slot, &hour) in load.iter_mut().zip(timestamps) {
+            let overlap = overlap_seconds(visit.arrival, end, hour, hour + HOUR);
+            *slot += power_kw * overlap as f64 / HOUR as f64;
+        }
+    }
+
+    (load, shortfalls)
+}
+
+/// The hours of a visit to deliver `energy` kWh in, cheapest first, with the
+/// average power in each; ties go to the earlier hour.
+fn cheapest_hours(
+    visit: &VehicleVisit,
+    energy: f64,
+    charger_power_kw: f64,
+    prices: Option<&Prices>,
+) -> Vec<(i64, f64)> {
+    let mut hours: Vec<(i64, f64)> = (floor_hour(visit.arrival)..visit.departure)
+        .step_by(HOUR as usize)
+        .map(|hour| {
+            (
+                hour,
+                charger_power_kw
+                    * overlap_seconds(visit.arrival, visit.departure, hour, hour + HOUR) as f64
+                    / HOUR as f64,
+            )
+        })
+        .collect();
+    if let Some(prices) = prices {
+        hours.sort_by(|a, b| prices.at(a.0).total_cmp(&prices.at(b.0)));
+    }
+    let mut remaining = energy;
+    let mut charged = Vec::new();
+    for (hour, available_kwh) in hours {
+        if remaining <= 0.0 {
+            break;
+        }
+        let kwh = available_kwh.min(remaining);
+        remaining -= kwh;
+        charged.push((hour, kwh));
+    }
+    charged
+}
+
+/// Add the scheduled fleet load to the background forecast.
+pub fn project(
+    background: Forecast,
+    visits: &[VehicleVisit],
+    config: &DepotConfig,
+    prices: Option<&Prices>,
+) -> DepotProjection {
+    let (fleet, shortfalls) = fleet_load(visits, &background.timestamps, config, prices);
+    let add = |v: &[f64]| v.iter().zip(&fleet).map(|(b, f)| b + f).collect::<Vec<_>>();
+    let total = Forecast {
+        timestamps: background.timestamps.clone(),
+        point: add(&background.point),
+        lower: add(&background.lower),
+        upper: add(&background.upper),
+    };
+    DepotProjection {
+        background,
+        fleet,
+        total,
+        shortfalls,
+    }
+}
+
+/// Write the projection as CSV, one row per hour.
+pub fn write_projection_csv(
+    projection: &DepotProjection,
+    path: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_writer(export::create(path)?);
+    wtr.write_record([
+        "timestamp",
+        "background_kw",
+        "fleet_kw",
+        "total_kw",
+        "total_lower_kw",
+        "total_upper_kw",
+    ])?;
+    let total = &projection.total;
+    for i in 0..total.len() {
+        wtr.write_record([
+            format_timestamp(total.timestamps[i]),
+            format!("{:.3}", projection.background.point[i]),
+            format!("{:.3}", projection.fleet[i]),
+            format!("{:.3}", total.point[i]),
+            format!("{:.3}", total.lower[i]),
+            format!("{:.3}", total.upper[i]),
+        ])?;
+    }
+    wtr.flush()?;
+    eprintln!("Depot projection written to {}", path);
+    Ok(())
+}
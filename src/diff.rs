@@ -0,0 +1,241 @@
+//! Compare two issues of a forecast, e.g. yesterday's and today's, on the
+//! hours they both cover.
+//!
+//! Either side is a forecast file (the CSV of `output.csv`, the JSON of the
+//! server or an archived run) or the id of a run in the archive. Large jumps
+//! between consecutive issues for the same target hours usually mean a
+//! configuration or data problem rather than a change in demand.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::error::Error;
+use std::path::Path;
+
+use crate::archive::{self, Archive, ArchivedForecast, HourDiff};
+use crate::config::DiffConfig;
+use crate::data::{format_timestamp, parse_timestamp_text};
+use crate::forecast::Forecast;
+
+/// A forecast file, or else a run id in `archive`.
+pub fn load(spec: &str, archive: &Archive) -> Result<Forecast, Box<dyn Error>> {
+    if !Path::new(spec).exists() {
+        return archive.get(spec).map(|run| run.forecast).map_err(|e| {
+            format!(
+                "{} is neither a forecast file nor an archived run: {}",
+                spec, e
+            )
+            .into()
+        });
+    }
+    let text =
+        std::fs::read_to_string(spec).map_err(|e| format!("Could not read {}: {}", spec, e))?;
+    let forecast = match spec.ends_with(".json") {
+        true => forecast_from_json(&text),
+        false => forecast_from_csv(&text),
+    }
+    .map_err(|e| format!("Could not read forecast {}: {}", spec, e))?;
+    if forecast.is_empty() {
+        return Err(format!("Forecast {} has no hours", spec).into());
+    }
+    Ok(forecast)
+}
+
+/// `timestamp,yhat,yhat_lower,yhat_upper`, as [`crate::export::write_forecast_csv`] writes it.
+fn forecast_from_csv(text: &str) -> Result<Forecast, Box<dyn Error>> {
+    let mut reader = csv::Reader::from_reader(text.as_bytes());
+    let mut forecast = Forecast::default();
+    for (i, record) in reader.records().enumerate() {
+        let record = record?;
+        let field = |j: usize| {
+            record
+                .get(j)
+                .ok_or_else(|| format!("row {} has {} columns", i + 2, record.len()))
+        };
+        let number = |j: usize| -> Result<f64, Box<dyn Error>> {
+            let text = field(j)?;
+            text.trim()
+                .parse()
+                .map_err(|_| format!("row {}: {:?} is not a number", i + 2, text).into())
+        };
+        let timestamp = field(0)?;
+        let timestamp = parse_timestamp_text(timestamp, None)
+            .ok_or_else(|| format!("row {}: invalid timestamp {:?}", i + 2, timestamp))?;
+        forecast.timestamps.push(timestamp);
+        forecast.point.push(number(1)?);
+        forecast.lower.push(number(2)?);
+        forecast.upper.push(number(3)?);
+    }
+    Ok(sorted(forecast))
+}
+
+/// An archived run, or `{"points": [...]}` as [`crate::export::forecast_json`] makes it.
+fn forecast_from_json(text: &str) -> Result<Forecast, Box<dyn Error>> {
+    if let Ok(run) = serde_json::from_str::<ArchivedForecast>(text) {
+        return Ok(run.forecast);
+    }
+    let json: Value = serde_json::from_str(text)?;
+    let points = json["points"].as_array().ok_or("no `points` array")?;
+    let mut forecast = Forecast::default();
+    for point in points {
+        let number = |key: &str| {
+            point[key]
+                .as_f64()
+                .ok_or_else(|| format!("point without a numeric `{}`", key))
+        };
+        let timestamp = point["timestamp"]
+            .as_str()
+            .and_then(|text| parse_timestamp_text(text, None));
+        forecast
+            .timestamps
+            .push(timestamp.ok_or("point without a valid `timestamp`")?);
+        forecast.point.push(number("yhat")?);
+        forecast.lower.push(number("yhat_lower")?);
+        forecast.upper.push(number("yhat_upper")?);
+    }
+    Ok(sorted(forecast))
+}
+
+/// Hours in order, as [`archive::diff`] looks them up.
+fn sorted(forecast: Forecast) -> Forecast {
+    if forecast.timestamps.is_sorted() {
+        return forecast;
+    }
+    let mut order: Vec<usize> = (0..forecast.len()).collect();
+    order.sort_by_key(|&i| forecast.timestamps[i]);
+    let pick = |v: &[f64]| order.iter().map(|&i| v[i]).collect();
+    Forecast {
+        timestamps: order.iter().map(|&i| forecast.timestamps[i]).collect(),
+        point: pick(&forecast.point),
+        lower: pick(&forecast.lower),
+        upper: pick(&forecast.upper),
+    }
+}
+
+/// How far one forecast moved from the other.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiffReport {
+    /// Hours in both forecasts.
+    pub common_hours: usize,
+    /// Hours in only one of them, e.g. the day a newer issue reaches further.
+    pub only_a: usize,
+    pub only_b: usize,
+    pub mean_abs_change_kw: f64,
+    pub max_abs_change_kw: f64,
+    /// Over the common hours.
+    pub energy_a_kwh: f64,
+    pub energy_b_kwh: f64,
+    pub peak_a_kw: f64,
+    pub peak_b_kw: f64,
+    /// The `top` largest changes, largest first.
+    pub largest: Vec<HourChange>,
+    /// Why the change is too large to pass on, per `[diff]` limits.
+    pub violations: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HourChange {
+    /// `YYYY-MM-DD HH:MM`, local time.
+    pub timestamp: String,
+    pub a: f64,
+    pub b: f64,
+    pub change_kw: f64,
+}
+
+impl DiffReport {
+    pub fn new(a: &Forecast, b: &Forecast, config: &DiffConfig) -> Self {
+        let hours = archive::diff(a, b);
+        let mut largest: Vec<&HourDiff> = hours.iter().collect();
+        largest.sort_by(|x, y| y.delta().abs().total_cmp(&x.delta().abs()));
+        let abs: Vec<f64> = hours.iter().map(|d| d.delta().abs()).collect();
+        let mut report = Self {
+            common_hours: hours.len(),
+            only_a: a.len() - hours.len(),
+            only_b: b.len() - hours.len(),
+            mean_abs_change_kw: abs.iter().sum::<f64>() / abs.len().max(1) as f64,
+            max_abs_change_kw: abs.iter().cloned().fold(0.0, f64::max),
+            energy_a_kwh: hours.iter().map(|d| d.a).sum::<f64>() * a.step_hours(),
+            energy_b_kwh: hours.iter().map(|d| d.b).sum::<f64>() * b.step_hours(),
+            peak_a_kw: hours.iter().map(|d| d.a).fold(0.0, f64::max),
+            peak_b_kw: hours.iter().map(|d| d.b).fold(0.0, f64::max),
+            largest: largest
+                .into_iter()
+                .take(config.top)
+                .map(|d| HourChange {
+                    timestamp: format_timestamp(d.timestamp),
+                    a: d.a,
+                    b: d.b,
+                    change_kw: d.delta(),
+                })
+                .collect(),
+            violations: Vec::new(),
+        };
+        report.violations = report.check(&hours, config);
+        report
+    }
+
+    /// Relative change in energy over the common hours.
+    pub fn energy_change(&self) -> f64 {
+        (self.energy_b_kwh - self.energy_a_kwh) / self.energy_a_kwh.max(f64::EPSILON)
+    }
+
+    fn check(&self, hours: &[HourDiff], config: &DiffConfig) -> Vec<String> {
+        let mut violations = Vec::new();
+        if self.common_hours == 0 {
+            violations.push("The forecasts have no hours in common".to_string());
+        }
+        if let Some(limit) = config.max_change_kw
+            && self.max_abs_change_kw > limit
+        {
+            let over = hours.iter().filter(|d| d.delta().abs() > limit).count();
+            let hours = if over == 1 {
+                "1 hour".to_string()
+            } else {
+                format!("{} hours", over)
+            };
+            violations.push(format!(
+                "{} changed by more than {} kW, up to {:.1} kW",
+                hours, limit, self.max_abs_change_kw
+            ));
+        }
+        if let Some(limit) = config.max_energy_change
+            && self.energy_change().abs() > limit
+        {
+            violations.push(format!(
+                "Energy changed by {:+.1}%, more than {:.1}%",
+                100.0 * self.energy_change(),
+                100.0 * limit
+            ));
+        }
+        violations
+    }
+
+    /// The report for stdout.
+    pub fn text(&self, a: &str, b: &str) -> String {
+        let mut text = format!("Timestamp | {} | {} | Δ kW\n", a, b);
+        for h in &self.largest {
+            text.push_str(&format!(
+                "{} | {:.1} | {:.1} | {:+.1}\n",
+                h.timestamp, h.a, h.b, h.change_kw
+            ));
+        }
+        text.push_str(&format!(
+            "{} common hours ({} only in {}, {} only in {}), mean |Δ| {:.2} kW, max |Δ| {:.2} kW\n",
+            self.common_hours,
+            self.only_a,
+            a,
+            self.only_b,
+            b,
+            self.mean_abs_change_kw,
+            self.max_abs_change_kw
+        ));
+        text.push_str(&format!(
+            "Energy {:.0} → {:.0} kWh ({:+.1}%), peak {:.1} → {:.1} kW\n",
+            self.energy_a_kwh,
+            self.energy_b_kwh,
+            100.0 * self.energy_change(),
+            self.peak_a_kw,
+            self.peak_b_kw
+        ));
+        text
+    }
+}
@@ -0,0 +1,206 @@
+//! Probability that demand exceeds a threshold, for each forecast step: the
+//! question a grid operator asks of a connection limit.
+//!
+//! The predictive distribution is the one [`crate::scenarios`] draws from: a
+//! normal on each side of the point forecast, with the spread the interval
+//! bound on that side implies. Demand doesn't go below zero, so a threshold
+//! at or below zero is always exceeded.
+//!
+//! With `[curtailment]`, the same distribution sizes pre-emptive load
+//! shedding: each hour's curtailment is what it takes to bring the chance of
+//! going over the site limit down to the risk tolerance, i.e. how far the
+//! demand exceeded with that probability is above the limit.
+
+use csv::Writer;
+use std::error::Error;
+
+use crate::data::format_timestamp;
+use crate::export;
+use crate::forecast::Forecast;
+use crate::metrics::{normal_cdf, normal_quantile};
+use crate::preprocess::HOUR;
+
+/// Probability of exceeding `threshold_kw` at each step of a forecast.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exceedance {
+    pub threshold_kw: f64,
+    pub probabilities: Vec<f64>,
+}
+
+impl Exceedance {
+    /// The step most likely to exceed the threshold, as `(index, probability)`.
+    pub fn highest(&self) -> Option<(usize, f64)> {
+        self.probabilities
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Expected hours above the threshold: the probabilities summed, in steps of `step` seconds.
+    pub fn expected_hours(&self, step: i64) -> f64 {
+        self.probabilities.iter().sum::<f64>() * step as f64 / HOUR as f64
+    }
+}
+
+/// Probability that demand exceeds `threshold_kw`, given the point forecast and
+/// the `lower`..`upper` interval of `interval_width`.
+pub fn probability(
+    point: f64,
+    lower: f64,
+    upper: f64,
+    interval_width: f64,
+    threshold_kw: f64,
+) -> f64 {
+    if threshold_kw < 0.0 {
+        return 1.0;
+    }
+    let z = normal_quantile((1.0 + interval_width) / 2.0).max(f64::EPSILON);
+    let spread = if threshold_kw >= point {
+        upper - point
+    } else {
+        point - lower
+    };
+    match spread > 0.0 {
+        true => 1.0 - normal_cdf((threshold_kw - point) * z / spread),
+        // No spread on that side: the point forecast is all there is
+        false => f64::from(point > threshold_kw),
+    }
+}
+
+/// The demand exceeded with probability `1 - p` (the inverse of [`probability`]).
+pub fn quantile(point: f64, lower: f64, upper: f64, interval_width: f64, p: f64) -> f64 {
+    let z = normal_quantile((1.0 + interval_width) / 2.0).max(f64::EPSILON);
+    let e = normal_quantile(p);
+    let spread = if e >= 0.0 {
+        upper - point
+    } else {
+        point - lower
+    };
+    (point + spread / z * e).max(0.0)
+}
+
+/// Exceedance probabilities of each threshold over the forecast.
+pub fn curves(forecast: &Forecast, interval_width: f64, thresholds_kw: &[f64]) -> Vec<Exceedance> {
+    thresholds_kw
+        .iter()
+        .map(|threshold_kw| Exceedance {
+            threshold_kw: *threshold_kw,
+            probabilities: (0..forecast.len())
+                .map(|i| {
+                    probability(
+                        forecast.point[i],
+                        forecast.lower[i],
+                        forecast.upper[i],
+                        interval_width,
+                        *threshold_kw,
+                    )
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Recommended curtailment of each step, in kW.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curtailment {
+    pub limit_kw: f64,
+    pub risk_tolerance: f64,
+    /// Probability of exceeding the limit without curtailment.
+    pub before: Vec<f64>,
+    /// Load to shed.
+    pub curtail_kw: Vec<f64>,
+}
+
+impl Curtailment {
+    /// Size the load shedding that keeps each step's chance of exceeding
+    /// `limit_kw` at or below `risk_tolerance`.
+    pub fn new(
+        forecast: &Forecast,
+        interval_width: f64,
+        limit_kw: f64,
+        risk_tolerance: f64,
+    ) -> Self {
+        let (point, lower, upper) = (&forecast.point, &forecast.lower, &forecast.upper);
+        Self {
+            limit_kw,
+            risk_tolerance,
+            before: (0..forecast.len())
+                .map(|i| probability(point[i], lower[i], upper[i], interval_width, limit_kw))
+                .collect(),
+            curtail_kw: (0..forecast.len())
+                .map(|i| {
+                    (quantile(
+                        point[i],
+                        lower[i],
+                        upper[i],
+                        interval_width,
+                        1.0 - risk_tolerance,
+                    ) - limit_kw)
+                        .max(0.0)
+                })
+                .collect(),
+        }
+    }
+
+    /// Probability of exceeding the limit after curtailing: at most the risk tolerance.
+    pub fn after(&self) -> Vec<f64> {
+        self.before
+            .iter()
+            .map(|p| p.min(self.risk_tolerance))
+            .collect()
+    }
+
+    /// Steps with any curtailment, and the energy shed over them (kWh) in steps of `step` seconds.
+    pub fn totals(&self, step: i64) -> (usize, f64) {
+        let steps = self.curtail_kw.iter().filter(|kw| **kw > 0.0).count();
+        (
+            steps,
+            self.curtail_kw.iter().sum::<f64>() * step as f64 / HOUR as f64,
+        )
+    }
+
+    /// Write as CSV: `timestamp,p_exceed,curtail_kw,p_exceed_after`.
+    pub fn write_csv(&self, forecast: &Forecast, path: &str) -> Result<(), Box<dyn Error>> {
+        let mut wtr = Writer::from_writer(export::create(path)?);
+        wtr.write_record(["timestamp", "p_exceed", "curtail_kw", "p_exceed_after"])?;
+        for ((ts, before), (kw, after)) in forecast
+            .timestamps
+            .iter()
+            .zip(&self.before)
+            .zip(self.curtail_kw.iter().zip(self.after()))
+        {
+            wtr.write_record([
+                format_timestamp(*ts),
+                format!("{:.4}", before),
+                format!("{:.2}", kw),
+                format!("{:.4}", after),
+            ])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+/// Write as CSV: `timestamp,p_exceed_<threshold>kw,...`.
+pub fn write_csv(
+    forecast: &Forecast,
+    curves: &[Exceedance],
+    path: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_writer(export::create(path)?);
+    let mut header = vec!["timestamp".to_string()];
+    header.extend(
+        curves
+            .iter()
+            .map(|c| format!("p_exceed_{}kw", c.threshold_kw)),
+    );
+    wtr.write_record(&header)?;
+    for i in 0..forecast.len() {
+        let mut row = vec![format_timestamp(forecast.timestamps[i])];
+        row.extend(curves.iter().map(|c| format!("{:.4}", c.probabilities[i])));
+        wtr.write_record(&row)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
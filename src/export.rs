@@ -0,0 +1,260 @@
+use chrono::{DateTime, SecondsFormat};
+use chrono_tz::Tz;
+use csv::Writer;
+use serde_json::{Value, json};
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+
+use crate::cim::CimExporter;
+use crate::config::{ExportFormat, OutputConfig};
+use crate::data::{STDIO, Series, format_timestamp};
+use crate::forecast::{Changepoint, Decomposition, Forecast};
+
+/// Create an output file, or write to stdout for [`STDIO`].
+pub fn create(path: impl AsRef<Path>) -> Result<Box<dyn Write>, Box<dyn Error>> {
+    let path = path.as_ref();
+    if path == Path::new(STDIO) {
+        return Ok(Box::new(std::io::stdout().lock()));
+    }
+    let file = std::fs::File::create(path)
+        .map_err(|e| format!("Could not create {}: {}", path.display(), e))?;
+    Ok(Box::new(std::io::BufWriter::new(file)))
+}
+
+/// Write `contents` to a file, or to stdout for [`STDIO`].
+pub fn write_text(path: impl AsRef<Path>, contents: &str) -> Result<(), Box<dyn Error>> {
+    let mut out = create(path)?;
+    out.write_all(contents.as_bytes())?;
+    out.flush()?;
+    Ok(())
+}
+
+/// What a forecast export may say about the forecast besides its hours.
+#[derive(Debug, Clone, Default)]
+pub struct ExportContext {
+    pub site: String,
+    pub model_version: String,
+    /// When the forecast was made, in UTC seconds.
+    pub issued_at: i64,
+    /// Timezone of the forecast's wall-clock hours; UTC when unset.
+    pub timezone: Option<Tz>,
+}
+
+/// A file format forecasts are written in.
+///
+/// [`exporter`] builds the ones `[[output.exports]]` can name; others can be
+/// written with [`export`] as well.
+pub trait Exporter {
+    /// Format name, for messages.
+    fn name(&self) -> &'static str;
+
+    fn write(
+        &self,
+        forecast: &Forecast,
+        context: &ExportContext,
+        out: &mut dyn Write,
+    ) -> Result<(), Box<dyn Error>>;
+}
+
+/// `timestamp,yhat,yhat_lower,yhat_upper` (kW).
+pub struct CsvExporter;
+
+impl Exporter for CsvExporter {
+    fn name(&self) -> &'static str {
+        "CSV"
+    }
+
+    fn write(
+        &self,
+        forecast: &Forecast,
+        _: &ExportContext,
+        out: &mut dyn Write,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut wtr = Writer::from_writer(out);
+        wtr.write_record(["timestamp", "yhat", "yhat_lower", "yhat_upper"])?;
+        for i in 0..forecast.len() {
+            wtr.write_record([
+                format_timestamp(forecast.timestamps[i]),
+                format!("{:.3}", forecast.point[i]),
+                format!("{:.3}", forecast.lower[i]),
+                format!("{:.3}", forecast.upper[i]),
+            ])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+/// [`forecast_json`] with the site, model version and issue time.
+pub struct JsonExporter;
+
+impl Exporter for JsonExporter {
+    fn name(&self) -> &'static str {
+        "JSON"
+    }
+
+    fn write(
+        &self,
+        forecast: &Forecast,
+        context: &ExportContext,
+        out: &mut dyn Write,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut json = forecast_json(forecast);
+        json["site"] = json!(context.site);
+        json["model_version"] = json!(context.model_version);
+        json["issued_at"] = json!(
+            DateTime::from_timestamp(context.issued_at, 0)
+                .unwrap_or_default()
+                .to_rfc3339_opts(SecondsFormat::Secs, true)
+        );
+        serde_json::to_writer_pretty(&mut *out, &json)?;
+        out.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+/// The exporter of `format`.
+pub fn exporter(format: ExportFormat, output: &OutputConfig) -> Box<dyn Exporter> {
+    match format {
+        ExportFormat::Csv => Box::new(CsvExporter),
+        ExportFormat::Json => Box::new(JsonExporter),
+        ExportFormat::Cim => Box::new(CimExporter::new(output.cim.clone())),
+    }
+}
+
+/// Write `forecast` to a file, or to stdout for [`STDIO`], with `exporter`.
+pub fn export(
+    exporter: &dyn Exporter,
+    forecast: &Forecast,
+    context: &ExportContext,
+    path: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut out = create(path)?;
+    exporter.write(forecast, context, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Write a forecast as CSV: `timestamp,yhat,yhat_lower,yhat_upper` (kW).
+pub fn write_forecast_csv(forecast: &Forecast, path: &str) -> Result<(), Box<dyn Error>> {
+    export(&CsvExporter, forecast, &ExportContext::default(), path)?;
+    eprintln!("Forecast written to {}", path);
+    Ok(())
+}
+
+/// Write an in-sample decomposition as CSV: `timestamp,actual,fitted,fitted_lower,fitted_upper,trend`
+/// and one column per component, next to the `history` it was fitted on.
+pub fn write_decomposition_csv(
+    decomposition: &Decomposition,
+    history: &Series,
+    path: &str,
+) -> Result<(), Box<dyn Error>> {
+    let fitted = &decomposition.fitted;
+    let mut wtr = Writer::from_writer(create(path)?);
+    let mut header = vec![
+        "timestamp",
+        "actual",
+        "fitted",
+        "fitted_lower",
+        "fitted_upper",
+        "trend",
+    ];
+    header.extend(
+        decomposition
+            .components
+            .iter()
+            .map(|(name, _)| name.as_str()),
+    );
+    wtr.write_record(&header)?;
+    for i in 0..fitted.len() {
+        let timestamp = fitted.timestamps[i];
+        let actual = history
+            .timestamps
+            .binary_search(&timestamp)
+            .map(|j| history.values[j]);
+        let mut record = vec![
+            format_timestamp(timestamp),
+            actual.map_or(String::new(), |v| format!("{:.3}", v)),
+            format!("{:.3}", fitted.point[i]),
+            format!("{:.3}", fitted.lower[i]),
+            format!("{:.3}", fitted.upper[i]),
+            format!("{:.4}", decomposition.trend[i]),
+        ];
+        record.extend(
+            decomposition
+                .components
+                .iter()
+                .map(|(_, values)| format!("{:.4}", values[i])),
+        );
+        wtr.write_record(&record)?;
+    }
+    wtr.flush()?;
+    eprintln!("Decomposition written to {}", path);
+    Ok(())
+}
+
+/// Write trend changepoints as CSV: `timestamp,rate_change_per_day,delta,significant`.
+pub fn write_changepoints_csv(
+    changepoints: &[Changepoint],
+    path: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_writer(create(path)?);
+    wtr.write_record(["timestamp", "rate_change_per_day", "delta", "significant"])?;
+    for changepoint in changepoints {
+        wtr.write_record([
+            format_timestamp(changepoint.timestamp),
+            format!("{:.4}", changepoint.rate_change_per_day),
+            format!("{:.5}", changepoint.delta),
+            changepoint.is_significant().to_string(),
+        ])?;
+    }
+    wtr.flush()?;
+    eprintln!("Changepoints written to {}", path);
+    Ok(())
+}
+
+/// A forecast as JSON: `{"points": [{"timestamp", "yhat", "yhat_lower", "yhat_upper"}, ...]}`.
+pub fn forecast_json(forecast: &Forecast) -> Value {
+    let points: Vec<Value> = (0..forecast.len())
+        .map(|i| {
+            json!({
+                "timestamp": format_timestamp(forecast.timestamps[i]),
+                "yhat": forecast.point[i],
+                "yhat_lower": forecast.lower[i],
+                "yhat_upper": forecast.upper[i],
+            })
+        })
+        .collect();
+    json!({ "points": points })
+}
+
+/// What each forecast hour is made of, as JSON: `{"units", "points": [{"timestamp",
+/// "yhat", "trend", "components": [{"name", "mode", "value", "contribution"}, ...]}, ...]}`.
+///
+/// A component's contribution is what it adds to the trend, largest first;
+/// the trend plus the contributions make the unclamped `yhat` in `units`.
+pub fn attribution_json(attribution: &Decomposition, units: &str) -> Value {
+    let fitted = &attribution.fitted;
+    let points: Vec<Value> = (0..fitted.len())
+        .map(|i| {
+            let mut contributions = attribution.contributions(i);
+            contributions.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()));
+            let components: Vec<Value> = contributions
+                .into_iter()
+                .map(|(name, contribution)| {
+                    let (_, values) = attribution.components.iter().find(|(n, _)| n == name).unwrap();
+                    let mode = if attribution.multiplicative.iter().any(|m| m == name) { "multiplicative" } else { "additive" };
+                    json!({ "name": name, "mode": mode, "value": values[i], "contribution": contribution })
+                })
+                .collect();
+            json!({
+                "timestamp": format_timestamp(fitted.timestamps[i]),
+                "yhat": fitted.point[i],
+                "trend": attribution.trend[i],
+                "components": components,
+            })
+        })
+        .collect();
+    json!({ "units": units, "points": points })
+}
@@ -0,0 +1,1001 @@
+use augurs::prophet::optimizer::{
+    self, Data, InitialParams, OptimizeOpts, OptimizedParams, Optimizer,
+};
+use augurs::prophet::{
+    FeatureMode, GrowthType, IntervalWidth, PositiveFloat, PredictionData, Predictions, Prophet,
+    ProphetOptions, Regressor, SeasonalityOption, TrainingData,
+};
+use chrono::{DateTime, Datelike, TimeDelta, TimeZone};
+use chrono_tz::Tz;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::StandardNormal;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::num::NonZeroU32;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::anomaly;
+use crate::baseline::{SeasonalNaive, WeeklyAverage};
+use crate::calendar::Calendar;
+use crate::config::{
+    CalendarConfig, Fallback, Growth, Method, ModelConfig, OptimizerConfig, RobustConfig,
+    SeasonalityMode, Transform,
+};
+use crate::convergence::{self, OptimizerDiagnostics};
+use crate::data::Series;
+use crate::fourier::FourierRegression;
+use crate::intermittent::Tsb;
+use crate::lags::LagFeatures;
+use crate::partial;
+use crate::preprocess::HOUR;
+use crate::prices::{self, Prices};
+use crate::transform::{self, Transformed};
+
+/// A forecast over a set of future timestamps, in kW.
+///
+/// `lower` and `upper` bound the uncertainty interval configured by
+/// `ModelConfig::interval_width`. Demand can't go negative, so all three are
+/// clamped at zero.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Forecast {
+    pub timestamps: Vec<i64>,
+    pub point: Vec<f64>,
+    pub lower: Vec<f64>,
+    pub upper: Vec<f64>,
+}
+
+impl Forecast {
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    /// Seconds between the first two points, as [`Series::step`]: an hour,
+    /// or the `data.step_minutes` grid of the history it was fitted on.
+    pub fn step(&self) -> i64 {
+        match self.timestamps.get(..2) {
+            Some([first, second]) => second - first,
+            _ => HOUR,
+        }
+    }
+
+    /// Hours each point lasts, turning its kW into kWh.
+    pub fn step_hours(&self) -> f64 {
+        self.step() as f64 / HOUR as f64
+    }
+}
+
+/// Identifies the crate version and model settings behind a forecast, e.g. `0.1.0+5f3a9c21`.
+///
+/// The suffix is an FNV-1a hash of the model config, so it is stable across
+/// runs and builds and changes whenever a model setting does.
+pub fn model_version(model: &ModelConfig) -> String {
+    let text = toml::to_string(model).unwrap_or_default();
+    let hash = text.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{}+{:08x}", env!("CARGO_PKG_VERSION"), hash as u32)
+}
+
+/// The next `horizon_hours` hours after `last_timestamp`, as local wall-clock timestamps.
+///
+/// Without a timezone every local hour is assumed to exist exactly once. With
+/// one, the grid follows real hours across DST changes: the hour skipped in
+/// spring is left out and the hour repeated in autumn appears once, the same
+/// way sessions are binned into the history.
+pub fn future_timestamps(
+    last_timestamp: i64,
+    horizon_hours: u32,
+    timezone: Option<Tz>,
+) -> Vec<i64> {
+    future_steps(last_timestamp, horizon_hours, HOUR, timezone)
+}
+
+/// [`future_timestamps`] in steps of `step` seconds, e.g. the 5-minute grid
+/// of a `data.step_minutes = 5` history.
+pub fn future_steps(
+    last_timestamp: i64,
+    horizon_hours: u32,
+    step: i64,
+    timezone: Option<Tz>,
+) -> Vec<i64> {
+    let n_steps = horizon_hours as i64 * HOUR / step;
+    let Some(tz) = timezone else {
+        return (1..=n_steps).map(|i| last_timestamp + i * step).collect();
+    };
+    let local = DateTime::from_timestamp(last_timestamp, 0)
+        .unwrap_or_default()
+        .naive_utc();
+    // An hour-long step back lands before a DST gap if `local` falls inside one
+    let Some(start) = tz.from_local_datetime(&local).latest().or_else(|| {
+        tz.from_local_datetime(&(local - TimeDelta::hours(1)))
+            .latest()
+            .map(|t| t + TimeDelta::hours(1))
+    }) else {
+        return future_steps(last_timestamp, horizon_hours, step, None);
+    };
+
+    let mut timestamps: Vec<i64> = Vec::new();
+    for i in 1..=n_steps {
+        let ts = (start + TimeDelta::seconds(i * step))
+            .naive_local()
+            .and_utc()
+            .timestamp();
+        // The repeated hour in autumn comes round again step by step
+        if ts > last_timestamp && timestamps.last().is_none_or(|last| ts > *last) {
+            timestamps.push(ts);
+        }
+    }
+    timestamps
+}
+
+fn seasonality_option(enabled: bool, fourier_order: Option<u32>) -> SeasonalityOption {
+    match fourier_order.and_then(NonZeroU32::new) {
+        Some(order) if enabled => SeasonalityOption::Fourier(order),
+        _ => SeasonalityOption::Manual(enabled),
+    }
+}
+
+/// Translate our model config into Prophet options.
+pub fn prophet_options(model: &ModelConfig) -> Result<ProphetOptions, Box<dyn Error>> {
+    Ok(ProphetOptions {
+        growth: match model.growth {
+            Growth::Linear => GrowthType::Linear,
+            Growth::Flat => GrowthType::Flat,
+        },
+        seasonality_mode: match model.seasonality_mode {
+            SeasonalityMode::Additive => FeatureMode::Additive,
+            SeasonalityMode::Multiplicative => FeatureMode::Multiplicative,
+        },
+        daily_seasonality: seasonality_option(model.daily_seasonality, model.daily_fourier_order),
+        weekly_seasonality: seasonality_option(
+            model.weekly_seasonality,
+            model.weekly_fourier_order,
+        ),
+        yearly_seasonality: seasonality_option(
+            model.yearly_seasonality,
+            model.yearly_fourier_order,
+        ),
+        interval_width: IntervalWidth::try_new(model.interval_width)?,
+        ..Default::default()
+    })
+}
+
+/// Changepoint times (Prophet's scaled `t_change`) and their fitted `delta`s.
+type TrendChanges = Arc<Mutex<Option<(Vec<f64>, Vec<f64>)>>>;
+
+/// The optimizer behind a Prophet fit, chosen at runtime.
+///
+/// It also keeps the fitted trend changes, which `Prophet` doesn't expose,
+/// and fails a fit that [`convergence::diagnose`] finds didn't converge.
+/// With a pinned trend it doesn't run `inner` at all, see [`partial::fit_on_trend`].
+#[derive(Debug)]
+pub struct BoxedOptimizer {
+    pub inner: Box<dyn Optimizer>,
+    trend_changes: TrendChanges,
+    diagnostics: Arc<Mutex<Option<OptimizerDiagnostics>>>,
+    /// The trend to keep, on Prophet's scaled values.
+    pinned_trend: Option<Vec<f64>>,
+    /// Scale and seed of the noise added to Prophet's starting values.
+    jitter: Option<(f64, u64)>,
+}
+
+impl BoxedOptimizer {
+    pub fn new(inner: Box<dyn Optimizer>) -> Self {
+        Self {
+            inner,
+            trend_changes: TrendChanges::default(),
+            diagnostics: Arc::default(),
+            pinned_trend: None,
+            jitter: None,
+        }
+    }
+}
+
+impl Optimizer for BoxedOptimizer {
+    fn optimize(
+        &self,
+        init: &InitialParams,
+        data: &Data,
+        opts: &OptimizeOpts,
+    ) -> Result<OptimizedParams, optimizer::Error> {
+        let params = match &self.pinned_trend {
+            Some(trend) => partial::fit_on_trend(data, trend)
+                .map_err(|e| optimizer::Error::string(e.to_string()))?,
+            None => {
+                let jittered = self.jitter.map(|(scale, seed)| jitter(init, scale, seed));
+                let init = jittered.as_ref().unwrap_or(init);
+                let started = Instant::now();
+                let params = self.inner.optimize(init, data, opts)?;
+                let diagnostics =
+                    convergence::diagnose(init, data, &params, started.elapsed().as_secs_f64());
+                if !diagnostics.converged() {
+                    return Err(optimizer::Error::string(format!(
+                        "Prophet did not converge: {}",
+                        diagnostics.problems.join("; ")
+                    )));
+                }
+                *self.diagnostics.lock().unwrap() = Some(diagnostics);
+                params
+            }
+        };
+        *self.trend_changes.lock().unwrap() = Some((data.t_change.clone(), params.delta.clone()));
+        Ok(params)
+    }
+}
+
+/// `init` with Gaussian noise of `scale` on each parameter, `sigma_obs` scaled by the exponent of its noise.
+fn jitter(init: &InitialParams, scale: f64, seed: u64) -> InitialParams {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut noise = || scale * rng.sample::<f64, _>(StandardNormal);
+    InitialParams {
+        k: init.k + noise(),
+        m: init.m + noise(),
+        delta: init.delta.iter().map(|d| d + noise()).collect(),
+        beta: init.beta.iter().map(|b| b + noise()).collect(),
+        sigma_obs: PositiveFloat::try_new(*init.sigma_obs * noise().exp())
+            .unwrap_or(init.sigma_obs),
+    }
+}
+
+/// Prophet's `delta` from which a changepoint counts as significant, as in Prophet's own plots.
+pub const CHANGEPOINT_THRESHOLD: f64 = 0.01;
+
+/// A trend changepoint of a Prophet fit.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Changepoint {
+    /// Local wall-clock seconds, rounded to the hour.
+    pub timestamp: i64,
+    /// Change in the trend's slope, in units of the fitted series (kW, or after
+    /// `model.transform`) per day.
+    pub rate_change_per_day: f64,
+    /// The same change as fitted, on Prophet's scaled time and values.
+    pub delta: f64,
+}
+
+impl Changepoint {
+    pub fn is_significant(&self) -> bool {
+        self.delta.abs() >= CHANGEPOINT_THRESHOLD
+    }
+}
+
+/// Changepoints from Prophet's scaled times and deltas, for a fit on `timestamps` and `values`.
+pub(crate) fn changepoints(
+    timestamps: &[i64],
+    values: &[f64],
+    t_change: &[f64],
+    delta: &[f64],
+) -> Vec<Changepoint> {
+    let (Some(first), Some(last)) = (timestamps.first(), timestamps.last()) else {
+        return Vec::new();
+    };
+    let span = (last - first) as f64;
+    // Prophet scales its target by the largest absolute value
+    let y_scale = values.iter().fold(0.0, |max: f64, v| max.max(v.abs()));
+    t_change
+        .iter()
+        .zip(delta)
+        .filter(|_| span > 0.0)
+        .map(|(t, delta)| Changepoint {
+            timestamp: first + ((t * span) / HOUR as f64).round() as i64 * HOUR,
+            rate_change_per_day: delta * y_scale / (span / 86_400.0),
+            delta: *delta,
+        })
+        .collect()
+}
+
+type OptimizerFactory = Rc<dyn Fn() -> Box<dyn Optimizer>>;
+
+thread_local! {
+    static OPTIMIZER: RefCell<Option<OptimizerFactory>> = const { RefCell::new(None) };
+}
+
+/// Fit Prophet with optimizers from `factory` on this thread, whatever `model.optimizer` says.
+///
+/// This is how a wasm32 build gets an optimizer at all, e.g. one implemented
+/// in JavaScript, and how tests fit with a mock.
+pub fn set_optimizer(factory: impl Fn() -> Box<dyn Optimizer> + 'static) {
+    OPTIMIZER.with_borrow_mut(|f| *f = Some(Rc::new(factory)));
+}
+
+/// The optimizer of [`set_optimizer`] or else `model.optimizer`.
+fn new_optimizer(model: &ModelConfig) -> Result<BoxedOptimizer, Box<dyn Error>> {
+    if let Some(factory) = OPTIMIZER.with_borrow(|f| f.clone()) {
+        return Ok(BoxedOptimizer::new(factory()));
+    }
+    let inner: Result<Box<dyn Optimizer>, Box<dyn Error>> = match &model.optimizer {
+        #[cfg(feature = "cmdstan")]
+        Some(OptimizerConfig::Cmdstan(cmdstan)) => augurs::prophet::cmdstan::CmdstanOptimizer::with_prophet_path(&cmdstan.prophet_path)
+            .map(|optimizer| Box::new(optimizer) as Box<dyn Optimizer>)
+            .map_err(|e| format!("model.optimizer: {}: {}", cmdstan.prophet_path, e).into()),
+        #[cfg(not(feature = "cmdstan"))]
+        Some(OptimizerConfig::Cmdstan(_)) => Err("model.optimizer type = \"cmdstan\" needs a build with the `cmdstan` feature".into()),
+        #[cfg(feature = "wasmstan")]
+        None | Some(OptimizerConfig::Wasmstan) => Ok(Box::new(augurs::prophet::wasmstan::WasmstanOptimizer::new())),
+        #[cfg(not(feature = "wasmstan"))]
+        None | Some(OptimizerConfig::Wasmstan) => {
+            Err("No Prophet optimizer: build with the `wasmstan` feature or call `forecast::set_optimizer`".into())
+        }
+    };
+    Ok(BoxedOptimizer::new(inner?))
+}
+
+/// A fitted Prophet model together with the calendar, lag and price regressors it was fitted with.
+pub struct ProphetModel {
+    pub prophet: Prophet<BoxedOptimizer>,
+    calendar: Calendar,
+    lags: Option<LagFeatures>,
+    prices: Option<Prices>,
+    /// Regressor names, in the order they were added.
+    regressors: Vec<String>,
+    /// Components that scale the trend rather than add to it.
+    multiplicative: Vec<String>,
+    changepoints: Vec<Changepoint>,
+    /// How the optimizer ended; `None` with a pinned trend.
+    diagnostics: Option<OptimizerDiagnostics>,
+}
+
+impl ProphetModel {
+    /// Values of the fitted regressors at the given timestamps.
+    fn features(
+        &self,
+        lags: Option<&LagFeatures>,
+        timestamps: &[i64],
+    ) -> HashMap<String, Vec<f64>> {
+        let mut features = self.calendar.features(timestamps);
+        if let Some(lags) = lags {
+            features.extend(lags.features(timestamps));
+        }
+        if let Some(prices) = &self.prices {
+            features.insert(prices::REGRESSOR.to_string(), prices.features(timestamps));
+        }
+        features
+            .into_iter()
+            .filter(|(name, _)| self.regressors.contains(name))
+            .collect()
+    }
+
+    /// The components of a prediction at `timestamps`, with lag regressors from `lags`.
+    fn attribute_batch(
+        &self,
+        lags: Option<&LagFeatures>,
+        timestamps: Vec<i64>,
+    ) -> Result<Decomposition, Box<dyn Error>> {
+        let mut data = PredictionData::new(timestamps.clone());
+        if !self.regressors.is_empty() {
+            data = data.with_regressors(self.features(lags, &timestamps))?;
+        }
+        Ok(self.components(self.prophet.predict(Some(data))?))
+    }
+
+    fn components(&self, predictions: Predictions) -> Decomposition {
+        let point = predictions.yhat.point;
+        let fitted = Forecast {
+            timestamps: predictions.ds,
+            lower: predictions.yhat.lower.unwrap_or_else(|| point.clone()),
+            upper: predictions.yhat.upper.unwrap_or_else(|| point.clone()),
+            point,
+        };
+        let mut components = vec![
+            ("additive_terms".to_string(), predictions.additive.point),
+            (
+                "multiplicative_terms".to_string(),
+                predictions.multiplicative.point,
+            ),
+        ];
+        // HashMaps: sort each group for a stable column order
+        for group in [
+            predictions.seasonalities,
+            predictions.holidays,
+            predictions.regressors,
+        ] {
+            let mut group: Vec<(String, Vec<f64>)> =
+                group.into_iter().map(|(name, p)| (name, p.point)).collect();
+            group.sort_by(|a, b| a.0.cmp(&b.0));
+            components.extend(group);
+        }
+        Decomposition {
+            fitted,
+            trend: predictions.trend.point,
+            components,
+            multiplicative: self.multiplicative.clone(),
+        }
+    }
+}
+
+/// Fit Prophet, with `model.robust` on the history with extreme hours pulled
+/// in; the history itself is left alone.
+pub fn fit_robust(
+    history: &Series,
+    model: &ModelConfig,
+    regressors: &Regressors,
+) -> Result<ProphetModel, Box<dyn Error>> {
+    fit_robust_jittered(history, model, regressors, None)
+}
+
+fn fit_robust_jittered(
+    history: &Series,
+    model: &ModelConfig,
+    regressors: &Regressors,
+    jitter: Option<(f64, u64)>,
+) -> Result<ProphetModel, Box<dyn Error>> {
+    let Some(robust) = &model.robust else {
+        return fit_prophet_pinned(history, model, regressors, None, jitter);
+    };
+    let (pulled, hours) = pull_in(history, robust);
+    if hours > 0 {
+        eprintln!("Robust fit: {} extreme hours pulled in", hours);
+    }
+    fit_prophet_pinned(&pulled, model, regressors, None, jitter)
+}
+
+/// Fit Prophet like [`fit_robust`] and, under `model.retry`, again from
+/// jittered starting values and then simplified while the optimizer fails
+/// or doesn't converge.
+pub fn fit_retrying(
+    history: &Series,
+    model: &ModelConfig,
+    regressors: &Regressors,
+) -> Result<ProphetModel, Box<dyn Error>> {
+    let mut error = match fit_robust(history, model, regressors) {
+        Ok(prophet) => return Ok(prophet),
+        Err(e) => e,
+    };
+    let Some(retry) = &model.retry else {
+        return Err(error);
+    };
+    for attempt in 1..=retry.attempts {
+        if !is_optimizer_failure(error.as_ref()) {
+            return Err(error);
+        }
+        eprintln!(
+            "⚠️  {}; retrying from jittered starting values ({} of {})",
+            error, attempt, retry.attempts
+        );
+        match fit_robust_jittered(
+            history,
+            model,
+            regressors,
+            Some((retry.jitter, attempt as u64)),
+        ) {
+            Ok(prophet) => return Ok(prophet),
+            Err(e) => error = e,
+        }
+    }
+    if retry.simplify && is_optimizer_failure(error.as_ref()) {
+        eprintln!(
+            "⚠️  {}; retrying with only additive daily and weekly seasonality",
+            error
+        );
+        return fit_robust(history, &simplified(model), &Regressors::default());
+    }
+    Err(error)
+}
+
+/// Whether `e` is the optimizer failing or not converging, rather than e.g. too short a history.
+fn is_optimizer_failure(e: &(dyn Error + 'static)) -> bool {
+    matches!(
+        e.downcast_ref::<augurs::prophet::Error>(),
+        Some(augurs::prophet::Error::OptimizationFailed(_))
+    )
+}
+
+/// `model` with its daily and weekly seasonality additive at Prophet's orders, and nothing else.
+fn simplified(model: &ModelConfig) -> ModelConfig {
+    ModelConfig {
+        seasonality_mode: SeasonalityMode::Additive,
+        yearly_seasonality: false,
+        daily_fourier_order: None,
+        weekly_fourier_order: None,
+        lags: Vec::new(),
+        rolling_means: Vec::new(),
+        calendar: CalendarConfig::default(),
+        prices: None,
+        ..model.clone()
+    }
+}
+
+/// `history` with each hour more than `robust.threshold` robust z-scores off
+/// its baseline clamped to that bound, and the number of hours clamped.
+///
+/// An hour's baseline is the median of the same hour of the week within
+/// `robust.window_weeks`, which a few extreme hours can't shift.
+fn pull_in(history: &Series, robust: &RobustConfig) -> (Series, usize) {
+    const WEEK: i64 = 168 * HOUR;
+    let weeks = robust.window_weeks as i64;
+    let baselines: Vec<f64> = history
+        .timestamps
+        .iter()
+        .map(|ts| {
+            let mut same_hours: Vec<f64> = (-weeks..=weeks)
+                .filter_map(|k| history.timestamps.binary_search(&(ts + k * WEEK)).ok())
+                .map(|j| history.values[j])
+                .collect();
+            same_hours.sort_by(f64::total_cmp);
+            let mid = same_hours.len() / 2;
+            match same_hours.len() % 2 {
+                0 => (same_hours[mid - 1] + same_hours[mid]) / 2.0,
+                _ => same_hours[mid],
+            }
+        })
+        .collect();
+    let residuals: Vec<f64> = history
+        .values
+        .iter()
+        .zip(&baselines)
+        .map(|(v, b)| v - b)
+        .collect();
+    // Night hours barely vary and daytime ones a lot, so each hour of the day gets its own spread
+    let hour_of_day = |i: usize| history.timestamps[i].rem_euclid(24 * HOUR) / HOUR;
+    let mut values = history.values.to_vec();
+    let mut hours = 0;
+    for hour in 0..24 {
+        let indices: Vec<usize> = (0..residuals.len())
+            .filter(|&i| hour_of_day(i) == hour)
+            .collect();
+        let Some((center, mad)) =
+            anomaly::spread(&indices.iter().map(|&i| residuals[i]).collect::<Vec<_>>())
+        else {
+            continue;
+        };
+        let bound = robust.threshold * mad / 0.6745;
+        for i in indices {
+            let clamped = residuals[i].clamp(center - bound, center + bound);
+            if clamped != residuals[i] {
+                values[i] = (baselines[i] + clamped).max(0.0);
+                hours += 1;
+            }
+        }
+    }
+    let pulled = Series {
+        values: values.into(),
+        ..history.clone()
+    };
+    (pulled, hours)
+}
+
+/// The holidays and day-ahead prices Prophet is fitted with as regressors.
+///
+/// Loading them may fetch from their APIs, so a run loads them once for its
+/// whole history and shares them between its fits: retries, the import and
+/// export channels, backtest folds.
+#[derive(Debug, Clone, Default)]
+pub struct Regressors {
+    pub calendar: Calendar,
+    pub prices: Option<Prices>,
+}
+
+impl Regressors {
+    /// The regressors `model` takes, for fits on `history` or any part of it.
+    ///
+    /// Holidays are loaded through the year after the history, to the end of
+    /// any horizon; prices through the day after it, the furthest day-ahead
+    /// prices reach. Methods that never fit Prophet load nothing.
+    pub fn load(history: &Series, model: &ModelConfig) -> Result<Self, Box<dyn Error>> {
+        let (Some(first), Some(last)) = (history.timestamps.first(), history.timestamps.last())
+        else {
+            return Ok(Self::default());
+        };
+        if matches!(model.method, Method::Tsb | Method::Fourier) {
+            return Ok(Self::default());
+        }
+        let date = |ts: i64| {
+            DateTime::from_timestamp(ts, 0)
+                .unwrap_or_default()
+                .date_naive()
+        };
+        let (first, last) = (date(*first), date(*last));
+        let calendar = Calendar::new(&model.calendar, first.year()..=last.year() + 1)?;
+        let prices = match &model.prices {
+            Some(config) => Some(Prices::load(
+                config,
+                first..=last.succ_opt().unwrap_or(last),
+            )?),
+            None => None,
+        };
+        Ok(Self { calendar, prices })
+    }
+}
+
+/// Fit Prophet on a history series.
+pub fn fit_prophet(history: &Series, model: &ModelConfig) -> Result<ProphetModel, Box<dyn Error>> {
+    let regressors = Regressors::load(history, model)?;
+    fit_prophet_pinned(history, model, &regressors, None, None)
+}
+
+/// Fit Prophet's seasonal and regressor coefficients on a history series,
+/// with the trend held at `trend` (kW at each hour of `history`).
+pub fn fit_prophet_on_trend(
+    history: &Series,
+    model: &ModelConfig,
+    trend: &[f64],
+    regressors: &Regressors,
+) -> Result<ProphetModel, Box<dyn Error>> {
+    if trend.len() != history.len() {
+        return Err(format!(
+            "A pinned trend needs a value for each of the {} hours, got {}",
+            history.len(),
+            trend.len()
+        )
+        .into());
+    }
+    fit_prophet_pinned(history, model, regressors, Some(trend), None)
+}
+
+fn fit_prophet_pinned(
+    history: &Series,
+    model: &ModelConfig,
+    regressors: &Regressors,
+    trend: Option<&[f64]>,
+    jitter: Option<(f64, u64)>,
+) -> Result<ProphetModel, Box<dyn Error>> {
+    // The first hours have no lag values to learn from
+    let lags = LagFeatures::new(model, history);
+    let per_hour = (HOUR / history.step()).max(1) as usize;
+    let warmup = lags
+        .as_ref()
+        .map_or(0, |l| l.warmup_hours() * per_hour)
+        .min(history.len());
+    let timestamps = &history.timestamps[warmup..];
+    let values = &history.values[warmup..];
+
+    // Ensure we have enough data points
+    let min_points = model.min_history_hours.max(2) * per_hour;
+    if timestamps.len() < min_points {
+        return Err(format!(
+            "Not enough data points for forecasting ({} hours, need {})",
+            timestamps.len() / per_hour,
+            (min_points + warmup) / per_hour
+        )
+        .into());
+    }
+
+    let mut optimizer = new_optimizer(model)?;
+    // Prophet scales its target by the largest absolute value
+    let y_scale = values.iter().fold(0.0, |max: f64, v| max.max(v.abs()));
+    optimizer.pinned_trend = trend.map(|trend| {
+        trend[warmup..]
+            .iter()
+            .map(|t| t / y_scale.max(f64::MIN_POSITIVE))
+            .collect()
+    });
+    optimizer.jitter = jitter;
+    let trend_changes = Arc::clone(&optimizer.trend_changes);
+    let diagnostics = Arc::clone(&optimizer.diagnostics);
+    let mut prophet = Prophet::new(prophet_options(model)?, optimizer);
+    let calendar = regressors.calendar.clone();
+    let mut features = calendar.features(timestamps);
+    let lag_names = lags.as_ref().map(|l| l.names()).unwrap_or_default();
+    if let Some(lags) = &lags {
+        features.extend(lags.features(timestamps));
+    }
+    // Only the prices published by the end of the history, through the day after it
+    let prices = match (&regressors.prices, timestamps.last()) {
+        (Some(prices), Some(last)) => {
+            Some(prices.until((last.div_euclid(24 * HOUR) + 2) * 24 * HOUR))
+        }
+        _ => None,
+    };
+    if let Some(prices) = &prices {
+        features.insert(prices::REGRESSOR.to_string(), prices.features(timestamps));
+    }
+
+    // The only copy of the history: Prophet needs it owned
+    let mut data = TrainingData::new(timestamps.to_vec(), values.to_vec())?;
+    let mut regressors = Vec::new();
+    let mut multiplicative = Vec::new();
+    if model.seasonality_mode == SeasonalityMode::Multiplicative {
+        multiplicative.extend(["daily", "weekly", "yearly"].map(String::from));
+    }
+    if !features.is_empty() {
+        // A regressor that never changes over the history (e.g. no holidays yet) can't be fitted
+        let features: HashMap<String, Vec<f64>> = features
+            .into_iter()
+            .filter(|(_, values)| values.iter().any(|v| *v != values[0]))
+            .collect();
+        for name in features.keys() {
+            // Lagged demand adds to the level rather than scaling the trend
+            let regressor = if model.seasonality_mode == SeasonalityMode::Multiplicative
+                && !lag_names.contains(name)
+            {
+                multiplicative.push(name.clone());
+                Regressor::multiplicative()
+            } else {
+                Regressor::additive()
+            };
+            prophet.add_regressor(name.clone(), regressor);
+            regressors.push(name.clone());
+        }
+        data = data.with_regressors(features)?;
+    }
+    let opts = OptimizeOpts {
+        seed: model.optimizer_seed,
+        ..Default::default()
+    };
+    prophet.fit(data, opts)?;
+    let (t_change, delta) = trend_changes.lock().unwrap().take().unwrap_or_default();
+    Ok(ProphetModel {
+        prophet,
+        calendar,
+        lags,
+        prices,
+        regressors,
+        multiplicative,
+        changepoints: changepoints(timestamps, values, &t_change, &delta),
+        diagnostics: diagnostics.lock().unwrap().take(),
+    })
+}
+
+/// Predict a fitted model at the given timestamps.
+///
+/// With lag regressors, hourly timestamps are predicted recursively: one
+/// shortest-lag-sized chunk at a time, with each chunk's point forecasts
+/// standing in for the demand the next chunk's lags refer to.
+pub fn predict(model: &ProphetModel, timestamps: Vec<i64>) -> Result<Forecast, Box<dyn Error>> {
+    let clamp = |v: Vec<f64>| v.into_iter().map(|x| x.max(0.0)).collect();
+    let fitted = attribute(model, timestamps)?.fitted;
+    Ok(Forecast {
+        timestamps: fitted.timestamps,
+        point: clamp(fitted.point),
+        lower: clamp(fitted.lower),
+        upper: clamp(fitted.upper),
+    })
+}
+
+/// The components of a prediction at the given timestamps, as [`predict`]
+/// makes it but without the clamping at zero.
+pub fn attribute(
+    model: &ProphetModel,
+    timestamps: Vec<i64>,
+) -> Result<Decomposition, Box<dyn Error>> {
+    let Some(lags) = &model.lags else {
+        return model.attribute_batch(None, timestamps);
+    };
+    let mut lags = lags.clone();
+    let mut attribution = Decomposition::default();
+    for chunk in timestamps.chunks(lags.chunk_hours()) {
+        let d = model.attribute_batch(Some(&lags), chunk.to_vec())?;
+        for (ts, p) in d.fitted.timestamps.iter().zip(&d.fitted.point) {
+            lags.remember(*ts, p.max(0.0));
+        }
+        attribution.append(d);
+    }
+    Ok(attribution)
+}
+
+/// A model's in-sample fit over its training hours, and what it attributes it to.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Decomposition {
+    /// In-sample predictions, in kW.
+    pub fitted: Forecast,
+    /// Trend, on the scale the model was fitted on (after `model.transform`).
+    pub trend: Vec<f64>,
+    /// Contributions on the trend's scale, by name: `additive_terms` and
+    /// `multiplicative_terms` (the sums), then each seasonality (`daily`,
+    /// `weekly`, `yearly`), holiday and regressor (e.g. `is_weekend`, `lag_24`).
+    pub components: Vec<(String, Vec<f64>)>,
+    /// Components that scale the trend (`trend * (1 + value)`) rather than add to it.
+    pub multiplicative: Vec<String>,
+}
+
+impl Decomposition {
+    /// Add the hours of `other`, a decomposition from the same model.
+    pub fn append(&mut self, other: Decomposition) {
+        if self.components.is_empty() {
+            *self = other;
+            return;
+        }
+        let fitted = &mut self.fitted;
+        fitted.timestamps.extend(other.fitted.timestamps);
+        fitted.point.extend(other.fitted.point);
+        fitted.lower.extend(other.fitted.lower);
+        fitted.upper.extend(other.fitted.upper);
+        self.trend.extend(other.trend);
+        for ((_, values), (_, more)) in self.components.iter_mut().zip(other.components) {
+            values.extend(more);
+        }
+    }
+
+    /// Each component's effect in the fitted units at hour `i`: its value for
+    /// additive components, `trend * value` for multiplicative ones.
+    pub fn contributions(&self, i: usize) -> Vec<(&str, f64)> {
+        self.components
+            .iter()
+            .filter(|(name, _)| name != "additive_terms" && name != "multiplicative_terms")
+            .map(|(name, values)| match self.multiplicative.contains(name) {
+                true => (name.as_str(), self.trend[i] * values[i]),
+                false => (name.as_str(), values[i]),
+            })
+            .collect()
+    }
+}
+
+/// A fitted model that can be evaluated at any hours, past (in-sample) or future.
+pub trait Forecaster {
+    /// Short method name for logs, e.g. `"prophet"`.
+    fn name(&self) -> &'static str;
+
+    fn predict(&self, timestamps: Vec<i64>) -> Result<Forecast, Box<dyn Error>>;
+
+    /// The in-sample fit and its components; only Prophet has components.
+    fn decompose(&self) -> Result<Decomposition, Box<dyn Error>> {
+        Err(format!("{} has no components to decompose", self.name()).into())
+    }
+
+    /// The components of the prediction at `timestamps`, like [`Forecaster::decompose`]
+    /// does in-sample.
+    fn attribute(&self, _timestamps: Vec<i64>) -> Result<Decomposition, Box<dyn Error>> {
+        Err(format!(
+            "{} has no components to attribute a forecast to",
+            self.name()
+        )
+        .into())
+    }
+
+    /// The trend changepoints, oldest first; only Prophet has a trend.
+    fn changepoints(&self) -> Vec<Changepoint> {
+        Vec::new()
+    }
+
+    /// How the optimizer ended; only Prophet has one.
+    fn optimizer(&self) -> Option<OptimizerDiagnostics> {
+        None
+    }
+}
+
+impl Forecaster for ProphetModel {
+    fn name(&self) -> &'static str {
+        "prophet"
+    }
+
+    fn predict(&self, timestamps: Vec<i64>) -> Result<Forecast, Box<dyn Error>> {
+        predict(self, timestamps)
+    }
+
+    fn decompose(&self) -> Result<Decomposition, Box<dyn Error>> {
+        decompose(self)
+    }
+
+    fn attribute(&self, timestamps: Vec<i64>) -> Result<Decomposition, Box<dyn Error>> {
+        attribute(self, timestamps)
+    }
+
+    fn changepoints(&self) -> Vec<Changepoint> {
+        self.changepoints.clone()
+    }
+
+    fn optimizer(&self) -> Option<OptimizerDiagnostics> {
+        self.diagnostics.clone()
+    }
+}
+
+/// Prophet's in-sample `predict(None)`, split into its components.
+///
+/// Unlike [`predict`], the fitted values aren't clamped at zero, so they add
+/// up from the components: `trend * (1 + multiplicative_terms) + additive_terms`.
+pub fn decompose(model: &ProphetModel) -> Result<Decomposition, Box<dyn Error>> {
+    Ok(model.components(model.prophet.predict(None)?))
+}
+
+/// Share of hours without any demand.
+pub fn zero_share(history: &Series) -> f64 {
+    let zeros = history.values.iter().filter(|v| **v <= 0.0).count();
+    zeros as f64 / history.len().max(1) as f64
+}
+
+/// Fit the method chosen by `model.method`, on the transformed target if `model.transform` is set.
+pub fn fit(history: &Series, model: &ModelConfig) -> Result<Box<dyn Forecaster>, Box<dyn Error>> {
+    fit_with(history, model, &Regressors::load(history, model)?)
+}
+
+/// [`fit`] with regressors already loaded (see [`Regressors::load`]).
+pub fn fit_with(
+    history: &Series,
+    model: &ModelConfig,
+    regressors: &Regressors,
+) -> Result<Box<dyn Forecaster>, Box<dyn Error>> {
+    let lambda = match model.transform {
+        Transform::None => return fit_method(history, model, regressors),
+        Transform::Log1p => 0.0,
+        Transform::BoxCox => model
+            .box_cox_lambda
+            .unwrap_or_else(|| transform::estimate_lambda(&history.values)),
+    };
+    let inner = fit_method(
+        &transform::forward_series(history, lambda),
+        model,
+        regressors,
+    )?;
+    Ok(Box::new(Transformed {
+        inner,
+        lambda,
+        interval_width: model.interval_width,
+    }))
+}
+
+/// The method `model.method` picks for `history`, resolving `auto`.
+pub fn resolve_method(history: &Series, model: &ModelConfig) -> Method {
+    match model.method {
+        Method::Auto if zero_share(history) > model.zero_share_threshold => Method::Tsb,
+        Method::Auto => Method::Prophet,
+        method => method,
+    }
+}
+
+/// Hours of `history` Prophet is trained on, after the lag warm-up.
+pub fn training_hours(history: &Series, model: &ModelConfig) -> usize {
+    let per_hour = (HOUR / history.step()).max(1) as usize;
+    let warmup = LagFeatures::new(model, history).map_or(0, |l| l.warmup_hours());
+    (history.len() / per_hour).saturating_sub(warmup)
+}
+
+fn fit_method(
+    history: &Series,
+    model: &ModelConfig,
+    regressors: &Regressors,
+) -> Result<Box<dyn Forecaster>, Box<dyn Error>> {
+    let method = resolve_method(history, model);
+    match method {
+        Method::Tsb => {
+            return Ok(Box::new(Tsb::fit(
+                history,
+                model.tsb_alpha,
+                model.tsb_beta,
+                model.interval_width,
+            )?));
+        }
+        Method::Fourier => return Ok(Box::new(FourierRegression::fit(history, model)?)),
+        _ => {}
+    }
+
+    // Degrade through the fallback chain rather than failing the whole run
+    let mut error = match fit_retrying(history, model, regressors) {
+        Ok(prophet) => return Ok(Box::new(prophet)),
+        Err(e) => e,
+    };
+    for fallback in &model.fallbacks {
+        let fitted: Result<Box<dyn Forecaster>, _> = match fallback {
+            Fallback::SeasonalNaive => SeasonalNaive::fit(history, model.interval_width)
+                .map(|m| Box::new(m) as Box<dyn Forecaster>),
+            Fallback::WeeklyAverage => WeeklyAverage::fit(history, model.interval_width)
+                .map(|m| Box::new(m) as Box<dyn Forecaster>),
+            Fallback::Fourier => {
+                FourierRegression::fit(history, model).map(|m| Box::new(m) as Box<dyn Forecaster>)
+            }
+        };
+        match fitted {
+            Ok(forecaster) => {
+                eprintln!("⚠️  {}; falling back to {}", error, forecaster.name());
+                return Ok(forecaster);
+            }
+            Err(e) => error = e,
+        }
+    }
+    Err(error)
+}
+
+/// Fit on `history` and forecast `horizon_hours` past its last timestamp, on
+/// the history's grid.
+pub fn forecast_series(
+    history: &Series,
+    model: &ModelConfig,
+    horizon_hours: u32,
+    timezone: Option<Tz>,
+) -> Result<Forecast, Box<dyn Error>> {
+    let forecaster = fit(history, model)?;
+    let last_timestamp = *history.timestamps.last().ok_or("Empty history")?;
+    forecaster.predict(future_steps(
+        last_timestamp,
+        horizon_hours,
+        history.step(),
+        timezone,
+    ))
+}
@@ -0,0 +1,185 @@
+//! Append-only forecast log: every issued forecast point as one JSON line,
+//! for the audit trail of flexibility-market compliance.
+//!
+//! Unlike the [`crate::archive`], which keeps one file per run, the log is
+//! only ever appended to, each run's lines written and synced in one go.
+//! Before a run would take it past `max_bytes`, the log is rotated like
+//! logrotate does: `forecasts.jsonl` becomes `forecasts.jsonl.1`, the `.1`
+//! becomes `.2` and so on, the oldest beyond `keep` being deleted.
+
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::archive::RUN_ID_FORMAT;
+use crate::config::{Config, ForecastLogConfig};
+use crate::data::format_timestamp;
+use crate::forecast::{Forecast, model_version};
+use crate::remote;
+
+/// One issued forecast point.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub site: String,
+    /// The issue's run id, as the [`crate::archive`] names it.
+    pub run_id: String,
+    /// When the forecast was issued, RFC 3339 in UTC.
+    pub issue_time: String,
+    /// The hour forecast, `YYYY-MM-DD HH:MM` local time.
+    pub target_time: String,
+    /// `target_time` in UTC, with a site `timezone`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_utc: Option<String>,
+    pub yhat: f64,
+    pub yhat_lower: f64,
+    pub yhat_upper: f64,
+    /// [`crate::forecast::model_version`]: crate version and model config hash.
+    pub model_version: String,
+}
+
+/// The log lines of `forecast`, issued at `issued_at`.
+pub fn entries(
+    site: &str,
+    issued_at: DateTime<Utc>,
+    forecast: &Forecast,
+    model_version: &str,
+    timezone: Option<Tz>,
+) -> Vec<LogEntry> {
+    let issue_time = issued_at.to_rfc3339_opts(SecondsFormat::Secs, true);
+    let run_id = issued_at.format(RUN_ID_FORMAT).to_string();
+    (0..forecast.len())
+        .map(|i| {
+            let target = forecast.timestamps[i];
+            let local = DateTime::from_timestamp(target, 0)
+                .unwrap_or_default()
+                .naive_utc();
+            LogEntry {
+                site: site.to_string(),
+                run_id: run_id.clone(),
+                issue_time: issue_time.clone(),
+                target_time: format_timestamp(target),
+                target_utc: timezone.and_then(|tz| utc(tz, &local)),
+                yhat: forecast.point[i],
+                yhat_lower: forecast.lower[i],
+                yhat_upper: forecast.upper[i],
+                model_version: model_version.to_string(),
+            }
+        })
+        .collect()
+}
+
+fn utc(timezone: Tz, local: &NaiveDateTime) -> Option<String> {
+    let time = timezone.from_local_datetime(local).earliest()?;
+    Some(
+        time.with_timezone(&Utc)
+            .to_rfc3339_opts(SecondsFormat::Secs, true),
+    )
+}
+
+/// The `n`th rotated file of the log at `path`; 0 is the log itself.
+pub fn rotated(path: &str, n: usize) -> PathBuf {
+    match n {
+        0 => PathBuf::from(path),
+        n => PathBuf::from(format!("{}.{}", path, n)),
+    }
+}
+
+/// Shift the rotated files of `path` up by one, deleting what falls past `keep`.
+fn rotate(path: &str, keep: usize) -> Result<(), Box<dyn Error>> {
+    let oldest = rotated(path, keep);
+    if keep == 0 || oldest.exists() {
+        std::fs::remove_file(&oldest)?;
+    }
+    for n in (0..keep).rev() {
+        let from = rotated(path, n);
+        if from.exists() {
+            std::fs::rename(&from, rotated(path, n + 1))?;
+        }
+    }
+    Ok(())
+}
+
+/// Held while appending, so the server's fit workers don't rotate the log under each other.
+static APPENDING: Mutex<()> = Mutex::new(());
+
+/// Log `forecast`, issued at `issued_at` for `config`'s site, if `[forecast_log]` is configured.
+pub fn log(
+    config: &Config,
+    issued_at: DateTime<Utc>,
+    forecast: &Forecast,
+) -> Result<(), Box<dyn Error>> {
+    let Some(log_config) = &config.forecast_log else {
+        return Ok(());
+    };
+    let entries = entries(
+        &config.site,
+        issued_at,
+        forecast,
+        &model_version(&config.model),
+        config.timezone,
+    );
+    append(log_config, &entries)?;
+    eprintln!(
+        "{} forecast points logged to {}",
+        entries.len(),
+        log_config.path
+    );
+    Ok(())
+}
+
+/// Append `entries` to the log, rotating it first when they'd take it past `config.max_bytes`.
+pub fn append(config: &ForecastLogConfig, entries: &[LogEntry]) -> Result<(), Box<dyn Error>> {
+    let _appending = APPENDING.lock().unwrap_or_else(|e| e.into_inner());
+    if remote::is_remote(&config.path) {
+        return Err(format!(
+            "forecast_log.path has to be a local file to append to, not {}",
+            config.path
+        )
+        .into());
+    }
+    let mut lines = Vec::new();
+    for entry in entries {
+        serde_json::to_writer(&mut lines, entry)?;
+        lines.push(b'\n');
+    }
+    let size = std::fs::metadata(&config.path)
+        .map(|m| m.len())
+        .unwrap_or_default();
+    if size > 0 && size + lines.len() as u64 > config.max_bytes {
+        rotate(&config.path, config.keep)?;
+    }
+    if let Some(dir) = Path::new(&config.path)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&config.path)?;
+    file.write_all(&lines)?;
+    file.sync_data()?;
+    Ok(())
+}
+
+/// The entries of one log file, oldest first.
+pub fn read(path: impl AsRef<Path>) -> Result<Vec<LogEntry>, Box<dyn Error>> {
+    let file = std::fs::File::open(path.as_ref())
+        .map_err(|e| format!("Could not open {}: {}", path.as_ref().display(), e))?;
+    let mut entries = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            entries.push(
+                serde_json::from_str(&line)
+                    .map_err(|e| format!("{} line {}: {}", path.as_ref().display(), i + 1, e))?,
+            );
+        }
+    }
+    Ok(entries)
+}
@@ -0,0 +1,303 @@
+//! A pure-Rust trend and seasonality fit: Prophet's additive model solved by
+//! least squares instead of Stan.
+//!
+//! The trend is piecewise linear, its changepoints spread over the first 80%
+//! of the history as Prophet spreads them, and the seasonalities are Prophet's
+//! Fourier terms at the model's orders. Gaussian priors stand in for Prophet's
+//! (Laplace on the trend changes), which makes the fit a ridge regression,
+//! solved again a few times as the noise estimate settles: fast and
+//! deterministic, and without the wasmstan runtime. Seasonality is always
+//! additive; calendar, lag and price regressors are left out.
+
+use std::error::Error;
+
+use crate::baseline::residual_offsets;
+use crate::config::{Growth, ModelConfig};
+use crate::data::Series;
+use crate::forecast::{self, Changepoint, Decomposition, Forecast, Forecaster};
+use crate::metrics::least_squares;
+use crate::preprocess::HOUR;
+
+/// Prophet's defaults: changepoints, the share of the history they spread
+/// over, and the prior scales of the trend changes and seasonal terms.
+const CHANGEPOINTS: usize = 25;
+const CHANGEPOINT_RANGE: f64 = 0.8;
+const CHANGEPOINT_PRIOR: f64 = 0.05;
+const SEASONALITY_PRIOR: f64 = 10.0;
+
+/// Fits, each weighing the priors against the noise the one before left.
+const RIDGE_ROUNDS: usize = 3;
+
+/// Smallest observation noise, on the scaled values.
+const MIN_SIGMA: f64 = 1e-6;
+
+/// A seasonality's name, period in seconds and Fourier order.
+pub(crate) type Seasonality = (&'static str, f64, u32);
+
+/// The seasonalities `model` enables, at their Fourier orders or Prophet's defaults.
+pub(crate) fn seasonalities(model: &ModelConfig) -> Vec<Seasonality> {
+    let seasonality = |name, days: f64, enabled: bool, order: Option<u32>, default| {
+        Some((name, days * 86_400.0, order.unwrap_or(default)))
+            .filter(|s: &Seasonality| enabled && s.2 > 0)
+    };
+    [
+        seasonality(
+            "daily",
+            1.0,
+            model.daily_seasonality,
+            model.daily_fourier_order,
+            4,
+        ),
+        seasonality(
+            "weekly",
+            7.0,
+            model.weekly_seasonality,
+            model.weekly_fourier_order,
+            3,
+        ),
+        seasonality(
+            "yearly",
+            365.25,
+            model.yearly_seasonality,
+            model.yearly_fourier_order,
+            10,
+        ),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Piecewise-linear trend plus Fourier seasonalities, fitted by least squares.
+#[derive(Debug, Clone)]
+pub struct FourierRegression {
+    /// The hours fitted on.
+    timestamps: Vec<i64>,
+    first: i64,
+    /// Seconds from the first to the last hour.
+    span: f64,
+    /// The largest absolute value of the history; values are fitted divided by it.
+    y_scale: f64,
+    growth: Growth,
+    /// Changepoints on the scaled time, 0 at the first hour and 1 at the last.
+    t_change: Vec<f64>,
+    seasonalities: Vec<Seasonality>,
+    /// Intercept, then with linear growth the slope and a rate change per changepoint, then the seasonal terms.
+    coefficients: Vec<f64>,
+    offsets: (f64, f64),
+    changepoints: Vec<Changepoint>,
+}
+
+impl FourierRegression {
+    pub fn fit(history: &Series, model: &ModelConfig) -> Result<Self, Box<dyn Error>> {
+        let first = *history.timestamps.first().ok_or("Empty history")?;
+        let last = *history.timestamps.last().ok_or("Empty history")?;
+        let per_hour = (HOUR / history.step()).max(1) as usize;
+        let min_points = model.min_history_hours.max(2) * per_hour;
+        if history.len() < min_points {
+            return Err(format!(
+                "Not enough data points for a Fourier regression ({} hours, need {})",
+                history.len() / per_hour,
+                min_points / per_hour
+            )
+            .into());
+        }
+
+        let span = (last - first).max(1) as f64;
+        let scaled = |ts: i64| (ts - first) as f64 / span;
+        // As Prophet: at evenly spaced hours of the first 80%, the first hour itself excluded
+        let t_change: Vec<f64> = match model.growth {
+            Growth::Linear => {
+                let range = ((history.len() - 1) as f64 * CHANGEPOINT_RANGE).floor() as usize;
+                let count = CHANGEPOINTS.min(range);
+                (1..=count)
+                    .map(|j| scaled(history.timestamps[j * range / count]))
+                    .collect()
+            }
+            Growth::Flat => Vec::new(),
+        };
+        let seasonalities = seasonalities(model);
+        let y_scale = history
+            .values
+            .iter()
+            .fold(0.0, |max: f64, v| max.max(v.abs()))
+            .max(f64::MIN_POSITIVE);
+        let mut fitted = Self {
+            timestamps: history.timestamps.to_vec(),
+            first,
+            span,
+            y_scale,
+            growth: model.growth,
+            t_change,
+            seasonalities,
+            coefficients: Vec::new(),
+            offsets: (0.0, 0.0),
+            changepoints: Vec::new(),
+        };
+
+        let x: Vec<Vec<f64>> = history
+            .timestamps
+            .iter()
+            .map(|ts| fitted.row(*ts))
+            .collect();
+        let y: Vec<f64> = history.values.iter().map(|v| v / y_scale).collect();
+        let trend_columns = fitted.trend_columns();
+        let priors: Vec<Option<f64>> = (0..x[0].len())
+            .map(|j| match j {
+                0 => None,
+                1 if trend_columns > 1 => None,
+                j if j < trend_columns => Some(CHANGEPOINT_PRIOR),
+                _ => Some(SEASONALITY_PRIOR),
+            })
+            .collect();
+        let rms = |r: &[f64]| {
+            (r.iter().map(|v| v * v).sum::<f64>() / r.len().max(1) as f64)
+                .sqrt()
+                .max(MIN_SIGMA)
+        };
+        let mean = y.iter().sum::<f64>() / y.len() as f64;
+        let mut sigma = rms(&y.iter().map(|v| v - mean).collect::<Vec<_>>());
+        let mut residuals = Vec::new();
+        for _ in 0..RIDGE_ROUNDS {
+            let mut rows = x.clone();
+            let mut targets = y.clone();
+            for (j, prior) in priors.iter().enumerate() {
+                if let Some(prior) = prior {
+                    let mut row = vec![0.0; priors.len()];
+                    row[j] = sigma / prior;
+                    rows.push(row);
+                    targets.push(0.0);
+                }
+            }
+            fitted.coefficients = least_squares(&rows, &targets)
+                .ok_or("The trend and seasonal terms can't be solved for")?;
+            residuals = x
+                .iter()
+                .zip(&y)
+                .map(|(row, y)| y - dot(row, &fitted.coefficients))
+                .collect();
+            sigma = rms(&residuals);
+        }
+
+        fitted.offsets = residual_offsets(
+            residuals.iter().map(|r| r * y_scale).collect(),
+            model.interval_width,
+        );
+        let delta = fitted.coefficients[2.min(trend_columns)..trend_columns].to_vec();
+        fitted.changepoints = forecast::changepoints(
+            &history.timestamps,
+            &history.values,
+            &fitted.t_change,
+            &delta,
+        );
+        Ok(fitted)
+    }
+
+    /// Intercept, slope and one rate change per changepoint with linear growth; the intercept alone if flat.
+    fn trend_columns(&self) -> usize {
+        match self.growth {
+            Growth::Linear => 2 + self.t_change.len(),
+            Growth::Flat => 1,
+        }
+    }
+
+    /// The regression's terms at `ts`.
+    fn row(&self, ts: i64) -> Vec<f64> {
+        let t = (ts - self.first) as f64 / self.span;
+        let mut row = vec![1.0];
+        if self.growth == Growth::Linear {
+            row.push(t);
+            row.extend(self.t_change.iter().map(|c| (t - c).max(0.0)));
+        }
+        for (_, period, order) in &self.seasonalities {
+            let phase = 2.0 * std::f64::consts::PI * (ts - self.first) as f64 / period;
+            for n in 1..=*order {
+                row.push((n as f64 * phase).sin());
+                row.push((n as f64 * phase).cos());
+            }
+        }
+        row
+    }
+
+    /// Trend and seasonalities at `timestamps`, in kW, with intervals that aren't clamped at zero.
+    fn components(&self, timestamps: Vec<i64>) -> Decomposition {
+        let trend_columns = self.trend_columns();
+        let mut trend = Vec::with_capacity(timestamps.len());
+        let mut seasonal: Vec<Vec<f64>> =
+            vec![Vec::with_capacity(timestamps.len()); self.seasonalities.len()];
+        for ts in &timestamps {
+            let row = self.row(*ts);
+            let term = |range: std::ops::Range<usize>| {
+                dot(&row[range.clone()], &self.coefficients[range]) * self.y_scale
+            };
+            trend.push(term(0..trend_columns));
+            let mut start = trend_columns;
+            for (values, (_, _, order)) in seasonal.iter_mut().zip(&self.seasonalities) {
+                let end = start + 2 * *order as usize;
+                values.push(term(start..end));
+                start = end;
+            }
+        }
+        let additive: Vec<f64> = (0..timestamps.len())
+            .map(|i| seasonal.iter().map(|s| s[i]).sum())
+            .collect();
+        let point: Vec<f64> = trend.iter().zip(&additive).map(|(t, a)| t + a).collect();
+        let mut components = vec![
+            ("additive_terms".to_string(), additive),
+            (
+                "multiplicative_terms".to_string(),
+                vec![0.0; timestamps.len()],
+            ),
+        ];
+        components.extend(
+            self.seasonalities
+                .iter()
+                .map(|s| s.0.to_string())
+                .zip(seasonal),
+        );
+        Decomposition {
+            fitted: Forecast {
+                lower: point.iter().map(|p| p + self.offsets.0).collect(),
+                upper: point.iter().map(|p| p + self.offsets.1).collect(),
+                point,
+                timestamps,
+            },
+            trend,
+            components,
+            multiplicative: Vec::new(),
+        }
+    }
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+impl Forecaster for FourierRegression {
+    fn name(&self) -> &'static str {
+        "fourier"
+    }
+
+    fn predict(&self, timestamps: Vec<i64>) -> Result<Forecast, Box<dyn Error>> {
+        let clamp = |v: Vec<f64>| v.into_iter().map(|x| x.max(0.0)).collect();
+        let fitted = self.components(timestamps).fitted;
+        Ok(Forecast {
+            timestamps: fitted.timestamps,
+            point: clamp(fitted.point),
+            lower: clamp(fitted.lower),
+            upper: clamp(fitted.upper),
+        })
+    }
+
+    fn decompose(&self) -> Result<Decomposition, Box<dyn Error>> {
+        Ok(self.components(self.timestamps.clone()))
+    }
+
+    fn attribute(&self, timestamps: Vec<i64>) -> Result<Decomposition, Box<dyn Error>> {
+        Ok(self.components(timestamps))
+    }
+
+    fn changepoints(&self) -> Vec<Changepoint> {
+        self.changepoints.clone()
+    }
+}
@@ -0,0 +1,97 @@
+//! Guards against forecasting from stale or patchy data.
+//!
+//! A forecast fitted on a history that stopped days ago, or that is mostly
+//! resampled filler, still looks plausible. With `[guards]`, the runs that
+//! issue forecasts refuse such a history instead: when its last observation
+//! is more than `max_age_hours` old, or more than `max_missing_share` of the
+//! last `window_hours` has no reading. The hours since the last observation
+//! count as missing, and so do the gaps resampling filled in a load series;
+//! a session history has no gaps of its own, an hour without sessions being
+//! an idle one.
+//!
+//! Times are local wall-clock time, like the history's timestamps.
+
+use chrono::Utc;
+use chrono_tz::Tz;
+use std::error::Error;
+
+use crate::config::GuardConfig;
+use crate::data::{RowCounts, Series, format_timestamp};
+use crate::preprocess::{HOUR, overlap_seconds};
+
+/// How recent and complete a history is, as of some time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Freshness {
+    pub last_observation: i64,
+    /// Hours from the end of the last observation's step to now.
+    pub age_hours: f64,
+    /// Share of the guard window without a reading.
+    pub missing_share: f64,
+}
+
+/// The current local wall-clock time, as a history timestamp.
+pub fn now(timezone: Option<Tz>) -> i64 {
+    match timezone {
+        Some(timezone) => Utc::now()
+            .with_timezone(&timezone)
+            .naive_local()
+            .and_utc()
+            .timestamp(),
+        None => Utc::now().timestamp(),
+    }
+}
+
+/// Measure `history`, and the gaps its source recorded in `counts`, as of `now`.
+pub fn freshness(
+    history: &Series,
+    counts: &RowCounts,
+    now: i64,
+    config: &GuardConfig,
+) -> Result<Freshness, Box<dyn Error>> {
+    let last_observation = *history.timestamps.last().ok_or("Empty history")?;
+    let observed_until = last_observation + history.step();
+    let from = now - config.window_hours as i64 * HOUR;
+    let missing: i64 = counts
+        .gaps
+        .iter()
+        .chain([&(observed_until, now)])
+        .map(|(start, end)| overlap_seconds(*start, *end, from, now))
+        .sum();
+    Ok(Freshness {
+        last_observation,
+        age_hours: (now - observed_until).max(0) as f64 / HOUR as f64,
+        missing_share: match now - from {
+            0 => 0.0,
+            window => missing as f64 / window as f64,
+        },
+    })
+}
+
+/// [`freshness`], failing when it's outside the guards.
+pub fn check(
+    history: &Series,
+    counts: &RowCounts,
+    now: i64,
+    config: &GuardConfig,
+) -> Result<Freshness, Box<dyn Error>> {
+    let freshness = freshness(history, counts, now, config)?;
+    if freshness.age_hours > config.max_age_hours as f64 {
+        return Err(format!(
+            "The last observation ({}) is {:.0} hours old, more than guards.max_age_hours ({}) allows",
+            format_timestamp(freshness.last_observation),
+            freshness.age_hours,
+            config.max_age_hours
+        )
+        .into());
+    }
+    if freshness.missing_share > config.max_missing_share {
+        return Err(format!(
+            "{:.0}% of the last {} hours has no reading, more than guards.max_missing_share ({:.0}%) allows",
+            100.0 * freshness.missing_share,
+            config.window_hours,
+            100.0 * config.max_missing_share
+        )
+        .into());
+    }
+    Ok(freshness)
+}
@@ -0,0 +1,184 @@
+//! Sessions pulled from a REST endpoint, e.g. a CPO backend's reporting API,
+//! instead of a CSV export (`[data.http]`).
+
+use chrono_tz::Tz;
+use serde_json::Value;
+use std::error::Error;
+
+use crate::config::{HttpAuth, HttpSourceConfig, Pagination};
+use crate::data::{RowCounts, Session, parse_timestamp_text};
+use crate::secret;
+
+/// The value at a dotted path (`data.items`); the empty path is the value itself.
+fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .filter(|key| !key.is_empty())
+        .try_fold(value, |value, key| value.get(key))
+}
+
+/// A string or number field as text.
+fn text(value: &Value, path: &str) -> Option<String> {
+    match lookup(value, path)? {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+/// Add one page of records to `sessions`, counting them like export rows.
+fn read_records(
+    config: &HttpSourceConfig,
+    timezone: Option<Tz>,
+    records: &[Value],
+    signed: bool,
+    sessions: &mut Vec<Session>,
+    counts: &mut RowCounts,
+) {
+    for record in records {
+        counts.read += 1;
+        let start =
+            text(record, &config.start_field).and_then(|t| parse_timestamp_text(&t, timezone));
+        let energy = text(record, &config.energy_field).and_then(|t| t.trim().parse::<f64>().ok());
+        let (Some(start), Some(energy)) = (start, energy) else {
+            counts.invalid += 1;
+            continue;
+        };
+        let energy_wh = energy * config.energy_scale;
+        if energy_wh == 0.0 || (energy_wh < 0.0 && !signed) {
+            counts.non_positive += 1;
+            continue;
+        }
+        // A missing or inconsistent stop time collapses the session to its start
+        let stop = config
+            .stop_field
+            .as_ref()
+            .and_then(|field| text(record, field))
+            .and_then(|t| parse_timestamp_text(&t, timezone))
+            .filter(|stop| *stop >= start)
+            .unwrap_or(start);
+        sessions.push(Session {
+            start,
+            stop,
+            energy_wh,
+        });
+    }
+}
+
+/// Fetch every page of sessions from the configured endpoint, keeping those
+/// with negative energy when `signed` (see `data.export`).
+pub fn fetch_sessions(
+    config: &HttpSourceConfig,
+    timezone: Option<Tz>,
+    signed: bool,
+) -> Result<(Vec<Session>, RowCounts), Box<dyn Error>> {
+    let mut sessions = Vec::new();
+    let mut counts = RowCounts::default();
+    let mut url = config.url.clone();
+    let mut params: Vec<(String, String)> = Vec::new();
+    let mut page = config.first_page;
+    let mut requests = 0;
+    loop {
+        if config.pagination == Pagination::Page {
+            params.retain(|(key, _)| *key != config.page_param);
+            params.push((config.page_param.clone(), page.to_string()));
+        }
+        let body = get(config, &url, &params)?;
+        requests += 1;
+        let records = lookup(&body, &config.records_field)
+            .and_then(Value::as_array)
+            .ok_or_else(|| {
+                format!(
+                    "No {:?} array in the response from {}",
+                    config.records_field, url
+                )
+            })?;
+        read_records(
+            config,
+            timezone,
+            records,
+            signed,
+            &mut sessions,
+            &mut counts,
+        );
+
+        let more = match config.pagination {
+            Pagination::None => false,
+            Pagination::Page => {
+                page += 1;
+                !records.is_empty()
+                    && (config.page_size_param.is_none() || records.len() >= config.page_size)
+            }
+            Pagination::Next => {
+                match text(&body, &config.next_field).filter(|next| !next.is_empty()) {
+                    // An absolute link carries its own query
+                    Some(next) if next.starts_with("http://") || next.starts_with("https://") => {
+                        url = next;
+                        params.clear();
+                        true
+                    }
+                    Some(cursor) => {
+                        params.retain(|(key, _)| *key != config.cursor_param);
+                        params.push((config.cursor_param.clone(), cursor));
+                        true
+                    }
+                    None => false,
+                }
+            }
+        };
+        if !more {
+            break;
+        }
+        if requests >= config.max_pages {
+            eprintln!(
+                "⚠️  Stopped fetching {} after {} pages (data.http.max_pages)",
+                config.url, requests
+            );
+            break;
+        }
+    }
+
+    eprintln!(
+        "Fetched {} records from {} in {} requests",
+        counts.read, config.url, requests
+    );
+    if sessions.is_empty() {
+        return Err(format!("No valid sessions from {}", config.url).into());
+    }
+    Ok((sessions, counts))
+}
+
+fn get(
+    config: &HttpSourceConfig,
+    url: &str,
+    params: &[(String, String)],
+) -> Result<Value, Box<dyn Error>> {
+    let mut request = ureq::get(url);
+    // The configured query goes on the first URL only; `next` links already carry it
+    if url == config.url {
+        for (key, value) in &config.query {
+            request = request.query(key, value);
+        }
+        if let Some(size_param) = &config.page_size_param {
+            request = request.query(size_param, &config.page_size.to_string());
+        }
+    }
+    for (key, value) in params {
+        request = request.query(key, value);
+    }
+    let token = || -> Result<String, Box<dyn Error>> {
+        let token = config
+            .token
+            .as_deref()
+            .ok_or_else(|| format!("data.http.auth = {:?} needs data.http.token", config.auth))?;
+        secret::resolve(token)
+    };
+    request = match config.auth {
+        HttpAuth::None => request,
+        HttpAuth::Bearer => request.set("Authorization", &format!("Bearer {}", token()?)),
+        HttpAuth::ApiKey => request.set(&config.api_key_header, &token()?),
+    };
+    let response = request
+        .call()
+        .map_err(|e| format!("Fetching sessions failed: {}", e))?;
+    Ok(response.into_json()?)
+}
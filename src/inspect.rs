@@ -0,0 +1,534 @@
+//! Guessing the layout of an unfamiliar session export, for onboarding an
+//! operator: `inspect` samples the file and proposes the `[data.locale]` and
+//! `[data.columns]` to read it with.
+//!
+//! The delimiter is the one splitting the sample's lines most consistently.
+//! The decimal and thousands separators, and the date format, are the ones
+//! parsing the most fields, digit groups having three digits; where these
+//! tie, the usual `YYYY-MM-DD HH:MM`, a decimal point and day-first dates win.
+//! A column is a timestamp or a number when nine in ten of its values parse
+//! as one. Headers then pick the columns: the start time is the timestamp
+//! column named for a start (the first one otherwise), the stop time one named
+//! for a stop or end that's rarely before it, the energy the number column
+//! named for energy or Wh, and the site ids a column named for a site,
+//! location or station with more than one id in it. Without a header, the
+//! energy is the number column with the most distinct values.
+
+use csv::{ReaderBuilder, StringRecord};
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read};
+
+use crate::config::{ColumnConfig, EnergyUnit, LocaleConfig};
+use crate::data::CsvFormat;
+
+/// Rows sampled by default.
+pub const SAMPLE_ROWS: usize = 1000;
+
+/// Share of a column's values that have to parse for it to be of a kind.
+const KIND_SHARE: f64 = 0.9;
+
+const DELIMITERS: [char; 4] = [',', ';', '\t', '|'];
+
+/// `(decimal, thousands)` separators, in order of preference.
+const SEPARATORS: [(char, Option<char>); 6] = [
+    ('.', None),
+    (',', None),
+    (',', Some('.')),
+    ('.', Some(',')),
+    (',', Some(' ')),
+    ('.', Some(' ')),
+];
+
+/// Words of header names, lowercase, that mark a column; a few languages of
+/// backends in the field.
+const START_WORDS: &[&str] = &[
+    "start", "started", "begin", "beginn", "inicio", "début", "debut", "inizio",
+];
+const STOP_WORDS: &[&str] = &["stop", "end", "ended", "finish", "ende", "fin", "fine"];
+const ENERGY_WORDS: &[&str] = &[
+    "energy",
+    "wh",
+    "kwh",
+    "consumption",
+    "energie",
+    "énergie",
+    "energía",
+    "energia",
+    "verbrauch",
+];
+const SITE_WORDS: &[&str] = &[
+    "site", "location", "station", "standort", "lieu", "sitio", "sito",
+];
+/// Words of columns of personal data (see [`crate::privacy`]).
+const PERSONAL_WORDS: &[&str] = &[
+    "user",
+    "username",
+    "rfid",
+    "idtag",
+    "tag",
+    "uid",
+    "card",
+    "badge",
+    "email",
+    "mail",
+    "phone",
+    "driver",
+    "customer",
+    "contract",
+    "emaid",
+    "benutzer",
+    "kunde",
+    "karte",
+    "fahrer",
+    "utilisateur",
+    "usuario",
+    "utente",
+];
+
+/// Date formats tried besides the usual `YYYY-MM-DD HH:MM`, day-first before month-first.
+pub const DATE_FORMATS: [&str; 12] = [
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%d.%m.%Y %H:%M",
+    "%d.%m.%Y %H:%M:%S",
+    "%d/%m/%Y %H:%M",
+    "%d/%m/%Y %H:%M:%S",
+    "%m/%d/%Y %H:%M",
+    "%m/%d/%Y %H:%M:%S",
+    "%d-%m-%Y %H:%M",
+    "%Y/%m/%d %H:%M",
+    "%d %b %Y %H:%M",
+];
+
+/// What a column's values are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Timestamp,
+    Number,
+    Text,
+    Empty,
+}
+
+/// One column of the sample.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnGuess {
+    pub index: usize,
+    /// The header's name for it; empty without a header.
+    pub name: String,
+    pub kind: Kind,
+    /// Distinct values in the sample.
+    pub distinct: usize,
+    /// Its first value.
+    pub example: String,
+}
+
+/// The proposed way of reading a sampled export.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inspection {
+    /// Data rows sampled, not counting a header.
+    pub rows: usize,
+    pub header: bool,
+    pub locale: LocaleConfig,
+    pub columns: Vec<ColumnGuess>,
+    pub mapping: ColumnConfig,
+    /// The ids in the site column, sorted.
+    pub site_ids: Vec<String>,
+    /// Columns of personal data, by their names; their values aren't shown.
+    pub personal: Vec<usize>,
+    /// The sample's dates read as well day-first as month-first.
+    pub ambiguous_dates: bool,
+}
+
+/// The first `rows` rows of `reader` after a possible header, as text.
+pub fn sample(reader: impl Read, rows: usize) -> Result<String, Box<dyn Error>> {
+    let mut sample = String::new();
+    for line in BufReader::new(reader).lines().take(rows + 1) {
+        sample.push_str(&line?);
+        sample.push('\n');
+    }
+    Ok(sample)
+}
+
+fn records(sample: &str, delimiter: char) -> Vec<StringRecord> {
+    ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter as u8)
+        .from_reader(sample.as_bytes())
+        .records()
+        .filter_map(Result::ok)
+        .collect()
+}
+
+/// Share of rows with the most common number of fields, and that number.
+fn consistency(records: &[StringRecord]) -> (f64, usize) {
+    let mut lengths: Vec<usize> = records.iter().map(StringRecord::len).collect();
+    lengths.sort_unstable();
+    let mut best = (0, 0);
+    for run in lengths.chunk_by(|a, b| a == b) {
+        if run.len() >= best.0 {
+            best = (run.len(), run[0]);
+        }
+    }
+    (best.0 as f64 / records.len().max(1) as f64, best.1)
+}
+
+/// A number with `decimal` and `thousands` separators, its digit groups of three.
+fn number(text: &str, decimal: char, thousands: Option<char>) -> Option<f64> {
+    let text = text.trim();
+    let unsigned = text.strip_prefix('-').unwrap_or(text);
+    let (whole, fraction) = match unsigned.split_once(decimal) {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (unsigned, None),
+    };
+    let digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    let whole: String = match thousands {
+        Some(thousands) if whole.contains(thousands) => {
+            let groups: Vec<&str> = whole.split(thousands).collect();
+            let grouped = groups[0].len() <= 3
+                && groups.iter().all(|g| digits(g))
+                && groups[1..].iter().all(|g| g.len() == 3);
+            grouped.then(|| groups.concat())?
+        }
+        _ => whole.to_string(),
+    };
+    if !digits(&whole) || fraction.is_some_and(|f| !digits(f)) {
+        return None;
+    }
+    let sign = if text.starts_with('-') { "-" } else { "" };
+    format!("{}{}.{}", sign, whole, fraction.unwrap_or("0"))
+        .parse()
+        .ok()
+}
+
+fn delimiter_name(delimiter: char) -> String {
+    match delimiter {
+        '\t' => "tab".to_string(),
+        c => format!("{:?}", c),
+    }
+}
+
+/// Propose how to read the export `sample` came from.
+pub fn inspect(sample: &str) -> Result<Inspection, Box<dyn Error>> {
+    // The delimiter splitting the lines most consistently, into the most fields
+    let (delimiter, records) = DELIMITERS
+        .iter()
+        .rev()
+        .map(|d| (*d, records(sample, *d)))
+        .filter(|(_, records)| consistency(records).1 > 1)
+        .max_by(|(_, a), (_, b)| {
+            let (a, b) = (consistency(a), consistency(b));
+            a.0.total_cmp(&b.0).then(a.1.cmp(&b.1))
+        })
+        .ok_or("The sample has no delimited rows")?;
+    let fields: Vec<&str> = records
+        .iter()
+        .flat_map(|r| r.iter())
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .collect();
+
+    let (decimal, thousands) = SEPARATORS
+        .iter()
+        .filter(|(decimal, thousands)| *decimal != delimiter && *thousands != Some(delimiter))
+        .rev()
+        .max_by_key(|(decimal, thousands)| {
+            fields
+                .iter()
+                .filter(|f| number(f, *decimal, *thousands).is_some())
+                .count()
+        })
+        .copied()
+        .ok_or("No decimal separator fits the delimiter")?;
+    let usual = CsvFormat::default();
+    let counts: Vec<(&str, usize)> = DATE_FORMATS
+        .iter()
+        .map(|format| {
+            (
+                *format,
+                fields
+                    .iter()
+                    .filter(|f| chrono::NaiveDateTime::parse_from_str(f, format).is_ok())
+                    .count(),
+            )
+        })
+        .collect();
+    let usual_count = fields
+        .iter()
+        .filter(|f| usual.timestamp(f.as_bytes()).is_some())
+        .count();
+    let best = counts
+        .iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .copied()
+        .filter(|(_, count)| *count > usual_count);
+    let ambiguous_dates = best.is_some_and(|(format, count)| {
+        let flipped = format
+            .replace("%d/%m", "%x")
+            .replace("%m/%d", "%d/%m")
+            .replace("%x", "%m/%d");
+        flipped != format
+            && counts
+                .iter()
+                .any(|(other, n)| *other == flipped && *n == count)
+    });
+    let locale = LocaleConfig {
+        delimiter,
+        decimal,
+        thousands,
+        date_formats: best
+            .map(|(format, _)| vec![format.to_string()])
+            .unwrap_or_default(),
+    };
+    let format = CsvFormat {
+        decimal: decimal as u8,
+        thousands: thousands.map(|t| t as u8),
+        date_formats: locale.date_formats.clone(),
+        ..CsvFormat::default()
+    };
+    let is_timestamp = |f: &str| format.timestamp(f.as_bytes()).is_some();
+    let is_number = |f: &str| number(f, decimal, thousands).is_some();
+
+    // A first row of names only is a header
+    let header = records.len() > 1 && records[0].iter().all(|f| !is_timestamp(f) && !is_number(f));
+    let names: Vec<String> = match header {
+        true => records[0].iter().map(|f| f.trim().to_string()).collect(),
+        false => Vec::new(),
+    };
+    let data = &records[header as usize..];
+    let width = data.iter().map(StringRecord::len).max().unwrap_or_default();
+    let columns: Vec<ColumnGuess> = (0..width)
+        .map(|index| {
+            let values: Vec<&str> = data
+                .iter()
+                .filter_map(|r| r.get(index))
+                .map(str::trim)
+                .filter(|f| !f.is_empty())
+                .collect();
+            let share = |test: &dyn Fn(&str) -> bool| {
+                values.iter().filter(|v| test(v)).count() as f64 / values.len() as f64
+            };
+            let kind = match values.len() {
+                0 => Kind::Empty,
+                _ if share(&is_timestamp) >= KIND_SHARE => Kind::Timestamp,
+                _ if share(&is_number) >= KIND_SHARE => Kind::Number,
+                _ => Kind::Text,
+            };
+            let mut distinct = values.clone();
+            distinct.sort_unstable();
+            distinct.dedup();
+            ColumnGuess {
+                index,
+                name: names.get(index).cloned().unwrap_or_default(),
+                kind,
+                distinct: distinct.len(),
+                example: values.first().map(|v| v.to_string()).unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    let named = |column: &ColumnGuess, words: &[&str]| {
+        let name = column.name.to_lowercase();
+        name.split(|c: char| !c.is_alphanumeric())
+            .any(|word| words.contains(&word))
+    };
+    let of_kind = |kind: Kind| columns.iter().filter(move |c| c.kind == kind);
+    let site = columns
+        .iter()
+        .find(|c| c.kind != Kind::Timestamp && c.distinct > 1 && named(c, SITE_WORDS));
+    let start = of_kind(Kind::Timestamp)
+        .find(|c| named(c, START_WORDS))
+        .or_else(|| of_kind(Kind::Timestamp).next())
+        .ok_or("No column of the sample holds timestamps")?;
+    let times = |column: &ColumnGuess| -> Vec<Option<i64>> {
+        data.iter()
+            .map(|r| {
+                r.get(column.index)
+                    .and_then(|f| format.timestamp(f.as_bytes()))
+            })
+            .collect()
+    };
+    let starts = times(start);
+    let after_start = |column: &ColumnGuess| {
+        let pairs: Vec<(i64, i64)> = times(column)
+            .iter()
+            .zip(&starts)
+            .filter_map(|(stop, start)| Some(((*stop)?, (*start)?)))
+            .collect();
+        pairs.iter().filter(|(stop, start)| stop >= start).count() as f64
+            >= KIND_SHARE * pairs.len() as f64
+    };
+    let stop = of_kind(Kind::Timestamp)
+        .filter(|c| c.index != start.index && named(c, STOP_WORDS))
+        .find(|c| after_start(c))
+        .unwrap_or(start);
+    let energies: Vec<&ColumnGuess> = of_kind(Kind::Number)
+        .filter(|c| Some(c.index) != site.map(|s| s.index))
+        .collect();
+    let energy = energies
+        .iter()
+        .find(|c| named(c, ENERGY_WORDS))
+        .or_else(|| {
+            energies
+                .iter()
+                .filter(|_| !header)
+                .max_by_key(|c| c.distinct)
+        })
+        .ok_or("No column of the sample is named for energy, or holds numbers without a header")?;
+
+    let energy_unit = if named(energy, &["kwh"]) {
+        EnergyUnit::Kwh
+    } else if named(energy, &["wh"]) {
+        EnergyUnit::Wh
+    } else {
+        // Sessions charge some kWh: a median in the hundreds or less isn't Wh
+        let mut values: Vec<f64> = data
+            .iter()
+            .filter_map(|r| number(r.get(energy.index)?, decimal, thousands))
+            .collect();
+        values.sort_by(f64::total_cmp);
+        match values.get(values.len() / 2) {
+            Some(median) if *median < 500.0 => EnergyUnit::Kwh,
+            _ => EnergyUnit::Wh,
+        }
+    };
+    let mapped = [
+        Some(start.index),
+        Some(stop.index),
+        Some(energy.index),
+        site.map(|s| s.index),
+    ];
+    let personal = columns
+        .iter()
+        .filter(|c| !mapped.contains(&Some(c.index)) && named(c, PERSONAL_WORDS))
+        .map(|c| c.index)
+        .collect();
+    let mut site_ids: Vec<String> = site
+        .map(|site| {
+            data.iter()
+                .filter_map(|r| r.get(site.index))
+                .map(|id| id.trim().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    site_ids.sort();
+    site_ids.dedup();
+
+    Ok(Inspection {
+        rows: data.len(),
+        header,
+        mapping: ColumnConfig {
+            start: start.index,
+            stop: stop.index,
+            energy: energy.index,
+            energy_unit,
+            site: site.map(|s| s.index),
+        },
+        locale,
+        columns,
+        site_ids,
+        personal,
+        ambiguous_dates,
+    })
+}
+
+impl Inspection {
+    /// One line per column, then the proposed mapping.
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{} rows sampled{}, delimiter {}, decimal {:?}{}",
+            self.rows,
+            if self.header {
+                " after a header"
+            } else {
+                ", no header"
+            },
+            delimiter_name(self.locale.delimiter),
+            self.locale.decimal,
+            self.locale
+                .thousands
+                .map(|t| format!(", thousands {:?}", t))
+                .unwrap_or_default(),
+        )];
+        if let Some(format) = self.locale.date_formats.first() {
+            lines.push(format!("Dates read as {}", format));
+        }
+        if self.ambiguous_dates {
+            lines.push("⚠️  The sample's dates read as well day-first as month-first; day-first is proposed".to_string());
+        }
+        let mapping = &self.mapping;
+        let role = |index: usize| match index {
+            _ if index == mapping.start && index == mapping.stop => " <- start (no stop time)",
+            _ if index == mapping.start => " <- start",
+            _ if index == mapping.stop => " <- stop",
+            _ if index == mapping.energy => " <- energy",
+            _ if Some(index) == mapping.site => " <- site",
+            _ if self.personal.contains(&index) => " <- personal",
+            _ => "",
+        };
+        for column in &self.columns {
+            let example = match self.personal.contains(&column.index) {
+                true => "…".to_string(),
+                false => format!("{:?}", column.example),
+            };
+            lines.push(format!(
+                "  {:>2} {:<30} {:<9} {:>5} distinct, e.g. {}{}",
+                column.index,
+                column.name,
+                format!("{:?}", column.kind).to_lowercase(),
+                column.distinct,
+                example,
+                role(column.index)
+            ));
+        }
+        if !self.site_ids.is_empty() {
+            lines.push(format!("Sites: {}", self.site_ids.join(", ")));
+        }
+        lines
+    }
+
+    /// The proposal as config keys for the export at `path`, to paste into a config.
+    pub fn to_toml(&self, path: &str) -> String {
+        let mut text = String::new();
+        if let Some(id) = self.site_ids.first() {
+            text.push_str(&format!(
+                "site = {:?}  # one of {}\n\n",
+                id,
+                self.site_ids.join(", ")
+            ));
+        }
+        text.push_str(&format!("[data]\npath = {:?}\n", path));
+        let mapping = &self.mapping;
+        if self.locale != LocaleConfig::default() {
+            text.push_str("\n[data.locale]\n");
+            let locale = toml::to_string(&self.locale).unwrap_or_default();
+            text.push_str(&locale);
+        }
+        text.push_str(&format!("\n[data.columns]\nstart = {}\n", mapping.start));
+        match mapping.stop == mapping.start {
+            true => text.push_str(&format!(
+                "stop = {}  # no stop times: sessions start and stop at once\n",
+                mapping.stop
+            )),
+            false => text.push_str(&format!("stop = {}\n", mapping.stop)),
+        }
+        text.push_str(&format!("energy = {}\n", mapping.energy));
+        text.push_str(&format!(
+            "energy_unit = \"{}\"\n",
+            format!("{:?}", mapping.energy_unit).to_lowercase()
+        ));
+        if let Some(site) = mapping.site {
+            text.push_str(&format!("site = {}\n", site));
+        }
+        if !self.personal.is_empty() {
+            let columns: Vec<String> = self.personal.iter().map(usize::to_string).collect();
+            text.push_str(&format!(
+                "\n[data.personal]\ncolumns = [{}]\naction = \"drop\"  # or \"hash\", with a key\n",
+                columns.join(", ")
+            ));
+        }
+        text
+    }
+}
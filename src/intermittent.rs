@@ -0,0 +1,106 @@
+//! Intermittent-demand forecasting (TSB) for chargers that are idle most hours.
+//!
+//! Prophet fits smooth curves and does badly on series that are mostly zero
+//! with the odd session. TSB (Teunter, Syntetos & Babai, 2011) instead tracks
+//! two things separately — how likely an hour is to have demand, and how much
+//! demand there is when it does — and forecasts their product. Unlike Croston,
+//! the probability decays during idle stretches, so a charger that has gone
+//! quiet is forecast down rather than held at its last level.
+
+use std::error::Error;
+
+use crate::data::Series;
+use crate::forecast::{Forecast, Forecaster};
+use crate::metrics::quantile;
+
+/// A fitted TSB model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tsb {
+    /// Fitted one-step-ahead values, aligned with the history timestamps.
+    timestamps: Vec<i64>,
+    fitted: Vec<f64>,
+    /// Forecast for every hour after the history.
+    level: f64,
+    /// Interval bounds: quantiles of recent hourly demand.
+    lower: f64,
+    upper: f64,
+}
+
+/// Hours of recent demand the interval is taken from (4 weeks).
+const INTERVAL_HOURS: usize = 24 * 28;
+
+impl Tsb {
+    /// Fit with smoothing constants `alpha` (size) and `beta` (probability).
+    pub fn fit(
+        history: &Series,
+        alpha: f64,
+        beta: f64,
+        interval_width: f64,
+    ) -> Result<Self, Box<dyn Error>> {
+        if history.is_empty() {
+            return Err("Empty history".into());
+        }
+        let (alpha, beta) = (alpha.clamp(0.0, 1.0), beta.clamp(0.0, 1.0));
+
+        // Start from the whole-series averages rather than the first observation
+        let nonzero: Vec<f64> = history
+            .values
+            .iter()
+            .cloned()
+            .filter(|v| *v > 0.0)
+            .collect();
+        let mut probability = nonzero.len() as f64 / history.len() as f64;
+        let mut size = if nonzero.is_empty() {
+            0.0
+        } else {
+            nonzero.iter().sum::<f64>() / nonzero.len() as f64
+        };
+
+        let mut fitted = Vec::with_capacity(history.len());
+        for &y in &history.values {
+            fitted.push(probability * size);
+            if y > 0.0 {
+                size += alpha * (y - size);
+                probability += beta * (1.0 - probability);
+            } else {
+                probability -= beta * probability;
+            }
+        }
+
+        // The mean forecast says little about a single hour that is either
+        // idle or busy, so the interval is what recent hours actually looked like
+        let mut recent = history.values[history.len().saturating_sub(INTERVAL_HOURS)..].to_vec();
+        recent.sort_by(f64::total_cmp);
+        Ok(Self {
+            timestamps: history.timestamps.to_vec(),
+            fitted,
+            level: probability * size,
+            lower: quantile(&recent, (1.0 - interval_width) / 2.0),
+            upper: quantile(&recent, (1.0 + interval_width) / 2.0),
+        })
+    }
+}
+
+impl Forecaster for Tsb {
+    fn name(&self) -> &'static str {
+        "tsb"
+    }
+
+    fn predict(&self, timestamps: Vec<i64>) -> Result<Forecast, Box<dyn Error>> {
+        let point: Vec<f64> = timestamps
+            .iter()
+            .map(|ts| match self.timestamps.binary_search(ts) {
+                Ok(i) => self.fitted[i],
+                Err(_) => self.level,
+            })
+            .collect();
+        let lower = point.iter().map(|p| p.min(self.lower).max(0.0)).collect();
+        let upper = point.iter().map(|p| p.max(self.upper)).collect();
+        Ok(Forecast {
+            timestamps,
+            point,
+            lower,
+            upper,
+        })
+    }
+}
@@ -0,0 +1,166 @@
+//! On-demand fits, queued and run by a fixed number of workers.
+//!
+//! A burst of requests turns into at most `workers` Stan optimizations at a
+//! time: further requests wait in a bounded queue, a site with a fit already
+//! waiting shares that job, and a full queue turns requests away.
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::{BTreeMap, VecDeque};
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Condvar, Mutex};
+use utoipa::ToSchema;
+
+/// Finished jobs remembered for status requests.
+const KEEP_FINISHED: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+/// One fit request and how it went.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FitJob {
+    pub id: u64,
+    pub site: String,
+    pub status: JobStatus,
+    /// Why the fit failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// UNIX seconds.
+    pub submitted_at: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<i64>,
+}
+
+#[derive(Default)]
+struct Jobs {
+    jobs: BTreeMap<u64, FitJob>,
+    queue: VecDeque<u64>,
+    finished: VecDeque<u64>,
+    next_id: u64,
+}
+
+#[derive(Clone)]
+pub struct FitQueue {
+    jobs: Arc<(Mutex<Jobs>, Condvar)>,
+    max_queued: usize,
+}
+
+impl FitQueue {
+    /// Start `workers` threads that fit sites with `fit`.
+    pub fn new<F>(workers: usize, max_queued: usize, fit: F) -> Self
+    where
+        F: Fn(&str) -> Result<(), String> + Send + Sync + 'static,
+    {
+        let queue = Self {
+            jobs: Arc::new((Mutex::new(Jobs::default()), Condvar::new())),
+            max_queued,
+        };
+        let fit = Arc::new(fit);
+        for _ in 0..workers.max(1) {
+            let queue = queue.clone();
+            let fit = Arc::clone(&fit);
+            std::thread::spawn(move || {
+                loop {
+                    let (id, site) = queue.next();
+                    // A panicking fit fails its job and leaves the worker running
+                    let result = std::panic::catch_unwind(AssertUnwindSafe(|| fit(&site)))
+                        .unwrap_or_else(|panic| Err(panic_message(panic.as_ref())));
+                    queue.finish(id, result);
+                }
+            });
+        }
+        queue
+    }
+
+    /// Queue a fit of `site`, or return the one already waiting for it.
+    ///
+    /// Fails when `max_queued` jobs are waiting.
+    pub fn submit(&self, site: &str) -> Result<FitJob, String> {
+        let (lock, ready) = &*self.jobs;
+        let mut jobs = lock.lock().unwrap();
+        if let Some(job) = jobs
+            .queue
+            .iter()
+            .map(|id| &jobs.jobs[id])
+            .find(|job| job.site == site)
+        {
+            return Ok(job.clone());
+        }
+        if jobs.queue.len() >= self.max_queued {
+            return Err(format!(
+                "{} fits are already waiting; try again later",
+                jobs.queue.len()
+            ));
+        }
+        jobs.next_id += 1;
+        let job = FitJob {
+            id: jobs.next_id,
+            site: site.to_string(),
+            status: JobStatus::Queued,
+            error: None,
+            submitted_at: Utc::now().timestamp(),
+            started_at: None,
+            finished_at: None,
+        };
+        jobs.jobs.insert(job.id, job.clone());
+        jobs.queue.push_back(job.id);
+        ready.notify_one();
+        Ok(job)
+    }
+
+    pub fn job(&self, id: u64) -> Option<FitJob> {
+        self.jobs.0.lock().unwrap().jobs.get(&id).cloned()
+    }
+
+    /// Wait for the next queued job and mark it running.
+    fn next(&self) -> (u64, String) {
+        let (lock, ready) = &*self.jobs;
+        let mut jobs = ready
+            .wait_while(lock.lock().unwrap(), |jobs| jobs.queue.is_empty())
+            .unwrap();
+        let id = jobs.queue.pop_front().unwrap();
+        let job = jobs.jobs.get_mut(&id).unwrap();
+        job.status = JobStatus::Running;
+        job.started_at = Some(Utc::now().timestamp());
+        (id, job.site.clone())
+    }
+
+    fn finish(&self, id: u64, result: Result<(), String>) {
+        let mut jobs = self.jobs.0.lock().unwrap();
+        if let Some(job) = jobs.jobs.get_mut(&id) {
+            job.finished_at = Some(Utc::now().timestamp());
+            match result {
+                Ok(()) => job.status = JobStatus::Done,
+                Err(e) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(e);
+                }
+            }
+        }
+        jobs.finished.push_back(id);
+        while jobs.finished.len() > KEEP_FINISHED {
+            let old = jobs.finished.pop_front().unwrap();
+            jobs.jobs.remove(&old);
+        }
+    }
+}
+
+/// What a panic said, for the failed job.
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    let message = panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("no message");
+    format!("The fit panicked: {}", message)
+}
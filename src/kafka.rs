@@ -0,0 +1,59 @@
+//! Kafka input: consume live meter values and hand each site's rolling
+//! series on for a new forecast once enough hours have come in.
+
+use chrono_tz::Tz;
+use kafka::consumer::{Consumer, FetchOffset, GroupOffsetStorage};
+use std::error::Error;
+
+use crate::config::KafkaConfig;
+use crate::data::Series;
+use crate::stream::{self, SiteStreams};
+
+/// Consume the topic until an error, calling `on_ready` with a site and its
+/// hourly load whenever the site is due (see [`SiteStreams::ready`]).
+///
+/// Offsets are committed to the consumer group after every poll, so a
+/// restart resumes where the last run stopped; the rolling series are
+/// rebuilt from what arrives after that. Undecodable messages and failed
+/// forecasts are reported and skipped.
+pub fn consume(
+    config: &KafkaConfig,
+    timezone: Option<Tz>,
+    mut on_ready: impl FnMut(&str, Series) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut consumer = Consumer::from_hosts(config.brokers.clone())
+        .with_topic(config.topic.clone())
+        .with_group(config.group.clone())
+        .with_fallback_offset(FetchOffset::Earliest)
+        .with_offset_storage(Some(GroupOffsetStorage::Kafka))
+        .create()
+        .map_err(|e| format!("Kafka {}: {}", config.brokers.join(","), e))?;
+    eprintln!("Consuming {} as group {}", config.topic, config.group);
+
+    let mut streams = SiteStreams::new(config);
+    loop {
+        for messages in consumer.poll()?.iter() {
+            for message in messages.messages() {
+                match stream::parse_message(config, timezone, message.value) {
+                    Ok(value) => streams.push(value),
+                    Err(e) => eprintln!(
+                        "⚠️  Skipping message {}/{} at offset {}: {}",
+                        messages.topic(),
+                        messages.partition(),
+                        message.offset,
+                        e
+                    ),
+                }
+            }
+            consumer.consume_messageset(messages)?;
+        }
+        consumer.commit_consumed()?;
+
+        for site in streams.ready() {
+            if let Err(e) = on_ready(&site, streams.series(&site)) {
+                eprintln!("⚠️  Forecast for {} failed: {}", site, e);
+            }
+            streams.mark_forecast(&site);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Lagged-demand and rolling-mean regressors.
+//!
+//! Sites with sticky week-to-week patterns are often forecast better from
+//! "same hour yesterday / last week" than from smooth seasonal terms. Past
+//! the shortest lag those values are themselves unknown, so predictions are
+//! made recursively, one shortest-lag-sized chunk at a time, feeding earlier
+//! predictions back in as lag values.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::config::ModelConfig;
+use crate::data::Series;
+use crate::preprocess::HOUR;
+
+/// Default gap between a rolling window's end and the predicted hour when no lags are set.
+const DEFAULT_OFFSET_HOURS: u32 = 24;
+
+/// Lag and rolling-mean feature definitions plus the values they can look up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LagFeatures {
+    lags: Vec<u32>,
+    rolling_means: Vec<u32>,
+    /// Hours between the end of each rolling window and the predicted hour.
+    offset: u32,
+    /// Stand-in for values before the start of the history.
+    fill: f64,
+    known: HashMap<i64, f64>,
+}
+
+impl LagFeatures {
+    /// `None` when no lag or rolling features are configured.
+    pub fn new(model: &ModelConfig, history: &Series) -> Option<Self> {
+        let lags: Vec<u32> = model.lags.iter().cloned().filter(|l| *l > 0).collect();
+        let rolling_means: Vec<u32> = model
+            .rolling_means
+            .iter()
+            .cloned()
+            .filter(|w| *w > 0)
+            .collect();
+        if lags.is_empty() && rolling_means.is_empty() {
+            return None;
+        }
+        Some(Self {
+            offset: lags.iter().cloned().min().unwrap_or(DEFAULT_OFFSET_HOURS),
+            fill: history.values.iter().sum::<f64>() / history.len().max(1) as f64,
+            known: history
+                .timestamps
+                .iter()
+                .cloned()
+                .zip(history.values.iter().cloned())
+                .collect(),
+            lags,
+            rolling_means,
+        })
+    }
+
+    /// Hours at the start of the history without a full set of feature values.
+    pub fn warmup_hours(&self) -> usize {
+        let longest_lag = self.lags.iter().cloned().max().unwrap_or(0);
+        let longest_window = self
+            .rolling_means
+            .iter()
+            .map(|w| self.offset + w - 1)
+            .max()
+            .unwrap_or(0);
+        longest_lag.max(longest_window) as usize
+    }
+
+    /// How many hours ahead can be predicted from known values alone.
+    pub fn chunk_hours(&self) -> usize {
+        self.offset as usize
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let lags = self.lags.iter().map(|l| format!("lag_{}", l));
+        lags.chain(
+            self.rolling_means
+                .iter()
+                .map(|w| format!("rolling_mean_{}", w)),
+        )
+        .collect()
+    }
+
+    /// Record an observed or predicted value; observed values are never overwritten.
+    pub fn remember(&mut self, timestamp: i64, value: f64) {
+        self.known.entry(timestamp).or_insert(value);
+    }
+
+    fn value(&self, timestamp: i64) -> f64 {
+        self.known.get(&timestamp).cloned().unwrap_or(self.fill)
+    }
+
+    /// Feature values at the given timestamps, by regressor name.
+    pub fn features(&self, timestamps: &[i64]) -> BTreeMap<String, Vec<f64>> {
+        let mut features = BTreeMap::new();
+        for lag in &self.lags {
+            let values = timestamps
+                .iter()
+                .map(|ts| self.value(ts - *lag as i64 * HOUR))
+                .collect();
+            features.insert(format!("lag_{}", lag), values);
+        }
+        for window in &self.rolling_means {
+            let values = timestamps
+                .iter()
+                .map(|ts| {
+                    let end = ts - self.offset as i64 * HOUR;
+                    (0..*window as i64)
+                        .map(|i| self.value(end - i * HOUR))
+                        .sum::<f64>()
+                        / *window as f64
+                })
+                .collect();
+            features.insert(format!("rolling_mean_{}", window), values);
+        }
+        features
+    }
+}
@@ -0,0 +1,90 @@
+//! EV charging demand forecasting for CPO sites.
+//!
+//! The pipeline is: load session exports ([`data`]), turn them into an hourly
+//! load series ([`preprocess`]), fit Prophet and forecast ([`forecast`]), then
+//! write and plot the results ([`export`], [`plot`]).
+
+pub mod alerts;
+pub mod anomaly;
+#[cfg(feature = "server")]
+pub mod api;
+pub mod archive;
+pub mod artifact;
+pub mod audit;
+pub mod backtest;
+pub mod baseline;
+pub mod batch;
+pub mod bias;
+pub mod breaks;
+pub mod calendar;
+pub mod cim;
+#[cfg(feature = "server")]
+pub mod client;
+pub mod cluster;
+#[cfg(feature = "arrow")]
+pub mod columnar;
+pub mod compare;
+pub mod config;
+pub mod convergence;
+pub mod data;
+#[cfg(feature = "db")]
+pub mod db;
+pub mod depot;
+pub mod diff;
+pub mod exceedance;
+pub mod export;
+pub mod forecast;
+pub mod forecast_log;
+pub mod fourier;
+pub mod guards;
+#[cfg(feature = "http")]
+pub mod http_source;
+pub mod inspect;
+pub mod intermittent;
+#[cfg(feature = "server")]
+pub mod jobs;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod lags;
+pub mod manifest;
+pub mod market;
+pub mod metrics;
+pub mod migrate;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+#[cfg(feature = "http")]
+pub mod notify;
+pub mod nowcast;
+pub mod openadr;
+pub mod outcome;
+pub mod partial;
+pub mod peaks;
+pub mod pipeline;
+pub mod plot;
+pub mod power;
+pub mod preprocess;
+pub mod prices;
+pub mod privacy;
+pub mod profile;
+pub mod ramp;
+pub mod register;
+pub mod remote;
+pub mod report;
+pub mod revenue;
+pub mod scenarios;
+pub mod seasonality;
+pub mod secret;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod shadow;
+pub mod sink;
+pub mod site;
+pub mod source;
+#[cfg(feature = "store")]
+pub mod store;
+pub mod stream;
+pub mod synth;
+pub mod transform;
+pub mod utilization;
+pub mod v2g;
+pub mod weather;
@@ -1,3 +1,2439 @@
-fn main() {
-    println!("✅ Rust environment is set up correctly!");
-}
\ No newline at end of file
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{Parser, Subcommand};
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
+
+use cpo_charging_forecast::anomaly::Anomaly;
+use cpo_charging_forecast::archive::{self, Archive, ArchivedForecast};
+use cpo_charging_forecast::artifact;
+use cpo_charging_forecast::audit::RevisionReport;
+use cpo_charging_forecast::backtest::{self, BacktestReport};
+use cpo_charging_forecast::batch::{self, BatchReport};
+use cpo_charging_forecast::breaks::{self, Break};
+use cpo_charging_forecast::config::{
+    ChargingStrategy, Config, ConfigKind, DiffConfig, Export, Method, PersonalAction, Preset,
+    StoreConfig, Transform,
+};
+use cpo_charging_forecast::data::{self, RowCounts, STDIO, Series, format_timestamp};
+use cpo_charging_forecast::diff::{self, DiffReport};
+use cpo_charging_forecast::forecast::{
+    Changepoint, Forecast, Forecaster, forecast_series, future_steps, model_version,
+    resolve_method, training_hours, zero_share,
+};
+use cpo_charging_forecast::manifest::Manifest;
+use cpo_charging_forecast::notify::RunSummary;
+use cpo_charging_forecast::outcome::{RunRecord, StageError, Status};
+use cpo_charging_forecast::pipeline::{
+    self, fit_and_forecast, load_history, load_history_counted, tail,
+};
+use cpo_charging_forecast::prices::Prices;
+use cpo_charging_forecast::privacy::{self, Pseudonymizer};
+use cpo_charging_forecast::profile::{self, Profiles};
+use cpo_charging_forecast::ramp::RampForecast;
+use cpo_charging_forecast::report::{self, Report};
+use cpo_charging_forecast::revenue::{self, RevenueForecast};
+use cpo_charging_forecast::secret;
+use cpo_charging_forecast::shadow::{self, ShadowRecord};
+use cpo_charging_forecast::sink::{Fanout, OutputSink};
+use cpo_charging_forecast::source::{self, SeriesSource};
+use cpo_charging_forecast::store::{ModelRecord, Store, Watermark};
+use cpo_charging_forecast::utilization::{self, UtilizationReport};
+use cpo_charging_forecast::{
+    alerts, anomaly, cluster, compare, depot, exceedance, export, forecast_log, guards, inspect,
+    kafka, market, metrics, migrate, notify, peaks, plot, preprocess, remote, scenarios,
+    seasonality, server, sink, site, synth, v2g, weather,
+};
+
+/// Lead times (hours) the backtest summary is broken down by.
+const LEAD_CHECKPOINTS: [usize; 5] = [1, 6, 24, 72, 168];
+
+/// Hours of history shown in front of the forecast on the plot (4 weeks).
+const PLOT_HISTORY_HOURS: usize = 24 * 28;
+
+#[derive(Parser)]
+#[command(version, about = "EV charging demand forecasting for CPO sites")]
+struct Cli {
+    /// TOML config file (defaults are used when omitted)
+    #[arg(short, long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Runtime config file (outputs and services), kept apart from the site/model config of `--config`
+    #[arg(long, global = true)]
+    runtime: Option<PathBuf>,
+
+    /// Load and check the data and config, and show what a forecast would fit and write, without fitting
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Fail when more than `data.max_skip_rate` of the export's rows can't be parsed (sets `data.strict`)
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Run for a site: the config with its `[sites.<id>]` overrides, or its config in the `[store]`
+    #[arg(long, global = true)]
+    site: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Forecast site demand (the default)
+    Forecast,
+    /// Combine a fleet schedule with the background forecast
+    Depot {
+        /// Fleet schedule CSV, overriding `depot.schedule`
+        #[arg(long)]
+        schedule: Option<PathBuf>,
+    },
+    /// Estimate hourly V2G discharge capacity from forecast occupancy
+    V2g,
+    /// Serve forecasts over HTTP (Grafana simple-JSON / Infinity compatible)
+    Serve,
+    /// Consume live meter values from Kafka and re-forecast each site as hours complete
+    Stream,
+    /// Score the model on held-out history and save the result
+    Backtest,
+    /// Write an HTML or Markdown site report
+    Report {
+        /// Output file, overriding `report.path` (`.md` for Markdown)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Rank several sites side by side on one HTML page
+    Compare {
+        /// One config file per site; every `[sites.<id>]` section or site in the `[store]` when omitted
+        sites: Vec<PathBuf>,
+        /// Output HTML file
+        #[arg(short, long, default_value = "sites.html")]
+        output: PathBuf,
+    },
+    /// Forecast every site in turn, retrying transient failures, and report which ones failed
+    Batch {
+        /// One config file per site; every `[sites.<id>]` section or site in the `[store]` when omitted
+        sites: Vec<PathBuf>,
+    },
+    /// Report past hours whose load changed since the last stored run
+    Audit {
+        /// Forecast again when any hour was revised
+        #[arg(long)]
+        refit: bool,
+    },
+    /// Compare two forecasts on their common hours and list the largest changes
+    Diff {
+        /// Forecast CSV or JSON file, or archived run id, e.g. yesterday's issue
+        a: String,
+        /// The forecast compared with `a`, e.g. today's issue
+        b: String,
+        /// Changes listed, overriding `diff.top`
+        #[arg(long)]
+        top: Option<usize>,
+    },
+    /// Forecast billing revenue per site from the energy forecast and `[pricing]`, with a monthly projection
+    Revenue {
+        /// One config file per site; the run's site alone, or every `[sites.<id>]` section or site in the `[store]` when omitted
+        sites: Vec<PathBuf>,
+    },
+    /// Write each site's average daily and weekly demand shapes, normalized to a mean of 1
+    Profiles {
+        /// One config file per site; the run's site alone, or every `[sites.<id>]` section or site in the `[store]` when omitted
+        sites: Vec<PathBuf>,
+        /// Output CSV
+        #[arg(short, long, default_value = "profiles.csv")]
+        output: String,
+    },
+    /// Remove the estimated temperature effect from the history (`[weather]`), for month-over-month comparisons
+    Normalize,
+    /// Recommend which seasonalities to fit, and with how many Fourier terms, from the history's autocorrelation and periodogram
+    Seasonality,
+    /// Sample a session export, guess which columns hold what, and print a config snippet to read it with
+    Inspect {
+        /// Export to sample, overriding `data.path`
+        file: Option<String>,
+        /// Rows sampled
+        #[arg(long, default_value_t = inspect::SAMPLE_ROWS)]
+        rows: usize,
+    },
+    /// Copy a session export for the forecasting store, with its `[data.personal]` columns emptied or hashed
+    Ingest {
+        /// Export to copy, overriding `data.path`
+        #[arg(long)]
+        file: Option<String>,
+        /// Output CSV
+        #[arg(short, long)]
+        output: String,
+    },
+    /// Group sites by the shape of their daily demand profile and list each one's cluster
+    Cluster {
+        /// One config file per site; every `[sites.<id>]` section or site in the `[store]` when omitted
+        sites: Vec<PathBuf>,
+        /// Number of clusters, overriding `cluster.k`
+        #[arg(short)]
+        k: Option<usize>,
+        /// Membership CSV, overriding `cluster.output`
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Forecast for the next market gate in `market.step_minutes` units, with the changes since the previous issue
+    Market {
+        /// Forecast the intraday window, overriding `market.preset`
+        #[arg(long)]
+        intraday: bool,
+    },
+    /// Compare the shadow challenger with the production model on live accuracy
+    Shadow,
+    /// Fill the archive with the daily forecasts that would have been issued in the past
+    Backfill {
+        /// First issue day (YYYY-MM-DD)
+        #[arg(long)]
+        from: String,
+        /// Last issue day (YYYY-MM-DD), defaulting to the last day of history
+        #[arg(long)]
+        to: Option<String>,
+        /// Hour of day the forecasts are issued
+        #[arg(long, default_value_t = 0)]
+        hour: u32,
+    },
+    /// Export Monte Carlo sample paths of the forecast
+    ///
+    /// The paths approximate the forecast distribution rather than draw from
+    /// the fitted model's posterior: each hour spreads as its interval does,
+    /// and hours are correlated by one AR(1) coefficient fitted to the last
+    /// `simulate.residual_hours` of in-sample residuals, the same at every
+    /// lead. Trend uncertainty enters only through the intervals.
+    Simulate {
+        /// Number of paths, overriding `simulate.samples`
+        #[arg(short = 'n', long)]
+        samples: Option<usize>,
+    },
+    /// Write a synthetic session export, for tests and demos
+    Synth {
+        /// Days to generate, overriding `synth.days`
+        #[arg(long)]
+        days: Option<u32>,
+        /// RNG seed, overriding `synth.seed`
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Output CSV, overriding `synth.output`
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Browse the forecast archive
+    Archive {
+        #[command(subcommand)]
+        command: ArchiveCommand,
+    },
+    /// Register and list the sites of the `[store]`
+    Sites {
+        #[command(subcommand)]
+        command: SitesCommand,
+    },
+    /// Sign and check the models published for gateways (`output.model`)
+    Model {
+        #[command(subcommand)]
+        command: ModelCommand,
+    },
+    /// Validate the config files
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Check the config and runtime files and every `[sites.<id>]` override, and list each problem
+    Check,
+    /// Rewrite config files for this version's option names, and stamp their `version`
+    Migrate {
+        /// Config files; the `--config` and `--runtime` files when omitted
+        files: Vec<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ModelCommand {
+    /// Make a key to sign published models with (`output.model_key`), its public key in `<key>.pub`
+    Keygen { key: String },
+    /// Check a published model against its `.sig` and the public key, and summarize it
+    Verify {
+        model: String,
+        /// Public key file, as `model keygen` writes it
+        #[arg(long)]
+        public_key: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ArchiveCommand {
+    /// List archived runs, oldest first
+    List,
+    /// Compare two archived runs hour by hour, largest change first (as `diff` does)
+    Diff { a: String, b: String },
+    /// Regenerate an archived run from its origin and model, and diff it with the original
+    Replay { run_id: String },
+}
+
+#[derive(Subcommand)]
+enum SitesCommand {
+    /// Register sites from their config files, or update their stored configs
+    Add {
+        #[arg(required = true)]
+        configs: Vec<PathBuf>,
+    },
+    /// Store the metadata of every site in a YAML registry
+    Import { registry: PathBuf },
+    /// List registered sites with their data watermark and latest forecast
+    List,
+    /// Forget a site and its stored models and forecasts
+    Remove { site: String },
+}
+
+impl Command {
+    /// Name in the run summary.
+    fn name(&self) -> &'static str {
+        match self {
+            Command::Forecast => "forecast",
+            Command::Depot { .. } => "depot",
+            Command::V2g => "v2g",
+            Command::Serve => "serve",
+            Command::Stream => "stream",
+            Command::Backtest => "backtest",
+            Command::Report { .. } => "report",
+            Command::Compare { .. } => "compare",
+            Command::Batch { .. } => "batch",
+            Command::Audit { .. } => "audit",
+            Command::Diff { .. } => "diff",
+            Command::Revenue { .. } => "revenue",
+            Command::Profiles { .. } => "profiles",
+            Command::Cluster { .. } => "cluster",
+            Command::Normalize => "normalize",
+            Command::Seasonality => "seasonality",
+            Command::Inspect { .. } => "inspect",
+            Command::Ingest { .. } => "ingest",
+            Command::Market { .. } => "market",
+            Command::Shadow => "shadow",
+            Command::Backfill { .. } => "backfill",
+            Command::Simulate { .. } => "simulate",
+            Command::Synth { .. } => "synth",
+            Command::Archive { .. } => "archive",
+            Command::Sites { .. } => "sites",
+            Command::Model { .. } => "model",
+            Command::Config { .. } => "config",
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match &cli.command {
+        Some(Command::Config {
+            command: ConfigCommand::Check,
+        }) => return check_config(&cli),
+        Some(Command::Config {
+            command: ConfigCommand::Migrate { files },
+        }) => {
+            let files: Vec<&PathBuf> = match files.is_empty() {
+                true => cli.config.iter().chain(&cli.runtime).collect(),
+                false => files.iter().collect(),
+            };
+            return match migrate_configs(&files) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    ExitCode::from(Status::Failed.code())
+                }
+            };
+        }
+        _ => {}
+    }
+    let shared = load_config(&cli).map(|mut shared| {
+        shared.data.strict |= cli.strict;
+        shared
+    });
+    // `shared` is the config before any `[sites.<id>]` overrides
+    let configs = shared.and_then(|shared| {
+        let config =
+            site_config(shared.clone(), cli.site.as_deref()).and_then(with_site_metadata)?;
+        Ok((shared, config))
+    });
+    let (shared, config) = match configs {
+        Ok(configs) => configs,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(Status::Failed.code());
+        }
+    };
+
+    let command = cli.command.unwrap_or(Command::Forecast);
+    let mut record = RunRecord::new(&config.site, command.name());
+    let result = match command {
+        Command::Forecast if cli.dry_run => run_dry_run(&config, &mut record),
+        _ if cli.dry_run => Err(format!(
+            "--dry-run only applies to `forecast`, not `{}`",
+            command.name()
+        )
+        .into()),
+        Command::Forecast => run_forecast(&config, &mut record),
+        Command::Depot { schedule } => run_depot(&config, schedule),
+        Command::V2g => run_v2g(&config),
+        Command::Serve => run_serve(&shared, &config),
+        Command::Stream => run_stream(&config),
+        Command::Backtest => run_backtest(&config),
+        Command::Report { output } => run_report(&config, output),
+        Command::Compare { sites, output } => run_compare(&shared, &sites, &output),
+        Command::Batch { sites } => run_batch(&shared, &sites),
+        Command::Audit { refit } => run_audit(&config, refit, &mut record),
+        Command::Diff { a, b, top } => run_diff(&config, &a, &b, top, &mut record),
+        Command::Revenue { sites } => run_revenue(&shared, &config, &sites),
+        Command::Profiles { sites, output } => run_profiles(&shared, &config, &sites, &output),
+        Command::Cluster { sites, k, output } => run_cluster(&shared, &sites, k, output),
+        Command::Normalize => run_normalize(&config),
+        Command::Seasonality => run_seasonality(&config),
+        Command::Inspect { file, rows } => run_inspect(&config, file.as_deref(), rows),
+        Command::Ingest { file, output } => run_ingest(&config, file.as_deref(), &output),
+        Command::Market { intraday } => run_market(&config, intraday, &mut record),
+        Command::Shadow => run_shadow(&config),
+        Command::Backfill { from, to, hour } => run_backfill(&config, &from, to.as_deref(), hour),
+        Command::Simulate { samples } => run_simulate(&config, samples),
+        Command::Synth { days, seed, output } => run_synth(&config, days, seed, output),
+        Command::Archive { command } => run_archive(&config, command),
+        Command::Sites { command } => run_sites(&config, command),
+        Command::Model { command } => run_model(command),
+        Command::Config { .. } => unreachable!("checked before loading the config"),
+    };
+
+    let status = record.finish(&result);
+    if let Err(e) = &result {
+        eprintln!("Error: {}", e);
+    }
+    // A dry run writes nothing, not even the summary
+    if let Some(path) = config.output.summary.as_ref().filter(|_| !cli.dry_run)
+        && let Err(e) = remote::with_output(path, |path| record.write(path))
+    {
+        eprintln!("⚠️  Could not write the run summary: {}", e);
+        return ExitCode::from(Status::Failed.code());
+    }
+    ExitCode::from(status.code())
+}
+
+/// The config of `--config` and `--runtime`, before any `[sites.<id>]` overrides.
+fn load_config(cli: &Cli) -> Result<Config, Box<dyn Error>> {
+    match (&cli.config, &cli.runtime) {
+        (site, Some(runtime)) => Config::from_files(site.as_deref(), runtime),
+        (Some(path), None) => Config::from_file(path),
+        (None, None) => Config::from_env(),
+    }
+}
+
+/// `config check`: load the config as a run would, and every site's, listing each problem.
+fn check_config(cli: &Cli) -> ExitCode {
+    let mut problems = Vec::new();
+    match load_config(cli) {
+        Ok(shared) => {
+            let sites: Vec<&String> = shared.sites.keys().collect();
+            let mut configs = vec![shared.clone()];
+            for site in &sites {
+                match shared.for_site(site) {
+                    Ok(config) => configs.push(config),
+                    Err(e) => problems.push(e.to_string()),
+                }
+            }
+            let mut plaintext = BTreeSet::new();
+            for (name, value) in configs.iter().flat_map(secret::credentials) {
+                if !secret::is_reference(&value) {
+                    plaintext.insert(name);
+                } else if let Err(e) = secret::resolve(&value) {
+                    let problem = format!("{}: {}", name, e);
+                    if !problems.contains(&problem) {
+                        problems.push(problem);
+                    }
+                }
+            }
+            if !plaintext.is_empty() {
+                let names: Vec<String> = plaintext.into_iter().collect();
+                eprintln!(
+                    "Note: {} written in plain text; `${{env:NAME}}` or `${{file:PATH}}` keeps secrets out of the file",
+                    names.join(", ")
+                );
+            }
+            if let Err(e) = shared.server.check_tenants() {
+                problems.push(e.to_string());
+            }
+            if let Err(e) =
+                site_config(shared.clone(), cli.site.as_deref()).and_then(with_site_metadata)
+            {
+                problems.push(e.to_string());
+            }
+            if cli.runtime.is_none()
+                && let Some(path) = &cli.config
+                && let Ok(table) = std::fs::read_to_string(path)
+                    .map(|text| text.parse::<toml::Table>().unwrap_or_default())
+            {
+                let runtime: Vec<&str> = table
+                    .keys()
+                    .map(String::as_str)
+                    .filter(|key| ConfigKind::of(key) == ConfigKind::Runtime)
+                    .collect();
+                if !runtime.is_empty() {
+                    eprintln!(
+                        "Note: {} could move to a --runtime file",
+                        runtime.join(", ")
+                    );
+                }
+            }
+            if problems.is_empty() {
+                println!("✅ Config OK ({} site overrides)", sites.len());
+            }
+        }
+        Err(e) => problems.push(e.to_string()),
+    }
+    for problem in &problems {
+        eprintln!("❌ {}", problem);
+    }
+    match problems.is_empty() {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::from(Status::Failed.code()),
+    }
+}
+
+/// `config migrate`: rewrite each file migrated to the current config version.
+fn migrate_configs(files: &[&PathBuf]) -> Result<(), Box<dyn Error>> {
+    if files.is_empty() {
+        return Err("No config files to migrate; give them or `--config`".into());
+    }
+    for path in files {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read config {}: {}", path.display(), e))?;
+        let mut doc: toml_edit::DocumentMut = text
+            .parse()
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+        let from = migrate::version(&doc)?;
+        let changes = migrate::migrate(&mut doc, migrate::MIGRATIONS)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let migrated = doc.to_string();
+        if migrated == text {
+            println!("{} is up to date (version {})", path.display(), from);
+            continue;
+        }
+        std::fs::write(path, &migrated)?;
+        match changes.is_empty() {
+            true => println!(
+                "{}: stamped version {}",
+                path.display(),
+                migrate::CONFIG_VERSION
+            ),
+            false => println!(
+                "{}: version {} -> {}",
+                path.display(),
+                from,
+                migrate::CONFIG_VERSION
+            ),
+        }
+        for change in changes {
+            println!("  {}", change);
+        }
+    }
+    Ok(())
+}
+
+/// Push the forecast to every configured downstream system.
+fn publish(config: &Config, forecast: &Forecast) -> Result<(), Box<dyn Error>> {
+    let context = export::ExportContext {
+        site: config.site.clone(),
+        model_version: model_version(&config.model),
+        issued_at: Utc::now().timestamp(),
+        timezone: config.timezone,
+    };
+    Fanout::from_config(config)?.write(forecast, &context)
+}
+
+/// Store the issued forecast in the archive, if one is configured.
+fn archive_run(
+    config: &Config,
+    issued_at: DateTime<Utc>,
+    history: &Series,
+    forecast: &Forecast,
+) -> Result<(), Box<dyn Error>> {
+    let Some(archive_config) = &config.archive else {
+        return Ok(());
+    };
+    let origin = *history.timestamps.last().ok_or("Empty history")?;
+    let run = ArchivedForecast::new(
+        &config.site,
+        issued_at,
+        origin,
+        &config.model,
+        forecast.clone(),
+    );
+    let path = Archive::open(&archive_config.dir, &config.site).save(&run)?;
+    eprintln!("Archived as run {} ({})", run.run_id, path.display());
+    Ok(())
+}
+
+/// Write the run's manifest next to the archived run and to `output.manifest`.
+fn write_manifest(
+    config: &Config,
+    issued_at: DateTime<Utc>,
+    history: &Series,
+    counts: &RowCounts,
+    record: &RunRecord,
+) -> Result<(), Box<dyn Error>> {
+    if config.archive.is_none() && config.output.manifest.is_none() {
+        return Ok(());
+    }
+    let manifest = Manifest::new(
+        config,
+        &source::source(config).name(),
+        issued_at,
+        history,
+        counts,
+        record,
+    );
+    if let Some(archive_config) = &config.archive {
+        Archive::open(&archive_config.dir, &config.site).save_manifest(&manifest)?;
+    }
+    if let Some(path) = &config.output.manifest {
+        let json = serde_json::to_string_pretty(&manifest)? + "\n";
+        remote::with_output(path, |path| export::write_text(path, &json))?;
+        eprintln!("Manifest written to {}", path);
+    }
+    Ok(())
+}
+
+/// The config for `--site`, or else for the config's own `site`: merged with
+/// its `[sites.<id>]` overrides, or else the site's config in the `[store]`.
+fn site_config(config: Config, site: Option<&str>) -> Result<Config, Box<dyn Error>> {
+    let Some(site) = site else {
+        return match config.sites.contains_key(&config.site) {
+            true => config.for_site(&config.site),
+            false => Ok(config),
+        };
+    };
+    if config.sites.contains_key(site) {
+        return config.for_site(site);
+    }
+    let store_config = config.store.ok_or_else(|| {
+        format!(
+            "No [sites.{}] overrides, and no [store] to look the site up in",
+            site
+        )
+    })?;
+    let mut site_config = Store::open(&store_config.path)?.config(site)?;
+    site_config.store = Some(store_config);
+    Ok(site_config)
+}
+
+/// Fill in the config from its site's metadata, in the `registry` or else the `[store]`.
+fn with_site_metadata(mut config: Config) -> Result<Config, Box<dyn Error>> {
+    let site = match (&config.registry, &config.store) {
+        (Some(path), _) => Some(
+            site::load_registry(path)?
+                .into_iter()
+                .find(|site| site.id == config.site)
+                .ok_or_else(|| format!("Site {} is not in the registry {}", config.site, path))?,
+        ),
+        (None, Some(store_config)) if Path::new(&store_config.path).exists() => {
+            Store::open(&store_config.path)?.site(&config.site)?
+        }
+        (None, _) => None,
+    };
+    if let Some(site) = site {
+        site.apply(&mut config);
+    }
+    let timezone = config.timezone;
+    for prices in config.model.prices.iter_mut().chain(
+        config
+            .depot
+            .iter_mut()
+            .flat_map(|depot| depot.prices.as_mut()),
+    ) {
+        prices.timezone = prices.timezone.or(timezone);
+    }
+    Ok(config)
+}
+
+/// Record the run's watermark, model and forecast in the `[store]`, if any.
+fn store_run(
+    config: &Config,
+    record: &RunRecord,
+    history: &Series,
+    forecast: &Forecast,
+) -> Result<(), Box<dyn Error>> {
+    let Some(store_config) = &config.store else {
+        return Ok(());
+    };
+    let store = Store::open(&store_config.path)?;
+    let now = Utc::now();
+    let origin = *history.timestamps.last().ok_or("Empty history")?;
+    store.set_watermark(
+        &config.site,
+        &Watermark {
+            last_timestamp: origin,
+            rows_read: record.rows_read.unwrap_or_default(),
+            updated_at: now.timestamp(),
+        },
+    )?;
+    let run = ArchivedForecast::new(&config.site, now, origin, &config.model, forecast.clone());
+    store.save_model(&ModelRecord {
+        site: config.site.clone(),
+        run_id: run.run_id.clone(),
+        fitted_at: now.timestamp(),
+        method: record.model.clone().unwrap_or_default(),
+        model_version: run.model_version.clone(),
+        model: config.model.clone(),
+        hours: history.len(),
+        fit_seconds: record.fit_seconds.unwrap_or_default(),
+        metrics: record.metrics,
+    })?;
+    store.save_forecast(&run, store_config.keep_forecasts)?;
+    store.save_history(&config.site, history)?;
+    eprintln!("Stored run {} in {}", run.run_id, store_config.path);
+    Ok(())
+}
+
+/// Report the hours of `history` revised since the site's last stored run.
+fn audit_history(
+    config: &Config,
+    store_config: &StoreConfig,
+    history: &Series,
+) -> Result<RevisionReport, Box<dyn Error>> {
+    let previous = Store::open(&store_config.path)?.history(&config.site)?;
+    let report = RevisionReport::new(&previous, history, store_config.revision_tolerance_kw);
+    match report.summary().split_first() {
+        Some((headline, largest)) => {
+            eprintln!("⚠️  {}", headline);
+            for line in largest {
+                eprintln!("{}", line);
+            }
+        }
+        None if !previous.is_empty() => eprintln!("✅ No past hours revised since the last run"),
+        None => {}
+    }
+    if let Some(path) = &store_config.revisions {
+        remote::with_output(path, |path| report.write(path))?;
+    }
+    Ok(report)
+}
+
+/// Check the site's history for revisions, and with `refit` forecast again if there are any.
+fn run_audit(config: &Config, refit: bool, record: &mut RunRecord) -> Result<(), Box<dyn Error>> {
+    let store_config = config
+        .store
+        .as_ref()
+        .ok_or("No [store] configured: it keeps the history of the last run")?;
+    let (history, counts) = load_history_counted(config)?;
+    let report = audit_history(config, store_config, &history)?;
+    record.revised_hours = Some(report.revisions.len());
+    if refit && !report.is_empty() {
+        eprintln!("Refitting {} on the revised history", config.site);
+        record.record_rows(&counts);
+        record.hourly_points = Some(history.len());
+        check_guards(config, &history, &counts)?;
+        return forecast_history(config, &history, &counts, record);
+    }
+    Ok(())
+}
+
+/// Forecast with the challenger model, if any, and log it next to the production forecast.
+fn record_challenger(
+    config: &Config,
+    history: &Series,
+    forecast: &Forecast,
+) -> Result<(), Box<dyn Error>> {
+    let Some(challenger) = &config.challenger else {
+        return Ok(());
+    };
+    let shadow_forecast = forecast_series(
+        history,
+        &challenger.model,
+        config.forecast.horizon_hours,
+        config.timezone,
+    )?;
+    let record = ShadowRecord::new(
+        *history.timestamps.last().ok_or("Empty history")?,
+        model_version(&config.model),
+        model_version(&challenger.model),
+        forecast.clone(),
+        shadow_forecast,
+    );
+    shadow::append(&challenger.log, &record)?;
+    eprintln!("Challenger forecast logged to {}", challenger.log);
+    Ok(())
+}
+
+/// Anomalous hours among the last `hours` of history, judged against the in-sample fit.
+fn recent_anomalies(
+    model: &dyn Forecaster,
+    history: &Series,
+    hours: usize,
+) -> Result<Vec<Anomaly>, Box<dyn Error>> {
+    let recent = tail(history, hours);
+    let fitted = model.predict(recent.timestamps.to_vec())?;
+    Ok(anomaly::detect(
+        &recent,
+        &fitted,
+        anomaly::DEFAULT_THRESHOLD,
+    ))
+}
+
+/// Post the run summary if a notifier is configured.
+fn notify_run(
+    config: &Config,
+    model: &dyn Forecaster,
+    history: &Series,
+    forecast: &Forecast,
+    violations: Vec<String>,
+) -> Result<(), Box<dyn Error>> {
+    let Some(notify_config) = &config.notify else {
+        return Ok(());
+    };
+    let anomalies = recent_anomalies(model, history, notify_config.anomaly_lookback_hours)?;
+    let site = notify_config.site_name.as_deref().unwrap_or(&config.site);
+    let summary = RunSummary::new(site, forecast, anomalies, violations);
+    notify::send(notify_config, &summary)
+}
+
+/// Print and export the forecast's utilization of the installed charging power.
+fn report_utilization(
+    config: &Config,
+    history: &Series,
+    forecast: &Forecast,
+) -> Result<(), Box<dyn Error>> {
+    let Some(utilization_config) = &config.utilization else {
+        return Ok(());
+    };
+    let installed_kw = utilization_config
+        .installed_kw
+        .filter(|kw| *kw > 0.0)
+        .ok_or(
+            "[utilization] needs installed_kw, or a registry site with the power of its chargers",
+        )?;
+    let report = UtilizationReport::new(history, forecast, installed_kw, utilization_config);
+    for line in report.summary() {
+        eprintln!("{}", line);
+    }
+    if let Some(path) = &utilization_config.csv {
+        remote::with_output(path, |path| {
+            utilization::write_csv(forecast, installed_kw, path)
+        })?;
+        eprintln!("Utilization written to {}", path);
+    }
+    if let Some(path) = &utilization_config.json {
+        remote::with_output(path, |path| report.write(path))?;
+    }
+    Ok(())
+}
+
+/// Print and write the probability-of-exceedance curves under `[exceedance]`.
+fn report_exceedance(config: &Config, forecast: &Forecast) -> Result<(), Box<dyn Error>> {
+    let Some(exceedance_config) = &config.exceedance else {
+        return Ok(());
+    };
+    if exceedance_config.thresholds_kw.is_empty() {
+        return Err("[exceedance] needs thresholds_kw".into());
+    }
+    let step = forecast
+        .timestamps
+        .get(1)
+        .zip(forecast.timestamps.first())
+        .map_or(preprocess::HOUR, |(b, a)| b - a);
+    let curves = exceedance::curves(
+        forecast,
+        config.model.interval_width,
+        &exceedance_config.thresholds_kw,
+    );
+    for curve in &curves {
+        if let Some((i, p)) = curve.highest() {
+            eprintln!(
+                "P(demand > {} kW): up to {:.0}% at {}, {:.1} hours above expected",
+                curve.threshold_kw,
+                100.0 * p,
+                format_timestamp(forecast.timestamps[i]),
+                curve.expected_hours(step)
+            );
+        }
+    }
+    if let Some(path) = &exceedance_config.csv {
+        remote::with_output(path, |path| exceedance::write_csv(forecast, &curves, path))?;
+        eprintln!("Exceedance probabilities written to {}", path);
+    }
+    if let Some(path) = &exceedance_config.plot {
+        remote::with_output(path, |path| plot::plot_exceedance(forecast, &curves, path))?;
+    }
+    Ok(())
+}
+
+/// Print and write the recommended curtailment under `[curtailment]`.
+fn report_curtailment(config: &Config, forecast: &Forecast) -> Result<(), Box<dyn Error>> {
+    let Some(curtailment_config) = &config.curtailment else {
+        return Ok(());
+    };
+    let limit_kw = curtailment_config
+        .limit_kw
+        .or(config
+            .alerts
+            .as_ref()
+            .and_then(|alerts| alerts.site_limit_kw))
+        .ok_or("[curtailment] needs limit_kw, or alerts.site_limit_kw")?;
+    if !(0.0..1.0).contains(&curtailment_config.risk_tolerance) {
+        return Err(format!(
+            "curtailment.risk_tolerance is {}; it has to be at least 0 and below 1",
+            curtailment_config.risk_tolerance
+        )
+        .into());
+    }
+    let step = forecast
+        .timestamps
+        .get(1)
+        .zip(forecast.timestamps.first())
+        .map_or(preprocess::HOUR, |(b, a)| b - a);
+    let curtailment = exceedance::Curtailment::new(
+        forecast,
+        config.model.interval_width,
+        limit_kw,
+        curtailment_config.risk_tolerance,
+    );
+    let (steps, kwh) = curtailment.totals(step);
+    let most = curtailment.curtail_kw.iter().copied().fold(0.0, f64::max);
+    eprintln!(
+        "Curtailment to stay under {:.0} kW with {:.0}% risk: {} steps, {:.0} kWh, at most {:.1} kW",
+        limit_kw,
+        100.0 * curtailment_config.risk_tolerance,
+        steps,
+        kwh,
+        most
+    );
+    if let Some(path) = &curtailment_config.csv {
+        remote::with_output(path, |path| curtailment.write_csv(forecast, path))?;
+        eprintln!("Curtailment written to {}", path);
+    }
+    Ok(())
+}
+
+/// Print and write the forecast's peak hours under `[peaks]`.
+fn report_peaks(config: &Config, forecast: &Forecast) -> Result<(), Box<dyn Error>> {
+    let Some(peaks_config) = &config.peaks else {
+        return Ok(());
+    };
+    let peaks = peaks::top(forecast, peaks_config);
+    if let Some(highest) = peaks.first() {
+        eprintln!(
+            "Peak hours: {} listed, the highest {:.1} kW at {}",
+            peaks.len(),
+            highest.kw,
+            highest.start
+        );
+    }
+    if let Some(path) = &peaks_config.json {
+        remote::with_output(path, |path| peaks::write_json(&peaks, path))?;
+        eprintln!("Peak hours written to {}", path);
+    }
+    if let Some(path) = &peaks_config.ics {
+        remote::with_output(path, |path| {
+            peaks::write_ics(&peaks, forecast, &config.site, config.timezone, path)
+        })?;
+        eprintln!("Peak calendar written to {}", path);
+    }
+    Ok(())
+}
+
+/// Forecast the ramp under `[ramp]`, returning the warning if it may break the ramp limit.
+fn report_ramp(
+    config: &Config,
+    history: &Series,
+    forecast: &Forecast,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let Some(ramp_config) = &config.ramp else {
+        return Ok(None);
+    };
+    let ramp = RampForecast::new(history, forecast, &config.model).map_err(StageError::fit)?;
+    eprintln!("{}", ramp.summary());
+    let warning = ramp.warning(ramp_config);
+    if let Some(warning) = &warning {
+        eprintln!("⚠️  {}", warning);
+    }
+    if let Some(path) = &ramp_config.csv {
+        remote::with_output(path, |path| ramp.write_csv(path))?;
+        eprintln!("Ramp forecast written to {}", path);
+    }
+    Ok(warning)
+}
+
+/// Flag hours where the forecast upper bound exceeds the configured site limit.
+///
+/// Returns the human-readable summary lines (empty when nothing is flagged).
+fn check_site_limit(config: &Config, forecast: &Forecast) -> Result<Vec<String>, Box<dyn Error>> {
+    let Some(alert_config) = &config.alerts else {
+        return Ok(Vec::new());
+    };
+    let Some(limit_kw) = alert_config.site_limit_kw else {
+        return Ok(Vec::new());
+    };
+
+    let width = config.model.interval_width;
+    let violations = alerts::check(forecast, limit_kw);
+    if violations.is_empty() {
+        eprintln!(
+            "✅ {} stays within the {} kW site limit",
+            alerts::upper_quantile_label(width),
+            limit_kw
+        );
+    }
+    let summary = alerts::summarize(&violations, limit_kw, width);
+    for line in &summary {
+        eprintln!("⚠️  {}", line);
+    }
+
+    let report = alerts::to_json(&violations, limit_kw, width);
+    if let Some(path) = &alert_config.json {
+        remote::with_output(path, |path| {
+            export::write_text(path, &serde_json::to_string_pretty(&report)?)
+        })?;
+        eprintln!("Alerts written to {}", path);
+    }
+    if let Some(url) = &alert_config.webhook
+        && !violations.is_empty()
+    {
+        notify::post_json(url, &report)?;
+    }
+    Ok(summary)
+}
+
+/// Look for a structural break in the recent history, with `[breaks]` set.
+///
+/// A break starting at `alerted`, or at the start last recorded in the
+/// `[store]` or else the `[archive]`, was reported before: it is returned but
+/// not posted again. New breaks are recorded there once reported.
+fn check_breaks(
+    config: &Config,
+    history: &Series,
+    alerted: Option<i64>,
+) -> Result<Option<Break>, Box<dyn Error>> {
+    let Some(break_config) = &config.breaks else {
+        return Ok(None);
+    };
+    let found = breaks::detect(history, break_config);
+    let alerted = alerted_break(config)?.or(alerted);
+    match &found {
+        Some(found) if alerted == Some(found.start) => return Ok(Some(found.clone())),
+        Some(found) => eprintln!("⚠️  {}: {}", config.site, found.summary()),
+        None => eprintln!(
+            "✅ No structural break in the last {} days",
+            break_config.window_days
+        ),
+    }
+    let report = breaks::report_json(&config.site, found.as_ref());
+    if let Some(path) = &break_config.json {
+        remote::with_output(path, |path| {
+            export::write_text(path, &serde_json::to_string_pretty(&report)?)
+        })?;
+    }
+    if let Some(url) = &break_config.webhook
+        && found.is_some()
+    {
+        notify::post_json(url, &report)?;
+    }
+    if let Some(found) = &found {
+        save_alerted_break(config, found.start)?;
+    }
+    Ok(found)
+}
+
+/// Start of the last break alerted for the site, from the store or else the archive.
+fn alerted_break(config: &Config) -> Result<Option<i64>, Box<dyn Error>> {
+    if let Some(store_config) = &config.store {
+        return Store::open(&store_config.path)?.alerted_break(&config.site);
+    }
+    match &config.archive {
+        Some(archive_config) => Archive::open(&archive_config.dir, &config.site).alerted_break(),
+        None => Ok(None),
+    }
+}
+
+fn save_alerted_break(config: &Config, start: i64) -> Result<(), Box<dyn Error>> {
+    if let Some(store_config) = &config.store {
+        return Store::open(&store_config.path)?.set_alerted_break(&config.site, start);
+    }
+    if let Some(archive_config) = &config.archive {
+        Archive::open(&archive_config.dir, &config.site).save_alerted_break(start)?;
+    }
+    Ok(())
+}
+
+/// Refuse a stale or patchy history under `[guards]`, as a data error.
+fn check_guards(
+    config: &Config,
+    history: &Series,
+    counts: &RowCounts,
+) -> Result<(), Box<dyn Error>> {
+    let Some(guard_config) = &config.guards else {
+        return Ok(());
+    };
+    let freshness = guards::check(history, counts, guards::now(config.timezone), guard_config)
+        .map_err(StageError::data)?;
+    eprintln!(
+        "Last observation {:.0} hours old, {:.0}% of the last {} hours missing",
+        freshness.age_hours,
+        100.0 * freshness.missing_share,
+        guard_config.window_hours
+    );
+    Ok(())
+}
+
+fn run_forecast(config: &Config, record: &mut RunRecord) -> Result<(), Box<dyn Error>> {
+    let (history, counts) = load_history_counted(config)?;
+    record.record_rows(&counts);
+    record.hourly_points = Some(history.len());
+    check_guards(config, &history, &counts)?;
+    if let Some(store_config) = &config.store {
+        record.revised_hours = Some(
+            audit_history(config, store_config, &history)?
+                .revisions
+                .len(),
+        );
+    }
+    forecast_history(config, &history, &counts, record)
+}
+
+/// Fit on a loaded history, audited already with a `[store]`, and issue the forecast.
+fn forecast_history(
+    config: &Config,
+    history: &Series,
+    counts: &RowCounts,
+    record: &mut RunRecord,
+) -> Result<(), Box<dyn Error>> {
+    let started = Instant::now();
+    let (model, forecast) = fit_and_forecast(config, history)?;
+    record.fit_seconds = Some(started.elapsed().as_secs_f64());
+    record.model = Some(model.name().to_string());
+    if let Some(diagnostics) = model.optimizer() {
+        eprintln!("{}", diagnostics.summary());
+        record.optimizer = Some(diagnostics);
+    }
+    record.forecast_hours = Some(forecast.len());
+
+    let recent = tail(history, config.forecast.horizon_hours as usize);
+    let fitted = model
+        .predict(recent.timestamps.to_vec())
+        .map_err(StageError::fit)?;
+    record.metrics = Some(metrics::evaluate(
+        &recent.values,
+        &fitted,
+        config.model.interval_width,
+    ));
+
+    // Print predictions with timestamps, unless stdout carries the CSV or summary
+    let stdout_taken = [&config.output.csv, &config.output.summary]
+        .iter()
+        .any(|path| path.as_deref() == Some(STDIO))
+        || sink::writes_stdout(config);
+    if !stdout_taken {
+        println!("Timestamp | Predicted Demand (kW)");
+        for (timestamp, prediction) in forecast.timestamps.iter().zip(&forecast.point) {
+            println!("{} | {:.1}", format_timestamp(*timestamp), prediction);
+        }
+    }
+
+    let violations = check_site_limit(config, &forecast)?;
+    record.warnings.extend(violations.iter().cloned());
+    record
+        .warnings
+        .extend(check_breaks(config, history, None)?.map(|found| found.summary()));
+    report_utilization(config, history, &forecast)?;
+    record
+        .warnings
+        .extend(report_ramp(config, history, &forecast)?);
+    report_peaks(config, &forecast)?;
+    report_exceedance(config, &forecast)?;
+    report_curtailment(config, &forecast)?;
+
+    if let Some(path) = &config.output.csv {
+        remote::with_output(path, |path| export::write_forecast_csv(&forecast, path))?;
+    }
+    if let Some(path) = &config.output.arrow {
+        remote::with_output(path, |path| write_forecast_columnar(&forecast, path))?;
+    }
+    let changepoints = model.changepoints();
+    report_changepoints(config, history, &changepoints)?;
+    if let Some(path) = &config.output.decomposition {
+        match model.decompose() {
+            Ok(decomposition) => remote::with_output(path, |path| {
+                export::write_decomposition_csv(&decomposition, history, path)
+            })?,
+            Err(e) => eprintln!("⚠️  Skipped output.decomposition: {}", e),
+        }
+    }
+    if let Some(path) = &config.output.attribution {
+        match model.attribute(forecast.timestamps.clone()) {
+            Ok(attribution) => {
+                let units = match config.model.transform {
+                    Transform::None => "kW",
+                    Transform::Log1p => "log1p(kW)",
+                    Transform::BoxCox => "boxcox(kW)",
+                };
+                let json =
+                    serde_json::to_string_pretty(&export::attribution_json(&attribution, units))?;
+                remote::with_output(path, |path| export::write_text(path, &json))?;
+                eprintln!("Attribution written to {}", path);
+            }
+            Err(e) => eprintln!("⚠️  Skipped output.attribution: {}", e),
+        }
+    }
+    if let Some(path) = &config.output.model {
+        // A missing key fails before anything unsigned is published
+        let key = config
+            .output
+            .model_key
+            .as_deref()
+            .map(artifact::signing_key)
+            .transpose()?;
+        match artifact::export(
+            model.as_ref(),
+            &config.model,
+            &config.site,
+            forecast.timestamps.clone(),
+        ) {
+            Ok(published) => {
+                artifact::save(&published, path, key.as_ref())?;
+                eprintln!("Model published to {}", path);
+            }
+            Err(e) => eprintln!("⚠️  Skipped output.model: {}", e),
+        }
+    }
+    publish(config, &forecast)?;
+    record_challenger(config, history, &forecast)?;
+    let issued_at = Utc::now();
+    archive_run(config, issued_at, history, &forecast)?;
+    forecast_log::log(config, issued_at, &forecast)?;
+    write_manifest(config, issued_at, history, counts, record)?;
+    store_run(config, record, history, &forecast)?;
+    if let Some(path) = &config.output.plot {
+        let history = tail(history, PLOT_HISTORY_HOURS);
+        let marked: Vec<i64> = changepoints
+            .iter()
+            .filter(|c| c.is_significant())
+            .map(|c| c.timestamp)
+            .collect();
+        remote::with_output(path, |path| {
+            plot::plot_forecast_with_changepoints(
+                &history,
+                &forecast,
+                &marked,
+                path,
+                config.output.plot_max_points,
+            )
+        })?;
+    }
+    notify_run(config, model.as_ref(), history, &forecast, violations)
+}
+
+/// Print the largest trend changes and write the configured changepoint outputs.
+fn report_changepoints(
+    config: &Config,
+    history: &Series,
+    changepoints: &[Changepoint],
+) -> Result<(), Box<dyn Error>> {
+    if changepoints.is_empty() {
+        return Ok(());
+    }
+    let mut significant: Vec<&Changepoint> =
+        changepoints.iter().filter(|c| c.is_significant()).collect();
+    significant.sort_by(|a, b| {
+        b.rate_change_per_day
+            .abs()
+            .total_cmp(&a.rate_change_per_day.abs())
+    });
+    eprintln!(
+        "Trend changepoints: {} of {} significant",
+        significant.len(),
+        changepoints.len()
+    );
+    for changepoint in significant.iter().take(3) {
+        eprintln!(
+            "  {} {:+.2}/day",
+            format_timestamp(changepoint.timestamp),
+            changepoint.rate_change_per_day
+        );
+    }
+    if let Some(path) = &config.output.changepoints {
+        remote::with_output(path, |path| {
+            export::write_changepoints_csv(changepoints, path)
+        })?;
+    }
+    if let Some(path) = &config.output.changepoint_plot {
+        remote::with_output(path, |path| {
+            plot::plot_changepoints(history, changepoints, path, config.output.plot_max_points)
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "arrow")]
+fn write_forecast_columnar(forecast: &Forecast, path: &str) -> Result<(), Box<dyn Error>> {
+    cpo_charging_forecast::columnar::write_forecast(forecast, path)
+}
+
+#[cfg(not(feature = "arrow"))]
+fn write_forecast_columnar(_forecast: &Forecast, path: &str) -> Result<(), Box<dyn Error>> {
+    Err(format!("Cannot write {}: built without the `arrow` feature", path).into())
+}
+
+/// `--dry-run`: load, validate and resample the export and resolve the config,
+/// then print what a forecast run would fit and write, without fitting.
+fn run_dry_run(config: &Config, record: &mut RunRecord) -> Result<(), Box<dyn Error>> {
+    let (history, counts) = load_history_counted(config)?;
+    record.record_rows(&counts);
+    record.hourly_points = Some(history.len());
+    check_guards(config, &history, &counts)?;
+
+    let source = config
+        .data
+        .http
+        .as_ref()
+        .map_or(&config.data.path, |http| &http.url);
+    println!(
+        "Export {}: {} rows, {} dropped ({} unparseable, {} without energy)",
+        source,
+        counts.read,
+        counts.dropped(),
+        counts.invalid,
+        counts.non_positive
+    );
+    println!(
+        "History: {} hourly points, {:.0}% without demand",
+        history.len(),
+        zero_share(&history) * 100.0
+    );
+
+    let model = &config.model;
+    let method = resolve_method(&history, model);
+    let training = training_hours(&history, model);
+    println!("Model {}:", model_version(model));
+    print!("{}", toml::to_string(model)?);
+    if method == Method::Prophet && training < model.min_history_hours.max(2) {
+        let fallback = match model.fallbacks.first() {
+            Some(fallback) => format!("fall back to {:?}", fallback),
+            None => "fail".to_string(),
+        };
+        let message = format!(
+            "Prophet needs {} training hours but would get {}; the fit would {}",
+            model.min_history_hours, training, fallback
+        );
+        println!("⚠️  {}", message);
+        record.warnings.push(message);
+    } else {
+        println!("Would fit {:?} on {} hours", method, training);
+    }
+
+    let last = *history.timestamps.last().ok_or("Empty history")?;
+    let horizon = future_steps(
+        last,
+        config.forecast.horizon_hours,
+        history.step(),
+        config.timezone,
+    );
+    if let (Some(first), Some(end)) = (horizon.first(), horizon.last()) {
+        println!(
+            "Would forecast {} hours ({} .. {})",
+            horizon.len() as i64 * history.step() / preprocess::HOUR,
+            format_timestamp(*first),
+            format_timestamp(*end)
+        );
+    }
+
+    let mut outputs = Vec::new();
+    outputs.extend(
+        config
+            .output
+            .csv
+            .iter()
+            .map(|path| format!("forecast CSV {}", path)),
+    );
+    outputs.extend(
+        config
+            .output
+            .arrow
+            .iter()
+            .map(|path| format!("forecast table {}", path)),
+    );
+    outputs.extend(
+        Fanout::from_config(config)?
+            .names()
+            .into_iter()
+            .map(|name| format!("forecast {}", name)),
+    );
+    outputs.extend(
+        config
+            .output
+            .decomposition
+            .iter()
+            .map(|path| format!("decomposition CSV {}", path)),
+    );
+    outputs.extend(
+        config
+            .output
+            .changepoints
+            .iter()
+            .map(|path| format!("changepoints CSV {}", path)),
+    );
+    outputs.extend(
+        config
+            .output
+            .attribution
+            .iter()
+            .map(|path| format!("attribution JSON {}", path)),
+    );
+    outputs.extend(
+        config
+            .output
+            .changepoint_plot
+            .iter()
+            .map(|path| format!("changepoint chart {}", path)),
+    );
+    outputs.extend(
+        config
+            .output
+            .plot
+            .iter()
+            .map(|path| format!("plot {}", path)),
+    );
+    outputs.extend(
+        config
+            .output
+            .summary
+            .iter()
+            .map(|path| format!("run summary {}", path)),
+    );
+    if let Some(alerts) = &config.alerts {
+        outputs.extend(alerts.json.iter().map(|path| format!("alerts {}", path)));
+        outputs.extend(alerts.webhook.iter().map(|_| "alert webhook".to_string()));
+    }
+    if let Some(breaks) = &config.breaks {
+        outputs.extend(
+            breaks
+                .json
+                .iter()
+                .map(|path| format!("structural break check {}", path)),
+        );
+        outputs.extend(
+            breaks
+                .webhook
+                .iter()
+                .map(|_| "structural break webhook".to_string()),
+        );
+    }
+    if let Some(utilization) = &config.utilization {
+        outputs.extend(
+            utilization
+                .csv
+                .iter()
+                .map(|path| format!("utilization CSV {}", path)),
+        );
+        outputs.extend(
+            utilization
+                .json
+                .iter()
+                .map(|path| format!("utilization KPIs {}", path)),
+        );
+    }
+    outputs.extend(
+        config
+            .challenger
+            .iter()
+            .map(|c| format!("challenger log {}", c.log)),
+    );
+    outputs.extend(
+        config
+            .archive
+            .iter()
+            .map(|a| format!("archive {}/{}", a.dir, config.site)),
+    );
+    outputs.extend(config.store.iter().map(|s| format!("store {}", s.path)));
+    outputs.extend(
+        config
+            .notify
+            .iter()
+            .map(|n| format!("{:?} notification", n.format)),
+    );
+    if outputs.is_empty() {
+        println!("Would write nothing");
+    }
+    for output in outputs {
+        println!("Would write {}", output);
+    }
+    Ok(())
+}
+
+fn run_depot(config: &Config, schedule: Option<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let mut depot_config = config.depot.clone().unwrap_or_default();
+    if let Some(schedule) = schedule {
+        depot_config.schedule = schedule.display().to_string();
+    }
+
+    let visits = remote::with_input(&depot_config.schedule, |path| {
+        depot::load_schedule(path, depot_config.target_soc_percent)
+    })?;
+    eprintln!("Loaded {} scheduled vehicle visits", visits.len());
+
+    let history = load_history(config)?;
+    let (model, background) = fit_and_forecast(config, &history)?;
+    let prices = match (
+        &depot_config.prices,
+        background.timestamps.first(),
+        background.timestamps.last(),
+    ) {
+        (Some(prices_config), Some(first), Some(last)) => {
+            let date = |ts: i64| {
+                DateTime::from_timestamp(ts, 0)
+                    .unwrap_or_default()
+                    .date_naive()
+            };
+            Some(Prices::load(prices_config, date(*first)..=date(*last))?)
+        }
+        _ if depot_config.strategy == ChargingStrategy::Cheapest => {
+            return Err("The `cheapest` depot strategy needs [depot.prices]".into());
+        }
+        _ => None,
+    };
+    let projection = depot::project(background, &visits, &depot_config, prices.as_ref());
+    if let Some(prices) = &prices {
+        let timestamps = &projection.total.timestamps;
+        let cost: f64 = projection
+            .fleet
+            .iter()
+            .zip(timestamps)
+            .map(|(kw, ts)| kw * prices.at(*ts) / 1000.0)
+            .sum();
+        eprintln!("Fleet energy cost at day-ahead prices: {:.2} EUR", cost);
+    }
+
+    for (vehicle, missing) in &projection.shortfalls {
+        eprintln!(
+            "⚠️  Vehicle {} will depart {:.1} kWh short of its requirement",
+            vehicle, missing
+        );
+    }
+
+    let total = &projection.total;
+    if let Some(peak) = (0..total.len()).max_by(|&a, &b| total.point[a].total_cmp(&total.point[b]))
+    {
+        eprintln!(
+            "Peak depot load {:.1} kW at {} (fleet {:.1} kW, upper bound {:.1} kW)",
+            total.point[peak],
+            format_timestamp(total.timestamps[peak]),
+            projection.fleet[peak],
+            total.upper[peak],
+        );
+    }
+
+    let violations = check_site_limit(config, total)?;
+
+    if let Some(path) = &depot_config.output {
+        remote::with_output(path, |path| depot::write_projection_csv(&projection, path))?;
+    }
+    publish(config, total)?;
+    forecast_log::log(config, Utc::now(), total)?;
+    if let Some(path) = &config.output.plot {
+        let history = tail(&history, PLOT_HISTORY_HOURS);
+        remote::with_output(path, |path| {
+            plot::plot_forecast(&history, total, path, config.output.plot_max_points)
+        })?;
+    }
+    notify_run(config, model.as_ref(), &history, total, violations)
+}
+
+fn run_v2g(config: &Config) -> Result<(), Box<dyn Error>> {
+    let v2g_config = config.v2g.clone().unwrap_or_default();
+
+    let (sessions, _) = pipeline::load_sessions(config)?;
+    let occupancy = preprocess::hourly_occupancy(&sessions);
+    eprintln!(
+        "Forecasting plugged-in occupancy from {} sessions",
+        sessions.len()
+    );
+
+    let occupancy_forecast = forecast_series(
+        &occupancy,
+        &config.model,
+        config.forecast.horizon_hours,
+        config.timezone,
+    )?;
+    let potential = v2g::estimate(occupancy_forecast, &v2g_config);
+
+    let capacity = &potential.capacity;
+    let mean = capacity.point.iter().sum::<f64>() / capacity.len().max(1) as f64;
+    let firm = capacity.lower.iter().cloned().fold(f64::INFINITY, f64::min);
+    eprintln!(
+        "V2G capacity over {} hours: mean {:.1} kW, firm (lower bound minimum) {:.1} kW",
+        capacity.len(),
+        mean,
+        if firm.is_finite() { firm } else { 0.0 },
+    );
+
+    if let Some(path) = &v2g_config.output {
+        remote::with_output(path, |path| v2g::write_potential_csv(&potential, path))?;
+    }
+    Ok(())
+}
+
+fn run_backtest(config: &Config) -> Result<(), Box<dyn Error>> {
+    let history = load_history(config)?;
+    let holdout = config
+        .backtest
+        .holdout_hours
+        .unwrap_or(config.forecast.horizon_hours);
+    let folds = backtest::run(
+        &history,
+        &config.model,
+        holdout,
+        config.backtest.folds,
+        config.backtest.step_hours,
+    )?;
+
+    let result = backtest::summarize(&folds, &config.model, holdout);
+    eprintln!("Overall: {}", backtest::summary_line(&result.overall));
+    eprintln!("         {}", backtest::pinball_line(&result.overall));
+    for (band, metrics) in backtest::by_lead_band(&folds, &config.model, &LEAD_CHECKPOINTS) {
+        eprintln!("  {:>9} ahead: {}", band, backtest::summary_line(&metrics));
+    }
+
+    let by_lead = backtest::by_lead(&folds, &config.model);
+    if let Some(path) = &config.backtest.lead_csv {
+        let rows: Vec<_> = by_lead
+            .iter()
+            .enumerate()
+            .map(|(i, m)| ((i + 1).to_string(), *m))
+            .collect();
+        remote::with_output(path, |path| {
+            backtest::write_breakdown_csv("lead_hours", &rows, path)
+        })?;
+    }
+    if let Some(path) = &config.backtest.hour_csv {
+        let rows: Vec<_> = backtest::by_hour_of_day(&folds, &config.model)
+            .into_iter()
+            .enumerate()
+            .map(|(h, m)| (format!("{:02}:00", h), m))
+            .collect();
+        remote::with_output(path, |path| {
+            backtest::write_breakdown_csv("hour", &rows, path)
+        })?;
+    }
+    if let Some(path) = &config.backtest.lead_plot {
+        remote::with_output(path, |path| plot::plot_error_by_lead(&by_lead, path))?;
+    }
+    remote::with_output(&config.backtest.output, |path| result.save(path))?;
+    eprintln!("Backtest saved to {}", config.backtest.output);
+    Ok(())
+}
+
+fn run_report(config: &Config, output: Option<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let history = load_history(config)?;
+    let (model, forecast) = fit_and_forecast(config, &history)?;
+    let anomalies = recent_anomalies(
+        model.as_ref(),
+        &history,
+        config.report.anomaly_lookback_hours,
+    )?;
+
+    // Accuracy comes from the last saved backtest, if there is one
+    let backtest =
+        remote::with_input(&config.backtest.output, |path| BacktestReport::load(path)).ok();
+
+    let mut report = Report::new(
+        &config.site,
+        tail(&history, PLOT_HISTORY_HOURS),
+        forecast,
+        backtest,
+        anomalies,
+        report::config_toml(config)?,
+    );
+    report.plot_max_points = config.output.plot_max_points;
+    let path = output
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|| config.report.path.clone());
+    report.write(&path)
+}
+
+/// The configs of `files`, or else of every `[sites.<id>]` section, or else of every site in the `[store]`.
+fn site_configs(config: &Config, files: &[PathBuf]) -> Result<Vec<Config>, Box<dyn Error>> {
+    let configs = match &config.store {
+        _ if !files.is_empty() => files
+            .iter()
+            .map(Config::from_file)
+            .collect::<Result<Vec<_>, _>>()?,
+        _ if !config.sites.is_empty() => config
+            .sites
+            .keys()
+            .map(|site| config.for_site(site))
+            .collect::<Result<_, _>>()?,
+        Some(store_config) => Store::open(&store_config.path)?.configs()?,
+        None => {
+            return Err(
+                "No site configs given, and no [sites] or [store] to take them from".into(),
+            );
+        }
+    };
+    configs.into_iter().map(with_site_metadata).collect()
+}
+
+fn run_compare(config: &Config, sites: &[PathBuf], output: &Path) -> Result<(), Box<dyn Error>> {
+    let configs = site_configs(config, sites)?;
+    // Load every export at once; the fits run one by one
+    let loaded = compare::load_sites(&configs);
+    let clustering = config.cluster.as_ref().and_then(|cluster_config| {
+        let profiles = configs
+            .iter()
+            .zip(&loaded)
+            .map(|(config, site)| match site {
+                Ok(site) => Profiles::new(&config.site, &site.history),
+                Err(e) => Err(e.to_string().into()),
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error>>>();
+        match profiles.and_then(|profiles| cluster::cluster(&profiles, cluster_config)) {
+            Ok(clustering) => Some(clustering),
+            Err(e) => {
+                eprintln!("⚠️  Sites left unclustered: {}", e);
+                None
+            }
+        }
+    });
+    let mut summaries = Vec::new();
+    for (config, site) in configs.iter().zip(loaded) {
+        eprintln!("Site {}:", config.site);
+        let mut summary = compare::summarize_history(config, site?, PLOT_HISTORY_HOURS)?;
+        summary.cluster = clustering
+            .as_ref()
+            .and_then(|c| c.member(&config.site))
+            .map(|m| format!("{}: {}", m.cluster, m.label));
+        summaries.push(summary);
+    }
+    compare::write_page(&summaries, output)
+}
+
+/// Serve the run's site, or with `[server.tenants]` every `[sites."<tenant>/<site>"]`
+/// section of a configured tenant.
+fn run_serve(shared: &Config, config: &Config) -> Result<(), Box<dyn Error>> {
+    let server_config = config.server.clone();
+    if server_config.tenants.is_empty() {
+        return server::serve(server_config, vec![config.clone()]);
+    }
+    let configs = shared
+        .sites
+        .keys()
+        .filter(|id| {
+            id.split_once('/')
+                .is_some_and(|(tenant, _)| server_config.tenants.contains_key(tenant))
+        })
+        .map(|id| shared.for_site(id).and_then(with_site_metadata))
+        .collect::<Result<Vec<_>, _>>()?;
+    if configs.is_empty() {
+        return Err(
+            "No [sites.\"<tenant>/<site>\"] sections for the configured [server.tenants]".into(),
+        );
+    }
+    server::serve(server_config, configs)
+}
+
+fn run_batch(shared: &Config, sites: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+    let configs = site_configs(shared, sites)?;
+    let mut report = BatchReport::new();
+    for config in &configs {
+        eprintln!("Site {}:", config.site);
+        let outcome = batch::run_site(&config.site, &shared.batch, std::thread::sleep, |attempt| {
+            let mut config = config.clone();
+            // Restart the optimizer from a fresh seed, in case it diverged
+            if attempt > 1 {
+                config.model.optimizer_seed = Some(rand::random());
+            }
+            run_forecast(&config, &mut RunRecord::new(&config.site, "forecast"))
+        });
+        report.push(outcome);
+    }
+    eprint!("{}", report.summary());
+    if let Some(path) = &shared.batch.report {
+        report.write(path)?;
+    }
+    match report.failed.len() {
+        0 => Ok(()),
+        failed => Err(format!(
+            "{} of {} sites failed: {}",
+            failed,
+            report.sites.len(),
+            report.failed.join(", ")
+        )
+        .into()),
+    }
+}
+
+fn run_stream(config: &Config) -> Result<(), Box<dyn Error>> {
+    let kafka_config = config.kafka.as_ref().ok_or("No [kafka] configured")?;
+    let files = sink::file_paths(config);
+    if !files.is_empty() {
+        return Err(format!(
+            "stream forecasts every site of the topic, which would overwrite each other in {}; use kafka.output_dir for a file per site",
+            files.join(", ")
+        )
+        .into());
+    }
+    if let Some(dir) = &kafka_config.output_dir {
+        std::fs::create_dir_all(dir)?;
+    }
+    // Start of the last break alerted per site, so each is alerted once
+    let mut alerted: HashMap<String, i64> = HashMap::new();
+    kafka::consume(kafka_config, config.timezone, |site, series| {
+        let mut site_config = config.clone();
+        site_config.site = site.to_string();
+        let source = SeriesSource::new(&format!("the meter values of {}", site), series);
+        let (history, _) = pipeline::load_history_from(&site_config, &source)?;
+        if let Some(found) = check_breaks(&site_config, &history, alerted.get(site).copied())? {
+            alerted.insert(site.to_string(), found.start);
+        }
+        let (_, forecast) = fit_and_forecast(&site_config, &history)?;
+        if let Some(dir) = &kafka_config.output_dir {
+            // Site names come off the wire, so keep them to one plain file name
+            let name: String = site
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || "-_.".contains(c) {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            let path = Path::new(dir).join(format!("{}.csv", name.trim_start_matches('.')));
+            export::write_forecast_csv(&forecast, &path.display().to_string())?;
+        }
+        publish(&site_config, &forecast)?;
+        forecast_log::log(&site_config, Utc::now(), &forecast)?;
+        eprintln!(
+            "✅ {}: {} forecast hours from {} hours of readings",
+            site,
+            forecast.len(),
+            history.len()
+        );
+        Ok(())
+    })
+}
+
+fn run_shadow(config: &Config) -> Result<(), Box<dyn Error>> {
+    let challenger = config
+        .challenger
+        .as_ref()
+        .ok_or("No [challenger] configured")?;
+    let records = shadow::load(&challenger.log)?;
+    let history = load_history(config)?;
+
+    let widths = (config.model.interval_width, challenger.model.interval_width);
+    let result = shadow::compare(&records, &history, challenger.window_days, widths);
+    if result.champion.n == 0 || result.challenger.n == 0 {
+        println!(
+            "No scored hours yet in the last {} days ({} logged runs)",
+            challenger.window_days,
+            records.len()
+        );
+        return Ok(());
+    }
+    println!(
+        "Live accuracy over the last {} days ({} runs):",
+        challenger.window_days, result.runs
+    );
+    println!(
+        "  Champion   {}: {}",
+        model_version(&config.model),
+        backtest::summary_line(&result.champion)
+    );
+    println!(
+        "  Challenger {}: {}",
+        model_version(&challenger.model),
+        backtest::summary_line(&result.challenger)
+    );
+    if result.challenger_wins() {
+        println!("✅ Challenger wins — copy [challenger.model] into [model] to promote it");
+    } else {
+        println!("Champion holds");
+    }
+    Ok(())
+}
+
+/// Every hour `a` and `b` have in common, largest change first, as `diff` lists them.
+fn print_diff(a: &Forecast, b: &Forecast, a_name: &str, b_name: &str) {
+    let every_hour = DiffConfig {
+        top: usize::MAX,
+        ..DiffConfig::default()
+    };
+    print!(
+        "{}",
+        DiffReport::new(a, b, &every_hour).text(a_name, b_name)
+    );
+}
+
+/// List the largest changes from forecast `a` to `b`; changes over the `[diff]`
+/// limits are validation warnings, to hold the forecast back.
+fn run_diff(
+    config: &Config,
+    a: &str,
+    b: &str,
+    top: Option<usize>,
+    record: &mut RunRecord,
+) -> Result<(), Box<dyn Error>> {
+    let mut diff_config = config.diff.clone();
+    diff_config.top = top.unwrap_or(diff_config.top);
+    let archive_config = config.archive.clone().unwrap_or_default();
+    let archive = Archive::open(&archive_config.dir, &config.site);
+    let (forecast_a, forecast_b) = (diff::load(a, &archive)?, diff::load(b, &archive)?);
+
+    let report = DiffReport::new(&forecast_a, &forecast_b, &diff_config);
+    print!("{}", report.text(a, b));
+    for violation in &report.violations {
+        eprintln!("⚠️  {}", violation);
+    }
+    if let Some(path) = &diff_config.report {
+        remote::with_output(path, |path| {
+            export::write_text(path, &serde_json::to_string_pretty(&report)?)
+        })?;
+        eprintln!("Diff report written to {}", path);
+    }
+    record.warnings.extend(report.violations);
+    Ok(())
+}
+
+/// Price each site's forecast per its `[pricing]` and sum it up by month over the sites.
+fn run_revenue(shared: &Config, config: &Config, sites: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+    let configs = match sites.is_empty() && shared.sites.is_empty() && shared.store.is_none() {
+        true => vec![config.clone()],
+        false => site_configs(shared, sites)?,
+    };
+    let mut months = Vec::new();
+    for config in &configs {
+        eprintln!("Site {}:", config.site);
+        let pricing = config.pricing.clone().unwrap_or_default();
+        let history = load_history(config)?;
+        let (_, forecast) = fit_and_forecast(config, &history)?;
+        let overstay = match pricing.idle_fee_per_hour > 0.0 {
+            true => {
+                let (sessions, _) = pipeline::load_sessions(config)?;
+                let overstay = preprocess::hourly_overstay(
+                    &sessions,
+                    pricing.charging_power_kw,
+                    pricing.idle_grace_minutes,
+                );
+                Some(forecast_series(
+                    &overstay,
+                    &config.model,
+                    config.forecast.horizon_hours,
+                    config.timezone,
+                )?)
+            }
+            false => None,
+        };
+        let revenue = RevenueForecast::new(&forecast, overstay.as_ref(), &pricing);
+        eprintln!(
+            "Revenue over {} forecast hours: {:.2} {} ({:.2} .. {:.2})",
+            revenue.len(),
+            revenue.total(),
+            pricing.currency,
+            revenue.lower.iter().sum::<f64>(),
+            revenue.upper.iter().sum::<f64>()
+        );
+        if let Some(path) = &pricing.output {
+            remote::with_output(path, |path| revenue.write_csv(path))?;
+            eprintln!("Hourly revenue written to {}", path);
+        }
+        months.extend(revenue::monthly(&config.site, &revenue));
+    }
+    let pricing = shared.pricing.clone().unwrap_or_default();
+    if let Some(path) = &pricing.monthly {
+        remote::with_output(path, |path| {
+            revenue::write_monthly_csv(&revenue::with_totals(months), &pricing.currency, path)
+        })?;
+        eprintln!("Monthly revenue written to {}", path);
+    }
+    Ok(())
+}
+
+fn run_profiles(
+    shared: &Config,
+    config: &Config,
+    sites: &[PathBuf],
+    output: &str,
+) -> Result<(), Box<dyn Error>> {
+    let configs = match sites.is_empty() && shared.sites.is_empty() && shared.store.is_none() {
+        true => vec![config.clone()],
+        false => site_configs(shared, sites)?,
+    };
+    let mut profiles = Vec::new();
+    for config in &configs {
+        let history = load_history(config)?;
+        let site = Profiles::new(&config.site, &history)?;
+        let peak = (0..24)
+            .max_by(|a, b| site.daily[*a].total_cmp(&site.daily[*b]))
+            .unwrap_or_default();
+        eprintln!(
+            "Site {}: {} hours, daily peak at {:02}:00 ({:.2}x the mean)",
+            config.site,
+            history.len(),
+            peak,
+            site.daily[peak]
+        );
+        profiles.push(site);
+    }
+    remote::with_output(output, |path| profile::write_csv(&profiles, path))?;
+    eprintln!(
+        "Profiles of {} site(s) written to {}",
+        profiles.len(),
+        output
+    );
+    Ok(())
+}
+
+fn run_cluster(
+    shared: &Config,
+    sites: &[PathBuf],
+    k: Option<usize>,
+    output: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    let mut cluster_config = shared.cluster.clone().unwrap_or_default();
+    cluster_config.k = k.unwrap_or(cluster_config.k);
+    cluster_config.output = output.unwrap_or(cluster_config.output);
+    let configs = site_configs(shared, sites)?;
+    let profiles = compare::load_sites(&configs)
+        .into_iter()
+        .zip(&configs)
+        .map(|(site, config)| Profiles::new(&config.site, &site?.history))
+        .collect::<Result<Vec<_>, _>>()?;
+    let clustering = cluster::cluster(&profiles, &cluster_config)?;
+    for cluster in &clustering.clusters {
+        eprintln!(
+            "Cluster {} ({}): {}",
+            cluster.id,
+            cluster.label,
+            cluster.sites.join(", ")
+        );
+    }
+    remote::with_output(&cluster_config.output, |path| {
+        cluster::write_csv(&clustering, path)
+    })?;
+    eprintln!("Cluster membership written to {}", cluster_config.output);
+    Ok(())
+}
+
+fn run_normalize(config: &Config) -> Result<(), Box<dyn Error>> {
+    let weather_config = config.weather.as_ref().ok_or("No [weather] configured")?;
+    let history = load_history(config)?;
+    let temperatures = remote::with_input(&weather_config.path, |path| {
+        weather::load_temperatures(path, config.timezone)
+    })?;
+    let normalization = weather::normalize(&history, &temperatures, weather_config)?;
+    let model = &normalization.model;
+    eprintln!(
+        "Weather effect over {} days: {:+.1} kWh per heating degree day (below {} °C), {:+.1} kWh per cooling degree day (above {} °C)",
+        model.days,
+        model.heating_kwh,
+        model.heating_base_c,
+        model.cooling_kwh,
+        model.cooling_base_c
+    );
+    if normalization.days_without_temperature > 0 {
+        eprintln!(
+            "⚠️  {} days without a temperature left as they were",
+            normalization.days_without_temperature
+        );
+    }
+    for month in &normalization.months {
+        eprintln!(
+            "{}: {:.0} kWh, {:.0} kWh weather-normalized",
+            month.month, month.actual_kwh, month.normalized_kwh
+        );
+    }
+    remote::with_output(&weather_config.output, |path| {
+        weather::write_csv(&normalization, path)
+    })?;
+    eprintln!("Normalized history written to {}", weather_config.output);
+    if let Some(path) = &weather_config.monthly {
+        remote::with_output(path, |path| {
+            weather::write_monthly_csv(&normalization.months, path)
+        })?;
+        eprintln!("Monthly energy written to {}", path);
+    }
+    Ok(())
+}
+
+/// Print the seasonalities the history calls for as `[model]` keys, and how they differ from the config.
+fn run_seasonality(config: &Config) -> Result<(), Box<dyn Error>> {
+    let history = load_history(config)?;
+    let recommendation = seasonality::detect(&history, &config.seasonality)?;
+    let differences = recommendation.differences(&config.model);
+    for detected in recommendation.all() {
+        let evidence = match detected.autocorrelation {
+            Some(a) => format!(
+                "autocorrelation {:.2} over {:.1} periods",
+                a, detected.periods
+            ),
+            None => format!("{:.1} periods of history, needs 2", detected.periods),
+        };
+        let verdict = match detected.fourier_order {
+            Some(order) => format!("on, {} Fourier terms", order),
+            None => "off".to_string(),
+        };
+        let changed = if differences.contains(&detected.name) {
+            " (differs from the config)"
+        } else {
+            ""
+        };
+        eprintln!("{}: {} -> {}{}", detected.name, evidence, verdict, changed);
+    }
+    print!("{}", recommendation.to_toml());
+    Ok(())
+}
+
+/// Propose `[data.locale]` and `[data.columns]` for an unfamiliar export, and
+/// check how many of the sampled rows they read.
+fn run_inspect(config: &Config, file: Option<&str>, rows: usize) -> Result<(), Box<dyn Error>> {
+    let path = file.unwrap_or(&config.data.path);
+    let sample = remote::with_input(path, |local| inspect::sample(data::open(local)?, rows))
+        .map_err(StageError::data)?;
+    let mut inspection = inspect::inspect(&sample).map_err(StageError::data)?;
+    if let Some(personal) = &config.data.personal {
+        inspection.personal.extend(&personal.columns);
+        inspection.personal.sort();
+        inspection.personal.dedup();
+    }
+    for line in inspection.summary() {
+        eprintln!("{}", line);
+    }
+    let site = inspection.site_ids.first().cloned().unwrap_or_default();
+    let format = source::csv_format(
+        &inspection.locale,
+        &inspection.mapping,
+        &site,
+        config.data.export != Export::Drop,
+    )?;
+    match data::read_sessions_formatted(sample.as_bytes(), &format) {
+        Ok((sessions, counts)) => eprintln!(
+            "Read this way, the sample has {} sessions in {} rows{}, {} unparseable",
+            sessions.len(),
+            counts.read,
+            if site.is_empty() {
+                String::new()
+            } else {
+                format!(" of site {}", site)
+            },
+            counts.invalid
+        ),
+        Err(e) => eprintln!("⚠️  Read this way, the sample has no sessions: {}", e),
+    }
+    print!("{}", inspection.to_toml(path));
+    Ok(())
+}
+
+/// `ingest`: copy the export with its personal columns replaced, so the
+/// forecasting store only ever gets the copy.
+fn run_ingest(config: &Config, file: Option<&str>, output: &str) -> Result<(), Box<dyn Error>> {
+    let personal = config
+        .data
+        .personal
+        .as_ref()
+        .ok_or("`ingest` needs [data.personal] to name the export's personal columns")?;
+    let pseudonymizer = Pseudonymizer::new(personal)?;
+    privacy::check_columns(&personal.columns, &config.data.columns)?;
+    let format = source::csv_format(
+        &config.data.locale,
+        &config.data.columns,
+        &config.site,
+        config.data.export != Export::Drop,
+    )?;
+    let path = file.unwrap_or(&config.data.path);
+    let mut rows = 0;
+    // A remote export is downloaded to an owner-only file under a random name
+    // (see remote::temp_file), removed once copied
+    remote::with_input(path, |input| {
+        remote::with_output(output, |output| {
+            rows = pseudonymizer.copy(data::open(input)?, export::create(output)?, &format)?;
+            Ok(())
+        })
+    })?;
+    let action = match personal.action {
+        PersonalAction::Drop => "emptied",
+        PersonalAction::Hash => "hashed",
+    };
+    eprintln!(
+        "Copied {} rows of {} to {}, personal columns {:?} {}",
+        rows, path, output, personal.columns, action
+    );
+    Ok(())
+}
+
+/// Forecast the window of the next market gate from the end of history, and
+/// list what changed since the previous issue in `market.output`.
+fn run_market(
+    config: &Config,
+    intraday: bool,
+    record: &mut RunRecord,
+) -> Result<(), Box<dyn Error>> {
+    let mut market_config = config.market.clone();
+    if intraday {
+        market_config.preset = Preset::Intraday;
+    }
+    let (history, counts) = load_history_counted(config)?;
+    check_guards(config, &history, &counts)?;
+    let last_timestamp = *history.timestamps.last().ok_or("Empty history")?;
+    let (window, note) = market::window(&market_config, last_timestamp + preprocess::HOUR)?;
+    if let Some(note) = note {
+        eprintln!("⚠️  {}", note);
+        record.warnings.push(note);
+    }
+
+    let mut config = config.clone();
+    config.forecast.horizon_hours = window.horizon_hours(last_timestamp);
+    let (_, forecast) = fit_and_forecast(&config, &history)?;
+    let schedule = market::schedule(&forecast, &window, market_config.step_minutes);
+    let energy = schedule.point.iter().sum::<f64>() * market_config.step_minutes as f64 / 60.0;
+    eprintln!(
+        "{} issue for {} .. {}: {} units of {} minutes, {:.0} kWh",
+        if market_config.preset == Preset::Intraday {
+            "Intraday"
+        } else {
+            "Day-ahead"
+        },
+        format_timestamp(window.start),
+        format_timestamp(window.end),
+        schedule.len(),
+        market_config.step_minutes,
+        energy
+    );
+
+    let output = &market_config.output;
+    if let Some(path) = &market_config.delta {
+        let previous = match remote::exists(output)? {
+            true => remote::with_input(output, market::read_csv)?,
+            false => Forecast::default(),
+        };
+        let delta = market::delta(&previous, &schedule, market_config.delta_tolerance_kw);
+        eprintln!(
+            "{} of {} units new or changed since the previous issue",
+            delta.len(),
+            schedule.len()
+        );
+        remote::with_output(path, |path| {
+            market::write_csv(&delta, window.issued_at, market_config.step_minutes, path)
+        })?;
+    }
+    remote::with_output(output, |path| {
+        market::write_csv(
+            &schedule,
+            window.issued_at,
+            market_config.step_minutes,
+            path,
+        )
+    })?;
+    eprintln!("Market schedule written to {}", output);
+    forecast_log::log(&config, Utc::now(), &schedule)
+}
+
+fn run_sites(config: &Config, command: SitesCommand) -> Result<(), Box<dyn Error>> {
+    let store_config = config.store.as_ref().ok_or("No [store] configured")?;
+    let store = Store::open(&store_config.path)?;
+    match command {
+        SitesCommand::Add { configs } => {
+            for path in configs {
+                let site_config = Config::from_file(&path)?;
+                let added = store.register(&site_config, Utc::now().timestamp())?;
+                let verb = if added { "Registered" } else { "Updated" };
+                eprintln!("{} site {} from {}", verb, site_config.site, path.display());
+            }
+        }
+        SitesCommand::Import { registry } => {
+            let sites = site::load_registry(&registry.display().to_string())?;
+            for site in &sites {
+                store.save_site(site)?;
+            }
+            eprintln!("Imported {} sites from {}", sites.len(), registry.display());
+        }
+        SitesCommand::List => {
+            let sites = store.sites()?;
+            if sites.is_empty() {
+                println!("No sites registered in {}", store_config.path);
+            }
+            for entry in sites {
+                let data = match entry.watermark {
+                    Some(w) => format!(
+                        "data through {} ({} rows)",
+                        format_timestamp(w.last_timestamp),
+                        w.rows_read
+                    ),
+                    None => "no data loaded yet".to_string(),
+                };
+                let name = match store.site(&entry.site)?.and_then(|site| site.name) {
+                    Some(name) => format!("{} ({})", entry.site, name),
+                    None => entry.site.clone(),
+                };
+                println!(
+                    "{} | {} | last run {}",
+                    name,
+                    data,
+                    entry.last_run.as_deref().unwrap_or("-")
+                );
+            }
+        }
+        SitesCommand::Remove { site } => {
+            if !store.remove(&site)? {
+                return Err(
+                    format!("Site {} is not registered in {}", site, store_config.path).into(),
+                );
+            }
+            eprintln!("Removed site {}", site);
+        }
+    }
+    Ok(())
+}
+
+fn run_archive(config: &Config, command: ArchiveCommand) -> Result<(), Box<dyn Error>> {
+    let archive_config = config.archive.clone().unwrap_or_default();
+    let archive = Archive::open(&archive_config.dir, &config.site);
+
+    match command {
+        ArchiveCommand::List => {
+            let runs = archive.list()?;
+            if runs.is_empty() {
+                println!("No archived forecasts for {}", config.site);
+            }
+            for run in runs {
+                let issued = DateTime::from_timestamp(run.issued_at, 0).unwrap_or_default();
+                let peak = run.forecast.point.iter().cloned().fold(0.0, f64::max);
+                println!(
+                    "{} | issued {} | origin {} | {} | {} h, peak {:.1} kW",
+                    run.run_id,
+                    issued.format("%Y-%m-%d %H:%M UTC"),
+                    format_timestamp(run.origin),
+                    run.model_version,
+                    run.forecast.len(),
+                    peak
+                );
+            }
+        }
+        ArchiveCommand::Diff { a, b } => {
+            let (run_a, run_b) = (archive.get(&a)?, archive.get(&b)?);
+            print_diff(&run_a.forecast, &run_b.forecast, &a, &b);
+        }
+        ArchiveCommand::Replay { run_id } => {
+            let run = archive.get(&run_id)?;
+            let history = load_history(config)?;
+            let replayed = archive::replay(&run, &history)?;
+            print_diff(&run.forecast, &replayed, &run_id, "replay");
+        }
+    }
+    Ok(())
+}
+
+fn parse_day(text: &str) -> Result<i64, Box<dyn Error>> {
+    let date = NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid date {:?}: {}", text, e))?;
+    Ok(date.and_time(Default::default()).and_utc().timestamp())
+}
+
+fn run_backfill(
+    config: &Config,
+    from: &str,
+    to: Option<&str>,
+    hour: u32,
+) -> Result<(), Box<dyn Error>> {
+    if hour > 23 {
+        return Err(format!("Issue hour must be 0-23, got {}", hour).into());
+    }
+    let archive_config = config.archive.clone().unwrap_or_default();
+    let archive = Archive::open(&archive_config.dir, &config.site);
+    let (sessions, _) = pipeline::load_sessions(config)?;
+
+    let from = parse_day(from)?;
+    let to = match to {
+        Some(to) => parse_day(to)?,
+        None => {
+            let last = sessions.iter().map(|s| s.stop).max().ok_or("No sessions")?;
+            last.div_euclid(24 * preprocess::HOUR) * 24 * preprocess::HOUR
+        }
+    };
+    let stored = archive::backfill(&archive, config, &sessions, (from, to), hour)?;
+    eprintln!("✅ Backfilled {} runs into {}", stored, archive_config.dir);
+    Ok(())
+}
+
+fn run_model(command: ModelCommand) -> Result<(), Box<dyn Error>> {
+    match command {
+        ModelCommand::Keygen { key } => {
+            let public_key = artifact::generate_key(&key)?;
+            eprintln!(
+                "Signing key written to {}; gateways verify with {}.pub:",
+                key, key
+            );
+            println!("{}", public_key);
+        }
+        ModelCommand::Verify { model, public_key } => {
+            let public_key = std::fs::read_to_string(&public_key)
+                .map_err(|e| format!("{}: {}", public_key, e))?;
+            let published = artifact::load(&model, Some(&public_key))?;
+            println!(
+                "{}: signature valid; {} {} for {}, fitted to {}, {} seasonalities, intervals for {} leads",
+                model,
+                published.method,
+                published.model_version,
+                published.site,
+                format_timestamp(published.fitted_to),
+                published.seasonalities.len(),
+                published.intervals.len()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn run_synth(
+    config: &Config,
+    days: Option<u32>,
+    seed: Option<u64>,
+    output: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    let mut synth_config = config.synth.clone();
+    synth_config.days = days.unwrap_or(synth_config.days);
+    synth_config.seed = seed.unwrap_or(synth_config.seed);
+    synth_config.output = output.unwrap_or(synth_config.output);
+
+    let sessions = synth::sessions(&synth_config)?;
+    remote::with_output(&synth_config.output, |path| {
+        synth::write_sessions_csv(&sessions, &synth_config, path)
+    })?;
+    eprintln!(
+        "Wrote {} synthetic sessions over {} days to {}",
+        sessions.len(),
+        synth_config.days,
+        synth_config.output
+    );
+    Ok(())
+}
+
+fn run_simulate(config: &Config, samples: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let history = load_history(config)?;
+    let (model, forecast) = fit_and_forecast(config, &history)?;
+
+    let recent = tail(&history, config.simulate.residual_hours);
+    let fitted = model.predict(recent.timestamps.to_vec())?;
+    let rho = scenarios::residual_autocorrelation(&recent, &fitted);
+    eprintln!("Residual lag-1 correlation: {:.2}", rho);
+
+    let samples = samples.unwrap_or(config.simulate.samples);
+    let paths = scenarios::sample_paths(
+        &forecast,
+        config.model.interval_width,
+        rho,
+        samples,
+        config.simulate.seed,
+    );
+    remote::with_output(&config.simulate.output, |path| {
+        scenarios::write_paths_csv(&forecast, &paths, path)
+    })
+}
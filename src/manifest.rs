@@ -0,0 +1,113 @@
+//! Run manifests: a machine-readable model card of every fit, so that any
+//! forecast can be traced back to what it was made from.
+//!
+//! A manifest records the data the model saw (its source, range and row
+//! counts), the options it was fitted with, the forecaster that ran, how long
+//! it took, how its optimizer ended and its in-sample accuracy, and the
+//! build: crate version and git commit. With an `[archive]`, the manifest of each run is stored next to
+//! it as `<run id>.manifest.json`; `output.manifest` writes it anywhere.
+//! Connection settings are left out, and with them any secret.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::archive::RUN_ID_FORMAT;
+use crate::config::{Config, Export, ForecastConfig, ModelConfig};
+use crate::convergence::OptimizerDiagnostics;
+use crate::data::{RowCounts, Series, format_timestamp};
+use crate::forecast::model_version;
+use crate::metrics::Metrics;
+use crate::outcome::RunRecord;
+use crate::preprocess::Pipeline;
+
+/// The commit the crate was built from, when built in a git checkout.
+pub const GIT_COMMIT: Option<&str> = option_env!("GIT_COMMIT");
+
+/// What a model was fitted on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DataManifest {
+    /// The source's name: a path, URL or query.
+    pub source: String,
+    /// First and last history step, local time.
+    pub first: String,
+    pub last: String,
+    pub points: usize,
+    pub rows_read: usize,
+    pub rows_invalid: usize,
+    pub rows_non_positive: usize,
+    pub rows_dropped: usize,
+}
+
+/// The options a model was fitted and forecast with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptionsManifest {
+    pub timezone: Option<Tz>,
+    pub step_minutes: u32,
+    pub export: Export,
+    pub preprocess: Pipeline,
+    pub model: ModelConfig,
+    pub forecast: ForecastConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub site: String,
+    pub run_id: String,
+    /// RFC 3339, UTC.
+    pub fitted_at: String,
+    pub crate_version: String,
+    pub git_commit: Option<String>,
+    pub data: DataManifest,
+    pub options: OptionsManifest,
+    /// The forecaster that ran, e.g. `prophet` or a fallback.
+    pub method: String,
+    pub model_version: String,
+    pub fit_seconds: Option<f64>,
+    pub optimizer: Option<OptimizerDiagnostics>,
+    pub metrics: Option<Metrics>,
+}
+
+impl Manifest {
+    /// The manifest of a run of `config` on `history`, issued at `issued_at`, as far as `record` got.
+    pub fn new(
+        config: &Config,
+        source: &str,
+        issued_at: DateTime<Utc>,
+        history: &Series,
+        counts: &RowCounts,
+        record: &RunRecord,
+    ) -> Self {
+        let step = |i: Option<&i64>| i.copied().map(format_timestamp).unwrap_or_default();
+        Self {
+            site: config.site.clone(),
+            run_id: issued_at.format(RUN_ID_FORMAT).to_string(),
+            fitted_at: issued_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            git_commit: GIT_COMMIT.map(str::to_string),
+            data: DataManifest {
+                source: source.to_string(),
+                first: step(history.timestamps.first()),
+                last: step(history.timestamps.last()),
+                points: history.len(),
+                rows_read: counts.read,
+                rows_invalid: counts.invalid,
+                rows_non_positive: counts.non_positive,
+                rows_dropped: counts.dropped(),
+            },
+            options: OptionsManifest {
+                timezone: config.timezone,
+                step_minutes: config.data.step_minutes,
+                export: config.data.export,
+                preprocess: config.data.preprocess.clone(),
+                model: config.model.clone(),
+                forecast: config.forecast.clone(),
+            },
+            method: record.model.clone().unwrap_or_default(),
+            model_version: model_version(&config.model),
+            fit_seconds: record.fit_seconds,
+            optimizer: record.optimizer.clone(),
+            metrics: record.metrics,
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Forecasts aligned to electricity-market gates, for imbalance settlement and
+//! trading.
+//!
+//! A `day_ahead` issue covers the whole delivery day after the issue, as long
+//! as its gate (`gate_hour` on the day before) is still open; an `intraday`
+//! issue covers the `intraday_hours` from the first market time unit past its
+//! lead time, so issues every `intraday_hours` follow on each other. Both are
+//! in `step_minutes` units, each at the power of the forecast hour it falls in.
+//!
+//! Every issue is kept in `output`; the next issue lists in `delta` only the
+//! units that are new or changed since, which is what has to be traded.
+
+use csv::Writer;
+use std::error::Error;
+
+use crate::config::{MarketConfig, Preset};
+use crate::data::{format_timestamp, parse_timestamp_text};
+use crate::export;
+use crate::forecast::Forecast;
+use crate::preprocess::{HOUR, floor_step};
+
+const DAY: i64 = 24 * HOUR;
+
+/// The delivery period of one issue, in local wall-clock seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    pub issued_at: i64,
+    /// First market time unit.
+    pub start: i64,
+    /// End of the last one, exclusive.
+    pub end: i64,
+}
+
+impl Window {
+    /// Forecast hours needed to reach the end of the window from `last_timestamp`.
+    pub fn horizon_hours(&self, last_timestamp: i64) -> u32 {
+        // One spare hour for a DST change inside the window
+        ((self.end - last_timestamp + HOUR - 1) / HOUR + 1).max(1) as u32
+    }
+}
+
+/// The window of an issue at `issued_at`, and why it isn't the next one, if it isn't.
+pub fn window(
+    config: &MarketConfig,
+    issued_at: i64,
+) -> Result<(Window, Option<String>), Box<dyn Error>> {
+    if config.step_minutes == 0 || 60 % config.step_minutes != 0 {
+        return Err(format!(
+            "market.step_minutes must divide the hour, not {}",
+            config.step_minutes
+        )
+        .into());
+    }
+    let step = config.step_minutes as i64 * 60;
+    match config.preset {
+        Preset::DayAhead => {
+            if config.gate_hour > 23 {
+                return Err(
+                    format!("market.gate_hour must be 0–23, not {}", config.gate_hour).into(),
+                );
+            }
+            let today = issued_at.div_euclid(DAY) * DAY;
+            let gate = today + config.gate_hour as i64 * HOUR;
+            let (start, note) = match issued_at <= gate {
+                true => (today + DAY, None),
+                false => (
+                    today + 2 * DAY,
+                    Some(format!(
+                        "The day-ahead gate for {} closed at {}; forecasting the day after",
+                        &format_timestamp(today + DAY)[..10],
+                        format_timestamp(gate)
+                    )),
+                ),
+            };
+            Ok((
+                Window {
+                    issued_at,
+                    start,
+                    end: start + DAY,
+                },
+                note,
+            ))
+        }
+        Preset::Intraday => {
+            if config.intraday_hours == 0 {
+                return Err("market.intraday_hours must be at least 1".into());
+            }
+            let earliest = issued_at + config.lead_minutes as i64 * 60;
+            let start = (earliest + step - 1).div_euclid(step) * step;
+            let end = start + config.intraday_hours as i64 * HOUR;
+            Ok((
+                Window {
+                    issued_at,
+                    start,
+                    end,
+                },
+                None,
+            ))
+        }
+    }
+}
+
+/// The `step_minutes` units of `window`, each at the mean power of the
+/// forecast steps it overlaps: its hour's, or those of a finer
+/// `data.step_minutes` grid. Units with no step forecast (a skipped DST hour)
+/// are left out.
+pub fn schedule(forecast: &Forecast, window: &Window, step_minutes: u32) -> Forecast {
+    let step = step_minutes as i64 * 60;
+    let forecast_step = forecast.step().max(1);
+    let mut schedule = Forecast::default();
+    let mut unit = window.start;
+    while unit < window.end {
+        let first = forecast
+            .timestamps
+            .partition_point(|ts| *ts < floor_step(unit, forecast_step));
+        let last = forecast.timestamps.partition_point(|ts| *ts < unit + step);
+        if first < last {
+            let mean =
+                |values: &[f64]| values[first..last].iter().sum::<f64>() / (last - first) as f64;
+            schedule.timestamps.push(unit);
+            schedule.point.push(mean(&forecast.point));
+            schedule.lower.push(mean(&forecast.lower));
+            schedule.upper.push(mean(&forecast.upper));
+        }
+        unit += step;
+    }
+    schedule
+}
+
+/// The units of `current` missing from `previous` or whose point moved by more than `tolerance_kw`.
+pub fn delta(previous: &Forecast, current: &Forecast, tolerance_kw: f64) -> Forecast {
+    let mut delta = Forecast::default();
+    for i in 0..current.len() {
+        let unchanged = previous
+            .timestamps
+            .binary_search(&current.timestamps[i])
+            .is_ok_and(|j| (current.point[i] - previous.point[j]).abs() <= tolerance_kw);
+        if !unchanged {
+            delta.timestamps.push(current.timestamps[i]);
+            delta.point.push(current.point[i]);
+            delta.lower.push(current.lower[i]);
+            delta.upper.push(current.upper[i]);
+        }
+    }
+    delta
+}
+
+/// `issued_at,delivery_start,delivery_end,kw,kw_lower,kw_upper,energy_kwh`, one row per unit.
+pub fn write_csv(
+    schedule: &Forecast,
+    issued_at: i64,
+    step_minutes: u32,
+    path: &str,
+) -> Result<(), Box<dyn Error>> {
+    let step = step_minutes as i64 * 60;
+    let hours = step_minutes as f64 / 60.0;
+    let mut wtr = Writer::from_writer(export::create(path)?);
+    wtr.write_record([
+        "issued_at",
+        "delivery_start",
+        "delivery_end",
+        "kw",
+        "kw_lower",
+        "kw_upper",
+        "energy_kwh",
+    ])?;
+    for i in 0..schedule.len() {
+        wtr.write_record([
+            format_timestamp(issued_at),
+            format_timestamp(schedule.timestamps[i]),
+            format_timestamp(schedule.timestamps[i] + step),
+            format!("{:.3}", schedule.point[i]),
+            format!("{:.3}", schedule.lower[i]),
+            format!("{:.3}", schedule.upper[i]),
+            format!("{:.3}", schedule.point[i] * hours),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// A schedule [`write_csv`] wrote.
+pub fn read_csv(path: &str) -> Result<Forecast, Box<dyn Error>> {
+    let mut reader =
+        csv::Reader::from_path(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let mut schedule = Forecast::default();
+    for (i, record) in reader.records().enumerate() {
+        let record = record?;
+        let row = i + 2;
+        let number = |j: usize| -> Result<f64, Box<dyn Error>> {
+            let text = record.get(j).unwrap_or_default();
+            text.trim()
+                .parse()
+                .map_err(|_| format!("{} row {}: {:?} is not a number", path, row, text).into())
+        };
+        let start = record.get(1).unwrap_or_default();
+        let start = parse_timestamp_text(start, None)
+            .ok_or_else(|| format!("{} row {}: invalid delivery_start {:?}", path, row, start))?;
+        schedule.timestamps.push(start);
+        schedule.point.push(number(3)?);
+        schedule.lower.push(number(4)?);
+        schedule.upper.push(number(5)?);
+    }
+    Ok(schedule)
+}
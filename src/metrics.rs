@@ -0,0 +1,234 @@
+//! Forecast accuracy metrics.
+
+use serde::{Deserialize, Serialize};
+
+use crate::forecast::Forecast;
+
+/// Point and interval accuracy of a forecast against actuals.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metrics {
+    /// Number of compared hours.
+    pub n: usize,
+    /// Mean absolute error (kW).
+    pub mae: f64,
+    /// Root mean squared error (kW).
+    pub rmse: f64,
+    /// Symmetric MAPE in percent; hours where both values are zero are skipped.
+    pub smape: f64,
+    /// Mean of forecast − actual (kW); positive means over-forecasting.
+    pub bias: f64,
+    /// Share of actuals inside the forecast interval (0–1).
+    pub coverage: f64,
+    /// Mean pinball loss (kW) of the lower bound at its quantile, `(1 - width) / 2`.
+    #[serde(default)]
+    pub pinball_lower: f64,
+    /// Mean pinball loss (kW) of the point forecast as the median.
+    #[serde(default)]
+    pub pinball_median: f64,
+    /// Mean pinball loss (kW) of the upper bound at its quantile, `(1 + width) / 2`.
+    #[serde(default)]
+    pub pinball_upper: f64,
+    /// Mean Winkler score (kW): interval width plus a penalty for actuals outside it.
+    #[serde(default)]
+    pub winkler: f64,
+}
+
+/// The `q` quantile of sorted values (nearest rank).
+pub fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (q * (sorted.len() - 1) as f64).round() as usize;
+    sorted[rank.min(sorted.len() - 1)]
+}
+
+/// Pinball (quantile) loss of `predicted` as quantile `q` of `actual`.
+pub fn pinball_loss(actual: f64, predicted: f64, q: f64) -> f64 {
+    let diff = actual - predicted;
+    if diff >= 0.0 {
+        q * diff
+    } else {
+        (q - 1.0) * diff
+    }
+}
+
+/// Winkler score of the interval `[lower, upper]` at coverage `width` (e.g. 0.8).
+pub fn winkler_score(actual: f64, lower: f64, upper: f64, width: f64) -> f64 {
+    let alpha = (1.0 - width).max(f64::EPSILON);
+    let penalty = if actual < lower {
+        2.0 / alpha * (lower - actual)
+    } else if actual > upper {
+        2.0 / alpha * (actual - upper)
+    } else {
+        0.0
+    };
+    (upper - lower) + penalty
+}
+
+/// Standard normal quantile of `p`, via the Acklam rational approximation.
+pub fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -39.69683028665376,
+        220.9460984245205,
+        -275.9285104469687,
+        138.357751867269,
+        -30.66479806614716,
+        2.506628274631,
+    ];
+    const B: [f64; 5] = [
+        -54.47609879822406,
+        161.5858368580409,
+        -155.6989798598866,
+        66.80131188771972,
+        -13.28068155288572,
+    ];
+    const C: [f64; 6] = [
+        -0.007784894002430293,
+        -0.3223964580411365,
+        -2.400758277161838,
+        -2.549732539343734,
+        4.374664141464968,
+        2.938163982698783,
+    ];
+    const D: [f64; 4] = [
+        0.007784695709041462,
+        0.3224671290700398,
+        2.445134137142996,
+        3.754408661907416,
+    ];
+
+    let p = p.clamp(1e-12, 1.0 - 1e-12);
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    if p < 0.02425 {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - 0.02425 {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+/// Standard normal CDF at `x`, via the complementary error function of
+/// Numerical Recipes (fractional error below 1.2e-7).
+pub fn normal_cdf(x: f64) -> f64 {
+    let z = x.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398
+                                + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let erfc = t * poly.exp();
+    if x >= 0.0 {
+        1.0 - erfc / 2.0
+    } else {
+        erfc / 2.0
+    }
+}
+
+/// Ordinary least squares: the coefficients fitting `y` best from the rows of
+/// `x`, via the normal equations; `None` when the columns aren't independent.
+pub fn least_squares(x: &[Vec<f64>], y: &[f64]) -> Option<Vec<f64>> {
+    let n = x.first()?.len();
+    let mut xtx = vec![vec![0.0; n]; n];
+    let mut xty = vec![0.0; n];
+    for (row, target) in x.iter().zip(y) {
+        for i in 0..n {
+            xty[i] += row[i] * target;
+            for j in 0..n {
+                xtx[i][j] += row[i] * row[j];
+            }
+        }
+    }
+    solve(xtx, xty)
+}
+
+/// Solve `a x = b` by Gaussian elimination with partial pivoting; `None` when singular.
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|i, j| a[*i][col].abs().total_cmp(&a[*j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-9 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..n {
+            let factor = a[row][col] / a[col][col];
+            let (above, below) = a.split_at_mut(row);
+            for (target, source) in below[0][col..].iter_mut().zip(&above[col][col..]) {
+                *target -= factor * source;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+/// Compare a forecast with the actual values at the same timestamps.
+///
+/// `interval_width` is the coverage the forecast's interval was built for; it
+/// sets the quantile levels of the bounds.
+pub fn evaluate(actual: &[f64], forecast: &Forecast, interval_width: f64) -> Metrics {
+    let n = actual.len().min(forecast.len());
+    if n == 0 {
+        return Metrics::default();
+    }
+
+    let (mut abs, mut sq, mut err, mut covered) = (0.0, 0.0, 0.0, 0usize);
+    let (mut smape, mut smape_n) = (0.0, 0usize);
+    let (mut pinball_lower, mut pinball_median, mut pinball_upper, mut winkler) =
+        (0.0, 0.0, 0.0, 0.0);
+    let (q_lower, q_upper) = ((1.0 - interval_width) / 2.0, (1.0 + interval_width) / 2.0);
+    let bounds = forecast.lower.iter().zip(&forecast.upper);
+    for ((&a, &f), (&lower, &upper)) in actual.iter().zip(&forecast.point).zip(bounds).take(n) {
+        let e = f - a;
+        abs += e.abs();
+        sq += e * e;
+        err += e;
+        if (lower..=upper).contains(&a) {
+            covered += 1;
+        }
+        pinball_lower += pinball_loss(a, lower, q_lower);
+        pinball_median += pinball_loss(a, f, 0.5);
+        pinball_upper += pinball_loss(a, upper, q_upper);
+        winkler += winkler_score(a, lower, upper, interval_width);
+        let denom = a.abs() + f.abs();
+        if denom > 0.0 {
+            smape += 2.0 * e.abs() / denom;
+            smape_n += 1;
+        }
+    }
+
+    let nf = n as f64;
+    Metrics {
+        n,
+        mae: abs / nf,
+        rmse: (sq / nf).sqrt(),
+        smape: if smape_n > 0 {
+            100.0 * smape / smape_n as f64
+        } else {
+            0.0
+        },
+        bias: err / nf,
+        coverage: covered as f64 / nf,
+        pinball_lower: pinball_lower / nf,
+        pinball_median: pinball_median / nf,
+        pinball_upper: pinball_upper / nf,
+        winkler: winkler / nf,
+    }
+}
@@ -0,0 +1,155 @@
+//! Config versions, and migrating older configs when option names change.
+//!
+//! Config files carry the `version` of the schema they were written for;
+//! files without one are version 1. A release that renames or drops an option
+//! bumps [`CONFIG_VERSION`] and adds a [`Migration`] to [`MIGRATIONS`], so
+//! older files still load, migrated on the fly with a warning, and
+//! `config migrate` rewrites them in place, comments and layout kept.
+
+use std::error::Error;
+use toml_edit::{Decor, DocumentMut, Item, Table, TableLike, value};
+
+/// Version of the config schema this build reads and writes.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// One step of the config schema, from the version before `to`.
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    pub to: u32,
+    /// Options moved to another name, as dotted paths, e.g.
+    /// `("forecast.horizon", "forecast.horizon_hours")`.
+    pub renames: &'static [(&'static str, &'static str)],
+    /// Options that are gone, with what to use instead.
+    pub removals: &'static [(&'static str, &'static str)],
+}
+
+/// Every step since version 1, oldest first.
+pub const MIGRATIONS: &[Migration] = &[];
+
+/// Migrate `doc` to the newest version of `migrations` (at least
+/// [`CONFIG_VERSION`]), its `[sites.<id>]` overrides included, and stamp it.
+///
+/// Returns what changed, one line per option. A config newer than this
+/// build is an error rather than read wrong.
+pub fn migrate(
+    doc: &mut DocumentMut,
+    migrations: &[Migration],
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let target = migrations
+        .iter()
+        .map(|m| m.to)
+        .fold(CONFIG_VERSION, u32::max);
+    let version = version(doc)?;
+    if version > target {
+        return Err(format!(
+            "config version {} is newer than this build reads ({}); upgrade cpo-charging-forecast",
+            version, target
+        )
+        .into());
+    }
+    let mut changes = Vec::new();
+    for migration in migrations.iter().filter(|m| m.to > version) {
+        apply(doc.as_table_mut(), migration, "", &mut changes);
+        if let Some(sites) = doc.get_mut("sites").and_then(Item::as_table_like_mut) {
+            for (site, overrides) in sites.iter_mut() {
+                if let Some(overrides) = overrides.as_table_like_mut() {
+                    apply(
+                        overrides,
+                        migration,
+                        &format!("sites.{}.", site),
+                        &mut changes,
+                    );
+                }
+            }
+        }
+    }
+    if doc.get("version").is_none() || version < target {
+        doc["version"] = value(target as i64);
+    }
+    Ok(changes)
+}
+
+/// The `version` of a config; 1 when it has none.
+pub fn version(doc: &DocumentMut) -> Result<u32, Box<dyn Error>> {
+    match doc.get("version") {
+        None => Ok(1),
+        Some(item) => item
+            .as_integer()
+            .and_then(|v| u32::try_from(v).ok())
+            .filter(|v| *v >= 1)
+            .ok_or_else(|| "config `version` must be a whole number from 1".into()),
+    }
+}
+
+fn apply(
+    table: &mut dyn TableLike,
+    migration: &Migration,
+    prefix: &str,
+    changes: &mut Vec<String>,
+) {
+    for (from, to) in migration.renames {
+        let Some((item, decor)) = take(table, from) else {
+            continue;
+        };
+        match get(table, to).is_some() {
+            true => changes.push(format!(
+                "{0}{1} dropped, {0}{2} is set too",
+                prefix, from, to
+            )),
+            false => {
+                put(table, to, item, decor);
+                changes.push(format!("{0}{1} renamed to {0}{2}", prefix, from, to));
+            }
+        }
+    }
+    for (key, instead) in migration.removals {
+        if take(table, key).is_some() {
+            changes.push(format!("{}{} removed: {}", prefix, key, instead));
+        }
+    }
+}
+
+fn get<'a>(table: &'a dyn TableLike, path: &str) -> Option<&'a Item> {
+    let (last, parents) = split(path);
+    let mut table = table;
+    for part in parents {
+        table = table.get(part)?.as_table_like()?;
+    }
+    table.get(last)
+}
+
+/// Remove the option at `path`, with the comments around its key.
+fn take(table: &mut dyn TableLike, path: &str) -> Option<(Item, Decor)> {
+    let (last, parents) = split(path);
+    let mut table = table;
+    for part in parents {
+        table = table.get_mut(part)?.as_table_like_mut()?;
+    }
+    let decor = table.get_key_value_mut(last)?.0.leaf_decor().clone();
+    Some((table.remove(last)?, decor))
+}
+
+/// Put `item` at `path`, creating the sections on the way.
+fn put(table: &mut dyn TableLike, path: &str, item: Item, decor: Decor) {
+    let (last, parents) = split(path);
+    let mut table = table;
+    for part in parents {
+        if table.get(part).and_then(Item::as_table_like).is_none() {
+            table.insert(part, Item::Table(Table::new()));
+        }
+        let Some(section) = table.get_mut(part).and_then(Item::as_table_like_mut) else {
+            return;
+        };
+        table = section;
+    }
+    table.insert(last, item);
+    if let Some((mut key, _)) = table.get_key_value_mut(last) {
+        *key.leaf_decor_mut() = decor;
+    }
+}
+
+fn split(path: &str) -> (&str, Vec<&str>) {
+    let mut parts: Vec<&str> = path.split('.').collect();
+    let last = parts.pop().unwrap_or_default();
+    (last, parts)
+}
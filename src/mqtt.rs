@@ -0,0 +1,70 @@
+//! MQTT output: publish each issued forecast for EMS/HEMS subscribers.
+
+use rumqttc::{Client, Event, MqttOptions, Outgoing, Packet, QoS};
+use serde_json::json;
+use std::error::Error;
+use std::time::Duration;
+
+use crate::config::MqttConfig;
+use crate::data::format_timestamp;
+use crate::export::forecast_json;
+use crate::forecast::Forecast;
+use crate::secret;
+
+/// Publish a forecast to the configured topics and wait until the broker has it.
+///
+/// The full series goes to `topic`; if `next_hour_topic` is set, the first
+/// forecast hour is also published there on its own. Both are retained by
+/// default so a controller that restarts picks up the latest issue at once.
+pub fn publish_forecast(config: &MqttConfig, forecast: &Forecast) -> Result<(), Box<dyn Error>> {
+    let qos = match config.qos {
+        0 => QoS::AtMostOnce,
+        1 => QoS::AtLeastOnce,
+        other => return Err(format!("Unsupported MQTT QoS {} (use 0 or 1)", other).into()),
+    };
+
+    let mut messages = vec![(
+        config.topic.clone(),
+        serde_json::to_vec(&forecast_json(forecast))?,
+    )];
+    if let (Some(topic), Some(&ts)) = (&config.next_hour_topic, forecast.timestamps.first()) {
+        let next = json!({
+            "timestamp": format_timestamp(ts),
+            "yhat": forecast.point[0],
+            "yhat_lower": forecast.lower[0],
+            "yhat_upper": forecast.upper[0],
+        });
+        messages.push((topic.clone(), serde_json::to_vec(&next)?));
+    }
+
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_max_packet_size(1 << 20, 1 << 20);
+    if let (Some(user), Some(pass)) = (&config.username, &config.password) {
+        options.set_credentials(secret::resolve(user)?, secret::resolve(pass)?);
+    }
+
+    let (client, mut connection) = Client::new(options, messages.len() + 1);
+    let total = messages.len();
+    for (topic, payload) in messages {
+        client.publish(topic, qos, config.retain, payload)?;
+    }
+
+    // Drive the event loop until every message is on the broker, then disconnect
+    let mut done = 0;
+    for event in connection.iter() {
+        match event.map_err(|e| format!("MQTT {}:{}: {}", config.host, config.port, e))? {
+            Event::Outgoing(Outgoing::Publish(_)) if qos == QoS::AtMostOnce => done += 1,
+            Event::Incoming(Packet::PubAck(_)) => done += 1,
+            Event::Outgoing(Outgoing::Disconnect) => break,
+            _ => {}
+        }
+        if done == total {
+            client.disconnect()?;
+            done += 1;
+        }
+    }
+
+    eprintln!("Forecast published to MQTT topic {}", config.topic);
+    Ok(())
+}
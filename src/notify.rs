@@ -0,0 +1,169 @@
+//! Post forecast summaries to Slack, Teams or a generic JSON webhook.
+
+use serde_json::{Value, json};
+use std::error::Error;
+
+use crate::anomaly::Anomaly;
+use crate::config::{NotifyConfig, WebhookFormat};
+use crate::data::format_timestamp;
+use crate::forecast::Forecast;
+use crate::preprocess::HOUR;
+use crate::secret;
+
+/// The headline numbers of one forecast run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunSummary {
+    pub site: String,
+    /// Peak hour of the calendar day after the forecast starts.
+    pub next_day_peak_kw: f64,
+    pub next_day_peak_at: i64,
+    pub next_day_energy_kwh: f64,
+    pub horizon_energy_kwh: f64,
+    pub horizon_hours: usize,
+    pub anomalies: Vec<Anomaly>,
+    /// Human-readable site-limit violations (see [`crate::alerts::summarize`]).
+    pub violations: Vec<String>,
+}
+
+impl RunSummary {
+    /// Summarise a forecast; its kW last a step each (see [`Forecast::step_hours`]).
+    pub fn new(
+        site: &str,
+        forecast: &Forecast,
+        anomalies: Vec<Anomaly>,
+        violations: Vec<String>,
+    ) -> Self {
+        let day = 24 * HOUR;
+        let next_day = forecast
+            .timestamps
+            .first()
+            .map(|ts| ts.div_euclid(day) + 1)
+            .unwrap_or_default();
+        let tomorrow: Vec<usize> = (0..forecast.len())
+            .filter(|&i| forecast.timestamps[i].div_euclid(day) == next_day)
+            .collect();
+        // Fall back to the first 24 hours if the horizon doesn't reach tomorrow
+        let window = if tomorrow.is_empty() {
+            (0..forecast.len().min((day / forecast.step().max(1)) as usize)).collect()
+        } else {
+            tomorrow
+        };
+        let step_hours = forecast.step_hours();
+        let peak = window
+            .iter()
+            .copied()
+            .max_by(|&a, &b| forecast.point[a].total_cmp(&forecast.point[b]));
+
+        Self {
+            site: site.to_string(),
+            next_day_peak_kw: peak.map(|i| forecast.point[i]).unwrap_or_default(),
+            next_day_peak_at: peak.map(|i| forecast.timestamps[i]).unwrap_or_default(),
+            next_day_energy_kwh: window.iter().map(|&i| forecast.point[i]).sum::<f64>()
+                * step_hours,
+            horizon_energy_kwh: forecast.point.iter().sum::<f64>() * step_hours,
+            horizon_hours: (forecast.len() as i64 * forecast.step() / HOUR) as usize,
+            anomalies,
+            violations,
+        }
+    }
+
+    /// Plain-text lines shared by the chat formats.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!(
+                "Next-day peak: {:.1} kW at {}",
+                self.next_day_peak_kw,
+                format_timestamp(self.next_day_peak_at)
+            ),
+            format!("Next-day energy: {:.0} kWh", self.next_day_energy_kwh),
+            format!(
+                "Total over {} h horizon: {:.0} kWh",
+                self.horizon_hours, self.horizon_energy_kwh
+            ),
+        ];
+        if self.anomalies.is_empty() {
+            lines.push("No anomalies in recent actuals".to_string());
+        } else {
+            lines.push(format!(
+                "{} anomalous hour(s) in recent actuals:",
+                self.anomalies.len()
+            ));
+            for a in self.anomalies.iter().take(5) {
+                lines.push(format!(
+                    "  {}: {:.1} kW actual vs {:.1} kW expected",
+                    format_timestamp(a.timestamp),
+                    a.actual_kw,
+                    a.expected_kw
+                ));
+            }
+        }
+        if self.violations.is_empty() {
+            lines.push("No site-limit violations".to_string());
+        } else {
+            lines.extend(self.violations.iter().map(|v| format!("⚠️ {}", v)));
+        }
+        lines
+    }
+
+    fn title(&self) -> String {
+        format!("EV charging forecast — {}", self.site)
+    }
+
+    /// The webhook body for the given format.
+    pub fn payload(&self, format: WebhookFormat) -> Value {
+        match format {
+            WebhookFormat::Slack => json!({
+                "text": format!("*{}*\n{}", self.title(), self.lines().join("\n")),
+            }),
+            WebhookFormat::Teams => json!({
+                "@type": "MessageCard",
+                "@context": "https://schema.org/extensions",
+                "summary": self.title(),
+                "title": self.title(),
+                "text": self.lines().join("<br>"),
+            }),
+            WebhookFormat::Generic => json!({
+                "site": self.site,
+                "next_day_peak_kw": self.next_day_peak_kw,
+                "next_day_peak_at": format_timestamp(self.next_day_peak_at),
+                "next_day_energy_kwh": self.next_day_energy_kwh,
+                "horizon_energy_kwh": self.horizon_energy_kwh,
+                "horizon_hours": self.horizon_hours,
+                "anomalies": self.anomalies.iter().map(|a| json!({
+                    "timestamp": format_timestamp(a.timestamp),
+                    "actual_kw": a.actual_kw,
+                    "expected_kw": a.expected_kw,
+                    "score": a.score,
+                })).collect::<Vec<_>>(),
+                "violations": self.violations,
+            }),
+        }
+    }
+}
+
+/// POST a JSON document to a webhook URL.
+///
+/// Errors name `url` as configured, with its secret references unresolved,
+/// or a plain URL by its host (see [`secret::url_host`]).
+pub fn post_json(url: &str, body: &Value) -> Result<(), Box<dyn Error>> {
+    ureq::post(&secret::resolve(url)?)
+        .send_json(body)
+        .map_err(|e| {
+            format!(
+                "Webhook {} failed: {}",
+                secret::url_host(url),
+                secret::http_error(&e)
+            )
+        })?;
+    Ok(())
+}
+
+/// Send the run summary to the configured webhook.
+pub fn send(config: &NotifyConfig, summary: &RunSummary) -> Result<(), Box<dyn Error>> {
+    if config.webhook.is_empty() {
+        return Err("notify.webhook is not set".into());
+    }
+    post_json(&config.webhook, &summary.payload(config.format))?;
+    eprintln!("Summary posted to {:?} webhook", config.format);
+    Ok(())
+}
@@ -0,0 +1,243 @@
+//! Near-term blending: the first hours of a forecast pulled towards a
+//! short-term autoregressive model of the latest readings.
+//!
+//! Prophet's seasonal fit barely reacts to what the site did in the last few
+//! hours, which is what a load manager acting on 1–6 hour leads needs. An
+//! AR model fitted on the recent history is good for those leads and poor
+//! after them, so the two are blended with the AR weight falling off linearly
+//! by lead time, to nothing after `near_term.hours`.
+//!
+//! Between runs, [`live`] corrects the current and next hour of a stored
+//! forecast by the telemetry since it was issued (`POST /nowcast` in server
+//! mode).
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::config::NearTermConfig;
+use crate::data::{Series, format_timestamp};
+use crate::forecast::Forecast;
+use crate::metrics::least_squares;
+use crate::pipeline::tail;
+use crate::preprocess::{HOUR, floor_hour};
+
+/// `y[t] = intercept + Σ coefficients[i] · y[t - 1 - i]`, fitted by least squares.
+///
+/// Recent readings too regular for all the lags (e.g. a site at zero) drop
+/// lags, down to plain persistence of the last reading.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Autoregressive {
+    pub intercept: f64,
+    /// Latest lag first.
+    pub coefficients: Vec<f64>,
+    /// The readings the first prediction is made from, oldest first.
+    recent: Vec<f64>,
+}
+
+impl Autoregressive {
+    /// Fit on the last `config.lookback_hours` of `history`.
+    pub fn fit(history: &Series, config: &NearTermConfig) -> Result<Self, Box<dyn Error>> {
+        let lags = config.lags.max(1);
+        let values = tail(history, config.lookback_hours).values;
+        if values.len() < 4 * (lags + 1) {
+            return Err(format!(
+                "The near-term model needs {} recent hours, got {}",
+                4 * (lags + 1),
+                values.len()
+            )
+            .into());
+        }
+        let recent = values[values.len() - lags..].to_vec();
+        for lags in (1..=lags).rev() {
+            let x: Vec<Vec<f64>> = values
+                .windows(lags + 1)
+                .map(|w| {
+                    std::iter::once(1.0)
+                        .chain(w[..lags].iter().rev().cloned())
+                        .collect()
+                })
+                .collect();
+            let y: Vec<f64> = values.windows(lags + 1).map(|w| w[lags]).collect();
+            if let Some(beta) = least_squares(&x, &y) {
+                return Ok(Self {
+                    intercept: beta[0],
+                    coefficients: beta[1..].to_vec(),
+                    recent,
+                });
+            }
+        }
+        Ok(Self {
+            intercept: values[values.len() - 1],
+            coefficients: Vec::new(),
+            recent,
+        })
+    }
+
+    /// The next `hours` values, each predicted from the ones before.
+    pub fn predict(&self, hours: usize) -> Vec<f64> {
+        let mut recent = self.recent.clone();
+        (0..hours)
+            .map(|_| {
+                let next = self.intercept
+                    + self
+                        .coefficients
+                        .iter()
+                        .zip(recent.iter().rev())
+                        .map(|(c, y)| c * y)
+                        .sum::<f64>();
+                recent.push(next);
+                next.max(0.0)
+            })
+            .collect()
+    }
+}
+
+/// Weight of the short-term model at a lead time (1 = the first forecast
+/// step) when `steps` are blended.
+pub fn weight(lead: usize, steps: usize) -> f64 {
+    (steps + 1).saturating_sub(lead) as f64 / (steps + 1) as f64
+}
+
+/// `forecast`, issued at the end of `history`, with its first
+/// `config.hours` blended, however many steps that is at the history's step.
+///
+/// The interval moves with the point forecast.
+pub fn blend(
+    forecast: &Forecast,
+    history: &Series,
+    config: &NearTermConfig,
+) -> Result<Forecast, Box<dyn Error>> {
+    let model = Autoregressive::fit(history, config)?;
+    let per_hour = (HOUR / history.step()).max(1) as usize;
+    let steps = config.hours * per_hour;
+    let mut blended = forecast.clone();
+    for (i, short_term) in model
+        .predict(steps.min(forecast.len()))
+        .into_iter()
+        .enumerate()
+    {
+        let w = weight(i + 1, steps);
+        let shift = w * (short_term - forecast.point[i]);
+        blended.point[i] = (forecast.point[i] + shift).max(0.0);
+        blended.lower[i] = (forecast.lower[i] + shift).max(0.0);
+        blended.upper[i] = (forecast.upper[i] + shift).max(0.0);
+    }
+    Ok(blended)
+}
+
+/// Share of the latest error carried over to the hour after the current one.
+const NEXT_HOUR_CARRY: f64 = 0.5;
+
+/// A stored forecast corrected by the telemetry since it was issued.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Live {
+    /// Hours since the issue with at least one reading.
+    pub hours_observed: usize,
+    /// How far the readings ran above (or below) the forecast, in kW, the
+    /// latest hours weighing most.
+    pub adjustment_kw: f64,
+    /// The current hour (that of the latest reading) and the next, as
+    /// forecast, averaged over their steps.
+    pub forecast: Forecast,
+    /// The same hours corrected.
+    pub estimate: Forecast,
+}
+
+/// Correct the current and next hour of `forecast` by `readings` (local
+/// wall-clock seconds, kW) taken since it was issued, without a refit.
+///
+/// Each observed hour's error is its mean reading less the forecast's mean
+/// over the hour's steps, the current hour's from the readings so far. Their
+/// mean, halving in weight with each hour back, moves the current hour, and
+/// half of it the next.
+pub fn live(forecast: &Forecast, readings: &[(i64, f64)]) -> Result<Live, Box<dyn Error>> {
+    let forecast = &hourly(forecast);
+    let issued = *forecast
+        .timestamps
+        .first()
+        .ok_or("No forecast to correct")?;
+    let mut hours: BTreeMap<i64, (f64, usize)> = BTreeMap::new();
+    for (ts, kw) in readings
+        .iter()
+        .filter(|(ts, kw)| *ts >= issued && kw.is_finite())
+    {
+        let hour = hours.entry(floor_hour(*ts)).or_default();
+        hour.0 += kw;
+        hour.1 += 1;
+    }
+    let Some(&latest) = hours.keys().next_back() else {
+        return Err(format!(
+            "No readings since the forecast was issued at {}",
+            format_timestamp(issued)
+        )
+        .into());
+    };
+    let current = forecast
+        .timestamps
+        .iter()
+        .position(|ts| *ts == latest)
+        .ok_or_else(|| {
+            format!(
+                "Readings at {} are past the forecast; it needs a refit",
+                format_timestamp(latest)
+            )
+        })?;
+
+    let (mut sum, mut weights) = (0.0, 0.0);
+    for (hour, (total, n)) in &hours {
+        let Some(i) = forecast.timestamps.iter().position(|ts| ts == hour) else {
+            continue;
+        };
+        let w = 0.5f64.powi(((latest - hour) / HOUR) as i32);
+        sum += w * (total / *n as f64 - forecast.point[i]);
+        weights += w;
+    }
+    let adjustment_kw = sum / weights;
+
+    let end = (current + 2).min(forecast.len());
+    let slice = |values: &[f64]| values[current..end].to_vec();
+    let forecast = Forecast {
+        timestamps: forecast.timestamps[current..end].to_vec(),
+        point: slice(&forecast.point),
+        lower: slice(&forecast.lower),
+        upper: slice(&forecast.upper),
+    };
+    let mut estimate = forecast.clone();
+    for i in 0..estimate.len() {
+        let shift = if i == 0 {
+            adjustment_kw
+        } else {
+            NEXT_HOUR_CARRY * adjustment_kw
+        };
+        estimate.point[i] = (estimate.point[i] + shift).max(0.0);
+        estimate.lower[i] = (estimate.lower[i] + shift).max(0.0);
+        estimate.upper[i] = (estimate.upper[i] + shift).max(0.0);
+    }
+    Ok(Live {
+        hours_observed: hours.len(),
+        adjustment_kw,
+        forecast,
+        estimate,
+    })
+}
+
+/// `forecast` averaged over the steps of each hour, at the hour's start.
+fn hourly(forecast: &Forecast) -> Forecast {
+    let mut hourly = Forecast::default();
+    let mut start = 0;
+    while start < forecast.len() {
+        let hour = floor_hour(forecast.timestamps[start]);
+        let end = start
+            + forecast.timestamps[start..]
+                .iter()
+                .take_while(|ts| floor_hour(**ts) == hour)
+                .count();
+        let mean = |values: &[f64]| values[start..end].iter().sum::<f64>() / (end - start) as f64;
+        hourly.timestamps.push(hour);
+        hourly.point.push(mean(&forecast.point));
+        hourly.lower.push(mean(&forecast.lower));
+        hourly.upper.push(mean(&forecast.upper));
+        start = end;
+    }
+    hourly
+}
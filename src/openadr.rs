@@ -0,0 +1,313 @@
+//! OpenADR 3 demand-response events, baked into the forecast horizon.
+//!
+//! Events are kept as JSON files under `openadr.events_dir/<site>`, one per
+//! event id, as a VTN's notifications deliver them (`POST /openadr` in server
+//! mode, or dropped there by any VEN client). Only events whose targets name
+//! the site are kept or applied, so sites sharing an `events_dir`, or tenants
+//! a server, never see each other's. Each forecast applies the intervals of
+//! the signals it understands to the hours they cover:
+//!
+//! - `SIMPLE`: levels 0–3, shedding `shed_per_level` of the load;
+//! - `PRICE`: scaling the load by `(price / reference_price) ^ price_elasticity`;
+//! - `IMPORT_CAPACITY_LIMIT`: capping the load, in kW;
+//! - `DISPATCH_SETPOINT_RELATIVE`: adding to the load, in kW.
+//!
+//! A forecast step only partly covered by an interval is adjusted by that share.
+
+use chrono_tz::Tz;
+use serde_json::Value;
+use std::error::Error;
+use std::path::PathBuf;
+
+use crate::config::OpenAdrConfig;
+use crate::data::parse_timestamp_text;
+use crate::forecast::Forecast;
+use crate::preprocess::{HOUR, overlap_seconds};
+
+/// The payload types applied to the forecast.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalKind {
+    Simple,
+    Price,
+    ImportCapacityLimit,
+    DispatchSetpointRelative,
+}
+
+impl SignalKind {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "SIMPLE" => Some(Self::Simple),
+            "PRICE" => Some(Self::Price),
+            "IMPORT_CAPACITY_LIMIT" => Some(Self::ImportCapacityLimit),
+            "DISPATCH_SETPOINT_RELATIVE" => Some(Self::DispatchSetpointRelative),
+            _ => None,
+        }
+    }
+}
+
+/// One interval of one payload, in local wall-clock seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signal {
+    pub event: String,
+    pub kind: SignalKind,
+    pub start: i64,
+    /// Exclusive.
+    pub end: i64,
+    pub value: f64,
+}
+
+/// The events in a payload: one event, an array of them, or a notification
+/// (`{"objectType": "EVENT", "operation": ..., "object": {...}}`), with
+/// whether they were deleted.
+pub fn events(payload: &Value) -> Vec<(Value, bool)> {
+    match payload {
+        Value::Array(items) => items.iter().flat_map(events).collect(),
+        Value::Object(object) if object.contains_key("objectType") => {
+            match object["objectType"].as_str() {
+                Some("EVENT") => {
+                    let deleted = object.get("operation").and_then(Value::as_str) == Some("DELETE");
+                    vec![(
+                        object.get("object").cloned().unwrap_or(Value::Null),
+                        deleted,
+                    )]
+                }
+                _ => Vec::new(),
+            }
+        }
+        Value::Object(_) => vec![(payload.clone(), false)],
+        _ => Vec::new(),
+    }
+}
+
+/// Where the events of `site` are kept: its own directory of `events_dir`,
+/// `<tenant>/<site>` for a tenant's.
+pub fn site_dir(events_dir: &str, site: &str) -> PathBuf {
+    site.split('/')
+        .fold(PathBuf::from(events_dir), |dir, part| {
+            dir.join(file_name(part))
+        })
+}
+
+/// Ids and site names come off the wire, so keep them to one plain file name.
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "-_".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Keep the events of `payload` for `site` in `dir` (see [`site_dir`]), and
+/// forget the deleted ones.
+///
+/// Fails, keeping none, when an event doesn't target `site`. Returns the ids
+/// stored and removed.
+pub fn save(
+    dir: &str,
+    site: &str,
+    payload: &Value,
+) -> Result<(Vec<String>, Vec<String>), Box<dyn Error>> {
+    let events = events(payload);
+    for (event, deleted) in &events {
+        let id = event["id"]
+            .as_str()
+            .ok_or("OpenADR event without an `id`")?;
+        if !deleted && !targets(event, site) {
+            return Err(format!("OpenADR event {} does not target {}", id, site).into());
+        }
+    }
+    let (mut stored, mut removed) = (Vec::new(), Vec::new());
+    std::fs::create_dir_all(dir)?;
+    for (event, deleted) in events {
+        let id = event["id"].as_str().unwrap_or_default();
+        let path = PathBuf::from(dir).join(format!("{}.json", file_name(id)));
+        if deleted {
+            if path.exists() {
+                std::fs::remove_file(&path)?;
+            }
+            removed.push(id.to_string());
+        } else {
+            std::fs::write(&path, serde_json::to_string_pretty(&event)?)?;
+            stored.push(id.to_string());
+        }
+    }
+    Ok((stored, removed))
+}
+
+/// The signals of every event kept for `site` (see [`site_dir`]) that targets it.
+pub fn load(
+    config: &OpenAdrConfig,
+    site: &str,
+    timezone: Option<Tz>,
+) -> Result<Vec<Signal>, Box<dyn Error>> {
+    let Ok(entries) = std::fs::read_dir(site_dir(&config.events_dir, site)) else {
+        return Ok(Vec::new());
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "json"))
+        .collect();
+    paths.sort();
+    let mut signals = Vec::new();
+    for path in paths {
+        let text = std::fs::read_to_string(&path)?;
+        let event: Value = serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid OpenADR event {}: {}", path.display(), e))?;
+        if targets(&event, site) {
+            signals.extend(
+                event_signals(&event, timezone)
+                    .map_err(|e| format!("Invalid OpenADR event {}: {}", path.display(), e))?,
+            );
+        }
+    }
+    Ok(signals)
+}
+
+/// Whether one of the targets of `event` names `site`, in full or without its
+/// tenant. Events without targets apply nowhere.
+fn targets(event: &Value, site: &str) -> bool {
+    let short = site.rsplit('/').next().unwrap_or(site);
+    event["targets"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|target| target["values"].as_array().into_iter().flatten())
+        .any(|value| {
+            value
+                .as_str()
+                .is_some_and(|name| name == site || name == short)
+        })
+}
+
+/// The intervals of an event, each starting where its own `intervalPeriod`
+/// says or else where the event's puts it in sequence.
+pub fn event_signals(event: &Value, timezone: Option<Tz>) -> Result<Vec<Signal>, Box<dyn Error>> {
+    let id = event["id"].as_str().unwrap_or("?").to_string();
+    let default_period = period(&event["intervalPeriod"], timezone)?;
+    let mut signals = Vec::new();
+    for (i, interval) in event["intervals"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .enumerate()
+    {
+        let (start, duration) = match period(&interval["intervalPeriod"], timezone)? {
+            Some(period) => period,
+            None => {
+                let (start, duration) = default_period
+                    .ok_or("an interval without an intervalPeriod, in an event without one")?;
+                (start + i as i64 * duration, duration)
+            }
+        };
+        for payload in interval["payloads"].as_array().into_iter().flatten() {
+            let (Some(kind), Some(value)) = (
+                payload["type"].as_str().and_then(SignalKind::parse),
+                payload["values"].get(0).and_then(Value::as_f64),
+            ) else {
+                continue;
+            };
+            signals.push(Signal {
+                event: id.clone(),
+                kind,
+                start,
+                end: start + duration,
+                value,
+            });
+        }
+    }
+    Ok(signals)
+}
+
+/// Start and length of an `intervalPeriod`, if there is one.
+fn period(period: &Value, timezone: Option<Tz>) -> Result<Option<(i64, i64)>, Box<dyn Error>> {
+    let Some(start) = period["start"].as_str() else {
+        return Ok(None);
+    };
+    let start = parse_timestamp_text(start, timezone)
+        .ok_or_else(|| format!("invalid start {:?}", start))?;
+    let duration = period["duration"]
+        .as_str()
+        .map_or(Ok(HOUR), parse_duration)?;
+    Ok(Some((start, duration)))
+}
+
+/// An ISO 8601 duration such as `PT1H`, `PT15M` or `P1D`, in seconds.
+pub fn parse_duration(text: &str) -> Result<i64, Box<dyn Error>> {
+    let invalid = || format!("invalid duration {:?}", text);
+    let rest = text.trim().strip_prefix('P').ok_or_else(invalid)?;
+    let (mut seconds, mut number, mut time) = (0, String::new(), false);
+    for c in rest.chars() {
+        let unit = match (c, time) {
+            ('T', false) => {
+                time = true;
+                continue;
+            }
+            (c, _) if c.is_ascii_digit() => {
+                number.push(c);
+                continue;
+            }
+            ('Y', false) => 365 * 24 * HOUR,
+            ('W', false) => 7 * 24 * HOUR,
+            ('D', false) => 24 * HOUR,
+            ('H', true) => HOUR,
+            ('M', true) => 60,
+            ('S', true) => 1,
+            _ => return Err(invalid().into()),
+        };
+        seconds += number.parse::<i64>().map_err(|_| invalid())? * unit;
+        number.clear();
+    }
+    match number.is_empty() {
+        true => Ok(seconds),
+        false => Err(invalid().into()),
+    }
+}
+
+/// `forecast` with `signals` applied, and the number of its steps they changed.
+///
+/// Each step holds from its timestamp to the next, on the forecast's grid.
+pub fn apply(forecast: &Forecast, signals: &[Signal], config: &OpenAdrConfig) -> (Forecast, usize) {
+    let mut adjusted = forecast.clone();
+    let step = forecast.step();
+    let mut steps = 0;
+    for i in 0..adjusted.len() {
+        let start = adjusted.timestamps[i];
+        let mut changed = false;
+        for signal in signals {
+            let share =
+                overlap_seconds(signal.start, signal.end, start, start + step) as f64 / step as f64;
+            if share <= 0.0 {
+                continue;
+            }
+            let adjust = |kw: f64| {
+                let full = match signal.kind {
+                    SignalKind::Simple => {
+                        let level = (signal.value.max(0.0) as usize)
+                            .min(config.shed_per_level.len().saturating_sub(1));
+                        kw * (1.0 - config.shed_per_level.get(level).copied().unwrap_or(0.0))
+                    }
+                    SignalKind::Price => match config.reference_price {
+                        Some(reference) if reference > 0.0 && signal.value > 0.0 => {
+                            kw * (signal.value / reference).powf(config.price_elasticity)
+                        }
+                        _ => kw,
+                    },
+                    SignalKind::ImportCapacityLimit => kw.min(signal.value),
+                    SignalKind::DispatchSetpointRelative => kw + signal.value,
+                };
+                ((1.0 - share) * kw + share * full).max(0.0)
+            };
+            let before = adjusted.point[i];
+            adjusted.point[i] = adjust(adjusted.point[i]);
+            adjusted.lower[i] = adjust(adjusted.lower[i]);
+            adjusted.upper[i] = adjust(adjusted.upper[i]);
+            changed |= adjusted.point[i] != before;
+        }
+        steps += changed as usize;
+    }
+    (adjusted, steps)
+}
@@ -0,0 +1,149 @@
+//! Exit statuses and the JSON run summary, for orchestration systems to branch on.
+
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+
+use crate::convergence::OptimizerDiagnostics;
+use crate::data::{RowCounts, SkippedRow};
+use crate::metrics::Metrics;
+
+/// How a run ended.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    #[default]
+    Success,
+    /// Finished, but with validation warnings such as skipped rows or site-limit violations.
+    Warnings,
+    /// Failed for any other reason, e.g. an invalid config or an unreachable output.
+    Failed,
+    /// The session export couldn't be read or held no usable data.
+    DataError,
+    /// The model couldn't be fitted or failed to predict.
+    FitFailed,
+}
+
+impl Status {
+    /// Process exit code; 2 is left to command-line usage errors.
+    pub fn code(self) -> u8 {
+        match self {
+            Status::Success => 0,
+            Status::Failed => 1,
+            Status::DataError => 3,
+            Status::FitFailed => 4,
+            Status::Warnings => 5,
+        }
+    }
+}
+
+/// An error tagged with the status it ends the run with.
+#[derive(Debug)]
+pub struct StageError {
+    pub status: Status,
+    pub source: Box<dyn Error>,
+}
+
+impl StageError {
+    /// Tag `e` as a [`Status::DataError`].
+    pub fn data(e: Box<dyn Error>) -> Box<dyn Error> {
+        Box::new(Self {
+            status: Status::DataError,
+            source: e,
+        })
+    }
+
+    /// Tag `e` as a [`Status::FitFailed`].
+    pub fn fit(e: Box<dyn Error>) -> Box<dyn Error> {
+        Box::new(Self {
+            status: Status::FitFailed,
+            source: e,
+        })
+    }
+}
+
+impl fmt::Display for StageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.source.fmt(f)
+    }
+}
+
+impl Error for StageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// The status `e` ends the run with; untagged errors are [`Status::Failed`].
+pub fn status_of(e: &(dyn Error + 'static)) -> Status {
+    e.downcast_ref::<StageError>()
+        .map_or(Status::Failed, |e| e.status)
+}
+
+/// Machine-readable summary of one CLI run (`output.summary`).
+///
+/// Fields a command doesn't get to (e.g. after a data error) stay null.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RunRecord {
+    pub site: String,
+    pub command: String,
+    pub status: Status,
+    pub exit_code: u8,
+    pub rows_read: Option<usize>,
+    pub rows_dropped: Option<usize>,
+    /// The first unparseable rows of the source, with the reason for each.
+    pub skipped_rows: Vec<SkippedRow>,
+    pub hourly_points: Option<usize>,
+    pub model: Option<String>,
+    /// Wall-clock seconds spent fitting and forecasting.
+    pub fit_seconds: Option<f64>,
+    /// How Prophet's optimizer ended, when Prophet was fitted.
+    pub optimizer: Option<OptimizerDiagnostics>,
+    /// In-sample accuracy over the last forecast horizon of history.
+    pub metrics: Option<Metrics>,
+    pub forecast_hours: Option<usize>,
+    /// Past hours whose load changed since the site's previous run in the `[store]`.
+    pub revised_hours: Option<usize>,
+    pub warnings: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl RunRecord {
+    pub fn new(site: &str, command: &str) -> Self {
+        Self {
+            site: site.to_string(),
+            command: command.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Record the rows of the session export and warn about unparseable ones.
+    pub fn record_rows(&mut self, counts: &RowCounts) {
+        self.rows_read = Some(counts.read);
+        self.rows_dropped = Some(counts.dropped());
+        self.skipped_rows = counts.skipped.clone();
+        if counts.invalid > 0 {
+            self.warnings
+                .push(format!("{} rows could not be parsed", counts.invalid));
+        }
+    }
+
+    /// Settle the status from the run's result and the warnings collected so far.
+    pub fn finish(&mut self, result: &Result<(), Box<dyn Error>>) -> Status {
+        self.status = match result {
+            Ok(()) if self.warnings.is_empty() => Status::Success,
+            Ok(()) => Status::Warnings,
+            Err(e) => {
+                self.error = Some(e.to_string());
+                status_of(e.as_ref())
+            }
+        };
+        self.exit_code = self.status.code();
+        self.status
+    }
+
+    /// Write the summary as JSON (`-` for stdout).
+    pub fn write(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        crate::export::write_text(path, &(serde_json::to_string_pretty(self)? + "\n"))
+    }
+}
@@ -0,0 +1,362 @@
+//! Partial refits: cheap updates between full refits, on recent data.
+//!
+//! With `[partial_refit]`, a full fit keeps its trend and its summed seasonal
+//! and regressor terms, hour by hour from the start of its history to
+//! `max_age_hours` past the end of its horizon, in `partial_refit.state`. Until
+//! the next full fit, a run with `refit = "seasonality"` or `"trend"` refits
+//! that part on the last `lookback_hours` and keeps the other:
+//!
+//! - `seasonality` fits Prophet with the trend held at the kept one. With the
+//!   trend fixed, the seasonal and regressor coefficients are a ridge
+//!   regression, Prophet's priors the penalties, so they are solved for
+//!   directly rather than by Stan.
+//! - `trend` fits a new level and slope under the kept terms by least squares,
+//!   without Prophet at all.
+
+use augurs::prophet::optimizer::{Data, OptimizedParams};
+use augurs::prophet::{PositiveFloat, TrendIndicator};
+use csv::{ReaderBuilder, Writer};
+use std::error::Error;
+
+use crate::config::{Growth, ModelConfig, PartialRefitConfig, RefitScope, Transform};
+use crate::data::{self, Series, format_timestamp, parse_timestamp_text};
+use crate::export;
+use crate::forecast::{self, Decomposition, Forecast, Forecaster, Regressors, future_timestamps};
+use crate::metrics::least_squares;
+use crate::pipeline::tail;
+use crate::preprocess::HOUR;
+
+/// Rounds of re-estimating the noise the ridge penalties are relative to.
+const RIDGE_ROUNDS: usize = 3;
+
+/// Smallest observation noise, on Prophet's scaled values.
+const MIN_SIGMA: f64 = 1e-6;
+
+/// What a full fit leaves for the partial refits after it, in kW.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct State {
+    pub timestamps: Vec<i64>,
+    pub trend: Vec<f64>,
+    pub additive_terms: Vec<f64>,
+    /// Relative to the trend.
+    pub multiplicative_terms: Vec<f64>,
+    /// The full fit's prediction, unclamped, and its interval.
+    pub fitted: Forecast,
+}
+
+impl State {
+    fn from_decomposition(decomposition: Decomposition) -> Result<Self, Box<dyn Error>> {
+        let mut components = decomposition.components.into_iter();
+        let mut sum = |name: &str| {
+            components
+                .find(|(component, _)| component == name)
+                .map(|(_, values)| values)
+                .ok_or_else(|| format!("The fit has no {}", name))
+        };
+        Ok(Self {
+            additive_terms: sum("additive_terms")?,
+            multiplicative_terms: sum("multiplicative_terms")?,
+            timestamps: decomposition.fitted.timestamps.clone(),
+            trend: decomposition.trend,
+            fitted: decomposition.fitted,
+        })
+    }
+
+    fn append(&mut self, other: State) {
+        self.timestamps.extend(other.timestamps);
+        self.trend.extend(other.trend);
+        self.additive_terms.extend(other.additive_terms);
+        self.multiplicative_terms.extend(other.multiplicative_terms);
+        self.fitted.timestamps.extend(other.fitted.timestamps);
+        self.fitted.point.extend(other.fitted.point);
+        self.fitted.lower.extend(other.fitted.lower);
+        self.fitted.upper.extend(other.fitted.upper);
+    }
+
+    /// Where each of `timestamps` is in the state.
+    fn positions(&self, timestamps: &[i64]) -> Result<Vec<usize>, Box<dyn Error>> {
+        timestamps
+            .iter()
+            .map(|ts| {
+                self.timestamps.binary_search(ts).map_err(|_| {
+                    let (first, last) = (self.timestamps.first().copied().unwrap_or_default(), self.timestamps.last().copied().unwrap_or_default());
+                    format!(
+                        "The last full fit covers {} to {}, not {}; the next run has to refit in full",
+                        format_timestamp(first),
+                        format_timestamp(last),
+                        format_timestamp(*ts)
+                    )
+                    .into()
+                })
+            })
+            .collect()
+    }
+
+    /// Write as CSV: `timestamp,trend,additive_terms,multiplicative_terms,yhat,yhat_lower,yhat_upper`.
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let mut wtr = Writer::from_writer(export::create(path)?);
+        wtr.write_record([
+            "timestamp",
+            "trend",
+            "additive_terms",
+            "multiplicative_terms",
+            "yhat",
+            "yhat_lower",
+            "yhat_upper",
+        ])?;
+        let f = &self.fitted;
+        for i in 0..self.timestamps.len() {
+            let values = [
+                self.trend[i],
+                self.additive_terms[i],
+                self.multiplicative_terms[i],
+                f.point[i],
+                f.lower[i],
+                f.upper[i],
+            ];
+            wtr.write_record(
+                std::iter::once(format_timestamp(self.timestamps[i]))
+                    .chain(values.iter().map(|v| v.to_string())),
+            )?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// Read a state [`State::save`] wrote.
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let reader = data::open(path)
+            .map_err(|e| format!("No partial refit state ({}); run a full fit first", e))?;
+        let mut rdr = ReaderBuilder::new().from_reader(reader);
+        let mut state = State::default();
+        for (i, record) in rdr.records().enumerate() {
+            let record = record?;
+            let bad = || format!("{} line {}: not a partial refit state row", path, i + 2);
+            let ts =
+                parse_timestamp_text(record.get(0).unwrap_or_default(), None).ok_or_else(bad)?;
+            let values = (1..7)
+                .map(|c| {
+                    record
+                        .get(c)
+                        .and_then(|v| v.parse::<f64>().ok())
+                        .ok_or_else(bad)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            state.timestamps.push(ts);
+            state.trend.push(values[0]);
+            state.additive_terms.push(values[1]);
+            state.multiplicative_terms.push(values[2]);
+            state.fitted.timestamps.push(ts);
+            state.fitted.point.push(values[3]);
+            state.fitted.lower.push(values[4]);
+            state.fitted.upper.push(values[5]);
+        }
+        Ok(state)
+    }
+}
+
+fn check(model: &ModelConfig) -> Result<(), Box<dyn Error>> {
+    match model.transform {
+        Transform::None => Ok(()),
+        _ => Err("Partial refits need model.transform = \"none\"".into()),
+    }
+}
+
+/// Keep the trend and terms of a full fit on `history` for the partial
+/// refits after it, through `max_age_hours` past its `horizon_hours`.
+pub fn keep(
+    model: &dyn Forecaster,
+    history: &Series,
+    model_config: &ModelConfig,
+    horizon_hours: u32,
+    timezone: Option<chrono_tz::Tz>,
+    config: &PartialRefitConfig,
+) -> Result<State, Box<dyn Error>> {
+    check(model_config)?;
+    let last = *history.timestamps.last().ok_or("Empty history")?;
+    let ahead =
+        horizon_hours.saturating_add(u32::try_from(config.max_age_hours).unwrap_or(u32::MAX));
+    let mut state = State::from_decomposition(model.decompose()?)?;
+    state.append(State::from_decomposition(
+        model.attribute(future_timestamps(last, ahead, timezone))?,
+    )?);
+    state.save(&config.state)?;
+    Ok(state)
+}
+
+/// Refit the part `config.refit` names on the last `config.lookback_hours`
+/// of `history`, keeping the rest of the last full fit.
+pub fn refit(
+    history: &Series,
+    model: &ModelConfig,
+    config: &PartialRefitConfig,
+    regressors: &Regressors,
+) -> Result<Box<dyn Forecaster>, Box<dyn Error>> {
+    if config.refit == RefitScope::Full {
+        return forecast::fit_with(history, model, regressors);
+    }
+    check(model)?;
+    let state = State::load(&config.state)?;
+    let recent = tail(history, config.lookback_hours);
+    let positions = state.positions(&recent.timestamps)?;
+    match config.refit {
+        RefitScope::Seasonality => {
+            let trend: Vec<f64> = positions.iter().map(|&i| state.trend[i]).collect();
+            Ok(Box::new(forecast::fit_prophet_on_trend(
+                &recent, model, &trend, regressors,
+            )?))
+        }
+        _ => Ok(Box::new(TrendRefit::fit(
+            &recent,
+            state,
+            &positions,
+            model.growth,
+        )?)),
+    }
+}
+
+/// Prophet's parameters for `data` with the trend held at `trend`, on
+/// Prophet's scaled values.
+///
+/// The trend becomes Prophet's own as the line through it: recent hours
+/// usually lie past the last changepoint of a full fit, which keeps to the
+/// first 80% of its history.
+pub fn fit_on_trend(data: &Data, trend: &[f64]) -> Result<OptimizedParams, Box<dyn Error>> {
+    let (n, k) = (data.y.len(), data.K as usize);
+    if trend.len() != n {
+        return Err(format!(
+            "The pinned trend has {} values for {} hours",
+            trend.len(),
+            n
+        )
+        .into());
+    }
+    let (rate, offset) = match data.trend_indicator {
+        TrendIndicator::Flat => (0.0, trend.iter().sum::<f64>() / n.max(1) as f64),
+        TrendIndicator::Linear => {
+            let x: Vec<Vec<f64>> = data.t.iter().map(|t| vec![1.0, *t]).collect();
+            let line = least_squares(&x, trend).ok_or("Too few hours to hold the trend on")?;
+            (line[1], line[0])
+        }
+        TrendIndicator::Logistic => {
+            return Err("Partial refits don't support logistic growth".into());
+        }
+    };
+    let line: Vec<f64> = data.t.iter().map(|t| rate * t + offset).collect();
+
+    // y = trend + X·(s_a ∘ β) + trend · X·(s_m ∘ β) is linear in β once the trend is fixed
+    let x: Vec<Vec<f64>> = (0..n)
+        .map(|i| {
+            (0..k)
+                .map(|j| data.X[i * k + j] * (data.s_a[j] as f64 + data.s_m[j] as f64 * line[i]))
+                .collect()
+        })
+        .collect();
+    let residuals: Vec<f64> = data.y.iter().zip(&line).map(|(y, t)| y - t).collect();
+    let rms = |r: &[f64]| {
+        (r.iter().map(|v| v * v).sum::<f64>() / r.len().max(1) as f64)
+            .sqrt()
+            .max(MIN_SIGMA)
+    };
+    let mut sigma = rms(&residuals);
+    let mut beta = vec![0.0; k];
+    for _ in 0..RIDGE_ROUNDS {
+        // β_j ~ N(0, sigmas_j): one penalty row each, weighed against noise of sigma
+        let mut rows = x.clone();
+        let mut targets = residuals.clone();
+        for (j, prior) in data.sigmas.iter().enumerate() {
+            let mut row = vec![0.0; k];
+            row[j] = sigma / **prior;
+            rows.push(row);
+            targets.push(0.0);
+        }
+        beta = least_squares(&rows, &targets)
+            .ok_or("The seasonal terms can't be solved for on the kept trend")?;
+        let left: Vec<f64> = x
+            .iter()
+            .zip(&residuals)
+            .map(|(row, r)| r - row.iter().zip(&beta).map(|(a, b)| a * b).sum::<f64>())
+            .collect();
+        sigma = rms(&left);
+    }
+    Ok(OptimizedParams {
+        k: rate,
+        m: offset,
+        sigma_obs: PositiveFloat::try_new(sigma)?,
+        delta: vec![0.0; data.S.max(0) as usize],
+        beta,
+        trend: line,
+    })
+}
+
+/// The last full fit's seasonal and regressor terms on a refitted linear trend.
+#[derive(Debug, Clone)]
+pub struct TrendRefit {
+    state: State,
+    /// Last hour fitted on.
+    origin: i64,
+    pub level_kw: f64,
+    pub slope_kw_per_hour: f64,
+}
+
+impl TrendRefit {
+    /// Fit the trend under the terms of `state` at `positions`, those of the hours of `recent`.
+    pub fn fit(
+        recent: &Series,
+        state: State,
+        positions: &[usize],
+        growth: Growth,
+    ) -> Result<Self, Box<dyn Error>> {
+        let origin = *recent.timestamps.last().ok_or("Empty history")?;
+        // y - additive = trend · (1 + multiplicative), with trend = level + slope · hours
+        let x: Vec<Vec<f64>> = positions
+            .iter()
+            .zip(&recent.timestamps)
+            .map(|(&i, ts)| {
+                let scale = 1.0 + state.multiplicative_terms[i];
+                match growth {
+                    Growth::Linear => vec![scale, scale * ((ts - origin) / HOUR) as f64],
+                    Growth::Flat => vec![scale],
+                }
+            })
+            .collect();
+        let y: Vec<f64> = positions
+            .iter()
+            .zip(&recent.values)
+            .map(|(&i, v)| v - state.additive_terms[i])
+            .collect();
+        let line =
+            least_squares(&x, &y).ok_or("The recent history is too short to refit the trend on")?;
+        Ok(Self {
+            state,
+            origin,
+            level_kw: line[0],
+            slope_kw_per_hour: line.get(1).copied().unwrap_or_default(),
+        })
+    }
+}
+
+impl Forecaster for TrendRefit {
+    fn name(&self) -> &'static str {
+        "prophet-trend"
+    }
+
+    fn predict(&self, timestamps: Vec<i64>) -> Result<Forecast, Box<dyn Error>> {
+        let positions = self.state.positions(&timestamps)?;
+        let (s, f) = (&self.state, &self.state.fitted);
+        let mut forecast = Forecast {
+            timestamps,
+            ..Forecast::default()
+        };
+        for (&i, ts) in positions.iter().zip(&forecast.timestamps) {
+            let trend = self.level_kw + self.slope_kw_per_hour * ((ts - self.origin) / HOUR) as f64;
+            // The interval moves with the point forecast
+            let shift =
+                trend * (1.0 + s.multiplicative_terms[i]) + s.additive_terms[i] - f.point[i];
+            forecast.point.push((f.point[i] + shift).max(0.0));
+            forecast.lower.push((f.lower[i] + shift).max(0.0));
+            forecast.upper.push((f.upper[i] + shift).max(0.0));
+        }
+        Ok(forecast)
+    }
+}
@@ -0,0 +1,239 @@
+//! The forecast's expected peak hours, for site staff and battery
+//! controllers: the `top` highest point forecasts within the first
+//! `within_hours` of the horizon, as a JSON list and as an iCalendar file
+//! whose events join adjacent peak hours.
+//!
+//! Calendar times are UTC when the site has a `timezone`, and floating local
+//! times otherwise.
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+use std::error::Error;
+
+use crate::config::PeaksConfig;
+use crate::data::format_timestamp;
+use crate::export;
+use crate::forecast::Forecast;
+use crate::preprocess::HOUR;
+
+/// One of the highest forecast steps.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Peak {
+    /// 1 for the highest.
+    pub rank: usize,
+    #[serde(skip)]
+    pub timestamp: i64,
+    /// `YYYY-MM-DD HH:MM`, local time.
+    pub start: String,
+    pub end: String,
+    pub kw: f64,
+    pub lower_kw: f64,
+    pub upper_kw: f64,
+}
+
+/// Adjacent peaks, as one calendar event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event<'a> {
+    pub start: i64,
+    pub end: i64,
+    /// In time order.
+    pub peaks: Vec<&'a Peak>,
+}
+
+/// Seconds between the forecast's steps.
+fn step_of(forecast: &Forecast) -> i64 {
+    match forecast.timestamps.as_slice() {
+        [first, second, ..] => second - first,
+        _ => HOUR,
+    }
+}
+
+/// The `config.top` highest points within `config.within_hours`, highest first.
+pub fn top(forecast: &Forecast, config: &PeaksConfig) -> Vec<Peak> {
+    let step = step_of(forecast);
+    let first = forecast.timestamps.first().copied().unwrap_or_default();
+    let until = config
+        .within_hours
+        .map_or(i64::MAX, |hours| first + hours as i64 * HOUR);
+    let mut steps: Vec<usize> = (0..forecast.len())
+        .filter(|i| forecast.timestamps[*i] < until)
+        .collect();
+    steps.sort_by(|a, b| {
+        forecast.point[*b]
+            .total_cmp(&forecast.point[*a])
+            .then(a.cmp(b))
+    });
+    steps
+        .into_iter()
+        .take(config.top)
+        .enumerate()
+        .map(|(rank, i)| Peak {
+            rank: rank + 1,
+            timestamp: forecast.timestamps[i],
+            start: format_timestamp(forecast.timestamps[i]),
+            end: format_timestamp(forecast.timestamps[i] + step),
+            kw: forecast.point[i],
+            lower_kw: forecast.lower[i],
+            upper_kw: forecast.upper[i],
+        })
+        .collect()
+}
+
+/// The peaks in time order, adjacent ones (`step` apart) joined into one event.
+pub fn events(peaks: &[Peak], step: i64) -> Vec<Event<'_>> {
+    let mut ordered: Vec<&Peak> = peaks.iter().collect();
+    ordered.sort_by_key(|p| p.timestamp);
+    let mut events: Vec<Event> = Vec::new();
+    for peak in ordered {
+        match events.last_mut() {
+            Some(event) if event.end == peak.timestamp => {
+                event.end += step;
+                event.peaks.push(peak);
+            }
+            _ => events.push(Event {
+                start: peak.timestamp,
+                end: peak.timestamp + step,
+                peaks: vec![peak],
+            }),
+        }
+    }
+    events
+}
+
+/// An iCalendar date-time: UTC with a timezone, floating local time without.
+fn ics_time(timestamp: i64, timezone: Option<Tz>) -> String {
+    let local = DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .naive_utc();
+    match timezone.and_then(|tz| tz.from_local_datetime(&local).earliest()) {
+        Some(time) => time
+            .with_timezone(&Utc)
+            .format("%Y%m%dT%H%M%SZ")
+            .to_string(),
+        None => local.format("%Y%m%dT%H%M%S").to_string(),
+    }
+}
+
+/// An iCalendar text value, escaped.
+fn ics_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// A content line, folded at 75 octets.
+fn push_line(ics: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            ics.push_str("\r\n ");
+            width = 1;
+        }
+        ics.push(c);
+        width += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+/// The peaks as an iCalendar file, one event per run of adjacent peaks.
+pub fn ics(
+    peaks: &[Peak],
+    step: i64,
+    site: &str,
+    timezone: Option<Tz>,
+    now: NaiveDateTime,
+) -> String {
+    let mut ics = String::new();
+    for line in [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        "PRODID:-//cpo-charging-forecast//peaks//EN",
+        "CALSCALE:GREGORIAN",
+    ] {
+        push_line(&mut ics, line);
+    }
+    push_line(
+        &mut ics,
+        &format!("X-WR-CALNAME:{}", ics_text(&format!("{} peak hours", site))),
+    );
+    for event in events(peaks, step) {
+        let highest = event
+            .peaks
+            .iter()
+            .map(|p| p.kw)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let description: Vec<String> = event
+            .peaks
+            .iter()
+            .map(|p| {
+                format!(
+                    "{} {:.0} kW ({:.0} .. {:.0}), #{}",
+                    p.start, p.kw, p.lower_kw, p.upper_kw, p.rank
+                )
+            })
+            .collect();
+        push_line(&mut ics, "BEGIN:VEVENT");
+        push_line(
+            &mut ics,
+            &format!(
+                "UID:{}-{}@cpo-charging-forecast",
+                ics_text(site),
+                ics_time(event.start, None)
+            ),
+        );
+        push_line(
+            &mut ics,
+            &format!("DTSTAMP:{}", now.format("%Y%m%dT%H%M%SZ")),
+        );
+        push_line(
+            &mut ics,
+            &format!("DTSTART:{}", ics_time(event.start, timezone)),
+        );
+        push_line(
+            &mut ics,
+            &format!("DTEND:{}", ics_time(event.end, timezone)),
+        );
+        push_line(
+            &mut ics,
+            &format!(
+                "SUMMARY:{}",
+                ics_text(&format!("Expected peak at {}: {:.0} kW", site, highest))
+            ),
+        );
+        push_line(
+            &mut ics,
+            &format!("DESCRIPTION:{}", ics_text(&description.join("\n"))),
+        );
+        push_line(&mut ics, "TRANSP:TRANSPARENT");
+        push_line(&mut ics, "END:VEVENT");
+    }
+    push_line(&mut ics, "END:VCALENDAR");
+    ics
+}
+
+/// Write the peaks as a JSON list.
+pub fn write_json(peaks: &[Peak], path: &str) -> Result<(), Box<dyn Error>> {
+    export::write_text(path, &(serde_json::to_string_pretty(peaks)? + "\n"))
+}
+
+/// Write the peaks of `forecast` as an iCalendar file, stamped now.
+pub fn write_ics(
+    peaks: &[Peak],
+    forecast: &Forecast,
+    site: &str,
+    timezone: Option<Tz>,
+    path: &str,
+) -> Result<(), Box<dyn Error>> {
+    export::write_text(
+        path,
+        &ics(
+            peaks,
+            step_of(forecast),
+            site,
+            timezone,
+            Utc::now().naive_utc(),
+        ),
+    )
+}
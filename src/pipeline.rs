@@ -0,0 +1,553 @@
+//! The standard load → resample → fit → forecast run, shared by the CLI commands and the server.
+
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+
+use crate::bias::BiasCorrection;
+use crate::config::{Config, Export, PowerConfig, RefitScope, RegisterConfig, Transform};
+use crate::data::{RowCounts, Series, Session, format_timestamp};
+use crate::forecast::{self, Forecast, Forecaster, Regressors, future_steps, zero_share};
+use crate::nowcast;
+use crate::openadr;
+use crate::outcome::StageError;
+use crate::partial;
+use crate::power;
+use crate::preprocess::{self, HOUR};
+use crate::register::{self, Reading};
+use crate::remote;
+use crate::source::{self, DataSource, Readings};
+
+/// Load the configured source's readings as an hourly load series.
+///
+/// Sessions are aggregated into hourly load; a load series (a Parquet or
+/// Arrow IPC `data.path`, see [`crate::data::is_columnar`], or a `[data.sql]` query) is
+/// checked and put on the hourly grid (see [`source::check_hourly`]). Either
+/// then goes through `[data.preprocess]`. With `data.step_minutes`, the grid
+/// is one of that many minutes instead. With `[data.power]`, a load series
+/// holds power samples, integrated to interval energy first (see
+/// [`crate::power`]), and with `[data.register]` cumulative energy readings,
+/// differenced first (see [`crate::register`]). File paths may be object-store URIs
+/// (see [`crate::remote`]).
+pub fn load_history(config: &Config) -> Result<Series, Box<dyn Error>> {
+    Ok(load_history_counted(config)?.0)
+}
+
+/// [`load_history`], also returning the source's row counts.
+///
+/// Errors are tagged as [`Status::DataError`](crate::outcome::Status::DataError).
+pub fn load_history_counted(config: &Config) -> Result<(Series, RowCounts), Box<dyn Error>> {
+    load_history_from(config, source::source(config).as_ref())
+}
+
+/// [`load_history_counted`] from any source.
+pub fn load_history_from(
+    config: &Config,
+    source: &dyn DataSource,
+) -> Result<(Series, RowCounts), Box<dyn Error>> {
+    let step = preprocess::step_seconds(config.data.step_minutes).map_err(StageError::data)?;
+    let (readings, mut counts) = source.read().map_err(StageError::data)?;
+    check_skipped(config, &counts).map_err(StageError::data)?;
+    let points = match step {
+        HOUR => "hourly".to_string(),
+        _ => format!("{}-minute", step / 60),
+    };
+    match readings {
+        Readings::Sessions(sessions) => {
+            let history = match config.data.export {
+                Export::Split => split_history(config, &sessions, step)?,
+                _ => preprocess_history(config, preprocess::load(&sessions, step), step)?,
+            };
+            eprintln!(
+                "Loaded {} sessions -> {} {} points ({} .. {})",
+                sessions.len(),
+                history.len(),
+                points,
+                format_timestamp(history.timestamps[0]),
+                format_timestamp(history.timestamps[history.len() - 1]),
+            );
+            Ok((history, counts))
+        }
+        Readings::Hourly(series) => {
+            let series = match (&config.data.power, &config.data.register) {
+                (Some(_), Some(_)) => return Err(StageError::data("data.power and data.register are both set; a load series holds one or the other".into())),
+                (Some(power_config), None) => integrate_power(config, power_config, series, step, &mut counts)?,
+                (None, Some(register_config)) => {
+                    let readings = series
+                        .timestamps
+                        .iter()
+                        .zip(&series.values)
+                        .map(|(&timestamp, &value)| Reading {
+                            charger: String::new(),
+                            timestamp,
+                            value,
+                        })
+                        .collect();
+                    difference_register(register_config, readings, step, &mut counts)
+                }
+                (None, None) => series,
+            };
+            load_series_history(config, source, series, step, &points, counts)
+        }
+        Readings::Registers(readings) => {
+            let Some(register_config) = &config.data.register else {
+                return Err(StageError::data(
+                    format!(
+                        "{} holds the chargers' energy registers; set [data.register]",
+                        source.name()
+                    )
+                    .into(),
+                ));
+            };
+            let series = difference_register(register_config, readings, step, &mut counts);
+            load_series_history(config, source, series, step, &points, counts)
+        }
+    }
+}
+
+/// A load series put on the grid and preprocessed.
+fn load_series_history(
+    config: &Config,
+    source: &dyn DataSource,
+    series: Series,
+    step: i64,
+    points: &str,
+    mut counts: RowCounts,
+) -> Result<(Series, RowCounts), Box<dyn Error>> {
+    let history = preprocess_history(config, source::check_grid(series, step, &mut counts), step)?;
+    eprintln!(
+        "Loaded {} {} points from {}",
+        history.len(),
+        points,
+        source.name()
+    );
+    Ok((history, counts))
+}
+
+/// Skipped rows shown on stderr; the rest are in the run summary.
+const SKIPPED_ROWS_SHOWN: usize = 5;
+
+/// Report the rows the source couldn't parse, failing a strict run with too many.
+fn check_skipped(config: &Config, counts: &RowCounts) -> Result<(), Box<dyn Error>> {
+    if counts.invalid == 0 {
+        return Ok(());
+    }
+    for skipped in counts.skipped.iter().take(SKIPPED_ROWS_SHOWN) {
+        match &skipped.column {
+            Some(column) => eprintln!(
+                "⚠️  Skipped row {}: {} in `{}`",
+                skipped.row, skipped.reason, column
+            ),
+            None => eprintln!("⚠️  Skipped row {}: {}", skipped.row, skipped.reason),
+        }
+    }
+    if counts.invalid > SKIPPED_ROWS_SHOWN {
+        eprintln!(
+            "⚠️  ...and {} more skipped rows",
+            counts.invalid - SKIPPED_ROWS_SHOWN
+        );
+    }
+    let rate = counts.skip_rate();
+    if config.data.strict && rate > config.data.max_skip_rate {
+        return Err(format!(
+            "{} of {} rows ({:.1}%) could not be parsed, more than data.max_skip_rate ({:.1}%) allows",
+            counts.invalid,
+            counts.read,
+            100.0 * rate,
+            100.0 * config.data.max_skip_rate
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Power samples integrated to interval energy, checked against the register if there is one.
+fn integrate_power(
+    config: &Config,
+    power_config: &PowerConfig,
+    series: Series,
+    step: i64,
+    counts: &mut RowCounts,
+) -> Result<Series, Box<dyn Error>> {
+    let samples = power::samples(series, counts);
+    let integrated = power::integrate(&samples, step, power_config.max_gap_minutes as i64 * 60);
+    eprintln!(
+        "Integrated {} power samples into {} points",
+        samples.len(),
+        integrated.len()
+    );
+    let Some(path) = &power_config.register else {
+        return Ok(integrated);
+    };
+    let register = remote::with_input(path, |path| power::load_register(path, config.timezone))
+        .map_err(StageError::data)?;
+    let check = power::check_register(&samples, &register, power_config);
+    match check.worst() {
+        Some(worst) => eprintln!(
+            "⚠️  {} of {} register intervals differ from the integrated power by more than {:.0}%, most at {} .. {}: {:.1} kWh on the register, {:.1} kWh integrated",
+            check.mismatches.len(),
+            check.intervals,
+            power_config.tolerance * 100.0,
+            format_timestamp(worst.from),
+            format_timestamp(worst.to),
+            worst.register_kwh,
+            worst.integrated_kwh
+        ),
+        None => eprintln!(
+            "Integrated power matches the register over {} intervals ({:.1} kWh on the register, {:.1} kWh integrated)",
+            check.intervals, check.register_kwh, check.integrated_kwh
+        ),
+    }
+    Ok(integrated)
+}
+
+/// Register readings differenced to interval energy, reporting rollovers and meter replacements.
+///
+/// Non-finite readings are dropped and counted as invalid.
+fn difference_register(
+    register_config: &RegisterConfig,
+    mut readings: Vec<Reading>,
+    step: i64,
+    counts: &mut RowCounts,
+) -> Series {
+    let before = readings.len();
+    readings.retain(|reading| reading.value.is_finite());
+    counts.invalid += before - readings.len();
+    let conversion = register::intervals(&readings, register_config);
+    let load = register::load(&conversion.intervals, step);
+    let chargers: BTreeSet<&str> = readings.iter().map(|r| r.charger.as_str()).collect();
+    eprintln!(
+        "Differenced {} register readings{} into {} points",
+        readings.len(),
+        match chargers.len() {
+            0 | 1 => String::new(),
+            n => format!(" of {} chargers", n),
+        },
+        load.len()
+    );
+    if !conversion.rollovers.is_empty() {
+        eprintln!(
+            "The register rolled over {} times",
+            conversion.rollovers.len()
+        );
+    }
+    if let Some(first) = conversion.replacements.first() {
+        eprintln!(
+            "⚠️  {} meter replacements (the first at {}); the energy across them is left out",
+            conversion.replacements.len(),
+            format_timestamp(*first)
+        );
+    }
+    if conversion.gaps > 0 {
+        eprintln!(
+            "{} gaps between register readings longer than {} minutes left unmetered",
+            conversion.gaps, register_config.max_gap_minutes
+        );
+    }
+    load
+}
+
+/// The configured source's sessions, for the commands that need more than the load.
+pub fn load_sessions(config: &Config) -> Result<(Vec<Session>, RowCounts), Box<dyn Error>> {
+    let source = source::source(config);
+    match source.read()? {
+        (Readings::Sessions(sessions), counts) => Ok((sessions, counts)),
+        (Readings::Hourly(_), _) => {
+            Err(format!("{} holds an hourly load, not sessions", source.name()).into())
+        }
+        (Readings::Registers(_), _) => {
+            Err(format!("{} holds energy registers, not sessions", source.name()).into())
+        }
+    }
+}
+
+/// The net load of `sessions`, carrying the export it nets (see
+/// [`Series::export`]); each channel goes through `[data.preprocess]` on its own.
+fn split_history(
+    config: &Config,
+    sessions: &[Session],
+    step: i64,
+) -> Result<Series, Box<dyn Error>> {
+    let (import, export) = preprocess::load_channels(sessions, step);
+    let import = preprocess_history(config, import, step)?;
+    let export = config
+        .data
+        .preprocess
+        .apply_at(export, step)
+        .map_err(StageError::data)?;
+    let exported: HashMap<i64, f64> = export
+        .timestamps
+        .iter()
+        .copied()
+        .zip(export.values.iter().copied())
+        .collect();
+    let export: Vec<f64> = import
+        .timestamps
+        .iter()
+        .map(|ts| exported.get(ts).copied().unwrap_or(0.0))
+        .collect();
+    let mut history = Series::from_buffers(
+        import.timestamps,
+        import
+            .values
+            .iter()
+            .zip(&export)
+            .map(|(i, e)| i - e)
+            .collect(),
+    );
+    history.export = Some(export);
+    Ok(history)
+}
+
+fn preprocess_history(
+    config: &Config,
+    series: Series,
+    step: i64,
+) -> Result<Series, Box<dyn Error>> {
+    let history = config
+        .data
+        .preprocess
+        .apply_at(series, step)
+        .map_err(StageError::data)?;
+    if history.is_empty() {
+        return Err(StageError::data(
+            "No points left after preprocessing".into(),
+        ));
+    }
+    Ok(history)
+}
+
+/// Fit on `history` and forecast the configured horizon, keeping the model around.
+///
+/// With `[partial_refit]`, only the configured part is refitted, or a full
+/// fit keeps what the partial ones need (see [`crate::partial`]). The forecast is bias-corrected when `[bias_correction]` is configured, its
+/// first hours blended with the latest readings with `[near_term]`, and
+/// adjusted for the demand-response events of `[openadr]`. A history with
+/// export is forecast as `data.export` says (see [`forecast_channels`] and
+/// [`forecast_net`]).
+/// Errors are tagged as [`Status::FitFailed`](crate::outcome::Status::FitFailed).
+pub fn fit_and_forecast(
+    config: &Config,
+    history: &Series,
+) -> Result<(Box<dyn Forecaster>, Forecast), Box<dyn Error>> {
+    let result = match config.data.export {
+        Export::Drop => Regressors::load(history, &config.model)
+            .and_then(|regressors| fit_and_forecast_untagged(config, history, &regressors)),
+        Export::Split => forecast_channels(config, history).map(|(model, channels)| {
+            eprintln!(
+                "Forecast {:.0} kWh of import and {:.0} kWh of export",
+                channels.import.point.iter().sum::<f64>() * history.step() as f64 / HOUR as f64,
+                channels.export.point.iter().sum::<f64>() * history.step() as f64 / HOUR as f64
+            );
+            (model, channels.net())
+        }),
+        Export::Net => forecast_net(config, history),
+    };
+    result.map_err(StageError::fit)
+}
+
+/// Import and export forecasts of a site with vehicle-to-grid export, in kW.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Channels {
+    pub import: Forecast,
+    pub export: Forecast,
+}
+
+impl Channels {
+    /// Import minus export; each bound pairs one channel's bound with the other's opposite one.
+    pub fn net(&self) -> Forecast {
+        let (import, export) = (&self.import, &self.export);
+        let difference = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(a, b)| a - b).collect();
+        Forecast {
+            timestamps: import.timestamps.clone(),
+            point: difference(&import.point, &export.point),
+            lower: difference(&import.lower, &export.upper),
+            upper: difference(&import.upper, &export.lower),
+        }
+    }
+}
+
+/// The import (positive) and export (negative, as a positive load) parts of
+/// a signed load: the ones it was loaded with (see [`Series::export`]), or
+/// else each step's net split by its sign.
+pub fn split_channels(history: &Series) -> (Series, Series) {
+    if let Some(export) = history.export.as_ref().filter(|e| e.len() == history.len()) {
+        return (
+            Series::from_buffers(
+                history.timestamps.clone(),
+                history
+                    .values
+                    .iter()
+                    .zip(export)
+                    .map(|(v, e)| v + e)
+                    .collect(),
+            ),
+            Series::from_buffers(history.timestamps.clone(), export.clone().into()),
+        );
+    }
+    let part = |sign: f64| {
+        Series::from_buffers(
+            history.timestamps.clone(),
+            history.values.iter().map(|v| (sign * v).max(0.0)).collect(),
+        )
+    };
+    (part(1.0), part(-1.0))
+}
+
+/// Forecast import and export separately, each as a load of its own
+/// (`data.export = "split"`); the model returned is the import's.
+///
+/// The export channel is forecast without `[openadr]`, whose events shed
+/// demand, and as zero while the site never exported.
+pub fn forecast_channels(
+    config: &Config,
+    history: &Series,
+) -> Result<(Box<dyn Forecaster>, Channels), Box<dyn Error>> {
+    let (import_history, export_history) = split_channels(history);
+    let regressors = Regressors::load(history, &config.model)?;
+    let (model, import) = fit_and_forecast_untagged(config, &import_history, &regressors)?;
+    let export = match export_history.values.iter().any(|v| *v > 0.0) {
+        true => {
+            let mut export_config = config.clone();
+            export_config.openadr = None;
+            export_config.partial_refit = None;
+            fit_and_forecast_untagged(&export_config, &export_history, &regressors)?.1
+        }
+        false => Forecast {
+            timestamps: import.timestamps.clone(),
+            point: vec![0.0; import.len()],
+            lower: vec![0.0; import.len()],
+            upper: vec![0.0; import.len()],
+        },
+    };
+    Ok((model, Channels { import, export }))
+}
+
+/// Forecast the signed net load as it is (`data.export = "net"`), below zero
+/// where export will outweigh import.
+///
+/// The corrections after the fit assume a load that can't go below zero, so
+/// `[partial_refit]`, `[bias_correction]`, `[near_term]` and `[openadr]` are
+/// for `split` only, as is a `model.transform`.
+pub fn forecast_net(
+    config: &Config,
+    history: &Series,
+) -> Result<(Box<dyn Forecaster>, Forecast), Box<dyn Error>> {
+    let unsupported = [
+        (config.partial_refit.is_some(), "[partial_refit]"),
+        (config.bias_correction.is_some(), "[bias_correction]"),
+        (config.near_term.is_some(), "[near_term]"),
+        (config.openadr.is_some(), "[openadr]"),
+        (config.model.transform != Transform::None, "model.transform"),
+    ];
+    if let Some((_, name)) = unsupported.iter().find(|(set, _)| *set) {
+        return Err(format!("data.export = \"net\" forecasts a load that goes below zero, which {} doesn't take; use \"split\"", name).into());
+    }
+    let model = forecast::fit(history, &config.model)?;
+    let last_timestamp = *history.timestamps.last().ok_or("Empty history")?;
+    let timestamps = future_steps(
+        last_timestamp,
+        config.forecast.horizon_hours,
+        history.step(),
+        config.timezone,
+    );
+    // Prophet's components give the forecast before it's kept at or above zero
+    let forecast = match model.attribute(timestamps.clone()) {
+        Ok(attribution) => attribution.fitted,
+        Err(_) => model.predict(timestamps)?,
+    };
+    Ok((model, forecast))
+}
+
+fn fit_and_forecast_untagged(
+    config: &Config,
+    history: &Series,
+    regressors: &Regressors,
+) -> Result<(Box<dyn Forecaster>, Forecast), Box<dyn Error>> {
+    let model = match &config.partial_refit {
+        Some(partial_config) => partial::refit(history, &config.model, partial_config, regressors)?,
+        None => forecast::fit_with(history, &config.model, regressors)?,
+    };
+    match &config.partial_refit {
+        Some(partial_config) if partial_config.refit == RefitScope::Full => {
+            match partial::keep(
+                model.as_ref(),
+                history,
+                &config.model,
+                config.forecast.horizon_hours,
+                config.timezone,
+                partial_config,
+            ) {
+                Ok(state) => eprintln!(
+                    "Kept {} hours of the fit for partial refits in {}",
+                    state.timestamps.len(),
+                    partial_config.state
+                ),
+                Err(e) => eprintln!("⚠️  Kept nothing for partial refits: {}", e),
+            }
+        }
+        Some(partial_config) => eprintln!(
+            "Refitted the {} on the last {} hours, the rest kept from the last full fit",
+            match partial_config.refit {
+                RefitScope::Trend => "trend",
+                _ => "seasonal and regressor terms",
+            },
+            partial_config.lookback_hours.min(history.len())
+        ),
+        None => {}
+    }
+    if model.name() == "tsb" {
+        eprintln!(
+            "Using {} ({:.0}% of hours without demand)",
+            model.name(),
+            zero_share(history) * 100.0
+        );
+    }
+    let last_timestamp = *history.timestamps.last().ok_or("Empty history")?;
+    let mut forecast = model.predict(future_steps(
+        last_timestamp,
+        config.forecast.horizon_hours,
+        history.step(),
+        config.timezone,
+    ))?;
+
+    if let Some(bias_config) = &config.bias_correction {
+        let recent = tail(history, bias_config.lookback_hours);
+        let fitted = model.predict(recent.timestamps.to_vec())?;
+        let correction = BiasCorrection::learn(&recent, &fitted, bias_config);
+        eprintln!(
+            "Bias correction applied (largest: {})",
+            correction.largest()
+        );
+        forecast = correction.apply(&forecast);
+    }
+    if let Some(near_term) = &config.near_term {
+        // The blend only sharpens the first hours; without it the forecast stands
+        match nowcast::blend(&forecast, history, near_term) {
+            Ok(blended) => {
+                forecast = blended;
+                let horizon_hours = forecast.len() as f64 * forecast.step_hours();
+                eprintln!(
+                    "First {} forecast hours blended with the latest readings",
+                    near_term.hours.min(horizon_hours as usize)
+                );
+            }
+            Err(e) => eprintln!("⚠️  Forecast left unblended: {}", e),
+        }
+    }
+    if let Some(openadr_config) = &config.openadr {
+        let signals = openadr::load(openadr_config, &config.site, config.timezone)?;
+        let (adjusted, steps) = openadr::apply(&forecast, &signals, openadr_config);
+        if steps > 0 {
+            eprintln!("OpenADR events adjusted {} forecast steps", steps);
+        }
+        forecast = adjusted;
+    }
+    Ok((model, forecast))
+}
+
+/// The last `hours` of a series, however many points that is at its step.
+pub fn tail(series: &Series, hours: usize) -> Series {
+    let per_hour = (HOUR / series.step()).max(1) as usize;
+    let start = series.len().saturating_sub(hours * per_hour);
+    series.slice(start..series.len())
+}
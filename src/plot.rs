@@ -0,0 +1,484 @@
+//! Charts of the history, forecasts and backtests, drawn with plotters when
+//! built with the `plot` feature. Without it the drawing functions return an
+//! error, and the downsampling that feeds them stays available.
+
+#[cfg(feature = "plot")]
+use chrono::DateTime;
+#[cfg(feature = "plot")]
+use plotters::coord::Shift;
+#[cfg(feature = "plot")]
+use plotters::prelude::*;
+use std::error::Error;
+
+use crate::data::Series;
+use crate::forecast::Forecast;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{exceedance::Exceedance, forecast::Changepoint, metrics::Metrics};
+
+/// Default chart title.
+pub const CAPTION: &str = "EV Charging Demand Forecast";
+
+/// Default for `output.plot_max_points`: a few points per pixel of the widest chart.
+pub const MAX_POINTS: usize = 2000;
+
+/// Indices of at most `max_points` points that keep the shape of the line,
+/// by largest-triangle-three-buckets (LTTB).
+///
+/// The first and last points are always kept; `max_points` of 0 keeps all.
+pub fn lttb_indices(timestamps: &[i64], values: &[f64], max_points: usize) -> Vec<usize> {
+    let n = timestamps.len().min(values.len());
+    if max_points == 0 || n <= max_points.max(3) {
+        return (0..n).collect();
+    }
+    let buckets = max_points.max(3) - 2;
+    let every = (n - 2) as f64 / buckets as f64;
+    let bucket = |i: usize| ((i as f64 * every) as usize + 1).min(n - 1);
+    let point = |i: usize| (timestamps[i] as f64, values[i]);
+
+    let mut indices = Vec::with_capacity(buckets + 2);
+    indices.push(0);
+    let mut previous = 0;
+    for i in 0..buckets {
+        // The next bucket's average stands in for the point after this one
+        let (next_start, next_end) = (bucket(i + 1), bucket(i + 2).max(bucket(i + 1) + 1).min(n));
+        let count = (next_end - next_start) as f64;
+        let (sum_x, sum_y) =
+            (next_start..next_end).fold((0.0, 0.0), |(x, y), j| (x + point(j).0, y + point(j).1));
+        let (next_x, next_y) = (sum_x / count, sum_y / count);
+
+        let (prev_x, prev_y) = point(previous);
+        let area = |j: usize| {
+            let (x, y) = point(j);
+            ((prev_x - next_x) * (y - prev_y) - (prev_x - x) * (next_y - prev_y)).abs()
+        };
+        let chosen = (bucket(i)..bucket(i + 1))
+            .max_by(|a, b| area(*a).total_cmp(&area(*b)))
+            .unwrap_or(bucket(i));
+        indices.push(chosen);
+        previous = chosen;
+    }
+    indices.push(n - 1);
+    indices
+}
+
+/// `series` downsampled to at most `max_points` points (see [`lttb_indices`]).
+pub fn downsample(series: &Series, max_points: usize) -> Series {
+    let indices = lttb_indices(&series.timestamps, &series.values, max_points);
+    Series::new(
+        indices.iter().map(|&i| series.timestamps[i]).collect(),
+        indices.iter().map(|&i| series.values[i]).collect(),
+    )
+}
+
+/// `forecast` downsampled on its point forecast, keeping the matching bounds.
+#[cfg(feature = "plot")]
+fn downsample_forecast(forecast: &Forecast, max_points: usize) -> Forecast {
+    let indices = lttb_indices(&forecast.timestamps, &forecast.point, max_points);
+    let pick = |values: &[f64]| indices.iter().map(|&i| values[i]).collect();
+    Forecast {
+        timestamps: indices.iter().map(|&i| forecast.timestamps[i]).collect(),
+        point: pick(&forecast.point),
+        lower: pick(&forecast.lower),
+        upper: pick(&forecast.upper),
+    }
+}
+
+/// Draw the history and the forecast (with its uncertainty band) to a PNG.
+///
+/// Each line is downsampled to at most `max_points` points (0 draws every point).
+#[cfg(all(feature = "plot", not(target_arch = "wasm32")))]
+pub fn plot_forecast(
+    history: &Series,
+    forecast: &Forecast,
+    output_file: &str,
+    max_points: usize,
+) -> Result<(), Box<dyn Error>> {
+    plot_forecast_with_changepoints(history, forecast, &[], output_file, max_points)
+}
+
+/// [`plot_forecast`], with a vertical line at each trend changepoint in the plotted range.
+#[cfg(all(feature = "plot", not(target_arch = "wasm32")))]
+pub fn plot_forecast_with_changepoints(
+    history: &Series,
+    forecast: &Forecast,
+    changepoints: &[i64],
+    output_file: &str,
+    max_points: usize,
+) -> Result<(), Box<dyn Error>> {
+    let root = BitMapBackend::new(output_file, (900, 600)).into_drawing_area();
+    draw_forecast(&root, history, forecast, changepoints, CAPTION, max_points)?;
+    eprintln!("Forecast saved to {}", output_file);
+    Ok(())
+}
+
+/// The whole history with its significant trend changepoints: green where
+/// growth sped up, magenta where it slowed down.
+#[cfg(all(feature = "plot", not(target_arch = "wasm32")))]
+pub fn plot_changepoints(
+    history: &Series,
+    changepoints: &[Changepoint],
+    output_file: &str,
+    max_points: usize,
+) -> Result<(), Box<dyn Error>> {
+    let root = BitMapBackend::new(output_file, (900, 500)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let history = &downsample(history, max_points);
+    let (Some(&min_x), Some(&max_x)) = (history.timestamps.first(), history.timestamps.last())
+    else {
+        return Err("Nothing to plot".into());
+    };
+    let max_y = history.values.iter().cloned().fold(1.0, f64::max);
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Trend changepoints", ("Arial", 20))
+        .margin(10)
+        .x_label_area_size(50)
+        .y_label_area_size(50)
+        .build_cartesian_2d(min_x..max_x.max(min_x + 1), 0.0..max_y * 1.05)?;
+    chart
+        .configure_mesh()
+        .x_label_formatter(&|ts| {
+            DateTime::from_timestamp(*ts, 0)
+                .map(|dt| dt.format("%Y-%m-%d").to_string())
+                .unwrap_or_default()
+        })
+        .y_desc("kW")
+        .draw()?;
+    chart
+        .draw_series(LineSeries::new(
+            history
+                .timestamps
+                .iter()
+                .zip(&history.values)
+                .map(|(x, y)| (*x, *y)),
+            BLUE.mix(0.6),
+        ))?
+        .label("Actual Demand")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 10, y)], BLUE));
+    for (faster, color) in [(true, GREEN), (false, MAGENTA)] {
+        let lines = changepoints
+            .iter()
+            .filter(|c| c.is_significant() && (c.delta > 0.0) == faster)
+            .map(|c| {
+                PathElement::new(
+                    vec![(c.timestamp, 0.0), (c.timestamp, max_y * 1.05)],
+                    color.stroke_width(2),
+                )
+            });
+        chart
+            .draw_series(lines)?
+            .label(if faster {
+                "Growth speeds up"
+            } else {
+                "Growth slows down"
+            })
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 10, y)], color));
+    }
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .draw()?;
+    root.present()?;
+    eprintln!("Changepoint chart saved to {}", output_file);
+    Ok(())
+}
+
+/// Same chart as [`plot_forecast`], rendered to an SVG string for embedding in reports.
+#[cfg(feature = "plot")]
+pub fn forecast_svg(
+    history: &Series,
+    forecast: &Forecast,
+    caption: &str,
+    size: (u32, u32),
+    max_points: usize,
+) -> Result<String, Box<dyn Error>> {
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, size).into_drawing_area();
+        draw_forecast(&root, history, forecast, &[], caption, max_points)?;
+    }
+    Ok(svg)
+}
+
+#[cfg(feature = "plot")]
+fn draw_forecast<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    history: &Series,
+    forecast: &Forecast,
+    changepoints: &[i64],
+    caption: &str,
+    max_points: usize,
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let history = &downsample(history, max_points);
+    let forecast = &downsample_forecast(forecast, max_points);
+    root.fill(&WHITE)?;
+
+    let min_x = *history
+        .timestamps
+        .first()
+        .or(forecast.timestamps.first())
+        .ok_or("Nothing to plot")?;
+    let max_x = *forecast
+        .timestamps
+        .last()
+        .or(history.timestamps.last())
+        .ok_or("Nothing to plot")?;
+    let all_y = history
+        .values
+        .iter()
+        .chain(forecast.upper.iter())
+        .chain(forecast.lower.iter());
+    let min_y = all_y
+        .clone()
+        .cloned()
+        .fold(f64::INFINITY, f64::min)
+        .min(0.0);
+    let max_y = all_y
+        .cloned()
+        .fold(f64::NEG_INFINITY, f64::max)
+        .max(min_y + 1.0);
+
+    let mut chart = ChartBuilder::on(root)
+        .caption(caption, ("Arial", 20))
+        .margin(10)
+        .x_label_area_size(50)
+        .y_label_area_size(50)
+        .build_cartesian_2d(min_x..max_x, min_y..max_y)?;
+
+    chart
+        .configure_mesh()
+        .x_label_formatter(&|ts| {
+            DateTime::from_timestamp(*ts, 0)
+                .map(|dt| dt.format("%m-%d %H:%M").to_string())
+                .unwrap_or_default()
+        })
+        .y_desc("kW")
+        .draw()?;
+
+    // Uncertainty band (light red)
+    if !forecast.is_empty() {
+        let band = forecast
+            .timestamps
+            .iter()
+            .zip(&forecast.upper)
+            .map(|(x, y)| (*x, *y))
+            .chain(
+                forecast
+                    .timestamps
+                    .iter()
+                    .zip(&forecast.lower)
+                    .rev()
+                    .map(|(x, y)| (*x, *y)),
+            );
+        chart.draw_series(std::iter::once(Polygon::new(
+            band.collect::<Vec<_>>(),
+            RED.mix(0.2),
+        )))?;
+    }
+
+    // Plot actual values (BLUE)
+    chart
+        .draw_series(LineSeries::new(
+            history
+                .timestamps
+                .iter()
+                .zip(&history.values)
+                .map(|(x, y)| (*x, *y)),
+            &BLUE,
+        ))?
+        .label("Actual Demand")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 10, y)], BLUE));
+
+    // Plot predicted values (RED)
+    chart
+        .draw_series(LineSeries::new(
+            forecast
+                .timestamps
+                .iter()
+                .zip(&forecast.point)
+                .map(|(x, y)| (*x, *y)),
+            &RED,
+        ))?
+        .label("Predicted Demand")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 10, y)], RED));
+
+    let shown: Vec<i64> = changepoints
+        .iter()
+        .copied()
+        .filter(|ts| (min_x..=max_x).contains(ts))
+        .collect();
+    if !shown.is_empty() {
+        chart
+            .draw_series(shown.iter().map(|ts| {
+                PathElement::new(vec![(*ts, min_y), (*ts, max_y)], GREEN.stroke_width(2))
+            }))?
+            .label("Trend changepoint")
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 10, y)], GREEN));
+    }
+
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .draw()?;
+    root.present()?;
+    Ok(())
+}
+
+/// Plot the probability of exceeding each threshold over the forecast to a PNG.
+#[cfg(all(feature = "plot", not(target_arch = "wasm32")))]
+pub fn plot_exceedance(
+    forecast: &Forecast,
+    curves: &[Exceedance],
+    output_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    let root = BitMapBackend::new(output_file, (900, 500)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let (Some(&min_x), Some(&max_x)) = (forecast.timestamps.first(), forecast.timestamps.last())
+    else {
+        return Err("Nothing to plot".into());
+    };
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Probability of exceedance", ("Arial", 20))
+        .margin(10)
+        .x_label_area_size(50)
+        .y_label_area_size(50)
+        .build_cartesian_2d(min_x..max_x.max(min_x + 1), 0.0..100.0)?;
+    chart
+        .configure_mesh()
+        .x_label_formatter(&|ts| {
+            DateTime::from_timestamp(*ts, 0)
+                .map(|dt| dt.format("%m-%d %H:%M").to_string())
+                .unwrap_or_default()
+        })
+        .y_desc("%")
+        .draw()?;
+    for (i, curve) in curves.iter().enumerate() {
+        let color = Palette99::pick(i).to_rgba();
+        chart
+            .draw_series(LineSeries::new(
+                forecast
+                    .timestamps
+                    .iter()
+                    .zip(&curve.probabilities)
+                    .map(|(x, p)| (*x, 100.0 * p)),
+                color.stroke_width(2),
+            ))?
+            .label(format!("> {} kW", curve.threshold_kw))
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 10, y)], color));
+    }
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .draw()?;
+    root.present()?;
+    eprintln!("Exceedance chart saved to {}", output_file);
+    Ok(())
+}
+
+/// Plot MAE and RMSE against lead time (index 0 = 1 h ahead) to a PNG.
+#[cfg(all(feature = "plot", not(target_arch = "wasm32")))]
+pub fn plot_error_by_lead(by_lead: &[Metrics], output_file: &str) -> Result<(), Box<dyn Error>> {
+    let root = BitMapBackend::new(output_file, (900, 500)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let max_lead = by_lead.len().max(2) as i64;
+    let max_y = by_lead.iter().map(|m| m.rmse).fold(1.0, f64::max);
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Backtest error by lead time", ("Arial", 20))
+        .margin(10)
+        .x_label_area_size(50)
+        .y_label_area_size(50)
+        .build_cartesian_2d(1..max_lead, 0.0..max_y * 1.05)?;
+    chart
+        .configure_mesh()
+        .x_desc("Hours ahead")
+        .y_desc("kW")
+        .draw()?;
+
+    let line = |f: fn(&Metrics) -> f64| {
+        by_lead
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.n > 0)
+            .map(move |(i, m)| (i as i64 + 1, f(m)))
+    };
+    chart
+        .draw_series(LineSeries::new(line(|m| m.mae), &BLUE))?
+        .label("MAE")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 10, y)], BLUE));
+    chart
+        .draw_series(LineSeries::new(line(|m| m.rmse), &RED))?
+        .label("RMSE")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 10, y)], RED));
+
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .draw()?;
+    root.present()?;
+    eprintln!("Lead-time chart saved to {}", output_file);
+    Ok(())
+}
+
+#[cfg(not(feature = "plot"))]
+fn no_plotting(chart: &str) -> Box<dyn Error> {
+    format!("Drawing {} needs the `plot` feature", chart).into()
+}
+
+#[cfg(all(not(feature = "plot"), not(target_arch = "wasm32")))]
+pub fn plot_forecast(
+    _: &Series,
+    _: &Forecast,
+    output_file: &str,
+    _: usize,
+) -> Result<(), Box<dyn Error>> {
+    Err(no_plotting(output_file))
+}
+
+#[cfg(all(not(feature = "plot"), not(target_arch = "wasm32")))]
+pub fn plot_forecast_with_changepoints(
+    _: &Series,
+    _: &Forecast,
+    _: &[i64],
+    output_file: &str,
+    _: usize,
+) -> Result<(), Box<dyn Error>> {
+    Err(no_plotting(output_file))
+}
+
+#[cfg(all(not(feature = "plot"), not(target_arch = "wasm32")))]
+pub fn plot_changepoints(
+    _: &Series,
+    _: &[Changepoint],
+    output_file: &str,
+    _: usize,
+) -> Result<(), Box<dyn Error>> {
+    Err(no_plotting(output_file))
+}
+
+#[cfg(not(feature = "plot"))]
+pub fn forecast_svg(
+    _: &Series,
+    _: &Forecast,
+    caption: &str,
+    _: (u32, u32),
+    _: usize,
+) -> Result<String, Box<dyn Error>> {
+    Err(no_plotting(caption))
+}
+
+#[cfg(all(not(feature = "plot"), not(target_arch = "wasm32")))]
+pub fn plot_exceedance(
+    _: &Forecast,
+    _: &[Exceedance],
+    output_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    Err(no_plotting(output_file))
+}
+
+#[cfg(all(not(feature = "plot"), not(target_arch = "wasm32")))]
+pub fn plot_error_by_lead(_: &[Metrics], output_file: &str) -> Result<(), Box<dyn Error>> {
+    Err(no_plotting(output_file))
+}
@@ -0,0 +1,214 @@
+//! Instantaneous power samples as interval energy.
+//!
+//! A meter that reports kW samples rather than interval energy is integrated
+//! with the trapezoidal rule: power is taken to change linearly from one sample
+//! to the next, and the area of each segment is split over the steps it spans.
+//! A step's value is its energy over the time its samples cover, in kW like any
+//! load series, so an hourly step holds its kWh. Gaps between samples longer
+//! than `data.power.max_gap_minutes` are not bridged.
+//!
+//! Cumulative register readings (`data.power.register`) check the result: the
+//! energy the register counted between two readings against the integral of
+//! the samples over the same span.
+
+use chrono_tz::Tz;
+use csv::ReaderBuilder;
+use std::error::Error;
+
+use crate::config::PowerConfig;
+use crate::data::{self, RowCounts, Series, parse_timestamp_text};
+use crate::preprocess::{HOUR, floor_step};
+
+/// Sorted, finite `(timestamp, kW)` samples of a series, one per timestamp.
+///
+/// Non-finite samples are dropped and counted as invalid; of samples at the
+/// same time the last one is kept.
+pub fn samples(series: Series, counts: &mut RowCounts) -> Vec<(i64, f64)> {
+    let mut points: Vec<(i64, f64)> = series
+        .timestamps
+        .iter()
+        .copied()
+        .zip(series.values.iter().copied())
+        .collect();
+    let before = points.len();
+    points.retain(|(_, kw)| kw.is_finite());
+    counts.invalid += before - points.len();
+    points.sort_by_key(|(ts, _)| *ts);
+    points.reverse();
+    points.dedup_by_key(|(ts, _)| *ts);
+    points.reverse();
+    points
+}
+
+/// The segments between consecutive samples at most `max_gap` seconds apart.
+fn segments(
+    samples: &[(i64, f64)],
+    max_gap: i64,
+) -> impl Iterator<Item = (&(i64, f64), &(i64, f64))> {
+    samples
+        .iter()
+        .zip(&samples[1.min(samples.len())..])
+        .filter(move |(a, b)| b.0 - a.0 <= max_gap)
+}
+
+/// Energy (kW·s) of the segment from `a` to `b` within `[from, to)`.
+fn area(a: (i64, f64), b: (i64, f64), from: i64, to: i64) -> f64 {
+    let (start, end) = (from.max(a.0), to.min(b.0));
+    if end <= start {
+        return 0.0;
+    }
+    let power = |t: i64| a.1 + (b.1 - a.1) * (t - a.0) as f64 / (b.0 - a.0) as f64;
+    (end - start) as f64 * (power(start) + power(end)) / 2.0
+}
+
+/// Integrate sorted samples (see [`samples`]) onto a grid of `step` seconds.
+///
+/// Steps no segment reaches are left out, for resampling to fill.
+pub fn integrate(samples: &[(i64, f64)], step: i64, max_gap: i64) -> Series {
+    let (Some(first), Some(last)) = (samples.first(), samples.last()) else {
+        return Series::default();
+    };
+    let origin = floor_step(first.0, step);
+    let n_steps = ((floor_step(last.0, step) - origin) / step + 1) as usize;
+    let mut energy = vec![0.0; n_steps];
+    let mut covered = vec![0i64; n_steps];
+    for (a, b) in segments(samples, max_gap) {
+        let mut slot = floor_step(a.0, step);
+        while slot < b.0 {
+            let i = ((slot - origin) / step) as usize;
+            energy[i] += area(*a, *b, slot, slot + step);
+            covered[i] += b.0.min(slot + step) - a.0.max(slot);
+            slot += step;
+        }
+    }
+    let (timestamps, values): (Vec<_>, Vec<_>) = (0..n_steps)
+        .filter(|i| covered[*i] > 0)
+        .map(|i| (origin + i as i64 * step, energy[i] / covered[i] as f64))
+        .unzip();
+    Series::new(timestamps, values)
+}
+
+/// Energy (kWh) of the samples over `[from, to)`; `None` unless they cover all of it.
+pub fn energy_between(samples: &[(i64, f64)], from: i64, to: i64, max_gap: i64) -> Option<f64> {
+    let start = samples
+        .partition_point(|(ts, _)| *ts <= from)
+        .checked_sub(1)?;
+    let mut kws = 0.0;
+    let mut reached = from;
+    for (a, b) in samples[start..].iter().zip(&samples[start + 1..]) {
+        if a.0 >= to {
+            break;
+        }
+        if b.0 - a.0 > max_gap {
+            return None;
+        }
+        kws += area(*a, *b, from, to);
+        reached = b.0;
+    }
+    (reached >= to).then_some(kws / HOUR as f64)
+}
+
+/// A span between register readings whose energy the samples don't match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub from: i64,
+    pub to: i64,
+    pub register_kwh: f64,
+    pub integrated_kwh: f64,
+}
+
+/// How the integrated samples compare with the register.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RegisterCheck {
+    /// Spans between consecutive readings that the samples cover throughout.
+    pub intervals: usize,
+    pub register_kwh: f64,
+    pub integrated_kwh: f64,
+    /// Spans off by more than `data.power.tolerance`, in time order.
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl RegisterCheck {
+    /// The mismatch furthest off, in kWh.
+    pub fn worst(&self) -> Option<&Mismatch> {
+        self.mismatches.iter().max_by(|a, b| {
+            (a.integrated_kwh - a.register_kwh)
+                .abs()
+                .total_cmp(&(b.integrated_kwh - b.register_kwh).abs())
+        })
+    }
+}
+
+/// Compare the energy between consecutive register readings with the samples'.
+///
+/// Spans the samples don't cover, and spans over which the register went
+/// down (a reset or a meter swap), are skipped.
+pub fn check_register(
+    samples: &[(i64, f64)],
+    register: &[(i64, f64)],
+    config: &PowerConfig,
+) -> RegisterCheck {
+    let max_gap = config.max_gap_minutes as i64 * 60;
+    let mut check = RegisterCheck::default();
+    for pair in register.windows(2) {
+        let ((from, start), (to, end)) = (pair[0], pair[1]);
+        let register_kwh = end - start;
+        if register_kwh < 0.0 {
+            continue;
+        }
+        let Some(integrated_kwh) = energy_between(samples, from, to, max_gap) else {
+            continue;
+        };
+        check.intervals += 1;
+        check.register_kwh += register_kwh;
+        check.integrated_kwh += integrated_kwh;
+        if (integrated_kwh - register_kwh).abs() > config.tolerance * register_kwh.max(1.0) {
+            check.mismatches.push(Mismatch {
+                from,
+                to,
+                register_kwh,
+                integrated_kwh,
+            });
+        }
+    }
+    check
+}
+
+/// Register readings of a `timestamp,energy_kwh` CSV, in time order.
+pub fn load_register(path: &str, timezone: Option<Tz>) -> Result<Vec<(i64, f64)>, Box<dyn Error>> {
+    let mut rdr = ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data::open(path)?);
+    let headers = rdr.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h == name)
+            .ok_or_else(|| format!("{} has no `{}` column", path, name))
+    };
+    let (ts_column, energy_column) = (column("timestamp")?, column("energy_kwh")?);
+    let mut readings = Vec::new();
+    for (i, record) in rdr.records().enumerate() {
+        let record = record?;
+        let field = |c: usize| record.get(c).unwrap_or_default();
+        let ts = parse_timestamp_text(field(ts_column), timezone).ok_or_else(|| {
+            format!(
+                "{} line {}: bad timestamp {:?}",
+                path,
+                i + 2,
+                field(ts_column)
+            )
+        })?;
+        let energy: f64 = field(energy_column).parse().map_err(|_| {
+            format!(
+                "{} line {}: bad energy {:?}",
+                path,
+                i + 2,
+                field(energy_column)
+            )
+        })?;
+        readings.push((ts, energy));
+    }
+    readings.sort_by_key(|(ts, _)| *ts);
+    Ok(readings)
+}
@@ -0,0 +1,425 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+use crate::data::{Series, Session, parse_datetime_to_timestamp};
+use crate::metrics::quantile;
+
+/// One hour in seconds, the default length of a step.
+pub const HOUR: i64 = 3600;
+
+/// Round a timestamp down to the start of its hour.
+pub fn floor_hour(timestamp: i64) -> i64 {
+    floor_step(timestamp, HOUR)
+}
+
+/// Round a timestamp down to the start of its `step`-second slot.
+pub fn floor_step(timestamp: i64, step: i64) -> i64 {
+    timestamp - timestamp.rem_euclid(step)
+}
+
+/// Seconds in a `data.step_minutes` step, which has to divide an hour.
+pub fn step_seconds(minutes: u32) -> Result<i64, Box<dyn Error>> {
+    if minutes == 0 || 60 % minutes != 0 {
+        return Err(format!(
+            "data.step_minutes has to divide an hour (1, 5, 15, 30, 60, ...), not {}",
+            minutes
+        )
+        .into());
+    }
+    Ok(minutes as i64 * 60)
+}
+
+/// Seconds of overlap between the half-open intervals `[a0, a1)` and `[b0, b1)`.
+pub fn overlap_seconds(a0: i64, a1: i64, b0: i64, b1: i64) -> i64 {
+    (a1.min(b1) - a0.max(b0)).max(0)
+}
+
+/// First slot and number of `step`-second slots covering every session.
+fn session_grid(sessions: &[Session], step: i64) -> Option<(i64, usize)> {
+    let first = sessions.iter().map(|s| s.start).min()?;
+    let last = sessions.iter().map(|s| s.stop.max(s.start)).max()?;
+    let origin = floor_step(first, step);
+    Some((
+        origin,
+        ((floor_step(last, step) - origin) / step + 1) as usize,
+    ))
+}
+
+pub(crate) fn hourly_timestamps(origin: i64, n_hours: usize) -> Vec<i64> {
+    step_timestamps(origin, n_hours, HOUR)
+}
+
+fn step_timestamps(origin: i64, n_steps: usize, step: i64) -> Vec<i64> {
+    (0..n_steps as i64).map(|i| origin + i * step).collect()
+}
+
+/// Turn sessions into an hourly load series in kW (= kWh per hour).
+///
+/// Each session's energy is spread evenly over its plugged-in time, so long
+/// sessions contribute to every hour they overlap instead of piling up on the
+/// start hour. Hours without any session are filled with zero, which gives
+/// Prophet a regular grid to fit on.
+pub fn hourly_load(sessions: &[Session]) -> Series {
+    load(sessions, HOUR)
+}
+
+/// [`hourly_load`] on a grid of `step` seconds, still in kW: a 5-minute
+/// slot that took 1 kWh is a 12 kW load.
+pub fn load(sessions: &[Session], step: i64) -> Series {
+    let Some((origin, n_steps)) = session_grid(sessions, step) else {
+        return Series::default();
+    };
+    Series::new(
+        step_timestamps(origin, n_steps, step),
+        spread(sessions.iter(), origin, n_steps, step),
+    )
+}
+
+/// [`load`] of the importing and the exporting (negative) sessions apart,
+/// on one grid, the export as a positive load: an hour with a car charging
+/// 10 kWh and one exporting 4 kWh has 10 kW of import and 4 kW of export,
+/// not 6 kW of import.
+pub fn load_channels(sessions: &[Session], step: i64) -> (Series, Series) {
+    let Some((origin, n_steps)) = session_grid(sessions, step) else {
+        return (Series::default(), Series::default());
+    };
+    let timestamps = step_timestamps(origin, n_steps, step);
+    let exports: Vec<Session> = sessions
+        .iter()
+        .filter(|s| s.energy_wh < 0.0)
+        .map(|s| Session {
+            energy_wh: -s.energy_wh,
+            ..*s
+        })
+        .collect();
+    let imports = sessions.iter().filter(|s| s.energy_wh >= 0.0);
+    (
+        Series::new(timestamps.clone(), spread(imports, origin, n_steps, step)),
+        Series::new(timestamps, spread(exports.iter(), origin, n_steps, step)),
+    )
+}
+
+/// Each session's energy spread over the `step`-second slots it overlaps, in kW.
+fn spread<'a>(
+    sessions: impl Iterator<Item = &'a Session>,
+    origin: i64,
+    n_steps: usize,
+    step: i64,
+) -> Vec<f64> {
+    let mut values = vec![0.0; n_steps];
+    let per_hour = HOUR as f64 / step as f64;
+
+    for session in sessions {
+        let energy_kwh = session.energy_wh / 1000.0;
+        let duration = session.stop - session.start;
+        if duration <= 0 {
+            values[((floor_step(session.start, step) - origin) / step) as usize] +=
+                energy_kwh * per_hour;
+            continue;
+        }
+        let mut slot = floor_step(session.start, step);
+        while slot < session.stop {
+            let overlap = overlap_seconds(session.start, session.stop, slot, slot + step);
+            values[((slot - origin) / step) as usize] +=
+                energy_kwh * per_hour * overlap as f64 / duration as f64;
+            slot += step;
+        }
+    }
+    values
+}
+
+/// Turn sessions into an hourly plugged-in occupancy series.
+///
+/// Each value is the average number of vehicles connected during that hour,
+/// so a car plugged in from 10:30 to 12:00 counts 0.5 at 10:00 and 1.0 at 11:00.
+pub fn hourly_occupancy(sessions: &[Session]) -> Series {
+    let Some((origin, n_hours)) = session_grid(sessions, HOUR) else {
+        return Series::default();
+    };
+    let mut values = vec![0.0; n_hours];
+
+    for session in sessions {
+        let mut hour = floor_hour(session.start);
+        while hour < session.stop {
+            let overlap = overlap_seconds(session.start, session.stop, hour, hour + HOUR);
+            values[((hour - origin) / HOUR) as usize] += overlap as f64 / HOUR as f64;
+            hour += HOUR;
+        }
+    }
+
+    Series::new(hourly_timestamps(origin, n_hours), values)
+}
+
+/// Turn sessions into an hourly overstay series: vehicles still plugged in
+/// after they could have finished charging.
+///
+/// A session is taken to charge at `charging_power_kw` from plug-in until its
+/// energy is delivered; after `grace_minutes` more it counts as overstaying,
+/// like [`hourly_occupancy`], until plug-out.
+pub fn hourly_overstay(sessions: &[Session], charging_power_kw: f64, grace_minutes: f64) -> Series {
+    let Some((origin, n_hours)) = session_grid(sessions, HOUR) else {
+        return Series::default();
+    };
+    let mut values = vec![0.0; n_hours];
+
+    for session in sessions {
+        let charging = (session.energy_wh / 1000.0 / charging_power_kw * HOUR as f64) as i64;
+        let idle_from = session.start + charging + (grace_minutes * 60.0) as i64;
+        let mut hour = floor_hour(idle_from);
+        while hour < session.stop {
+            let overlap = overlap_seconds(idle_from, session.stop, hour, hour + HOUR);
+            values[((hour - origin) / HOUR) as usize] += overlap as f64 / HOUR as f64;
+            hour += HOUR;
+        }
+    }
+
+    Series::new(hourly_timestamps(origin, n_hours), values)
+}
+
+/// How [`Resample`] fills hours missing from the grid.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fill {
+    /// No sessions means no demand.
+    #[default]
+    Zero,
+    /// Carry the last value forward.
+    Previous,
+    /// Interpolate between the neighbouring values.
+    Linear,
+}
+
+/// Put the series on a regular grid, hourly unless `data.step_minutes` says
+/// otherwise: values are averaged within each step and missing steps are filled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Resample {
+    pub fill: Fill,
+}
+
+impl Resample {
+    pub fn hourly() -> Self {
+        Self::default()
+    }
+
+    pub fn fill(mut self, fill: Fill) -> Self {
+        self.fill = fill;
+        self
+    }
+
+    fn apply(&self, series: Series, step: i64) -> Series {
+        let (Some(first), Some(last)) = (
+            series.timestamps.iter().min(),
+            series.timestamps.iter().max(),
+        ) else {
+            return series;
+        };
+        let origin = floor_step(*first, step);
+        let n_hours = ((floor_step(*last, step) - origin) / step + 1) as usize;
+        let mut sums = vec![0.0; n_hours];
+        let mut counts = vec![0usize; n_hours];
+        for (ts, v) in series.timestamps.iter().zip(&series.values) {
+            let i = ((floor_step(*ts, step) - origin) / step) as usize;
+            sums[i] += v;
+            counts[i] += 1;
+        }
+        let known: Vec<Option<f64>> = sums
+            .iter()
+            .zip(&counts)
+            .map(|(s, n)| (*n > 0).then(|| s / *n as f64))
+            .collect();
+
+        let mut values = vec![0.0; n_hours];
+        let mut previous: Option<(usize, f64)> = None;
+        for i in 0..n_hours {
+            values[i] = match (known[i], self.fill) {
+                (Some(v), _) => v,
+                (None, Fill::Zero) => 0.0,
+                (None, Fill::Previous) => previous.map(|(_, v)| v).unwrap_or(0.0),
+                (None, Fill::Linear) => {
+                    let next = (i + 1..n_hours).find_map(|j| known[j].map(|v| (j, v)));
+                    match (previous, next) {
+                        (Some((a, va)), Some((b, vb))) => {
+                            va + (vb - va) * (i - a) as f64 / (b - a) as f64
+                        }
+                        (Some((_, v)), None) | (None, Some((_, v))) => v,
+                        (None, None) => 0.0,
+                    }
+                }
+            };
+            if let Some(v) = known[i] {
+                previous = Some((i, v));
+            }
+        }
+        Series::new(step_timestamps(origin, n_hours, step), values)
+    }
+}
+
+/// Clip values outside `[Q1 - k·IQR, Q3 + k·IQR]` (and below zero).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClipOutliers {
+    pub iqr: f64,
+}
+
+impl ClipOutliers {
+    pub fn iqr(k: f64) -> Self {
+        Self { iqr: k }
+    }
+
+    fn apply(&self, mut series: Series) -> Series {
+        let mut sorted = series.values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let (q1, q3) = (quantile(&sorted, 0.25), quantile(&sorted, 0.75));
+        let spread = q3 - q1;
+        if spread <= 0.0 {
+            return series;
+        }
+        // A load with export (see `data.export`) can go below zero; others can't
+        let floor = if sorted[0] < 0.0 {
+            f64::NEG_INFINITY
+        } else {
+            0.0
+        };
+        let (low, high) = ((q1 - self.iqr * spread).max(floor), q3 + self.iqr * spread);
+        series.values = series.values.iter().map(|v| v.clamp(low, high)).collect();
+        series
+    }
+}
+
+/// Keep only the hours in `[from, to]` (`YYYY-MM-DD` or `YYYY-MM-DD HH:MM`), e.g. to
+/// drop a commissioning period or an outdated tariff regime.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Window {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+fn parse_bound(text: &str) -> Result<i64, Box<dyn Error>> {
+    if text.len() == 10 {
+        parse_datetime_to_timestamp(&format!("{} 00:00", text))
+    } else {
+        parse_datetime_to_timestamp(text)
+    }
+}
+
+impl Window {
+    pub fn from(date: &str) -> Self {
+        Self {
+            from: Some(date.to_string()),
+            to: None,
+        }
+    }
+
+    pub fn to(mut self, date: &str) -> Self {
+        self.to = Some(date.to_string());
+        self
+    }
+
+    fn apply(&self, series: Series) -> Result<Series, Box<dyn Error>> {
+        let from = self
+            .from
+            .as_deref()
+            .map(parse_bound)
+            .transpose()?
+            .unwrap_or(i64::MIN);
+        let to = self
+            .to
+            .as_deref()
+            .map(parse_bound)
+            .transpose()?
+            .unwrap_or(i64::MAX);
+        let (timestamps, values): (Vec<_>, Vec<_>) = series
+            .timestamps
+            .iter()
+            .zip(&series.values)
+            .filter(|(ts, _)| (from..=to).contains(*ts))
+            .unzip();
+        Ok(Series::new(timestamps, values))
+    }
+}
+
+/// One preprocessing step, as written in the config (`step = "clip_outliers"`, ...).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum Step {
+    Resample(Resample),
+    ClipOutliers(ClipOutliers),
+    Window(Window),
+}
+
+impl From<Resample> for Step {
+    fn from(step: Resample) -> Self {
+        Step::Resample(step)
+    }
+}
+
+impl From<ClipOutliers> for Step {
+    fn from(step: ClipOutliers) -> Self {
+        Step::ClipOutliers(step)
+    }
+}
+
+impl From<Window> for Step {
+    fn from(step: Window) -> Self {
+        Step::Window(step)
+    }
+}
+
+/// An ordered chain of preprocessing steps applied to the hourly load series.
+///
+/// ```
+/// use cpo_charging_forecast::preprocess::{ClipOutliers, Pipeline, Resample, Window};
+///
+/// let pipeline = Pipeline::new()
+///     .step(Window::from("2024-03-01"))
+///     .step(Resample::hourly())
+///     .step(ClipOutliers::iqr(3.0));
+/// assert_eq!(pipeline.steps().len(), 3);
+/// ```
+///
+/// It serializes as the list of its steps, so the exact chain a forecast was
+/// made with can be stored in (and reloaded from) the config.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Pipeline {
+    steps: Vec<Step>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn step(mut self, step: impl Into<Step>) -> Self {
+        self.steps.push(step.into());
+        self
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Apply the steps to an hourly series.
+    pub fn apply(&self, series: Series) -> Result<Series, Box<dyn Error>> {
+        self.apply_at(series, HOUR)
+    }
+
+    /// Apply the steps to a series of `step`-second points, which any
+    /// `resample` keeps it on.
+    pub fn apply_at(&self, mut series: Series, step: i64) -> Result<Series, Box<dyn Error>> {
+        for s in &self.steps {
+            series = match s {
+                Step::Resample(s) => s.apply(series, step),
+                Step::ClipOutliers(s) => s.apply(series),
+                Step::Window(s) => s.apply(series)?,
+            };
+        }
+        Ok(series)
+    }
+}
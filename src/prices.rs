@@ -0,0 +1,371 @@
+//! Day-ahead electricity prices, as a Prophet regressor and for price-aware
+//! depot charging.
+//!
+//! Prices come from the ENTSO-E transparency platform (document type A44) in
+//! EUR/MWh and are cached per day under `cache_dir`. Hourly prices are kept in
+//! local wall-clock time, like the load; 15-minute products are averaged into
+//! their hour.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use crate::config::PricesConfig;
+use crate::data::{format_timestamp, parse_timestamp_text};
+use crate::preprocess::{HOUR, floor_hour};
+
+/// Name of the price regressor.
+pub const REGRESSOR: &str = "day_ahead_price";
+
+const DAY: i64 = 24 * HOUR;
+
+/// A day with fewer hours is still being published, and isn't cached.
+const MIN_CACHED_HOURS: usize = 23;
+
+/// Most days asked for in one API request.
+const MAX_REQUEST_DAYS: usize = 365;
+
+/// Hourly prices, by local wall-clock hour.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Prices {
+    hours: BTreeMap<i64, f64>,
+}
+
+impl Prices {
+    pub fn new(hours: BTreeMap<i64, f64>) -> Self {
+        Self { hours }
+    }
+
+    /// The prices of `days`, from the cache or else the API.
+    ///
+    /// Days that can't be fetched (offline, no token, not published yet) are
+    /// left out with a warning rather than failing the forecast.
+    pub fn load(
+        config: &PricesConfig,
+        days: RangeInclusive<NaiveDate>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut hours = BTreeMap::new();
+        let mut missing = Vec::new();
+        for day in days.start().iter_days().take_while(|day| day <= days.end()) {
+            match std::fs::read_to_string(cache_path(config, day)) {
+                Ok(text) => {
+                    let cached: BTreeMap<String, f64> = serde_json::from_str(&text)?;
+                    for (timestamp, price) in cached {
+                        let ts = parse_timestamp_text(&timestamp, None).ok_or_else(|| {
+                            format!("Invalid price hour {:?} in the cache", timestamp)
+                        })?;
+                        hours.insert(ts, price);
+                    }
+                }
+                Err(_) => missing.push(day),
+            }
+        }
+        for run in consecutive(&missing) {
+            match fetch_days(config, run[0], run[run.len() - 1]) {
+                Ok(fetched) => hours.extend(fetched),
+                Err(e) => eprintln!("⚠️  {}; leaving them out", e),
+            }
+        }
+        Ok(Self { hours })
+    }
+
+    /// The prices of the hours before `end`.
+    pub fn until(&self, end: i64) -> Self {
+        Self {
+            hours: self.hours.range(..end).map(|(ts, p)| (*ts, *p)).collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.hours.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hours.is_empty()
+    }
+
+    /// The published price of the hour of `ts`.
+    pub fn get(&self, ts: i64) -> Option<f64> {
+        self.hours.get(&floor_hour(ts)).copied()
+    }
+
+    /// The price of the hour of `ts`, or for an hour not published (yet) that
+    /// of the same hour a whole number of weeks, else days, before.
+    pub fn at(&self, ts: i64) -> f64 {
+        let hour = floor_hour(ts);
+        let earlier = |step: i64| (0..=8).find_map(|n| self.hours.get(&(hour - n * step)).copied());
+        earlier(7 * DAY)
+            .or_else(|| earlier(DAY))
+            .unwrap_or_else(|| self.hours.values().sum::<f64>() / self.hours.len().max(1) as f64)
+    }
+
+    /// [`Prices::at`] each timestamp, as the regressor's values.
+    pub fn features(&self, timestamps: &[i64]) -> Vec<f64> {
+        timestamps.iter().map(|ts| self.at(*ts)).collect()
+    }
+}
+
+fn cache_path(config: &PricesConfig, day: NaiveDate) -> PathBuf {
+    Path::new(&config.cache_dir).join(format!("{}-{}.json", config.bidding_zone, day))
+}
+
+/// Runs of consecutive days, each at most [`MAX_REQUEST_DAYS`] long.
+fn consecutive(days: &[NaiveDate]) -> Vec<Vec<NaiveDate>> {
+    let mut runs: Vec<Vec<NaiveDate>> = Vec::new();
+    for &day in days {
+        match runs.last_mut() {
+            Some(run)
+                if run.len() < MAX_REQUEST_DAYS
+                    && run.last().and_then(|d| d.succ_opt()) == Some(day) =>
+            {
+                run.push(day)
+            }
+            _ => runs.push(vec![day]),
+        }
+    }
+    runs
+}
+
+/// Fetch `first..=last` in one request, caching every complete day.
+fn fetch_days(
+    config: &PricesConfig,
+    first: NaiveDate,
+    last: NaiveDate,
+) -> Result<BTreeMap<i64, f64>, Box<dyn Error>> {
+    let local_midnight = |day: NaiveDate| {
+        local_to_utc(
+            day.and_hms_opt(0, 0, 0).unwrap_or_default(),
+            config.timezone,
+        )
+    };
+    let (start, end) = (
+        local_midnight(first),
+        local_midnight(last.succ_opt().unwrap_or(last)),
+    );
+    let xml = fetch_document(config, start, end).map_err(|e| {
+        format!(
+            "Fetching prices for {} {} .. {} failed: {}",
+            config.bidding_zone, first, last, e
+        )
+    })?;
+    let hours = parse_document(&xml, config.timezone)?;
+
+    let mut by_day: BTreeMap<NaiveDate, BTreeMap<String, f64>> = BTreeMap::new();
+    for (&ts, &price) in &hours {
+        let day = DateTime::from_timestamp(ts, 0)
+            .unwrap_or_default()
+            .date_naive();
+        by_day
+            .entry(day)
+            .or_default()
+            .insert(format_timestamp(ts), price);
+    }
+    std::fs::create_dir_all(&config.cache_dir)?;
+    let mut cached = 0;
+    for (day, day_hours) in by_day
+        .iter()
+        .filter(|(_, day_hours)| day_hours.len() >= MIN_CACHED_HOURS)
+    {
+        std::fs::write(
+            cache_path(config, *day),
+            serde_json::to_string_pretty(day_hours)?,
+        )?;
+        cached += 1;
+    }
+    eprintln!(
+        "Fetched {} hours of {} prices, {} days cached in {}",
+        hours.len(),
+        config.bidding_zone,
+        cached,
+        config.cache_dir
+    );
+    Ok(hours
+        .into_iter()
+        .filter(|(ts, _)| {
+            (first..=last).contains(
+                &DateTime::from_timestamp(*ts, 0)
+                    .unwrap_or_default()
+                    .date_naive(),
+            )
+        })
+        .collect())
+}
+
+#[cfg(feature = "http")]
+fn fetch_document(config: &PricesConfig, start: i64, end: i64) -> Result<String, Box<dyn Error>> {
+    let token = crate::secret::resolve(
+        config
+            .token
+            .as_deref()
+            .ok_or("no prices token configured")?,
+    )?;
+    let period = |ts: i64| {
+        DateTime::from_timestamp(ts, 0)
+            .unwrap_or_default()
+            .format("%Y%m%d%H%M")
+            .to_string()
+    };
+    let response = ureq::get(&config.api_url)
+        .query("securityToken", &token)
+        .query("documentType", "A44")
+        .query("in_Domain", &config.bidding_zone)
+        .query("out_Domain", &config.bidding_zone)
+        .query("periodStart", &period(start))
+        .query("periodEnd", &period(end))
+        .call();
+    match response {
+        Ok(response) => Ok(response.into_string()?),
+        // No data for the period comes back as an acknowledgement
+        Err(ureq::Error::Status(_, response)) => {
+            let text = response.into_string().unwrap_or_default();
+            match text.contains("Acknowledgement_MarketDocument") {
+                true => Ok(text),
+                false => Err(text.into()),
+            }
+        }
+        Err(e) => Err(format!("{}: {}", config.api_url, crate::secret::http_error(&e)).into()),
+    }
+}
+
+#[cfg(not(feature = "http"))]
+fn fetch_document(_: &PricesConfig, _: i64, _: i64) -> Result<String, Box<dyn Error>> {
+    Err("no cached prices, and fetching needs the `http` feature".into())
+}
+
+fn local_to_utc(local: NaiveDateTime, timezone: Option<Tz>) -> i64 {
+    match timezone.and_then(|tz| tz.from_local_datetime(&local).earliest()) {
+        Some(time) => time.timestamp(),
+        None => local.and_utc().timestamp(),
+    }
+}
+
+fn utc_to_local(ts: i64, timezone: Option<Tz>) -> i64 {
+    let time = DateTime::<Utc>::from_timestamp(ts, 0).unwrap_or_default();
+    match timezone {
+        Some(tz) => time.with_timezone(&tz).naive_local().and_utc().timestamp(),
+        None => ts,
+    }
+}
+
+/// One `Period` of a price document.
+#[derive(Default)]
+struct Period {
+    start: Option<i64>,
+    end: Option<i64>,
+    resolution_minutes: i64,
+    points: BTreeMap<i64, f64>,
+}
+
+/// Hourly prices in a `Publication_MarketDocument`; an acknowledgement (no
+/// data for the period) has none.
+///
+/// Positions left out of a period repeat the price before them (curve type A03).
+pub fn parse_document(
+    xml: &str,
+    timezone: Option<Tz>,
+) -> Result<BTreeMap<i64, f64>, Box<dyn Error>> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+    let mut path: Vec<String> = Vec::new();
+    let mut period: Option<Period> = None;
+    let mut position = None;
+    // Sums and counts per UTC hour, for sub-hourly resolutions
+    let mut sums: BTreeMap<i64, (f64, usize)> = BTreeMap::new();
+    loop {
+        match reader.read_event()? {
+            Event::Start(tag) => {
+                let name = String::from_utf8_lossy(tag.local_name().as_ref()).into_owned();
+                if name == "Period" {
+                    period = Some(Period::default());
+                }
+                path.push(name);
+            }
+            Event::End(_) => {
+                if path.pop().as_deref() == Some("Period")
+                    && let Some(period) = period.take()
+                {
+                    add_period(&period, &mut sums)?;
+                }
+            }
+            Event::Text(text) => {
+                let (Some(period), Some(name)) = (period.as_mut(), path.last()) else {
+                    continue;
+                };
+                let text = text.decode()?;
+                let parent = path.len().checked_sub(2).map(|i| path[i].as_str());
+                match (parent, name.as_str()) {
+                    (Some("timeInterval"), "start") => period.start = Some(parse_utc(&text)?),
+                    (Some("timeInterval"), "end") => period.end = Some(parse_utc(&text)?),
+                    (_, "resolution") => period.resolution_minutes = parse_resolution(&text)?,
+                    (Some("Point"), "position") => position = Some(text.trim().parse::<i64>()?),
+                    (Some("Point"), "price.amount") => {
+                        let position = position.take().ok_or("price.amount before its position")?;
+                        period.points.insert(position, text.trim().parse()?);
+                    }
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    let mut hours = BTreeMap::new();
+    for (ts, (sum, n)) in sums {
+        // The repeated hour when clocks go back keeps its first price
+        hours
+            .entry(utc_to_local(ts, timezone))
+            .or_insert(sum / n as f64);
+    }
+    Ok(hours)
+}
+
+fn add_period(
+    period: &Period,
+    sums: &mut BTreeMap<i64, (f64, usize)>,
+) -> Result<(), Box<dyn Error>> {
+    let (Some(start), Some(end)) = (period.start, period.end) else {
+        return Err("price period without a timeInterval".into());
+    };
+    if period.resolution_minutes <= 0 {
+        return Err("price period without a resolution".into());
+    }
+    let step = period.resolution_minutes * 60;
+    let mut price = None;
+    for i in 0..(end - start) / step {
+        price = period.points.get(&(i + 1)).copied().or(price);
+        if let Some(price) = price {
+            let sum = sums.entry(floor_hour(start + i * step)).or_default();
+            sum.0 += price;
+            sum.1 += 1;
+        }
+    }
+    Ok(())
+}
+
+/// `2024-01-01T23:00Z`, as the documents write their intervals.
+fn parse_utc(text: &str) -> Result<i64, Box<dyn Error>> {
+    let time = NaiveDateTime::parse_from_str(text.trim(), "%Y-%m-%dT%H:%MZ")
+        .map_err(|e| format!("Invalid price interval {:?}: {}", text, e))?;
+    Ok(time.and_utc().timestamp())
+}
+
+/// `PT60M`, `PT15M` or `PT1H`, in minutes.
+fn parse_resolution(text: &str) -> Result<i64, Box<dyn Error>> {
+    let text = text.trim();
+    let minutes = text
+        .strip_prefix("PT")
+        .and_then(|rest| match rest.strip_suffix('M') {
+            Some(minutes) => minutes.parse().ok(),
+            None => rest
+                .strip_suffix('H')
+                .and_then(|hours| hours.parse::<i64>().ok())
+                .map(|hours| hours * 60),
+        })
+        .ok_or_else(|| format!("Unsupported price resolution {:?}", text))?;
+    Ok(minutes)
+}
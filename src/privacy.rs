@@ -0,0 +1,148 @@
+//! Personal data in session exports: user ids, RFID tags, card numbers.
+//!
+//! Forecasting needs none of it, and reading an export keeps none of it: a
+//! session is a start, a stop and an energy. `[data.personal]` names the
+//! export's personal columns so nothing else passes them on either. Loading
+//! fails when a session field would be read from one of them (its values
+//! would end up in skipped-row reports and run summaries), `inspect` doesn't
+//! show them, and `ingest` copies the export for the forecasting store with
+//! each of them emptied or pseudonymized.
+//!
+//! Pseudonyms are HMAC-SHA256 under `data.personal.key`, the first 128 bits
+//! in hex: the same tag gets the same pseudonym in every export hashed with
+//! the key, and without the key tags can't be recovered by hashing guesses.
+
+use std::error::Error;
+use std::io::{Read, Write};
+
+use csv::{ByteRecord, ReaderBuilder, WriterBuilder};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::config::{ColumnConfig, PersonalAction, PersonalDataConfig};
+use crate::data::CsvFormat;
+use crate::secret;
+
+/// Fail when `columns` reads a session field from one of the `personal` columns.
+pub fn check_columns(personal: &[usize], columns: &ColumnConfig) -> Result<(), Box<dyn Error>> {
+    let fields = [
+        ("start", Some(columns.start)),
+        ("stop", Some(columns.stop)),
+        ("energy", Some(columns.energy)),
+        ("site", columns.site),
+    ];
+    match fields
+        .iter()
+        .find(|(_, column)| column.is_some_and(|column| personal.contains(&column)))
+    {
+        Some((name, Some(column))) => Err(format!(
+            "data.columns.{} reads column {}, which data.personal.columns lists as personal",
+            name, column
+        )
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// Replaces the personal fields of export rows.
+pub struct Pseudonymizer {
+    columns: Vec<usize>,
+    /// The resolved key, for the `hash` action.
+    key: Option<Vec<u8>>,
+}
+
+impl Pseudonymizer {
+    /// Fails on `hash` without a key, as unkeyed hashes of short ids are easily reversed.
+    pub fn new(config: &PersonalDataConfig) -> Result<Self, Box<dyn Error>> {
+        let key = match config.action {
+            PersonalAction::Drop => None,
+            PersonalAction::Hash => {
+                let key = config
+                    .key
+                    .as_deref()
+                    .ok_or("data.personal.action = \"hash\" needs data.personal.key")?;
+                let key = secret::resolve(key)?;
+                if key.is_empty() {
+                    return Err("data.personal.key is empty".into());
+                }
+                Some(key.into_bytes())
+            }
+        };
+        Ok(Self {
+            columns: config.columns.clone(),
+            key,
+        })
+    }
+
+    /// What's kept of a personal `value`: nothing, or its pseudonym. Empty values stay empty.
+    pub fn pseudonym(&self, value: &[u8]) -> String {
+        let value = value.trim_ascii();
+        match &self.key {
+            Some(key) if !value.is_empty() => {
+                let mut mac =
+                    Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+                mac.update(value);
+                mac.finalize().into_bytes()[..16]
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect()
+            }
+            _ => String::new(),
+        }
+    }
+
+    /// `record` with its personal fields replaced.
+    pub fn record(&self, record: &ByteRecord) -> ByteRecord {
+        record
+            .iter()
+            .enumerate()
+            .map(|(i, field)| match self.columns.contains(&i) {
+                true => self.pseudonym(field).into_bytes(),
+                false => field.to_vec(),
+            })
+            .collect()
+    }
+
+    /// Copy the export in `reader` to `writer` in the same `format`, with the
+    /// personal fields replaced; a header, a first row whose start field isn't
+    /// a time and whose energy field isn't a number, is copied as it is. A
+    /// garbled first row is still a row, and pseudonymized like any other.
+    /// Returns the data rows copied.
+    pub fn copy(
+        &self,
+        reader: impl Read,
+        writer: impl Write,
+        format: &CsvFormat,
+    ) -> Result<usize, Box<dyn Error>> {
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(false)
+            .delimiter(format.delimiter)
+            .flexible(true)
+            .from_reader(reader);
+        let mut wtr = WriterBuilder::new()
+            .delimiter(format.delimiter)
+            .flexible(true)
+            .from_writer(writer);
+        let mut record = ByteRecord::new();
+        let (mut first, mut rows) = (true, 0);
+        while rdr.read_byte_record(&mut record)? {
+            let header = first
+                && record
+                    .get(format.start)
+                    .is_none_or(|field| format.timestamp(field).is_none())
+                && record
+                    .get(format.energy)
+                    .is_none_or(|field| format.number(field).is_none());
+            first = false;
+            match header {
+                true => wtr.write_byte_record(&record)?,
+                false => {
+                    wtr.write_byte_record(&self.record(&record))?;
+                    rows += 1;
+                }
+            }
+        }
+        wtr.flush()?;
+        Ok(rows)
+    }
+}
@@ -0,0 +1,92 @@
+//! Seasonal profiles: a site's average daily and weekly demand shapes.
+//!
+//! Each curve is the mean load of its hour of the day or of the week over the
+//! checked hourly history, divided by the overall mean, so it averages 1 and
+//! sites of any size compare. Siting and planning models that only need the
+//! shape of demand take these instead of a forecast.
+
+use chrono::{DateTime, Datelike, Timelike};
+use csv::Writer;
+use std::error::Error;
+
+use crate::data::Series;
+use crate::export;
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profiles {
+    pub site: String,
+    /// Relative load of each hour of the day, 0 = 00:00.
+    pub daily: Vec<f64>,
+    /// Relative load of each hour of the week, 0 = Monday 00:00.
+    pub weekly: Vec<f64>,
+}
+
+impl Profiles {
+    /// The profiles of an hourly load history in local time.
+    ///
+    /// Every hour of the week has to appear at least once, and the site has
+    /// to have had some demand.
+    pub fn new(site: &str, history: &Series) -> Result<Self, Box<dyn Error>> {
+        let mut sum = vec![0.0; 7 * 24];
+        let mut count = vec![0usize; 7 * 24];
+        for (ts, value) in history.timestamps.iter().zip(&history.values) {
+            let dt = DateTime::from_timestamp(*ts, 0).unwrap_or_default();
+            let slot = dt.weekday().num_days_from_monday() as usize * 24 + dt.hour() as usize;
+            sum[slot] += value;
+            count[slot] += 1;
+        }
+        if let Some(slot) = count.iter().position(|n| *n == 0) {
+            return Err(format!(
+                "Site {} has no history on {} {:02}:00; profiles need at least a full week",
+                site,
+                WEEKDAYS[slot / 24],
+                slot % 24
+            )
+            .into());
+        }
+        let weekly: Vec<f64> = sum.iter().zip(&count).map(|(s, n)| s / *n as f64).collect();
+        let daily: Vec<f64> = (0..24)
+            .map(|hour| (0..7).map(|day| weekly[day * 24 + hour]).sum::<f64>() / 7.0)
+            .collect();
+        let mean = weekly.iter().sum::<f64>() / weekly.len() as f64;
+        if mean <= 0.0 {
+            return Err(format!("Site {} has no demand to take profiles from", site).into());
+        }
+        Ok(Self {
+            site: site.to_string(),
+            daily: daily.iter().map(|v| v / mean).collect(),
+            weekly: weekly.iter().map(|v| v / mean).collect(),
+        })
+    }
+}
+
+/// Write profiles as CSV: `site,profile,weekday,hour,value`, the `daily`
+/// rows with an empty weekday.
+pub fn write_csv(profiles: &[Profiles], path: &str) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_writer(export::create(path)?);
+    wtr.write_record(["site", "profile", "weekday", "hour", "value"])?;
+    for site in profiles {
+        for (hour, value) in site.daily.iter().enumerate() {
+            wtr.write_record([
+                site.site.as_str(),
+                "daily",
+                "",
+                &hour.to_string(),
+                &format!("{:.4}", value),
+            ])?;
+        }
+        for (slot, value) in site.weekly.iter().enumerate() {
+            wtr.write_record([
+                site.site.as_str(),
+                "weekly",
+                WEEKDAYS[slot / 24],
+                &(slot % 24).to_string(),
+                &format!("{:.4}", value),
+            ])?;
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
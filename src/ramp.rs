@@ -0,0 +1,167 @@
+//! Ramp forecasts: the change in load from one step to the next (ΔkW).
+//!
+//! Differencing consecutive point forecasts says little about how steep a
+//! ramp can get: each point is a smoothed expectation, and interval bounds
+//! don't difference. With `[ramp]`, the differenced history is forecast as a
+//! series of its own, so each step's ramp gets an interval, checked against
+//! the ramp limit of the connection agreement.
+//!
+//! The differences are fitted with Prophet around zero: flat growth, additive
+//! seasonality, no transform or lags, and daily terms of at least
+//! [`DAILY_FOURIER_ORDER`], as a ramp lasts an hour or two where a level
+//! lasts all afternoon. Summed up, a ramp forecast isn't the
+//! level forecast, so the level forecast's own differences are written next
+//! to it.
+
+use csv::Writer;
+use std::error::Error;
+
+use crate::config::{Growth, Method, ModelConfig, RampConfig, SeasonalityMode, Transform};
+use crate::data::{Series, format_timestamp};
+use crate::export;
+use crate::forecast::{self, Forecast};
+
+/// The change from each point of `series` to the next, at the later point.
+pub fn differences(series: &Series) -> Series {
+    Series::new(
+        series.timestamps.iter().skip(1).copied().collect(),
+        series.values.windows(2).map(|w| w[1] - w[0]).collect(),
+    )
+}
+
+/// Fewest daily Fourier terms of a ramp model: enough for one-hour peaks.
+pub const DAILY_FOURIER_ORDER: u32 = 12;
+
+/// `model` as it's fitted on differences.
+pub fn model_config(model: &ModelConfig) -> ModelConfig {
+    ModelConfig {
+        daily_fourier_order: Some(
+            model
+                .daily_fourier_order
+                .unwrap_or_default()
+                .max(DAILY_FOURIER_ORDER),
+        ),
+        method: Method::Prophet,
+        fallbacks: Vec::new(),
+        transform: Transform::None,
+        growth: Growth::Flat,
+        seasonality_mode: SeasonalityMode::Additive,
+        lags: Vec::new(),
+        rolling_means: Vec::new(),
+        ..model.clone()
+    }
+}
+
+/// A ramp forecast over the level forecast's steps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RampForecast {
+    /// Change from the step before, in kW.
+    pub ramp: Forecast,
+    /// The level forecast's point minus the point before (the last observation first).
+    pub level_ramp: Vec<f64>,
+}
+
+impl RampForecast {
+    /// Fit the differences of `history` and forecast them at the steps of `level`.
+    pub fn new(
+        history: &Series,
+        level: &Forecast,
+        model: &ModelConfig,
+    ) -> Result<Self, Box<dyn Error>> {
+        let last = *history.values.last().ok_or("Empty history")?;
+        let fitted = forecast::fit(&differences(history), &model_config(model))
+            .map_err(|e| format!("Could not fit the ramp: {}", e))?;
+        // The components give the forecast before it's kept at or above zero
+        let ramp = fitted.attribute(level.timestamps.clone())?.fitted;
+        let level_ramp = std::iter::once(last)
+            .chain(level.point.iter().copied())
+            .collect::<Vec<f64>>()
+            .windows(2)
+            .map(|w| w[1] - w[0])
+            .collect();
+        Ok(Self { ramp, level_ramp })
+    }
+
+    /// Steps whose ramp interval reaches beyond `±max_ramp_kw`, with the bound that does.
+    pub fn violations(&self, max_ramp_kw: f64) -> Vec<(i64, f64)> {
+        let ramp = &self.ramp;
+        (0..ramp.len())
+            .filter_map(|i| match (ramp.lower[i], ramp.upper[i]) {
+                (_, upper) if upper > max_ramp_kw => Some((ramp.timestamps[i], upper)),
+                (lower, _) if lower < -max_ramp_kw => Some((ramp.timestamps[i], lower)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The steepest forecast ramps up and down, next to the level forecast's.
+    pub fn summary(&self) -> String {
+        let ramp = &self.ramp;
+        let (mut up, mut down) = (0, 0);
+        for i in 0..ramp.len() {
+            if ramp.point[i] > ramp.point[up] {
+                up = i;
+            }
+            if ramp.point[i] < ramp.point[down] {
+                down = i;
+            }
+        }
+        let level = |pick: fn(f64, f64) -> f64| {
+            self.level_ramp
+                .iter()
+                .copied()
+                .reduce(pick)
+                .unwrap_or_default()
+        };
+        match ramp.len() {
+            0 => "Ramp: nothing forecast".to_string(),
+            _ => format!(
+                "Ramp: up to {:+.1} kW at {}, down to {:+.1} kW at {}; the level forecast ramps {:+.1} .. {:+.1} kW",
+                ramp.point[up],
+                format_timestamp(ramp.timestamps[up]),
+                ramp.point[down],
+                format_timestamp(ramp.timestamps[down]),
+                level(f64::min),
+                level(f64::max),
+            ),
+        }
+    }
+
+    /// A validation warning when any step may ramp beyond `ramp.max_ramp_kw`.
+    pub fn warning(&self, config: &RampConfig) -> Option<String> {
+        let max_ramp_kw = config.max_ramp_kw?;
+        let violations = self.violations(max_ramp_kw);
+        let (first, kw) = violations.first()?;
+        Some(format!(
+            "{} steps may ramp beyond ±{:.1} kW, the first at {} ({:+.1} kW)",
+            violations.len(),
+            max_ramp_kw,
+            format_timestamp(*first),
+            kw
+        ))
+    }
+
+    /// Write as CSV: `timestamp,ramp_kw,ramp_lower_kw,ramp_upper_kw,level_ramp_kw`.
+    pub fn write_csv(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let mut wtr = Writer::from_writer(export::create(path)?);
+        wtr.write_record([
+            "timestamp",
+            "ramp_kw",
+            "ramp_lower_kw",
+            "ramp_upper_kw",
+            "level_ramp_kw",
+        ])?;
+        let ramp = &self.ramp;
+        for i in 0..ramp.len() {
+            wtr.write_record([
+                format_timestamp(ramp.timestamps[i]),
+                format!("{:.2}", ramp.point[i]),
+                format!("{:.2}", ramp.lower[i]),
+                format!("{:.2}", ramp.upper[i]),
+                format!("{:.2}", self.level_ramp[i]),
+            ])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
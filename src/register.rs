@@ -0,0 +1,167 @@
+//! Cumulative energy registers as interval energy.
+//!
+//! Many chargers only report a lifetime energy counter. Consecutive readings
+//! are differenced into the energy of the interval between them, spread evenly
+//! over that interval's steps like a session's energy (see
+//! [`crate::preprocess::load`]), which gives a load in kW.
+//!
+//! A counter that goes down has either rolled over at `data.register.rollover`
+//! or been replaced: it rolled over if the energy the wrap-around implies is
+//! within `data.register.max_kw`, and was replaced otherwise, as it was if it
+//! jumps up faster than that. The energy across a replacement is unknown and
+//! left out, like that across gaps longer than `max_gap_minutes`; counting
+//! carries on from the new meter's first reading.
+//!
+//! A site whose chargers each keep a counter reports them apart, with a
+//! charger id (see [`Reading`]): each counter is differenced on its own and
+//! the intervals summed, as interleaved readings of several counters have
+//! no meaningful difference.
+
+use std::collections::BTreeMap;
+
+use crate::config::{EnergyUnit, RegisterConfig};
+use crate::data::Series;
+use crate::preprocess::{HOUR, floor_step, overlap_seconds};
+
+/// Energy metered between two readings of one charger's counter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interval {
+    /// The charger, as in its [`Reading`]s.
+    pub charger: String,
+    pub from: i64,
+    pub to: i64,
+    pub energy_kwh: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Conversion {
+    pub intervals: Vec<Interval>,
+    /// Times of the readings after the counter wrapped around.
+    pub rollovers: Vec<i64>,
+    /// Times of the first readings of a replaced meter.
+    pub replacements: Vec<i64>,
+    /// Intervals left out for being longer than `max_gap_minutes`.
+    pub gaps: usize,
+}
+
+/// One reading of a charger's energy counter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reading {
+    /// The charger whose counter this is; empty for a site's single counter.
+    pub charger: String,
+    pub timestamp: i64,
+    pub value: f64,
+}
+
+/// Difference each charger's readings into intervals, in any order.
+///
+/// Every counter is differenced on its own, rolling over or being replaced
+/// apart from the others; the site's load is their sum (see [`load`]). Of a
+/// charger's readings at the same time the last one is kept.
+pub fn intervals(readings: &[Reading], config: &RegisterConfig) -> Conversion {
+    let mut counters: BTreeMap<&str, Vec<(i64, f64)>> = BTreeMap::new();
+    for reading in readings {
+        counters
+            .entry(&reading.charger)
+            .or_default()
+            .push((reading.timestamp, reading.value));
+    }
+    let mut conversion = Conversion::default();
+    for (charger, counter) in counters.iter_mut() {
+        let start = conversion.intervals.len();
+        counter.sort_by_key(|(ts, _)| *ts);
+        counter.reverse();
+        counter.dedup_by_key(|(ts, _)| *ts);
+        counter.reverse();
+        difference(counter, config, &mut conversion);
+        for interval in &mut conversion.intervals[start..] {
+            interval.charger = charger.to_string();
+        }
+    }
+    conversion.rollovers.sort_unstable();
+    conversion.replacements.sort_unstable();
+    conversion
+}
+
+/// Difference one counter's sorted `(timestamp, reading)` pairs into `conversion`.
+fn difference(readings: &[(i64, f64)], config: &RegisterConfig, conversion: &mut Conversion) {
+    let scale = match config.unit {
+        EnergyUnit::Wh => 0.001,
+        EnergyUnit::Kwh => 1.0,
+    };
+    for pair in readings.windows(2) {
+        let ((from, start), (to, end)) = (pair[0], pair[1]);
+        if to - from > config.max_gap_minutes as i64 * 60 {
+            conversion.gaps += 1;
+            continue;
+        }
+        let limit = config.max_kw * (to - from) as f64 / HOUR as f64;
+        let energy_kwh = match (end - start) * scale {
+            delta if delta >= 0.0 && delta <= limit => delta,
+            delta if delta >= 0.0 => {
+                conversion.replacements.push(to);
+                continue;
+            }
+            _ => match config.rollover.map(|wrap| (wrap - start + end) * scale) {
+                Some(wrapped) if (0.0..=limit).contains(&wrapped) => {
+                    conversion.rollovers.push(to);
+                    wrapped
+                }
+                _ => {
+                    conversion.replacements.push(to);
+                    continue;
+                }
+            },
+        };
+        conversion.intervals.push(Interval {
+            charger: String::new(),
+            from,
+            to,
+            energy_kwh,
+        });
+    }
+}
+
+/// Spread the intervals' energy onto a grid of `step` seconds, as kW.
+///
+/// A step's value is, summed over the chargers, each one's energy over the
+/// time its intervals cover of the step; steps none covers are left out, for
+/// resampling to fill.
+pub fn load(intervals: &[Interval], step: i64) -> Series {
+    let (Some(first), Some(last)) = (
+        intervals.iter().map(|i| i.from).min(),
+        intervals.iter().map(|i| i.to).max(),
+    ) else {
+        return Series::default();
+    };
+    let origin = floor_step(first, step);
+    let n_steps = ((floor_step(last, step) - origin) / step + 1) as usize;
+    // Each charger's energy and the seconds its counter covers, per step
+    let mut chargers: BTreeMap<&str, (Vec<f64>, Vec<i64>)> = BTreeMap::new();
+    for interval in intervals {
+        let (energy, covered) = chargers
+            .entry(&interval.charger)
+            .or_insert_with(|| (vec![0.0; n_steps], vec![0; n_steps]));
+        let duration = (interval.to - interval.from) as f64;
+        let mut slot = floor_step(interval.from, step);
+        while slot < interval.to {
+            let i = ((slot - origin) / step) as usize;
+            let overlap = overlap_seconds(interval.from, interval.to, slot, slot + step);
+            energy[i] += interval.energy_kwh * overlap as f64 / duration;
+            covered[i] += overlap;
+            slot += step;
+        }
+    }
+    let (timestamps, values): (Vec<_>, Vec<_>) = (0..n_steps)
+        .filter(|i| chargers.values().any(|(_, covered)| covered[*i] > 0))
+        .map(|i| {
+            let kw: f64 = chargers
+                .values()
+                .filter(|(_, covered)| covered[i] > 0)
+                .map(|(energy, covered)| energy[i] * HOUR as f64 / covered[i] as f64)
+                .sum();
+            (origin + i as i64 * step, kw)
+        })
+        .unzip();
+    Series::new(timestamps, values)
+}
@@ -0,0 +1,141 @@
+//! Object-store URIs (`s3://`, `gs://`, `az://`, ...) as inputs and outputs.
+//!
+//! The readers and writers themselves only know local paths: a remote input
+//! is downloaded to a temporary file first, and a remote output is written
+//! to one and then uploaded. Credentials and regions come from the usual
+//! environment variables (`AWS_ACCESS_KEY_ID`, `GOOGLE_SERVICE_ACCOUNT`,
+//! `AZURE_STORAGE_ACCOUNT_NAME`, ...).
+
+use std::error::Error;
+use std::path::PathBuf;
+
+/// URI schemes handed to the object store rather than the file system.
+const SCHEMES: [&str; 9] = [
+    "s3", "s3a", "gs", "az", "azure", "abfs", "abfss", "adl", "file",
+];
+
+/// Whether `path` is an object-store URI rather than a local path.
+pub fn is_remote(path: &str) -> bool {
+    path.split_once("://")
+        .is_some_and(|(scheme, _)| SCHEMES.iter().any(|s| scheme.eq_ignore_ascii_case(s)))
+}
+
+/// Call `read` with `path`, or with a downloaded copy of it when remote.
+pub fn with_input<T>(
+    path: &str,
+    read: impl FnOnce(&str) -> Result<T, Box<dyn Error>>,
+) -> Result<T, Box<dyn Error>> {
+    if !is_remote(path) {
+        return read(path);
+    }
+    let bytes = get(path)?;
+    let local = temp_file(path)?;
+    let result = std::fs::write(&local, bytes)
+        .map_err(Into::into)
+        .and_then(|()| read(&local.display().to_string()));
+    std::fs::remove_file(&local).ok();
+    result
+}
+
+/// Call `write` with `path`, or with a temporary file uploaded to it when remote.
+pub fn with_output(
+    path: &str,
+    write: impl FnOnce(&str) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    if !is_remote(path) {
+        return write(path);
+    }
+    let local = temp_file(path)?;
+    let result =
+        write(&local.display().to_string()).and_then(|()| put(path, std::fs::read(&local)?));
+    std::fs::remove_file(&local).ok();
+    result?;
+    eprintln!("Uploaded {}", path);
+    Ok(())
+}
+
+/// Whether `path` exists, asking the object store when remote.
+pub fn exists(path: &str) -> Result<bool, Box<dyn Error>> {
+    match is_remote(path) {
+        true => head(path),
+        false => Ok(std::path::Path::new(path).exists()),
+    }
+}
+
+/// A new, empty temporary file readable by its owner only, under a random
+/// name ending like `url`'s object so extensions still pick formats.
+pub fn temp_file(url: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let name = url.rsplit('/').next().unwrap_or_default();
+    loop {
+        let path =
+            std::env::temp_dir().join(format!("cpo-{:016x}-{}", rand::random::<u64>(), name));
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&path) {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("{}: {}", path.display(), e).into()),
+        }
+    }
+}
+
+#[cfg(feature = "cloud")]
+fn store(
+    url: &str,
+) -> Result<(Box<dyn object_store::ObjectStore>, object_store::path::Path), Box<dyn Error>> {
+    let parsed = url::Url::parse(url).map_err(|e| format!("Invalid URI {}: {}", url, e))?;
+    let options = std::env::vars().map(|(key, value)| (key.to_ascii_lowercase(), value));
+    Ok(object_store::parse_url_opts(&parsed, options).map_err(|e| format!("{}: {}", url, e))?)
+}
+
+#[cfg(feature = "cloud")]
+fn block_on<T>(future: impl Future<Output = T>) -> Result<T, Box<dyn Error>> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    Ok(runtime.block_on(future))
+}
+
+#[cfg(feature = "cloud")]
+fn get(url: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (store, path) = store(url)?;
+    let bytes = block_on(async { store.get(&path).await?.bytes().await })?;
+    Ok(bytes
+        .map_err(|e| format!("Could not download {}: {}", url, e))?
+        .to_vec())
+}
+
+#[cfg(feature = "cloud")]
+fn put(url: &str, contents: Vec<u8>) -> Result<(), Box<dyn Error>> {
+    let (store, path) = store(url)?;
+    block_on(store.put(&path, contents.into()))?
+        .map_err(|e| format!("Could not upload {}: {}", url, e))?;
+    Ok(())
+}
+
+#[cfg(feature = "cloud")]
+fn head(url: &str) -> Result<bool, Box<dyn Error>> {
+    let (store, path) = store(url)?;
+    match block_on(store.head(&path))? {
+        Ok(_) => Ok(true),
+        Err(object_store::Error::NotFound { .. }) => Ok(false),
+        Err(e) => Err(format!("Could not look up {}: {}", url, e).into()),
+    }
+}
+
+#[cfg(not(feature = "cloud"))]
+fn head(url: &str) -> Result<bool, Box<dyn Error>> {
+    Err(format!("Cannot look up {}: built without the `cloud` feature", url).into())
+}
+
+#[cfg(not(feature = "cloud"))]
+fn get(url: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    Err(format!("Cannot read {}: built without the `cloud` feature", url).into())
+}
+
+#[cfg(not(feature = "cloud"))]
+fn put(url: &str, _: Vec<u8>) -> Result<(), Box<dyn Error>> {
+    Err(format!("Cannot write {}: built without the `cloud` feature", url).into())
+}
@@ -0,0 +1,297 @@
+//! Standalone per-site reports (HTML or Markdown) for weekly distribution.
+
+use chrono::{DateTime, Utc};
+use std::error::Error;
+use std::fmt::Write;
+
+use crate::anomaly::Anomaly;
+use crate::backtest::{BacktestReport, summary_line};
+use crate::config::Config;
+use crate::data::{Series, format_timestamp};
+use crate::forecast::Forecast;
+use crate::plot;
+use crate::preprocess::HOUR;
+use crate::secret;
+
+/// Peak and energy of one forecast day.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DailyPeak {
+    pub date: String,
+    pub peak_kw: f64,
+    pub peak_at: String,
+    /// Upper bound at the peak hour.
+    pub upper_kw: f64,
+    pub energy_kwh: f64,
+}
+
+/// Group a forecast by calendar day.
+pub fn daily_peaks(forecast: &Forecast) -> Vec<DailyPeak> {
+    let hours = forecast.step_hours();
+    let mut peaks: Vec<(i64, DailyPeak)> = Vec::new();
+    for i in 0..forecast.len() {
+        let ts = forecast.timestamps[i];
+        let day = ts.div_euclid(24 * HOUR);
+        let hour = DateTime::from_timestamp(ts, 0).unwrap_or_default();
+        match peaks.last_mut() {
+            Some((d, peak)) if *d == day => {
+                peak.energy_kwh += forecast.point[i] * hours;
+                if forecast.point[i] > peak.peak_kw {
+                    peak.peak_kw = forecast.point[i];
+                    peak.peak_at = hour.format("%H:%M").to_string();
+                    peak.upper_kw = forecast.upper[i];
+                }
+            }
+            _ => peaks.push((
+                day,
+                DailyPeak {
+                    date: hour.format("%a %Y-%m-%d").to_string(),
+                    peak_kw: forecast.point[i],
+                    peak_at: hour.format("%H:%M").to_string(),
+                    upper_kw: forecast.upper[i],
+                    energy_kwh: forecast.point[i] * hours,
+                },
+            )),
+        }
+    }
+    peaks.into_iter().map(|(_, p)| p).collect()
+}
+
+/// The `[model]`, `[forecast]` and `[data.preprocess]` settings of `config`, as
+/// TOML for a report.
+///
+/// Reports are shared beyond the people who hold the config, so connections,
+/// sinks and the server are left out, and the secrets of what is shown
+/// (a price feed token) are redacted.
+pub fn config_toml(config: &Config) -> Result<String, Box<dyn Error>> {
+    let config = secret::redact(config);
+    let mut data = toml::Table::new();
+    data.insert(
+        "preprocess".to_string(),
+        toml::Value::try_from(&config.data.preprocess)?,
+    );
+    let mut view = toml::Table::new();
+    view.insert("model".to_string(), toml::Value::try_from(&config.model)?);
+    view.insert(
+        "forecast".to_string(),
+        toml::Value::try_from(&config.forecast)?,
+    );
+    view.insert("data".to_string(), toml::Value::Table(data));
+    Ok(toml::to_string_pretty(&view)?)
+}
+
+/// Everything that goes into a report.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub site: String,
+    pub generated_at: String,
+    /// Recent history shown in the chart.
+    pub history: Series,
+    pub forecast: Forecast,
+    pub backtest: Option<BacktestReport>,
+    pub anomalies: Vec<Anomaly>,
+    /// The settings behind the forecast, as TOML (see [`config_toml`]).
+    pub config: String,
+    /// Most points per chart line (see `output.plot_max_points`).
+    pub plot_max_points: usize,
+}
+
+impl Report {
+    pub fn new(
+        site: &str,
+        history: Series,
+        forecast: Forecast,
+        backtest: Option<BacktestReport>,
+        anomalies: Vec<Anomaly>,
+        config: String,
+    ) -> Self {
+        Self {
+            site: site.to_string(),
+            generated_at: Utc::now().format("%Y-%m-%d %H:%M UTC").to_string(),
+            history,
+            forecast,
+            backtest,
+            anomalies,
+            config,
+            plot_max_points: plot::MAX_POINTS,
+        }
+    }
+
+    fn accuracy_lines(&self) -> Vec<String> {
+        match &self.backtest {
+            None => {
+                vec!["No backtest found — run `cpo-charging-forecast backtest` first.".to_string()]
+            }
+            Some(b) => {
+                let mut lines = vec![format!(
+                    "Backtest of {} ({} folds × {} h, run {}): {}",
+                    b.model_version,
+                    b.folds.len(),
+                    b.holdout_hours,
+                    b.run_at,
+                    summary_line(&b.overall)
+                )];
+                lines.extend(
+                    b.folds
+                        .iter()
+                        .map(|f| format!("From {}: {}", f.origin, summary_line(&f.metrics))),
+                );
+                lines
+            }
+        }
+    }
+
+    /// Render as a single HTML file with the chart inlined as SVG.
+    pub fn render_html(&self) -> Result<String, Box<dyn Error>> {
+        let svg = plot::forecast_svg(
+            &self.history,
+            &self.forecast,
+            plot::CAPTION,
+            (900, 500),
+            self.plot_max_points,
+        )?;
+        let mut html = String::new();
+        writeln!(
+            html,
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">"
+        )?;
+        writeln!(
+            html,
+            "<title>Forecast report — {}</title>",
+            escape(&self.site)
+        )?;
+        writeln!(
+            html,
+            "<style>body{{font-family:sans-serif;max-width:960px;margin:auto}}\
+             table{{border-collapse:collapse}}td,th{{border:1px solid #ccc;padding:4px 8px;text-align:right}}\
+             pre{{background:#f6f6f6;padding:8px}}</style></head><body>"
+        )?;
+        writeln!(html, "<h1>Forecast report — {}</h1>", escape(&self.site))?;
+        writeln!(html, "<p>Generated {}</p>", self.generated_at)?;
+        writeln!(html, "<h2>Forecast</h2>\n{}", svg)?;
+
+        writeln!(html, "<h2>Daily peaks</h2>\n<table>")?;
+        writeln!(
+            html,
+            "<tr><th>Day</th><th>Peak kW</th><th>At</th><th>Upper kW</th><th>Energy kWh</th></tr>"
+        )?;
+        for p in daily_peaks(&self.forecast) {
+            writeln!(
+                html,
+                "<tr><td>{}</td><td>{:.1}</td><td>{}</td><td>{:.1}</td><td>{:.0}</td></tr>",
+                p.date, p.peak_kw, p.peak_at, p.upper_kw, p.energy_kwh
+            )?;
+        }
+        writeln!(html, "</table>")?;
+
+        writeln!(html, "<h2>Accuracy</h2>\n<ul>")?;
+        for line in self.accuracy_lines() {
+            writeln!(html, "<li>{}</li>", escape(&line))?;
+        }
+        writeln!(html, "</ul>")?;
+
+        writeln!(html, "<h2>Anomalies</h2>")?;
+        if self.anomalies.is_empty() {
+            writeln!(html, "<p>No anomalies in recent actuals.</p>")?;
+        } else {
+            writeln!(
+                html,
+                "<table>\n<tr><th>Hour</th><th>Actual kW</th><th>Expected kW</th></tr>"
+            )?;
+            for a in &self.anomalies {
+                writeln!(
+                    html,
+                    "<tr><td>{}</td><td>{:.1}</td><td>{:.1}</td></tr>",
+                    format_timestamp(a.timestamp),
+                    a.actual_kw,
+                    a.expected_kw
+                )?;
+            }
+            writeln!(html, "</table>")?;
+        }
+
+        writeln!(
+            html,
+            "<h2>Configuration</h2>\n<pre>{}</pre>",
+            escape(&self.config)
+        )?;
+        writeln!(html, "</body></html>")?;
+        Ok(html)
+    }
+
+    /// Render as Markdown, referencing the chart image by relative path.
+    pub fn render_markdown(&self, chart: &str) -> Result<String, Box<dyn Error>> {
+        let mut md = String::new();
+        writeln!(md, "# Forecast report — {}\n", self.site)?;
+        writeln!(md, "Generated {}\n", self.generated_at)?;
+        writeln!(md, "## Forecast\n\n![Forecast]({})\n", chart)?;
+
+        writeln!(md, "## Daily peaks\n")?;
+        writeln!(md, "| Day | Peak kW | At | Upper kW | Energy kWh |")?;
+        writeln!(md, "|---|---:|---:|---:|---:|")?;
+        for p in daily_peaks(&self.forecast) {
+            writeln!(
+                md,
+                "| {} | {:.1} | {} | {:.1} | {:.0} |",
+                p.date, p.peak_kw, p.peak_at, p.upper_kw, p.energy_kwh
+            )?;
+        }
+
+        writeln!(md, "\n## Accuracy\n")?;
+        for line in self.accuracy_lines() {
+            writeln!(md, "- {}", line)?;
+        }
+
+        writeln!(md, "\n## Anomalies\n")?;
+        if self.anomalies.is_empty() {
+            writeln!(md, "No anomalies in recent actuals.")?;
+        } else {
+            writeln!(md, "| Hour | Actual kW | Expected kW |\n|---|---:|---:|")?;
+            for a in &self.anomalies {
+                writeln!(
+                    md,
+                    "| {} | {:.1} | {:.1} |",
+                    format_timestamp(a.timestamp),
+                    a.actual_kw,
+                    a.expected_kw
+                )?;
+            }
+        }
+
+        writeln!(md, "\n## Configuration\n\n```toml\n{}```", self.config)?;
+        Ok(md)
+    }
+
+    /// Write the report; a `.md` path gives Markdown plus a PNG chart next to it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        use std::path::Path;
+
+        let is_markdown = Path::new(path)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("md"));
+        if is_markdown {
+            let chart = Path::new(path).with_extension("png");
+            plot::plot_forecast(
+                &self.history,
+                &self.forecast,
+                &chart.display().to_string(),
+                self.plot_max_points,
+            )?;
+            let chart_name = chart
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("report.png");
+            crate::export::write_text(path, &self.render_markdown(chart_name)?)?;
+        } else {
+            crate::export::write_text(path, &self.render_html()?)?;
+        }
+        eprintln!("Report written to {}", path);
+        Ok(())
+    }
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
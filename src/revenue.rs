@@ -0,0 +1,242 @@
+//! Billing revenue forecast: the energy forecast priced per `[pricing]`,
+//! plus idle fees on the forecast overstay.
+//!
+//! Amounts carry the forecast's interval: the lower bound prices the lower
+//! bounds of energy and overstay, the upper bound their upper bounds.
+
+use chrono::{DateTime, Datelike, Timelike, Weekday};
+use csv::Writer;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::config::{Days, PricingConfig, TouRate};
+use crate::data::format_timestamp;
+use crate::export;
+use crate::forecast::Forecast;
+use crate::preprocess::{floor_hour, floor_step};
+
+/// Per kWh at local time `ts`: the first time-of-use rate covering it, else the flat price.
+pub fn price_at(pricing: &PricingConfig, ts: i64) -> f64 {
+    let dt = DateTime::from_timestamp(ts, 0).unwrap_or_default();
+    let weekend = matches!(dt.weekday(), Weekday::Sat | Weekday::Sun);
+    pricing
+        .tou
+        .iter()
+        .find(|rate| covers(rate, dt.hour(), weekend))
+        .map_or(pricing.energy_price, |rate| rate.price)
+}
+
+fn covers(rate: &TouRate, hour: u32, weekend: bool) -> bool {
+    let day = match rate.days {
+        Days::All => true,
+        Days::Weekdays => !weekend,
+        Days::Weekends => weekend,
+    };
+    let hour = match rate.from_hour <= rate.to_hour {
+        true => (rate.from_hour..rate.to_hour).contains(&hour),
+        false => hour >= rate.from_hour || hour < rate.to_hour,
+    };
+    day && hour
+}
+
+/// Revenue over the forecast's steps: hours, or a finer `data.step_minutes` grid.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RevenueForecast {
+    pub timestamps: Vec<i64>,
+    /// kWh, the energy forecast's point over its step.
+    pub energy_kwh: Vec<f64>,
+    pub energy_revenue: Vec<f64>,
+    pub idle_revenue: Vec<f64>,
+    /// Energy and idle revenue.
+    pub revenue: Vec<f64>,
+    pub lower: Vec<f64>,
+    pub upper: Vec<f64>,
+}
+
+impl RevenueForecast {
+    /// Price `energy` (kW) and, when given, bill `overstay` (vehicles
+    /// overstaying) over each step of `energy`, at the overstay point covering
+    /// it; overstay steps missing from the energy forecast are dropped, energy
+    /// steps without overstay pay no idle fees.
+    pub fn new(energy: &Forecast, overstay: Option<&Forecast>, pricing: &PricingConfig) -> Self {
+        let mut revenue = Self::default();
+        let hours = energy.step_hours();
+        for i in 0..energy.len() {
+            let ts = energy.timestamps[i];
+            let price = price_at(pricing, ts) * hours;
+            let idle = overstay
+                .and_then(|o| {
+                    o.timestamps
+                        .binary_search(&floor_step(ts, o.step().max(1)))
+                        .ok()
+                        .map(|j| (o.point[j], o.lower[j], o.upper[j]))
+                })
+                .unwrap_or_default();
+            let fee = pricing.idle_fee_per_hour * hours;
+            revenue.timestamps.push(ts);
+            revenue.energy_kwh.push(energy.point[i] * hours);
+            revenue.energy_revenue.push(energy.point[i] * price);
+            revenue.idle_revenue.push(idle.0 * fee);
+            revenue.revenue.push(energy.point[i] * price + idle.0 * fee);
+            revenue
+                .lower
+                .push(energy.lower[i].max(0.0) * price + idle.1.max(0.0) * fee);
+            revenue.upper.push(energy.upper[i] * price + idle.2 * fee);
+        }
+        revenue
+    }
+
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    pub fn total(&self) -> f64 {
+        self.revenue.iter().sum()
+    }
+
+    /// `timestamp,energy_kwh,energy_revenue,idle_revenue,revenue,revenue_lower,revenue_upper`.
+    pub fn write_csv(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let mut wtr = Writer::from_writer(export::create(path)?);
+        wtr.write_record([
+            "timestamp",
+            "energy_kwh",
+            "energy_revenue",
+            "idle_revenue",
+            "revenue",
+            "revenue_lower",
+            "revenue_upper",
+        ])?;
+        for i in 0..self.len() {
+            let amounts = [
+                self.energy_kwh[i],
+                self.energy_revenue[i],
+                self.idle_revenue[i],
+                self.revenue[i],
+                self.lower[i],
+                self.upper[i],
+            ];
+            let mut record = vec![format_timestamp(self.timestamps[i])];
+            record.extend(amounts.iter().map(|v| format!("{:.2}", v)));
+            wtr.write_record(&record)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+/// One site's revenue in one calendar month of the forecast.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MonthlyRevenue {
+    /// `total` for the sum over all sites.
+    pub site: String,
+    /// `YYYY-MM`.
+    pub month: String,
+    /// Forecast hours in the month; the first and last months are partial.
+    pub hours: usize,
+    pub energy_kwh: f64,
+    pub energy_revenue: f64,
+    pub idle_revenue: f64,
+    pub revenue: f64,
+    pub revenue_lower: f64,
+    pub revenue_upper: f64,
+}
+
+/// `revenue` summed by month.
+pub fn monthly(site: &str, revenue: &RevenueForecast) -> Vec<MonthlyRevenue> {
+    let mut months: BTreeMap<String, MonthlyRevenue> = BTreeMap::new();
+    for i in 0..revenue.len() {
+        let month = format_timestamp(revenue.timestamps[i])[..7].to_string();
+        let row = months
+            .entry(month.clone())
+            .or_insert_with(|| empty(site, month));
+        // Hours started in the month, however many steps each has
+        let hour = floor_hour(revenue.timestamps[i]);
+        if i == 0 || floor_hour(revenue.timestamps[i - 1]) != hour {
+            row.hours += 1;
+        }
+        row.energy_kwh += revenue.energy_kwh[i];
+        row.energy_revenue += revenue.energy_revenue[i];
+        row.idle_revenue += revenue.idle_revenue[i];
+        row.revenue += revenue.revenue[i];
+        row.revenue_lower += revenue.lower[i];
+        row.revenue_upper += revenue.upper[i];
+    }
+    months.into_values().collect()
+}
+
+/// Add a `total` row per month over all sites' rows.
+///
+/// The total's bounds are the sums of the sites' bounds, which is wider than
+/// the interval of the total but is what finance can reconcile.
+pub fn with_totals(rows: Vec<MonthlyRevenue>) -> Vec<MonthlyRevenue> {
+    let mut totals: BTreeMap<String, MonthlyRevenue> = BTreeMap::new();
+    for row in &rows {
+        let total = totals
+            .entry(row.month.clone())
+            .or_insert_with(|| empty("total", row.month.clone()));
+        total.hours = total.hours.max(row.hours);
+        total.energy_kwh += row.energy_kwh;
+        total.energy_revenue += row.energy_revenue;
+        total.idle_revenue += row.idle_revenue;
+        total.revenue += row.revenue;
+        total.revenue_lower += row.revenue_lower;
+        total.revenue_upper += row.revenue_upper;
+    }
+    rows.into_iter().chain(totals.into_values()).collect()
+}
+
+fn empty(site: &str, month: String) -> MonthlyRevenue {
+    MonthlyRevenue {
+        site: site.to_string(),
+        month,
+        hours: 0,
+        energy_kwh: 0.0,
+        energy_revenue: 0.0,
+        idle_revenue: 0.0,
+        revenue: 0.0,
+        revenue_lower: 0.0,
+        revenue_upper: 0.0,
+    }
+}
+
+/// One row per site and month, in `currency`.
+pub fn write_monthly_csv(
+    rows: &[MonthlyRevenue],
+    currency: &str,
+    path: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_writer(export::create(path)?);
+    wtr.write_record([
+        "site",
+        "month",
+        "hours",
+        "energy_kwh",
+        "currency",
+        "energy_revenue",
+        "idle_revenue",
+        "revenue",
+        "revenue_lower",
+        "revenue_upper",
+    ])?;
+    for row in rows {
+        wtr.write_record([
+            row.site.clone(),
+            row.month.clone(),
+            row.hours.to_string(),
+            format!("{:.1}", row.energy_kwh),
+            currency.to_string(),
+            format!("{:.2}", row.energy_revenue),
+            format!("{:.2}", row.idle_revenue),
+            format!("{:.2}", row.revenue),
+            format!("{:.2}", row.revenue_lower),
+            format!("{:.2}", row.revenue_upper),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
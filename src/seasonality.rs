@@ -0,0 +1,242 @@
+//! Seasonality detection: which of Prophet's daily, weekly and yearly terms a
+//! site's history calls for, and with how many Fourier terms.
+//!
+//! Each period is checked on the history with its linear trend removed: the
+//! day on the series at its own step, the week and the year on daily means.
+//! A seasonality is recommended when the history covers two periods and its
+//! autocorrelation one period apart reaches `seasonality.min_autocorrelation`.
+//! Its Fourier order is read off the periodogram of the mean profile over one
+//! period: the fewest harmonics carrying `seasonality.variance_share` of the
+//! profile's variance.
+
+use std::error::Error;
+
+use crate::config::{ModelConfig, SeasonalityConfig};
+use crate::data::Series;
+use crate::preprocess::HOUR;
+
+const DAY: i64 = 24 * HOUR;
+
+/// Most Fourier terms recommended for each profile; a week of daily means has no more than 3.
+const MAX_DAILY_ORDER: usize = 12;
+const MAX_WEEKLY_ORDER: usize = 3;
+const MAX_YEARLY_ORDER: usize = 20;
+
+/// Prophet's Fourier orders for a seasonality a model enables without one.
+const PROPHET_ORDERS: [(&str, u32); 3] = [("daily", 4), ("weekly", 3), ("yearly", 10)];
+
+/// What the history says about one seasonality.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Detected {
+    /// `daily`, `weekly` or `yearly`.
+    pub name: &'static str,
+    /// Periods the history covers.
+    pub periods: f64,
+    /// Autocorrelation one period apart; `None` under two periods of history.
+    pub autocorrelation: Option<f64>,
+    pub enabled: bool,
+    /// Recommended Fourier terms, when enabled.
+    pub fourier_order: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recommendation {
+    pub daily: Detected,
+    pub weekly: Detected,
+    pub yearly: Detected,
+}
+
+impl Recommendation {
+    pub fn all(&self) -> [&Detected; 3] {
+        [&self.daily, &self.weekly, &self.yearly]
+    }
+
+    /// Set the model's seasonalities and Fourier orders to the recommended ones.
+    pub fn apply(&self, model: &mut ModelConfig) {
+        (model.daily_seasonality, model.daily_fourier_order) =
+            (self.daily.enabled, self.daily.fourier_order);
+        (model.weekly_seasonality, model.weekly_fourier_order) =
+            (self.weekly.enabled, self.weekly.fourier_order);
+        (model.yearly_seasonality, model.yearly_fourier_order) =
+            (self.yearly.enabled, self.yearly.fourier_order);
+    }
+
+    /// The seasonalities `model` fits differently: off where on is recommended,
+    /// or with another number of Fourier terms.
+    pub fn differences(&self, model: &ModelConfig) -> Vec<&'static str> {
+        let configured = [
+            (model.daily_seasonality, model.daily_fourier_order),
+            (model.weekly_seasonality, model.weekly_fourier_order),
+            (model.yearly_seasonality, model.yearly_fourier_order),
+        ];
+        let fitted = |(enabled, order): (bool, Option<u32>), default: u32| {
+            enabled.then(|| order.filter(|o| *o > 0).unwrap_or(default))
+        };
+        self.all()
+            .into_iter()
+            .zip(configured)
+            .zip(PROPHET_ORDERS)
+            .filter(|((detected, configured), (_, default))| {
+                fitted(*configured, *default)
+                    != fitted((detected.enabled, detected.fourier_order), *default)
+            })
+            .map(|((detected, _), _)| detected.name)
+            .collect()
+    }
+
+    /// The recommendation as `[model]` keys, to paste into a config.
+    pub fn to_toml(&self) -> String {
+        let mut text = String::from("[model]\n");
+        for detected in self.all() {
+            text.push_str(&format!(
+                "{}_seasonality = {}\n",
+                detected.name, detected.enabled
+            ));
+            if let Some(order) = detected.fourier_order {
+                text.push_str(&format!("{}_fourier_order = {}\n", detected.name, order));
+            }
+        }
+        text
+    }
+}
+
+/// `values` minus their least-squares line.
+fn detrend(values: &[f64]) -> Vec<f64> {
+    let n = values.len() as f64;
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = values.iter().sum::<f64>() / n.max(1.0);
+    let (mut sxy, mut sxx) = (0.0, 0.0);
+    for (i, y) in values.iter().enumerate() {
+        sxy += (i as f64 - mean_x) * (y - mean_y);
+        sxx += (i as f64 - mean_x).powi(2);
+    }
+    let slope = if sxx > 0.0 { sxy / sxx } else { 0.0 };
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, y)| y - mean_y - slope * (i as f64 - mean_x))
+        .collect()
+}
+
+/// Sample autocorrelation of `values` at `lag` points; 0 for a constant series.
+pub fn autocorrelation(values: &[f64], lag: usize) -> f64 {
+    let mean = values.iter().sum::<f64>() / values.len().max(1) as f64;
+    let variance: f64 = values.iter().map(|v| (v - mean).powi(2)).sum();
+    if variance <= 0.0 || lag >= values.len() {
+        return 0.0;
+    }
+    let covariance: f64 = values
+        .iter()
+        .zip(&values[lag..])
+        .map(|(a, b)| (a - mean) * (b - mean))
+        .sum();
+    covariance / variance
+}
+
+/// The fewest harmonics of `profile`'s periodogram carrying `share` of its
+/// variance, at most `max`.
+pub fn fourier_order(profile: &[f64], share: f64, max: usize) -> u32 {
+    let n = profile.len();
+    let power: Vec<f64> = (1..=n / 2)
+        .map(|k| {
+            let (mut re, mut im) = (0.0, 0.0);
+            for (t, v) in profile.iter().enumerate() {
+                let angle = 2.0 * std::f64::consts::PI * (k * t) as f64 / n as f64;
+                re += v * angle.cos();
+                im -= v * angle.sin();
+            }
+            re * re + im * im
+        })
+        .collect();
+    let total: f64 = power.iter().sum();
+    let mut carried = 0.0;
+    for (k, p) in power.iter().enumerate() {
+        carried += p;
+        if carried >= share * total {
+            return (k + 1).min(max).max(1) as u32;
+        }
+    }
+    max.clamp(1, power.len().max(1)) as u32
+}
+
+/// Check one period of `period` points on detrended `values`.
+fn check(
+    name: &'static str,
+    values: &[f64],
+    period: usize,
+    max_order: usize,
+    config: &SeasonalityConfig,
+) -> Detected {
+    let periods = values.len() as f64 / period as f64;
+    let autocorrelation = (periods >= 2.0).then(|| autocorrelation(values, period));
+    let enabled = autocorrelation.is_some_and(|a| a >= config.min_autocorrelation);
+    let fourier_order = enabled.then(|| {
+        let mut profile = vec![(0.0, 0usize); period];
+        for (i, v) in values.iter().enumerate() {
+            profile[i % period].0 += v;
+            profile[i % period].1 += 1;
+        }
+        let profile: Vec<f64> = profile
+            .iter()
+            .map(|(sum, n)| sum / (*n).max(1) as f64)
+            .collect();
+        fourier_order(&profile, config.variance_share, max_order.min(period / 2))
+    });
+    Detected {
+        name,
+        periods,
+        autocorrelation,
+        enabled,
+        fourier_order,
+    }
+}
+
+/// Mean load of each complete day of a regular series.
+fn daily_means(history: &Series, per_day: usize) -> Vec<f64> {
+    let mut days: Vec<(i64, f64, usize)> = Vec::new();
+    for (ts, v) in history.timestamps.iter().zip(&history.values) {
+        let day = ts.div_euclid(DAY);
+        match days.last_mut() {
+            Some(last) if last.0 == day => {
+                last.1 += v;
+                last.2 += 1;
+            }
+            _ => days.push((day, *v, 1)),
+        }
+    }
+    days.into_iter()
+        .filter(|d| d.2 == per_day)
+        .map(|d| d.1 / per_day as f64)
+        .collect()
+}
+
+/// Recommend seasonalities for a regular (hourly or finer) load history.
+pub fn detect(
+    history: &Series,
+    config: &SeasonalityConfig,
+) -> Result<Recommendation, Box<dyn Error>> {
+    if history.len() < 2 {
+        return Err("Seasonality detection needs at least two points of history".into());
+    }
+    let step = history.step();
+    if step <= 0 || DAY % step != 0 {
+        return Err(format!(
+            "Seasonality detection needs a step that divides a day, not {} s",
+            step
+        )
+        .into());
+    }
+    let per_day = (DAY / step) as usize;
+    let days = detrend(&daily_means(history, per_day));
+    Ok(Recommendation {
+        daily: check(
+            "daily",
+            &detrend(&history.values),
+            per_day,
+            MAX_DAILY_ORDER,
+            config,
+        ),
+        weekly: check("weekly", &days, 7, MAX_WEEKLY_ORDER, config),
+        yearly: check("yearly", &days, 365, MAX_YEARLY_ORDER, config),
+    })
+}
@@ -0,0 +1,220 @@
+//! Credentials referenced from config files instead of written into them.
+//!
+//! Connection strings, tokens, passwords, API keys and webhook URLs may hold
+//! `${env:NAME}`, replaced by the environment variable `NAME`, or
+//! `${file:/run/secrets/pg}`, replaced by that file's contents without their
+//! trailing newline: a Docker or Kubernetes secret, a systemd credential, or a
+//! file age or sops decrypted at deploy time. A reference can be the whole
+//! value or part of it, e.g. `url = "host=db user=forecast password=${env:PG_PASSWORD}"`.
+//!
+//! References are resolved only where the connection is made, so configs
+//! registered in the `[store]`, printed by `config` commands or named in
+//! errors keep the reference, never the secret.
+
+use std::error::Error;
+
+use crate::config::{Config, SinkConfig};
+
+/// `value` with its `${env:…}` and `${file:…}` references replaced.
+///
+/// Other `${…}` text is left as it is.
+pub fn resolve(value: &str) -> Result<String, Box<dyn Error>> {
+    let mut resolved = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        resolved.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some((source, reference)) = ["env", "file"]
+            .iter()
+            .find_map(|source| Some((*source, after.strip_prefix(source)?.strip_prefix(':')?)))
+        else {
+            resolved.push_str("${");
+            rest = after;
+            continue;
+        };
+        let end = reference
+            .find('}')
+            .ok_or_else(|| format!("Unterminated secret reference ${{{}:{}", source, reference))?;
+        let name = &reference[..end];
+        match source {
+            "env" => resolved.push_str(
+                &std::env::var(name)
+                    .map_err(|_| format!("Secret ${{env:{}}}: {} is not set", name, name))?,
+            ),
+            _ => {
+                let contents = std::fs::read_to_string(name)
+                    .map_err(|e| format!("Secret ${{file:{}}}: {}", name, e))?;
+                resolved.push_str(
+                    contents
+                        .strip_suffix('\n')
+                        .map_or(contents.as_str(), |c| c.strip_suffix('\r').unwrap_or(c)),
+                );
+            }
+        }
+        rest = &reference[end + 1..];
+    }
+    resolved.push_str(rest);
+    Ok(resolved)
+}
+
+/// [`resolve`] for optional settings.
+pub fn resolve_option(value: Option<&str>) -> Result<Option<String>, Box<dyn Error>> {
+    value.map(resolve).transpose()
+}
+
+/// Whether `value` references a secret rather than holding it.
+pub fn is_reference(value: &str) -> bool {
+    value.contains("${env:") || value.contains("${file:")
+}
+
+/// What went wrong with an HTTP request, without naming the request: ureq's
+/// own message holds the resolved URL, query string and all, and with it any
+/// secret resolved into them. Callers name the URL as configured.
+#[cfg(feature = "http")]
+pub(crate) fn http_error(error: &ureq::Error) -> String {
+    match error {
+        ureq::Error::Status(code, _) => format!("{} (status {})", error.kind(), code),
+        ureq::Error::Transport(_) => error.kind().to_string(),
+    }
+}
+
+/// A URL as messages may show it: a reference as it is, a plain URL down to
+/// its scheme and host, without the user, path or query a token may be in.
+pub fn url_host(url: &str) -> String {
+    if is_reference(url) {
+        return url.to_string();
+    }
+    let (scheme, rest) = url.split_once("://").unwrap_or(("", url));
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    match scheme {
+        "" => host.to_string(),
+        _ => format!("{}://{}", scheme, host),
+    }
+}
+
+/// What [`redact`] writes in place of a secret.
+pub const REDACTED: &str = "<redacted>";
+
+/// The credential settings of `config` that are set, by option name: where a
+/// secret would be written in plain text.
+///
+/// Connection strings count only when they hold a password.
+pub fn credentials(config: &Config) -> Vec<(String, String)> {
+    let mut config = config.clone();
+    fields(&mut config)
+        .into_iter()
+        .filter(|(name, value)| {
+            !name.ends_with("url") || holds_password(value) || is_reference(value)
+        })
+        .map(|(name, value)| (name, value.clone()))
+        .collect()
+}
+
+/// `config` with the secrets written in it replaced by [`REDACTED`], for
+/// reports and anything else shown or stored outside the config file.
+///
+/// References are kept; they hold no secret.
+pub fn redact(config: &Config) -> Config {
+    let mut config = config.clone();
+    for (name, value) in fields(&mut config) {
+        if !is_reference(value) && (!name.ends_with("url") || holds_password(value)) {
+            *value = REDACTED.to_string();
+        }
+    }
+    config
+}
+
+/// Every credential setting of `config` that is set, by option name.
+fn fields(config: &mut Config) -> Vec<(String, &mut String)> {
+    let mut found: Vec<(String, Option<&mut String>)> = vec![
+        (
+            "data.http.token".to_string(),
+            config
+                .data
+                .http
+                .as_mut()
+                .and_then(|http| http.token.as_mut()),
+        ),
+        (
+            "data.sql.url".to_string(),
+            config.data.sql.as_mut().map(|sql| &mut sql.url),
+        ),
+        (
+            "data.personal.key".to_string(),
+            config
+                .data
+                .personal
+                .as_mut()
+                .and_then(|personal| personal.key.as_mut()),
+        ),
+        (
+            "model.prices.token".to_string(),
+            config
+                .model
+                .prices
+                .as_mut()
+                .and_then(|prices| prices.token.as_mut()),
+        ),
+        (
+            "depot.prices.token".to_string(),
+            config
+                .depot
+                .as_mut()
+                .and_then(|depot| depot.prices.as_mut()?.token.as_mut()),
+        ),
+        (
+            "mqtt.password".to_string(),
+            config.mqtt.as_mut().and_then(|mqtt| mqtt.password.as_mut()),
+        ),
+        (
+            "postgres.url".to_string(),
+            config.postgres.as_mut().map(|postgres| &mut postgres.url),
+        ),
+        (
+            "influx.token".to_string(),
+            config
+                .influx
+                .as_mut()
+                .and_then(|influx| influx.token.as_mut()),
+        ),
+        (
+            "server.jwt_secret".to_string(),
+            config.server.jwt_secret.as_mut(),
+        ),
+    ];
+    for (i, sink) in config.sinks.iter_mut().enumerate() {
+        found.push(match sink {
+            SinkConfig::Postgres(postgres) => {
+                (format!("sinks[{}].url", i), Some(&mut postgres.url))
+            }
+            SinkConfig::Influx(influx) => (format!("sinks[{}].token", i), influx.token.as_mut()),
+            SinkConfig::Mqtt(mqtt) => (format!("sinks[{}].password", i), mqtt.password.as_mut()),
+            _ => continue,
+        });
+    }
+    for (id, tenant) in config.server.tenants.iter_mut() {
+        found.extend(
+            tenant
+                .api_keys
+                .iter_mut()
+                .map(|key| (format!("server.tenants.{}.api_keys", id), Some(key))),
+        );
+    }
+    found
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+        .collect()
+}
+
+/// Whether a libpq connection string has a `password=`, or a URL a `user:password@`.
+fn holds_password(url: &str) -> bool {
+    let userinfo = url
+        .split_once("://")
+        .and_then(|(_, rest)| rest.split_once('@'))
+        .map(|(userinfo, _)| userinfo);
+    url.to_ascii_lowercase().contains("password=")
+        || userinfo.is_some_and(|userinfo| userinfo.contains(':'))
+}
@@ -0,0 +1,909 @@
+//! HTTP server mode.
+//!
+//! Keeps the latest history and forecast in memory and serves them through the
+//! endpoints of Grafana's simple-JSON datasource (also usable from the Infinity
+//! plugin), so dashboards can chart forecast vs actual straight from here.
+//! `GET /forecast` returns a slice of the forecast, optionally re-aggregated
+//! to days or weeks and paged.
+//!
+//! One server can serve several customers: with `[server.tenants]`, each
+//! request authenticates with an API key or JWT, and sees only its tenant's
+//! sites.
+//!
+//! `POST /fits` queues an on-demand refit (see [`crate::jobs`]), and
+//! `GET /fits/{id}` reports how it went. `POST /openadr` keeps demand-response
+//! events (see [`crate::openadr`]) and queues a refit that applies them.
+//! `POST /nowcast` corrects the current and next hour by the telemetry since
+//! the forecast was issued, without a refit (see [`crate::nowcast::live`]).
+//! `GET /openapi.json` describes every endpoint (see [`crate::api`]).
+
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::{Cursor, Read};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::api;
+use crate::config::{Config, ServerConfig};
+use crate::data::{CompactSeries, format_timestamp, parse_timestamp_text};
+use crate::forecast::Forecast;
+use crate::forecast_log;
+use crate::jobs::{FitJob, FitQueue};
+use crate::nowcast;
+use crate::openadr;
+use crate::pipeline::{fit_and_forecast, load_history};
+use crate::preprocess::HOUR;
+use crate::secret;
+
+/// Largest request body read, in bytes; larger ones are answered with 413.
+pub const MAX_BODY: u64 = 4 << 20;
+
+/// Series names offered to Grafana.
+pub const METRICS: [&str; 4] = ["actual", "forecast", "forecast_lower", "forecast_upper"];
+
+/// What the server currently knows.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    /// Kept compact: a server may hold the history of thousands of sites.
+    pub history: CompactSeries,
+    pub forecast: Forecast,
+    /// Site timezone, for `/forecast` bounds given as instants.
+    pub timezone: Option<Tz>,
+    /// Where `POST /openadr` keeps the site's events, with `[openadr]` (see
+    /// [`openadr::site_dir`]).
+    pub events_dir: Option<String>,
+}
+
+impl Snapshot {
+    /// Timestamps and values of one named metric.
+    pub fn metric(&self, name: &str) -> Option<(Vec<i64>, Vec<f64>)> {
+        let f = &self.forecast;
+        match name {
+            "actual" => {
+                let history = self.history.to_series();
+                Some((history.timestamps.to_vec(), history.values.to_vec()))
+            }
+            "forecast" => Some((f.timestamps.clone(), f.point.clone())),
+            "forecast_lower" => Some((f.timestamps.clone(), f.lower.clone())),
+            "forecast_upper" => Some((f.timestamps.clone(), f.upper.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// Latest snapshot of every served site, by site id.
+pub type Sites = BTreeMap<String, Snapshot>;
+
+/// Run the standard pipeline once, logging the forecast with `[forecast_log]`.
+pub fn build_snapshot(config: &Config) -> Result<Snapshot, Box<dyn Error>> {
+    let history = load_history(config)?;
+    let (_, forecast) = fit_and_forecast(config, &history)?;
+    forecast_log::log(config, Utc::now(), &forecast)?;
+    Ok(Snapshot {
+        history: CompactSeries::from_series(&history)?,
+        forecast,
+        timezone: config.timezone,
+        events_dir: config.openadr.as_ref().map(|openadr| {
+            openadr::site_dir(&openadr.events_dir, &config.site)
+                .display()
+                .to_string()
+        }),
+    })
+}
+
+/// Serve the sites of `configs` until the process is stopped.
+///
+/// With `server.tenants` set, the site ids are `<tenant>/<site>`.
+pub fn serve(mut server: ServerConfig, configs: Vec<Config>) -> Result<(), Box<dyn Error>> {
+    server.check_tenants()?;
+    if server.jwt_secret.is_some() && server.tenants.is_empty() {
+        return Err(
+            "server.jwt_secret needs [server.tenants] to check the tenant claim against".into(),
+        );
+    }
+    // Secret references are resolved once rather than on every request
+    server.jwt_secret = secret::resolve_option(server.jwt_secret.as_deref())?;
+    for tenant in server.tenants.values_mut() {
+        tenant.api_keys = tenant
+            .api_keys
+            .iter()
+            .map(|key| secret::resolve(key))
+            .collect::<Result<_, _>>()?;
+    }
+    let mut sites = Sites::new();
+    for config in &configs {
+        sites.insert(config.site.clone(), build_snapshot(config)?);
+    }
+    let state = Arc::new(RwLock::new(sites));
+
+    let ids: Vec<String> = configs.iter().map(|config| config.site.clone()).collect();
+    let fits = {
+        let state = Arc::clone(&state);
+        let configs: BTreeMap<String, Config> = configs
+            .into_iter()
+            .map(|config| (config.site.clone(), config))
+            .collect();
+        FitQueue::new(server.fit_workers, server.max_queued_fits, move |site| {
+            // Keep serving the previous forecast if a fit fails
+            let snapshot = build_snapshot(&configs[site]).map_err(|e| {
+                eprintln!("Refit of {} failed, keeping previous forecast: {}", site, e);
+                e.to_string()
+            })?;
+            state.write().unwrap().insert(site.to_string(), snapshot);
+            Ok(())
+        })
+    };
+
+    if server.refresh_minutes > 0 {
+        let fits = fits.clone();
+        let minutes = server.refresh_minutes;
+        std::thread::spawn(move || {
+            loop {
+                std::thread::sleep(Duration::from_secs(minutes * 60));
+                for site in &ids {
+                    if let Err(e) = fits.submit(site) {
+                        eprintln!("⚠️  Skipped the refresh of {}: {}", site, e);
+                    }
+                }
+            }
+        });
+    }
+
+    let bind = &server.bind;
+    let http = Server::http(bind).map_err(|e| format!("Could not bind {}: {}", bind, e))?;
+    eprintln!("Serving forecasts on http://{}", bind);
+
+    for mut request in http.incoming_requests() {
+        let response = {
+            let sites = state.read().unwrap();
+            handle(&mut request, &sites, &server, &fits)
+        };
+        if let Err(e) = request.respond(response) {
+            eprintln!("Failed to send response: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// The tenant a request authenticates as, or `None` when the server has no tenants.
+///
+/// `authorization` is the `Authorization` header, holding `Bearer <key or
+/// JWT>`; `api_key` is the `X-API-Key` header.
+pub fn authenticate(
+    server: &ServerConfig,
+    authorization: Option<&str>,
+    api_key: Option<&str>,
+) -> Result<Option<String>, String> {
+    if server.tenants.is_empty() {
+        return Ok(None);
+    }
+    let token = api_key
+        .or_else(|| authorization.and_then(|value| value.strip_prefix("Bearer ")))
+        .map(str::trim)
+        .ok_or("Missing API key or token")?;
+    let by_key = server.tenants.iter().find(|(_, tenant)| {
+        tenant
+            .api_keys
+            .iter()
+            .any(|key| constant_time_eq(key.as_bytes(), token.as_bytes()))
+    });
+    if let Some((id, _)) = by_key {
+        return Ok(Some(id.clone()));
+    }
+    if let Some(secret) = &server.jwt_secret {
+        let key = DecodingKey::from_secret(secret.as_bytes());
+        if let Ok(data) =
+            jsonwebtoken::decode::<Claims>(token, &key, &Validation::new(Algorithm::HS256))
+            && server.tenants.contains_key(&data.claims.tenant)
+        {
+            return Ok(Some(data.claims.tenant));
+        }
+    }
+    Err("Invalid API key or token".to_string())
+}
+
+/// JWT claims the server reads; `exp` is required and checked by the decoder.
+#[derive(Debug, Deserialize)]
+struct Claims {
+    tenant: String,
+}
+
+/// Compare secrets without leaking where they first differ.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// The sites a request may see, by the names it uses for them: a tenant's
+/// own sites without the `<tenant>/` prefix, or every site without tenants.
+pub struct View<'a> {
+    /// `<tenant>/`, or empty without tenants.
+    prefix: String,
+    sites: Vec<(&'a str, &'a Snapshot)>,
+}
+
+impl<'a> View<'a> {
+    pub fn new(sites: &'a Sites, tenant: Option<&str>) -> Self {
+        let prefix = tenant.map_or(String::new(), |tenant| format!("{}/", tenant));
+        let sites = sites
+            .iter()
+            .filter_map(|(id, snapshot)| {
+                id.strip_prefix(prefix.as_str())
+                    .map(|name| (name, snapshot))
+            })
+            .collect();
+        Self { prefix, sites }
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.sites.iter().map(|(name, _)| *name).collect()
+    }
+
+    /// A site by name; the name may be left out when there's only one.
+    pub fn site(&self, name: Option<&str>) -> Result<&'a Snapshot, String> {
+        self.find(name).map(|(_, snapshot)| snapshot)
+    }
+
+    /// The full site id of a site name, as [`View::site`] resolves it.
+    pub fn site_id(&self, name: Option<&str>) -> Result<String, String> {
+        self.find(name)
+            .map(|(name, _)| format!("{}{}", self.prefix, name))
+    }
+
+    /// The name of a full site id, if this view shows it.
+    pub fn name_of<'i>(&self, id: &'i str) -> Option<&'i str> {
+        id.strip_prefix(self.prefix.as_str())
+            .filter(|name| self.sites.iter().any(|(site, _)| site == name))
+    }
+
+    fn find(&self, name: Option<&str>) -> Result<(&'a str, &'a Snapshot), String> {
+        match (name, self.sites.as_slice()) {
+            (None, [site]) => Ok(*site),
+            (None, _) => Err("Several sites; pick one with site=".to_string()),
+            (Some(name), sites) => sites
+                .iter()
+                .find(|(site, _)| *site == name)
+                .copied()
+                .ok_or_else(|| format!("No site {}", name)),
+        }
+    }
+
+    /// Grafana metric names: plain [`METRICS`] for a single site, else `<site>/<metric>`.
+    pub fn metric_names(&self) -> Vec<String> {
+        match self.sites.as_slice() {
+            [_] => METRICS.iter().map(|m| m.to_string()).collect(),
+            sites => sites
+                .iter()
+                .flat_map(|(site, _)| METRICS.iter().map(move |m| format!("{}/{}", site, m)))
+                .collect(),
+        }
+    }
+
+    fn metric(&self, target: &str) -> Option<(Vec<i64>, Vec<f64>)> {
+        let (site, metric) = match target.rsplit_once('/') {
+            Some((site, metric)) => (Some(site), metric),
+            None => (None, target),
+        };
+        self.site(site).ok()?.metric(metric)
+    }
+}
+
+fn json_response(status: u16, body: &Value) -> Response<Cursor<Vec<u8>>> {
+    Response::from_data(serde_json::to_vec(body).unwrap_or_default())
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap())
+        .with_header(Header::from_bytes("Access-Control-Allow-Origin", "*").unwrap())
+        .with_header(
+            Header::from_bytes(
+                "Access-Control-Allow-Headers",
+                "accept, authorization, content-type, x-api-key",
+            )
+            .unwrap(),
+        )
+}
+
+/// The request's JSON body, null when it isn't JSON; an error when it is
+/// over [`MAX_BODY`].
+fn read_json(request: &mut Request) -> Result<Value, String> {
+    let too_large = || format!("Request body over {} bytes", MAX_BODY);
+    if request.body_length().is_some_and(|n| n as u64 > MAX_BODY) {
+        return Err(too_large());
+    }
+    let mut body = String::new();
+    let _ = request
+        .as_reader()
+        .take(MAX_BODY + 1)
+        .read_to_string(&mut body);
+    if body.len() as u64 > MAX_BODY {
+        return Err(too_large());
+    }
+    Ok(serde_json::from_str(&body).unwrap_or(Value::Null))
+}
+
+fn header<'r>(request: &'r Request, name: &str) -> Option<&'r str> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(name))
+        .map(|h| h.value.as_str())
+}
+
+/// Authenticate and route one request.
+pub fn handle(
+    request: &mut Request,
+    sites: &Sites,
+    server: &ServerConfig,
+    fits: &FitQueue,
+) -> Response<Cursor<Vec<u8>>> {
+    // CORS preflight from the browser-side Infinity plugin, sent without credentials
+    if request.method() == &Method::Options {
+        return json_response(200, &Value::Null);
+    }
+    let path = request.url().split('?').next().unwrap_or("").to_string();
+    if (request.method(), path.as_str()) == (&Method::Get, "/openapi.json") {
+        return openapi_json();
+    }
+    let tenant = match authenticate(
+        server,
+        header(request, "Authorization"),
+        header(request, "X-API-Key"),
+    ) {
+        Ok(tenant) => tenant,
+        Err(e) => return json_response(401, &json!({ "error": e })),
+    };
+    let view = View::new(sites, tenant.as_deref());
+    let body = match request.method() {
+        Method::Post => match read_json(request) {
+            Ok(body) => body,
+            Err(e) => return json_response(413, &json!({ "error": e })),
+        },
+        _ => Value::Null,
+    };
+
+    match (request.method(), path.as_str()) {
+        (Method::Get, "/") => health(),
+        (Method::Get, "/sites") => list_sites(&view),
+        (_, "/search") => search(&view),
+        (_, "/metrics") => metrics(&view),
+        (Method::Post, "/query") => query_route(&view, &body),
+        (Method::Post, "/annotations") => annotations(),
+        (Method::Get, "/forecast") => forecast(
+            &view,
+            request
+                .url()
+                .split_once('?')
+                .map_or("", |(_, params)| params),
+        ),
+        (Method::Post, "/fits") => submit_fit(
+            &view,
+            fits,
+            request
+                .url()
+                .split_once('?')
+                .map_or("", |(_, params)| params),
+        ),
+        (Method::Get, _) if path.starts_with("/fits/") => {
+            fit_status(&view, fits, &path["/fits/".len()..])
+        }
+        (Method::Post, "/openadr") => openadr_events(
+            &view,
+            fits,
+            request
+                .url()
+                .split_once('?')
+                .map_or("", |(_, params)| params),
+            &body,
+        ),
+        (Method::Post, "/nowcast") => nowcast(
+            &view,
+            request
+                .url()
+                .split_once('?')
+                .map_or("", |(_, params)| params),
+            &body,
+        ),
+        _ => json_response(404, &json!({ "error": format!("No route for {}", path) })),
+    }
+}
+
+/// Datasource "Save & test".
+#[utoipa::path(get, path = "/", responses((status = 200, body = api::Status), (status = 401, body = api::ApiError)))]
+pub(crate) fn health() -> Response<Cursor<Vec<u8>>> {
+    json_response(200, &json!({ "status": "ok" }))
+}
+
+/// This document; needs no credentials.
+#[utoipa::path(get, path = "/openapi.json", security(()), responses((status = 200, description = "OpenAPI 3.1 document")))]
+pub(crate) fn openapi_json() -> Response<Cursor<Vec<u8>>> {
+    json_response(200, &json!(api::openapi()))
+}
+
+/// The sites the caller may see.
+#[utoipa::path(get, path = "/sites", responses((status = 200, body = Vec<String>), (status = 401, body = api::ApiError)))]
+pub(crate) fn list_sites(view: &View) -> Response<Cursor<Vec<u8>>> {
+    json_response(200, &json!(view.names()))
+}
+
+/// Grafana metric names; `<site>/<metric>` when the caller sees several sites.
+#[utoipa::path(post, path = "/search", responses((status = 200, body = Vec<String>), (status = 401, body = api::ApiError)))]
+pub(crate) fn search(view: &View) -> Response<Cursor<Vec<u8>>> {
+    json_response(200, &json!(view.metric_names()))
+}
+
+/// Grafana metric names, as Infinity options.
+#[utoipa::path(post, path = "/metrics", responses((status = 200, body = Vec<api::MetricOption>), (status = 401, body = api::ApiError)))]
+pub(crate) fn metrics(view: &View) -> Response<Cursor<Vec<u8>>> {
+    json_response(
+        200,
+        &json!(
+            view.metric_names()
+                .iter()
+                .map(|m| json!({ "label": m, "value": m }))
+                .collect::<Vec<_>>()
+        ),
+    )
+}
+
+/// Grafana series in the dashboard's time range.
+#[utoipa::path(
+    post,
+    path = "/query",
+    request_body = api::QueryRequest,
+    responses(
+        (status = 200, body = Vec<api::TimeSeries>),
+        (status = 401, body = api::ApiError),
+        (status = 413, description = "Body over 4 MiB", body = api::ApiError),
+    )
+)]
+pub(crate) fn query_route(view: &View, body: &Value) -> Response<Cursor<Vec<u8>>> {
+    json_response(200, &query(view, body))
+}
+
+/// Grafana annotations; there are none.
+#[utoipa::path(post, path = "/annotations", responses((status = 200, body = Vec<Value>)))]
+pub(crate) fn annotations() -> Response<Cursor<Vec<u8>>> {
+    json_response(200, &json!([]))
+}
+
+/// A slice of a site's forecast; see [`forecast_slice`].
+#[utoipa::path(
+    get,
+    path = "/forecast",
+    params(api::ForecastParams),
+    responses(
+        (status = 200, body = api::ForecastPage),
+        (status = 400, description = "Invalid parameters", body = api::ApiError),
+        (status = 401, body = api::ApiError),
+        (status = 404, description = "No such site", body = api::ApiError),
+    )
+)]
+pub(crate) fn forecast(view: &View, params: &str) -> Response<Cursor<Vec<u8>>> {
+    let site = params
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("site="))
+        .map(percent_decode);
+    let response = view.site(site.as_deref()).and_then(|snapshot| {
+        let query = ForecastQuery::parse(params, snapshot.timezone)?;
+        Ok(forecast_slice(&snapshot.forecast, &query))
+    });
+    match response {
+        Ok(body) => json_response(200, &body),
+        Err(e) if e.starts_with("No site") => json_response(404, &json!({ "error": e })),
+        Err(e) => json_response(400, &json!({ "error": e })),
+    }
+}
+
+/// Queue a refit of a site, on fresh data; poll `/fits/{id}` for how it went.
+#[utoipa::path(
+    post,
+    path = "/fits",
+    params(("site" = Option<String>, Query, description = "Site name; may be left out when the caller sees only one site")),
+    responses(
+        (status = 202, description = "Queued, or already waiting", body = FitJob),
+        (status = 401, body = api::ApiError),
+        (status = 404, description = "No such site", body = api::ApiError),
+        (status = 429, description = "Too many fits waiting", body = api::ApiError),
+    )
+)]
+pub(crate) fn submit_fit(view: &View, fits: &FitQueue, params: &str) -> Response<Cursor<Vec<u8>>> {
+    let site = params
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("site="))
+        .map(percent_decode);
+    let id = match view.site_id(site.as_deref()) {
+        Ok(id) => id,
+        Err(e) if e.starts_with("No site") => return json_response(404, &json!({ "error": e })),
+        Err(e) => return json_response(400, &json!({ "error": e })),
+    };
+    match fits.submit(&id) {
+        Ok(job) => json_response(202, &job_json(view, job)),
+        Err(e) => json_response(429, &json!({ "error": e })),
+    }
+}
+
+/// Keep OpenADR 3 events (one, an array, or a VTN notification) for a site
+/// and queue a refit, so the published forecast reflects them.
+#[utoipa::path(
+    post,
+    path = "/openadr",
+    params(("site" = Option<String>, Query, description = "Site name; may be left out when the caller sees only one site")),
+    request_body(content = Value, description = "OpenADR 3 event, array of events, or notification"),
+    responses(
+        (status = 202, description = "Events kept and refit queued", body = api::OpenAdrReceipt),
+        (status = 400, description = "Not an event payload, or an event not targeting the site", body = api::ApiError),
+        (status = 401, body = api::ApiError),
+        (status = 404, description = "No such site, or no [openadr] for it", body = api::ApiError),
+        (status = 413, description = "Body over 4 MiB", body = api::ApiError),
+        (status = 429, description = "Too many fits waiting", body = api::ApiError),
+    )
+)]
+pub fn openadr_events(
+    view: &View,
+    fits: &FitQueue,
+    params: &str,
+    body: &Value,
+) -> Response<Cursor<Vec<u8>>> {
+    let site = params
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("site="))
+        .map(percent_decode);
+    let (id, snapshot) = match view
+        .site_id(site.as_deref())
+        .and_then(|id| Ok((id, view.site(site.as_deref())?)))
+    {
+        Ok(found) => found,
+        Err(e) if e.starts_with("No site") => return json_response(404, &json!({ "error": e })),
+        Err(e) => return json_response(400, &json!({ "error": e })),
+    };
+    let Some(dir) = &snapshot.events_dir else {
+        return json_response(
+            404,
+            &json!({ "error": format!("No [openadr] configured for {}", view.name_of(&id).unwrap_or(&id)) }),
+        );
+    };
+    if openadr::events(body).is_empty() {
+        return json_response(400, &json!({ "error": "No OpenADR events in the payload" }));
+    }
+    let (stored, removed) = match openadr::save(dir, &id, body) {
+        Ok(saved) => saved,
+        Err(e) => return json_response(400, &json!({ "error": e.to_string() })),
+    };
+    match fits.submit(&id) {
+        Ok(job) => json_response(
+            202,
+            &json!({ "stored": stored, "removed": removed, "fit": job_json(view, job) }),
+        ),
+        Err(e) => json_response(429, &json!({ "error": e })),
+    }
+}
+
+/// The current and next hour of a site's forecast, corrected by the readings
+/// since it was issued.
+#[utoipa::path(
+    post,
+    path = "/nowcast",
+    params(("site" = Option<String>, Query, description = "Site name; may be left out when the caller sees only one site")),
+    request_body = api::NowcastRequest,
+    responses(
+        (status = 200, body = api::Nowcast),
+        (status = 400, description = "No usable readings, or readings past the forecast", body = api::ApiError),
+        (status = 401, body = api::ApiError),
+        (status = 404, description = "No such site", body = api::ApiError),
+        (status = 413, description = "Body over 4 MiB", body = api::ApiError),
+    )
+)]
+pub(crate) fn nowcast(view: &View, params: &str, body: &Value) -> Response<Cursor<Vec<u8>>> {
+    let site = params
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("site="))
+        .map(percent_decode);
+    let snapshot = match view.site(site.as_deref()) {
+        Ok(snapshot) => snapshot,
+        Err(e) if e.starts_with("No site") => return json_response(404, &json!({ "error": e })),
+        Err(e) => return json_response(400, &json!({ "error": e })),
+    };
+    match nowcast_json(snapshot, body) {
+        Ok(body) => json_response(200, &body),
+        Err(e) => json_response(400, &json!({ "error": e })),
+    }
+}
+
+/// The `POST /nowcast` answer for a snapshot and a [`api::NowcastRequest`] body.
+pub fn nowcast_json(snapshot: &Snapshot, body: &Value) -> Result<Value, String> {
+    let request: api::NowcastRequest = serde_json::from_value(body.clone())
+        .map_err(|e| format!("Invalid nowcast request: {}", e))?;
+    let readings = request
+        .readings
+        .iter()
+        .map(|r| {
+            parse_timestamp_text(&r.timestamp, snapshot.timezone)
+                .map(|ts| (ts, r.power_kw))
+                .ok_or_else(|| format!("Invalid reading time {:?}", r.timestamp))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let live = nowcast::live(&snapshot.forecast, &readings).map_err(|e| e.to_string())?;
+    let hour = |i: usize| api::NowcastHour {
+        timestamp: format_timestamp(live.estimate.timestamps[i]),
+        yhat: live.estimate.point[i],
+        yhat_lower: live.estimate.lower[i],
+        yhat_upper: live.estimate.upper[i],
+        forecast: live.forecast.point[i],
+    };
+    let answer = api::Nowcast {
+        issued: format_timestamp(snapshot.forecast.timestamps[0]),
+        hours_observed: live.hours_observed,
+        adjustment_kw: live.adjustment_kw,
+        current: hour(0),
+        next: (live.estimate.len() > 1).then(|| hour(1)),
+    };
+    Ok(json!(answer))
+}
+
+/// How a queued fit went.
+#[utoipa::path(
+    get,
+    path = "/fits/{id}",
+    params(("id" = u64, Path, description = "Job id from `POST /fits`")),
+    responses((status = 200, body = FitJob), (status = 401, body = api::ApiError), (status = 404, body = api::ApiError))
+)]
+pub(crate) fn fit_status(view: &View, fits: &FitQueue, id: &str) -> Response<Cursor<Vec<u8>>> {
+    // Other tenants' jobs don't exist for the caller
+    let job = id
+        .parse()
+        .ok()
+        .and_then(|id| fits.job(id))
+        .filter(|job| view.name_of(&job.site).is_some());
+    match job {
+        Some(job) => json_response(200, &job_json(view, job)),
+        None => json_response(404, &json!({ "error": format!("No fit job {}", id) })),
+    }
+}
+
+/// A job as the caller sees it, with its site name.
+fn job_json(view: &View, mut job: FitJob) -> Value {
+    job.site = view.name_of(&job.site).unwrap_or(&job.site).to_string();
+    json!(job)
+}
+
+/// Parse a Grafana range bound (RFC 3339) into UNIX seconds.
+fn range_bound(body: &Value, key: &str) -> Option<i64> {
+    let text = body.get("range")?.get(key)?.as_str()?;
+    DateTime::parse_from_rfc3339(text)
+        .ok()
+        .map(|dt| dt.timestamp())
+}
+
+/// Answer a simple-JSON `/query` request.
+///
+/// Returns one `{"target", "datapoints": [[value, epoch_ms], ...]}` entry per
+/// requested metric (see [`View::metric_names`]), restricted to the
+/// dashboard's time range.
+pub fn query(view: &View, body: &Value) -> Value {
+    let from = range_bound(body, "from").unwrap_or(i64::MIN);
+    let to = range_bound(body, "to").unwrap_or(i64::MAX);
+    let targets: Vec<&str> = body
+        .get("targets")
+        .and_then(Value::as_array)
+        .map(|targets| {
+            targets
+                .iter()
+                .filter_map(|t| t.get("target").and_then(Value::as_str))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let series: Vec<Value> = targets
+        .into_iter()
+        .filter_map(|target| {
+            let (timestamps, values) = view.metric(target)?;
+            let datapoints: Vec<Value> = timestamps
+                .iter()
+                .zip(&values)
+                .filter(|(ts, _)| (from..=to).contains(*ts))
+                .map(|(ts, v)| json!([v, ts * 1000]))
+                .collect();
+            Some(json!({ "target": target, "datapoints": datapoints }))
+        })
+        .collect();
+    json!(series)
+}
+
+/// Bucket size of `/forecast` points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Resolution {
+    /// The steps of a sub-hourly forecast averaged into hours.
+    #[default]
+    Hour,
+    Day,
+    /// Monday to Sunday.
+    Week,
+}
+
+impl Resolution {
+    fn name(self) -> &'static str {
+        match self {
+            Resolution::Hour => "hour",
+            Resolution::Day => "day",
+            Resolution::Week => "week",
+        }
+    }
+
+    /// Start of the bucket holding `timestamp`.
+    fn bucket(self, timestamp: i64) -> i64 {
+        const DAY: i64 = 24 * HOUR;
+        // 1970-01-05 was a Monday
+        const MONDAY: i64 = 4 * DAY;
+        match self {
+            Resolution::Hour => timestamp.div_euclid(HOUR) * HOUR,
+            Resolution::Day => timestamp.div_euclid(DAY) * DAY,
+            Resolution::Week => (timestamp - MONDAY).div_euclid(7 * DAY) * 7 * DAY + MONDAY,
+        }
+    }
+}
+
+/// Query parameters of `GET /forecast`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ForecastQuery {
+    /// First hour included (local wall-clock seconds).
+    pub from: Option<i64>,
+    /// First hour no longer included.
+    pub to: Option<i64>,
+    pub resolution: Resolution,
+    /// Points skipped, after aggregation.
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl ForecastQuery {
+    /// Parse `from=&to=&resolution=&offset=&limit=` (and ignore `site=`).
+    ///
+    /// Bounds are dates, local `YYYY-MM-DD HH:MM` times, or instants (Unix
+    /// seconds, RFC 3339) shown in `timezone`; `resolution` is `hour`, `day`
+    /// or `week`.
+    pub fn parse(params: &str, timezone: Option<Tz>) -> Result<Self, String> {
+        let mut query = ForecastQuery::default();
+        for pair in params.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value);
+            let bound = || {
+                NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+                    .map(|date| {
+                        date.and_hms_opt(0, 0, 0)
+                            .unwrap_or_default()
+                            .and_utc()
+                            .timestamp()
+                    })
+                    .ok()
+                    .or_else(|| parse_timestamp_text(&value, timezone))
+                    .ok_or_else(|| format!("Invalid {} time {:?}", key, value))
+            };
+            let count = || {
+                value
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid {} {:?}", key, value))
+            };
+            match key {
+                "from" => query.from = Some(bound()?),
+                "to" => query.to = Some(bound()?),
+                "resolution" => {
+                    query.resolution = match value.as_str() {
+                        "hour" | "hourly" => Resolution::Hour,
+                        "day" | "daily" => Resolution::Day,
+                        "week" | "weekly" => Resolution::Week,
+                        other => {
+                            return Err(format!(
+                                "Unknown resolution {:?} (hour, day or week)",
+                                other
+                            ));
+                        }
+                    }
+                }
+                // Picks the snapshot before parsing, see `handle`
+                "site" => {}
+                "offset" => query.offset = count()?,
+                "limit" => query.limit = Some(count()?),
+                other => return Err(format!("Unknown parameter {:?}", other)),
+            }
+        }
+        Ok(query)
+    }
+}
+
+/// Decode `%XX` escapes and `+` in a query value.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], escaped) {
+            (_, Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (b'+', None) => decoded.push(b' '),
+            (byte, None) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The forecast points in `[from, to)`, averaged into `resolution` buckets
+/// and paged with `offset` and `limit`.
+///
+/// Each point has the mean `yhat`, `yhat_lower` and `yhat_upper` in kW;
+/// aggregated points also have the hours covered, the energy in kWh and the
+/// peak `yhat` of a forecast step. `next_offset` is set while points remain.
+pub fn forecast_slice(forecast: &Forecast, query: &ForecastQuery) -> Value {
+    let from = query.from.unwrap_or(i64::MIN);
+    let to = query.to.unwrap_or(i64::MAX);
+    let step = forecast.step();
+    // (bucket, points, point sum, lower sum, upper sum, peak)
+    let mut buckets: Vec<(i64, usize, f64, f64, f64, f64)> = Vec::new();
+    for i in 0..forecast.len() {
+        let timestamp = forecast.timestamps[i];
+        if !(from..to).contains(&timestamp) {
+            continue;
+        }
+        let bucket = query.resolution.bucket(timestamp);
+        match buckets.last_mut() {
+            Some(last) if last.0 == bucket => {
+                last.1 += 1;
+                last.2 += forecast.point[i];
+                last.3 += forecast.lower[i];
+                last.4 += forecast.upper[i];
+                last.5 = last.5.max(forecast.point[i]);
+            }
+            _ => buckets.push((
+                bucket,
+                1,
+                forecast.point[i],
+                forecast.lower[i],
+                forecast.upper[i],
+                forecast.point[i],
+            )),
+        }
+    }
+
+    let total = buckets.len();
+    let end = query
+        .limit
+        .map_or(total, |limit| query.offset.saturating_add(limit).min(total));
+    let points: Vec<Value> = buckets
+        .get(query.offset.min(total)..end)
+        .unwrap_or_default()
+        .iter()
+        .map(|&(bucket, points, point, lower, upper, peak)| {
+            let n = points as f64;
+            let mut json = json!({
+                "timestamp": format_timestamp(bucket),
+                "yhat": point / n,
+                "yhat_lower": lower / n,
+                "yhat_upper": upper / n,
+            });
+            if query.resolution != Resolution::Hour {
+                json["hours"] = json!(points as i64 * step / HOUR);
+                json["energy_kwh"] = json!(point * step as f64 / HOUR as f64);
+                json["peak_kw"] = json!(peak);
+            }
+            json
+        })
+        .collect();
+    json!({
+        "resolution": query.resolution.name(),
+        "total": total,
+        "offset": query.offset,
+        "next_offset": (end < total).then_some(end),
+        "points": points,
+    })
+}
@@ -0,0 +1,157 @@
+//! Champion/challenger shadow runs.
+//!
+//! Every production forecast is logged together with the challenger's forecast
+//! for the same origin. Once actuals arrive, both are scored on the same hours
+//! so the challenger can be promoted on live accuracy rather than backtests.
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+
+use crate::data::Series;
+use crate::forecast::Forecast;
+use crate::metrics::{Metrics, evaluate};
+use crate::preprocess::HOUR;
+
+/// One logged pair of forecasts issued from the same history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShadowRecord {
+    /// Last actual the models were fitted on.
+    pub origin: i64,
+    pub recorded_at: String,
+    pub champion_version: String,
+    pub challenger_version: String,
+    pub champion: Forecast,
+    pub challenger: Forecast,
+}
+
+impl ShadowRecord {
+    pub fn new(
+        origin: i64,
+        champion_version: String,
+        challenger_version: String,
+        champion: Forecast,
+        challenger: Forecast,
+    ) -> Self {
+        Self {
+            origin,
+            recorded_at: Utc::now().format("%Y-%m-%d %H:%M UTC").to_string(),
+            champion_version,
+            challenger_version,
+            champion,
+            challenger,
+        }
+    }
+}
+
+/// Append a record to the shadow log.
+pub fn append(path: &str, record: &ShadowRecord) -> Result<(), Box<dyn Error>> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+/// Read every record from the shadow log.
+pub fn load(path: &str) -> Result<Vec<ShadowRecord>, Box<dyn Error>> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Could not open shadow log {}: {}", path, e))?;
+    let mut records = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        records.push(serde_json::from_str(&line)?);
+    }
+    Ok(records)
+}
+
+/// Live accuracy of both models over the same hours.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowComparison {
+    /// Logged runs that contributed at least one scored hour.
+    pub runs: usize,
+    pub champion: Metrics,
+    pub challenger: Metrics,
+}
+
+impl ShadowComparison {
+    /// Whether the challenger beat the champion on MAE.
+    pub fn challenger_wins(&self) -> bool {
+        self.challenger.n > 0 && self.challenger.mae < self.champion.mae
+    }
+}
+
+/// Keep the forecast hours that have an actual and fall inside `[from, to]`.
+fn observed(
+    forecast: &Forecast,
+    actuals: &HashMap<i64, f64>,
+    from: i64,
+    to: i64,
+) -> (Vec<f64>, Forecast) {
+    let mut actual = Vec::new();
+    let mut kept = Forecast::default();
+    for i in 0..forecast.len() {
+        let ts = forecast.timestamps[i];
+        let Some(value) = actuals.get(&ts) else {
+            continue;
+        };
+        if ts < from || ts > to {
+            continue;
+        }
+        actual.push(*value);
+        kept.timestamps.push(ts);
+        kept.point.push(forecast.point[i]);
+        kept.lower.push(forecast.lower[i]);
+        kept.upper.push(forecast.upper[i]);
+    }
+    (actual, kept)
+}
+
+/// Score the logged forecasts against `history` over the last `window_days`.
+///
+/// `interval_widths` are the champion's and challenger's configured interval widths.
+pub fn compare(
+    records: &[ShadowRecord],
+    history: &Series,
+    window_days: u32,
+    interval_widths: (f64, f64),
+) -> ShadowComparison {
+    let actuals: HashMap<i64, f64> = history
+        .timestamps
+        .iter()
+        .cloned()
+        .zip(history.values.iter().cloned())
+        .collect();
+    let to = history.timestamps.last().cloned().unwrap_or_default();
+    let from = to - window_days as i64 * 24 * HOUR;
+
+    let (mut champion, mut challenger) = (Forecast::default(), Forecast::default());
+    let (mut champion_actual, mut challenger_actual) = (Vec::new(), Vec::new());
+    let mut runs = 0;
+    for record in records {
+        let (a, f) = observed(&record.champion, &actuals, from, to);
+        let (b, g) = observed(&record.challenger, &actuals, from, to);
+        if a.is_empty() && b.is_empty() {
+            continue;
+        }
+        runs += 1;
+        for (pooled, part) in [(&mut champion, f), (&mut challenger, g)] {
+            pooled.timestamps.extend(part.timestamps);
+            pooled.point.extend(part.point);
+            pooled.lower.extend(part.lower);
+            pooled.upper.extend(part.upper);
+        }
+        champion_actual.extend(a);
+        challenger_actual.extend(b);
+    }
+
+    ShadowComparison {
+        runs,
+        champion: evaluate(&champion_actual, &champion, interval_widths.0),
+        challenger: evaluate(&challenger_actual, &challenger, interval_widths.1),
+    }
+}
@@ -0,0 +1,250 @@
+//! Output sinks: everywhere an issued forecast goes.
+//!
+//! A run fans its forecast out to every sink of its config (see
+//! [`Fanout::from_config`]): the `[mqtt]`, `[postgres]` and `[influx]`
+//! sections, each `[[output.exports]]` file and each `[[sinks]]` entry. A
+//! failing sink doesn't keep the others from being written; the run still
+//! fails afterwards, naming it.
+
+use std::error::Error;
+
+use crate::config::{Config, OutputConfig, SinkConfig};
+use crate::data::STDIO;
+use crate::export::{self, ExportContext, Exporter};
+use crate::forecast::Forecast;
+use crate::remote;
+#[cfg(feature = "http")]
+use crate::secret;
+
+/// Somewhere forecasts are written: a file, a database, a broker, an HTTP endpoint.
+pub trait OutputSink {
+    /// What the sink writes to, for messages.
+    fn name(&self) -> String;
+
+    fn write(&self, forecast: &Forecast, context: &ExportContext) -> Result<(), Box<dyn Error>>;
+}
+
+/// A file in one export format, or stdout for [`STDIO`]; remote paths are uploaded.
+pub struct FileSink {
+    exporter: Box<dyn Exporter>,
+    path: String,
+}
+
+impl FileSink {
+    pub fn new(exporter: Box<dyn Exporter>, path: &str) -> Self {
+        Self {
+            exporter,
+            path: path.to_string(),
+        }
+    }
+}
+
+impl OutputSink for FileSink {
+    fn name(&self) -> String {
+        format!("{} {}", self.exporter.name(), self.path)
+    }
+
+    fn write(&self, forecast: &Forecast, context: &ExportContext) -> Result<(), Box<dyn Error>> {
+        remote::with_output(&self.path, |path| {
+            export::export(self.exporter.as_ref(), forecast, context, path)
+        })?;
+        eprintln!(
+            "Forecast written to {} ({})",
+            self.path,
+            self.exporter.name()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(feature = "db")]
+pub struct PostgresSink(pub crate::config::PostgresConfig);
+
+#[cfg(feature = "db")]
+impl OutputSink for PostgresSink {
+    fn name(&self) -> String {
+        format!("Postgres table {}", self.0.table)
+    }
+
+    fn write(&self, forecast: &Forecast, context: &ExportContext) -> Result<(), Box<dyn Error>> {
+        crate::db::write_postgres(&self.0, &context.site, &context.model_version, forecast)
+    }
+}
+
+#[cfg(feature = "db")]
+pub struct InfluxSink(pub crate::config::InfluxConfig);
+
+#[cfg(feature = "db")]
+impl OutputSink for InfluxSink {
+    fn name(&self) -> String {
+        format!("InfluxDB bucket {}", self.0.bucket)
+    }
+
+    fn write(&self, forecast: &Forecast, context: &ExportContext) -> Result<(), Box<dyn Error>> {
+        crate::db::write_influx(&self.0, &context.site, &context.model_version, forecast)
+    }
+}
+
+#[cfg(feature = "mqtt")]
+pub struct MqttSink(pub crate::config::MqttConfig);
+
+#[cfg(feature = "mqtt")]
+impl OutputSink for MqttSink {
+    fn name(&self) -> String {
+        format!("MQTT topic {}", self.0.topic)
+    }
+
+    fn write(&self, forecast: &Forecast, _: &ExportContext) -> Result<(), Box<dyn Error>> {
+        crate::mqtt::publish_forecast(&self.0, forecast)
+    }
+}
+
+#[cfg(feature = "http")]
+pub struct WebhookSink {
+    pub url: String,
+}
+
+#[cfg(feature = "http")]
+impl OutputSink for WebhookSink {
+    /// The host only: the path and query of a webhook URL often hold its token.
+    fn name(&self) -> String {
+        format!("webhook {}", secret::url_host(&self.url))
+    }
+
+    fn write(&self, forecast: &Forecast, context: &ExportContext) -> Result<(), Box<dyn Error>> {
+        let mut body = Vec::new();
+        export::JsonExporter.write(forecast, context, &mut body)?;
+        crate::notify::post_json(&self.url, &serde_json::from_slice(&body)?)?;
+        eprintln!("Forecast posted to {}", self.name());
+        Ok(())
+    }
+}
+
+/// The sink of one `[[sinks]]` entry.
+pub fn sink(
+    config: &SinkConfig,
+    output: &OutputConfig,
+) -> Result<Box<dyn OutputSink>, Box<dyn Error>> {
+    Ok(match config {
+        SinkConfig::File(file) => Box::new(FileSink::new(
+            export::exporter(file.format, output),
+            &file.path,
+        )),
+        SinkConfig::Stdout(stdout) => Box::new(FileSink::new(
+            export::exporter(stdout.format, output),
+            STDIO,
+        )),
+        #[cfg(feature = "db")]
+        SinkConfig::Postgres(postgres) => Box::new(PostgresSink(postgres.clone())),
+        #[cfg(feature = "db")]
+        SinkConfig::Influx(influx) => Box::new(InfluxSink(influx.clone())),
+        #[cfg(not(feature = "db"))]
+        SinkConfig::Postgres(_) | SinkConfig::Influx(_) => {
+            return Err("Postgres and InfluxDB sinks need the `db` feature".into());
+        }
+        #[cfg(feature = "mqtt")]
+        SinkConfig::Mqtt(mqtt) => Box::new(MqttSink(mqtt.clone())),
+        #[cfg(not(feature = "mqtt"))]
+        SinkConfig::Mqtt(_) => return Err("MQTT sinks need the `mqtt` feature".into()),
+        #[cfg(feature = "http")]
+        SinkConfig::Webhook(webhook) => Box::new(WebhookSink {
+            url: webhook.url.clone(),
+        }),
+        #[cfg(not(feature = "http"))]
+        SinkConfig::Webhook(_) => return Err("webhook sinks need the `http` feature".into()),
+    })
+}
+
+/// Several sinks written as one.
+#[derive(Default)]
+pub struct Fanout {
+    sinks: Vec<Box<dyn OutputSink>>,
+}
+
+impl Fanout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every sink `config` names.
+    pub fn from_config(config: &Config) -> Result<Self, Box<dyn Error>> {
+        let mut entries = Vec::new();
+        entries.extend(config.mqtt.clone().map(SinkConfig::Mqtt));
+        entries.extend(config.postgres.clone().map(SinkConfig::Postgres));
+        entries.extend(config.influx.clone().map(SinkConfig::Influx));
+        entries.extend(config.output.exports.iter().cloned().map(SinkConfig::File));
+        entries.extend(config.sinks.iter().cloned());
+        let mut fanout = Self::new();
+        for entry in &entries {
+            fanout.push(sink(entry, &config.output)?);
+        }
+        Ok(fanout)
+    }
+
+    pub fn push(&mut self, sink: Box<dyn OutputSink>) {
+        self.sinks.push(sink);
+    }
+
+    pub fn len(&self) -> usize {
+        self.sinks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    /// The sinks' names, in the order they are written.
+    pub fn names(&self) -> Vec<String> {
+        self.sinks.iter().map(|sink| sink.name()).collect()
+    }
+}
+
+impl OutputSink for Fanout {
+    fn name(&self) -> String {
+        self.names().join(", ")
+    }
+
+    fn write(&self, forecast: &Forecast, context: &ExportContext) -> Result<(), Box<dyn Error>> {
+        let failed: Vec<String> = self
+            .sinks
+            .iter()
+            .filter_map(|sink| {
+                sink.write(forecast, context)
+                    .err()
+                    .map(|e| format!("{}: {}", sink.name(), e))
+            })
+            .collect();
+        match failed.is_empty() {
+            true => Ok(()),
+            false => Err(format!("Writing the forecast failed for {}", failed.join("; ")).into()),
+        }
+    }
+}
+
+/// Whether any sink of `config` writes to stdout.
+pub fn writes_stdout(config: &Config) -> bool {
+    config
+        .output
+        .exports
+        .iter()
+        .any(|export| export.path == STDIO)
+        || config.sinks.iter().any(|sink| match sink {
+            SinkConfig::File(file) => file.path == STDIO,
+            SinkConfig::Stdout(_) => true,
+            _ => false,
+        })
+}
+
+/// The files the sinks of `config` write, stdout left out.
+pub fn file_paths(config: &Config) -> Vec<&str> {
+    let exports = config
+        .output
+        .exports
+        .iter()
+        .map(|export| export.path.as_str());
+    let sinks = config.sinks.iter().filter_map(|sink| match sink {
+        SinkConfig::File(file) => Some(file.path.as_str()),
+        _ => None,
+    });
+    exports.chain(sinks).filter(|path| *path != STDIO).collect()
+}
@@ -0,0 +1,104 @@
+//! Site metadata: where a site is and what is installed there.
+//!
+//! Sites are listed once in a YAML registry (`registry`) or the project
+//! store, and a run's config only names its site; the timezone, charger
+//! count and grid limit then come from the site's entry.
+
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+use crate::config::{AlertConfig, Config};
+
+/// One charge point.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Charger {
+    pub id: String,
+    /// Rated power in kW.
+    pub power_kw: f64,
+    #[serde(default = "one")]
+    pub connectors: u32,
+    /// Can discharge for V2G.
+    #[serde(default)]
+    pub bidirectional: bool,
+}
+
+fn one() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Site {
+    /// Matches `site` in the config.
+    pub id: String,
+    pub name: Option<String>,
+    /// Location in degrees, e.g. for weather data.
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub timezone: Option<Tz>,
+    /// Grid connection limit in kW.
+    pub grid_limit_kw: Option<f64>,
+    #[serde(default)]
+    pub chargers: Vec<Charger>,
+}
+
+impl Site {
+    /// Installed charging power in kW.
+    pub fn installed_kw(&self) -> f64 {
+        self.chargers.iter().map(|c| c.power_kw).sum()
+    }
+
+    /// Fill in the config's timezone, charger counts, installed power and site limit from
+    /// this site, where the config doesn't set them itself.
+    pub fn apply(&self, config: &mut Config) {
+        config.timezone = config.timezone.or(self.timezone);
+        if config.chargers.is_none() && !self.chargers.is_empty() {
+            config.chargers = Some(self.chargers.len() as u32);
+        }
+        let bidirectional = self.chargers.iter().filter(|c| c.bidirectional).count() as u32;
+        if let Some(v2g) = config
+            .v2g
+            .as_mut()
+            .filter(|v2g| v2g.chargers.is_none() && bidirectional > 0)
+        {
+            v2g.chargers = Some(bidirectional);
+        }
+        let installed_kw = self.installed_kw();
+        if let Some(utilization) = config
+            .utilization
+            .as_mut()
+            .filter(|u| u.installed_kw.is_none() && installed_kw > 0.0)
+        {
+            utilization.installed_kw = Some(installed_kw);
+        }
+        if let Some(limit_kw) = self.grid_limit_kw {
+            let alerts = config.alerts.get_or_insert_with(AlertConfig::default);
+            alerts.site_limit_kw = alerts.site_limit_kw.or(Some(limit_kw));
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Registry {
+    sites: Vec<Site>,
+}
+
+/// Parse a YAML registry: a `sites:` list of [`Site`]s.
+pub fn parse_registry(text: &str) -> Result<Vec<Site>, Box<dyn Error>> {
+    let registry: Registry = serde_yaml::from_str(text)?;
+    for (i, site) in registry.sites.iter().enumerate() {
+        if registry.sites[..i].iter().any(|other| other.id == site.id) {
+            return Err(format!("Site {} is listed twice", site.id).into());
+        }
+    }
+    Ok(registry.sites)
+}
+
+pub fn load_registry(path: &str) -> Result<Vec<Site>, Box<dyn Error>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read site registry {}: {}", path, e))?;
+    parse_registry(&text).map_err(|e| format!("Invalid site registry {}: {}", path, e).into())
+}
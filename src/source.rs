@@ -0,0 +1,309 @@
+//! Data sources: where a site's readings come from.
+//!
+//! A source hands over either charging sessions or a load series;
+//! [`crate::pipeline::load_history_from`] turns both into the same checked,
+//! regular hourly series, through `[data.preprocess]`, whichever source it was.
+//! [`source`] picks the configured one; other sources implement [`DataSource`].
+
+use chrono_tz::Tz;
+use std::error::Error;
+
+use crate::config::{
+    ColumnConfig, Config, EnergyUnit, Export, HttpSourceConfig, LocaleConfig, SqlSourceConfig,
+};
+use crate::data::{CsvFormat, RowCounts, Series, Session, is_columnar, load_sessions_formatted};
+use crate::preprocess::{HOUR, Pipeline, Resample, floor_step};
+use crate::privacy;
+use crate::register::Reading;
+use crate::remote;
+
+/// What a source read.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Readings {
+    Sessions(Vec<Session>),
+    /// Load in kW; not necessarily sorted, complete or hourly.
+    Hourly(Series),
+    /// Several chargers' cumulative energy counters, for `[data.register]`.
+    Registers(Vec<Reading>),
+}
+
+pub trait DataSource {
+    /// What the source reads from, for messages.
+    fn name(&self) -> String;
+
+    /// The readings, with how many rows were read and dropped.
+    fn read(&self) -> Result<(Readings, RowCounts), Box<dyn Error>>;
+}
+
+/// A site session export CSV, or stdin for [`crate::data::STDIO`]; remote paths are downloaded.
+pub struct CsvSource {
+    pub path: String,
+    /// Keep sessions with negative energy (see `data.export`).
+    pub signed: bool,
+    pub locale: LocaleConfig,
+    pub columns: ColumnConfig,
+    /// The site whose rows `columns.site` keeps.
+    pub site: String,
+    /// Columns of personal data, which no session field may be read from.
+    pub personal: Vec<usize>,
+}
+
+/// The CSV format `locale` and `columns` describe, failing on separators that
+/// can't be told apart and on a site column without a `site`.
+pub fn csv_format(
+    locale: &LocaleConfig,
+    columns: &ColumnConfig,
+    site: &str,
+    signed: bool,
+) -> Result<CsvFormat, Box<dyn Error>> {
+    let byte = |c: char, name: &str| match c.is_ascii() && !c.is_ascii_alphanumeric() {
+        true => Ok(c as u8),
+        false => Err(format!(
+            "data.locale.{} has to be an ASCII punctuation or space character, not {:?}",
+            name, c
+        )),
+    };
+    let format = CsvFormat {
+        signed,
+        delimiter: byte(locale.delimiter, "delimiter")?,
+        decimal: byte(locale.decimal, "decimal")?,
+        thousands: locale.thousands.map(|c| byte(c, "thousands")).transpose()?,
+        date_formats: locale.date_formats.clone(),
+        start: columns.start,
+        stop: columns.stop,
+        energy: columns.energy,
+        energy_scale: match columns.energy_unit {
+            EnergyUnit::Wh => 1.0,
+            EnergyUnit::Kwh => 1000.0,
+        },
+        site: columns.site.map(|column| (column, site.to_string())),
+    };
+    if columns.site.is_some() && site.is_empty() {
+        return Err("data.columns.site is set, but not the `site` whose rows to read".into());
+    }
+    if format.delimiter == format.decimal
+        || format
+            .thousands
+            .is_some_and(|t| t == format.decimal || t == format.delimiter)
+    {
+        return Err(
+            "data.locale: the delimiter, decimal and thousands separators have to differ".into(),
+        );
+    }
+    Ok(format)
+}
+
+impl DataSource for CsvSource {
+    fn name(&self) -> String {
+        self.path.clone()
+    }
+
+    fn read(&self) -> Result<(Readings, RowCounts), Box<dyn Error>> {
+        privacy::check_columns(&self.personal, &self.columns)?;
+        let format = csv_format(&self.locale, &self.columns, &self.site, self.signed)?;
+        let (sessions, counts) =
+            remote::with_input(&self.path, |path| load_sessions_formatted(path, &format))?;
+        Ok((Readings::Sessions(sessions), counts))
+    }
+}
+
+/// A Parquet or Arrow IPC file of an hourly series, or of chargers' energy
+/// registers with a `charger` column (see [`is_columnar`]).
+pub struct ColumnarSource {
+    pub path: String,
+}
+
+impl DataSource for ColumnarSource {
+    fn name(&self) -> String {
+        self.path.clone()
+    }
+
+    fn read(&self) -> Result<(Readings, RowCounts), Box<dyn Error>> {
+        let readings = remote::with_input(&self.path, read_columnar)?;
+        let read = match &readings {
+            Readings::Sessions(sessions) => sessions.len(),
+            Readings::Hourly(series) => series.len(),
+            Readings::Registers(registers) => registers.len(),
+        };
+        let counts = RowCounts {
+            read,
+            ..RowCounts::default()
+        };
+        Ok((readings, counts))
+    }
+}
+
+#[cfg(feature = "arrow")]
+fn read_columnar(path: &str) -> Result<Readings, Box<dyn Error>> {
+    crate::columnar::read_readings(path)
+}
+
+#[cfg(not(feature = "arrow"))]
+fn read_columnar(path: &str) -> Result<Readings, Box<dyn Error>> {
+    Err(format!("Cannot read {}: built without the `arrow` feature", path).into())
+}
+
+/// Sessions from a REST endpoint (`[data.http]`).
+pub struct HttpSource {
+    pub config: HttpSourceConfig,
+    pub timezone: Option<Tz>,
+    /// Keep sessions with negative energy (see `data.export`).
+    pub signed: bool,
+}
+
+impl DataSource for HttpSource {
+    fn name(&self) -> String {
+        self.config.url.clone()
+    }
+
+    #[cfg(feature = "http")]
+    fn read(&self) -> Result<(Readings, RowCounts), Box<dyn Error>> {
+        let (sessions, counts) =
+            crate::http_source::fetch_sessions(&self.config, self.timezone, self.signed)?;
+        Ok((Readings::Sessions(sessions), counts))
+    }
+
+    #[cfg(not(feature = "http"))]
+    fn read(&self) -> Result<(Readings, RowCounts), Box<dyn Error>> {
+        Err(format!(
+            "Fetching sessions from {} needs the `http` feature",
+            self.config.url
+        )
+        .into())
+    }
+}
+
+/// A Postgres/Timescale query (`[data.sql]`).
+pub struct SqlSource {
+    pub config: SqlSourceConfig,
+    pub site: String,
+    pub timezone: Option<Tz>,
+    /// Keep sessions with negative energy (see `data.export`).
+    pub signed: bool,
+}
+
+impl DataSource for SqlSource {
+    fn name(&self) -> String {
+        "the data.sql query".to_string()
+    }
+
+    #[cfg(feature = "db")]
+    fn read(&self) -> Result<(Readings, RowCounts), Box<dyn Error>> {
+        crate::db::read_postgres(&self.config, &self.site, self.timezone, self.signed)
+    }
+
+    #[cfg(not(feature = "db"))]
+    fn read(&self) -> Result<(Readings, RowCounts), Box<dyn Error>> {
+        Err("Querying Postgres needs the `db` feature".into())
+    }
+}
+
+/// A load series already in hand, e.g. a site's Kafka meter values so far.
+pub struct SeriesSource {
+    name: String,
+    series: Series,
+}
+
+impl SeriesSource {
+    pub fn new(name: &str, series: Series) -> Self {
+        Self {
+            name: name.to_string(),
+            series,
+        }
+    }
+}
+
+impl DataSource for SeriesSource {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn read(&self) -> Result<(Readings, RowCounts), Box<dyn Error>> {
+        let counts = RowCounts {
+            read: self.series.len(),
+            ..RowCounts::default()
+        };
+        Ok((Readings::Hourly(self.series.clone()), counts))
+    }
+}
+
+/// The configured source: `[data.http]`, else `[data.sql]`, else the `data.path` file.
+pub fn source(config: &Config) -> Box<dyn DataSource> {
+    if let Some(http) = &config.data.http {
+        return Box::new(HttpSource {
+            config: http.clone(),
+            timezone: config.timezone,
+            signed: config.data.export != Export::Drop,
+        });
+    }
+    if let Some(sql) = &config.data.sql {
+        return Box::new(SqlSource {
+            config: sql.clone(),
+            site: config.site.clone(),
+            timezone: config.timezone,
+            signed: config.data.export != Export::Drop,
+        });
+    }
+    match is_columnar(&config.data.path) {
+        true => Box::new(ColumnarSource {
+            path: config.data.path.clone(),
+        }),
+        false => Box::new(CsvSource {
+            path: config.data.path.clone(),
+            signed: config.data.export != Export::Drop,
+            locale: config.data.locale.clone(),
+            columns: config.data.columns.clone(),
+            site: config.site.clone(),
+            personal: config
+                .data
+                .personal
+                .as_ref()
+                .map(|personal| personal.columns.clone())
+                .unwrap_or_default(),
+        }),
+    }
+}
+
+/// A load series as the models take it: sorted, finite, one value per hour.
+///
+/// Non-finite values are dropped and counted as invalid; a series off the
+/// hourly grid (sub-hourly readings, duplicates, gaps) is resampled like
+/// `{ step = "resample" }` does, the gaps it fills recorded in `counts`.
+pub fn check_hourly(series: Series, counts: &mut RowCounts) -> Series {
+    check_grid(series, HOUR, counts)
+}
+
+/// [`check_hourly`] on a grid of `step` seconds.
+pub fn check_grid(mut series: Series, step: i64, counts: &mut RowCounts) -> Series {
+    let mut points: Vec<(i64, f64)> = series
+        .timestamps
+        .iter()
+        .copied()
+        .zip(series.values.iter().copied())
+        .collect();
+    let before = points.len();
+    points.retain(|(_, value)| value.is_finite());
+    counts.invalid += before - points.len();
+    points.sort_by_key(|(ts, _)| *ts);
+    series = Series::new(
+        points.iter().map(|p| p.0).collect(),
+        points.iter().map(|p| p.1).collect(),
+    );
+    counts.gaps.extend(points.windows(2).filter_map(|w| {
+        let (from, to) = (floor_step(w[0].0, step) + step, floor_step(w[1].0, step));
+        (to > from).then_some((from, to))
+    }));
+
+    let regular = series
+        .timestamps
+        .first()
+        .is_none_or(|ts| ts.rem_euclid(step) == 0)
+        && series.timestamps.windows(2).all(|w| w[1] - w[0] == step);
+    match regular {
+        true => series,
+        false => Pipeline::new()
+            .step(Resample::hourly())
+            .apply_at(series, step)
+            .unwrap_or_default(),
+    }
+}
@@ -0,0 +1,402 @@
+//! Project store: one SQLite file that tracks many sites.
+//!
+//! It holds each registered site's config and metadata (see [`Site`]), how
+//! far its data has been ingested (the watermark) and the hourly history
+//! last fitted on, the last structural break alerted, the models fitted for it
+//! and its most recent forecasts, so a fleet of sites runs from `--site <id>`
+//! instead of a directory of config files, archives and summaries.
+
+use rusqlite::types::Type;
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use serde::de::DeserializeOwned;
+use std::error::Error;
+
+use crate::archive::ArchivedForecast;
+use crate::config::{Config, ModelConfig};
+use crate::data::Series;
+use crate::metrics::Metrics;
+use crate::secret;
+use crate::site::Site;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS sites (
+        site TEXT PRIMARY KEY,
+        config TEXT NOT NULL,
+        registered_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS site_metadata (
+        site TEXT PRIMARY KEY,
+        metadata TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS watermarks (
+        site TEXT PRIMARY KEY,
+        last_timestamp INTEGER NOT NULL,
+        rows_read INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS models (
+        site TEXT NOT NULL,
+        run_id TEXT NOT NULL,
+        fitted_at INTEGER NOT NULL,
+        method TEXT NOT NULL,
+        model_version TEXT NOT NULL,
+        model TEXT NOT NULL,
+        hours INTEGER NOT NULL,
+        fit_seconds REAL NOT NULL,
+        metrics TEXT,
+        PRIMARY KEY (site, run_id)
+    );
+    CREATE TABLE IF NOT EXISTS forecasts (
+        site TEXT NOT NULL,
+        run_id TEXT NOT NULL,
+        issued_at INTEGER NOT NULL,
+        run TEXT NOT NULL,
+        PRIMARY KEY (site, run_id)
+    );
+    CREATE TABLE IF NOT EXISTS history (
+        site TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        value REAL NOT NULL,
+        PRIMARY KEY (site, timestamp)
+    );
+    CREATE TABLE IF NOT EXISTS breaks (
+        site TEXT PRIMARY KEY,
+        start INTEGER NOT NULL
+    );
+";
+
+/// How far a site's data has been ingested.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Watermark {
+    /// Last hour of history loaded (local wall-clock seconds).
+    pub last_timestamp: i64,
+    pub rows_read: usize,
+    /// When it was loaded (UNIX seconds).
+    pub updated_at: i64,
+}
+
+/// A model fitted for a site's forecast run.
+#[derive(Debug, Clone)]
+pub struct ModelRecord {
+    pub site: String,
+    /// The run id of the forecast it made (see [`ArchivedForecast::run_id`]).
+    pub run_id: String,
+    /// UNIX seconds.
+    pub fitted_at: i64,
+    /// The forecaster used, e.g. `prophet` or a fallback.
+    pub method: String,
+    pub model_version: String,
+    pub model: ModelConfig,
+    /// Hours of history fitted on.
+    pub hours: usize,
+    pub fit_seconds: f64,
+    pub metrics: Option<Metrics>,
+}
+
+/// A registered site and where it stands.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiteEntry {
+    pub site: String,
+    /// UNIX seconds.
+    pub registered_at: i64,
+    pub watermark: Option<Watermark>,
+    /// Run id of the latest stored forecast.
+    pub last_run: Option<String>,
+}
+
+pub struct Store {
+    path: String,
+    conn: Connection,
+}
+
+impl Store {
+    /// Open the database at `path`, creating it and its tables as needed.
+    pub fn open(path: &str) -> Result<Self, Box<dyn Error>> {
+        let conn =
+            Connection::open(path).map_err(|e| format!("Could not open store {}: {}", path, e))?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            path: path.to_string(),
+            conn,
+        })
+    }
+
+    /// Register a site under `config.site`, or replace its stored config.
+    ///
+    /// Configs with a credential written in plain text are refused, so the
+    /// store only ever holds `${env:…}` or `${file:…}` references (see
+    /// [`crate::secret`]). Returns whether the site is new.
+    pub fn register(&self, config: &Config, registered_at: i64) -> Result<bool, Box<dyn Error>> {
+        let text = toml::to_string(config)?;
+        let plaintext: Vec<String> = secret::credentials(config)
+            .into_iter()
+            .filter(|(_, value)| !secret::is_reference(value) && text.contains(value.as_str()))
+            .map(|(name, _)| name)
+            .collect();
+        if !plaintext.is_empty() {
+            return Err(format!(
+                "Site {}: {} written in plain text; use `${{env:NAME}}` or `${{file:PATH}}` so the store keeps no secrets",
+                config.site,
+                plaintext.join(", ")
+            )
+            .into());
+        }
+        let existed = self.has_site(&config.site)?;
+        self.conn.execute(
+            "INSERT INTO sites (site, config, registered_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT (site) DO UPDATE SET config = excluded.config",
+            params![config.site, text, registered_at],
+        )?;
+        Ok(!existed)
+    }
+
+    /// Forget a site and everything stored for it. Returns whether it was registered.
+    pub fn remove(&self, site: &str) -> Result<bool, Box<dyn Error>> {
+        let existed = self.has_site(site)?;
+        for table in [
+            "sites",
+            "site_metadata",
+            "watermarks",
+            "history",
+            "models",
+            "forecasts",
+        ] {
+            self.conn
+                .execute(&format!("DELETE FROM {table} WHERE site = ?1"), [site])?;
+        }
+        Ok(existed)
+    }
+
+    fn has_site(&self, site: &str) -> Result<bool, Box<dyn Error>> {
+        let found = self
+            .conn
+            .query_row("SELECT 1 FROM sites WHERE site = ?1", [site], |_| Ok(()))
+            .optional()?;
+        Ok(found.is_some())
+    }
+
+    /// A registered site's config, with `EVFORECAST_*` overrides applied.
+    pub fn config(&self, site: &str) -> Result<Config, Box<dyn Error>> {
+        let text: Option<String> = self
+            .conn
+            .query_row("SELECT config FROM sites WHERE site = ?1", [site], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        let text =
+            text.ok_or_else(|| format!("Site {} is not registered in {}", site, self.path))?;
+        Config::from_toml(&text, &format!("of site {} in {}", site, self.path))
+    }
+
+    /// Every registered site's config, by site id.
+    pub fn configs(&self) -> Result<Vec<Config>, Box<dyn Error>> {
+        self.sites()?
+            .iter()
+            .map(|entry| self.config(&entry.site))
+            .collect()
+    }
+
+    /// Every registered site, by site id.
+    pub fn sites(&self) -> Result<Vec<SiteEntry>, Box<dyn Error>> {
+        let mut statement = self.conn.prepare(
+            "SELECT sites.site, sites.registered_at, w.last_timestamp, w.rows_read, w.updated_at,
+                    (SELECT MAX(run_id) FROM forecasts f WHERE f.site = sites.site)
+             FROM sites LEFT JOIN watermarks w ON w.site = sites.site
+             ORDER BY sites.site",
+        )?;
+        let rows = statement.query_map([], |row| {
+            let last_timestamp: Option<i64> = row.get(2)?;
+            Ok(SiteEntry {
+                site: row.get(0)?,
+                registered_at: row.get(1)?,
+                watermark: match last_timestamp {
+                    Some(last_timestamp) => Some(Watermark {
+                        last_timestamp,
+                        rows_read: row.get::<_, i64>(3)? as usize,
+                        updated_at: row.get(4)?,
+                    }),
+                    None => None,
+                },
+                last_run: row.get(5)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Store a site's metadata, replacing any earlier entry.
+    pub fn save_site(&self, site: &Site) -> Result<(), Box<dyn Error>> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO site_metadata (site, metadata) VALUES (?1, ?2)",
+            params![site.id, serde_json::to_string(site)?],
+        )?;
+        Ok(())
+    }
+
+    pub fn site(&self, site: &str) -> Result<Option<Site>, Box<dyn Error>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT metadata FROM site_metadata WHERE site = ?1",
+                [site],
+                |row| json(row, 0),
+            )
+            .optional()?)
+    }
+
+    pub fn set_watermark(&self, site: &str, watermark: &Watermark) -> Result<(), Box<dyn Error>> {
+        self.conn.execute(
+            "INSERT INTO watermarks (site, last_timestamp, rows_read, updated_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (site) DO UPDATE SET
+                last_timestamp = excluded.last_timestamp,
+                rows_read = excluded.rows_read,
+                updated_at = excluded.updated_at",
+            params![site, watermark.last_timestamp, watermark.rows_read as i64, watermark.updated_at],
+        )?;
+        Ok(())
+    }
+
+    pub fn watermark(&self, site: &str) -> Result<Option<Watermark>, Box<dyn Error>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT last_timestamp, rows_read, updated_at FROM watermarks WHERE site = ?1",
+                [site],
+                |row| {
+                    Ok(Watermark {
+                        last_timestamp: row.get(0)?,
+                        rows_read: row.get::<_, i64>(1)? as usize,
+                        updated_at: row.get(2)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    /// Replace the hourly history kept for a site, to find revisions in the next one.
+    pub fn save_history(&self, site: &str, history: &Series) -> Result<(), Box<dyn Error>> {
+        let transaction = self.conn.unchecked_transaction()?;
+        transaction.execute("DELETE FROM history WHERE site = ?1", [site])?;
+        {
+            let mut insert = transaction
+                .prepare("INSERT INTO history (site, timestamp, value) VALUES (?1, ?2, ?3)")?;
+            for (timestamp, value) in history.timestamps.iter().zip(&history.values) {
+                insert.execute(params![site, timestamp, value])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// The hourly history a site was last fitted on; empty before its first run.
+    pub fn history(&self, site: &str) -> Result<Series, Box<dyn Error>> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT timestamp, value FROM history WHERE site = ?1 ORDER BY timestamp")?;
+        let rows = statement.query_map([site], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?))
+        })?;
+        let (timestamps, values): (Vec<_>, Vec<_>) =
+            rows.collect::<Result<Vec<_>, _>>()?.into_iter().unzip();
+        Ok(Series::new(timestamps, values))
+    }
+
+    /// Record that the structural break starting at `start` was alerted.
+    pub fn set_alerted_break(&self, site: &str, start: i64) -> Result<(), Box<dyn Error>> {
+        self.conn.execute(
+            "INSERT INTO breaks (site, start) VALUES (?1, ?2)
+             ON CONFLICT (site) DO UPDATE SET start = excluded.start",
+            params![site, start],
+        )?;
+        Ok(())
+    }
+
+    /// Start of the last structural break alerted for a site.
+    pub fn alerted_break(&self, site: &str) -> Result<Option<i64>, Box<dyn Error>> {
+        Ok(self
+            .conn
+            .query_row("SELECT start FROM breaks WHERE site = ?1", [site], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    pub fn save_model(&self, model: &ModelRecord) -> Result<(), Box<dyn Error>> {
+        let metrics = model
+            .metrics
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        self.conn.execute(
+            "INSERT OR REPLACE INTO models
+                (site, run_id, fitted_at, method, model_version, model, hours, fit_seconds, metrics)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                model.site,
+                model.run_id,
+                model.fitted_at,
+                model.method,
+                model.model_version,
+                serde_json::to_string(&model.model)?,
+                model.hours as i64,
+                model.fit_seconds,
+                metrics,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// A site's fitted models, newest first.
+    pub fn models(&self, site: &str) -> Result<Vec<ModelRecord>, Box<dyn Error>> {
+        let mut statement = self.conn.prepare(
+            "SELECT run_id, fitted_at, method, model_version, model, hours, fit_seconds, metrics
+             FROM models WHERE site = ?1 ORDER BY run_id DESC",
+        )?;
+        let rows = statement.query_map([site], |row| {
+            Ok(ModelRecord {
+                site: site.to_string(),
+                run_id: row.get(0)?,
+                fitted_at: row.get(1)?,
+                method: row.get(2)?,
+                model_version: row.get(3)?,
+                model: json(row, 4)?,
+                hours: row.get::<_, i64>(5)? as usize,
+                fit_seconds: row.get(6)?,
+                metrics: match row.get::<_, Option<String>>(7)? {
+                    Some(_) => Some(json(row, 7)?),
+                    None => None,
+                },
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Store a forecast run, keeping only the site's newest `keep` runs.
+    pub fn save_forecast(&self, run: &ArchivedForecast, keep: usize) -> Result<(), Box<dyn Error>> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO forecasts (site, run_id, issued_at, run) VALUES (?1, ?2, ?3, ?4)",
+            params![run.site, run.run_id, run.issued_at, serde_json::to_string(run)?],
+        )?;
+        self.conn.execute(
+            "DELETE FROM forecasts WHERE site = ?1 AND run_id NOT IN
+                (SELECT run_id FROM forecasts WHERE site = ?1 ORDER BY run_id DESC LIMIT ?2)",
+            params![run.site, keep as i64],
+        )?;
+        Ok(())
+    }
+
+    /// A site's stored forecast runs, oldest first.
+    pub fn forecasts(&self, site: &str) -> Result<Vec<ArchivedForecast>, Box<dyn Error>> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT run FROM forecasts WHERE site = ?1 ORDER BY run_id")?;
+        let rows = statement.query_map([site], |row| json(row, 0))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+}
+
+/// Decode a JSON text column.
+fn json<T: DeserializeOwned>(row: &Row, index: usize) -> rusqlite::Result<T> {
+    let text: String = row.get(index)?;
+    serde_json::from_str(&text)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, Box::new(e)))
+}
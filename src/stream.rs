@@ -0,0 +1,172 @@
+//! Rolling per-site hourly series from live meter values, e.g. off a Kafka
+//! topic (see `kafka`), and when each site is due for a new forecast.
+
+use chrono_tz::Tz;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+
+use crate::config::{KafkaConfig, MessageFormat};
+use crate::data::{Series, parse_timestamp_text};
+use crate::preprocess::{HOUR, floor_hour, hourly_timestamps};
+
+/// One power reading.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeterValue {
+    pub site: String,
+    pub connector: Option<String>,
+    /// Local wall-clock seconds, like every other timestamp in the crate.
+    pub timestamp: i64,
+    pub power_kw: f64,
+}
+
+/// Decode one message payload with the configured format and field names.
+pub fn parse_message(
+    config: &KafkaConfig,
+    timezone: Option<Tz>,
+    payload: &[u8],
+) -> Result<MeterValue, Box<dyn Error>> {
+    let fields = match config.format {
+        MessageFormat::Json => json_fields(payload)?,
+        MessageFormat::Csv => csv_fields(config, payload)?,
+    };
+    let field = |name: &str| {
+        fields
+            .get(name)
+            .ok_or_else(|| format!("No {:?} field", name))
+    };
+    let timestamp = field(&config.timestamp_field)?;
+    let power = field(&config.power_field)?;
+    Ok(MeterValue {
+        site: field(&config.site_field)?.clone(),
+        connector: match &config.connector_field {
+            Some(name) => Some(field(name)?.clone()),
+            None => None,
+        },
+        timestamp: parse_timestamp_text(timestamp, timezone)
+            .ok_or_else(|| format!("Invalid timestamp {:?}", timestamp))?,
+        power_kw: power
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid power {:?}", power))?,
+    })
+}
+
+/// Top-level fields of a JSON object, as text.
+fn json_fields(payload: &[u8]) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let object: serde_json::Map<String, serde_json::Value> = serde_json::from_slice(payload)?;
+    Ok(object
+        .into_iter()
+        .map(|(name, value)| match value {
+            serde_json::Value::String(text) => (name, text),
+            other => (name, other.to_string()),
+        })
+        .collect())
+}
+
+fn csv_fields(
+    config: &KafkaConfig,
+    payload: &[u8],
+) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(payload);
+    let record = reader.records().next().ok_or("Empty message")??;
+    Ok(config
+        .csv_columns
+        .iter()
+        .zip(record.iter())
+        .map(|(name, value)| (name.clone(), value.to_string()))
+        .collect())
+}
+
+/// One site's readings, summed and counted per hour and connector.
+#[derive(Debug, Default)]
+struct SiteStream {
+    hours: BTreeMap<i64, HashMap<String, (f64, usize)>>,
+    /// Start of the series: the oldest reading's hour, or the window start.
+    first_hour: i64,
+    /// Hour of the newest reading; every hour before it is complete.
+    latest_hour: i64,
+    /// Hours completed since the last forecast.
+    new_hours: usize,
+}
+
+/// Rolling hourly load of every site seen on the stream.
+#[derive(Debug)]
+pub struct SiteStreams {
+    refit_after_hours: usize,
+    window_hours: usize,
+    sites: BTreeMap<String, SiteStream>,
+}
+
+impl SiteStreams {
+    pub fn new(config: &KafkaConfig) -> Self {
+        Self {
+            refit_after_hours: config.refit_after_hours.max(1),
+            window_hours: config.window_hours.max(1),
+            sites: BTreeMap::new(),
+        }
+    }
+
+    /// Add a reading; ones older than the window are dropped.
+    pub fn push(&mut self, value: MeterValue) {
+        let hour = floor_hour(value.timestamp);
+        let window = self.window_hours as i64 * HOUR;
+        let stream = self.sites.entry(value.site).or_insert_with(|| SiteStream {
+            first_hour: hour,
+            latest_hour: hour,
+            ..SiteStream::default()
+        });
+        if hour <= stream.latest_hour - window {
+            return;
+        }
+        if hour > stream.latest_hour {
+            stream.new_hours += ((hour - stream.latest_hour) / HOUR) as usize;
+            stream.latest_hour = hour;
+            stream.first_hour = stream.first_hour.max(hour - window + HOUR);
+            stream.hours = stream.hours.split_off(&stream.first_hour);
+        }
+        stream.first_hour = stream.first_hour.min(hour);
+        let reading = stream
+            .hours
+            .entry(hour)
+            .or_default()
+            .entry(value.connector.unwrap_or_default())
+            .or_default();
+        reading.0 += value.power_kw;
+        reading.1 += 1;
+    }
+
+    /// Sites with at least `refit_after_hours` new complete hours.
+    pub fn ready(&self) -> Vec<String> {
+        self.sites
+            .iter()
+            .filter(|(_, stream)| stream.new_hours >= self.refit_after_hours)
+            .map(|(site, _)| site.clone())
+            .collect()
+    }
+
+    /// Complete hours of a site's load, in kW; hours without readings are zero.
+    pub fn series(&self, site: &str) -> Series {
+        let Some(stream) = self.sites.get(site) else {
+            return Series::default();
+        };
+        let first = stream.first_hour;
+        let n_hours = ((stream.latest_hour - first) / HOUR) as usize;
+        let mut values = vec![0.0; n_hours];
+        for (hour, connectors) in stream.hours.range(..stream.latest_hour) {
+            values[((hour - first) / HOUR) as usize] = connectors
+                .values()
+                .map(|(sum, count)| sum / *count as f64)
+                .sum();
+        }
+        Series::new(hourly_timestamps(first, n_hours), values)
+    }
+
+    /// Restart a site's count of new hours after forecasting it.
+    pub fn mark_forecast(&mut self, site: &str) {
+        if let Some(stream) = self.sites.get_mut(site) {
+            stream.new_hours = 0;
+        }
+    }
+}
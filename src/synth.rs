@@ -0,0 +1,148 @@
+//! Synthetic charging sessions, for tests, benchmarks and demos without real site exports.
+//!
+//! Arrivals are Poisson with a morning and an evening peak, fewer at
+//! weekends, growing over time and scaled by a day-to-day noise factor.
+//! Random outages leave whole stretches without sessions.
+
+use chrono::{DateTime, Datelike, NaiveDate};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Distribution, Gamma, Poisson};
+use std::error::Error;
+
+use crate::config::SynthConfig;
+use crate::data::{Series, Session, format_timestamp};
+use crate::export;
+use crate::preprocess::{self, HOUR};
+
+/// Relative arrival rate by hour of day, summing to one.
+fn daily_profile(config: &SynthConfig) -> [f64; 24] {
+    let peak =
+        |hour: f64, centre: f64, width: f64| (-0.5 * ((hour - centre) / width).powi(2)).exp();
+    let mut profile = [0.0; 24];
+    for (hour, weight) in profile.iter_mut().enumerate() {
+        let hour = hour as f64 + 0.5;
+        *weight = 0.1
+            + peak(hour, config.morning_peak_hour as f64, 1.5)
+            + 1.3 * peak(hour, config.evening_peak_hour as f64, 2.0);
+    }
+    let total: f64 = profile.iter().sum();
+    profile.map(|weight| weight / total)
+}
+
+/// Gamma-distributed factor with mean one and the given coefficient of variation.
+fn gamma_factor(cv: f64) -> Result<Option<Gamma<f64>>, Box<dyn Error>> {
+    if cv <= 0.0 {
+        return Ok(None);
+    }
+    let shape = 1.0 / (cv * cv);
+    Ok(Some(Gamma::new(shape, 1.0 / shape)?))
+}
+
+/// Generate sessions for `config.days` days from `config.start`.
+pub fn sessions(config: &SynthConfig) -> Result<Vec<Session>, Box<dyn Error>> {
+    let start = NaiveDate::parse_from_str(&config.start, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid synth.start {:?}: {}", config.start, e))?;
+    if config.charger_kw <= 0.0 || config.mean_duration_minutes <= 0.0 {
+        return Err("synth.charger_kw and synth.mean_duration_minutes must be positive".into());
+    }
+    let origin = start
+        .and_hms_opt(0, 0, 0)
+        .unwrap_or_default()
+        .and_utc()
+        .timestamp();
+    let profile = daily_profile(config);
+    let noise = gamma_factor(config.noise)?;
+    let duration = Gamma::new(2.0, config.mean_duration_minutes / 2.0)?;
+    let mut rng = StdRng::seed_from_u64(config.seed);
+
+    let mut sessions = Vec::new();
+    let mut outage_until = i64::MIN;
+    for day in 0..config.days as i64 {
+        let day_start = origin + day * 24 * HOUR;
+        let weekday = DateTime::from_timestamp(day_start, 0)
+            .unwrap_or_default()
+            .weekday();
+        let mut rate =
+            config.sessions_per_day * (1.0 + config.growth_per_year).powf(day as f64 / 365.0);
+        if weekday.number_from_monday() >= 6 {
+            rate *= config.weekend_factor;
+        }
+        if let Some(noise) = &noise {
+            rate *= noise.sample(&mut rng);
+        }
+        if rng.gen_bool(config.outage_probability.clamp(0.0, 1.0)) {
+            let hours = rng.gen_range(1..=config.max_outage_hours.max(1)) as i64;
+            outage_until = day_start + rng.gen_range(0..24) * HOUR + hours * HOUR;
+        }
+
+        for (hour, weight) in profile.iter().enumerate() {
+            let hour_start = day_start + hour as i64 * HOUR;
+            let lambda = rate * weight;
+            if hour_start < outage_until || lambda <= 0.0 {
+                continue;
+            }
+            let arrivals = Poisson::new(lambda)?.sample(&mut rng) as usize;
+            for _ in 0..arrivals {
+                // Minute resolution, like the site exports
+                let start = hour_start + rng.gen_range(0..60) * 60;
+                let minutes = duration.sample(&mut rng).round().max(1.0);
+                let average_kw = config.charger_kw * rng.gen_range(0.3..0.9);
+                sessions.push(Session {
+                    start,
+                    stop: start + minutes as i64 * 60,
+                    energy_wh: average_kw * minutes / 60.0 * 1000.0,
+                });
+            }
+        }
+    }
+    sessions.sort_by_key(|s| s.start);
+    if sessions.is_empty() {
+        return Err("The synthetic settings produced no sessions".into());
+    }
+    Ok(sessions)
+}
+
+/// Hourly load (kW) of [`sessions`].
+pub fn demand(config: &SynthConfig) -> Result<Series, Box<dyn Error>> {
+    Ok(preprocess::hourly_load(&sessions(config)?))
+}
+
+/// Write sessions in the site export layout, so [`crate::data::load_sessions`] reads them back.
+pub fn write_sessions_csv(
+    sessions: &[Session],
+    config: &SynthConfig,
+    path: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_writer(export::create(path)?);
+    writer.write_record([
+        "Created",
+        "Start time",
+        "Count.Stop time",
+        "Count.Duration",
+        "Max power(kW)",
+        "Index",
+        "Day of Week",
+        "Modified Count.Energy (Wh)",
+    ])?;
+    // Spread sessions round-robin over the site's charge points
+    let chargers = config.chargers.max(1) as usize;
+    for (i, session) in sessions.iter().enumerate() {
+        let start = format_timestamp(session.start);
+        let weekday = DateTime::from_timestamp(session.start, 0)
+            .unwrap_or_default()
+            .weekday();
+        writer.write_record([
+            start.clone(),
+            start,
+            format_timestamp(session.stop),
+            ((session.stop - session.start) / 60).to_string(),
+            format!("{}", config.charger_kw),
+            (1 + i % chargers).to_string(),
+            weekday.num_days_from_sunday().to_string(),
+            format!("{:.0}", session.energy_wh),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,206 @@
+//! Capacity utilization of a site's installed charging power: how much of it
+//! the forecast uses, how often it runs close to full, and when the growth
+//! in daily peaks would saturate it.
+
+use csv::Writer;
+use serde::Serialize;
+use std::error::Error;
+
+use crate::config::UtilizationConfig;
+use crate::data::{Series, format_timestamp};
+use crate::export;
+use crate::forecast::Forecast;
+use crate::preprocess::HOUR;
+
+/// Fewest days of daily peaks a growth trend is fitted on.
+const MIN_TREND_DAYS: usize = 14;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UtilizationReport {
+    pub installed_kw: f64,
+    /// Share of `installed_kw` counted as running close to full (0–1).
+    pub threshold: f64,
+    pub mean_utilization_pct: f64,
+    pub peak_utilization_pct: f64,
+    /// Hour of the peak, `YYYY-MM-DD HH:MM`.
+    pub peak_at: Option<String>,
+    /// Forecast hours whose point forecast is above `threshold`.
+    pub hours_above_threshold: f64,
+    /// Forecast hours whose upper bound is.
+    pub hours_above_threshold_upper: f64,
+    /// Left out when the history is too short to tell a trend.
+    pub saturation: Option<Saturation>,
+}
+
+/// Linear trend of the history's daily peaks, extrapolated to the installed power.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Saturation {
+    /// Days of history the trend is fitted on.
+    pub days: usize,
+    pub growth_kw_per_day: f64,
+    /// The trend's daily peak on the last day of history.
+    pub trend_peak_kw: f64,
+    /// Until the trend's daily peak reaches `threshold` of the installed power;
+    /// `None` when peaks aren't growing.
+    pub days_to_threshold: Option<f64>,
+    /// Until it reaches all of it.
+    pub days_to_saturation: Option<f64>,
+}
+
+/// `kw` as a percentage of `installed_kw`.
+pub fn utilization_pct(kw: f64, installed_kw: f64) -> f64 {
+    100.0 * kw / installed_kw
+}
+
+impl UtilizationReport {
+    pub fn new(
+        history: &Series,
+        forecast: &Forecast,
+        installed_kw: f64,
+        config: &UtilizationConfig,
+    ) -> Self {
+        let limit = config.threshold * installed_kw;
+        let peak = forecast
+            .point
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(i, kw)| (forecast.timestamps[i], *kw));
+        let mean = forecast.point.iter().sum::<f64>() / forecast.len().max(1) as f64;
+        let hours_above =
+            |kw: &[f64]| kw.iter().filter(|kw| **kw > limit).count() as f64 * forecast.step_hours();
+        Self {
+            installed_kw,
+            threshold: config.threshold,
+            mean_utilization_pct: utilization_pct(mean, installed_kw),
+            peak_utilization_pct: utilization_pct(peak.map_or(0.0, |(_, kw)| kw), installed_kw),
+            peak_at: peak.map(|(timestamp, _)| format_timestamp(timestamp)),
+            hours_above_threshold: hours_above(&forecast.point),
+            hours_above_threshold_upper: hours_above(&forecast.upper),
+            saturation: saturation(history, installed_kw, config),
+        }
+    }
+
+    /// One line per KPI, for stderr.
+    pub fn summary(&self) -> Vec<String> {
+        let threshold = 100.0 * self.threshold;
+        let mut lines = vec![
+            format!(
+                "Utilization of {:.0} kW installed: mean {:.1}%, peak {:.1}%{}",
+                self.installed_kw,
+                self.mean_utilization_pct,
+                self.peak_utilization_pct,
+                self.peak_at
+                    .as_ref()
+                    .map_or(String::new(), |at| format!(" at {}", at))
+            ),
+            format!(
+                "{} forecast hours above {:.0}% ({} by the upper bound)",
+                self.hours_above_threshold, threshold, self.hours_above_threshold_upper
+            ),
+        ];
+        match &self.saturation {
+            Some(Saturation {
+                growth_kw_per_day,
+                days_to_threshold: Some(days),
+                days_to_saturation,
+                ..
+            }) => lines.push(format!(
+                "Daily peaks growing {:+.2} kW/day: {:.0}% in {:.0} days, saturated in {}",
+                growth_kw_per_day,
+                threshold,
+                days,
+                days_to_saturation.map_or("-".to_string(), |days| format!("{:.0} days", days))
+            )),
+            Some(saturation) => lines.push(format!(
+                "Daily peaks not growing ({:+.2} kW/day): no saturation in sight",
+                saturation.growth_kw_per_day
+            )),
+            None => {}
+        }
+        lines
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        export::write_text(path, &serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Trend of the daily peaks over the last `config.trend_days` days of history.
+pub fn saturation(
+    history: &Series,
+    installed_kw: f64,
+    config: &UtilizationConfig,
+) -> Option<Saturation> {
+    let mut days: Vec<(i64, f64, usize)> = Vec::new();
+    for (timestamp, kw) in history.timestamps.iter().zip(&history.values) {
+        let day = timestamp.div_euclid(24 * HOUR);
+        match days.last_mut() {
+            Some((last, peak, points)) if *last == day => {
+                *peak = peak.max(*kw);
+                *points += 1;
+            }
+            _ => days.push((day, *kw, 1)),
+        }
+    }
+    // A day still in progress hasn't had its peak yet
+    let per_day = (24 * HOUR / history.step().max(1)) as usize;
+    let peaks: Vec<(i64, f64)> = days
+        .into_iter()
+        .filter(|(_, _, points)| *points == per_day)
+        .map(|(day, peak, _)| (day, peak))
+        .collect();
+    let peaks = &peaks[peaks.len().saturating_sub(config.trend_days)..];
+    if peaks.len() < MIN_TREND_DAYS {
+        return None;
+    }
+
+    // Least squares over the day numbers
+    let n = peaks.len() as f64;
+    let mean_x = peaks.iter().map(|(day, _)| *day as f64).sum::<f64>() / n;
+    let mean_y = peaks.iter().map(|(_, kw)| kw).sum::<f64>() / n;
+    let sxx: f64 = peaks
+        .iter()
+        .map(|(day, _)| (*day as f64 - mean_x).powi(2))
+        .sum();
+    let sxy: f64 = peaks
+        .iter()
+        .map(|(day, kw)| (*day as f64 - mean_x) * (kw - mean_y))
+        .sum();
+    let slope = sxy / sxx;
+    let last_day = peaks.last()?.0 as f64;
+    let trend_peak_kw = mean_y + slope * (last_day - mean_x);
+    let days_to = |kw: f64| match trend_peak_kw >= kw {
+        true => Some(0.0),
+        false => (slope > 0.0).then(|| (kw - trend_peak_kw) / slope),
+    };
+    Some(Saturation {
+        days: peaks.len(),
+        growth_kw_per_day: slope,
+        trend_peak_kw,
+        days_to_threshold: days_to(config.threshold * installed_kw),
+        days_to_saturation: days_to(installed_kw),
+    })
+}
+
+/// Write the forecast as utilization, one row per hour:
+/// `timestamp,utilization_pct,utilization_lower_pct,utilization_upper_pct`.
+pub fn write_csv(forecast: &Forecast, installed_kw: f64, path: &str) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_writer(export::create(path)?);
+    wtr.write_record([
+        "timestamp",
+        "utilization_pct",
+        "utilization_lower_pct",
+        "utilization_upper_pct",
+    ])?;
+    for i in 0..forecast.len() {
+        wtr.write_record([
+            format_timestamp(forecast.timestamps[i]),
+            format!("{:.2}", utilization_pct(forecast.point[i], installed_kw)),
+            format!("{:.2}", utilization_pct(forecast.lower[i], installed_kw)),
+            format!("{:.2}", utilization_pct(forecast.upper[i], installed_kw)),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
@@ -0,0 +1,72 @@
+//! Vehicle-to-grid discharge potential from the plugged-in occupancy forecast.
+
+use csv::Writer;
+use std::error::Error;
+
+use crate::config::V2gConfig;
+use crate::data::format_timestamp;
+use crate::export;
+use crate::forecast::Forecast;
+
+/// Forecast occupancy and the aggregate discharge capacity it allows, in kW.
+#[derive(Debug, Clone, PartialEq)]
+pub struct V2gPotential {
+    pub occupancy: Forecast,
+    pub capacity: Forecast,
+}
+
+/// Aggregate discharge capacity for a given number of plugged-in vehicles.
+pub fn capacity_kw(occupancy: f64, config: &V2gConfig) -> f64 {
+    let vehicles = match config.chargers {
+        Some(chargers) => occupancy.min(chargers as f64),
+        None => occupancy,
+    };
+    vehicles.max(0.0) * config.participation.clamp(0.0, 1.0) * config.discharge_limit_kw
+}
+
+/// Turn an occupancy forecast into a V2G capacity forecast.
+///
+/// The occupancy bounds carry straight through, so the capacity interval
+/// reflects the uncertainty in how many vehicles will be connected.
+pub fn estimate(occupancy: Forecast, config: &V2gConfig) -> V2gPotential {
+    let scale = |v: &[f64]| {
+        v.iter()
+            .map(|o| capacity_kw(*o, config))
+            .collect::<Vec<_>>()
+    };
+    let capacity = Forecast {
+        timestamps: occupancy.timestamps.clone(),
+        point: scale(&occupancy.point),
+        lower: scale(&occupancy.lower),
+        upper: scale(&occupancy.upper),
+    };
+    V2gPotential {
+        occupancy,
+        capacity,
+    }
+}
+
+/// Write the V2G series as CSV, one row per hour.
+pub fn write_potential_csv(potential: &V2gPotential, path: &str) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_writer(export::create(path)?);
+    wtr.write_record([
+        "timestamp",
+        "occupancy",
+        "v2g_kw",
+        "v2g_lower_kw",
+        "v2g_upper_kw",
+    ])?;
+    let capacity = &potential.capacity;
+    for i in 0..capacity.len() {
+        wtr.write_record([
+            format_timestamp(capacity.timestamps[i]),
+            format!("{:.3}", potential.occupancy.point[i]),
+            format!("{:.3}", capacity.point[i]),
+            format!("{:.3}", capacity.lower[i]),
+            format!("{:.3}", capacity.upper[i]),
+        ])?;
+    }
+    wtr.flush()?;
+    eprintln!("V2G capacity written to {}", path);
+    Ok(())
+}
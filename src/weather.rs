@@ -0,0 +1,322 @@
+//! Weather-normalized actuals: the history as it would have been in a month
+//! of usual temperatures, so month-over-month comparisons of a site aren't
+//! dominated by a cold snap.
+//!
+//! Daily energy is regressed on the weekday and on heating and cooling degree
+//! days of the daily mean temperature, as energy performance reporting does.
+//! Each day is then moved by the fitted effect of its degree days against the
+//! mean degree days of its calendar month in the temperature record, its hours
+//! scaled alike.
+
+use chrono::{DateTime, Datelike};
+use chrono_tz::Tz;
+use csv::{ReaderBuilder, Writer};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::config::WeatherConfig;
+use crate::data::{self, Series, format_timestamp, parse_timestamp_text};
+use crate::export;
+use crate::metrics::least_squares;
+use crate::preprocess::HOUR;
+
+const DAY: i64 = 24 * HOUR;
+
+/// Fewest complete days with a temperature the effect is estimated on.
+const MIN_FIT_DAYS: usize = 28;
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Daily mean temperatures (°C) of a `timestamp,temperature` CSV, by local day start.
+pub fn load_temperatures(
+    path: &str,
+    timezone: Option<Tz>,
+) -> Result<BTreeMap<i64, f64>, Box<dyn Error>> {
+    let mut rdr = ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data::open(path)?);
+    let headers = rdr.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h == name)
+            .ok_or_else(|| format!("{} has no `{}` column", path, name))
+    };
+    let (ts_column, temperature_column) = (column("timestamp")?, column("temperature")?);
+    let mut days: BTreeMap<i64, (f64, usize)> = BTreeMap::new();
+    for (i, record) in rdr.records().enumerate() {
+        let record = record?;
+        let field = |c: usize| record.get(c).unwrap_or_default();
+        let ts = parse_timestamp_text(field(ts_column), timezone).ok_or_else(|| {
+            format!(
+                "{} line {}: bad timestamp {:?}",
+                path,
+                i + 2,
+                field(ts_column)
+            )
+        })?;
+        // Gaps in the readings are left out rather than failing
+        let Ok(temperature) = field(temperature_column).parse::<f64>() else {
+            continue;
+        };
+        let day = days.entry(ts.div_euclid(DAY) * DAY).or_default();
+        day.0 += temperature;
+        day.1 += 1;
+    }
+    Ok(days
+        .into_iter()
+        .map(|(day, (sum, n))| (day, sum / n as f64))
+        .collect())
+}
+
+fn month_of(ts: i64) -> (i32, u32) {
+    let dt = DateTime::from_timestamp(ts, 0).unwrap_or_default();
+    (dt.year(), dt.month())
+}
+
+/// Fitted effect of the weekday and the temperature on a site's daily energy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeatherModel {
+    /// Energy of a day without degree days, Monday first.
+    pub weekday_kwh: [f64; 7],
+    /// Extra energy per heating degree day; 0 when the record has none.
+    pub heating_kwh: f64,
+    /// Extra energy per cooling degree day; 0 when the record has none.
+    pub cooling_kwh: f64,
+    /// Days the model was fitted on.
+    pub days: usize,
+    pub heating_base_c: f64,
+    pub cooling_base_c: f64,
+}
+
+impl WeatherModel {
+    /// Heating and cooling degree days of a daily mean temperature.
+    pub fn degree_days(&self, temperature: f64) -> (f64, f64) {
+        (
+            (self.heating_base_c - temperature).max(0.0),
+            (temperature - self.cooling_base_c).max(0.0),
+        )
+    }
+
+    /// Fit on the complete days of `history` that have a temperature.
+    pub fn fit(
+        history: &Series,
+        temperatures: &BTreeMap<i64, f64>,
+        config: &WeatherConfig,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut model = Self {
+            weekday_kwh: [0.0; 7],
+            heating_kwh: 0.0,
+            cooling_kwh: 0.0,
+            days: 0,
+            heating_base_c: config.heating_base_c,
+            cooling_base_c: config.cooling_base_c,
+        };
+        let per_day = (DAY / history.step().max(1)) as usize;
+        let rows: Vec<(usize, (f64, f64), f64)> = daily_energy(history)
+            .into_iter()
+            .filter(|(_, _, points)| *points == per_day)
+            .filter_map(|(day, kwh, _)| {
+                let weekday = DateTime::from_timestamp(day, 0)?
+                    .weekday()
+                    .num_days_from_monday() as usize;
+                Some((weekday, model.degree_days(*temperatures.get(&day)?), kwh))
+            })
+            .collect();
+        if rows.len() < MIN_FIT_DAYS {
+            return Err(format!("Only {} complete days of history have a temperature; weather normalization needs {}", rows.len(), MIN_FIT_DAYS).into());
+        }
+        if let Some(day) = (0..7).find(|d| !rows.iter().any(|r| r.0 == *d)) {
+            return Err(format!(
+                "No {} in the history with a temperature to fit the weather effect on",
+                WEEKDAYS[day]
+            )
+            .into());
+        }
+
+        // Least squares on weekday dummies, plus whichever degree days occur
+        let heating = rows.iter().any(|r| r.1.0 > 0.0);
+        let cooling = rows.iter().any(|r| r.1.1 > 0.0);
+        let features = |r: &(usize, (f64, f64), f64)| {
+            let mut x: Vec<f64> = (0..7).map(|d| if d == r.0 { 1.0 } else { 0.0 }).collect();
+            x.extend(heating.then_some(r.1.0));
+            x.extend(cooling.then_some(r.1.1));
+            x
+        };
+        let x: Vec<Vec<f64>> = rows.iter().map(features).collect();
+        let y: Vec<f64> = rows.iter().map(|r| r.2).collect();
+        let beta = least_squares(&x, &y)
+            .ok_or("The temperatures don't vary enough to tell their effect on demand")?;
+        model.weekday_kwh.copy_from_slice(&beta[..7]);
+        let mut rest = beta[7..].iter();
+        if heating {
+            model.heating_kwh = *rest.next().unwrap_or(&0.0);
+        }
+        if cooling {
+            model.cooling_kwh = *rest.next().unwrap_or(&0.0);
+        }
+        model.days = rows.len();
+        Ok(model)
+    }
+
+    /// The energy effect of a day's temperature against `normal` degree days.
+    fn effect(&self, temperature: f64, normal: (f64, f64)) -> f64 {
+        let (heating, cooling) = self.degree_days(temperature);
+        self.heating_kwh * (heating - normal.0) + self.cooling_kwh * (cooling - normal.1)
+    }
+}
+
+/// Energy (kWh) and points of each day of a series, by day start.
+fn daily_energy(history: &Series) -> Vec<(i64, f64, usize)> {
+    let hours = history.step() as f64 / HOUR as f64;
+    let mut days: Vec<(i64, f64, usize)> = Vec::new();
+    for (ts, kw) in history.timestamps.iter().zip(&history.values) {
+        let kwh = kw * hours;
+        let day = ts.div_euclid(DAY) * DAY;
+        match days.last_mut() {
+            Some(last) if last.0 == day => {
+                last.1 += kwh;
+                last.2 += 1;
+            }
+            _ => days.push((day, kwh, 1)),
+        }
+    }
+    days
+}
+
+/// One month of actual and weather-normalized energy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonthlyNormalized {
+    /// `YYYY-MM`.
+    pub month: String,
+    pub actual_kwh: f64,
+    pub normalized_kwh: f64,
+    pub heating_degree_days: f64,
+    pub cooling_degree_days: f64,
+}
+
+/// A normalized history, with what went into it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Normalization {
+    pub model: WeatherModel,
+    pub actual: Series,
+    /// The same hours with the temperature effect against normal removed.
+    pub normalized: Series,
+    /// Days left as they were for want of a temperature.
+    pub days_without_temperature: usize,
+    pub months: Vec<MonthlyNormalized>,
+}
+
+/// Normalize `history` to the usual temperatures of each calendar month.
+pub fn normalize(
+    history: &Series,
+    temperatures: &BTreeMap<i64, f64>,
+    config: &WeatherConfig,
+) -> Result<Normalization, Box<dyn Error>> {
+    let model = WeatherModel::fit(history, temperatures, config)?;
+
+    // Normal degree days: the mean of each calendar month over the whole temperature record
+    let mut sums: BTreeMap<u32, (f64, f64, usize)> = BTreeMap::new();
+    for (day, temperature) in temperatures {
+        let (heating, cooling) = model.degree_days(*temperature);
+        let sum = sums.entry(month_of(*day).1).or_default();
+        sum.0 += heating;
+        sum.1 += cooling;
+        sum.2 += 1;
+    }
+    let normal = |day: i64| {
+        sums.get(&month_of(day).1)
+            .map_or((0.0, 0.0), |s| (s.0 / s.2 as f64, s.1 / s.2 as f64))
+    };
+
+    let mut values = history.values.to_vec();
+    let mut days_without_temperature = 0;
+    let mut months: Vec<MonthlyNormalized> = Vec::new();
+    let mut i = 0;
+    for (day, kwh, points) in daily_energy(history) {
+        let (factor, degree_days) = match temperatures.get(&day) {
+            Some(temperature) if kwh > 0.0 => (
+                (kwh - model.effect(*temperature, normal(day))).max(0.0) / kwh,
+                model.degree_days(*temperature),
+            ),
+            Some(temperature) => (1.0, model.degree_days(*temperature)),
+            None => {
+                days_without_temperature += 1;
+                (1.0, (0.0, 0.0))
+            }
+        };
+        for value in &mut values[i..i + points] {
+            *value *= factor;
+        }
+        i += points;
+
+        let (year, month) = month_of(day);
+        let month = format!("{}-{:02}", year, month);
+        if months.last().is_none_or(|m| m.month != month) {
+            months.push(MonthlyNormalized {
+                month,
+                actual_kwh: 0.0,
+                normalized_kwh: 0.0,
+                heating_degree_days: 0.0,
+                cooling_degree_days: 0.0,
+            });
+        }
+        if let Some(last) = months.last_mut() {
+            last.actual_kwh += kwh;
+            last.normalized_kwh += kwh * factor;
+            last.heating_degree_days += degree_days.0;
+            last.cooling_degree_days += degree_days.1;
+        }
+    }
+    Ok(Normalization {
+        model,
+        actual: history.clone(),
+        normalized: Series::from_buffers(history.timestamps.clone(), values.into()),
+        days_without_temperature,
+        months,
+    })
+}
+
+/// Write the hourly history as CSV: `timestamp,actual_kw,normalized_kw`.
+pub fn write_csv(normalization: &Normalization, path: &str) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_writer(export::create(path)?);
+    wtr.write_record(["timestamp", "actual_kw", "normalized_kw"])?;
+    let (actual, normalized) = (&normalization.actual, &normalization.normalized);
+    for ((ts, a), n) in actual
+        .timestamps
+        .iter()
+        .zip(&actual.values)
+        .zip(&normalized.values)
+    {
+        wtr.write_record([
+            format_timestamp(*ts),
+            format!("{:.2}", a),
+            format!("{:.2}", n),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Write the months as CSV: `month,actual_kwh,normalized_kwh,heating_degree_days,cooling_degree_days`.
+pub fn write_monthly_csv(months: &[MonthlyNormalized], path: &str) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_writer(export::create(path)?);
+    wtr.write_record([
+        "month",
+        "actual_kwh",
+        "normalized_kwh",
+        "heating_degree_days",
+        "cooling_degree_days",
+    ])?;
+    for m in months {
+        wtr.write_record([
+            m.month.clone(),
+            format!("{:.1}", m.actual_kwh),
+            format!("{:.1}", m.normalized_kwh),
+            format!("{:.1}", m.heating_degree_days),
+            format!("{:.1}", m.cooling_degree_days),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
@@ -0,0 +1,181 @@
+//! Models published for gateways, and predicting from them.
+
+use cpo_charging_forecast::artifact::{self, PublishedModel};
+use cpo_charging_forecast::config::{Method, ModelConfig, Transform};
+use cpo_charging_forecast::data::Series;
+use cpo_charging_forecast::forecast::{Forecaster, fit};
+
+/// 2024-01-01 00:00, a Monday.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+
+/// A daily cycle, busier on weekdays, on a rising trend.
+fn history(hours: i64) -> Series {
+    let load = |h: i64| {
+        let daily = 8.0 * (2.0 * std::f64::consts::PI * (h % 24) as f64 / 24.0).sin();
+        let weekday = if (h / 24) % 7 < 5 { 4.0 } else { -6.0 };
+        30.0 + 2.0 * h as f64 / 168.0 + daily + weekday
+    };
+    Series::new(
+        (0..hours).map(|h| ORIGIN + h * HOUR).collect(),
+        (0..hours).map(load).collect(),
+    )
+}
+
+fn future(from: i64, hours: i64) -> Vec<i64> {
+    (from..from + hours).map(|h| ORIGIN + h * HOUR).collect()
+}
+
+/// The published model predicts what the fitted one does.
+fn assert_matches(model: &dyn Forecaster, published: &PublishedModel, timestamps: Vec<i64>) {
+    let forecast = model.predict(timestamps.clone()).unwrap();
+    for (i, point) in published.predict_all(&timestamps).iter().enumerate() {
+        assert!(
+            (point.point - forecast.point[i]).abs() < 1e-4,
+            "{} against {} kW",
+            point.point,
+            forecast.point[i]
+        );
+        assert!(point.lower <= point.point && point.point <= point.upper);
+    }
+}
+
+#[test]
+fn fourier_fits_publish_and_round_trip() {
+    let history = history(6 * 168);
+    let config = ModelConfig {
+        method: Method::Fourier,
+        ..ModelConfig::default()
+    };
+    let model = fit(&history, &config).unwrap();
+    let published =
+        artifact::export(model.as_ref(), &config, "north", future(6 * 168, 48)).unwrap();
+    assert_eq!(
+        (
+            published.method.as_str(),
+            published.fitted_to,
+            published.step
+        ),
+        ("fourier", ORIGIN + (6 * 168 - 1) * HOUR, HOUR)
+    );
+    assert_eq!(
+        published
+            .seasonalities
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>(),
+        ["daily", "weekly"]
+    );
+    assert_eq!(published.intervals.len(), 48);
+    // Beyond the published horizon the trend and the last interval carry on
+    assert_matches(model.as_ref(), &published, future(6 * 168, 168));
+
+    let path = std::env::temp_dir().join(format!("cpo-artifact-{}.json", std::process::id()));
+    let path = path.to_str().unwrap();
+    artifact::save(&published, path, None).unwrap();
+    let loaded = artifact::load(path, None).unwrap();
+    assert_eq!(
+        (loaded.trend.len(), loaded.intervals.len()),
+        (published.trend.len(), published.intervals.len())
+    );
+    assert_matches(model.as_ref(), &loaded, future(6 * 168, 48));
+    std::fs::remove_file(path).ok();
+}
+
+#[cfg(feature = "wasmstan")]
+#[test]
+fn prophet_fits_publish_with_multiplicative_seasonality() {
+    let history = history(6 * 168);
+    let config = ModelConfig::default();
+    let model = fit(&history, &config).unwrap();
+    assert_eq!(model.name(), "prophet");
+    let published =
+        artifact::export(model.as_ref(), &config, "north", future(6 * 168, 168)).unwrap();
+    assert!(published.seasonalities.iter().all(|s| s.multiplicative));
+    assert!(published.trend.len() > 2, "{:?}", published.trend);
+    assert_matches(model.as_ref(), &published, future(6 * 168, 168));
+}
+
+#[test]
+fn only_the_trend_and_seasonalities_publish() {
+    let history = history(6 * 168);
+    let fourier = ModelConfig {
+        method: Method::Fourier,
+        ..ModelConfig::default()
+    };
+    let transformed = ModelConfig {
+        transform: Transform::Log1p,
+        ..fourier.clone()
+    };
+    let model = fit(&history, &transformed).unwrap();
+    let error = artifact::export(model.as_ref(), &transformed, "north", future(6 * 168, 24))
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("model.transform"), "{}", error);
+
+    let tsb = ModelConfig {
+        method: Method::Tsb,
+        ..fourier
+    };
+    let model = fit(&history, &tsb).unwrap();
+    assert!(artifact::export(model.as_ref(), &tsb, "north", future(6 * 168, 24)).is_err());
+}
+
+#[test]
+fn signed_models_load_only_unaltered_and_with_their_key() {
+    let history = history(4 * 168);
+    let config = ModelConfig {
+        method: Method::Fourier,
+        ..ModelConfig::default()
+    };
+    let model = fit(&history, &config).unwrap();
+    let published =
+        artifact::export(model.as_ref(), &config, "north", future(4 * 168, 24)).unwrap();
+
+    let dir = std::env::temp_dir().join(format!("cpo-signed-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+    let public_key = artifact::generate_key(&path("model.key")).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(path("model.key.pub"))
+            .unwrap()
+            .trim(),
+        public_key
+    );
+    assert!(
+        artifact::generate_key(&path("model.key")).is_err(),
+        "keys aren't overwritten"
+    );
+    let key = artifact::signing_key(&path("model.key")).unwrap();
+
+    artifact::save(&published, &path("model.json"), Some(&key)).unwrap();
+    let loaded = artifact::load(&path("model.json"), Some(&public_key)).unwrap();
+    assert_eq!(
+        (loaded.site, loaded.fitted_to, loaded.trend.len()),
+        (published.site, published.fitted_to, published.trend.len())
+    );
+
+    // Someone else's key
+    let other = artifact::generate_key(&path("other.key")).unwrap();
+    let error = artifact::load(&path("model.json"), Some(&other))
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("doesn't match"), "{}", error);
+
+    // One digit changed in transit
+    let json = std::fs::read_to_string(path("model.json")).unwrap();
+    let tampered = json.replacen("\"step\": 3600", "\"step\": 3601", 1);
+    assert_ne!(tampered, json);
+    std::fs::write(path("model.json"), &tampered).unwrap();
+    assert!(artifact::load(&path("model.json"), Some(&public_key)).is_err());
+    assert!(
+        artifact::verify(
+            tampered.as_bytes(),
+            &std::fs::read_to_string(path("model.json.sig")).unwrap(),
+            &public_key
+        )
+        .is_err()
+    );
+    assert!(artifact::verify(json.as_bytes(), "not hex", &public_key).is_err());
+    std::fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,108 @@
+//! Batch retries and the succeeded/failed report.
+
+use cpo_charging_forecast::batch::{BatchReport, backoff, is_transient, run_site};
+use cpo_charging_forecast::config::BatchConfig;
+use cpo_charging_forecast::outcome::{StageError, Status};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::time::Duration;
+
+fn config() -> BatchConfig {
+    BatchConfig {
+        retries: 2,
+        retry_delay_seconds: 10.0,
+        report: None,
+    }
+}
+
+#[test]
+fn transient_failures_are_retried() {
+    let mut waits = Vec::new();
+    let outcome = run_site(
+        "north",
+        &config(),
+        |d| waits.push(d),
+        |attempt| match attempt {
+            1 => Err(StageError::fit("optimizer diverged".into())),
+            2 => Err("timed out".into()),
+            _ => Ok(()),
+        },
+    );
+    assert_eq!(
+        (outcome.status, outcome.attempts, outcome.error),
+        (Status::Success, 3, None)
+    );
+    // Doubling, ±50%
+    assert_eq!(waits.len(), 2);
+    assert!((5.0..15.0).contains(&waits[0].as_secs_f64()), "{:?}", waits);
+    assert!(
+        (10.0..30.0).contains(&waits[1].as_secs_f64()),
+        "{:?}",
+        waits
+    );
+
+    let outcome = run_site(
+        "south",
+        &config(),
+        |_| {},
+        |_| Err(StageError::fit("optimizer diverged".into())),
+    );
+    assert_eq!((outcome.status, outcome.attempts), (Status::FitFailed, 3));
+    assert_eq!(outcome.error.as_deref(), Some("optimizer diverged"));
+}
+
+#[test]
+fn bad_data_fails_at_once() {
+    let bad = StageError::data("No valid sessions".into());
+    assert!(!is_transient(bad.as_ref()));
+    let outcome = run_site(
+        "east",
+        &config(),
+        |_| panic!("no retry"),
+        |_| Err(StageError::data("No valid sessions".into())),
+    );
+    assert_eq!((outcome.status, outcome.attempts), (Status::DataError, 1));
+}
+
+#[test]
+fn backoff_jitters_around_a_doubling_delay() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let delays: Vec<Duration> = (1..=200)
+        .map(|retry| backoff(&config(), 1 + retry % 3, &mut rng))
+        .collect();
+    assert!(
+        delays
+            .iter()
+            .all(|d| (5.0..60.0).contains(&d.as_secs_f64()))
+    );
+    // Not all the same
+    assert!(delays.windows(2).any(|w| w[0] != w[1]));
+}
+
+#[test]
+fn the_report_lists_failures_without_stopping() {
+    let mut report = BatchReport::new();
+    for (site, fails) in [("north", false), ("south", true), ("east", false)] {
+        report.push(run_site(
+            site,
+            &config(),
+            |_| {},
+            |_| match fails {
+                true => Err(StageError::data("empty export".into())),
+                false => Ok(()),
+            },
+        ));
+    }
+    assert_eq!(report.succeeded, ["north", "east"]);
+    assert_eq!(report.failed, ["south"]);
+    let summary = report.summary();
+    assert!(summary.starts_with("2 of 3 sites succeeded"), "{}", summary);
+    assert!(
+        summary.contains("south failed (1 attempt): empty export"),
+        "{}",
+        summary
+    );
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["sites"][1]["status"], "data_error");
+}
@@ -0,0 +1,93 @@
+//! Structural break detection: lasting level shifts, not odd days.
+
+use cpo_charging_forecast::breaks::{self, detect};
+use cpo_charging_forecast::config::BreakConfig;
+use cpo_charging_forecast::data::Series;
+
+/// 2024-01-01 00:00, a Monday.
+const MONDAY: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+const DAY: i64 = 24 * HOUR;
+
+/// Uniform in -1..1, fixed by `seed`.
+fn noise(seed: i64) -> f64 {
+    let mut x = (seed as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (x ^ (x >> 31)) as f64 / u64::MAX as f64 * 2.0 - 1.0
+}
+
+/// Six weeks of a site with a daily pattern and some day-to-day noise, scaled by `level` per day.
+fn history(level: impl Fn(i64) -> f64) -> Series {
+    let hours = 42 * 24;
+    let timestamps: Vec<i64> = (0..hours).map(|h| MONDAY + h * HOUR).collect();
+    let values = (0..hours)
+        .map(|h| {
+            let day = h / 24;
+            let shape = if (8..20).contains(&(h % 24)) {
+                30.0
+            } else {
+                5.0
+            };
+            shape * (1.0 + 0.3 * noise(h)) * (1.0 + 0.1 * noise(7000 + day)) * level(day)
+        })
+        .collect();
+    Series::new(timestamps, values)
+}
+
+#[test]
+fn a_lasting_drop_is_a_break() {
+    let config = BreakConfig::default();
+    // Volume halves from day 35, a Monday
+    let found = detect(&history(|day| if day >= 35 { 0.5 } else { 1.0 }), &config).unwrap();
+    assert!(
+        (found.start - (MONDAY + 35 * DAY)).abs() <= DAY,
+        "{:?}",
+        found
+    );
+    assert!(found.detected_at <= MONDAY + 38 * DAY, "{:?}", found);
+    // The day the drop is dated from may still be one at the old level
+    assert!(found.change() < -0.3, "{}", found.change());
+    assert!(
+        found.summary().starts_with("Demand fell "),
+        "{}",
+        found.summary()
+    );
+
+    let report = breaks::report_json("north", Some(&found));
+    assert_eq!(report["site"], "north");
+    assert_eq!(report["break"]["summary"], found.summary());
+}
+
+#[test]
+fn steady_demand_and_single_odd_days_are_not() {
+    let config = BreakConfig::default();
+    assert_eq!(detect(&history(|_| 1.0), &config), None);
+    // An outage day and an event day
+    assert_eq!(
+        detect(
+            &history(|day| if day == 33 {
+                0.0
+            } else if day == 37 {
+                1.6
+            } else {
+                1.0
+            }),
+            &config
+        ),
+        None
+    );
+    // Without a week of baseline before the window there is nothing to compare with
+    let config = BreakConfig {
+        baseline_days: 5,
+        ..BreakConfig::default()
+    };
+    assert_eq!(
+        detect(&history(|day| if day >= 35 { 0.5 } else { 1.0 }), &config),
+        None
+    );
+    assert_eq!(
+        breaks::report_json("north", None)["break"],
+        serde_json::Value::Null
+    );
+}
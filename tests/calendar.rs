@@ -0,0 +1,89 @@
+//! Calendar regressors around public holidays: their windows and bridge days.
+
+use cpo_charging_forecast::calendar::Calendar;
+use cpo_charging_forecast::config::CalendarConfig;
+
+/// Noon of a day in 2024.
+fn noon(date: &str) -> i64 {
+    chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap()
+        .and_utc()
+        .timestamp()
+}
+
+#[test]
+fn days_around_holidays_have_features_of_their_own() {
+    // Ascension on a Thursday, Christmas on Wednesday and Thursday
+    let config = CalendarConfig {
+        public_holidays: ["2024-05-09", "2024-12-25", "2024-12-26"]
+            .map(String::from)
+            .to_vec(),
+        holiday_lower_window: -1,
+        holiday_upper_window: 1,
+        bridge_days: true,
+        ..CalendarConfig::default()
+    };
+    let calendar = Calendar::new(&config, 2024..=2024).unwrap();
+    let days = [
+        "2024-05-08",
+        "2024-05-09",
+        "2024-05-10",
+        "2024-12-23",
+        "2024-12-24",
+        "2024-12-26",
+        "2024-12-27",
+    ];
+    let features = calendar.features(&days.map(noon));
+    assert_eq!(
+        features.keys().collect::<Vec<_>>(),
+        [
+            "is_bridge_day",
+            "is_public_holiday",
+            "public_holiday_minus_1",
+            "public_holiday_plus_1"
+        ]
+    );
+    assert_eq!(
+        features["is_public_holiday"],
+        [0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]
+    );
+    assert_eq!(
+        features["public_holiday_minus_1"],
+        [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0]
+    );
+    // Boxing Day is a holiday itself, not the day after Christmas
+    assert_eq!(
+        features["public_holiday_plus_1"],
+        [0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]
+    );
+    // The Fridays after the Thursday holidays; the Monday before Christmas Eve is a working week
+    assert_eq!(
+        features["is_bridge_day"],
+        [0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]
+    );
+
+    let wide = Calendar::new(
+        &CalendarConfig {
+            holiday_lower_window: -2,
+            ..config.clone()
+        },
+        2024..=2024,
+    )
+    .unwrap();
+    assert_eq!(
+        wide.features(&[noon("2024-05-07")])["public_holiday_minus_2"],
+        [1.0]
+    );
+    assert!(
+        Calendar::new(
+            &CalendarConfig {
+                holiday_lower_window: 1,
+                ..config
+            },
+            2024..=2024
+        )
+        .is_err()
+    );
+}
@@ -0,0 +1,82 @@
+//! Clustering sites on their daily demand profiles.
+
+use cpo_charging_forecast::cluster::{self, dtw, label};
+use cpo_charging_forecast::config::{ClusterConfig, ProfileDistance};
+use cpo_charging_forecast::profile::Profiles;
+
+/// A site whose load peaks at `peak`, `height` times the base load.
+fn site(name: &str, peak: usize, height: f64) -> Profiles {
+    let raw: Vec<f64> = (0..24usize)
+        .map(|h| match h.abs_diff(peak) {
+            0 => height,
+            1 => (1.0 + height) / 2.0,
+            _ => 1.0,
+        })
+        .collect();
+    let mean = raw.iter().sum::<f64>() / 24.0;
+    Profiles {
+        site: name.to_string(),
+        daily: raw.iter().map(|v| v / mean).collect(),
+        weekly: Vec::new(),
+    }
+}
+
+#[test]
+fn sites_with_the_same_peak_cluster_together() {
+    let sites = vec![
+        site("office", 8, 4.0),
+        site("mall", 18, 4.0),
+        site("depot", 8, 5.0),
+        site("homes", 19, 3.0),
+    ];
+    let config = ClusterConfig {
+        k: 2,
+        ..ClusterConfig::default()
+    };
+    let clustering = cluster::cluster(&sites, &config).unwrap();
+    assert_eq!(clustering.clusters.len(), 2);
+    assert_eq!(clustering.clusters[0].sites, ["office", "depot"]);
+    assert_eq!(clustering.clusters[0].label, "morning peak (08:00)");
+    assert_eq!(clustering.clusters[1].sites, ["mall", "homes"]);
+    assert_eq!(clustering.member("homes").unwrap().cluster, 2);
+
+    // More clusters than sites gives one per site; none is an error
+    let config = ClusterConfig {
+        k: 9,
+        ..ClusterConfig::default()
+    };
+    assert_eq!(cluster::cluster(&sites, &config).unwrap().clusters.len(), 4);
+    assert!(cluster::cluster(&[], &config).is_err());
+
+    let path = std::env::temp_dir().join(format!("cpo-clusters-{}.csv", std::process::id()));
+    cluster::write_csv(&clustering, path.to_str().unwrap()).unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+    assert!(
+        text.starts_with("site,cluster,label,distance\noffice,1,morning peak (08:00),"),
+        "{}",
+        text
+    );
+}
+
+#[test]
+fn dtw_matches_peaks_a_little_apart() {
+    let (a, b) = (site("a", 17, 4.0).daily, site("b", 18, 4.0).daily);
+    assert!(dtw(&a, &b, 2) < dtw(&a, &b, 0));
+    assert_eq!(dtw(&a, &a, 2), 0.0);
+
+    // Under DTW, a peak one hour later is closer than a peak of another height
+    let sites = vec![site("a", 17, 4.0), site("b", 18, 4.0), site("c", 17, 1.2)];
+    let config = ClusterConfig {
+        k: 2,
+        distance: ProfileDistance::Dtw,
+        ..ClusterConfig::default()
+    };
+    let clustering = cluster::cluster(&sites, &config).unwrap();
+    assert_eq!(
+        clustering.member("a").unwrap().cluster,
+        clustering.member("b").unwrap().cluster
+    );
+    assert_eq!(clustering.member("c").unwrap().label, "flat");
+    assert_eq!(label(&site("x", 12, 3.0).daily), "midday peak (12:00)");
+}
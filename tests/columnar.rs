@@ -0,0 +1,148 @@
+//! Parquet and Arrow IPC round trips of hourly series and forecasts.
+
+#![cfg(feature = "arrow")]
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, Float32Array, Int64Array, RecordBatch, StringArray, TimestampMillisecondArray,
+};
+use cpo_charging_forecast::columnar;
+use cpo_charging_forecast::config::Config;
+use cpo_charging_forecast::data::Series;
+use cpo_charging_forecast::forecast::Forecast;
+use cpo_charging_forecast::pipeline::load_history;
+
+/// 2024-01-01 00:00, local time.
+const ORIGIN: i64 = 1_704_067_200;
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("cpo-columnar-{}-{}", std::process::id(), name))
+}
+
+fn series(hours: i64) -> Series {
+    let timestamps = (0..hours).map(|h| ORIGIN + h * 3600).collect();
+    let values = (0..hours).map(|h| (h % 24) as f64 * 1.5).collect();
+    Series::new(timestamps, values)
+}
+
+#[test]
+fn series_round_trips_through_parquet_and_ipc() {
+    for name in ["series.parquet", "series.arrow"] {
+        let path = temp_path(name);
+        let batch = columnar::series_to_batch(series(48)).unwrap();
+        columnar::write_batch(&path, &batch).unwrap();
+        let back = columnar::read_series(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(back, series(48), "{}", name);
+    }
+}
+
+#[test]
+fn series_share_their_buffers_with_batches() {
+    let series = series(48);
+    let batch = columnar::series_to_batch(series.clone()).unwrap();
+    let back = columnar::series_from_batch(batch.clone()).unwrap();
+    assert_eq!(back.values.as_ptr(), series.values.as_ptr());
+    assert_eq!(back.timestamps.as_ptr(), series.timestamps.as_ptr());
+    assert_eq!(
+        back.slice(24..48).values.as_ptr(),
+        series.values[24..].as_ptr()
+    );
+
+    // An integer timestamp column is taken as it is
+    let timestamps: ArrayRef = Arc::new(Int64Array::new(series.timestamps.clone(), None));
+    let batch = RecordBatch::try_from_iter([
+        ("timestamp", timestamps),
+        ("value", Arc::clone(batch.column(1))),
+    ])
+    .unwrap();
+    let back = columnar::series_from_batch(batch).unwrap();
+    assert_eq!(back.timestamps.as_ptr(), series.timestamps.as_ptr());
+}
+
+#[test]
+fn forecast_batch_has_the_csv_columns() {
+    let forecast = Forecast {
+        timestamps: vec![ORIGIN, ORIGIN + 3600],
+        point: vec![1.0, 2.0],
+        lower: vec![0.5, 1.0],
+        upper: vec![1.5, 3.0],
+    };
+    let path = temp_path("forecast.parquet");
+    columnar::write_forecast(&forecast, path.to_str().unwrap()).unwrap();
+    let batches = columnar::read_batches(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+    let schema = batches[0].schema();
+    let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+    assert_eq!(names, ["timestamp", "yhat", "yhat_lower", "yhat_upper"]);
+    assert_eq!(batches[0].num_rows(), 2);
+}
+
+#[test]
+fn zoned_timestamps_become_local_wall_clock() {
+    // 2024-07-01 00:00 UTC is 02:00 in Amsterdam; other column names and types
+    let utc_ms = 1_719_792_000_000;
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(
+            TimestampMillisecondArray::from(vec![utc_ms, utc_ms + 3_600_000])
+                .with_timezone("Europe/Amsterdam"),
+        ),
+        Arc::new(Float32Array::from(vec![4.0, 8.0])),
+    ];
+    let batch =
+        RecordBatch::try_from_iter([("ts", columns[0].clone()), ("kw", columns[1].clone())])
+            .unwrap();
+    let series = columnar::series_from_batch(batch).unwrap();
+    assert_eq!(
+        series.timestamps,
+        [utc_ms / 1000 + 7200, utc_ms / 1000 + 10_800]
+    );
+    assert_eq!(series.values, [4.0, 8.0]);
+}
+
+#[test]
+fn load_history_reads_a_columnar_series() {
+    let path = temp_path("history.parquet");
+    columnar::write_batch(&path, &columnar::series_to_batch(series(72)).unwrap()).unwrap();
+    let mut config = Config::default();
+    config.data.path = path.to_str().unwrap().to_string();
+    let history = load_history(&config);
+    std::fs::remove_file(&path).ok();
+    assert_eq!(history.unwrap().len(), 72);
+}
+
+#[test]
+fn files_with_a_charger_column_hold_registers() {
+    let path = temp_path("registers.parquet");
+    let hour = 3600;
+    let columns: Vec<(&str, ArrayRef)> = vec![
+        (
+            "charger",
+            Arc::new(StringArray::from(vec!["cp-1", "cp-2", "cp-1", "cp-2"])),
+        ),
+        (
+            "timestamp",
+            Arc::new(Int64Array::from(vec![
+                ORIGIN,
+                ORIGIN,
+                ORIGIN + hour,
+                ORIGIN + hour,
+            ])),
+        ),
+        (
+            "value",
+            Arc::new(Float32Array::from(vec![
+                1_000.0, 500_000.0, 8_000.0, 503_000.0,
+            ])),
+        ),
+    ];
+    columnar::write_batch(&path, &RecordBatch::try_from_iter(columns).unwrap()).unwrap();
+    let mut config: Config = toml::from_str("[data.register]\nunit = \"wh\"").unwrap();
+    config.data.path = path.to_str().unwrap().to_string();
+    let history = load_history(&config);
+    std::fs::remove_file(&path).ok();
+    // 7 kWh and 3 kWh in the hour
+    assert_eq!(history.unwrap().values, vec![10.0]);
+}
@@ -0,0 +1,162 @@
+//! One shared config with `[sites.<id>]` overrides merged per site, the
+//! site/runtime split, schema errors and the choice of optimizer.
+
+use cpo_charging_forecast::config::{
+    CmdstanConfig, Config, ConfigKind, Method, OptimizerConfig, SeasonalityMode, suggestion,
+};
+use cpo_charging_forecast::data::Series;
+use cpo_charging_forecast::forecast::{fit_prophet, model_version};
+
+const SHARED: &str = r#"
+site = "north"
+
+[model]
+seasonality_mode = "additive"
+daily_seasonality = false
+
+[forecast]
+horizon_hours = 168
+
+[sites.north.model]
+seasonality_mode = "multiplicative"
+
+[sites.south]
+chargers = 4
+forecast = { horizon_hours = 48 }
+alerts = { site_limit_kw = 150.0 }
+"#;
+
+#[test]
+fn sites_inherit_what_they_dont_override() {
+    let shared = Config::from_toml(SHARED, "test").unwrap();
+    assert_eq!(shared.sites.len(), 2);
+
+    let north = shared.for_site("north").unwrap();
+    assert_eq!(
+        north.model.seasonality_mode,
+        SeasonalityMode::Multiplicative
+    );
+    // Sections merge key by key
+    assert!(!north.model.daily_seasonality);
+    assert_eq!(north.forecast.horizon_hours, 168);
+
+    let south = shared.for_site("south").unwrap();
+    assert_eq!(south.site, "south");
+    assert_eq!(south.model.seasonality_mode, SeasonalityMode::Additive);
+    assert_eq!(
+        (south.chargers, south.forecast.horizon_hours),
+        (Some(4), 48)
+    );
+    assert_eq!(south.alerts.unwrap().site_limit_kw, Some(150.0));
+
+    let east = shared.for_site("east").unwrap();
+    assert_eq!(
+        (east.site.as_str(), east.forecast.horizon_hours),
+        ("east", 168)
+    );
+}
+
+#[test]
+fn bad_overrides_name_the_site() {
+    let shared = Config::from_toml("[sites.south.forecast]\nhorizon = 48\n", "test").unwrap();
+    let e = shared.for_site("south").unwrap_err();
+    assert!(e.to_string().contains("site south"), "{}", e);
+}
+
+#[test]
+fn tenant_ids_cannot_hold_a_slash() {
+    let config = |tenants: &str| Config::from_toml(tenants, "test").unwrap().server;
+    assert!(config("[server.tenants.acme]").check_tenants().is_ok());
+    let e = config("[server.tenants.\"acme/eu\"]")
+        .check_tenants()
+        .unwrap_err();
+    assert!(e.to_string().contains("\"acme/eu\""), "{}", e);
+    assert!(config("[server.tenants.\"\"]").check_tenants().is_err());
+}
+
+#[test]
+fn schema_errors_point_at_the_line_and_suggest() {
+    let e = Config::from_toml(
+        "site = \"north\"\n[model]\nmethod = \"profet\"\n",
+        "test.toml",
+    )
+    .unwrap_err()
+    .to_string();
+    assert!(e.contains("line 3"), "{}", e);
+    assert!(e.ends_with("did you mean `prophet`?"), "{}", e);
+
+    assert_eq!(
+        suggestion("unknown field `horizon`, expected `horizon_hours`"),
+        "\ndid you mean `horizon_hours`?"
+    );
+    assert_eq!(
+        suggestion("unknown field `zzz`, expected one of `plot`, `csv`"),
+        ""
+    );
+    assert_eq!(suggestion("invalid type: string \"a\", expected u32"), "");
+}
+
+#[test]
+fn runtime_config_lives_in_its_own_file() {
+    let dir = std::env::temp_dir().join("config_split");
+    std::fs::create_dir_all(&dir).unwrap();
+    let (site, runtime) = (dir.join("site.toml"), dir.join("runtime.toml"));
+    std::fs::write(&site, "site = \"north\"\n[model]\nmethod = \"tsb\"\n").unwrap();
+    std::fs::write(
+        &runtime,
+        "version = 1\n[output]\ncsv = \"forecast.csv\"\n[mqtt]\ntopic = \"north\"\n",
+    )
+    .unwrap();
+    let config = Config::from_files(Some(&site), &runtime).unwrap();
+    assert_eq!(
+        (config.site.as_str(), config.model.method),
+        ("north", Method::Tsb)
+    );
+    assert_eq!(config.output.csv.as_deref(), Some("forecast.csv"));
+    assert!(config.mqtt.is_some());
+
+    // Keys in the wrong file are named
+    std::fs::write(&runtime, "[forecast]\nhorizon_hours = 24\n").unwrap();
+    let e = Config::from_files(Some(&site), &runtime)
+        .unwrap_err()
+        .to_string();
+    assert!(
+        e.contains("`forecast` belongs in the site/model config"),
+        "{}",
+        e
+    );
+    assert_eq!(
+        (ConfigKind::of("kafka"), ConfigKind::of("model")),
+        (ConfigKind::Runtime, ConfigKind::Site)
+    );
+}
+
+#[test]
+fn optimizers_are_chosen_by_type() {
+    let toml = "[model.optimizer]\ntype = \"cmdstan\"\nprophet_path = \"/nonexistent/prophet\"\n";
+    let config = Config::from_toml(toml, "test").unwrap();
+    let cmdstan = OptimizerConfig::Cmdstan(CmdstanConfig {
+        prophet_path: "/nonexistent/prophet".to_string(),
+    });
+    assert_eq!(config.model.optimizer, Some(cmdstan));
+    // Part of the model's settings
+    assert_ne!(
+        model_version(&config.model),
+        model_version(&Config::default().model)
+    );
+
+    let history = Series::new(
+        (0..72).map(|h| 1_704_067_200 + h * 3600).collect(),
+        vec![1.0; 72],
+    );
+    let e = fit_prophet(&history, &config.model)
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(e.starts_with("model.optimizer"), "{}", e);
+
+    let e = Config::from_toml("[model.optimizer]\ntype = \"lbfgs\"\n", "test")
+        .unwrap_err()
+        .to_string();
+    assert!(e.contains("lbfgs"), "{}", e);
+}
@@ -0,0 +1,167 @@
+//! Prophet's optimizer diagnostics, and fits that didn't converge or failed.
+
+use augurs::prophet::optimizer::{
+    self, Data, InitialParams, OptimizeOpts, OptimizedParams, Optimizer,
+};
+use cpo_charging_forecast::config::ModelConfig;
+#[cfg(feature = "wasmstan")]
+use cpo_charging_forecast::config::RetryConfig;
+use cpo_charging_forecast::data::Series;
+use cpo_charging_forecast::forecast::{self, fit, fit_prophet};
+#[cfg(feature = "wasmstan")]
+use cpo_charging_forecast::forecast::{Forecaster, Regressors, fit_retrying};
+
+/// 2024-01-01 00:00.
+const ORIGIN: i64 = 1_704_067_200;
+
+/// Four weeks of a daily cycle around 20 kW.
+fn history() -> Series {
+    let hours = 28 * 24;
+    let timestamps: Vec<i64> = (0..hours).map(|h| ORIGIN + h * 3600).collect();
+    let values: Vec<f64> = (0..hours)
+        .map(|h| 20.0 + 8.0 * (2.0 * std::f64::consts::PI * (h % 24) as f64 / 24.0).sin())
+        .collect();
+    Series::new(timestamps, values)
+}
+
+/// An optimizer that hands back its initial values.
+#[derive(Debug)]
+struct Stalled;
+
+impl Optimizer for Stalled {
+    fn optimize(
+        &self,
+        init: &InitialParams,
+        data: &Data,
+        _: &OptimizeOpts,
+    ) -> Result<OptimizedParams, optimizer::Error> {
+        Ok(OptimizedParams {
+            k: init.k,
+            m: init.m,
+            sigma_obs: init.sigma_obs,
+            delta: init.delta.clone(),
+            beta: init.beta.clone(),
+            trend: data.t.iter().map(|t| init.m + init.k * t).collect(),
+        })
+    }
+}
+
+/// Stan's optimizer, failing from Prophet's own starting values (no trend
+/// changes) with `Flaky::Start`, and on multiplicative seasonality with `Flaky::Multiplicative`.
+#[cfg(feature = "wasmstan")]
+#[derive(Debug)]
+enum Flaky {
+    Start,
+    Multiplicative,
+}
+
+#[cfg(feature = "wasmstan")]
+impl Optimizer for Flaky {
+    fn optimize(
+        &self,
+        init: &InitialParams,
+        data: &Data,
+        opts: &OptimizeOpts,
+    ) -> Result<OptimizedParams, optimizer::Error> {
+        match self {
+            Flaky::Start if init.delta.iter().all(|d| *d == 0.0) => {
+                Err(optimizer::Error::string("line search failed".to_string()))
+            }
+            Flaky::Multiplicative if data.s_m.contains(&1) => Err(optimizer::Error::string(
+                "gradient went to infinity".to_string(),
+            )),
+            _ => augurs::prophet::wasmstan::WasmstanOptimizer::new().optimize(init, data, opts),
+        }
+    }
+}
+
+#[cfg(feature = "wasmstan")]
+#[test]
+fn a_converged_fit_reports_its_optimum() {
+    let model = fit(&history(), &ModelConfig::default()).unwrap();
+    let diagnostics = model.optimizer().unwrap();
+    assert!(diagnostics.converged(), "{}", diagnostics.summary());
+    assert!(diagnostics.moved);
+    assert!(diagnostics.log_prob.is_finite());
+    assert!(
+        diagnostics.residual_rms < 0.5 * diagnostics.spread,
+        "{}",
+        diagnostics.summary()
+    );
+}
+
+#[test]
+fn a_stalled_fit_fails_or_falls_back() {
+    forecast::set_optimizer(|| Box::new(Stalled));
+    let history = history();
+    let error = fit_prophet(&history, &ModelConfig::default())
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(
+        error.contains("never moved off its initial values"),
+        "{}",
+        error
+    );
+
+    let fallen_back = fit(&history, &ModelConfig::default()).unwrap();
+    assert_eq!(fallen_back.name(), "seasonal naive");
+    assert_eq!(fallen_back.optimizer(), None);
+
+    let strict = ModelConfig {
+        fallbacks: Vec::new(),
+        ..ModelConfig::default()
+    };
+    assert!(fit(&history, &strict).is_err());
+}
+
+#[cfg(feature = "wasmstan")]
+#[test]
+fn failed_fits_are_retried_from_jittered_starting_values() {
+    forecast::set_optimizer(|| Box::new(Flaky::Start));
+    let history = history();
+    let model = ModelConfig {
+        fallbacks: Vec::new(),
+        ..ModelConfig::default()
+    };
+    assert!(fit_retrying(&history, &model, &Regressors::default()).is_err());
+
+    let retried = ModelConfig {
+        retry: Some(RetryConfig::default()),
+        ..model
+    };
+    let prophet = fit_retrying(&history, &retried, &Regressors::default()).unwrap();
+    assert!(prophet.optimizer().unwrap().converged());
+}
+
+#[cfg(feature = "wasmstan")]
+#[test]
+fn fits_failing_on_every_start_are_simplified() {
+    forecast::set_optimizer(|| Box::new(Flaky::Multiplicative));
+    let history = history();
+    let retry = RetryConfig {
+        attempts: 1,
+        simplify: false,
+        ..RetryConfig::default()
+    };
+    let model = ModelConfig {
+        retry: Some(retry.clone()),
+        fallbacks: Vec::new(),
+        ..ModelConfig::default()
+    };
+    let error = fit_retrying(&history, &model, &Regressors::default())
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(error.contains("gradient went to infinity"), "{}", error);
+
+    let simplified = ModelConfig {
+        retry: Some(RetryConfig {
+            simplify: true,
+            ..retry
+        }),
+        ..model
+    };
+    let prophet = fit_retrying(&history, &simplified, &Regressors::default()).unwrap();
+    assert!(prophet.optimizer().unwrap().converged());
+}
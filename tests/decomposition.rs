@@ -0,0 +1,196 @@
+//! In-sample fitted values, Prophet's components and trend changepoints.
+
+#![cfg(feature = "wasmstan")]
+
+use cpo_charging_forecast::config::{Config, Method, ModelConfig, SeasonalityMode, Transform};
+use cpo_charging_forecast::data::Series;
+use cpo_charging_forecast::export::{attribution_json, write_decomposition_csv};
+use cpo_charging_forecast::forecast::fit;
+use cpo_charging_forecast::pipeline::load_history;
+
+fn history() -> Series {
+    let mut config = Config::default();
+    config.data.path = "tests/data/synthetic.csv".to_string();
+    load_history(&config).unwrap()
+}
+
+#[test]
+fn components_add_up_to_the_fit() {
+    let history = history();
+    let mut model = ModelConfig::default();
+    model.calendar.weekend = true;
+    let decomposition = fit(&history, &model).unwrap().decompose().unwrap();
+    let fitted = &decomposition.fitted;
+    assert_eq!(fitted.timestamps, history.timestamps);
+
+    let names: Vec<&str> = decomposition
+        .components
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "additive_terms",
+            "multiplicative_terms",
+            "daily",
+            "weekly",
+            "is_weekend"
+        ]
+    );
+    let component = |name: &str| {
+        &decomposition
+            .components
+            .iter()
+            .find(|(n, _)| n == name)
+            .unwrap()
+            .1
+    };
+    for i in (0..fitted.len()).step_by(97) {
+        let trend = decomposition.trend[i];
+        let rebuilt =
+            trend * (1.0 + component("multiplicative_terms")[i]) + component("additive_terms")[i];
+        assert!(
+            (rebuilt - fitted.point[i]).abs() < 1e-6 * trend.abs().max(1.0),
+            "hour {}",
+            i
+        );
+        // Multiplicative by default: the seasonalities sum to the multiplicative terms
+        let seasonal = component("daily")[i] + component("weekly")[i] + component("is_weekend")[i];
+        assert!((seasonal - component("multiplicative_terms")[i]).abs() < 1e-9);
+    }
+
+    let path = std::env::temp_dir().join(format!("cpo-decomposition-{}.csv", std::process::id()));
+    write_decomposition_csv(&decomposition, &history, path.to_str().unwrap()).unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+    assert!(
+        text.starts_with("timestamp,actual,fitted,fitted_lower,fitted_upper,trend,additive_terms,"),
+        "{}",
+        &text[..80]
+    );
+    assert_eq!(text.lines().count(), history.len() + 1);
+}
+
+#[test]
+fn contributions_add_up_to_the_forecast() {
+    let history = history();
+    let mut model = ModelConfig::default();
+    model.calendar.weekend = true;
+    model.lags = vec![24];
+    let model = fit(&history, &model).unwrap();
+    let last = *history.timestamps.last().unwrap();
+    let timestamps: Vec<i64> = (1..=72).map(|h| last + h * 3600).collect();
+    let attribution = model.attribute(timestamps.clone()).unwrap();
+    assert_eq!(attribution.fitted.timestamps, timestamps);
+    assert_eq!(
+        attribution.multiplicative,
+        ["daily", "weekly", "yearly", "is_weekend"]
+    );
+
+    let forecast = model.predict(timestamps).unwrap();
+    let json = attribution_json(&attribution, "kW");
+    assert_eq!(json["units"], "kW");
+    for (i, point) in json["points"].as_array().unwrap().iter().enumerate() {
+        let components = point["components"].as_array().unwrap();
+        let names: Vec<&str> = components
+            .iter()
+            .map(|c| c["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names.len(), 4, "{:?}", names);
+        assert!(names.contains(&"lag_24") && !names.contains(&"additive_terms"));
+        let lag = components.iter().find(|c| c["name"] == "lag_24").unwrap();
+        assert_eq!(lag["mode"], "additive");
+        let contributions: Vec<f64> = components
+            .iter()
+            .map(|c| c["contribution"].as_f64().unwrap())
+            .collect();
+        assert!(contributions.windows(2).all(|w| w[0].abs() >= w[1].abs()));
+
+        let yhat = point["yhat"].as_f64().unwrap();
+        let rebuilt = point["trend"].as_f64().unwrap() + contributions.iter().sum::<f64>();
+        assert!(
+            (rebuilt - yhat).abs() < 1e-6 * yhat.abs().max(1.0),
+            "hour {}: {} vs {}",
+            i,
+            rebuilt,
+            yhat
+        );
+        assert!((yhat.max(0.0) - forecast.point[i]).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn transformed_fits_are_in_kw() {
+    let history = history();
+    let model = ModelConfig {
+        transform: Transform::Log1p,
+        ..ModelConfig::default()
+    };
+    let decomposition = fit(&history, &model).unwrap().decompose().unwrap();
+    let mean = |v: &[f64]| v.iter().sum::<f64>() / v.len() as f64;
+    let (fitted, actual) = (mean(&decomposition.fitted.point), mean(&history.values));
+    assert!(
+        (fitted - actual).abs() < 0.3 * actual,
+        "fitted {} vs actual {}",
+        fitted,
+        actual
+    );
+    // The trend stays on the log scale
+    assert!(mean(&decomposition.trend) < actual.ln_1p() * 2.0);
+
+    let tsb = ModelConfig {
+        method: Method::Tsb,
+        ..ModelConfig::default()
+    };
+    let e = fit(&history, &tsb).unwrap().decompose().unwrap_err();
+    assert!(e.to_string().contains("tsb has no components"), "{}", e);
+}
+
+#[test]
+fn changepoints_find_the_growth_spurt() {
+    // Flat at 20 kW for 30 days, then growing by 2 kW a day, with a daily cycle
+    const ORIGIN: i64 = 1_704_067_200;
+    let hours = 60 * 24;
+    let timestamps: Vec<i64> = (0..hours).map(|h| ORIGIN + h * 3600).collect();
+    let values: Vec<f64> = (0..hours)
+        .map(|h| {
+            let day = h as f64 / 24.0;
+            let daily = 5.0 * (2.0 * std::f64::consts::PI * (h % 24) as f64 / 24.0).sin();
+            20.0 + 2.0 * (day - 30.0).max(0.0) + daily
+        })
+        .collect();
+    let history = Series::new(timestamps, values);
+    let model = ModelConfig {
+        seasonality_mode: SeasonalityMode::Additive,
+        ..ModelConfig::default()
+    };
+    let changepoints = fit(&history, &model).unwrap().changepoints();
+    assert_eq!(changepoints.len(), 25);
+    assert!(
+        changepoints
+            .windows(2)
+            .all(|w| w[0].timestamp < w[1].timestamp)
+    );
+
+    let largest = changepoints
+        .iter()
+        .max_by(|a, b| a.rate_change_per_day.total_cmp(&b.rate_change_per_day))
+        .unwrap();
+    assert!(largest.is_significant());
+    let day = (largest.timestamp - ORIGIN) as f64 / 86_400.0;
+    assert!(
+        (27.0..=33.0).contains(&day),
+        "largest change on day {}",
+        day
+    );
+    // The spurt may be spread over neighbouring changepoints; together they add up to 2 kW/day
+    let total: f64 = changepoints.iter().map(|c| c.rate_change_per_day).sum();
+    assert!((total - 2.0).abs() < 0.3, "total rate change {}", total);
+
+    let tsb = ModelConfig {
+        method: Method::Tsb,
+        ..ModelConfig::default()
+    };
+    assert!(fit(&history, &tsb).unwrap().changepoints().is_empty());
+}
@@ -0,0 +1,113 @@
+//! Comparing two forecasts: reading them back and the largest changes.
+
+use cpo_charging_forecast::archive::{Archive, ArchivedForecast};
+use cpo_charging_forecast::config::{DiffConfig, ModelConfig};
+use cpo_charging_forecast::diff::{self, DiffReport};
+use cpo_charging_forecast::export::{forecast_json, write_forecast_csv};
+use cpo_charging_forecast::forecast::Forecast;
+use std::path::PathBuf;
+
+const ORIGIN: i64 = 1_709_251_200;
+
+fn forecast(hours: i64, bump: f64) -> Forecast {
+    let timestamps: Vec<i64> = (0..hours).map(|h| ORIGIN + h * 3600).collect();
+    let point: Vec<f64> = (0..hours)
+        .map(|h| 20.0 + (h % 24) as f64 + if h == 30 { bump } else { 0.0 })
+        .collect();
+    Forecast {
+        lower: point.iter().map(|p| p - 2.0).collect(),
+        upper: point.iter().map(|p| p + 2.0).collect(),
+        timestamps,
+        point,
+    }
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cpo-diff-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn forecasts_load_from_files_and_the_archive() {
+    let dir = temp_dir("load");
+    let archive = Archive::open(dir.to_str().unwrap(), "north");
+    let original = forecast(48, 0.0);
+
+    let csv = dir.join("forecast.csv");
+    write_forecast_csv(&original, csv.to_str().unwrap()).unwrap();
+    let loaded = diff::load(csv.to_str().unwrap(), &archive).unwrap();
+    assert_eq!(loaded.timestamps, original.timestamps);
+    assert_eq!(loaded.point, original.point);
+
+    let json = dir.join("forecast.json");
+    std::fs::write(&json, forecast_json(&original).to_string()).unwrap();
+    assert_eq!(
+        diff::load(json.to_str().unwrap(), &archive).unwrap(),
+        original
+    );
+
+    let run = ArchivedForecast::new(
+        "north",
+        chrono::Utc::now(),
+        ORIGIN - 3600,
+        &ModelConfig::default(),
+        original.clone(),
+    );
+    archive.save(&run).unwrap();
+    assert_eq!(diff::load(&run.run_id, &archive).unwrap(), original);
+
+    let e = diff::load("20000101T000000Z", &archive).unwrap_err();
+    assert!(
+        e.to_string()
+            .contains("neither a forecast file nor an archived run"),
+        "{}",
+        e
+    );
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn largest_changes_come_first() {
+    let config = DiffConfig {
+        top: 3,
+        max_change_kw: Some(20.0),
+        ..DiffConfig::default()
+    };
+    let (a, b) = (forecast(48, 0.0), forecast(72, 25.0));
+    let report = DiffReport::new(&a, &b, &config);
+    assert_eq!(
+        (report.common_hours, report.only_a, report.only_b),
+        (48, 0, 24)
+    );
+    assert_eq!(report.largest.len(), 3);
+    assert_eq!(report.largest[0].change_kw, 25.0);
+    assert_eq!(report.largest[0].timestamp, "2024-03-02 06:00");
+    assert_eq!(report.largest[1].change_kw, 0.0);
+    assert_eq!(report.max_abs_change_kw, 25.0);
+    assert_eq!(
+        report.violations,
+        ["1 hour changed by more than 20 kW, up to 25.0 kW"]
+    );
+
+    // Within the limits, and a forecast against itself changes nothing
+    assert!(
+        DiffReport::new(&a, &b, &DiffConfig::default())
+            .violations
+            .is_empty()
+    );
+    let same = DiffReport::new(&a, &a, &config);
+    assert_eq!((same.max_abs_change_kw, same.energy_change()), (0.0, 0.0));
+
+    let energy = DiffConfig {
+        max_energy_change: Some(0.01),
+        ..DiffConfig::default()
+    };
+    let report = DiffReport::new(&a, &b, &energy);
+    assert_eq!(report.violations.len(), 1);
+    assert!(
+        report.violations[0].starts_with("Energy changed by +1.7%"),
+        "{:?}",
+        report.violations
+    );
+}
@@ -0,0 +1,50 @@
+//! Invariants of the chart downsampling, checked on random series.
+
+use cpo_charging_forecast::data::Series;
+use cpo_charging_forecast::plot::{downsample, lttb_indices};
+use proptest::prelude::*;
+
+/// 2024-01-01 00:00, local time.
+const ORIGIN: i64 = 1_704_067_200;
+
+fn minute_series() -> impl Strategy<Value = Series> {
+    prop::collection::vec(0.0..500.0f64, 1..5000).prop_map(|values| {
+        let timestamps = (0..values.len() as i64).map(|m| ORIGIN + m * 60).collect();
+        Series::new(timestamps, values)
+    })
+}
+
+proptest! {
+    #[test]
+    fn lttb_keeps_ends_and_order(series in minute_series(), max_points in 0..600usize) {
+        let n = series.len();
+        let indices = lttb_indices(&series.timestamps, &series.values, max_points);
+        if max_points == 0 || n <= max_points.max(3) {
+            prop_assert_eq!(indices, (0..n).collect::<Vec<_>>());
+        } else {
+            prop_assert_eq!(indices.len(), max_points.max(3));
+            prop_assert_eq!(indices[0], 0);
+            prop_assert_eq!(*indices.last().unwrap(), n - 1);
+            prop_assert!(indices.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+    }
+
+    #[test]
+    fn downsample_picks_original_points(series in minute_series(), max_points in 3..600usize) {
+        let small = downsample(&series, max_points);
+        prop_assert!(small.len() <= max_points);
+        for (ts, value) in small.timestamps.iter().zip(&small.values) {
+            let i = ((ts - ORIGIN) / 60) as usize;
+            prop_assert_eq!(series.values[i], *value);
+        }
+    }
+}
+
+#[test]
+fn lttb_keeps_a_lone_spike() {
+    let mut values = vec![1.0; 10_000];
+    values[6_543] = 300.0;
+    let timestamps = (0..values.len() as i64).map(|m| ORIGIN + m * 60).collect();
+    let small = downsample(&Series::new(timestamps, values), 100);
+    assert!(small.values.contains(&300.0));
+}
@@ -0,0 +1,32 @@
+//! `EVFORECAST_*` environment overrides, typed after the keys they set.
+
+use cpo_charging_forecast::config::Config;
+
+#[test]
+fn overrides_keep_the_type_of_their_key() {
+    // SAFETY: this is the only test in this binary, and nothing else reads them
+    unsafe {
+        std::env::set_var("EVFORECAST_SITE", "042");
+        std::env::set_var("EVFORECAST_DATA__PATH", "2024");
+        std::env::set_var("EVFORECAST_DATA__STRICT", "true");
+        std::env::set_var("EVFORECAST_DATA__LOCALE__DELIMITER", ";");
+        std::env::set_var("EVFORECAST_FORECAST__HORIZON_HOURS", "48");
+        std::env::set_var("EVFORECAST_OUTPUT__CSV", "\"forecast.csv\"");
+    }
+    let config = Config::from_toml("[data]\npath = \"north.csv\"", "test").unwrap();
+
+    // Strings stay strings, digits or not, whether set in the file or by default
+    assert_eq!(config.site, "042");
+    assert_eq!(config.data.path, "2024");
+    assert_eq!(config.output.csv.as_deref(), Some("forecast.csv"));
+    assert!(config.data.strict);
+    assert_eq!(config.data.locale.delimiter, ';');
+    assert_eq!(config.forecast.horizon_hours, 48);
+
+    // A number where one belongs stays an error
+    // SAFETY: as above
+    unsafe {
+        std::env::set_var("EVFORECAST_FORECAST__HORIZON_HOURS", "two days");
+    }
+    assert!(Config::from_env().is_err());
+}
@@ -0,0 +1,116 @@
+//! Probability-of-exceedance curves from the forecast interval, and the
+//! curtailment that keeps the risk of going over a limit tolerable.
+
+use cpo_charging_forecast::exceedance::{Curtailment, curves, probability, quantile};
+use cpo_charging_forecast::forecast::Forecast;
+use cpo_charging_forecast::metrics::{normal_cdf, normal_quantile};
+
+/// 2024-01-01 00:00.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+
+#[test]
+fn normal_cdf_inverts_the_quantile() {
+    for p in [0.001, 0.05, 0.2, 0.5, 0.8, 0.975, 0.999] {
+        assert!((normal_cdf(normal_quantile(p)) - p).abs() < 1e-6, "{}", p);
+    }
+    assert!((normal_cdf(0.0) - 0.5).abs() < 1e-7);
+}
+
+#[test]
+fn bounds_are_the_interval_quantiles() {
+    // An 80% interval of 100 ± 20 kW: each bound is exceeded by the 10% or 90% tail
+    let p = |threshold: f64| probability(100.0, 80.0, 120.0, 0.8, threshold);
+    assert!((p(100.0) - 0.5).abs() < 1e-6);
+    assert!((p(120.0) - 0.1).abs() < 1e-6);
+    assert!((p(80.0) - 0.9).abs() < 1e-6);
+    assert!(p(200.0) < 1e-6);
+    assert_eq!(p(-1.0), 1.0);
+
+    // A skewed interval takes each side's spread
+    let skewed = |threshold: f64| probability(100.0, 95.0, 140.0, 0.8, threshold);
+    assert!((skewed(140.0) - 0.1).abs() < 1e-6);
+    assert!((skewed(95.0) - 0.9).abs() < 1e-6);
+
+    // Without spread, it's the point forecast
+    assert_eq!(probability(100.0, 100.0, 100.0, 0.8, 90.0), 1.0);
+    assert_eq!(probability(100.0, 100.0, 100.0, 0.8, 110.0), 0.0);
+}
+
+#[test]
+fn one_curve_per_threshold() {
+    let forecast = Forecast {
+        timestamps: (0..3).map(|h| ORIGIN + h * HOUR).collect(),
+        point: vec![50.0, 150.0, 250.0],
+        lower: vec![30.0, 120.0, 200.0],
+        upper: vec![70.0, 180.0, 300.0],
+    };
+    let curves = curves(&forecast, 0.8, &[150.0, 200.0]);
+    assert_eq!(curves.len(), 2);
+    assert_eq!(curves[0].threshold_kw, 150.0);
+    // Rising demand, rising probability
+    assert!(
+        curves[0].probabilities.windows(2).all(|w| w[0] < w[1]),
+        "{:?}",
+        curves[0].probabilities
+    );
+    assert!((curves[0].probabilities[1] - 0.5).abs() < 1e-6);
+    assert!(
+        curves[1]
+            .probabilities
+            .iter()
+            .zip(&curves[0].probabilities)
+            .all(|(high, low)| high <= low)
+    );
+    assert_eq!(curves[0].highest().unwrap().0, 2);
+    let expected: f64 = curves[0].probabilities.iter().sum();
+    assert!((curves[0].expected_hours(HOUR) - expected).abs() < 1e-12);
+    assert!((curves[0].expected_hours(15 * 60) - expected / 4.0).abs() < 1e-12);
+}
+
+#[test]
+fn curtailment_brings_the_risk_down_to_the_tolerance() {
+    // The interval bound is the 90% quantile
+    assert!((quantile(100.0, 80.0, 120.0, 0.8, 0.9) - 120.0).abs() < 1e-6);
+    assert!(
+        (probability(
+            100.0,
+            80.0,
+            120.0,
+            0.8,
+            quantile(100.0, 80.0, 120.0, 0.8, 0.7)
+        ) - 0.3)
+            .abs()
+            < 1e-6
+    );
+
+    let forecast = Forecast {
+        timestamps: (0..3).map(|h| ORIGIN + h * HOUR).collect(),
+        point: vec![50.0, 180.0, 240.0],
+        lower: vec![40.0, 160.0, 200.0],
+        upper: vec![60.0, 200.0, 280.0],
+    };
+    let curtailment = Curtailment::new(&forecast, 0.8, 200.0, 0.1);
+    // Quiet hours need nothing; busy ones shed down to the 10% risk
+    assert_eq!(curtailment.curtail_kw[0], 0.0);
+    assert!(
+        curtailment.curtail_kw[1].abs() < 1e-6,
+        "{:?}",
+        curtailment.curtail_kw
+    );
+    assert!(
+        (curtailment.curtail_kw[2] - 80.0).abs() < 1e-6,
+        "{:?}",
+        curtailment.curtail_kw
+    );
+    assert!(curtailment.before[2] > 0.5);
+    assert_eq!(curtailment.after()[2], 0.1);
+    assert_eq!(curtailment.after()[0], curtailment.before[0]);
+
+    // A lower tolerance sheds more
+    let cautious = Curtailment::new(&forecast, 0.8, 200.0, 0.01);
+    assert!(cautious.curtail_kw[1] > 0.0 && cautious.curtail_kw[2] > 80.0);
+    let (steps, kwh) = curtailment.totals(HOUR);
+    assert_eq!(steps, 1);
+    assert!((kwh - 80.0).abs() < 1e-6);
+}
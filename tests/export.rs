@@ -0,0 +1,104 @@
+//! Forecast exporters: CSV, JSON and CIM schedule XML.
+
+use cpo_charging_forecast::cim::CimExporter;
+use cpo_charging_forecast::config::{CimConfig, ExportFormat, OutputConfig};
+use cpo_charging_forecast::export::{self, CsvExporter, ExportContext, Exporter};
+use cpo_charging_forecast::forecast::Forecast;
+
+/// 2024-03-31 00:00, the night clocks go forward in Europe.
+const ORIGIN: i64 = 1_711_843_200;
+const HOUR: i64 = 3600;
+
+fn forecast(hours: i64) -> Forecast {
+    let n = hours as usize;
+    Forecast {
+        timestamps: (0..hours).map(|h| ORIGIN + h * HOUR).collect(),
+        point: (0..hours).map(|h| 10.0 + h as f64).collect(),
+        lower: vec![5.0; n],
+        upper: vec![20.0; n],
+    }
+}
+
+fn context() -> ExportContext {
+    ExportContext {
+        site: "north".to_string(),
+        model_version: "abc".to_string(),
+        issued_at: ORIGIN - 6 * HOUR,
+        timezone: Some(chrono_tz::Europe::Berlin),
+    }
+}
+
+fn written(exporter: &dyn Exporter, forecast: &Forecast) -> String {
+    let mut out = Vec::new();
+    exporter.write(forecast, &context(), &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn cim_config() -> CimConfig {
+    CimConfig {
+        sender_mrid: "10X-CPO".to_string(),
+        receiver_mrid: "10X-DSO".to_string(),
+        ..CimConfig::default()
+    }
+}
+
+#[test]
+fn csv_and_json_carry_the_points() {
+    let csv = written(&CsvExporter, &forecast(2));
+    assert_eq!(
+        csv.lines().next(),
+        Some("timestamp,yhat,yhat_lower,yhat_upper")
+    );
+    assert_eq!(csv.lines().count(), 3);
+
+    let json: serde_json::Value = serde_json::from_str(&written(
+        export::exporter(ExportFormat::Json, &OutputConfig::default()).as_ref(),
+        &forecast(2),
+    ))
+    .unwrap();
+    assert_eq!(json["site"], "north");
+    assert_eq!(json["points"].as_array().unwrap().len(), 2);
+    assert_eq!(json["points"][1]["yhat"], 11.0);
+}
+
+#[test]
+fn cim_schedule_is_in_utc_positions() {
+    let xml = written(&CimExporter::new(cim_config()), &forecast(4));
+    assert!(xml.contains(r#"<receiver_MarketParticipant.mRID codingScheme="A01">10X-DSO</receiver_MarketParticipant.mRID>"#));
+    assert!(xml.contains(
+        "<MarketEvaluationPoint.mRID codingScheme=\"A01\">north</MarketEvaluationPoint.mRID>"
+    ));
+    // Local midnight is 23:00 UTC; 02:00 doesn't exist locally and is left out
+    assert!(xml.contains("<start>2024-03-30T23:00Z</start>"));
+    assert!(xml.contains("<end>2024-03-31T02:00Z</end>"));
+    assert!(xml.contains("<resolution>PT60M</resolution>"));
+    let positions: Vec<&str> = xml
+        .lines()
+        .filter_map(|l| {
+            l.trim()
+                .strip_prefix("<position>")?
+                .strip_suffix("</position>")
+        })
+        .collect();
+    assert_eq!(positions, vec!["1", "2", "3"]);
+    assert!(xml.contains("<quantity>13.000</quantity>"));
+    assert_eq!(xml.matches("<TimeSeries>").count(), 1);
+}
+
+#[test]
+fn cim_bounds_and_units() {
+    let config = CimConfig {
+        bounds: true,
+        unit: "MAW".to_string(),
+        measurement_point: Some("DE0001".to_string()),
+        ..cim_config()
+    };
+    let xml = written(&CimExporter::new(config), &forecast(1));
+    assert_eq!(xml.matches("<TimeSeries>").count(), 3);
+    assert!(xml.contains("<quantity>0.020000</quantity>"));
+    assert!(xml.contains(">DE0001</MarketEvaluationPoint.mRID>"));
+
+    let mut out = Vec::new();
+    let unnamed = CimExporter::new(CimConfig::default());
+    assert!(unnamed.write(&forecast(1), &context(), &mut out).is_err());
+}
@@ -0,0 +1,138 @@
+//! The append-only forecast log and its rotation.
+
+use chrono::{DateTime, Utc};
+use cpo_charging_forecast::config::{Config, ForecastLogConfig};
+use cpo_charging_forecast::forecast::Forecast;
+use cpo_charging_forecast::forecast_log::{self, rotated};
+
+/// 2024-03-31 00:00 local, the night clocks go forward in Europe.
+const ORIGIN: i64 = 1_711_843_200;
+const HOUR: i64 = 3600;
+
+fn forecast(hours: i64) -> Forecast {
+    let n = hours as usize;
+    Forecast {
+        timestamps: (0..hours).map(|h| ORIGIN + h * HOUR).collect(),
+        point: (0..hours).map(|h| 10.0 + h as f64).collect(),
+        lower: vec![5.0; n],
+        upper: vec![20.0; n],
+    }
+}
+
+fn issued(hour: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(ORIGIN - 6 * HOUR + hour * HOUR, 0).unwrap()
+}
+
+#[test]
+fn entries_carry_issue_and_target_times() {
+    let entries = forecast_log::entries(
+        "north",
+        issued(0),
+        &forecast(4),
+        "1.0+abc",
+        Some(chrono_tz::Europe::Berlin),
+    );
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[0].issue_time, "2024-03-30T18:00:00Z");
+    assert_eq!(entries[0].run_id, "20240330T180000Z");
+    assert_eq!(entries[1].target_time, "2024-03-31 01:00");
+    assert_eq!(
+        entries[1].target_utc.as_deref(),
+        Some("2024-03-31T00:00:00Z")
+    );
+    // 02:00 doesn't exist locally
+    assert_eq!(entries[2].target_utc, None);
+    assert_eq!(
+        entries[3].target_utc.as_deref(),
+        Some("2024-03-31T01:00:00Z")
+    );
+    assert_eq!(
+        (
+            entries[3].yhat,
+            entries[3].yhat_lower,
+            entries[3].yhat_upper
+        ),
+        (13.0, 5.0, 20.0)
+    );
+
+    let floating = forecast_log::entries("north", issued(0), &forecast(1), "1.0+abc", None);
+    let line = serde_json::to_string(&floating[0]).unwrap();
+    assert!(!line.contains("target_utc"), "{}", line);
+}
+
+#[test]
+fn runs_append_and_rotate() {
+    let dir = std::env::temp_dir().join(format!("cpo-forecast-log-{}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    let path = dir.join("forecasts.jsonl").display().to_string();
+    let line_bytes = serde_json::to_string(
+        &forecast_log::entries("north", issued(0), &forecast(1), "1.0+abc", None)[0],
+    )
+    .unwrap()
+    .len() as u64
+        + 1;
+    let config = ForecastLogConfig {
+        path: path.clone(),
+        // Room for two runs of three points
+        max_bytes: 6 * line_bytes,
+        keep: 2,
+    };
+    for run in 0..7 {
+        forecast_log::append(
+            &config,
+            &forecast_log::entries("north", issued(run), &forecast(3), "1.0+abc", None),
+        )
+        .unwrap();
+    }
+    let runs = |n: usize| -> Vec<String> {
+        let mut runs: Vec<String> = forecast_log::read(rotated(&path, n))
+            .unwrap()
+            .into_iter()
+            .map(|e| e.run_id)
+            .collect();
+        runs.dedup();
+        runs
+    };
+    // Two runs per file, newest in the log itself; the oldest rotated out
+    assert_eq!(runs(0), vec!["20240331T000000Z"]);
+    assert_eq!(runs(1), vec!["20240330T220000Z", "20240330T230000Z"]);
+    assert_eq!(runs(2), vec!["20240330T200000Z", "20240330T210000Z"]);
+    assert!(!rotated(&path, 3).exists());
+    assert_eq!(forecast_log::read(&path).unwrap().len(), 3);
+    std::fs::remove_dir_all(&dir).ok();
+
+    let remote = ForecastLogConfig {
+        path: "s3://audit/forecasts.jsonl".to_string(),
+        ..ForecastLogConfig::default()
+    };
+    assert!(forecast_log::append(&remote, &[]).is_err());
+}
+
+#[test]
+fn every_site_issuing_at_once_is_logged() {
+    let dir = std::env::temp_dir().join(format!("cpo-forecast-log-sites-{}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    let path = dir.join("forecasts.jsonl").display().to_string();
+    let mut config = Config::default();
+    // Without a [forecast_log] nothing is written
+    forecast_log::log(&config, issued(0), &forecast(3)).unwrap();
+    config.forecast_log = Some(ForecastLogConfig {
+        path: path.clone(),
+        ..ForecastLogConfig::default()
+    });
+
+    // As the server's fit workers do
+    std::thread::scope(|scope| {
+        for site in 0..8 {
+            let mut config = config.clone();
+            config.site = format!("site-{}", site);
+            scope.spawn(move || forecast_log::log(&config, issued(site), &forecast(3)).unwrap());
+        }
+    });
+    let entries = forecast_log::read(&path).unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+    assert_eq!(entries.len(), 8 * 3);
+    let mut sites: Vec<String> = entries.into_iter().map(|e| e.site).collect();
+    sites.dedup();
+    assert_eq!(sites.len(), 8);
+}
@@ -0,0 +1,104 @@
+//! The least-squares trend and seasonality fit, as a method and as a fallback.
+
+use augurs::prophet::optimizer::{
+    self, Data, InitialParams, OptimizeOpts, OptimizedParams, Optimizer,
+};
+use cpo_charging_forecast::config::{Fallback, Growth, Method, ModelConfig};
+use cpo_charging_forecast::data::Series;
+use cpo_charging_forecast::forecast::{self, fit};
+
+/// 2024-01-01 00:00, a Monday.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+
+/// A daily cycle, busier on weekdays, on a trend rising 2 kW a week.
+fn load(h: i64) -> f64 {
+    let daily = 8.0 * (2.0 * std::f64::consts::PI * (h % 24) as f64 / 24.0).sin();
+    let weekday = if (h / 24) % 7 < 5 { 4.0 } else { -6.0 };
+    30.0 + 2.0 * h as f64 / 168.0 + daily + weekday
+}
+
+fn history(hours: i64) -> Series {
+    Series::new(
+        (0..hours).map(|h| ORIGIN + h * HOUR).collect(),
+        (0..hours).map(load).collect(),
+    )
+}
+
+#[test]
+fn trend_and_seasonality_carry_into_the_future() {
+    let history = history(8 * 168);
+    let model = ModelConfig {
+        method: Method::Fourier,
+        ..ModelConfig::default()
+    };
+    let fitted = fit(&history, &model).unwrap();
+    assert_eq!(fitted.name(), "fourier");
+
+    let future: Vec<i64> = (8 * 168..9 * 168).map(|h| ORIGIN + h * HOUR).collect();
+    let forecast = fitted.predict(future).unwrap();
+    let error = (0..168)
+        .map(|i| (forecast.point[i] - load(8 * 168 + i as i64)).abs())
+        .sum::<f64>()
+        / 168.0;
+    assert!(error < 2.0, "mean absolute error {:.2} kW", error);
+    assert!(
+        (0..168)
+            .all(|i| forecast.lower[i] <= forecast.point[i]
+                && forecast.point[i] <= forecast.upper[i])
+    );
+
+    // The components add up to the fit
+    let decomposition = fitted.decompose().unwrap();
+    let contributions: f64 = decomposition
+        .contributions(100)
+        .iter()
+        .map(|(_, v)| v)
+        .sum();
+    assert!(
+        (decomposition.trend[100] + contributions - decomposition.fitted.point[100]).abs() < 1e-9
+    );
+    let names: Vec<&str> = decomposition
+        .components
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        ["additive_terms", "multiplicative_terms", "daily", "weekly"]
+    );
+
+    let flat = ModelConfig {
+        growth: Growth::Flat,
+        ..model
+    };
+    assert!(fit(&history, &flat).unwrap().changepoints().is_empty());
+}
+
+/// An optimizer that never gets anywhere, as if Stan weren't there.
+#[derive(Debug)]
+struct Unavailable;
+
+impl Optimizer for Unavailable {
+    fn optimize(
+        &self,
+        _: &InitialParams,
+        _: &Data,
+        _: &OptimizeOpts,
+    ) -> Result<OptimizedParams, optimizer::Error> {
+        Err(optimizer::Error::string("no Stan runtime".to_string()))
+    }
+}
+
+#[test]
+fn prophet_falls_back_to_it_without_stan() {
+    forecast::set_optimizer(|| Box::new(Unavailable));
+    let model = ModelConfig {
+        fallbacks: vec![Fallback::Fourier, Fallback::SeasonalNaive],
+        ..ModelConfig::default()
+    };
+    assert_eq!(fit(&history(4 * 168), &model).unwrap().name(), "fourier");
+
+    // Too short for it, as for Prophet
+    assert_eq!(fit(&history(24), &model).unwrap().name(), "seasonal naive");
+}
@@ -0,0 +1,187 @@
+//! Golden-file regression tests: fit on the bundled synthetic export and
+//! compare the forecasts with the stored ones in `tests/golden/`.
+//!
+//! After an intended change in the numbers, regenerate the files with
+//! `UPDATE_GOLDEN=1 cargo test --test golden` and review the diff.
+
+#![cfg(feature = "wasmstan")]
+
+use std::path::Path;
+
+use cpo_charging_forecast::config::{Config, Fallback, Method, ModelConfig, Transform};
+use cpo_charging_forecast::data::parse_datetime_to_timestamp;
+use cpo_charging_forecast::export;
+use cpo_charging_forecast::forecast::Forecast;
+use cpo_charging_forecast::pipeline::{fit_and_forecast, load_history};
+
+/// `synth --days 56 --seed 1`, kept in the repo so generator changes don't move the goldens.
+const DATA: &str = "tests/data/synthetic.csv";
+const HORIZON_HOURS: u32 = 48;
+/// Allowed difference, in kW, on top of the relative tolerances.
+const ABS_TOLERANCE: f64 = 0.05;
+const REL_TOLERANCE: f64 = 1e-3;
+/// Prophet simulates its intervals without a seed, so the bounds are only
+/// checked loosely per hour (as a share of the interval width) and more
+/// tightly as the mean width over the horizon.
+const BOUND_TOLERANCE: f64 = 0.5;
+const MEAN_WIDTH_TOLERANCE: f64 = 0.05;
+const PROPHET_POINT_SHARE: f64 = 0.05;
+
+fn config(model: ModelConfig) -> Config {
+    let mut config = Config::default();
+    config.data.path = DATA.to_string();
+    config.forecast.horizon_hours = HORIZON_HOURS;
+    config.model = model;
+    config
+}
+
+fn read_golden(path: &Path) -> Forecast {
+    let mut reader = csv::Reader::from_path(path).unwrap_or_else(|e| {
+        panic!(
+            "{}: {} (run with UPDATE_GOLDEN=1 to create it)",
+            path.display(),
+            e
+        )
+    });
+    let mut forecast = Forecast::default();
+    for record in reader.records() {
+        let record = record.unwrap();
+        let value = |i: usize| record[i].parse::<f64>().unwrap();
+        forecast
+            .timestamps
+            .push(parse_datetime_to_timestamp(&record[0]).unwrap());
+        forecast.point.push(value(1));
+        forecast.lower.push(value(2));
+        forecast.upper.push(value(3));
+    }
+    forecast
+}
+
+fn mean_width(forecast: &Forecast) -> f64 {
+    let total: f64 = forecast
+        .upper
+        .iter()
+        .zip(&forecast.lower)
+        .map(|(u, l)| u - l)
+        .sum();
+    total / forecast.len().max(1) as f64
+}
+
+/// Fit and forecast with `model`, and compare with `tests/golden/{name}.csv`.
+///
+/// `point_width_share` loosens the point check by a share of the interval
+/// width. Prophet's fit varies slightly from process to process, since augurs
+/// keeps its seasonalities in a `HashMap`, and a transformed model's point
+/// forecast also depends on the simulated interval.
+fn check(name: &str, model: ModelConfig, point_width_share: f64) {
+    let config = config(model);
+    let history = load_history(&config).unwrap();
+    let (_, forecast) = fit_and_forecast(&config, &history).unwrap();
+
+    let path = Path::new("tests/golden").join(format!("{}.csv", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        export::write_forecast_csv(&forecast, path.to_str().unwrap()).unwrap();
+        return;
+    }
+    let golden = read_golden(&path);
+    assert_eq!(
+        forecast.timestamps, golden.timestamps,
+        "{}: forecast timestamps changed",
+        name
+    );
+    for i in 0..golden.len() {
+        let width = golden.upper[i] - golden.lower[i];
+        let point_tolerance =
+            ABS_TOLERANCE + REL_TOLERANCE * golden.point[i].abs() + point_width_share * width;
+        let bound_tolerance = ABS_TOLERANCE + BOUND_TOLERANCE * width;
+        for (column, actual, expected, tolerance) in [
+            ("yhat", forecast.point[i], golden.point[i], point_tolerance),
+            (
+                "yhat_lower",
+                forecast.lower[i],
+                golden.lower[i],
+                bound_tolerance,
+            ),
+            (
+                "yhat_upper",
+                forecast.upper[i],
+                golden.upper[i],
+                bound_tolerance,
+            ),
+        ] {
+            assert!(
+                (actual - expected).abs() <= tolerance,
+                "{}: {} at hour {} is {:.3}, golden {:.3}",
+                name,
+                column,
+                i + 1,
+                actual,
+                expected
+            );
+        }
+    }
+    let (actual, expected) = (mean_width(&forecast), mean_width(&golden));
+    assert!(
+        (actual - expected).abs() <= ABS_TOLERANCE + MEAN_WIDTH_TOLERANCE * expected,
+        "{}: mean interval width is {:.3}, golden {:.3}",
+        name,
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn prophet() {
+    check("prophet", ModelConfig::default(), PROPHET_POINT_SHARE);
+}
+
+#[test]
+fn prophet_with_lags() {
+    check(
+        "prophet_lags",
+        ModelConfig {
+            lags: vec![24, 168],
+            rolling_means: vec![24],
+            ..ModelConfig::default()
+        },
+        PROPHET_POINT_SHARE,
+    );
+}
+
+#[test]
+fn prophet_log1p() {
+    check(
+        "prophet_log1p",
+        ModelConfig {
+            transform: Transform::Log1p,
+            ..ModelConfig::default()
+        },
+        0.1,
+    );
+}
+
+#[test]
+fn tsb() {
+    check(
+        "tsb",
+        ModelConfig {
+            method: Method::Tsb,
+            ..ModelConfig::default()
+        },
+        0.0,
+    );
+}
+
+#[test]
+fn seasonal_naive_fallback() {
+    // Too short for Prophet, so the fallback chain kicks in
+    check(
+        "seasonal_naive",
+        ModelConfig {
+            min_history_hours: 100_000,
+            fallbacks: vec![Fallback::SeasonalNaive],
+            ..ModelConfig::default()
+        },
+        0.0,
+    );
+}
@@ -0,0 +1,85 @@
+//! Data guards: stale or patchy histories are refused before forecasting.
+
+use cpo_charging_forecast::config::GuardConfig;
+use cpo_charging_forecast::data::{RowCounts, Series};
+use cpo_charging_forecast::guards::{check, freshness};
+use cpo_charging_forecast::source::check_hourly;
+
+/// 2024-01-01 00:00.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+
+/// A load series of `hours` hours, leaving out those in `missing`.
+fn meter(hours: i64, missing: std::ops::Range<i64>) -> Series {
+    let hours: Vec<i64> = (0..hours).filter(|h| !missing.contains(h)).collect();
+    Series::new(
+        hours.iter().map(|h| ORIGIN + h * HOUR).collect(),
+        vec![10.0; hours.len()],
+    )
+}
+
+#[test]
+fn recent_complete_histories_pass() {
+    let config = GuardConfig::default();
+    let history = meter(60 * 24, 0..0);
+    let now = ORIGIN + 60 * 24 * HOUR + 2 * HOUR;
+    let fresh = check(&history, &RowCounts::default(), now, &config).unwrap();
+    assert_eq!(fresh.last_observation, ORIGIN + (60 * 24 - 1) * HOUR);
+    assert_eq!(fresh.age_hours, 2.0);
+    assert!((fresh.missing_share - 2.0 / 720.0).abs() < 1e-12);
+}
+
+#[test]
+fn stale_histories_are_refused() {
+    let config = GuardConfig::default();
+    let history = meter(60 * 24, 0..0);
+    let now = ORIGIN + 60 * 24 * HOUR + 49 * HOUR;
+    let error = check(&history, &RowCounts::default(), now, &config)
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("is 49 hours old"), "{}", error);
+
+    let relaxed = GuardConfig {
+        max_age_hours: 72,
+        ..GuardConfig::default()
+    };
+    assert!(check(&history, &RowCounts::default(), now, &relaxed).is_ok());
+}
+
+#[test]
+fn gaps_in_a_load_series_count_as_missing() {
+    // A week without readings in the last month
+    let mut counts = RowCounts::default();
+    let history = check_hourly(meter(60 * 24, 40 * 24..47 * 24), &mut counts);
+    assert_eq!(history.len(), 60 * 24);
+    assert_eq!(
+        counts.gaps,
+        vec![(ORIGIN + 40 * 24 * HOUR, ORIGIN + 47 * 24 * HOUR)]
+    );
+
+    let now = ORIGIN + 60 * 24 * HOUR;
+    let share = freshness(&history, &counts, now, &GuardConfig::default())
+        .unwrap()
+        .missing_share;
+    assert!((share - 7.0 / 30.0).abs() < 1e-12, "{}", share);
+    let error = check(&history, &counts, now, &GuardConfig::default())
+        .err()
+        .unwrap();
+    assert!(
+        error.to_string().contains("23% of the last 720 hours"),
+        "{}",
+        error
+    );
+
+    // Outside the window, it doesn't
+    let counts = RowCounts {
+        gaps: vec![(ORIGIN, ORIGIN + 7 * 24 * HOUR)],
+        ..RowCounts::default()
+    };
+    assert_eq!(
+        freshness(&history, &counts, now, &GuardConfig::default())
+            .unwrap()
+            .missing_share,
+        0.0
+    );
+}
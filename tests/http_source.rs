@@ -0,0 +1,124 @@
+//! Pulling sessions from a REST endpoint, against a local stand-in server.
+
+#![cfg(feature = "native")]
+
+use std::thread;
+
+use cpo_charging_forecast::config::{HttpAuth, HttpSourceConfig, Pagination};
+use cpo_charging_forecast::http_source::fetch_sessions;
+use tiny_http::{Header, Response, Server};
+
+/// Serve `pages` in turn, answering 401 without the expected header.
+///
+/// Each page is picked by `page_of(url)`; the URLs requested are returned.
+fn serve(
+    header: (&'static str, &'static str),
+    pages: Vec<&'static str>,
+    page_of: fn(&str) -> usize,
+) -> (String, thread::JoinHandle<Vec<String>>) {
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", server.server_addr().to_ip().unwrap());
+    let handle = thread::spawn(move || {
+        let mut urls = Vec::new();
+        for _ in 0..pages.len() {
+            let request = server.recv().unwrap();
+            urls.push(request.url().to_string());
+            let authorized = request
+                .headers()
+                .iter()
+                .any(|h| h.field.equiv(header.0) && h.value.as_str() == header.1);
+            let response = if authorized {
+                let json = Header::from_bytes("Content-Type", "application/json").unwrap();
+                Response::from_string(pages[page_of(request.url())]).with_header(json)
+            } else {
+                Response::from_string("unauthorized").with_status_code(401)
+            };
+            request.respond(response).unwrap();
+        }
+        urls
+    });
+    (base, handle)
+}
+
+#[test]
+fn follows_cursors_with_a_bearer_token() {
+    let pages = vec![
+        r#"{"result": {"items": [
+            {"begin": "2024-01-01 10:00", "end": "2024-01-01 11:30", "kwh": 12.5},
+            {"begin": "2024-01-01 12:00", "kwh": 0}
+        ]}, "next": "abc"}"#,
+        r#"{"result": {"items": [{"begin": 1704117600, "end": "2024-01-01 15:00", "kwh": "3"}]}, "next": null}"#,
+    ];
+    let (base, server) = serve(("Authorization", "Bearer secret"), pages, |url| {
+        url.contains("cursor=abc") as usize
+    });
+    let config = HttpSourceConfig {
+        url: format!("{}/sessions", base),
+        query: [("site".to_string(), "north".to_string())].into(),
+        auth: HttpAuth::Bearer,
+        token: Some("secret".to_string()),
+        pagination: Pagination::Next,
+        records_field: "result.items".to_string(),
+        start_field: "begin".to_string(),
+        stop_field: Some("end".to_string()),
+        energy_field: "kwh".to_string(),
+        energy_scale: 1000.0,
+        ..HttpSourceConfig::default()
+    };
+    let (sessions, counts) = fetch_sessions(&config, None, false).unwrap();
+    let urls = server.join().unwrap();
+
+    assert_eq!(
+        urls,
+        ["/sessions?site=north", "/sessions?site=north&cursor=abc"]
+    );
+    assert_eq!(
+        (counts.read, counts.non_positive, counts.invalid),
+        (3, 1, 0)
+    );
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions[0].energy_wh, 12_500.0);
+    assert_eq!(sessions[0].stop - sessions[0].start, 90 * 60);
+    assert_eq!(sessions[1].start, 1_704_117_600);
+}
+
+#[test]
+fn counts_pages_up_with_an_api_key() {
+    let pages = vec![
+        r#"[{"start": "2024-01-01 10:00", "energy_wh": 100}, {"start": "2024-01-01 11:00", "energy_wh": -200}]"#,
+        r#"[{"start": "not a time", "energy_wh": 300}]"#,
+    ];
+    let (base, server) = serve(("X-API-Key", "k"), pages, |url| {
+        url.contains("page=2") as usize
+    });
+    let config = HttpSourceConfig {
+        url: base,
+        auth: HttpAuth::ApiKey,
+        token: Some("k".to_string()),
+        pagination: Pagination::Page,
+        page_size_param: Some("limit".to_string()),
+        page_size: 2,
+        records_field: String::new(),
+        ..HttpSourceConfig::default()
+    };
+    // Export, signed, is kept
+    let (sessions, counts) = fetch_sessions(&config, None, true).unwrap();
+    let urls = server.join().unwrap();
+
+    // The short second page ends it
+    assert_eq!(urls, ["/?limit=2&page=1", "/?limit=2&page=2"]);
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions[1].energy_wh, -200.0);
+    assert_eq!(counts.invalid, 1);
+}
+
+#[test]
+fn a_missing_token_is_an_error() {
+    let config = HttpSourceConfig {
+        url: "http://127.0.0.1:9/sessions".to_string(),
+        auth: HttpAuth::Bearer,
+        ..HttpSourceConfig::default()
+    };
+    let e = fetch_sessions(&config, None, false).unwrap_err();
+    assert!(e.to_string().contains("data.http.token"), "{}", e);
+}
@@ -0,0 +1,204 @@
+//! Session export parsing: the memory-mapped, parallel and streamed paths,
+//! and the hand-written timestamp parser against chrono.
+
+use cpo_charging_forecast::config::{ColumnConfig, Config, LocaleConfig, SynthConfig};
+use cpo_charging_forecast::data::{
+    Session, SkippedRow, load_sessions_counted, load_sessions_formatted, read_sessions,
+    read_sessions_counted,
+};
+use cpo_charging_forecast::outcome::{Status, status_of};
+use cpo_charging_forecast::pipeline::load_history_counted;
+use cpo_charging_forecast::source::csv_format;
+use cpo_charging_forecast::synth;
+use proptest::prelude::*;
+
+/// 2024-01-01 00:00, local time.
+const ORIGIN: i64 = 1_704_067_200;
+
+fn session() -> impl Strategy<Value = Session> {
+    (0..60 * 24 * 3650i64, 0..48 * 60i64, 1.0..200_000.0f64).prop_map(
+        |(start, minutes, energy_wh)| {
+            let start = ORIGIN + start * 60;
+            Session {
+                start,
+                stop: start + minutes * 60,
+                // Whole Wh, as written to the export
+                energy_wh: energy_wh.round(),
+            }
+        },
+    )
+}
+
+proptest! {
+    #[test]
+    fn mapped_and_streamed_reads_agree(mut sessions in prop::collection::vec(session(), 1..200)) {
+        sessions.sort_by_key(|s| s.start);
+        let path = std::env::temp_dir().join(format!("cpo-ingest-{}.csv", std::process::id()));
+        synth::write_sessions_csv(&sessions, &SynthConfig::default(), path.to_str().unwrap()).unwrap();
+
+        let mapped = load_sessions_counted(&path).unwrap();
+        let streamed = read_sessions_counted(std::fs::File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).ok();
+        prop_assert_eq!(&mapped, &streamed);
+        prop_assert_eq!(mapped.0, sessions);
+    }
+}
+
+#[test]
+fn unpadded_and_padded_timestamps_parse_alike() {
+    let padded = "h,start,stop,d,p,i,w,energy\n\
+                  x,2024-03-05 07:05,2024-03-05 08:00,0,,1,2,1000\n\
+                  x, 2024-12-31 23:59 ,2025-01-01 00:30,0,,1,2,2000\n";
+    let unpadded = "h,start,stop,d,p,i,w,energy\n\
+                    x,2024-3-5 7:05,2024-3-5 8:00,0,,1,2,1000\n\
+                    x,2024-12-31 23:59,2025-1-1 0:30,0,,1,2,2000\n";
+    let sessions = read_sessions(padded.as_bytes()).unwrap();
+    assert_eq!(sessions, read_sessions(unpadded.as_bytes()).unwrap());
+    assert_eq!(sessions[0].start, 1_709_622_300);
+    assert_eq!(sessions[1].stop - sessions[1].start, 31 * 60);
+}
+
+#[test]
+fn impossible_dates_are_invalid_rows() {
+    let csv = "h,start,stop,d,p,i,w,energy\n\
+               x,2024-02-30 10:00,,0,,1,2,1000\n\
+               x,2024-02-29 24:00,,0,,1,2,1000\n\
+               x,2024-02-29 10:00,,0,,1,2,1000\n";
+    let (sessions, counts) = read_sessions_counted(csv.as_bytes()).unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!((counts.read, counts.invalid), (3, 2));
+}
+
+#[test]
+fn skipped_rows_say_where_and_why() {
+    let csv = "h,start,stop,d,p,i,w,energy\n\
+               x,2024-02-29 10:00,,0,,1,2,1000\n\
+               x,yesterday,,0,,1,2,1000\n\
+               x,2024-02-29 11:00,,0,,1,2,lots\n\
+               x,2024-02-29 12:00\n";
+    let (_, counts) = read_sessions_counted(csv.as_bytes()).unwrap();
+    let skipped = |row: usize, column: Option<&str>, reason: &str| SkippedRow {
+        row,
+        column: column.map(str::to_string),
+        reason: reason.to_string(),
+    };
+    assert_eq!(
+        counts.skipped,
+        vec![
+            skipped(3, Some("Start time"), "bad timestamp \"yesterday\""),
+            skipped(4, Some("Modified Count.Energy (Wh)"), "bad energy \"lots\""),
+            skipped(5, None, "2 columns, 8 expected"),
+        ]
+    );
+    assert_eq!(counts.skip_rate(), 0.75);
+
+    // Only a strict run fails on them
+    let path = std::env::temp_dir().join(format!("cpo-ingest-strict-{}.csv", std::process::id()));
+    std::fs::write(&path, csv).unwrap();
+    let mut config = Config::default();
+    config.data.path = path.display().to_string();
+    assert_eq!(load_history_counted(&config).unwrap().1.invalid, 3);
+    config.data.strict = true;
+    config.data.max_skip_rate = 0.8;
+    assert!(load_history_counted(&config).is_ok());
+    config.data.max_skip_rate = 0.5;
+    let error = load_history_counted(&config).err().unwrap();
+    std::fs::remove_file(&path).ok();
+    assert_eq!(status_of(error.as_ref()), Status::DataError);
+    assert!(
+        error.to_string().contains("3 of 4 rows (75.0%)"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn local_separators_and_dates_parse_like_the_default() {
+    let default = "h,start,stop,d,p,i,w,energy\n\
+                   x,2024-03-05 07:05,2024-03-05 08:00,0,,1,2,1234.5\n\
+                   x,2024-12-31 23:59,2025-01-01 00:30,0,,1,2,12000\n";
+    let german = "h;start;stop;d;p;i;w;energy\n\
+                  x;05.03.2024 07:05;05.03.2024 08:00;0;;1;2;1.234,5\n\
+                  x;2024-12-31 23:59;01.01.2025 00:30;0;;1;2;12.000\n";
+    let path = std::env::temp_dir().join(format!("cpo-ingest-locale-{}.csv", std::process::id()));
+    std::fs::write(&path, german).unwrap();
+    let locale = LocaleConfig {
+        delimiter: ';',
+        decimal: ',',
+        thousands: Some('.'),
+        date_formats: vec!["%d.%m.%Y %H:%M".to_string()],
+    };
+    let (sessions, counts) = load_sessions_formatted(
+        &path,
+        &csv_format(&locale, &ColumnConfig::default(), "", false).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(counts.invalid, 0);
+    assert_eq!(sessions, read_sessions(default.as_bytes()).unwrap());
+
+    // The default format reads every row as a single field
+    assert!(load_sessions_counted(&path).is_err());
+    let mut config = Config::default();
+    config.data.path = path.display().to_string();
+    config.data.locale = locale.clone();
+    assert_eq!(load_history_counted(&config).unwrap().1.invalid, 0);
+    std::fs::remove_file(&path).ok();
+
+    let ambiguous = LocaleConfig {
+        thousands: Some(';'),
+        ..locale
+    };
+    assert!(csv_format(&ambiguous, &ColumnConfig::default(), "", false).is_err());
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn parallel_chunks_match_one_pass() {
+    use cpo_charging_forecast::data::read_sessions_bytes;
+
+    // Past the parallel threshold, with a few rows that get skipped
+    let config = SynthConfig {
+        days: 365,
+        sessions_per_day: 100.0,
+        ..SynthConfig::default()
+    };
+    let path = std::env::temp_dir().join(format!("cpo-ingest-parallel-{}.csv", std::process::id()));
+    synth::write_sessions_csv(
+        &synth::sessions(&config).unwrap(),
+        &config,
+        path.to_str().unwrap(),
+    )
+    .unwrap();
+    let mut csv = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+    for _ in 0..3 {
+        let middle = csv.len() / 2
+            + csv[csv.len() / 2..]
+                .iter()
+                .position(|b| *b == b'\n')
+                .unwrap()
+            + 1;
+        csv.splice(
+            middle..middle,
+            b"x,not a time,,0,,1,2,100\n".iter().copied(),
+        );
+    }
+    assert!(csv.len() > 1 << 20);
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(4)
+        .build()
+        .unwrap();
+    let parallel = pool
+        .install(|| read_sessions_bytes(&csv).map_err(|e| e.to_string()))
+        .unwrap();
+    let sequential = read_sessions_counted(&csv[..]).unwrap();
+    // Skipped rows are numbered from the start of the file, not of their chunk
+    assert_eq!(parallel, sequential);
+    assert_eq!(parallel.1.invalid, 3);
+    assert!(
+        parallel.1.skipped.iter().all(|s| s.row > 1000),
+        "{:?}",
+        parallel.1.skipped
+    );
+}
@@ -0,0 +1,83 @@
+//! Guessing an export's layout, and reading it with the proposed config.
+
+use cpo_charging_forecast::config::{ColumnConfig, Config, EnergyUnit, LocaleConfig, SynthConfig};
+use cpo_charging_forecast::inspect::{self, Kind};
+use cpo_charging_forecast::pipeline::load_history_counted;
+use cpo_charging_forecast::synth;
+
+#[test]
+fn the_usual_export_reads_as_the_defaults() {
+    let config = SynthConfig {
+        days: 14,
+        ..SynthConfig::default()
+    };
+    let path = std::env::temp_dir().join(format!("cpo-inspect-usual-{}.csv", std::process::id()));
+    synth::write_sessions_csv(
+        &synth::sessions(&config).unwrap(),
+        &config,
+        path.to_str().unwrap(),
+    )
+    .unwrap();
+    let sample = inspect::sample(std::fs::File::open(&path).unwrap(), 200).unwrap();
+    std::fs::remove_file(&path).ok();
+
+    let inspection = inspect::inspect(&sample).unwrap();
+    assert!(inspection.header);
+    assert_eq!(inspection.rows, 200);
+    assert_eq!(inspection.locale, LocaleConfig::default());
+    assert_eq!(inspection.mapping, ColumnConfig::default());
+    assert_eq!(inspection.columns[1].kind, Kind::Timestamp);
+    assert!(inspection.site_ids.is_empty());
+}
+
+#[test]
+fn a_localized_multi_site_export_gets_a_working_snippet() {
+    let csv = "Ladepunkt;Standort;Beginn;Ende;Energie (kWh);Preis\n\
+               CP1;Nord;05.03.2024 07:05;05.03.2024 08:00;12,5;3,10\n\
+               CP2;Süd;05.03.2024 09:05;05.03.2024 10:30;1.234,5;300,00\n\
+               CP1;Nord;06.03.2024 17:00;06.03.2024 18:00;22,25;5,00\n";
+    let inspection = inspect::inspect(csv).unwrap();
+    assert_eq!(
+        inspection.locale,
+        LocaleConfig {
+            delimiter: ';',
+            decimal: ',',
+            thousands: Some('.'),
+            date_formats: vec!["%d.%m.%Y %H:%M".to_string()],
+        }
+    );
+    assert_eq!(
+        inspection.mapping,
+        ColumnConfig {
+            start: 2,
+            stop: 3,
+            energy: 4,
+            energy_unit: EnergyUnit::Kwh,
+            site: Some(1),
+        }
+    );
+    assert_eq!(inspection.site_ids, vec!["Nord", "Süd"]);
+
+    // The snippet is a config reading the site's sessions
+    let path =
+        std::env::temp_dir().join(format!("cpo-inspect-localized-{}.csv", std::process::id()));
+    std::fs::write(&path, csv).unwrap();
+    let config: Config = toml::from_str(&inspection.to_toml(path.to_str().unwrap())).unwrap();
+    assert_eq!(config.site, "Nord");
+    let (history, counts) = load_history_counted(&config).unwrap();
+    std::fs::remove_file(&path).ok();
+    assert_eq!((counts.read, counts.invalid), (2, 0));
+    assert!((history.values.iter().sum::<f64>() - 34.75).abs() < 1e-6);
+}
+
+#[test]
+fn unclear_dates_and_missing_columns() {
+    // 03/05 and 04/06 read either way round
+    let csv = "start,kwh\n03/05/2024 10:00,12.5\n04/06/2024 11:00,8\n";
+    let inspection = inspect::inspect(csv).unwrap();
+    assert!(inspection.ambiguous_dates);
+    assert_eq!(inspection.locale.date_formats, vec!["%d/%m/%Y %H:%M"]);
+    assert_eq!(inspection.mapping.stop, inspection.mapping.start);
+
+    assert!(inspect::inspect("name,comment\na,b\nc,d\n").is_err());
+}
@@ -0,0 +1,87 @@
+//! The on-demand fit queue: bounded concurrency, shared and rejected jobs.
+
+#![cfg(feature = "native")]
+
+use cpo_charging_forecast::jobs::{FitJob, FitQueue, JobStatus};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn wait(queue: &FitQueue, id: u64) -> FitJob {
+    for _ in 0..500 {
+        let job = queue.job(id).unwrap();
+        if matches!(job.status, JobStatus::Done | JobStatus::Failed) {
+            return job;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    panic!("job {} never finished", id);
+}
+
+#[test]
+fn fits_run_a_few_at_a_time() {
+    let running = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    // Fits block until the test lets them go
+    let gate = Arc::new(Mutex::new(()));
+    let held = gate.lock().unwrap();
+    let queue = {
+        let (running, peak, gate) = (Arc::clone(&running), Arc::clone(&peak), Arc::clone(&gate));
+        FitQueue::new(2, 3, move |site| {
+            peak.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+            drop(gate.lock().unwrap());
+            running.fetch_sub(1, Ordering::SeqCst);
+            match site {
+                "broken" => Err("optimizer diverged".to_string()),
+                _ => Ok(()),
+            }
+        })
+    };
+
+    let first = queue.submit("a").unwrap();
+    let second = queue.submit("b").unwrap();
+    while running.load(Ordering::SeqCst) < 2 {
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(queue.job(first.id).unwrap().status, JobStatus::Running);
+
+    // Both workers are busy: three more wait, a repeat shares its job, a fourth is turned away
+    let waiting: Vec<_> = ["a", "c", "broken"]
+        .iter()
+        .map(|site| queue.submit(site).unwrap())
+        .collect();
+    assert_ne!(waiting[0].id, first.id);
+    assert_eq!(queue.submit("c").unwrap().id, waiting[1].id);
+    assert_eq!(queue.job(waiting[1].id).unwrap().status, JobStatus::Queued);
+    assert!(queue.submit("d").unwrap_err().contains("waiting"));
+
+    drop(held);
+    for job in [&first, &second].into_iter().chain(&waiting) {
+        wait(&queue, job.id);
+    }
+    assert_eq!(peak.load(Ordering::SeqCst), 2);
+    let failed = queue.job(waiting[2].id).unwrap();
+    assert_eq!(
+        (failed.status, failed.error.as_deref()),
+        (JobStatus::Failed, Some("optimizer diverged"))
+    );
+    assert_eq!(queue.job(first.id).unwrap().status, JobStatus::Done);
+    assert!(queue.job(999).is_none());
+}
+
+#[test]
+fn panicking_fits_fail_their_job() {
+    let queue = FitQueue::new(1, 8, |site| match site {
+        "broken" => panic!("index out of bounds"),
+        _ => Ok(()),
+    });
+    let broken = wait(&queue, queue.submit("broken").unwrap().id);
+    assert_eq!(broken.status, JobStatus::Failed);
+    assert_eq!(
+        broken.error.as_deref(),
+        Some("The fit panicked: index out of bounds")
+    );
+    // The worker is still there for the next fit
+    let next = wait(&queue, queue.submit("north").unwrap().id);
+    assert_eq!(next.status, JobStatus::Done);
+}
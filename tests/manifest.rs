@@ -0,0 +1,92 @@
+//! Run manifests, and their place in the archive.
+
+use chrono::DateTime;
+use cpo_charging_forecast::archive::{Archive, ArchivedForecast};
+use cpo_charging_forecast::config::{Config, ModelConfig};
+use cpo_charging_forecast::data::{RowCounts, Series};
+use cpo_charging_forecast::forecast::{Forecast, model_version};
+use cpo_charging_forecast::manifest::Manifest;
+use cpo_charging_forecast::outcome::RunRecord;
+
+/// 2024-01-01 00:00.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+
+fn manifest() -> Manifest {
+    let history = Series::new((0..48).map(|h| ORIGIN + h * HOUR).collect(), vec![1.0; 48]);
+    let counts = RowCounts {
+        read: 120,
+        invalid: 3,
+        non_positive: 2,
+        ..RowCounts::default()
+    };
+    let mut record = RunRecord::new("north", "forecast");
+    record.model = Some("prophet".to_string());
+    record.fit_seconds = Some(1.5);
+    let config = Config {
+        site: "north".to_string(),
+        ..Config::default()
+    };
+    let issued = DateTime::from_timestamp(ORIGIN + 48 * HOUR, 0).unwrap();
+    Manifest::new(
+        &config,
+        "exports/north.csv",
+        issued,
+        &history,
+        &counts,
+        &record,
+    )
+}
+
+#[test]
+fn manifest_traces_the_fit() {
+    let manifest = manifest();
+    assert_eq!(manifest.run_id, "20240103T000000Z");
+    assert_eq!(manifest.fitted_at, "2024-01-03T00:00:00Z");
+    assert_eq!(
+        (manifest.data.first.as_str(), manifest.data.last.as_str()),
+        ("2024-01-01 00:00", "2024-01-02 23:00")
+    );
+    assert_eq!(
+        (
+            manifest.data.points,
+            manifest.data.rows_read,
+            manifest.data.rows_dropped
+        ),
+        (48, 120, 5)
+    );
+    assert_eq!(manifest.method, "prophet");
+    assert_eq!(
+        manifest.model_version,
+        model_version(&ModelConfig::default())
+    );
+    assert_eq!(manifest.crate_version, env!("CARGO_PKG_VERSION"));
+
+    let json: serde_json::Value = serde_json::to_value(&manifest).unwrap();
+    assert_eq!(json["options"]["forecast"]["horizon_hours"], 168);
+    assert_eq!(json["data"]["source"], "exports/north.csv");
+}
+
+#[test]
+fn archived_manifests_sit_next_to_their_run() {
+    let dir = std::env::temp_dir().join(format!("cpo-manifest-{}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    let archive = Archive::open(dir.to_str().unwrap(), "north");
+    let manifest = manifest();
+    let issued = DateTime::from_timestamp(ORIGIN + 48 * HOUR, 0).unwrap();
+    let run = ArchivedForecast::new(
+        "north",
+        issued,
+        ORIGIN + 47 * HOUR,
+        &ModelConfig::default(),
+        Forecast::default(),
+    );
+    archive.save(&run).unwrap();
+    let path = archive.save_manifest(&manifest).unwrap();
+    assert!(path.ends_with("north/20240103T000000Z.manifest.json"));
+
+    // Listing runs passes over their manifests
+    assert_eq!(archive.list().unwrap().len(), 1);
+    assert_eq!(archive.manifest(&run.run_id).unwrap().data.rows_invalid, 3);
+    std::fs::remove_dir_all(&dir).ok();
+}
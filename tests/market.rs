@@ -0,0 +1,98 @@
+//! Market-gate windows, quarter-hour schedules and deltas between issues.
+
+use cpo_charging_forecast::config::{MarketConfig, Preset};
+use cpo_charging_forecast::forecast::Forecast;
+use cpo_charging_forecast::market::{self, Window};
+
+/// 2024-01-01 00:00.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+const DAY: i64 = 24 * HOUR;
+
+fn hourly(hours: i64, kw: impl Fn(i64) -> f64) -> Forecast {
+    let timestamps: Vec<i64> = (0..hours).map(|h| ORIGIN + h * HOUR).collect();
+    let point: Vec<f64> = (0..hours).map(kw).collect();
+    Forecast {
+        lower: point.iter().map(|kw| kw - 1.0).collect(),
+        upper: point.iter().map(|kw| kw + 1.0).collect(),
+        timestamps,
+        point,
+    }
+}
+
+#[test]
+fn day_ahead_covers_the_next_day_until_the_gate() {
+    let config = MarketConfig::default();
+    let (window, note) = market::window(&config, ORIGIN + 9 * HOUR).unwrap();
+    assert_eq!((window.start, window.end), (ORIGIN + DAY, ORIGIN + 2 * DAY));
+    assert!(note.is_none());
+    // Past noon the next day's gate has closed
+    let (window, note) = market::window(&config, ORIGIN + 13 * HOUR).unwrap();
+    assert_eq!(window.start, ORIGIN + 2 * DAY);
+    assert!(note.unwrap().contains("2024-01-02"));
+}
+
+#[test]
+fn intraday_starts_past_the_lead_time_on_a_unit() {
+    let config = MarketConfig {
+        preset: Preset::Intraday,
+        lead_minutes: 50,
+        ..MarketConfig::default()
+    };
+    let (window, _) = market::window(&config, ORIGIN + 9 * HOUR).unwrap();
+    assert_eq!(
+        (window.start, window.end),
+        (ORIGIN + 10 * HOUR, ORIGIN + 14 * HOUR)
+    );
+    let config = MarketConfig {
+        step_minutes: 7,
+        ..config
+    };
+    assert!(market::window(&config, ORIGIN).is_err());
+}
+
+#[test]
+fn schedule_splits_hours_into_units() {
+    let forecast = hourly(48, |h| h as f64);
+    let window = Window {
+        issued_at: ORIGIN,
+        start: ORIGIN + 10 * HOUR,
+        end: ORIGIN + 12 * HOUR,
+    };
+    let schedule = market::schedule(&forecast, &window, 15);
+    assert_eq!(schedule.len(), 8);
+    assert_eq!(schedule.timestamps[1], ORIGIN + 10 * HOUR + 900);
+    assert_eq!(
+        schedule.point,
+        vec![10.0, 10.0, 10.0, 10.0, 11.0, 11.0, 11.0, 11.0]
+    );
+    assert_eq!(window.horizon_hours(ORIGIN), 13);
+}
+
+#[test]
+fn delta_keeps_new_and_changed_units() {
+    let window = Window {
+        issued_at: ORIGIN,
+        start: ORIGIN,
+        end: ORIGIN + 3 * HOUR,
+    };
+    let previous = market::schedule(&hourly(2, |_| 10.0), &window, 60);
+    let current = market::schedule(
+        &hourly(3, |h| if h == 0 { 10.05 } else { 12.0 }),
+        &window,
+        60,
+    );
+    let delta = market::delta(&previous, &current, 0.1);
+    assert_eq!(delta.timestamps, vec![ORIGIN + HOUR, ORIGIN + 2 * HOUR]);
+
+    // And survive a round trip through the issue file
+    let path = std::env::temp_dir()
+        .join("market_delta.csv")
+        .display()
+        .to_string();
+    market::write_csv(&current, ORIGIN, 60, &path).unwrap();
+    let read = market::read_csv(&path).unwrap();
+    assert_eq!(read.timestamps, current.timestamps);
+    assert_eq!(read.point, current.point);
+    assert!(market::delta(&read, &current, 0.1).is_empty());
+}
@@ -0,0 +1,103 @@
+//! Config versions: migrating renamed and removed options, and stamping files.
+
+use toml_edit::DocumentMut;
+
+use cpo_charging_forecast::config::Config;
+use cpo_charging_forecast::migrate::{self, CONFIG_VERSION, MIGRATIONS, Migration};
+
+const STEPS: &[Migration] = &[
+    Migration {
+        to: 2,
+        renames: &[("forecast.horizon", "forecast.horizon_hours")],
+        removals: &[],
+    },
+    Migration {
+        to: 3,
+        renames: &[("plot", "output.plot")],
+        removals: &[("model.legacy", "use model.method")],
+    },
+];
+
+#[test]
+fn older_configs_migrate_step_by_step() {
+    let text = r#"# Site north
+site = "north"
+# The chart
+plot = "north.png"
+
+[forecast]
+horizon = 48 # two days
+
+[model]
+legacy = true
+
+[sites.south]
+forecast = { horizon = 24 }
+"#;
+    let mut doc: DocumentMut = text.parse().unwrap();
+    let changes = migrate::migrate(&mut doc, STEPS).unwrap();
+    assert_eq!(
+        changes,
+        vec![
+            "forecast.horizon renamed to forecast.horizon_hours",
+            "sites.south.forecast.horizon renamed to sites.south.forecast.horizon_hours",
+            "plot renamed to output.plot",
+            "model.legacy removed: use model.method",
+        ]
+    );
+    assert_eq!(migrate::version(&doc).unwrap(), 3);
+    let migrated = doc.to_string();
+    assert!(migrated.starts_with("# Site north"));
+    assert!(
+        migrated.contains("# The chart\nplot = \"north.png\""),
+        "{}",
+        migrated
+    );
+    assert!(
+        migrated.contains("horizon_hours = 48 # two days"),
+        "{}",
+        migrated
+    );
+    assert_eq!(
+        doc["sites"]["south"]["forecast"]["horizon_hours"].as_integer(),
+        Some(24)
+    );
+    assert_eq!(doc["output"]["plot"].as_str(), Some("north.png"));
+
+    // A config already at version 2 only takes the later step
+    let mut doc: DocumentMut = "version = 2\n[forecast]\nhorizon = 1\n".parse().unwrap();
+    assert!(migrate::migrate(&mut doc, STEPS).unwrap().is_empty());
+    assert_eq!(doc["forecast"]["horizon"].as_integer(), Some(1));
+
+    // A renamed option set under both names keeps the new one
+    let mut doc: DocumentMut = "[forecast]\nhorizon = 1\nhorizon_hours = 2\n"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        migrate::migrate(&mut doc, &STEPS[..1]).unwrap(),
+        vec!["forecast.horizon dropped, forecast.horizon_hours is set too"]
+    );
+    assert_eq!(doc["forecast"]["horizon_hours"].as_integer(), Some(2));
+}
+
+#[test]
+fn newer_configs_are_refused() {
+    let mut doc: DocumentMut = format!("version = {}\n", CONFIG_VERSION + 1)
+        .parse()
+        .unwrap();
+    assert!(migrate::migrate(&mut doc, MIGRATIONS).is_err());
+    assert!(Config::from_toml(&format!("version = {}\n", CONFIG_VERSION + 1), "test").is_err());
+    assert!(Config::from_toml("version = 0\n", "test").is_err());
+}
+
+#[test]
+fn unversioned_configs_are_the_first_version() {
+    let config = Config::from_toml("site = \"north\"\n", "test").unwrap();
+    assert_eq!(config.version, CONFIG_VERSION);
+    let mut doc: DocumentMut = "site = \"north\"\n".parse().unwrap();
+    assert!(migrate::migrate(&mut doc, MIGRATIONS).unwrap().is_empty());
+    assert!(
+        doc.to_string()
+            .contains(&format!("version = {}", CONFIG_VERSION))
+    );
+}
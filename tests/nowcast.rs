@@ -0,0 +1,182 @@
+//! Near-term blending with a short-term autoregressive model, and live
+//! correction by the readings since an issue.
+
+use cpo_charging_forecast::config::{Config, Method, NearTermConfig};
+use cpo_charging_forecast::data::Series;
+use cpo_charging_forecast::forecast::Forecast;
+use cpo_charging_forecast::nowcast::{Autoregressive, blend, live, weight};
+use cpo_charging_forecast::pipeline::fit_and_forecast;
+
+/// 2024-01-01 00:00.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+
+/// Load decaying from 100 kW towards 20 kW by a third of the gap each hour.
+fn history() -> Series {
+    let values: Vec<f64> = (0..48)
+        .map(|h| 20.0 + 80.0 * (2.0f64 / 3.0).powi(h))
+        .collect();
+    Series::new((0..48).map(|h| ORIGIN + h * HOUR).collect(), values)
+}
+
+#[test]
+fn the_autoregressive_model_follows_the_latest_readings() {
+    let config = NearTermConfig {
+        lags: 1,
+        ..NearTermConfig::default()
+    };
+    let model = Autoregressive::fit(&history(), &config).unwrap();
+    assert!(
+        (model.coefficients[0] - 2.0 / 3.0).abs() < 1e-6,
+        "{:?}",
+        model
+    );
+    assert!((model.intercept - 20.0 / 3.0).abs() < 1e-6, "{:?}", model);
+
+    // Three lags of an exact one-lag process can't be told apart: it is fitted with one
+    let model = Autoregressive::fit(&history(), &NearTermConfig::default()).unwrap();
+    assert_eq!(model.coefficients.len(), 1);
+    // A site sitting at one level is just persisted
+    let flat = Series::new(history().timestamps.to_vec(), vec![7.0; 48]);
+    assert_eq!(
+        Autoregressive::fit(&flat, &config).unwrap().predict(3),
+        [7.0; 3]
+    );
+
+    let short = Series::new(vec![ORIGIN, ORIGIN + HOUR], vec![1.0, 2.0]);
+    assert!(Autoregressive::fit(&short, &config).is_err());
+}
+
+#[test]
+fn blend_weights_decay_to_the_model_forecast() {
+    assert_eq!(weight(1, 6), 6.0 / 7.0);
+    assert_eq!(weight(6, 6), 1.0 / 7.0);
+    assert_eq!(weight(7, 6), 0.0);
+
+    // The long-term model expects 50 kW throughout; the site is at about 20 kW
+    let timestamps: Vec<i64> = (48..72).map(|h| ORIGIN + h * HOUR).collect();
+    let forecast = Forecast {
+        timestamps,
+        point: vec![50.0; 24],
+        lower: vec![40.0; 24],
+        upper: vec![60.0; 24],
+    };
+    let config = NearTermConfig::default();
+    let blended = blend(&forecast, &history(), &config).unwrap();
+    assert!(
+        (blended.point[0] - (20.0 + 30.0 / 7.0)).abs() < 0.01,
+        "{:?}",
+        &blended.point[..7]
+    );
+    assert!(blended.point.windows(2).take(6).all(|w| w[0] < w[1]));
+    assert_eq!(&blended.point[6..], &forecast.point[6..]);
+    assert!((blended.upper[0] - blended.point[0] - 10.0).abs() < 1e-9);
+}
+
+#[test]
+fn blend_covers_the_configured_hours_on_a_sub_hourly_grid() {
+    // The decaying load at 15-minute steps, and a forecast 6 hours on
+    let step = HOUR / 4;
+    let values: Vec<f64> = (0..192)
+        .map(|i| 20.0 + 80.0 * (2.0f64 / 3.0).powi(i))
+        .collect();
+    let history = Series::new((0..192).map(|i| ORIGIN + i * step).collect(), values);
+    let forecast = Forecast {
+        timestamps: (192..216).map(|i| ORIGIN + i * step).collect(),
+        point: vec![50.0; 24],
+        lower: vec![40.0; 24],
+        upper: vec![60.0; 24],
+    };
+    let config = NearTermConfig {
+        hours: 2,
+        lookback_hours: 48,
+        ..NearTermConfig::default()
+    };
+    let blended = blend(&forecast, &history, &config).unwrap();
+    // 2 hours are 8 steps, the weight falling over all of them
+    assert!(
+        (blended.point[0] - (20.0 + 30.0 / 9.0)).abs() < 0.01,
+        "{:?}",
+        blended.point
+    );
+    assert!(blended.point[..9].windows(2).all(|w| w[0] < w[1]));
+    assert_eq!(&blended.point[8..], &forecast.point[8..]);
+}
+
+#[test]
+fn a_history_too_short_to_blend_leaves_the_forecast_unblended() {
+    let mut config = Config::default();
+    config.model.method = Method::Tsb;
+    config.forecast.horizon_hours = 6;
+    let (_, unblended) = fit_and_forecast(&config, &history()).unwrap();
+
+    // 48 hours can't fit 20 lags; the model's forecast is issued as it is
+    config.near_term = Some(NearTermConfig {
+        lags: 20,
+        ..NearTermConfig::default()
+    });
+    let (_, forecast) = fit_and_forecast(&config, &history()).unwrap();
+    assert_eq!(forecast, unblended);
+}
+
+#[test]
+fn live_readings_correct_the_current_and_next_hour() {
+    // A flat 10 kW issued at hour 48
+    let issued = ORIGIN + 48 * HOUR;
+    let forecast = Forecast {
+        timestamps: (0..4).map(|h| issued + h * HOUR).collect(),
+        point: vec![10.0; 4],
+        lower: vec![8.0; 4],
+        upper: vec![12.0; 4],
+    };
+    // 4 kW over in the first hour, 8 kW so far in the second; the reading before the issue doesn't count
+    let readings = [
+        (ORIGIN, 1000.0),
+        (issued, 13.0),
+        (issued + 1800, 15.0),
+        (issued + HOUR + 600, 18.0),
+    ];
+    let corrected = live(&forecast, &readings).unwrap();
+    assert_eq!(corrected.hours_observed, 2);
+    let adjustment = (8.0 + 0.5 * 4.0) / 1.5;
+    assert!(
+        (corrected.adjustment_kw - adjustment).abs() < 1e-9,
+        "{:?}",
+        corrected
+    );
+    assert_eq!(
+        corrected.estimate.timestamps,
+        [issued + HOUR, issued + 2 * HOUR]
+    );
+    assert!((corrected.estimate.point[0] - (10.0 + adjustment)).abs() < 1e-9);
+    assert!((corrected.estimate.point[1] - (10.0 + adjustment / 2.0)).abs() < 1e-9);
+    assert!((corrected.estimate.upper[0] - (12.0 + adjustment)).abs() < 1e-9);
+    assert_eq!(corrected.forecast.point, [10.0, 10.0]);
+
+    // At the last forecast hour there is no next one
+    let last = live(&forecast, &[(issued + 3 * HOUR, 10.0)]).unwrap();
+    assert_eq!(last.estimate.len(), 1);
+
+    assert!(live(&forecast, &[(ORIGIN, 5.0)]).is_err());
+    assert!(live(&forecast, &[(issued + 4 * HOUR, 5.0)]).is_err());
+}
+
+#[test]
+fn live_readings_are_compared_with_the_forecast_over_each_hour() {
+    // 15-minute steps rising from 8 to 14 kW within each hour, a 11 kW mean
+    let issued = ORIGIN + 48 * HOUR;
+    let step = HOUR / 4;
+    let point: Vec<f64> = (0..12).map(|i| 8.0 + 2.0 * (i % 4) as f64).collect();
+    let forecast = Forecast {
+        timestamps: (0..12).map(|i| issued + i * step).collect(),
+        lower: point.iter().map(|kw| kw - 2.0).collect(),
+        upper: point.iter().map(|kw| kw + 2.0).collect(),
+        point,
+    };
+    // Readings early in the first hour, at 11 kW; the hour's forecast is on target
+    let corrected = live(&forecast, &[(issued, 11.0), (issued + 600, 11.0)]).unwrap();
+    assert_eq!(corrected.adjustment_kw, 0.0);
+    assert_eq!(corrected.forecast.timestamps, [issued, issued + HOUR]);
+    assert_eq!(corrected.forecast.point, [11.0, 11.0]);
+    assert_eq!(corrected.estimate.upper, [13.0, 13.0]);
+}
@@ -0,0 +1,192 @@
+//! OpenADR 3 events: payloads, intervals and their adjustment of the forecast.
+
+use serde_json::json;
+
+use cpo_charging_forecast::config::OpenAdrConfig;
+use cpo_charging_forecast::forecast::Forecast;
+use cpo_charging_forecast::openadr::{self, Signal, SignalKind, parse_duration};
+
+/// 2024-01-01 00:00.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+
+fn forecast(hours: i64, kw: f64) -> Forecast {
+    let n = hours as usize;
+    Forecast {
+        timestamps: (0..hours).map(|h| ORIGIN + h * HOUR).collect(),
+        point: vec![kw; n],
+        lower: vec![kw / 2.0; n],
+        upper: vec![kw * 2.0; n],
+    }
+}
+
+fn signal(kind: SignalKind, start: i64, end: i64, value: f64) -> Signal {
+    Signal {
+        event: "e".to_string(),
+        kind,
+        start,
+        end,
+        value,
+    }
+}
+
+#[test]
+fn durations_parse() {
+    assert_eq!(parse_duration("PT1H").unwrap(), HOUR);
+    assert_eq!(parse_duration("PT15M").unwrap(), 900);
+    assert_eq!(parse_duration("P1DT2H30M").unwrap(), 26 * HOUR + 1800);
+    assert!(parse_duration("1H").is_err());
+    assert!(parse_duration("PT1").is_err());
+}
+
+#[test]
+fn intervals_follow_the_event_period() {
+    let event = json!({
+        "id": "shed-1",
+        "intervalPeriod": { "start": "2024-01-01T17:00:00Z", "duration": "PT30M" },
+        "intervals": [
+            { "id": 0, "payloads": [{ "type": "SIMPLE", "values": [2] }] },
+            { "id": 1, "payloads": [{ "type": "SIMPLE", "values": [1] }, { "type": "GHG", "values": [300] }] },
+            { "id": 2, "intervalPeriod": { "start": "2024-01-01T20:00:00Z", "duration": "PT1H" },
+              "payloads": [{ "type": "IMPORT_CAPACITY_LIMIT", "values": [40.0] }] }
+        ]
+    });
+    let signals = openadr::event_signals(&event, Some(chrono_tz::Europe::Berlin)).unwrap();
+    // Local time is an hour ahead of UTC in winter; the GHG payload isn't applied
+    let local = |h: i64, m: i64| ORIGIN + h * HOUR + m * 60;
+    assert_eq!(
+        signals,
+        vec![
+            Signal {
+                event: "shed-1".to_string(),
+                kind: SignalKind::Simple,
+                start: local(18, 0),
+                end: local(18, 30),
+                value: 2.0
+            },
+            Signal {
+                event: "shed-1".to_string(),
+                kind: SignalKind::Simple,
+                start: local(18, 30),
+                end: local(19, 0),
+                value: 1.0
+            },
+            Signal {
+                event: "shed-1".to_string(),
+                kind: SignalKind::ImportCapacityLimit,
+                start: local(21, 0),
+                end: local(22, 0),
+                value: 40.0
+            },
+        ]
+    );
+}
+
+#[test]
+fn signals_adjust_the_hours_they_cover() {
+    let config = OpenAdrConfig {
+        reference_price: Some(0.30),
+        price_elasticity: -1.0,
+        ..OpenAdrConfig::default()
+    };
+    let signals = [
+        // Level 2 sheds 25%, over half of hour 0
+        signal(SignalKind::Simple, ORIGIN, ORIGIN + HOUR / 2, 2.0),
+        signal(
+            SignalKind::ImportCapacityLimit,
+            ORIGIN + HOUR,
+            ORIGIN + 2 * HOUR,
+            60.0,
+        ),
+        signal(
+            SignalKind::Price,
+            ORIGIN + 2 * HOUR,
+            ORIGIN + 3 * HOUR,
+            0.60,
+        ),
+        signal(
+            SignalKind::DispatchSetpointRelative,
+            ORIGIN + 3 * HOUR,
+            ORIGIN + 4 * HOUR,
+            -120.0,
+        ),
+    ];
+    let (adjusted, steps) = openadr::apply(&forecast(5, 100.0), &signals, &config);
+    assert_eq!(adjusted.point, vec![87.5, 60.0, 50.0, 0.0, 100.0]);
+    assert_eq!(adjusted.upper, vec![175.0, 60.0, 100.0, 80.0, 200.0]);
+    assert_eq!(adjusted.lower[1], 50.0);
+    assert_eq!(steps, 4);
+}
+
+#[test]
+fn signals_adjust_only_the_steps_they_cover_on_a_sub_hourly_grid() {
+    let step = 300;
+    let forecast = Forecast {
+        timestamps: (0..24).map(|i| ORIGIN + i * step).collect(),
+        point: vec![100.0; 24],
+        lower: vec![50.0; 24],
+        upper: vec![200.0; 24],
+    };
+    // Caps the second hour, which starts at the 12th step
+    let signals = [signal(
+        SignalKind::ImportCapacityLimit,
+        ORIGIN + HOUR,
+        ORIGIN + 2 * HOUR,
+        60.0,
+    )];
+    let (adjusted, steps) = openadr::apply(&forecast, &signals, &OpenAdrConfig::default());
+    assert_eq!(steps, 12);
+    assert!(adjusted.point[..12].iter().all(|&kw| kw == 100.0));
+    assert!(adjusted.point[12..].iter().all(|&kw| kw == 60.0));
+}
+
+#[test]
+fn notifications_store_and_delete_events() {
+    let dir = std::env::temp_dir().join("openadr_events");
+    let _ = std::fs::remove_dir_all(&dir);
+    let dir = dir.display().to_string();
+    let event = json!({
+        "id": "price/1",
+        "targets": [{ "type": "RESOURCE_NAME", "values": ["north"] }],
+        "intervalPeriod": { "start": "2024-01-01T00:00:00Z", "duration": "PT1H" },
+        "intervals": [{ "id": 0, "payloads": [{ "type": "PRICE", "values": [0.5] }] }]
+    });
+    let notification =
+        |operation: &str| json!({ "objectType": "EVENT", "operation": operation, "object": event });
+    let config = OpenAdrConfig {
+        events_dir: dir.clone(),
+        ..OpenAdrConfig::default()
+    };
+    let north = openadr::site_dir(&dir, "north").display().to_string();
+    assert_eq!(
+        openadr::save(&north, "north", &notification("POST"))
+            .unwrap()
+            .0,
+        vec!["price/1"]
+    );
+    assert_eq!(openadr::load(&config, "north", None).unwrap().len(), 1);
+    // Each site reads only its own directory
+    assert!(
+        openadr::load(&config, "acme/north", None)
+            .unwrap()
+            .is_empty()
+    );
+    assert!(openadr::load(&config, "south", None).unwrap().is_empty());
+    assert!(openadr::site_dir(&dir, "acme/../north").ends_with("acme/__/north"));
+
+    // Events targeting other resources, or none, are refused
+    let south = openadr::site_dir(&dir, "south").display().to_string();
+    assert!(openadr::save(&south, "south", &notification("POST")).is_err());
+    let untargeted = json!({ "id": "shed/1", "intervals": [] });
+    assert!(openadr::save(&north, "north", &untargeted).is_err());
+
+    assert_eq!(
+        openadr::save(&north, "north", &notification("DELETE"))
+            .unwrap()
+            .1,
+        vec!["price/1"]
+    );
+    assert!(openadr::load(&config, "north", None).unwrap().is_empty());
+    // Other objects than events are no payload
+    assert!(openadr::events(&json!({ "objectType": "PROGRAM", "object": {} })).is_empty());
+}
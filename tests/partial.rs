@@ -0,0 +1,118 @@
+//! Partial refits: the trend or the seasonal terms refitted on recent data,
+//! the rest kept from the last full fit.
+
+#![cfg(feature = "wasmstan")]
+
+use cpo_charging_forecast::config::{ModelConfig, PartialRefitConfig, RefitScope};
+use cpo_charging_forecast::data::Series;
+use cpo_charging_forecast::forecast::{Regressors, fit, future_timestamps};
+use cpo_charging_forecast::partial::{State, keep, refit};
+
+/// 2024-01-01 00:00.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+
+/// `days` days at 20 kW with a daily cycle and some noise, scaled by `level` from hour `from`.
+fn history(days: i64, from: i64, level: f64) -> Series {
+    let timestamps: Vec<i64> = (0..days * 24).map(|h| ORIGIN + h * HOUR).collect();
+    let values = (0..days * 24)
+        .map(|h| {
+            let daily = 8.0 * (2.0 * std::f64::consts::PI * (h % 24) as f64 / 24.0).sin();
+            let noise = 2.0 * ((h * 7919 % 101) as f64 / 50.0 - 1.0);
+            (20.0 + daily + noise) * if h >= from { level } else { 1.0 }
+        })
+        .collect();
+    Series::new(timestamps, values)
+}
+
+fn state_path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!("cpo-partial-{}-{}.csv", name, std::process::id()))
+        .display()
+        .to_string()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+#[test]
+fn partial_refits_start_from_the_last_full_fit() {
+    let model = ModelConfig::default();
+    let mut config = PartialRefitConfig {
+        state: state_path("refit"),
+        lookback_hours: 7 * 24,
+        ..PartialRefitConfig::default()
+    };
+    let full_history = history(28, 0, 1.0);
+    let full = fit(&full_history, &model).unwrap();
+    let state = keep(full.as_ref(), &full_history, &model, 48, None, &config).unwrap();
+    assert_eq!(
+        state.timestamps.len(),
+        full_history.len() + 48 + config.max_age_hours
+    );
+    assert_eq!(State::load(&config.state).unwrap(), state);
+
+    // Three days on, demand is up by half: refit the trend on those
+    let later = history(31, 28 * 24, 1.5);
+    let next_day = future_timestamps(*later.timestamps.last().unwrap(), 24, None);
+    let kept = full.predict(next_day.clone()).unwrap();
+
+    config.refit = RefitScope::Trend;
+    config.lookback_hours = 3 * 24;
+    let trend = refit(&later, &model, &config, &Regressors::default()).unwrap();
+    assert_eq!(trend.name(), "prophet-trend");
+    let raised = trend.predict(next_day.clone()).unwrap();
+    let ratio = mean(&raised.point) / mean(&kept.point);
+    assert!((ratio - 1.5).abs() < 0.05, "{}", ratio);
+    // The daily cycle is the full fit's
+    let peak = |point: &[f64]| {
+        point
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .unwrap()
+            .0
+    };
+    assert_eq!(peak(&raised.point), peak(&kept.point));
+
+    // On unchanged demand, new seasonal terms on the kept trend forecast much the same
+    config.refit = RefitScope::Seasonality;
+    config.lookback_hours = 7 * 24;
+    let seasonal = refit(&full_history, &model, &config, &Regressors::default()).unwrap();
+    let same_days = future_timestamps(*full_history.timestamps.last().unwrap(), 24, None);
+    let (refitted, original) = (
+        seasonal.predict(same_days.clone()).unwrap(),
+        full.predict(same_days).unwrap(),
+    );
+    let ratio = mean(&refitted.point) / mean(&original.point);
+    assert!((ratio - 1.0).abs() < 0.1, "{}", ratio);
+    assert_eq!(peak(&refitted.point), peak(&original.point));
+
+    // Past the kept hours, only a full fit will do
+    let too_late = Series::new(vec![state.timestamps.last().unwrap() + HOUR], vec![20.0]);
+    assert!(refit(&too_late, &model, &config, &Regressors::default()).is_err());
+    let _ = std::fs::remove_file(&config.state);
+}
+
+#[test]
+fn partial_refits_need_a_kept_fit() {
+    let config = PartialRefitConfig {
+        refit: RefitScope::Trend,
+        state: state_path("missing"),
+        ..PartialRefitConfig::default()
+    };
+    let error = refit(
+        &history(7, 0, 1.0),
+        &ModelConfig::default(),
+        &config,
+        &Regressors::default(),
+    )
+    .err()
+    .unwrap();
+    assert!(
+        error.to_string().contains("run a full fit first"),
+        "{}",
+        error
+    );
+}
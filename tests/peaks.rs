@@ -0,0 +1,106 @@
+//! Peak hours of the forecast, as a ranked list and an iCalendar file.
+
+use chrono::NaiveDate;
+use cpo_charging_forecast::config::PeaksConfig;
+use cpo_charging_forecast::forecast::Forecast;
+use cpo_charging_forecast::peaks::{events, ics, top};
+
+/// 2024-07-01 00:00, local time.
+const ORIGIN: i64 = 1_719_792_000;
+const HOUR: i64 = 3600;
+
+/// Two days: peaks at 17:00 and 18:00 on the first, a higher one at 08:00 on the second.
+fn forecast() -> Forecast {
+    let point: Vec<f64> = (0..48)
+        .map(|h| match h {
+            17 => 90.0,
+            18 => 80.0,
+            32 => 120.0,
+            _ => 10.0 + h as f64 / 10.0,
+        })
+        .collect();
+    Forecast {
+        timestamps: (0..48).map(|h| ORIGIN + h * HOUR).collect(),
+        lower: point.iter().map(|p| p - 5.0).collect(),
+        upper: point.iter().map(|p| p + 5.0).collect(),
+        point,
+    }
+}
+
+fn config(top: usize, within_hours: Option<usize>) -> PeaksConfig {
+    PeaksConfig {
+        top,
+        within_hours,
+        ..PeaksConfig::default()
+    }
+}
+
+#[test]
+fn highest_hours_come_first() {
+    let peaks = top(&forecast(), &config(3, None));
+    let starts: Vec<&str> = peaks.iter().map(|p| p.start.as_str()).collect();
+    assert_eq!(
+        starts,
+        vec!["2024-07-02 08:00", "2024-07-01 17:00", "2024-07-01 18:00"]
+    );
+    assert_eq!(
+        peaks.iter().map(|p| p.rank).collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    assert_eq!(
+        (peaks[0].end.as_str(), peaks[0].upper_kw),
+        ("2024-07-02 09:00", 125.0)
+    );
+
+    // Only the first day
+    let today = top(&forecast(), &config(2, Some(24)));
+    assert_eq!(
+        today.iter().map(|p| p.kw).collect::<Vec<_>>(),
+        vec![90.0, 80.0]
+    );
+
+    // Adjacent hours are one event, in time order
+    let events = events(&peaks, HOUR);
+    assert_eq!(events.len(), 2);
+    assert_eq!(
+        (events[0].start, events[0].end),
+        (ORIGIN + 17 * HOUR, ORIGIN + 19 * HOUR)
+    );
+    assert_eq!(events[1].peaks[0].rank, 1);
+}
+
+#[test]
+fn calendar_events_are_in_utc_with_a_timezone() {
+    let peaks = top(&forecast(), &config(3, None));
+    let now = NaiveDate::from_ymd_opt(2024, 6, 30)
+        .unwrap()
+        .and_hms_opt(6, 0, 0)
+        .unwrap();
+    let calendar = ics(
+        &peaks,
+        HOUR,
+        "north, hub",
+        Some(chrono_tz::Europe::Berlin),
+        now,
+    );
+    assert!(calendar.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+    assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+    assert_eq!(calendar.matches("BEGIN:VEVENT").count(), 2);
+    // 17:00 in Berlin summer time is 15:00 UTC
+    assert!(
+        calendar.contains("DTSTART:20240701T150000Z\r\nDTEND:20240701T170000Z\r\n"),
+        "{}",
+        calendar
+    );
+    assert!(calendar.contains("SUMMARY:Expected peak at north\\, hub: 90 kW"));
+    assert!(calendar.contains("DTSTAMP:20240630T060000Z"));
+    assert!(
+        calendar.lines().all(|line| line.len() <= 75),
+        "{}",
+        calendar
+    );
+
+    // Floating local times without one
+    let floating = ics(&peaks, HOUR, "north", None, now);
+    assert!(floating.contains("DTSTART:20240701T170000\r\n"));
+}
@@ -0,0 +1,114 @@
+//! Power samples integrated to interval energy, and checked against a register.
+
+use cpo_charging_forecast::config::{Config, PowerConfig};
+use cpo_charging_forecast::data::{RowCounts, Series};
+use cpo_charging_forecast::pipeline::load_history_from;
+use cpo_charging_forecast::power::{check_register, energy_between, integrate, samples};
+use cpo_charging_forecast::source::SeriesSource;
+
+/// 2024-01-01 00:00.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+const MINUTE: i64 = 60;
+
+#[test]
+fn samples_are_integrated_with_the_trapezoidal_rule() {
+    // A ramp from 0 to 60 kW over the first hour, sampled unevenly, then flat at 60 kW
+    let ramp = |t: i64| 60.0 * t.min(HOUR) as f64 / HOUR as f64;
+    let times = [0, 7, 19, 30, 45, 60, 75, 90, 120].map(|m| m * MINUTE);
+    let mut counts = RowCounts::default();
+    let mut timestamps: Vec<i64> = times.iter().map(|t| ORIGIN + t).collect();
+    let mut values: Vec<f64> = times.iter().map(|t| ramp(*t)).collect();
+    timestamps.push(ORIGIN + 5 * MINUTE);
+    values.push(f64::NAN);
+    let series = Series::new(timestamps, values);
+    let points = samples(series, &mut counts);
+    assert_eq!((points.len(), counts.invalid), (9, 1));
+
+    // Linear between samples, so exact: 30 kWh in the first hour. The half hour
+    // from 01:30 isn't bridged at 15 minutes, so the second hour is the mean of
+    // its metered half, and nothing reaches the last sample's hour
+    let hourly = integrate(&points, HOUR, 15 * MINUTE);
+    assert_eq!(hourly.timestamps, vec![ORIGIN, ORIGIN + HOUR]);
+    assert!(
+        (hourly.values[0] - 30.0).abs() < 1e-9 && (hourly.values[1] - 60.0).abs() < 1e-9,
+        "{:?}",
+        hourly.values
+    );
+    assert_eq!(
+        energy_between(&points, ORIGIN, ORIGIN + 2 * HOUR, 15 * MINUTE),
+        None
+    );
+    assert!(
+        (energy_between(
+            &points,
+            ORIGIN + 30 * MINUTE,
+            ORIGIN + 90 * MINUTE,
+            30 * MINUTE
+        )
+        .unwrap()
+            - 52.5)
+            .abs()
+            < 1e-9
+    );
+    let sparse = integrate(
+        &[(ORIGIN, 10.0), (ORIGIN + 2 * HOUR, 10.0)],
+        HOUR,
+        15 * MINUTE,
+    );
+    assert!(sparse.is_empty());
+}
+
+#[test]
+fn the_register_checks_the_integrated_energy() {
+    let points: Vec<(i64, f64)> = (0..=24).map(|i| (ORIGIN + i * 5 * MINUTE, 12.0)).collect();
+    let config = PowerConfig::default();
+    // 12 kW for two hours: 12 kWh an hour
+    let register = [
+        (ORIGIN, 100.0),
+        (ORIGIN + HOUR, 112.0),
+        (ORIGIN + 2 * HOUR, 124.3),
+        (ORIGIN + 3 * HOUR, 200.0),
+    ];
+    let check = check_register(&points, &register, &config);
+    assert_eq!(check.intervals, 2);
+    assert!(check.mismatches.is_empty());
+
+    let register = [
+        (ORIGIN, 100.0),
+        (ORIGIN + HOUR, 120.0),
+        (ORIGIN + 2 * HOUR, 110.0),
+    ];
+    let check = check_register(&points, &register, &config);
+    assert_eq!((check.intervals, check.mismatches.len()), (1, 1));
+    assert_eq!(check.worst().unwrap().register_kwh, 20.0);
+}
+
+#[test]
+fn power_series_are_integrated_before_the_grid() {
+    let mut config: Config = toml::from_str("[data.power]\nmax_gap_minutes = 60").unwrap();
+    // A short spike: the mean of the samples says 40 kW, the energy is 30 kWh
+    let meter = SeriesSource::new(
+        "meter",
+        Series::new(
+            vec![
+                ORIGIN,
+                ORIGIN + 30 * MINUTE,
+                ORIGIN + 40 * MINUTE,
+                ORIGIN + HOUR,
+            ],
+            vec![0.0, 0.0, 120.0, 0.0],
+        ),
+    );
+    let (history, _) = load_history_from(&config, &meter).unwrap();
+    assert_eq!(history.timestamps, vec![ORIGIN]);
+    assert!(
+        (history.values[0] - 30.0).abs() < 1e-9,
+        "{:?}",
+        history.values
+    );
+
+    config.data.power = None;
+    let (history, _) = load_history_from(&config, &meter).unwrap();
+    assert_eq!(history.values[0], 40.0);
+}
@@ -0,0 +1,157 @@
+//! Invariants of the preprocessing steps, checked on random sessions and series.
+
+use cpo_charging_forecast::data::{
+    CompactSeries, Series, Session, format_timestamp, parse_datetime_to_timestamp,
+};
+use cpo_charging_forecast::preprocess::{
+    self, ClipOutliers, Fill, HOUR, Pipeline, Resample, Window, floor_hour,
+};
+use cpo_charging_forecast::transform;
+use proptest::prelude::*;
+
+/// 2024-01-01 00:00, local time.
+const ORIGIN: i64 = 1_704_067_200;
+
+fn session() -> impl Strategy<Value = Session> {
+    // Up to two months in, up to two days long, at minute resolution
+    (0..60 * 24 * 60i64, 0..48 * 60i64, 1.0..200_000.0f64).prop_map(
+        |(start, minutes, energy_wh)| {
+            let start = ORIGIN + start * 60;
+            Session {
+                start,
+                stop: start + minutes * 60,
+                energy_wh,
+            }
+        },
+    )
+}
+
+/// Points at random times (not on the hour, unsorted, with gaps and repeats).
+fn scattered_series() -> impl Strategy<Value = Series> {
+    prop::collection::vec((0..24 * 30 * 3600i64, 0.0..500.0f64), 1..200).prop_map(|points| {
+        let (timestamps, values) = points
+            .into_iter()
+            .map(|(offset, v)| (ORIGIN + offset, v))
+            .unzip();
+        Series::new(timestamps, values)
+    })
+}
+
+fn fill() -> impl Strategy<Value = Fill> {
+    prop_oneof![Just(Fill::Zero), Just(Fill::Previous), Just(Fill::Linear)]
+}
+
+fn assert_hourly_grid(series: &Series) -> Result<(), TestCaseError> {
+    prop_assert_eq!(series.timestamps.len(), series.values.len());
+    for pair in series.timestamps.windows(2) {
+        prop_assert_eq!(pair[1] - pair[0], HOUR);
+    }
+    for ts in &series.timestamps {
+        prop_assert_eq!(floor_hour(*ts), *ts);
+    }
+    Ok(())
+}
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-6 * (1.0 + a.abs().max(b.abs()))
+}
+
+proptest! {
+    #[test]
+    fn hourly_load_keeps_total_energy(sessions in prop::collection::vec(session(), 1..50)) {
+        let series = preprocess::hourly_load(&sessions);
+        let raw_kwh: f64 = sessions.iter().map(|s| s.energy_wh / 1000.0).sum();
+        let resampled_kwh: f64 = series.values.iter().sum();
+        prop_assert!(close(raw_kwh, resampled_kwh), "{} kWh in, {} kWh out", raw_kwh, resampled_kwh);
+        assert_hourly_grid(&series)?;
+    }
+
+    #[test]
+    fn five_minute_load_keeps_total_energy(sessions in prop::collection::vec(session(), 1..50)) {
+        let series = preprocess::load(&sessions, 300);
+        let raw_kwh: f64 = sessions.iter().map(|s| s.energy_wh / 1000.0).sum();
+        let resampled_kwh: f64 = series.values.iter().sum::<f64>() / 12.0;
+        prop_assert!(close(raw_kwh, resampled_kwh), "{} kWh in, {} kWh out", raw_kwh, resampled_kwh);
+        prop_assert!(series.timestamps.windows(2).all(|pair| pair[1] - pair[0] == 300));
+        prop_assert!(series.timestamps.iter().all(|ts| ts % 300 == 0));
+    }
+
+    #[test]
+    fn hourly_occupancy_keeps_plugged_in_hours(sessions in prop::collection::vec(session(), 1..50)) {
+        let series = preprocess::hourly_occupancy(&sessions);
+        let raw_hours: f64 = sessions.iter().map(|s| (s.stop - s.start) as f64 / HOUR as f64).sum();
+        let resampled_hours: f64 = series.values.iter().sum();
+        prop_assert!(close(raw_hours, resampled_hours), "{} h in, {} h out", raw_hours, resampled_hours);
+        assert_hourly_grid(&series)?;
+    }
+
+    #[test]
+    fn resample_fills_a_monotonic_hourly_grid(series in scattered_series(), fill in fill()) {
+        let first = floor_hour(*series.timestamps.iter().min().unwrap());
+        let last = floor_hour(*series.timestamps.iter().max().unwrap());
+        let (low, high) = series.values.iter().fold((f64::MAX, f64::MIN), |(l, h), v| (l.min(*v), h.max(*v)));
+
+        let resampled = Pipeline::new().step(Resample::hourly().fill(fill)).apply(series).unwrap();
+        assert_hourly_grid(&resampled)?;
+        prop_assert_eq!(resampled.timestamps[0], first);
+        prop_assert_eq!(*resampled.timestamps.last().unwrap(), last);
+        // Averages and fills never leave the observed range, except zero-filled gaps
+        for v in &resampled.values {
+            prop_assert!(*v >= low - 1e-9 || (*v == 0.0 && fill == Fill::Zero));
+            prop_assert!(*v <= high + 1e-9);
+        }
+    }
+
+    #[test]
+    fn resample_is_idempotent(series in scattered_series(), fill in fill()) {
+        let pipeline = Pipeline::new().step(Resample::hourly().fill(fill));
+        let once = pipeline.apply(series).unwrap();
+        let twice = pipeline.apply(once.clone()).unwrap();
+        prop_assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn clip_outliers_keeps_length_and_range(series in scattered_series(), k in 0.0..5.0f64) {
+        let (low, high) = series.values.iter().fold((f64::MAX, f64::MIN), |(l, h), v| (l.min(*v), h.max(*v)));
+        let clipped = Pipeline::new().step(ClipOutliers::iqr(k)).apply(series.clone()).unwrap();
+        prop_assert_eq!(&clipped.timestamps, &series.timestamps);
+        for v in &clipped.values {
+            prop_assert!(*v >= low.max(0.0) - 1e-9 && *v <= high + 1e-9);
+        }
+    }
+
+    #[test]
+    fn window_keeps_a_subsequence(sessions in prop::collection::vec(session(), 1..50), days in 0..60u32) {
+        let series = preprocess::hourly_load(&sessions);
+        let from = format_timestamp(ORIGIN + days as i64 * 24 * HOUR);
+        let windowed = Pipeline::new().step(Window::from(&from)).apply(series.clone()).unwrap();
+        let from = parse_datetime_to_timestamp(&from).unwrap();
+        prop_assert!(windowed.timestamps.iter().all(|ts| *ts >= from));
+        prop_assert!(windowed.timestamps.windows(2).all(|pair| pair[0] < pair[1]));
+        prop_assert_eq!(windowed.len(), series.timestamps.iter().filter(|ts| **ts >= from).count());
+    }
+
+    #[test]
+    fn compact_series_round_trips_within_f32(sessions in prop::collection::vec(session(), 1..50)) {
+        let series = preprocess::hourly_load(&sessions);
+        let back = CompactSeries::from_series(&series).unwrap().to_series();
+        prop_assert_eq!(&back.timestamps, &series.timestamps);
+        for (a, b) in back.values.iter().zip(&series.values) {
+            prop_assert!((a - b).abs() <= 1e-6 * b.abs().max(1.0), "{} became {}", b, a);
+        }
+        let scattered = Series::new(vec![ORIGIN, ORIGIN + HOUR, ORIGIN + 3 * HOUR], vec![1.0; 3]);
+        prop_assert!(CompactSeries::from_series(&scattered).is_err());
+    }
+
+    #[test]
+    fn transform_round_trips(y in 0.0..10_000.0f64, lambda in -1.0..2.0f64) {
+        let back = transform::inverse(transform::forward(y, lambda), lambda);
+        prop_assert!(close(y, back), "{} -> {} at lambda {}", y, back, lambda);
+    }
+
+    #[test]
+    fn timestamps_round_trip_through_text(minutes in 0..60 * 24 * 3650i64) {
+        let ts = ORIGIN + minutes * 60;
+        prop_assert_eq!(parse_datetime_to_timestamp(&format_timestamp(ts)).unwrap(), ts);
+    }
+}
@@ -0,0 +1,128 @@
+//! Day-ahead price documents, the price cache and price-aware depot charging.
+
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+
+use cpo_charging_forecast::config::{ChargingStrategy, DepotConfig, PricesConfig};
+use cpo_charging_forecast::depot::{VehicleVisit, fleet_load};
+use cpo_charging_forecast::prices::{Prices, parse_document};
+
+/// 2024-01-01 00:00.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+const DAY: i64 = 24 * HOUR;
+
+const DOCUMENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Publication_MarketDocument xmlns="urn:iec62325.351:tc57wg16:451-3:publicationdocument:7:3">
+  <TimeSeries>
+    <Period>
+      <timeInterval><start>2023-12-31T23:00Z</start><end>2024-01-01T03:00Z</end></timeInterval>
+      <resolution>PT60M</resolution>
+      <Point><position>1</position><price.amount>50.5</price.amount></Point>
+      <Point><position>2</position><price.amount>40</price.amount></Point>
+      <Point><position>4</position><price.amount>-5</price.amount></Point>
+    </Period>
+  </TimeSeries>
+  <TimeSeries>
+    <Period>
+      <timeInterval><start>2024-01-01T03:00Z</start><end>2024-01-01T04:00Z</end></timeInterval>
+      <resolution>PT15M</resolution>
+      <Point><position>1</position><price.amount>10</price.amount></Point>
+      <Point><position>2</position><price.amount>20</price.amount></Point>
+      <Point><position>3</position><price.amount>30</price.amount></Point>
+      <Point><position>4</position><price.amount>40</price.amount></Point>
+    </Period>
+  </TimeSeries>
+</Publication_MarketDocument>"#;
+
+#[test]
+fn documents_parse_into_local_hours() {
+    let hours = parse_document(DOCUMENT, Some(chrono_tz::Europe::Berlin)).unwrap();
+    let expected = BTreeMap::from([
+        (ORIGIN, 50.5),
+        (ORIGIN + HOUR, 40.0),
+        (ORIGIN + 2 * HOUR, 40.0),
+        (ORIGIN + 3 * HOUR, -5.0),
+        (ORIGIN + 4 * HOUR, 25.0),
+    ]);
+    assert_eq!(hours, expected);
+    // In UTC the same prices start an hour earlier
+    assert_eq!(
+        parse_document(DOCUMENT, None).unwrap().keys().next(),
+        Some(&(ORIGIN - HOUR))
+    );
+}
+
+#[test]
+fn acknowledgements_have_no_prices() {
+    let ack = r#"<Acknowledgement_MarketDocument><Reason><code>999</code><text>No matching data found</text></Reason></Acknowledgement_MarketDocument>"#;
+    assert!(parse_document(ack, None).unwrap().is_empty());
+}
+
+#[test]
+fn cached_days_load_without_the_api() {
+    let dir = std::env::temp_dir().join("price_cache");
+    std::fs::create_dir_all(&dir).unwrap();
+    let cached = BTreeMap::from([("2024-01-01 00:00", 42.0), ("2024-01-01 01:00", 43.0)]);
+    std::fs::write(
+        dir.join("ZONE-2024-01-01.json"),
+        serde_json::to_string(&cached).unwrap(),
+    )
+    .unwrap();
+    let config = PricesConfig {
+        bidding_zone: "ZONE".to_string(),
+        cache_dir: dir.display().to_string(),
+        ..PricesConfig::default()
+    };
+    let day = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let prices = Prices::load(&config, day..=day).unwrap();
+    assert_eq!(prices.len(), 2);
+    assert_eq!(prices.get(ORIGIN + HOUR + 1800), Some(43.0));
+}
+
+#[test]
+fn unpublished_hours_repeat_earlier_prices() {
+    let prices = Prices::new(BTreeMap::from([
+        (ORIGIN, 10.0),
+        (ORIGIN + HOUR, 20.0),
+        (ORIGIN + 7 * DAY + HOUR, 30.0),
+    ]));
+    // A week later first, else a day later
+    assert_eq!(prices.at(ORIGIN + 14 * DAY + HOUR), 30.0);
+    assert_eq!(prices.at(ORIGIN + 2 * DAY), 10.0);
+    assert_eq!(prices.at(ORIGIN + 5 * HOUR), 20.0);
+
+    // A fit on the first day only knows that day's prices
+    let known = prices.until(ORIGIN + DAY);
+    assert_eq!(known.len(), 2);
+    assert_eq!(known.at(ORIGIN + 7 * DAY + HOUR), 20.0);
+}
+
+#[test]
+fn cheapest_strategy_charges_in_the_cheapest_hours() {
+    let prices = Prices::new(
+        (0..6)
+            .map(|h| {
+                (
+                    ORIGIN + h * HOUR,
+                    [50.0, 40.0, 10.0, 30.0, 5.0, 60.0][h as usize],
+                )
+            })
+            .collect(),
+    );
+    let visit = VehicleVisit {
+        vehicle_id: "bus".to_string(),
+        arrival: ORIGIN,
+        departure: ORIGIN + 6 * HOUR,
+        required_kwh: 50.0,
+    };
+    let config = DepotConfig {
+        charger_power_kw: 22.0,
+        strategy: ChargingStrategy::Cheapest,
+        ..DepotConfig::default()
+    };
+    let timestamps: Vec<i64> = (0..6).map(|h| ORIGIN + h * HOUR).collect();
+    let (load, shortfalls) = fleet_load(&[visit], &timestamps, &config, Some(&prices));
+    assert_eq!(load, vec![0.0, 0.0, 22.0, 6.0, 22.0, 0.0]);
+    assert!(shortfalls.is_empty());
+}
@@ -0,0 +1,169 @@
+//! Personal columns of session exports: kept out of loads, inspections and ingested copies.
+
+use cpo_charging_forecast::config::{
+    ColumnConfig, Config, EnergyUnit, LocaleConfig, PersonalAction, PersonalDataConfig,
+};
+use cpo_charging_forecast::data::read_sessions_formatted;
+use cpo_charging_forecast::inspect;
+use cpo_charging_forecast::pipeline::load_history_counted;
+use cpo_charging_forecast::privacy::{self, Pseudonymizer};
+use cpo_charging_forecast::source::csv_format;
+
+const EXPORT: &str = "Session;User;Start;Stop;RFID tag;Energy (kWh)\n\
+                      1;alice@example.com;05.03.2024 07:05;05.03.2024 08:00;04A2B3C4;12,5\n\
+                      2;bob@example.com;05.03.2024 09:05;05.03.2024 10:30;04FFEE11;8,25\n\
+                      3;alice@example.com;06.03.2024 17:00;06.03.2024 18:00;04A2B3C4;22\n";
+
+fn columns() -> ColumnConfig {
+    ColumnConfig {
+        start: 2,
+        stop: 3,
+        energy: 5,
+        energy_unit: EnergyUnit::Kwh,
+        site: None,
+    }
+}
+
+fn locale() -> LocaleConfig {
+    LocaleConfig {
+        delimiter: ';',
+        decimal: ',',
+        thousands: None,
+        date_formats: vec!["%d.%m.%Y %H:%M".to_string()],
+    }
+}
+
+fn ingest(personal: &PersonalDataConfig) -> String {
+    let format = csv_format(&locale(), &columns(), "", false).unwrap();
+    let mut copy = Vec::new();
+    let rows = Pseudonymizer::new(personal)
+        .unwrap()
+        .copy(EXPORT.as_bytes(), &mut copy, &format)
+        .unwrap();
+    assert_eq!(rows, 3);
+    String::from_utf8(copy).unwrap()
+}
+
+#[test]
+fn ingested_copies_keep_sessions_but_no_ids() {
+    let format = csv_format(&locale(), &columns(), "", false).unwrap();
+    let (sessions, _) = read_sessions_formatted(EXPORT.as_bytes(), &format).unwrap();
+
+    let dropped = ingest(&PersonalDataConfig {
+        columns: vec![1, 4],
+        ..PersonalDataConfig::default()
+    });
+    assert!(
+        dropped.starts_with("Session;User;Start;Stop;RFID tag;Energy (kWh)\n"),
+        "{}",
+        dropped
+    );
+    assert!(
+        dropped.contains("\n1;;05.03.2024 07:05;05.03.2024 08:00;;12,5\n"),
+        "{}",
+        dropped
+    );
+    // The same config reads the copy
+    assert_eq!(
+        read_sessions_formatted(dropped.as_bytes(), &format)
+            .unwrap()
+            .0,
+        sessions
+    );
+
+    let hashed = |key: &str| {
+        ingest(&PersonalDataConfig {
+            columns: vec![1, 4],
+            action: PersonalAction::Hash,
+            key: Some(key.to_string()),
+        })
+    };
+    let copy = hashed("k1");
+    assert!(
+        !copy.contains("alice") && !copy.contains("04A2B3C4"),
+        "{}",
+        copy
+    );
+    assert_eq!(
+        read_sessions_formatted(copy.as_bytes(), &format).unwrap().0,
+        sessions
+    );
+    let users: Vec<&str> = copy
+        .lines()
+        .skip(1)
+        .map(|line| line.split(';').nth(1).unwrap())
+        .collect();
+    assert_eq!(users[0], users[2], "the same user gets the same pseudonym");
+    assert_ne!(users[0], users[1]);
+    assert_eq!(users[0].len(), 32);
+    assert_ne!(hashed("k2"), copy, "pseudonyms depend on the key");
+
+    // Without a header, a first row with a garbled start time is still a row
+    let garbled = "1;carol@example.com;05.03.2024 7h;05.03.2024 08:00;04C0FFEE;12,5\n";
+    let mut copy = Vec::new();
+    let rows = Pseudonymizer::new(&PersonalDataConfig {
+        columns: vec![1, 4],
+        ..PersonalDataConfig::default()
+    })
+    .unwrap()
+    .copy(garbled.as_bytes(), &mut copy, &format)
+    .unwrap();
+    let copy = String::from_utf8(copy).unwrap();
+    assert_eq!(rows, 1);
+    assert_eq!(copy, "1;;05.03.2024 7h;05.03.2024 08:00;;12,5\n");
+
+    let unkeyed = PersonalDataConfig {
+        columns: vec![1],
+        action: PersonalAction::Hash,
+        key: None,
+    };
+    assert!(Pseudonymizer::new(&unkeyed).is_err());
+}
+
+#[test]
+fn session_fields_are_never_read_from_personal_columns() {
+    assert!(privacy::check_columns(&[1, 4], &columns()).is_ok());
+    let error = privacy::check_columns(&[5], &columns()).unwrap_err();
+    assert!(
+        error.to_string().contains("data.columns.energy"),
+        "{}",
+        error
+    );
+
+    let path = std::env::temp_dir().join(format!("cpo-privacy-{}.csv", std::process::id()));
+    std::fs::write(&path, EXPORT).unwrap();
+    let config = Config::from_toml(
+        &format!(
+            "[data]\npath = {:?}\n[data.locale]\ndelimiter = \";\"\ndecimal = \",\"\ndate_formats = [\"%d.%m.%Y %H:%M\"]\n\
+             [data.columns]\nstart = 2\nstop = 4\nenergy = 5\nenergy_unit = \"kwh\"\n[data.personal]\ncolumns = [1, 4]\n",
+            path.display()
+        ),
+        "test",
+    )
+    .unwrap();
+    let error = load_history_counted(&config).unwrap_err();
+    std::fs::remove_file(&path).ok();
+    assert!(
+        error
+            .to_string()
+            .contains("data.columns.stop reads column 4"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn inspect_proposes_personal_columns_without_showing_them() {
+    let inspection = inspect::inspect(EXPORT).unwrap();
+    assert_eq!(inspection.personal, vec![1, 4]);
+    let summary = inspection.summary().join("\n");
+    assert!(
+        summary.contains("<- personal")
+            && !summary.contains("alice")
+            && !summary.contains("04A2B3C4"),
+        "{}",
+        summary
+    );
+    let config: Config = toml::from_str(&inspection.to_toml("export.csv")).unwrap();
+    assert_eq!(config.data.personal.unwrap().columns, vec![1, 4]);
+}
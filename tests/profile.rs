@@ -0,0 +1,56 @@
+//! Seasonal profiles: normalized daily and weekly demand shapes.
+
+use cpo_charging_forecast::data::Series;
+use cpo_charging_forecast::profile::{self, Profiles};
+
+/// 2024-01-01 00:00, a Monday.
+const MONDAY: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+
+/// `weeks` of load: 2 kW in the evening hours, 1 kW otherwise, nothing on Sundays.
+fn history(weeks: i64) -> Series {
+    let timestamps: Vec<i64> = (0..weeks * 168).map(|h| MONDAY + h * HOUR).collect();
+    let values = (0..weeks * 168)
+        .map(|h| match (h % 168 / 24, h % 24) {
+            (6, _) => 0.0,
+            (_, 17..=19) => 2.0,
+            _ => 1.0,
+        })
+        .collect();
+    Series::new(timestamps, values)
+}
+
+#[test]
+fn profiles_are_relative_to_the_mean_load() {
+    let profiles = Profiles::new("north", &history(2)).unwrap();
+    assert_eq!((profiles.daily.len(), profiles.weekly.len()), (24, 168));
+    // Both curves average 1
+    assert!((profiles.daily.iter().sum::<f64>() / 24.0 - 1.0).abs() < 1e-9);
+    assert!((profiles.weekly.iter().sum::<f64>() / 168.0 - 1.0).abs() < 1e-9);
+    // Mean load: 6 days of 21 h at 1 kW and 3 h at 2 kW, over 7 days
+    let mean = 6.0 * 27.0 / 168.0;
+    assert!((profiles.weekly[18] - 2.0 / mean).abs() < 1e-9);
+    assert!((profiles.daily[18] - 6.0 * 2.0 / 7.0 / mean).abs() < 1e-9);
+    assert_eq!(profiles.weekly[6 * 24 + 18], 0.0);
+
+    let path = std::env::temp_dir().join(format!("cpo-profiles-{}.csv", std::process::id()));
+    profile::write_csv(&[profiles], path.to_str().unwrap()).unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 1 + 24 + 168);
+    assert_eq!(lines[0], "site,profile,weekday,hour,value");
+    assert_eq!(lines[1], "north,daily,,0,0.8889");
+    assert_eq!(lines[25], "north,weekly,Mon,0,1.0370");
+}
+
+#[test]
+fn profiles_need_a_full_week_with_demand() {
+    let week = history(1);
+    let short = Series::new(week.timestamps[..100].to_vec(), week.values[..100].to_vec());
+    let error = Profiles::new("north", &short).unwrap_err().to_string();
+    assert!(error.contains("Fri 04:00"), "{}", error);
+
+    let idle = Series::new(history(1).timestamps.to_vec(), vec![0.0; 168]);
+    assert!(Profiles::new("north", &idle).is_err());
+}
@@ -0,0 +1,108 @@
+//! Ramp forecasts: the step-to-step change in load, fitted on the differenced history.
+
+#[cfg(feature = "wasmstan")]
+use cpo_charging_forecast::config::{ModelConfig, RampConfig};
+use cpo_charging_forecast::data::Series;
+#[cfg(feature = "wasmstan")]
+use cpo_charging_forecast::forecast::{Forecast, future_timestamps};
+#[cfg(feature = "wasmstan")]
+use cpo_charging_forecast::ramp::RampForecast;
+use cpo_charging_forecast::ramp::differences;
+
+/// 2024-01-01 00:00.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+
+/// 50 kW from 08:00 to 18:00 and 10 kW otherwise, with a little noise.
+#[cfg(feature = "wasmstan")]
+fn level(hour: i64) -> f64 {
+    let noise = (hour * 7919 % 13) as f64 / 13.0;
+    match hour % 24 {
+        8..=17 => 50.0 + noise,
+        _ => 10.0 + noise,
+    }
+}
+
+#[cfg(feature = "wasmstan")]
+fn history(days: i64) -> Series {
+    Series::new(
+        (0..days * 24).map(|h| ORIGIN + h * HOUR).collect(),
+        (0..days * 24).map(level).collect(),
+    )
+}
+
+#[test]
+fn differences_are_at_the_later_point() {
+    let series = Series::new(
+        vec![ORIGIN, ORIGIN + HOUR, ORIGIN + 2 * HOUR],
+        vec![5.0, 12.0, 3.0],
+    );
+    let ramps = differences(&series);
+    assert_eq!(ramps.timestamps, vec![ORIGIN + HOUR, ORIGIN + 2 * HOUR]);
+    assert_eq!(ramps.values, vec![7.0, -9.0]);
+}
+
+#[cfg(feature = "wasmstan")]
+#[test]
+fn ramps_are_forecast_up_and_down() {
+    let history = history(21);
+    let timestamps = future_timestamps(*history.timestamps.last().unwrap(), 24, None);
+    let level_forecast = Forecast {
+        point: timestamps
+            .iter()
+            .map(|ts| level((ts - ORIGIN) / HOUR))
+            .collect(),
+        lower: vec![0.0; 24],
+        upper: vec![0.0; 24],
+        timestamps,
+    };
+    let ramp = RampForecast::new(&history, &level_forecast, &ModelConfig::default()).unwrap();
+    assert_eq!(ramp.ramp.timestamps, level_forecast.timestamps);
+    // The morning ramp up and the evening ramp down, below zero where it goes down
+    assert!(ramp.ramp.point[8] > 25.0, "{:?}", ramp.ramp.point);
+    assert!(ramp.ramp.point[18] < -25.0, "{:?}", ramp.ramp.point);
+    assert!(ramp.ramp.point[12].abs() < 10.0, "{:?}", ramp.ramp.point);
+    assert!((ramp.level_ramp[8] - (level(21 * 24 + 8) - level(21 * 24 + 7))).abs() < 1e-9);
+    assert!(
+        ramp.summary().starts_with("Ramp: up to +"),
+        "{}",
+        ramp.summary()
+    );
+
+    // Only the ramp hours reach beyond a limit between the daily ramp and none
+    let limit = RampConfig {
+        max_ramp_kw: Some(30.0),
+        ..RampConfig::default()
+    };
+    let violations = ramp.violations(30.0);
+    assert!(
+        violations
+            .iter()
+            .any(|(ts, kw)| *ts == ramp.ramp.timestamps[8] && *kw > 30.0),
+        "{:?}",
+        violations
+    );
+    assert!(
+        violations
+            .iter()
+            .any(|(ts, kw)| *ts == ramp.ramp.timestamps[18] && *kw < -30.0),
+        "{:?}",
+        violations
+    );
+    assert!(
+        ramp.warning(&limit)
+            .unwrap()
+            .contains("may ramp beyond ±30.0 kW")
+    );
+    assert_eq!(ramp.warning(&RampConfig::default()), None);
+
+    let path = std::env::temp_dir().join(format!("cpo-ramp-{}.csv", std::process::id()));
+    ramp.write_csv(path.to_str().unwrap()).unwrap();
+    let csv = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+    assert_eq!(
+        csv.lines().next(),
+        Some("timestamp,ramp_kw,ramp_lower_kw,ramp_upper_kw,level_ramp_kw")
+    );
+    assert_eq!(csv.lines().count(), 25);
+}
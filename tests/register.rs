@@ -0,0 +1,156 @@
+//! Cumulative energy registers differenced to interval energy.
+
+use std::error::Error;
+
+use cpo_charging_forecast::config::{Config, EnergyUnit, RegisterConfig};
+use cpo_charging_forecast::data::{RowCounts, Series};
+use cpo_charging_forecast::pipeline::load_history_from;
+use cpo_charging_forecast::register::{Interval, Reading, intervals, load};
+use cpo_charging_forecast::source::{DataSource, Readings, SeriesSource};
+
+/// 2024-01-01 00:00.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+
+/// Readings of `charger`'s counter.
+fn counter(charger: &str, readings: &[(i64, f64)]) -> Vec<Reading> {
+    readings
+        .iter()
+        .map(|&(timestamp, value)| Reading {
+            charger: charger.to_string(),
+            timestamp,
+            value,
+        })
+        .collect()
+}
+
+/// Chargers' registers held in memory.
+struct Registers(Vec<Reading>);
+
+impl DataSource for Registers {
+    fn name(&self) -> String {
+        "test registers".to_string()
+    }
+
+    fn read(&self) -> Result<(Readings, RowCounts), Box<dyn Error>> {
+        Ok((Readings::Registers(self.0.clone()), RowCounts::default()))
+    }
+}
+
+#[test]
+fn rollovers_are_counted_through_and_replacements_left_out() {
+    let config = RegisterConfig {
+        rollover: Some(100_000.0),
+        max_kw: 50.0,
+        ..RegisterConfig::default()
+    };
+    let readings = [
+        (ORIGIN, 80_000.0),
+        (ORIGIN + HOUR, 90_000.0),
+        // 10 kWh wrapping past 100 000 Wh
+        (ORIGIN + 2 * HOUR, 0.0),
+        (ORIGIN + 3 * HOUR, 12_000.0),
+        // A new meter: far too much to have wrapped around in an hour
+        (ORIGIN + 4 * HOUR, 500.0),
+        (ORIGIN + 5 * HOUR, 20_500.0),
+        // A day without readings, then an impossible jump
+        (ORIGIN + 29 * HOUR, 40_500.0),
+        (ORIGIN + 30 * HOUR, 95_000.0),
+    ];
+    let conversion = intervals(&counter("", &readings), &config);
+    assert_eq!(conversion.rollovers, vec![ORIGIN + 2 * HOUR]);
+    assert_eq!(
+        conversion.replacements,
+        vec![ORIGIN + 4 * HOUR, ORIGIN + 30 * HOUR]
+    );
+    assert_eq!(conversion.gaps, 1);
+    let energy: Vec<f64> = conversion.intervals.iter().map(|i| i.energy_kwh).collect();
+    assert_eq!(energy, vec![10.0, 10.0, 12.0, 20.0]);
+
+    let kwh = RegisterConfig {
+        unit: EnergyUnit::Kwh,
+        ..RegisterConfig::default()
+    };
+    assert_eq!(
+        intervals(&counter("", &[(ORIGIN, 1.5), (ORIGIN + HOUR, 4.0)]), &kwh).intervals[0]
+            .energy_kwh,
+        2.5
+    );
+}
+
+#[test]
+fn interval_energy_is_spread_over_its_steps() {
+    // 30 kWh from 00:30 to 02:00, 5 kWh in the quarter hour from 03:00
+    let spread = load(
+        &[
+            Interval {
+                charger: String::new(),
+                from: ORIGIN + HOUR / 2,
+                to: ORIGIN + 2 * HOUR,
+                energy_kwh: 30.0,
+            },
+            Interval {
+                charger: String::new(),
+                from: ORIGIN + 3 * HOUR,
+                to: ORIGIN + 3 * HOUR + 900,
+                energy_kwh: 5.0,
+            },
+        ],
+        HOUR,
+    );
+    assert_eq!(
+        spread.timestamps,
+        vec![ORIGIN, ORIGIN + HOUR, ORIGIN + 3 * HOUR]
+    );
+    // 20 kW while metered
+    assert_eq!(spread.values, vec![20.0, 20.0, 20.0]);
+}
+
+#[test]
+fn register_series_are_differenced_before_the_grid() {
+    let config: Config = toml::from_str("[data.register]\nunit = \"wh\"").unwrap();
+    let counter = SeriesSource::new(
+        "counter",
+        Series::new(
+            (0..4).map(|h| ORIGIN + h * HOUR).collect(),
+            vec![1_000.0, 8_000.0, 8_000.0, 20_000.0],
+        ),
+    );
+    let (history, _) = load_history_from(&config, &counter).unwrap();
+    assert_eq!(history.values, vec![7.0, 0.0, 12.0]);
+
+    let both: Config = toml::from_str("[data.register]\n[data.power]").unwrap();
+    assert!(load_history_from(&both, &counter).is_err());
+}
+
+#[test]
+fn each_chargers_counter_is_differenced_on_its_own() {
+    let config: Config = toml::from_str("[data.register]\nunit = \"kwh\"").unwrap();
+    // Two counters far apart, read out of order: 10 kW on one, 4 kW on the other
+    let mut readings = counter(
+        "cp-1",
+        &[
+            (ORIGIN, 100.0),
+            (ORIGIN + HOUR, 110.0),
+            (ORIGIN + 2 * HOUR, 120.0),
+        ],
+    );
+    readings.extend(counter(
+        "cp-2",
+        &[
+            (ORIGIN + 2 * HOUR, 90_008.0),
+            (ORIGIN, 90_000.0),
+            (ORIGIN + HOUR, 90_004.0),
+        ],
+    ));
+    readings.swap(0, 4);
+    let conversion = intervals(&readings, config.data.register.as_ref().unwrap());
+    assert!(conversion.replacements.is_empty());
+    assert_eq!(conversion.intervals.len(), 4);
+    let (history, _) = load_history_from(&config, &Registers(readings.clone())).unwrap();
+    assert_eq!(history.values, vec![14.0, 14.0]);
+
+    // Registers need [data.register] to be differenced
+    let e = load_history_from(&Config::default(), &Registers(readings)).unwrap_err();
+    assert!(e.to_string().contains("[data.register]"), "{}", e);
+}
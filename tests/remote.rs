@@ -0,0 +1,93 @@
+//! Object-store inputs and outputs, through `file://` URIs.
+
+use cpo_charging_forecast::remote::{is_remote, temp_file, with_input, with_output};
+
+#[test]
+fn only_object_store_schemes_are_remote() {
+    for path in [
+        "s3://bucket/site.csv",
+        "gs://bucket/a/b.parquet",
+        "az://container/x",
+        "file:///tmp/x.csv",
+    ] {
+        assert!(is_remote(path), "{}", path);
+    }
+    for path in [
+        "data/site.csv",
+        "-",
+        "C:\\data\\site.csv",
+        "https://example.com/site.csv",
+    ] {
+        assert!(!is_remote(path), "{}", path);
+    }
+}
+
+#[test]
+fn local_paths_are_passed_through() {
+    let seen = with_input("data/site.csv", |path| Ok(path.to_string())).unwrap();
+    assert_eq!(seen, "data/site.csv");
+}
+
+#[test]
+fn temporary_copies_are_private_and_unpredictable() {
+    let (a, b) = (
+        temp_file("s3://bucket/site.csv").unwrap(),
+        temp_file("s3://bucket/site.csv").unwrap(),
+    );
+    assert_ne!(a, b);
+    assert!(a.display().to_string().ends_with("-site.csv"), "{:?}", a);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&a).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    std::fs::remove_file(&a).ok();
+    std::fs::remove_file(&b).ok();
+}
+
+#[cfg(feature = "cloud")]
+#[test]
+fn exports_round_trip_through_the_store() {
+    use cpo_charging_forecast::config::SynthConfig;
+    use cpo_charging_forecast::data::load_sessions_counted;
+    use cpo_charging_forecast::synth;
+
+    let config = SynthConfig {
+        days: 3,
+        ..SynthConfig::default()
+    };
+    let sessions = synth::sessions(&config).unwrap();
+    let dir = std::env::temp_dir().join(format!("cpo-remote-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let url = format!("file://{}/sessions.csv", dir.display());
+
+    with_output(&url, |path| {
+        // The writer sees a local file with the object's extension
+        assert!(
+            path.ends_with("sessions.csv") && !path.starts_with("file:"),
+            "{}",
+            path
+        );
+        synth::write_sessions_csv(&sessions, &config, path)
+    })
+    .unwrap();
+    assert!(dir.join("sessions.csv").exists());
+
+    let (loaded, _) = with_input(&url, |path| load_sessions_counted(path)).unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+    assert_eq!(loaded.len(), sessions.len());
+
+    let missing = with_input(&url, |path| load_sessions_counted(path)).unwrap_err();
+    assert!(missing.to_string().contains("sessions.csv"), "{}", missing);
+}
+
+#[cfg(not(feature = "cloud"))]
+#[test]
+fn remote_paths_need_the_cloud_feature() {
+    let e = with_output("s3://bucket/forecast.csv", |path| {
+        Ok(std::fs::write(path, "")?)
+    })
+    .unwrap_err();
+    assert!(e.to_string().contains("`cloud` feature"), "{}", e);
+}
@@ -0,0 +1,126 @@
+//! Revenue of a forecast under flat and time-of-use pricing, with idle fees.
+
+use cpo_charging_forecast::config::{Days, PricingConfig, TouRate};
+use cpo_charging_forecast::data::Session;
+use cpo_charging_forecast::forecast::Forecast;
+use cpo_charging_forecast::preprocess::hourly_overstay;
+use cpo_charging_forecast::revenue::{RevenueForecast, monthly, price_at, with_totals};
+
+/// 2024-01-01 00:00, a Monday.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+const DAY: i64 = 24 * HOUR;
+
+fn pricing() -> PricingConfig {
+    PricingConfig {
+        energy_price: 0.40,
+        tou: vec![
+            TouRate {
+                from_hour: 17,
+                to_hour: 21,
+                price: 0.60,
+                days: Days::Weekdays,
+            },
+            TouRate {
+                from_hour: 22,
+                to_hour: 6,
+                price: 0.30,
+                days: Days::All,
+            },
+        ],
+        idle_fee_per_hour: 6.0,
+        ..PricingConfig::default()
+    }
+}
+
+fn flat(timestamps: Vec<i64>, kw: f64, spread: f64) -> Forecast {
+    let n = timestamps.len();
+    Forecast {
+        timestamps,
+        point: vec![kw; n],
+        lower: vec![kw - spread; n],
+        upper: vec![kw + spread; n],
+    }
+}
+
+#[test]
+fn time_of_use_rates_apply_by_hour_and_day() {
+    let pricing = pricing();
+    assert_eq!(price_at(&pricing, ORIGIN + 12 * HOUR), 0.40);
+    assert_eq!(price_at(&pricing, ORIGIN + 17 * HOUR), 0.60);
+    assert_eq!(price_at(&pricing, ORIGIN + 21 * HOUR), 0.40);
+    // The night rate wraps past midnight
+    assert_eq!(price_at(&pricing, ORIGIN + 23 * HOUR), 0.30);
+    assert_eq!(price_at(&pricing, ORIGIN + DAY + 5 * HOUR), 0.30);
+    // Saturday evening isn't peak
+    assert_eq!(price_at(&pricing, ORIGIN + 5 * DAY + 18 * HOUR), 0.40);
+}
+
+#[test]
+fn overstay_starts_after_charging_and_grace() {
+    // 22 kWh at 11 kW is two hours of charging, then 30 minutes of grace
+    let sessions = [Session {
+        start: ORIGIN,
+        stop: ORIGIN + 4 * HOUR,
+        energy_wh: 22_000.0,
+    }];
+    let overstay = hourly_overstay(&sessions, 11.0, 30.0);
+    assert_eq!(overstay.values, vec![0.0, 0.0, 0.5, 1.0, 0.0]);
+    // Sessions that charge until plug-out never overstay
+    assert!(
+        hourly_overstay(&sessions, 5.5, 0.0)
+            .values
+            .iter()
+            .all(|v| *v == 0.0)
+    );
+}
+
+#[test]
+fn revenue_prices_energy_and_idle_hours() {
+    let timestamps: Vec<i64> = (16..19).map(|h| ORIGIN + h * HOUR).collect();
+    let energy = flat(timestamps.clone(), 100.0, 50.0);
+    // Overstay only known for the last hour
+    let overstay = flat(timestamps[2..].to_vec(), 2.0, 3.0);
+    let revenue = RevenueForecast::new(&energy, Some(&overstay), &pricing());
+    assert_eq!(revenue.energy_revenue, vec![40.0, 60.0, 60.0]);
+    assert_eq!(revenue.idle_revenue, vec![0.0, 0.0, 12.0]);
+    assert_eq!(revenue.revenue, vec![40.0, 60.0, 72.0]);
+    // Negative lower bounds bill nothing
+    assert_eq!(revenue.lower, vec![20.0, 30.0, 30.0]);
+    assert_eq!(revenue.upper, vec![60.0, 90.0, 120.0]);
+    assert_eq!(revenue.total(), 172.0);
+}
+
+#[test]
+fn monthly_rows_add_up_to_totals() {
+    let pricing = PricingConfig {
+        energy_price: 0.5,
+        ..PricingConfig::default()
+    };
+    // The last day of January and the first of February
+    let timestamps: Vec<i64> = (0..48).map(|h| ORIGIN + 30 * DAY + h * HOUR).collect();
+    let north = RevenueForecast::new(&flat(timestamps.clone(), 10.0, 2.0), None, &pricing);
+    let south = RevenueForecast::new(&flat(timestamps, 30.0, 2.0), None, &pricing);
+    let rows = with_totals([monthly("north", &north), monthly("south", &south)].concat());
+
+    let months: Vec<(&str, &str)> = rows
+        .iter()
+        .map(|r| (r.site.as_str(), r.month.as_str()))
+        .collect();
+    assert_eq!(
+        months,
+        [
+            ("north", "2024-01"),
+            ("north", "2024-02"),
+            ("south", "2024-01"),
+            ("south", "2024-02"),
+            ("total", "2024-01"),
+            ("total", "2024-02")
+        ]
+    );
+    let total = &rows[4];
+    assert_eq!(total.hours, 24);
+    assert_eq!(total.energy_kwh, 960.0);
+    assert_eq!(total.revenue, 480.0);
+    assert_eq!((total.revenue_lower, total.revenue_upper), (432.0, 528.0));
+}
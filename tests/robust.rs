@@ -0,0 +1,57 @@
+//! Robust Prophet fits: extreme hours pulled in rather than dragging the trend.
+
+#![cfg(feature = "wasmstan")]
+
+use cpo_charging_forecast::config::{ModelConfig, RobustConfig};
+use cpo_charging_forecast::data::Series;
+use cpo_charging_forecast::forecast::fit;
+
+/// 2024-01-01 00:00.
+const ORIGIN: i64 = 1_704_067_200;
+
+/// Flat at 20 kW with a daily cycle and some noise, and a meter glitch of
+/// 400 kW in some of the last hours.
+fn history() -> Series {
+    let hours = 42 * 24;
+    let timestamps: Vec<i64> = (0..hours).map(|h| ORIGIN + h * 3600).collect();
+    let values: Vec<f64> = (0..hours)
+        .map(|h| {
+            let daily = 8.0 * (2.0 * std::f64::consts::PI * (h % 24) as f64 / 24.0).sin();
+            let noise = 2.0 * ((h * 7919 % 101) as f64 / 50.0 - 1.0);
+            let glitch = if h >= hours - 72 && h % 6 == 0 {
+                400.0
+            } else {
+                0.0
+            };
+            20.0 + daily + noise + glitch
+        })
+        .collect();
+    Series::new(timestamps, values)
+}
+
+#[test]
+fn extreme_hours_do_not_drag_the_trend() {
+    let history = history();
+    let last = *history.timestamps.last().unwrap();
+    let future: Vec<i64> = (1..=168).map(|h| last + h * 3600).collect();
+    let mean = |model: &ModelConfig| {
+        let forecast = fit(&history, model)
+            .unwrap()
+            .predict(future.clone())
+            .unwrap();
+        forecast.point.iter().sum::<f64>() / forecast.len() as f64
+    };
+
+    let plain = mean(&ModelConfig::default());
+    let robust = mean(&ModelConfig {
+        robust: Some(RobustConfig::default()),
+        ..ModelConfig::default()
+    });
+    assert!((robust - 20.0).abs() < 3.0, "robust mean {:.1} kW", robust);
+    assert!(
+        (robust - 20.0).abs() < (plain - 20.0).abs() / 3.0,
+        "robust {:.1} vs plain {:.1} kW",
+        robust,
+        plain
+    );
+}
@@ -0,0 +1,99 @@
+//! Seasonality detection from the autocorrelation and periodogram of a history.
+
+use cpo_charging_forecast::config::{ModelConfig, SeasonalityConfig};
+use cpo_charging_forecast::data::Series;
+use cpo_charging_forecast::seasonality::{detect, fourier_order};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f64::consts::PI;
+
+/// 2024-01-01 00:00, a Monday.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+
+/// Nine weeks of hourly demand from `value(hour)`.
+fn history(value: impl Fn(i64) -> f64) -> Series {
+    let hours = 9 * 7 * 24;
+    Series::new(
+        (0..hours).map(|h| ORIGIN + h * HOUR).collect(),
+        (0..hours).map(value).collect(),
+    )
+}
+
+/// Pseudo-random noise in [-1, 1).
+fn noise(h: i64) -> f64 {
+    (h * 7919 % 101) as f64 / 50.5 - 1.0
+}
+
+#[test]
+fn daily_and_weekly_cycles_are_found_with_their_shape() {
+    // Morning and evening peaks, quieter weekends, growing demand
+    let demand = history(|h| {
+        let phase = 2.0 * PI * (h % 24) as f64 / 24.0;
+        let weekend = if (h / 24) % 7 >= 5 { 0.6 } else { 1.0 };
+        (20.0 + 0.01 * h as f64 + 8.0 * phase.sin() + 6.0 * (2.0 * phase).cos()) * weekend
+            + noise(h)
+    });
+    let recommendation = detect(&demand, &SeasonalityConfig::default()).unwrap();
+    assert!(recommendation.daily.enabled && recommendation.weekly.enabled);
+    assert_eq!(recommendation.daily.fourier_order, Some(2));
+    assert!(recommendation.daily.autocorrelation.unwrap() > 0.5);
+    // Nine weeks are no two years
+    assert_eq!(
+        (
+            recommendation.yearly.enabled,
+            recommendation.yearly.autocorrelation
+        ),
+        (false, None)
+    );
+
+    let mut model = ModelConfig::default();
+    // Prophet's 4 and 3 terms are more than the shapes need
+    assert_eq!(recommendation.weekly.fourier_order, Some(2));
+    assert_eq!(recommendation.differences(&model), vec!["daily", "weekly"]);
+    recommendation.apply(&mut model);
+    assert!(recommendation.differences(&model).is_empty());
+    assert_eq!(model.daily_fourier_order, Some(2));
+    assert!(
+        recommendation
+            .to_toml()
+            .contains("daily_fourier_order = 2\n")
+    );
+}
+
+#[test]
+fn noise_calls_for_no_seasonality() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let values: Vec<f64> = (0..9 * 7 * 24).map(|_| rng.gen_range(15.0..25.0)).collect();
+    let recommendation = detect(
+        &history(|h| values[h as usize]),
+        &SeasonalityConfig::default(),
+    )
+    .unwrap();
+    assert!(
+        recommendation
+            .all()
+            .iter()
+            .all(|d| !d.enabled && d.fourier_order.is_none())
+    );
+    assert!(
+        detect(
+            &Series::new(vec![ORIGIN], vec![1.0]),
+            &SeasonalityConfig::default()
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn the_fourier_order_covers_the_profile_variance() {
+    let profile: Vec<f64> = (0..24)
+        .map(|h| {
+            (2.0 * PI * 3.0 * h as f64 / 24.0).cos()
+                + 0.1 * (2.0 * PI * 5.0 * h as f64 / 24.0).sin()
+        })
+        .collect();
+    assert_eq!(fourier_order(&profile, 0.95, 12), 3);
+    assert_eq!(fourier_order(&profile, 0.999, 12), 5);
+    assert_eq!(fourier_order(&profile, 0.999, 4), 4);
+}
@@ -0,0 +1,398 @@
+//! `GET /forecast` slicing, re-aggregation and paging, tenant isolation, and
+//! the OpenAPI shapes.
+
+#![cfg(feature = "native")]
+
+use cpo_charging_forecast::api::{self, ForecastPage, Nowcast, TimeSeries};
+use cpo_charging_forecast::config::{OpenAdrConfig, ServerConfig, TenantConfig};
+use cpo_charging_forecast::data::{CompactSeries, Series};
+use cpo_charging_forecast::forecast::Forecast;
+use cpo_charging_forecast::jobs::FitQueue;
+use cpo_charging_forecast::openadr;
+use cpo_charging_forecast::server::{
+    self, ForecastQuery, MAX_BODY, Resolution, Sites, Snapshot, View, authenticate, forecast_slice,
+    nowcast_json, openadr_events, query,
+};
+use serde_json::{Value, json};
+
+/// 2024-01-01 00:00, a Monday.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+
+/// Nine days of hours from Sunday 12:00, with `yhat` the hour index.
+fn forecast() -> Forecast {
+    let timestamps: Vec<i64> = (0..9 * 24).map(|h| ORIGIN - 12 * HOUR + h * HOUR).collect();
+    let point: Vec<f64> = (0..timestamps.len()).map(|h| h as f64).collect();
+    Forecast {
+        lower: point.iter().map(|p| p - 1.0).collect(),
+        upper: point.iter().map(|p| p + 1.0).collect(),
+        timestamps,
+        point,
+    }
+}
+
+#[test]
+fn query_strings_parse() {
+    let query = ForecastQuery::parse(
+        "from=2024-01-02&to=2024-01-03%2012:00&resolution=daily&limit=5",
+        None,
+    )
+    .unwrap();
+    assert_eq!(query.from, Some(ORIGIN + 24 * HOUR));
+    assert_eq!(query.to, Some(ORIGIN + 60 * HOUR));
+    assert_eq!(
+        (query.resolution, query.limit, query.offset),
+        (Resolution::Day, Some(5), 0)
+    );
+
+    // Instants are shown in the site timezone
+    let berlin =
+        ForecastQuery::parse("from=2024-01-01T00:00:00Z", Some(chrono_tz::Europe::Berlin)).unwrap();
+    assert_eq!(berlin.from, Some(ORIGIN + HOUR));
+
+    for bad in ["resolution=month", "from=soon", "limit=-1", "page=2"] {
+        assert!(ForecastQuery::parse(bad, None).is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn hours_are_sliced_and_paged() {
+    let query = ForecastQuery {
+        from: Some(ORIGIN),
+        to: Some(ORIGIN + 5 * HOUR),
+        offset: 2,
+        limit: Some(2),
+        ..ForecastQuery::default()
+    };
+    let page = forecast_slice(&forecast(), &query);
+    assert_eq!(page["total"], 5);
+    assert_eq!(page["next_offset"], 4);
+    let points = page["points"].as_array().unwrap();
+    assert_eq!(points.len(), 2);
+    assert_eq!(points[0]["timestamp"], "2024-01-01 02:00");
+    assert_eq!(points[0]["yhat"], 14.0);
+    assert!(points[0].get("energy_kwh").is_none());
+
+    let last = forecast_slice(&forecast(), &ForecastQuery { offset: 4, ..query });
+    assert!(last["next_offset"].is_null());
+    assert_eq!(last["points"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn sub_hourly_steps_are_averaged_into_hours() {
+    // Quarter hours with `yhat` the step index
+    let step = HOUR / 4;
+    let point: Vec<f64> = (0..8).map(|i| i as f64).collect();
+    let forecast = Forecast {
+        timestamps: (0..8).map(|i| ORIGIN + i * step).collect(),
+        lower: point.iter().map(|p| p - 1.0).collect(),
+        upper: point.iter().map(|p| p + 1.0).collect(),
+        point,
+    };
+    let page = forecast_slice(&forecast, &ForecastQuery::default());
+    assert_eq!(page["total"], 2);
+    let points = page["points"].as_array().unwrap();
+    assert_eq!(points[1]["timestamp"], "2024-01-01 01:00");
+    assert_eq!(
+        (points[1]["yhat"].as_f64(), points[1]["yhat_upper"].as_f64()),
+        (Some(5.5), Some(6.5))
+    );
+}
+
+#[test]
+fn days_and_weeks_are_aggregated() {
+    let daily = forecast_slice(
+        &forecast(),
+        &ForecastQuery {
+            resolution: Resolution::Day,
+            ..ForecastQuery::default()
+        },
+    );
+    let days = daily["points"].as_array().unwrap();
+    // A half day on each end
+    assert_eq!(days.len(), 10);
+    assert_eq!(days[0]["timestamp"], "2023-12-31 00:00");
+    assert_eq!(days[0]["hours"], 12);
+    let day = &days[1];
+    assert_eq!(day["timestamp"], "2024-01-01 00:00");
+    assert_eq!(
+        (day["yhat"].as_f64(), day["yhat_lower"].as_f64()),
+        (Some(23.5), Some(22.5))
+    );
+    assert_eq!(
+        (day["energy_kwh"].as_f64(), day["peak_kw"].as_f64()),
+        (Some(564.0), Some(35.0))
+    );
+
+    let weekly = forecast_slice(
+        &forecast(),
+        &ForecastQuery {
+            resolution: Resolution::Week,
+            ..ForecastQuery::default()
+        },
+    );
+    let weeks: Vec<_> = weekly["points"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|w| (w["timestamp"].clone(), w["hours"].clone()))
+        .collect();
+    assert_eq!(
+        weeks,
+        [
+            ("2023-12-25 00:00".into(), 12.into()),
+            ("2024-01-01 00:00".into(), 168.into()),
+            ("2024-01-08 00:00".into(), 36.into())
+        ]
+    );
+}
+
+fn tenants() -> ServerConfig {
+    let tenant = |key: &str| TenantConfig {
+        api_keys: vec![key.to_string()],
+    };
+    ServerConfig {
+        tenants: [
+            ("acme".to_string(), tenant("acme-key")),
+            ("volt".to_string(), tenant("volt-key")),
+        ]
+        .into(),
+        jwt_secret: Some("secret".to_string()),
+        ..ServerConfig::default()
+    }
+}
+
+fn token(tenant: &str, secret: &str) -> String {
+    let claims = json!({ "tenant": tenant, "exp": 4_102_444_800i64 });
+    let key = jsonwebtoken::EncodingKey::from_secret(secret.as_bytes());
+    format!(
+        "Bearer {}",
+        jsonwebtoken::encode(&jsonwebtoken::Header::default(), &claims, &key).unwrap()
+    )
+}
+
+#[test]
+fn requests_authenticate_as_a_tenant() {
+    assert_eq!(authenticate(&ServerConfig::default(), None, None), Ok(None));
+
+    let server = tenants();
+    assert_eq!(
+        authenticate(&server, None, Some("volt-key")),
+        Ok(Some("volt".to_string()))
+    );
+    assert_eq!(
+        authenticate(&server, Some("Bearer acme-key"), None),
+        Ok(Some("acme".to_string()))
+    );
+    assert_eq!(
+        authenticate(&server, Some(&token("volt", "secret")), None),
+        Ok(Some("volt".to_string()))
+    );
+
+    for (authorization, api_key) in [(None, None), (None, Some("acme")), (Some("acme-key"), None)] {
+        assert!(
+            authenticate(&server, authorization, api_key).is_err(),
+            "{:?}",
+            (authorization, api_key)
+        );
+    }
+    // Signed with another secret, or naming an unknown tenant
+    assert!(authenticate(&server, Some(&token("volt", "guess")), None).is_err());
+    assert!(authenticate(&server, Some(&token("other", "secret")), None).is_err());
+}
+
+#[test]
+fn tenants_see_only_their_sites() {
+    let snapshot = |kw: f64| Snapshot {
+        history: CompactSeries::from_series(&Series::new(vec![ORIGIN], vec![kw])).unwrap(),
+        forecast: Forecast::default(),
+        timezone: None,
+        events_dir: None,
+    };
+    let sites: Sites = [
+        ("acme/north".to_string(), snapshot(1.0)),
+        ("acme/south".to_string(), snapshot(2.0)),
+        ("volt/north".to_string(), snapshot(3.0)),
+    ]
+    .into();
+
+    let acme = View::new(&sites, Some("acme"));
+    assert_eq!(acme.names(), ["north", "south"]);
+    assert!(acme.site(None).is_err());
+    assert_eq!(acme.site(Some("south")).unwrap().history.values, [2.0]);
+    assert!(acme.site(Some("volt/north")).is_err());
+    assert!(acme.metric_names().contains(&"south/actual".to_string()));
+
+    let range = json!({ "from": "2023-12-31T00:00:00Z", "to": "2024-01-02T00:00:00Z" });
+    let body = |target: &str| json!({ "range": range, "targets": [{ "target": target }] });
+    assert_eq!(
+        query(&acme, &body("north/actual"))[0]["datapoints"][0][0],
+        1.0
+    );
+    assert_eq!(query(&acme, &body("volt/north/actual")), json!([]));
+
+    // A tenant with one site uses plain metric names
+    let volt = View::new(&sites, Some("volt"));
+    assert_eq!(volt.site(None).unwrap().history.values, [3.0]);
+    assert_eq!(query(&volt, &body("actual"))[0]["datapoints"][0][0], 3.0);
+    assert!(View::new(&sites, Some("ac")).names().is_empty());
+}
+
+#[test]
+fn answers_match_the_openapi_shapes() {
+    let spec = serde_json::to_value(api::openapi()).unwrap();
+    for path in [
+        "/",
+        "/openapi.json",
+        "/sites",
+        "/forecast",
+        "/search",
+        "/metrics",
+        "/query",
+        "/annotations",
+        "/fits",
+        "/fits/{id}",
+        "/nowcast",
+    ] {
+        assert!(spec["paths"].get(path).is_some(), "{}", path);
+    }
+    let forecast_params: Vec<_> = spec["paths"]["/forecast"]["get"]["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        forecast_params,
+        ["site", "from", "to", "resolution", "offset", "limit"]
+    );
+    assert!(
+        spec["components"]["securitySchemes"]
+            .get("api_key")
+            .is_some()
+    );
+
+    // Typed answers serialize back to exactly what the server sent
+    for resolution in [Resolution::Hour, Resolution::Week] {
+        let page = forecast_slice(
+            &forecast(),
+            &ForecastQuery {
+                resolution,
+                limit: Some(3),
+                ..ForecastQuery::default()
+            },
+        );
+        let typed: ForecastPage = serde_json::from_value(page.clone()).unwrap();
+        assert_eq!(serde_json::to_value(typed).unwrap(), page);
+    }
+    let snapshot = Snapshot {
+        history: CompactSeries::from_series(&Series::new(vec![ORIGIN], vec![1.5])).unwrap(),
+        ..Snapshot::default()
+    };
+    let sites: Sites = [("north".to_string(), snapshot)].into();
+    let body = json!({ "range": { "from": "2023-12-31T00:00:00Z", "to": "2024-01-02T00:00:00Z" }, "targets": [{ "target": "actual" }] });
+    let series = query(&View::new(&sites, None), &body);
+    let typed: Vec<TimeSeries> = serde_json::from_value(series.clone()).unwrap();
+    assert_eq!(typed[0].datapoints, [(1.5, ORIGIN * 1000)]);
+    assert_eq!(serde_json::to_value(typed).unwrap(), series);
+}
+
+#[test]
+fn nowcasts_take_readings_since_the_issue() {
+    let snapshot = Snapshot {
+        forecast: forecast(),
+        timezone: Some(chrono_tz::Europe::Berlin),
+        ..Snapshot::default()
+    };
+    // 2024-01-01 00:30 in Berlin: the hour the forecast says 12 kW in
+    let body = json!({ "readings": [{ "timestamp": "2023-12-31T23:30:00Z", "power_kw": 16.0 }] });
+    let answer = nowcast_json(&snapshot, &body).unwrap();
+    let typed: Nowcast = serde_json::from_value(answer.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&typed).unwrap(), answer);
+    assert_eq!(typed.issued, "2023-12-31 12:00");
+    assert_eq!(typed.current.timestamp, "2024-01-01 00:00");
+    assert_eq!((typed.current.forecast, typed.current.yhat), (12.0, 16.0));
+    assert_eq!(typed.next.map(|next| next.yhat), Some(15.0));
+
+    for bad in [
+        json!({}),
+        json!({ "readings": [{ "timestamp": "soon", "power_kw": 1.0 }] }),
+        json!({ "readings": [] }),
+    ] {
+        assert!(nowcast_json(&snapshot, &bad).is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn openadr_events_stay_with_the_tenant_site() {
+    let dir = std::env::temp_dir().join(format!("cpo-server-openadr-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let events_dir = dir.display().to_string();
+    let snapshot = |id: &str| Snapshot {
+        events_dir: Some(openadr::site_dir(&events_dir, id).display().to_string()),
+        ..Snapshot::default()
+    };
+    let sites: Sites = [
+        ("acme/north".to_string(), snapshot("acme/north")),
+        ("volt/north".to_string(), snapshot("volt/north")),
+    ]
+    .into();
+    let fits = FitQueue::new(1, 8, |_| Ok(()));
+    let acme = View::new(&sites, Some("acme"));
+    let event = |id: &str, targets: Value| {
+        json!({
+            "id": id,
+            "targets": targets,
+            "intervalPeriod": { "start": "2024-01-01T00:00:00Z", "duration": "PT1H" },
+            "intervals": [{ "id": 0, "payloads": [{ "type": "SIMPLE", "values": [3] }] }]
+        })
+    };
+    let post = |body: &Value| {
+        openadr_events(&acme, &fits, "site=north", body)
+            .status_code()
+            .0
+    };
+
+    // Both tenants call their site `north`; acme's event stays with acme's
+    let north = json!([{ "type": "RESOURCE_NAME", "values": ["north"] }]);
+    assert_eq!(post(&event("shed/1", north)), 202);
+    let config = OpenAdrConfig {
+        events_dir: events_dir.clone(),
+        ..OpenAdrConfig::default()
+    };
+    assert_eq!(openadr::load(&config, "acme/north", None).unwrap().len(), 1);
+    assert!(
+        openadr::load(&config, "volt/north", None)
+            .unwrap()
+            .is_empty()
+    );
+
+    // Events for another tenant's site, or for every site, are refused
+    let volt = json!([{ "type": "RESOURCE_NAME", "values": ["volt/north"] }]);
+    assert_eq!(post(&event("shed/2", volt)), 400);
+    assert_eq!(post(&event("shed/3", Value::Null)), 400);
+    assert_eq!(openadr::load(&config, "acme/north", None).unwrap().len(), 1);
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn bodies_over_the_limit_are_refused() {
+    let http = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/query", http.server_addr().to_ip().unwrap());
+    let responder = std::thread::spawn(move || {
+        let (sites, fits) = (Sites::new(), FitQueue::new(1, 1, |_| Ok(())));
+        for _ in 0..2 {
+            let mut request = http.recv().unwrap();
+            let response = server::handle(&mut request, &sites, &ServerConfig::default(), &fits);
+            request.respond(response).unwrap();
+        }
+    });
+    let small = ureq::post(&url).send_string("{}").unwrap();
+    assert_eq!(small.status(), 200);
+    let large = " ".repeat(MAX_BODY as usize + 1);
+    match ureq::post(&url).send_string(&large) {
+        Err(ureq::Error::Status(413, _)) => {}
+        other => panic!("{:?}", other.map(|r| r.status())),
+    }
+    responder.join().unwrap();
+}
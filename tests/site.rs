@@ -0,0 +1,72 @@
+//! Site metadata from a YAML registry, and how it fills in a run's config.
+
+use cpo_charging_forecast::config::{AlertConfig, Config, UtilizationConfig, V2gConfig};
+use cpo_charging_forecast::site::parse_registry;
+
+const REGISTRY: &str = "
+sites:
+  - id: north
+    name: North Depot
+    latitude: 52.52
+    longitude: 13.40
+    timezone: Europe/Berlin
+    grid_limit_kw: 250
+    chargers:
+      - { id: c1, power_kw: 150, connectors: 2 }
+      - { id: c2, power_kw: 22, bidirectional: true }
+  - id: south
+";
+
+#[test]
+fn registry_entries_parse_with_defaults() {
+    let sites = parse_registry(REGISTRY).unwrap();
+    assert_eq!(sites.len(), 2);
+    let north = &sites[0];
+    assert_eq!(north.timezone, Some(chrono_tz::Europe::Berlin));
+    assert_eq!(north.installed_kw(), 172.0);
+    assert_eq!(
+        (north.chargers[0].connectors, north.chargers[1].connectors),
+        (2, 1)
+    );
+    assert!(sites[1].chargers.is_empty() && sites[1].name.is_none());
+}
+
+#[test]
+fn bad_registries_are_rejected() {
+    assert!(parse_registry("sites:\n  - id: a\n    grid_limit: 5\n").is_err());
+    let e = parse_registry("sites:\n  - id: a\n  - id: a\n").unwrap_err();
+    assert!(e.to_string().contains("twice"), "{}", e);
+}
+
+#[test]
+fn metadata_fills_only_what_the_config_leaves_open() {
+    let north = &parse_registry(REGISTRY).unwrap()[0];
+    let mut config = Config {
+        v2g: Some(V2gConfig::default()),
+        utilization: Some(UtilizationConfig::default()),
+        ..Config::default()
+    };
+    north.apply(&mut config);
+    assert_eq!(config.utilization.unwrap().installed_kw, Some(172.0));
+    assert_eq!(config.timezone, Some(chrono_tz::Europe::Berlin));
+    assert_eq!(config.chargers, Some(2));
+    assert_eq!(config.v2g.unwrap().chargers, Some(1));
+    assert_eq!(config.alerts.unwrap().site_limit_kw, Some(250.0));
+
+    let mut config = Config {
+        chargers: Some(12),
+        timezone: Some(chrono_tz::UTC),
+        alerts: Some(AlertConfig {
+            site_limit_kw: Some(400.0),
+            ..AlertConfig::default()
+        }),
+        ..Config::default()
+    };
+    north.apply(&mut config);
+    assert_eq!(
+        (config.chargers, config.timezone),
+        (Some(12), Some(chrono_tz::UTC))
+    );
+    assert!(config.utilization.is_none());
+    assert_eq!(config.alerts.unwrap().site_limit_kw, Some(400.0));
+}
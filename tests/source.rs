@@ -0,0 +1,223 @@
+//! Data sources: the configured choice, and one pipeline for every source's readings.
+
+use std::error::Error;
+
+#[cfg(all(feature = "http", feature = "db"))]
+use cpo_charging_forecast::breaks;
+#[cfg(all(feature = "http", feature = "db"))]
+use cpo_charging_forecast::config::BreakConfig;
+use cpo_charging_forecast::config::{Config, SqlSourceConfig};
+use cpo_charging_forecast::data::{RowCounts, Series, Session};
+#[cfg(feature = "db")]
+use cpo_charging_forecast::db;
+use cpo_charging_forecast::forecast::future_steps;
+#[cfg(all(feature = "http", feature = "db"))]
+use cpo_charging_forecast::notify::RunSummary;
+use cpo_charging_forecast::pipeline::{fit_and_forecast, load_history_from};
+use cpo_charging_forecast::source::{self, DataSource, Readings, SeriesSource, check_hourly};
+
+/// 2024-01-01 00:00.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+
+/// A source of sessions held in memory, as a third party would write one.
+struct Sessions(Vec<Session>);
+
+impl DataSource for Sessions {
+    fn name(&self) -> String {
+        "test sessions".to_string()
+    }
+
+    fn read(&self) -> Result<(Readings, RowCounts), Box<dyn Error>> {
+        let counts = RowCounts {
+            read: self.0.len(),
+            ..RowCounts::default()
+        };
+        Ok((Readings::Sessions(self.0.clone()), counts))
+    }
+}
+
+#[test]
+fn configured_source_follows_the_data_section() {
+    let mut config = Config::default();
+    assert_eq!(source::source(&config).name(), "data/site_data.csv");
+    config.data.sql = Some(SqlSourceConfig::default());
+    assert_eq!(source::source(&config).name(), "the data.sql query");
+}
+
+#[test]
+fn load_series_are_put_on_the_hourly_grid() {
+    // Quarter-hour readings, out of order, with a missing hour and a NaN
+    let timestamps = vec![
+        ORIGIN + 900,
+        ORIGIN,
+        ORIGIN + 2 * HOUR,
+        ORIGIN + 2 * HOUR + 900,
+        ORIGIN + 3 * HOUR,
+    ];
+    let values = vec![20.0, 10.0, 30.0, f64::NAN, 40.0];
+    let mut counts = RowCounts::default();
+    let hourly = check_hourly(Series::new(timestamps, values), &mut counts);
+    assert_eq!(
+        hourly.timestamps,
+        (0..4).map(|h| ORIGIN + h * HOUR).collect::<Vec<_>>()
+    );
+    assert_eq!(hourly.values, vec![15.0, 0.0, 30.0, 40.0]);
+    assert_eq!(counts.invalid, 1);
+
+    // An hourly series is left as it is
+    let series = Series::new(vec![ORIGIN, ORIGIN + HOUR], vec![1.0, 2.0]);
+    assert_eq!(
+        check_hourly(series.clone(), &mut RowCounts::default()),
+        series
+    );
+}
+
+#[test]
+fn every_source_goes_through_the_preprocessing() {
+    let config: Config =
+        toml::from_str(r#"data.preprocess = [{ step = "window", from = "2024-01-01 02:00" }]"#)
+            .unwrap();
+    let sessions = Sessions(vec![
+        Session {
+            start: ORIGIN,
+            stop: ORIGIN + HOUR,
+            energy_wh: 11_000.0,
+        },
+        Session {
+            start: ORIGIN + 3 * HOUR,
+            stop: ORIGIN + 4 * HOUR,
+            energy_wh: 7_000.0,
+        },
+    ]);
+    let (history, counts) = load_history_from(&config, &sessions).unwrap();
+    assert_eq!(history.timestamps.first(), Some(&(ORIGIN + 2 * HOUR)));
+    assert_eq!(counts.read, 2);
+
+    let meter = SeriesSource::new(
+        "meter",
+        Series::new(
+            vec![ORIGIN + 3 * HOUR, ORIGIN + 2 * HOUR, ORIGIN],
+            vec![3.0, 2.0, 0.0],
+        ),
+    );
+    let (history, _) = load_history_from(&config, &meter).unwrap();
+    assert_eq!(history.values, vec![2.0, 3.0]);
+
+    let early = SeriesSource::new("meter", Series::new(vec![ORIGIN], vec![1.0]));
+    assert!(load_history_from(&config, &early).is_err());
+}
+
+#[test]
+fn five_minute_histories_are_forecast_on_their_own_grid() {
+    let mut config: Config =
+        toml::from_str("data.step_minutes = 5\nforecast.horizon_hours = 2").unwrap();
+    let sessions = Sessions(vec![Session {
+        start: ORIGIN + 600,
+        stop: ORIGIN + 1200,
+        energy_wh: 2_000.0,
+    }]);
+    let (history, _) = load_history_from(&config, &sessions).unwrap();
+    assert_eq!(
+        history.timestamps,
+        vec![ORIGIN + 600, ORIGIN + 900, ORIGIN + 1200]
+    );
+    // 1 kWh in five minutes is 12 kW
+    assert_eq!(history.values, vec![12.0, 12.0, 0.0]);
+
+    // Three days of minute readings with an evening peak
+    let minutes = 3 * 24 * 60;
+    let timestamps = (0..minutes).map(|m| ORIGIN + m * 60).collect();
+    let values = (0..minutes)
+        .map(|m| {
+            if (17 * 60..19 * 60).contains(&(m % (24 * 60))) {
+                50.0
+            } else {
+                5.0
+            }
+        })
+        .collect();
+    let meter = SeriesSource::new("meter", Series::new(timestamps, values));
+    let (history, _) = load_history_from(&config, &meter).unwrap();
+    assert_eq!((history.len(), history.step()), (3 * 24 * 12, 300));
+    let (_, forecast) = fit_and_forecast(&config, &history).unwrap();
+    assert_eq!(forecast.len(), 24);
+    assert!(forecast.timestamps.windows(2).all(|w| w[1] - w[0] == 300));
+    assert_eq!(
+        forecast.timestamps[0],
+        history.timestamps.last().unwrap() + 300
+    );
+
+    // Across the autumn change the repeated hour comes once
+    let berlin: chrono_tz::Tz = "Europe/Berlin".parse().unwrap();
+    // 2024-10-27 01:00, two hours before clocks go back
+    let before_change = ORIGIN + 300 * 24 * HOUR + HOUR;
+    let steps = future_steps(before_change, 3, 300, Some(berlin));
+    assert!(steps.windows(2).all(|w| w[1] > w[0]));
+    assert_eq!(steps.len(), 3 * 12 - 12);
+
+    config.data.step_minutes = 7;
+    assert!(load_history_from(&config, &meter).is_err());
+}
+
+#[cfg(all(feature = "http", feature = "db"))]
+#[test]
+fn five_minute_forecasts_count_energy_by_the_step() {
+    let config: Config =
+        toml::from_str("data.step_minutes = 5\nforecast.horizon_hours = 48").unwrap();
+    // Six weeks of minute readings, 20 kW by day and 5 kW by night, a little
+    // busier or quieter from day to day, and halved from day 35
+    let day = 24 * HOUR;
+    let minutes = 42 * 24 * 60;
+    let level = |d: i64| match d {
+        35.. => 0.5,
+        _ => 1.0 + 0.05 * ((d * 7919) % 5 - 2) as f64,
+    };
+    let timestamps = (0..minutes).map(|m| ORIGIN + m * 60).collect();
+    let values = (0..minutes)
+        .map(|m| {
+            let shape = if (8 * 60..20 * 60).contains(&(m % (24 * 60))) {
+                20.0
+            } else {
+                5.0
+            };
+            shape * level(m / (24 * 60))
+        })
+        .collect();
+    let meter = SeriesSource::new("meter", Series::new(timestamps, values));
+    let (history, _) = load_history_from(&config, &meter).unwrap();
+    assert_eq!(history.step(), 300);
+
+    // Days count complete on the 5-minute grid too
+    let found = breaks::detect(&history, &BreakConfig::default()).unwrap();
+    assert!(
+        (found.start - (ORIGIN + 35 * day)).abs() <= day,
+        "{:?}",
+        found
+    );
+    assert!(found.change() < -0.3, "{}", found.change());
+
+    let (_, forecast) = fit_and_forecast(&config, &history).unwrap();
+    assert_eq!((forecast.len(), forecast.step()), (48 * 12, 300));
+    let kwh = forecast.point.iter().sum::<f64>() / 12.0;
+    let summary = RunSummary::new("north", &forecast, Vec::new(), Vec::new());
+    assert_eq!(summary.horizon_hours, 48);
+    assert!((summary.horizon_energy_kwh - kwh).abs() < 1e-6);
+    let tomorrow = forecast
+        .timestamps
+        .iter()
+        .zip(&forecast.point)
+        .filter(|(ts, _)| ts.div_euclid(day) == forecast.timestamps[0].div_euclid(day) + 1)
+        .map(|(_, kw)| kw / 12.0)
+        .sum::<f64>();
+    assert!((summary.next_day_energy_kwh - tomorrow).abs() < 1e-6);
+
+    // Leads count in steps, one per point
+    let lines = db::influx_lines("forecast", "north", "v1", &forecast);
+    let horizons: Vec<&str> = lines
+        .lines()
+        .filter_map(|line| line.split(" yhat=").next()?.split("horizon=").nth(1))
+        .collect();
+    assert_eq!(horizons.first(), Some(&"1"));
+    assert_eq!(horizons.last(), Some(&"576"));
+}
@@ -0,0 +1,238 @@
+//! The SQLite project store: sites, watermarks, history, breaks, models and forecasts.
+
+#![cfg(feature = "native")]
+
+use chrono::DateTime;
+use cpo_charging_forecast::archive::ArchivedForecast;
+use cpo_charging_forecast::audit::RevisionReport;
+use cpo_charging_forecast::config::{Config, ModelConfig};
+use cpo_charging_forecast::data::Series;
+use cpo_charging_forecast::forecast::Forecast;
+use cpo_charging_forecast::site::Site;
+use cpo_charging_forecast::store::{ModelRecord, Store, Watermark};
+
+/// 2024-01-01 00:00.
+const ORIGIN: i64 = 1_704_067_200;
+
+fn store(name: &str) -> (Store, std::path::PathBuf) {
+    let path = std::env::temp_dir().join(format!("cpo-store-{}-{}.db", name, std::process::id()));
+    std::fs::remove_file(&path).ok();
+    (Store::open(path.to_str().unwrap()).unwrap(), path)
+}
+
+fn site(name: &str) -> Config {
+    Config {
+        site: name.to_string(),
+        ..Config::default()
+    }
+}
+
+fn run(site: &str, hours_later: i64) -> ArchivedForecast {
+    let issued = DateTime::from_timestamp(ORIGIN + hours_later * 3600, 0).unwrap();
+    let forecast = Forecast {
+        timestamps: vec![ORIGIN + 3600],
+        point: vec![hours_later as f64],
+        lower: vec![0.0],
+        upper: vec![1.0],
+    };
+    ArchivedForecast::new(site, issued, ORIGIN, &ModelConfig::default(), forecast)
+}
+
+#[test]
+fn sites_keep_their_configs() {
+    let (store, path) = store("sites");
+    let mut north = site("north");
+    north.forecast.horizon_hours = 48;
+    assert!(store.register(&north, ORIGIN).unwrap());
+    assert!(store.register(&site("south"), ORIGIN).unwrap());
+    north.forecast.horizon_hours = 24;
+    assert!(!store.register(&north, ORIGIN + 10).unwrap());
+
+    let sites = store.sites().unwrap();
+    assert_eq!(
+        sites.iter().map(|s| s.site.as_str()).collect::<Vec<_>>(),
+        ["north", "south"]
+    );
+    assert_eq!(sites[0].registered_at, ORIGIN);
+    assert_eq!(store.config("north").unwrap().forecast.horizon_hours, 24);
+    assert!(
+        store
+            .config("east")
+            .unwrap_err()
+            .to_string()
+            .contains("not registered")
+    );
+
+    let metadata = Site {
+        id: "south".to_string(),
+        name: Some("South".to_string()),
+        latitude: None,
+        longitude: None,
+        timezone: Some(chrono_tz::Europe::Berlin),
+        grid_limit_kw: Some(100.0),
+        chargers: Vec::new(),
+    };
+    store.save_site(&metadata).unwrap();
+    assert_eq!(store.site("south").unwrap(), Some(metadata));
+    assert_eq!(store.site("north").unwrap(), None);
+
+    assert!(store.remove("south").unwrap());
+    assert_eq!(store.site("south").unwrap(), None);
+    assert!(!store.remove("south").unwrap());
+    assert_eq!(store.configs().unwrap().len(), 1);
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn runs_are_recorded_and_pruned() {
+    let (store, path) = store("runs");
+    store.register(&site("north"), ORIGIN).unwrap();
+    let watermark = Watermark {
+        last_timestamp: ORIGIN,
+        rows_read: 120,
+        updated_at: ORIGIN + 60,
+    };
+    store.set_watermark("north", &watermark).unwrap();
+    for hours_later in 1..=4 {
+        let run = run("north", hours_later);
+        store
+            .save_model(&ModelRecord {
+                site: "north".to_string(),
+                run_id: run.run_id.clone(),
+                fitted_at: run.issued_at,
+                method: "prophet".to_string(),
+                model_version: run.model_version.clone(),
+                model: ModelConfig::default(),
+                hours: 500,
+                fit_seconds: 1.5,
+                metrics: None,
+            })
+            .unwrap();
+        store.save_forecast(&run, 2).unwrap();
+    }
+
+    assert_eq!(store.watermark("north").unwrap(), Some(watermark));
+    assert_eq!(store.watermark("south").unwrap(), None);
+    let models = store.models("north").unwrap();
+    assert_eq!(models.len(), 4);
+    assert_eq!(models[0].run_id, run("north", 4).run_id);
+    // Only the two newest forecasts are kept, oldest first
+    let forecasts = store.forecasts("north").unwrap();
+    assert_eq!(
+        forecasts
+            .iter()
+            .map(|r| r.forecast.point[0])
+            .collect::<Vec<_>>(),
+        [3.0, 4.0]
+    );
+
+    let sites = store.sites().unwrap();
+    assert_eq!(sites[0].watermark, Some(watermark));
+    assert_eq!(
+        sites[0].last_run.as_deref(),
+        Some(run("north", 4).run_id.as_str())
+    );
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn revised_hours_are_found_against_the_last_history() {
+    let (store, path) = store("history");
+    let hours = |values: &[f64]| {
+        Series::new(
+            (0..values.len() as i64)
+                .map(|h| ORIGIN + h * 3600)
+                .collect(),
+            values.to_vec(),
+        )
+    };
+    assert!(store.history("north").unwrap().is_empty());
+    store
+        .save_history("north", &hours(&[1.0, 2.0, 3.0, 4.0]))
+        .unwrap();
+    store.save_history("south", &hours(&[9.0])).unwrap();
+
+    // A corrected second hour, a rounding difference in the third, and a new fifth hour
+    let previous = store.history("north").unwrap();
+    assert_eq!(previous, hours(&[1.0, 2.0, 3.0, 4.0]));
+    let current = hours(&[1.0, 2.5, 3.001, 4.0, 5.0]);
+    let report = RevisionReport::new(&previous, &current, 0.01);
+    assert_eq!(report.checked_hours, 4);
+    assert_eq!(report.revisions.len(), 1);
+    assert_eq!(
+        (report.revisions[0].timestamp, report.revisions[0].after),
+        (ORIGIN + 3600, Some(2.5))
+    );
+    assert_eq!(report.net_change_kwh(), 0.5);
+    assert!(
+        report.summary()[0].starts_with("1 of 4 past hours revised"),
+        "{:?}",
+        report.summary()
+    );
+
+    // Saving replaces the history; a history that no longer reaches back drops hours
+    store.save_history("north", &current).unwrap();
+    assert_eq!(store.history("north").unwrap(), current);
+    let shorter = Series::new(
+        current.timestamps[2..].to_vec(),
+        current.values[2..].to_vec(),
+    );
+    let report = RevisionReport::new(&current, &shorter, 0.01);
+    assert_eq!(
+        report.revisions.iter().map(|r| r.after).collect::<Vec<_>>(),
+        [None, None]
+    );
+    assert_eq!(report.net_change_kwh(), -3.5);
+    assert_eq!(store.history("south").unwrap().values, [9.0]);
+
+    store.remove("north").unwrap();
+    assert!(store.history("north").unwrap().is_empty());
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn sites_are_stored_without_secrets() {
+    let (store, path) = store("secrets");
+    let plaintext = Config::from_toml(
+        r#"
+        site = "north"
+
+        [postgres]
+        url = "host=db user=forecast password=hunter2"
+        "#,
+        "north.toml",
+    )
+    .unwrap();
+    let error = store.register(&plaintext, ORIGIN).unwrap_err().to_string();
+    assert!(error.contains("postgres.url"), "{}", error);
+    assert!(!error.contains("hunter2"), "{}", error);
+    assert!(store.sites().unwrap().is_empty());
+
+    let reference = Config::from_toml(
+        r#"
+        site = "north"
+
+        [postgres]
+        url = "host=db user=forecast password=${env:PG_PASSWORD}"
+        "#,
+        "north.toml",
+    )
+    .unwrap();
+    assert!(store.register(&reference, ORIGIN).unwrap());
+    assert_eq!(
+        store.config("north").unwrap().postgres.unwrap().url,
+        "host=db user=forecast password=${env:PG_PASSWORD}"
+    );
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn the_last_alerted_break_is_kept_per_site() {
+    let (store, path) = store("breaks");
+    assert_eq!(store.alerted_break("north").unwrap(), None);
+    store.set_alerted_break("north", ORIGIN).unwrap();
+    store.set_alerted_break("north", ORIGIN + 86_400).unwrap();
+    assert_eq!(store.alerted_break("north").unwrap(), Some(ORIGIN + 86_400));
+    assert_eq!(store.alerted_break("south").unwrap(), None);
+    std::fs::remove_file(path).ok();
+}
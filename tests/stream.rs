@@ -0,0 +1,113 @@
+//! Meter value decoding and the rolling per-site series behind `stream`.
+
+use cpo_charging_forecast::config::{KafkaConfig, MessageFormat};
+use cpo_charging_forecast::stream::{MeterValue, SiteStreams, parse_message};
+
+/// 2024-01-01 00:00, local time.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+
+fn reading(site: &str, connector: Option<&str>, timestamp: i64, power_kw: f64) -> MeterValue {
+    MeterValue {
+        site: site.to_string(),
+        connector: connector.map(str::to_string),
+        timestamp,
+        power_kw,
+    }
+}
+
+#[test]
+fn json_and_csv_messages_decode_alike() {
+    let json = KafkaConfig::default();
+    let csv = KafkaConfig {
+        format: MessageFormat::Csv,
+        ..KafkaConfig::default()
+    };
+    let from_json = parse_message(
+        &json,
+        None,
+        br#"{"site": "north", "timestamp": "2024-01-01 10:15", "power_kw": 42.5}"#,
+    );
+    let from_csv = parse_message(&csv, None, b"north,2024-01-01 10:15,42.5");
+    let expected = reading("north", None, ORIGIN + 10 * HOUR + 15 * 60, 42.5);
+    assert_eq!(from_json.unwrap(), expected);
+    assert_eq!(from_csv.unwrap(), expected);
+}
+
+#[test]
+fn instants_are_shown_in_the_site_timezone() {
+    let config = KafkaConfig {
+        connector_field: Some("evse".to_string()),
+        ..KafkaConfig::default()
+    };
+    let tz = Some(chrono_tz::Europe::Berlin);
+    // 09:00 UTC is 10:00 in Berlin in winter
+    let unix = parse_message(
+        &config,
+        tz,
+        br#"{"site": 7, "evse": 2, "timestamp": 1704099600, "power_kw": "11"}"#,
+    );
+    let rfc3339 = parse_message(
+        &config,
+        tz,
+        br#"{"site": 7, "evse": 2, "timestamp": "2024-01-01T09:00:00Z", "power_kw": 11}"#,
+    );
+    let expected = reading("7", Some("2"), ORIGIN + 10 * HOUR, 11.0);
+    assert_eq!(unix.unwrap(), expected);
+    assert_eq!(rfc3339.unwrap(), expected);
+}
+
+#[test]
+fn bad_messages_are_errors() {
+    let config = KafkaConfig::default();
+    for payload in [
+        &b"not json"[..],
+        br#"{"site": "a", "timestamp": "2024-01-01 10:00"}"#,
+        br#"{"site": "a", "timestamp": "yesterday", "power_kw": 1}"#,
+    ] {
+        assert!(parse_message(&config, None, payload).is_err());
+    }
+}
+
+#[test]
+fn connectors_are_averaged_then_summed() {
+    let mut streams = SiteStreams::new(&KafkaConfig::default());
+    streams.push(reading("a", Some("1"), ORIGIN, 10.0));
+    streams.push(reading("a", Some("1"), ORIGIN + 1800, 20.0));
+    streams.push(reading("a", Some("2"), ORIGIN + 600, 7.0));
+    // Nothing at 01:00; 02:00 is still open
+    streams.push(reading("a", Some("1"), ORIGIN + 2 * HOUR, 50.0));
+
+    let series = streams.series("a");
+    assert_eq!(series.timestamps, [ORIGIN, ORIGIN + HOUR]);
+    assert_eq!(series.values, [22.0, 0.0]);
+}
+
+#[test]
+fn sites_are_due_after_enough_new_hours() {
+    let config = KafkaConfig {
+        refit_after_hours: 3,
+        window_hours: 4,
+        ..KafkaConfig::default()
+    };
+    let mut streams = SiteStreams::new(&config);
+    for hour in 0..3 {
+        streams.push(reading("a", None, ORIGIN + hour * HOUR, 1.0));
+        streams.push(reading("b", None, ORIGIN + hour * HOUR, 1.0));
+    }
+    assert!(streams.ready().is_empty());
+    streams.push(reading("a", None, ORIGIN + 3 * HOUR, 1.0));
+    assert_eq!(streams.ready(), ["a"]);
+
+    streams.mark_forecast("a");
+    assert!(streams.ready().is_empty());
+
+    // The window keeps the last four hours, the open one included
+    streams.push(reading("a", None, ORIGIN + 9 * HOUR, 1.0));
+    streams.push(reading("a", None, ORIGIN, 100.0));
+    assert_eq!(streams.ready(), ["a"]);
+    assert_eq!(
+        streams.series("a").timestamps,
+        [ORIGIN + 6 * HOUR, ORIGIN + 7 * HOUR, ORIGIN + 8 * HOUR]
+    );
+}
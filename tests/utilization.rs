@@ -0,0 +1,89 @@
+//! Utilization KPIs of a forecast against the installed charging power.
+
+use cpo_charging_forecast::config::UtilizationConfig;
+use cpo_charging_forecast::data::Series;
+use cpo_charging_forecast::forecast::Forecast;
+use cpo_charging_forecast::utilization::{UtilizationReport, saturation};
+
+/// 2024-01-01 00:00.
+const ORIGIN: i64 = 1_704_067_200;
+
+/// `days` of history whose daily peak (at noon) starts at 50 kW and grows by `growth` kW a day.
+fn history(days: i64, growth: f64) -> Series {
+    let timestamps: Vec<i64> = (0..days * 24).map(|h| ORIGIN + h * 3600).collect();
+    let values = (0..days * 24)
+        .map(|h| {
+            if h % 24 == 12 {
+                50.0 + growth * (h / 24) as f64
+            } else {
+                10.0
+            }
+        })
+        .collect();
+    Series::new(timestamps, values)
+}
+
+#[test]
+fn forecast_hours_near_capacity_are_counted() {
+    let forecast = Forecast {
+        timestamps: (0..4).map(|h| ORIGIN + h * 3600).collect(),
+        point: vec![20.0, 70.0, 90.0, 40.0],
+        lower: vec![0.0; 4],
+        upper: vec![40.0, 90.0, 110.0, 85.0],
+    };
+    let report = UtilizationReport::new(
+        &history(7, 0.0),
+        &forecast,
+        100.0,
+        &UtilizationConfig::default(),
+    );
+    assert_eq!(report.mean_utilization_pct, 55.0);
+    assert_eq!(report.peak_utilization_pct, 90.0);
+    assert_eq!(report.peak_at.as_deref(), Some("2024-01-01 02:00"));
+    assert_eq!(
+        (
+            report.hours_above_threshold,
+            report.hours_above_threshold_upper
+        ),
+        (1.0, 3.0)
+    );
+    // A week is too short a history for a trend
+    assert!(report.saturation.is_none());
+    assert_eq!(report.summary().len(), 2);
+}
+
+#[test]
+fn growing_peaks_reach_saturation() {
+    let config = UtilizationConfig {
+        trend_days: 30,
+        ..UtilizationConfig::default()
+    };
+    // Day 59 peaks at 109 kW; 160 kW (80% of 200) is 51 days later, 200 kW 91 days later
+    let growing = saturation(&history(60, 1.0), 200.0, &config).unwrap();
+    assert_eq!(growing.days, 30);
+    assert!((growing.growth_kw_per_day - 1.0).abs() < 1e-9);
+    assert!((growing.trend_peak_kw - 109.0).abs() < 1e-9);
+    assert!((growing.days_to_threshold.unwrap() - 51.0).abs() < 1e-6);
+    assert!((growing.days_to_saturation.unwrap() - 91.0).abs() < 1e-6);
+
+    let flat = saturation(&history(60, 0.0), 200.0, &config).unwrap();
+    assert_eq!(
+        (flat.days_to_threshold, flat.days_to_saturation),
+        (None, None)
+    );
+    // Already beyond it
+    let full = saturation(&history(60, 1.0), 100.0, &config).unwrap();
+    assert_eq!(
+        (full.days_to_threshold, full.days_to_saturation),
+        (Some(0.0), Some(0.0))
+    );
+
+    // The last, unfinished day is left out
+    let full = history(60, 1.0);
+    let mut timestamps = full.timestamps.to_vec();
+    let mut values = full.values.to_vec();
+    timestamps.push(ORIGIN + 60 * 24 * 3600);
+    values.push(0.0);
+    let partial = Series::new(timestamps, values);
+    assert_eq!(saturation(&partial, 200.0, &config), Some(growing));
+}
@@ -0,0 +1,165 @@
+//! Vehicle-to-grid export: sessions with negative energy, forecast as
+//! separate import and export channels or as a signed net load.
+
+#![cfg(feature = "wasmstan")]
+
+use cpo_charging_forecast::config::{Config, Export, Transform};
+use cpo_charging_forecast::data::{Series, load_sessions_counted, load_sessions_signed};
+use cpo_charging_forecast::forecast::Forecast;
+use cpo_charging_forecast::pipeline::{
+    Channels, fit_and_forecast, forecast_channels, load_history, split_channels,
+};
+
+/// 2024-01-01 00:00.
+const ORIGIN: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+
+/// Three weeks of charging by day and export in the evening peak, in kW.
+fn signed_history() -> Series {
+    let timestamps: Vec<i64> = (0..21 * 24).map(|h| ORIGIN + h * HOUR).collect();
+    let values = (0..21 * 24)
+        .map(|h| match h % 24 {
+            9..=15 => 30.0 + (h * 7919 % 11) as f64,
+            18..=20 => -12.0 - (h * 7919 % 5) as f64,
+            _ => 2.0,
+        })
+        .collect();
+    Series::new(timestamps, values)
+}
+
+fn config(export: Export) -> Config {
+    let mut config = Config::default();
+    config.data.export = export;
+    config.forecast.horizon_hours = 24;
+    config
+}
+
+#[test]
+fn negative_sessions_are_kept_only_when_signed() {
+    let path = std::env::temp_dir().join(format!("cpo-export-{}.csv", std::process::id()));
+    let header = "Created,Start time,Count.Stop time,Count.Duration,Max power(kW),Index,Day of Week,Modified Count.Energy (Wh)";
+    let rows = [
+        "2024-01-01 13:00,2024-01-01 13:00,2024-01-01 14:00,60,22,1,1,11000",
+        "2024-01-01 18:00,2024-01-01 18:00,2024-01-01 19:00,60,11,2,1,-7000",
+        "2024-01-01 20:00,2024-01-01 20:00,2024-01-01 21:00,60,11,3,1,0",
+    ];
+    std::fs::write(&path, format!("{}\n{}\n", header, rows.join("\n"))).unwrap();
+
+    let (dropped, counts) = load_sessions_counted(&path).unwrap();
+    assert_eq!(dropped.len(), 1);
+    assert_eq!(counts.non_positive, 2);
+    let (signed, counts) = load_sessions_signed(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+    assert_eq!(
+        signed.iter().map(|s| s.energy_wh).collect::<Vec<_>>(),
+        vec![11_000.0, -7_000.0]
+    );
+    assert_eq!(counts.non_positive, 1);
+}
+
+#[test]
+fn sessions_sharing_an_hour_keep_import_and_export() {
+    let path = std::env::temp_dir().join(format!("cpo-channels-{}.csv", std::process::id()));
+    let header = "Created,Start time,Count.Stop time,Count.Duration,Max power(kW),Index,Day of Week,Modified Count.Energy (Wh)";
+    let rows = [
+        "2024-01-01 18:00,2024-01-01 18:00,2024-01-01 19:00,60,22,1,1,10000",
+        "2024-01-01 18:00,2024-01-01 18:00,2024-01-01 19:00,60,11,2,1,-4000",
+        "2024-01-01 19:00,2024-01-01 19:00,2024-01-01 20:00,60,11,3,1,-2000",
+    ];
+    std::fs::write(&path, format!("{}\n{}\n", header, rows.join("\n"))).unwrap();
+    let mut config = config(Export::Split);
+    config.data.path = path.to_str().unwrap().to_string();
+    let history = load_history(&config).unwrap();
+    std::fs::remove_file(&path).ok();
+
+    // The net is what's reported, but 10 kW went in and 4 kW out at 18:00
+    assert_eq!(history.values, vec![6.0, -2.0, 0.0]);
+    let (import, export) = split_channels(&history);
+    assert_eq!(import.values, vec![10.0, 0.0, 0.0]);
+    assert_eq!(export.values, vec![4.0, 2.0, 0.0]);
+}
+
+#[test]
+fn split_channels_forecast_import_and_export_apart() {
+    let history = Series::new(
+        vec![ORIGIN, ORIGIN + HOUR, ORIGIN + 2 * HOUR],
+        vec![5.0, -3.0, 0.0],
+    );
+    let (import, export) = split_channels(&history);
+    assert_eq!(import.values, vec![5.0, 0.0, 0.0]);
+    assert_eq!(export.values, vec![0.0, 3.0, 0.0]);
+
+    let forecast = |point: f64, spread: f64| Forecast {
+        timestamps: vec![ORIGIN],
+        point: vec![point],
+        lower: vec![point - spread],
+        upper: vec![point + spread],
+    };
+    let channels = Channels {
+        import: forecast(10.0, 2.0),
+        export: forecast(4.0, 1.0),
+    };
+    assert_eq!(channels.net(), forecast(6.0, 3.0));
+
+    let history = signed_history();
+    let (_, channels) = forecast_channels(&config(Export::Split), &history).unwrap();
+    let evening = |f: &Forecast| {
+        f.timestamps
+            .iter()
+            .zip(&f.point)
+            .filter(|(ts, _)| (18..=20).contains(&(*ts / HOUR % 24)))
+            .map(|(_, p)| *p)
+            .sum::<f64>()
+            / 3.0
+    };
+    assert!(
+        evening(&channels.export) > 8.0,
+        "{}",
+        evening(&channels.export)
+    );
+    assert!(
+        channels
+            .import
+            .point
+            .iter()
+            .chain(&channels.export.point)
+            .all(|p| *p >= 0.0)
+    );
+
+    // What's written is the difference (the fits vary a little from run to run)
+    let (_, net) = fit_and_forecast(&config(Export::Split), &history).unwrap();
+    let expected = channels.net();
+    assert_eq!(net.timestamps, expected.timestamps);
+    assert!(
+        net.point
+            .iter()
+            .zip(&expected.point)
+            .all(|(a, b)| (a - b).abs() < 1.0),
+        "{:?}",
+        net.point
+    );
+    assert!((18..=20).any(|h| net.point[h] < -5.0), "{:?}", net.point);
+}
+
+#[test]
+fn net_forecasts_go_below_zero() {
+    let history = signed_history();
+    let (_, forecast) = fit_and_forecast(&config(Export::Net), &history).unwrap();
+    assert_eq!(forecast.len(), 24);
+    assert!(
+        forecast.point.iter().any(|p| *p < -5.0),
+        "{:?}",
+        forecast.point
+    );
+    assert!(
+        forecast.point.iter().any(|p| *p > 20.0),
+        "{:?}",
+        forecast.point
+    );
+
+    // A transform that can't take negative demand needs the split
+    let mut log = config(Export::Net);
+    log.model.transform = Transform::Log1p;
+    let error = fit_and_forecast(&log, &history).err().unwrap();
+    assert!(error.to_string().contains("model.transform"), "{}", error);
+}
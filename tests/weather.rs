@@ -0,0 +1,89 @@
+//! Weather-normalized actuals: the temperature effect estimated and removed.
+
+use std::collections::BTreeMap;
+
+use cpo_charging_forecast::config::WeatherConfig;
+use cpo_charging_forecast::data::Series;
+use cpo_charging_forecast::weather::{self, WeatherModel};
+
+/// 2024-01-01 00:00, a Monday.
+const MONDAY: i64 = 1_704_067_200;
+const HOUR: i64 = 3600;
+const DAY: i64 = 24 * HOUR;
+
+/// 8 weeks at 5–15 °C with a cold snap in week 3, and a site using 20 kWh
+/// more on Saturdays and 4 kWh more per heating degree day.
+fn site() -> (Series, BTreeMap<i64, f64>) {
+    let temperature = |day: i64| {
+        if (14..21).contains(&day) {
+            -5.0
+        } else {
+            10.0 + 5.0 * ((day % 5) as f64 / 2.0 - 1.0)
+        }
+    };
+    let temperatures: BTreeMap<i64, f64> = (0..56)
+        .map(|day| (MONDAY + day * DAY, temperature(day)))
+        .collect();
+    let mut values = Vec::new();
+    for day in 0..56 {
+        let kwh = 240.0
+            + if day % 7 == 5 { 20.0 } else { 0.0 }
+            + 4.0 * (15.0 - temperature(day)).max(0.0);
+        values.extend((0..24).map(|h| kwh * if (8..20).contains(&h) { 1.5 } else { 0.5 } / 24.0));
+    }
+    (
+        Series::new((0..56 * 24).map(|h| MONDAY + h * HOUR).collect(), values),
+        temperatures,
+    )
+}
+
+#[test]
+fn the_temperature_effect_is_estimated_and_removed() {
+    let (history, temperatures) = site();
+    let config = WeatherConfig::default();
+    let model = WeatherModel::fit(&history, &temperatures, &config).unwrap();
+    assert!((model.heating_kwh - 4.0).abs() < 1e-6, "{:?}", model);
+    assert_eq!(model.cooling_kwh, 0.0);
+    assert!((model.weekday_kwh[5] - model.weekday_kwh[0] - 20.0).abs() < 1e-6);
+
+    let normalization = weather::normalize(&history, &temperatures, &config).unwrap();
+    // Every January day, cold snap or not, uses what a usual January day does
+    let day = |series: &Series, d: usize| series.values[d * 24..(d + 1) * 24].iter().sum::<f64>();
+    let usual = day(&normalization.normalized, 0);
+    assert!((day(&normalization.normalized, 14) - usual).abs() < 1e-6);
+    assert!(day(&normalization.actual, 14) > usual + 30.0);
+    // Hours keep their shape
+    let normalized = &normalization.normalized.values;
+    assert!((normalized[14 * 24 + 12] / normalized[14 * 24] - 3.0).abs() < 1e-9);
+
+    assert_eq!(
+        normalization
+            .months
+            .iter()
+            .map(|m| m.month.as_str())
+            .collect::<Vec<_>>(),
+        ["2024-01", "2024-02"]
+    );
+    let january = &normalization.months[0];
+    assert!(january.actual_kwh > january.normalized_kwh);
+    assert!(january.heating_degree_days > 0.0);
+}
+
+#[test]
+fn normalization_needs_enough_days_with_a_temperature() {
+    let (history, temperatures) = site();
+    let few: BTreeMap<i64, f64> = temperatures.into_iter().take(20).collect();
+    let error = weather::normalize(&history, &few, &WeatherConfig::default())
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("Only 20 complete days"), "{}", error);
+
+    let path = std::env::temp_dir().join(format!("cpo-weather-{}.csv", std::process::id()));
+    std::fs::write(&path, "timestamp,temperature\n2024-01-01 00:00,4\n2024-01-01 12:00,8\n2024-01-02 00:00,\n2024-01-02T01:00:00Z,1.5\n").unwrap();
+    let daily = weather::load_temperatures(path.to_str().unwrap(), None).unwrap();
+    std::fs::remove_file(&path).ok();
+    assert_eq!(
+        daily.into_iter().collect::<Vec<_>>(),
+        [(MONDAY, 6.0), (MONDAY + DAY, 1.5)]
+    );
+}
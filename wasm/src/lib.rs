@@ -0,0 +1,264 @@
+//! Browser bindings: forecast uploaded session exports client-side.
+//!
+//! Prophet's Stan optimizer can't run inside this module, so the page passes
+//! one in with `setOptimizer`, e.g. `optimizer` from
+//! `@bsull/augurs-prophet-wasmstan`. Series are `{ timestamps, values }`
+//! objects (UNIX seconds of local time); model configs take the same keys as
+//! the `[model]` TOML section.
+
+use std::str::FromStr;
+
+use augurs_prophet::PositiveFloat;
+use augurs_prophet::optimizer::{
+    self, Data, InitialParams, OptimizeOpts, OptimizedParams, Optimizer,
+};
+use charging::config::ModelConfig;
+use charging::data::{Series, read_sessions};
+use charging::forecast::{self, Forecast};
+use charging::metrics;
+use charging::preprocess::{self, Pipeline};
+use chrono_tz::Tz;
+use js_sys::{Function, Reflect};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+fn to_js(value: &impl Serialize) -> Result<JsValue, JsError> {
+    Ok(value.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
+
+fn js_error(e: Box<dyn std::error::Error>) -> JsError {
+    JsError::new(&e.to_string())
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsSeries {
+    timestamps: Vec<i64>,
+    values: Vec<f64>,
+}
+
+impl From<Series> for JsSeries {
+    fn from(series: Series) -> Self {
+        Self {
+            timestamps: series.timestamps.into(),
+            values: series.values.into(),
+        }
+    }
+}
+
+fn series(value: JsValue) -> Result<Series, JsError> {
+    let s: JsSeries = serde_wasm_bindgen::from_value(value)?;
+    Ok(Series::new(s.timestamps, s.values))
+}
+
+fn model_config(model: JsValue) -> Result<ModelConfig, JsError> {
+    if model.is_undefined() || model.is_null() {
+        return Ok(ModelConfig::default());
+    }
+    Ok(serde_wasm_bindgen::from_value(model)?)
+}
+
+/// Initial parameters in the shape the JS optimizer expects.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsInits<'a> {
+    k: f64,
+    m: f64,
+    sigma_obs: f64,
+    delta: &'a [f64],
+    beta: &'a [f64],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsOptimizeOpts {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    algorithm: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chain: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    init_alpha: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tol_obj: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tol_rel_obj: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tol_grad: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tol_rel_grad: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tol_param: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    history_size: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iter: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    jacobian: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refresh: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsOptimizedParams {
+    k: f64,
+    m: f64,
+    sigma_obs: f64,
+    delta: Vec<f64>,
+    beta: Vec<f64>,
+    trend: Vec<f64>,
+}
+
+#[derive(Deserialize)]
+struct JsOptimizeOutput {
+    params: JsOptimizedParams,
+}
+
+/// A JS object with an `optimize(init, dataJson, opts)` method returning `{ params }`.
+#[derive(Debug, Clone)]
+struct JsOptimizer {
+    this: JsValue,
+    optimize: Function,
+}
+
+impl Optimizer for JsOptimizer {
+    fn optimize(
+        &self,
+        init: &InitialParams,
+        data: &Data,
+        opts: &OptimizeOpts,
+    ) -> Result<OptimizedParams, optimizer::Error> {
+        let failed = |what: &str, e: String| {
+            optimizer::Error::string(format!("JS optimizer {}: {}", what, e))
+        };
+        let init = to_js(&JsInits {
+            k: init.k,
+            m: init.m,
+            sigma_obs: *init.sigma_obs,
+            delta: &init.delta,
+            beta: &init.beta,
+        })
+        .map_err(|e| failed("input", format!("{:?}", JsValue::from(e))))?;
+        let data = serde_json::to_string(data).map_err(optimizer::Error::custom)?;
+        let opts = to_js(&JsOptimizeOpts {
+            algorithm: opts.algorithm.map(|a| a.to_string()),
+            seed: opts.seed,
+            chain: opts.chain,
+            init_alpha: opts.init_alpha,
+            tol_obj: opts.tol_obj,
+            tol_rel_obj: opts.tol_rel_obj,
+            tol_grad: opts.tol_grad,
+            tol_rel_grad: opts.tol_rel_grad,
+            tol_param: opts.tol_param,
+            history_size: opts.history_size,
+            iter: opts.iter,
+            jacobian: opts.jacobian,
+            refresh: opts.refresh,
+        })
+        .map_err(|e| failed("input", format!("{:?}", JsValue::from(e))))?;
+
+        let result = self
+            .optimize
+            .call3(&self.this, &init, &JsValue::from_str(&data), &opts)
+            .map_err(|e| failed("failed", format!("{:?}", e)))?;
+        let output: JsOptimizeOutput =
+            serde_wasm_bindgen::from_value(result).map_err(|e| failed("output", e.to_string()))?;
+        let params = output.params;
+        Ok(OptimizedParams {
+            k: params.k,
+            m: params.m,
+            sigma_obs: PositiveFloat::try_new(params.sigma_obs)
+                .map_err(optimizer::Error::custom)?,
+            delta: params.delta,
+            beta: params.beta,
+            trend: params.trend,
+        })
+    }
+}
+
+/// Use `optimizer` (an object with an `optimize` method) for every Prophet fit.
+#[wasm_bindgen(js_name = setOptimizer)]
+pub fn set_optimizer(optimizer: JsValue) -> Result<(), JsError> {
+    let optimize: Function = Reflect::get(&optimizer, &JsValue::from_str("optimize"))
+        .ok()
+        .and_then(|f| f.dyn_into().ok())
+        .ok_or_else(|| JsError::new("optimizer has no optimize() method"))?;
+    let js = JsOptimizer {
+        this: optimizer,
+        optimize,
+    };
+    forecast::set_optimizer(move || Box::new(js.clone()));
+    Ok(())
+}
+
+/// Hourly load (kW) of a session export CSV's contents.
+#[wasm_bindgen(js_name = hourlyLoad)]
+pub fn hourly_load(csv: &str) -> Result<JsValue, JsError> {
+    let sessions = read_sessions(csv.as_bytes()).map_err(js_error)?;
+    to_js(&JsSeries::from(preprocess::hourly_load(&sessions)))
+}
+
+/// Apply preprocessing steps (the `data.preprocess` list) to a series.
+#[wasm_bindgen]
+pub fn preprocess(history: JsValue, steps: JsValue) -> Result<JsValue, JsError> {
+    let pipeline: Pipeline = serde_wasm_bindgen::from_value(steps)?;
+    to_js(&JsSeries::from(
+        pipeline.apply(series(history)?).map_err(js_error)?,
+    ))
+}
+
+/// The next `horizonHours` hourly timestamps, DST-aware if an IANA `timezone` is given.
+#[wasm_bindgen(js_name = futureTimestamps)]
+pub fn future_timestamps(
+    last_timestamp: f64,
+    horizon_hours: u32,
+    timezone: Option<String>,
+) -> Result<Vec<f64>, JsError> {
+    let timezone = timezone
+        .as_deref()
+        .map(Tz::from_str)
+        .transpose()
+        .map_err(|e| JsError::new(&e.to_string()))?;
+    let timestamps = forecast::future_timestamps(last_timestamp as i64, horizon_hours, timezone);
+    Ok(timestamps.into_iter().map(|ts| ts as f64).collect())
+}
+
+/// A fitted model.
+#[wasm_bindgen]
+pub struct Forecaster {
+    inner: Box<dyn forecast::Forecaster>,
+}
+
+#[wasm_bindgen]
+impl Forecaster {
+    /// Short method name, e.g. `"prophet"`.
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.inner.name().to_string()
+    }
+
+    /// `{ timestamps, point, lower, upper }` at the given timestamps.
+    pub fn predict(&self, timestamps: Vec<f64>) -> Result<JsValue, JsError> {
+        let timestamps = timestamps.into_iter().map(|ts| ts as i64).collect();
+        to_js(&self.inner.predict(timestamps).map_err(js_error)?)
+    }
+}
+
+/// Fit the forecaster on an hourly series, with an optional model config.
+#[wasm_bindgen]
+pub fn fit(history: JsValue, model: JsValue) -> Result<Forecaster, JsError> {
+    let inner = forecast::fit(&series(history)?, &model_config(model)?).map_err(js_error)?;
+    Ok(Forecaster { inner })
+}
+
+/// Accuracy metrics of a forecast against the actual values.
+#[wasm_bindgen]
+pub fn evaluate(
+    actual: Vec<f64>,
+    forecast: JsValue,
+    interval_width: f64,
+) -> Result<JsValue, JsError> {
+    let forecast: Forecast = serde_wasm_bindgen::from_value(forecast)?;
+    to_js(&metrics::evaluate(&actual, &forecast, interval_width))
+}